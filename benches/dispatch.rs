@@ -0,0 +1,49 @@
+//! How fast instructions are dispatched, running random programs (see
+//! [fill_random_program]) rather than real ROMs
+//!
+//! Run with `cargo bench --bench dispatch`.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use emul8rs::config::EmulatorConfig;
+use emul8rs::emulator::Emulator;
+use emul8rs::headless_frontend::HeadlessFrontend;
+use emul8rs::random_program::fill_random_program;
+use emul8rs::rom::{MAX_CHIP8_ROM_SIZE, PROGRAM_START};
+
+/// Seeds of the programs run, each one measured separately
+const SEEDS: [u64; 4] = [1, 2, 3, 4];
+/// How long to run each program for
+const RUN_TIME: Duration = Duration::from_secs(2);
+
+/// Run the program from `seed` from the start over and over for [RUN_TIME],
+/// returning the instructions executed per second
+fn measure(seed: u64) -> Result<f64> {
+    let config = EmulatorConfig {
+        synchronous_timers: true,
+        ..EmulatorConfig::default()
+    };
+    let mut emulator = Emulator::new(Box::new(HeadlessFrontend::new()), config)?;
+    let end = fill_random_program(&mut emulator, seed, MAX_CHIP8_ROM_SIZE / 2)?;
+    let start = Instant::now();
+    while start.elapsed() < RUN_TIME {
+        emulator.set_program_counter(PROGRAM_START)?;
+        while emulator.program_counter() < end {
+            emulator.step()?;
+        }
+    }
+    Ok(emulator.cycles() as f64 / start.elapsed().as_secs_f64())
+}
+
+fn main() -> Result<()> {
+    for seed in SEEDS {
+        let per_second = measure(seed)?;
+        println!(
+            "seed {seed}: {:.1} million instructions/s",
+            per_second / 1_000_000.0
+        );
+    }
+    Ok(())
+}
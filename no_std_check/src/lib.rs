@@ -0,0 +1,76 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+
+use anyhow::Result;
+use emul8rs::config::EmulatorConfig;
+use emul8rs::display::{Display, HIGH_RES_COLS, HIGH_RES_ROWS};
+use emul8rs::emulator::Emulator;
+use emul8rs::frontend::Frontend;
+
+/// A monochrome panel (e.g. an OLED), one bit per pixel, with the keypad read
+/// from GPIO pins
+#[derive(Debug, Clone, Copy)]
+pub struct Panel {
+    /// The pixels, one row per entry with column 0 in the top bit, big enough
+    /// for high resolution (only the top left 64x32 is used in low resolution)
+    pub rows: [u128; HIGH_RES_ROWS],
+    /// Keypad keys held down (bit N for key N)
+    pub keys: u16,
+}
+
+impl Frontend for Panel {
+    fn draw(&mut self, display: &Display) -> Result<()> {
+        self.rows = [0; HIGH_RES_ROWS];
+        for (row, bits) in self.rows.iter_mut().enumerate().take(display.rows()) {
+            for col in 0..display.cols() {
+                if display.get(row, col)? {
+                    *bits |= 1 << (HIGH_RES_COLS - 1 - col);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_key(&mut self, key: u8) -> Result<bool> {
+        Ok(self.keys & (1 << key) != 0)
+    }
+
+    fn play_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn should_stop(&mut self) -> bool {
+        false
+    }
+
+    fn step(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Run `rom` for `frames` 60Hz frames with the `keys` held, returning the
+/// panel showing the last frame
+///
+/// Just enough to drive an [Emulator] the way firmware would (calling
+/// [Emulator::run_frame] from a 60Hz interrupt, with the timers ticked each
+/// frame), so that building this checks the emulator core doesn't need std.
+pub fn run_frames(rom: &[u8], frames: usize, keys: u16) -> Result<Panel> {
+    let mut panel = Panel {
+        rows: [0; HIGH_RES_ROWS],
+        keys,
+    };
+    let mut emulator = Emulator::new(Box::new(panel), EmulatorConfig::default())?;
+    emulator.load_rom(rom)?;
+    for _ in 0..frames {
+        emulator.run_frame()?;
+    }
+    panel.draw(emulator.presented_display())?;
+    Ok(panel)
+}
@@ -0,0 +1,225 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use anyhow::{Context, Result, bail};
+
+use crate::memory_layout::{parse_address, parse_number};
+
+/// How a [Cheat] writes its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    /// Write the value after every frame, so the program can't change it
+    Freeze,
+    /// Write the value once, after the ROM is loaded (and again on a reset)
+    WriteOnce,
+}
+
+impl CheatKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CheatKind::Freeze => "freeze",
+            CheatKind::WriteOnce => "write-once",
+        }
+    }
+}
+
+/// A value written into memory to change how a game plays, e.g. to keep the
+/// number of lives from going down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    /// Name of the cheat, from the config
+    pub name: String,
+    pub kind: CheatKind,
+    pub address: usize,
+    pub value: u8,
+}
+
+impl Cheat {
+    /// Parse the cheat `name` from `spec`, `freeze ADDRESS = VALUE` or
+    /// `write-once ADDRESS = VALUE` (in hex or decimal)
+    pub fn parse(name: &str, spec: &str) -> Result<Self> {
+        let (kind, assignment) = spec
+            .trim()
+            .split_once(char::is_whitespace)
+            .context("Expected e.g. \"freeze 0x3A2 = 0x05\"")?;
+        let kind = match kind {
+            "freeze" => CheatKind::Freeze,
+            "write-once" => CheatKind::WriteOnce,
+            _ => bail!("Unknown kind of cheat {kind:?}, expected freeze or write-once"),
+        };
+        let (address, value) = assignment
+            .split_once('=')
+            .context("Expected ADDRESS = VALUE after the kind of cheat")?;
+        let value = value.trim();
+        let value = parse_number(value)
+            .ok()
+            .and_then(|value| u8::try_from(value).ok())
+            .with_context(|| format!("Invalid value {value:?}, expected a byte (0 to 0xFF)"))?;
+        Ok(Self {
+            name: name.to_string(),
+            kind,
+            address: parse_address(address.trim())?,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} {:#05x} = {:#04x}",
+            self.name,
+            self.kind.name(),
+            self.address,
+            self.value
+        )
+    }
+}
+
+/// Parse the `[cheats]` table of the config (cheat name -> cheat)
+pub fn parse_cheats(table: &BTreeMap<String, String>) -> Result<Vec<Cheat>> {
+    table
+        .iter()
+        .map(|(name, spec)| {
+            Cheat::parse(name, spec).with_context(|| format!("Invalid cheat {name:?} ({spec:?})"))
+        })
+        .collect()
+}
+
+/// The enabled cheats, and which of them have been written
+#[derive(Debug, Clone, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+    /// Whether each cheat has been written since the last reset, write-once
+    /// cheats aren't written again until then
+    written: Vec<bool>,
+}
+
+impl Cheats {
+    pub fn new(cheats: Vec<Cheat>) -> Self {
+        let written = vec![false; cheats.len()];
+        Self { cheats, written }
+    }
+
+    /// Enable `cheat`, written the next time the cheats are applied
+    pub fn add(&mut self, cheat: Cheat) {
+        self.cheats.push(cheat);
+        self.written.push(false);
+    }
+
+    /// The enabled cheats
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    /// Write the cheats into `memory`, every freeze and the write-once cheats
+    /// not written since the last reset
+    pub fn apply(&mut self, memory: &mut [u8]) {
+        for (cheat, written) in self.cheats.iter().zip(&mut self.written) {
+            if cheat.kind == CheatKind::WriteOnce && *written {
+                continue;
+            }
+            // Addresses are checked to be in memory when parsed
+            if let Some(byte) = memory.get_mut(cheat.address) {
+                *byte = cheat.value;
+            }
+            *written = true;
+        }
+    }
+
+    /// Write the write-once cheats again the next time the cheats are applied,
+    /// e.g. after the ROM is reloaded
+    pub fn reset(&mut self) {
+        self.written.fill(false);
+    }
+
+    /// Names of the enabled cheats, for listing in the debug overlay
+    pub fn summary(&self) -> Option<String> {
+        if self.cheats.is_empty() {
+            return None;
+        }
+        let names: Vec<&str> = self
+            .cheats
+            .iter()
+            .map(|cheat| cheat.name.as_str())
+            .collect();
+        Some(format!("Cheats: {}", names.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod test_cheats {
+    use super::*;
+
+    #[test]
+    /// Test parsing valid and malformed cheats
+    fn test_parse() -> Result<()> {
+        let cheat = Cheat::parse("lives", "freeze 0x3A2 = 0x05")?;
+        assert_eq!(
+            cheat,
+            Cheat {
+                name: "lives".to_string(),
+                kind: CheatKind::Freeze,
+                address: 0x3A2,
+                value: 0x05
+            }
+        );
+        assert_eq!(cheat.to_string(), "lives: freeze 0x3a2 = 0x05");
+        let cheat = Cheat::parse("skip intro", "  write-once 528=0")?;
+        assert_eq!(
+            (cheat.kind, cheat.address, cheat.value),
+            (CheatKind::WriteOnce, 0x210, 0)
+        );
+
+        for invalid in [
+            "",
+            "freeze",
+            "freeze 0x3A2",
+            "freeze 0x3A2 =",
+            "freeze = 5",
+            "melt 0x3A2 = 5",
+            "freeze 0x1000 = 5",
+            "freeze 0x3A2 = 0x100",
+            "freeze 0x3A2 = five",
+        ] {
+            assert!(Cheat::parse("lives", invalid).is_err(), "{invalid:?}");
+        }
+        let table = BTreeMap::from([("lives".to_string(), "melt 0x3A2 = 5".to_string())]);
+        let err = parse_cheats(&table).unwrap_err();
+        assert!(format!("{err:#}").contains("\"lives\""), "{err:#}");
+        Ok(())
+    }
+
+    #[test]
+    /// Test freezes are written every time, and write-once cheats once per reset
+    fn test_apply() -> Result<()> {
+        let mut table = BTreeMap::new();
+        table.insert("lives".to_string(), "freeze 0x10 = 3".to_string());
+        table.insert("level".to_string(), "write-once 0x11 = 9".to_string());
+        let mut cheats = Cheats::new(parse_cheats(&table)?);
+        let mut memory = [0u8; 32];
+        cheats.apply(&mut memory);
+        assert_eq!(memory[0x10..0x12], [3, 9]);
+
+        memory[0x10] = 2;
+        memory[0x11] = 1;
+        cheats.apply(&mut memory);
+        assert_eq!(memory[0x10..0x12], [3, 1]);
+        cheats.reset();
+        cheats.apply(&mut memory);
+        assert_eq!(memory[0x10..0x12], [3, 9]);
+
+        assert_eq!(cheats.summary().as_deref(), Some("Cheats: level, lives"));
+        assert_eq!(Cheats::default().summary(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,636 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+
+use crate::config::EmulatorConfig;
+use crate::emulator::RunLimits;
+use crate::memory_layout::{LoadSpec, parse_address};
+use crate::poke::Poke;
+
+/// A simple chip8 emulator with multiple possible frontends
+///
+/// Command line arguments override values from the config. In Chip8 each instruction
+/// is two bytes, with 4 half-byte nibbles that are meaningful,
+/// i.e.  each instruction is of the form SXYN, with S determining the instruction,
+/// X/Y being registers to get values from, and N being an immediate u8 number. VX
+/// and VY are used to refer to the values in the X and Y registers respectively.
+///
+/// Running a ROM is the default, so `emul8rs game.ch8` is the same as
+/// `emul8rs run game.ch8`.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, arg_required_else_help = true)]
+pub struct Cli {
+    /// Sets a custom configuration file
+    #[arg(short, long, value_name = "CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Turn on logging
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub logging: u8,
+
+    /// Log levels for individual modules, on top of the --logging level
+    /// (e.g. "emul8rs::emulator=warn,emul8rs::raylib_frontend=trace")
+    #[arg(long, value_name = "FILTER", global = true)]
+    pub log_filter: Option<String>,
+
+    /// Write the log to this file instead of the terminal
+    #[arg(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Append to the log file instead of replacing it
+    #[arg(long, requires = "log_file", global = true)]
+    pub log_append: bool,
+
+    /// Still show errors in the terminal when writing the log to a file
+    #[arg(long, requires = "log_file", global = true)]
+    pub log_errors_to_stderr: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// The emul8rs subcommands
+// Only ever created once, so the size of the run arguments doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a chip8 program (the default when the first argument is a path)
+    Run(RunArgs),
+    /// Manage the configuration file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// List the ROMs in the ROM directory, or the recently played games
+    #[command(subcommand)]
+    Library(LibraryCommand),
+    /// Attract mode, show each ROM in a playlist for a while, over and over, until
+    /// a keypad key is pressed to play the current one
+    Play(PlayArgs),
+    /// Print what can be worked out about a ROM without running it (size, which
+    /// CHIP-8 extensions it uses, and the profile that suits it)
+    Info {
+        /// Path to the chip8 program
+        program: PathBuf,
+    },
+    /// Print the disassembly of a ROM, one instruction per line
+    Disasm {
+        /// Path to the chip8 program
+        program: PathBuf,
+        /// Include the raw bytes of each instruction, like objdump
+        #[arg(long)]
+        listing: bool,
+        /// Run the ROM headless first, to tell the code that runs from the
+        /// sprites and data it reads, and the bytes it never uses
+        #[arg(long)]
+        trace_first: bool,
+        /// Most instructions to run when tracing with --trace-first
+        #[arg(long, default_value_t = 100_000)]
+        trace_cycles: u64,
+    },
+    /// Run a ROM under two quirk profiles in lockstep, reporting the first
+    /// instruction after which they differ, to find which quirk it depends on
+    Compare(CompareArgs),
+    /// Compile Octo assembly source (.8o) into a ROM
+    Asm {
+        /// Path to the Octo source
+        source: PathBuf,
+        /// Where to write the ROM, defaults to the source with a .ch8 extension
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Run ROMs headless and compare the display against their golden images
+    /// (the display expected, as ASCII in a .txt file next to each ROM), e.g.
+    /// to check a test suite still passes
+    Test(TestArgs),
+}
+
+/// Arguments for testing ROMs against their golden images
+#[derive(Args, Debug)]
+pub struct TestArgs {
+    /// ROMs to test, or directories to test every ROM with a golden image in
+    #[arg(required = true)]
+    pub roms: Vec<PathBuf>,
+
+    /// Frames to run each ROM for before comparing the display
+    #[arg(long, value_name = "N", default_value_t = 300)]
+    pub frames: u64,
+
+    /// Write the golden images from the display, rather than comparing
+    #[arg(long)]
+    pub update: bool,
+}
+
+/// Arguments for comparing a program under two profiles
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+    /// Path to the chip8 program
+    pub program: PathBuf,
+
+    /// Profile of the first run: cosmac (or chip8), schip, or xo-chip
+    #[arg(long, value_name = "PROFILE")]
+    pub a_profile: String,
+
+    /// Profile of the second run
+    #[arg(long, value_name = "PROFILE")]
+    pub b_profile: String,
+
+    /// Keys to press, as a script of the frames each change happens on (see
+    /// the README), nothing is pressed without one
+    #[arg(long, value_name = "FILE")]
+    pub input: Option<PathBuf>,
+
+    /// Seed for the random numbers, the same in both runs
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Most instructions to compare
+    #[arg(long, value_name = "N", default_value_t = 1_000_000)]
+    pub max_cycles: u64,
+}
+
+/// Arguments for running a program
+#[derive(Args, Debug, Default)]
+pub struct RunArgs {
+    /// Path to chip8 program to load, or an http(s) URL to download it from
+    #[arg(required_unless_present_any = ["list_keymaps", "list_keys", "load"])]
+    pub program: Option<PathBuf>,
+
+    /// Foreground color (as a hex string like FFFFFF, #FFFFFF, or FFF, or a name like white)
+    #[arg(short, long)]
+    pub foreground: Option<String>,
+
+    /// Background color (as a hex string like FFFFFF, #FFFFFF, or FFF, or a name like white)
+    #[arg(short, long)]
+    pub background: Option<String>,
+
+    /// Width of the window in pixels
+    #[arg(long)]
+    pub window_width: Option<u32>,
+
+    /// Height of the window in pixels
+    #[arg(long)]
+    pub window_height: Option<u32>,
+
+    /// Size the window to N times the display resolution (64x32), overriding
+    /// the window width and height
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub scale: Option<u32>,
+
+    /// Number of chip8 instructions to try and execute per second
+    #[arg(long)]
+    pub instructions_per_second: Option<u64>,
+
+    /// Whether to shift value in Y register and move result into
+    /// X register, or shift X inplace
+    #[arg(long)]
+    pub shift_use_vy: Option<bool>,
+
+    /// Whether to use value from 0 register when performing jump with
+    /// offset, or to use value from the X register instead.
+    #[arg(long)]
+    pub jump_offset_use_v0: Option<bool>,
+
+    /// Whether to update the Index register when storing/loading
+    /// registers into memory
+    #[arg(long)]
+    pub store_memory_update_index: Option<bool>,
+
+    /// Whether sprites drawn past the right and bottom edges of the display
+    /// wrap around, or are clipped (sets both --wrap-x and --wrap-y)
+    #[arg(long)]
+    pub sprite_wrap: Option<bool>,
+
+    /// Whether sprites drawn past the right edge of the display wrap around
+    /// to the left edge, or are clipped
+    #[arg(long)]
+    pub wrap_x: Option<bool>,
+
+    /// Whether sprites drawn past the bottom edge of the display wrap around
+    /// to the top edge, or are clipped
+    #[arg(long)]
+    pub wrap_y: Option<bool>,
+
+    /// Most subroutine calls which can be nested before a stack overflow (12 on
+    /// the original interpreter, 16 on SUPER-CHIP)
+    #[arg(long)]
+    pub stack_depth: Option<u16>,
+
+    /// Disable audio (no audio device is opened)
+    #[arg(long)]
+    pub no_audio: bool,
+
+    /// Start with the beep muted (the mute hotkey toggles it)
+    #[arg(long)]
+    pub muted: bool,
+
+    /// Frequency of the beep in Hz
+    #[arg(long)]
+    pub sound_frequency_hz: Option<f32>,
+
+    /// Volume of the beep, between 0.0 and 1.0
+    #[arg(long)]
+    pub sound_volume: Option<f32>,
+
+    /// Mirror or rotate the display when it is drawn (none, flip_h, flip_v, or
+    /// rotate_180)
+    #[arg(long)]
+    pub display_transform: Option<String>,
+
+    /// How the terminal frontend colors the display (auto, truecolor, or
+    /// monochrome)
+    #[arg(long)]
+    pub terminal_color: Option<String>,
+
+    /// Shape of the beep (square, sine, or triangle)
+    #[arg(long)]
+    pub sound_waveform: Option<String>,
+
+    /// Sound file (wav or ogg) to use for the beep instead of the synthesized tone
+    #[arg(long)]
+    pub sound_file: Option<PathBuf>,
+
+    /// What to do when an instruction fails (abort, skip_instruction, or halt)
+    #[arg(long)]
+    pub on_error: Option<String>,
+
+    /// Whether to check the keys once per frame, rather than every time an
+    /// instruction needs them
+    #[arg(long)]
+    pub poll_keys_per_frame: Option<bool>,
+
+    /// Whether to count the timers down in the main loop at the start of each
+    /// frame (deterministic), rather than in a background thread
+    #[arg(long)]
+    pub synchronous_timers: Option<bool>,
+
+    /// Built in keymap to use (qwerty, azerty, qwertz, colemak, or dvorak),
+    /// individual keys can be overridden in the [keymap] table of the config
+    #[arg(long)]
+    pub keymap_preset: Option<String>,
+
+    /// Print the available keymap presets and exit
+    #[arg(long)]
+    pub list_keymaps: bool,
+
+    /// Print the keypad keys and the keyboard keys bound to them (from the
+    /// config, any per ROM settings for the program, and --keymap-preset), and
+    /// exit
+    #[arg(long)]
+    pub list_keys: bool,
+
+    /// Print the configuration that would be used, annotated with where each
+    /// setting came from, and exit
+    #[arg(long)]
+    pub dump_config: bool,
+
+    /// Frontend to run the program with, none runs it headless
+    #[arg(long, value_enum, default_value_t = FrontendKind::Raylib)]
+    pub frontend: FrontendKind,
+
+    /// Stop after executing this many instructions
+    #[arg(long, value_name = "N")]
+    pub max_cycles: Option<u64>,
+
+    /// Stop after running for this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    pub max_seconds: Option<f64>,
+
+    /// Write statistics about the run (instructions executed, achieved speed,
+    /// opcode counts, ...) to this file as JSON when it ends
+    #[arg(long, value_name = "FILE")]
+    pub stats_out: Option<PathBuf>,
+
+    /// Reload the ROM (restarting it) whenever its file changes, for working on
+    /// a ROM while it runs
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Seconds between autosaves while running, 0 to not autosave
+    #[arg(long, value_name = "SECONDS")]
+    pub autosave_interval_seconds: Option<f64>,
+
+    /// Carry on from the most recent autosave of the ROM, if there is one
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Take commands (pause, step, press a key, screenshot, ...) as lines of
+    /// JSON on this Unix domain socket, for scripting the emulator (on Windows,
+    /// a port number on localhost)
+    #[arg(long, value_name = "PATH")]
+    pub control_socket: Option<String>,
+
+    /// Show the program side by side with itself running with the quirks of
+    /// this profile (cosmac, schip, or xo-chip), both getting the same keys
+    #[arg(long, value_name = "PROFILE")]
+    pub compare_profile: Option<String>,
+
+    /// Use the quirk settings the ROM database recommends for the ROM, if it
+    /// knows it (settings for the ROM in [roms] and on the command line still
+    /// take priority)
+    #[arg(long)]
+    pub auto_profile: bool,
+
+    /// Print a report of what the program did which matters for compatibility
+    /// (quirk sensitive instructions, unknown opcodes, writes below 0x200, ...)
+    /// when it exits, also included in --stats-out
+    #[arg(long)]
+    pub compat_report: bool,
+
+    /// Don't warn when the ROM uses instructions whose behavior depends on the
+    /// quirk settings
+    #[arg(long)]
+    pub quiet_compat: bool,
+
+    /// When the program is a URL, download it again rather than using the copy
+    /// downloaded before
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Load a file into memory at an address (in hex or decimal) before
+    /// starting, e.g. 0x600:data.bin, can be given more than once and with or
+    /// without the program
+    #[arg(long, value_name = "ADDRESS:PATH")]
+    pub load: Vec<LoadSpec>,
+
+    /// Address to start executing at, instead of the lowest address loaded
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    pub start_address: Option<usize>,
+
+    /// Set memory or a register before the first instruction runs, e.g.
+    /// mem:0x3F0=0x1A, reg:V3=7, I=0x300, PC=0x400, DT=60, or ST=10, can be
+    /// given more than once (applied in order)
+    #[arg(long, value_name = "TARGET=VALUE")]
+    pub poke: Vec<Poke>,
+}
+
+/// Arguments for attract mode
+#[derive(Args, Debug)]
+pub struct PlayArgs {
+    /// A directory of ROMs (searched recursively), or a file listing one ROM
+    /// path per line
+    #[arg(long, value_name = "PATH")]
+    pub playlist: PathBuf,
+
+    /// Seconds to show each ROM for, unless it halts sooner
+    #[arg(long, value_name = "SECONDS", default_value_t = 60.0)]
+    pub each: f64,
+
+    /// Frontend to run the playlist with, none runs it headless
+    #[arg(long, value_enum, default_value_t = FrontendKind::Raylib)]
+    pub frontend: FrontendKind,
+}
+
+impl PlayArgs {
+    /// How long to show each ROM for
+    pub fn each(&self) -> Result<Duration> {
+        let each = Duration::try_from_secs_f64(self.each)
+            .with_context(|| format!("Invalid --each {}", self.each))?;
+        if each.is_zero() {
+            bail!("--each must be more than 0 seconds");
+        }
+        Ok(each)
+    }
+}
+
+/// Frontends a program can be run with
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrontendKind {
+    /// A window drawn with raylib
+    #[default]
+    Raylib,
+    /// No window, keyboard, or sound (for scripted runs)
+    None,
+    /// The display drawn in the terminal, with keys read from it (see terminal_color)
+    Terminal,
+}
+
+/// The `config` subcommands
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum ConfigCommand {
+    /// Print the path of the configuration file
+    Path,
+    /// Write a commented default configuration file
+    Init {
+        /// Where to write the file, instead of the configuration file path
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Overwrite the file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Open the configuration file in $VISUAL or $EDITOR (creating it if needed)
+    Edit,
+    /// Print the effective configuration, annotated with where each setting came from
+    Show {
+        /// Include the per ROM settings which apply to this ROM
+        #[arg(long)]
+        rom: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ConfigFormat::Toml)]
+        format: ConfigFormat,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum LibraryCommand {
+    /// List the ROMs in the rom_directory from the configuration
+    List {
+        /// Include the SHA-1 hash of each ROM, in the format of sha1sum
+        #[arg(long)]
+        hashes: bool,
+        /// Include the title of each ROM the ROM database knows
+        #[arg(long)]
+        titles: bool,
+    },
+    /// Print the most recently played games, most recent first
+    Recent {
+        /// Number of games to print
+        #[arg(short = 'n', long, default_value_t = 5)]
+        count: usize,
+
+        /// Print just the paths, one per line, for use in scripts
+        #[arg(long)]
+        paths: bool,
+    },
+}
+
+/// Formats the configuration can be shown in
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// TOML, with a comment after each setting giving its source
+    Toml,
+    /// JSON, with the sources of the settings listed separately
+    Json,
+}
+
+impl Cli {
+    /// Parse the command line arguments in `args` (including the binary name)
+    ///
+    /// If the arguments don't name a subcommand they are parsed as `run`, so
+    /// `emul8rs game.ch8` keeps working.
+    pub fn parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let err = match Self::try_parse_from(&args) {
+            Ok(cli) => return Ok(cli),
+            Err(err) => err,
+        };
+        // Help and version requests aren't really errors, and if a subcommand was
+        // given the error is about that subcommand
+        if matches!(
+            err.kind(),
+            ErrorKind::DisplayHelp
+                | ErrorKind::DisplayVersion
+                | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        ) || Self::names_subcommand(&args)
+        {
+            return Err(err);
+        }
+        let mut run_args = args;
+        run_args.insert(run_args.len().min(1), "run".into());
+        Self::try_parse_from(run_args)
+    }
+
+    /// Parse the command line arguments of the current process, exiting with a
+    /// usage message if they are invalid
+    pub fn parse_env() -> Self {
+        Self::parse_args(std::env::args_os()).unwrap_or_else(|err| err.exit())
+    }
+
+    /// Whether the first positional argument is the name of a subcommand
+    fn names_subcommand(args: &[OsString]) -> bool {
+        let Some(first) = args
+            .iter()
+            .skip(1)
+            .find(|arg| !arg.to_string_lossy().starts_with('-'))
+        else {
+            return false;
+        };
+        Self::command()
+            .get_subcommands()
+            .any(|subcommand| first.to_str() == Some(subcommand.get_name()))
+    }
+}
+
+impl RunArgs {
+    /// The settings given on the command line, as a table of config settings
+    /// (see [EmulatorConfig::merge])
+    pub fn overrides(&self) -> Result<toml::Table> {
+        let mut settings = toml::Table::new();
+        let mut set = |key: &str, value: toml::Value| {
+            settings.insert(key.to_string(), value);
+        };
+        if let Some(foreground) = &self.foreground {
+            set("foreground", foreground.as_str().into());
+        }
+        if let Some(background) = &self.background {
+            set("background", background.as_str().into());
+        }
+        if let Some(width) = self.window_width {
+            set("window_width", i64::from(width).into());
+        }
+        if let Some(height) = self.window_height {
+            set("window_height", i64::from(height).into());
+        }
+        if let Some(scale) = self.scale {
+            set("window_scale", i64::from(scale).into());
+        }
+        if let Some(ips) = self.instructions_per_second {
+            let ips = i64::try_from(ips).context("Instructions per second is too large")?;
+            set("instructions_per_second", ips.into());
+        }
+        if let Some(use_vy) = self.shift_use_vy {
+            set("shift_use_vy", use_vy.into());
+        }
+        if let Some(use_v0) = self.jump_offset_use_v0 {
+            set("jump_offset_use_v0", use_v0.into());
+        }
+        if let Some(update_index) = self.store_memory_update_index {
+            set("store_memory_update_index", update_index.into());
+        }
+        // The combined flag first, so --wrap-x and --wrap-y can override it
+        if let Some(wrap) = self.sprite_wrap {
+            set("wrap_x", wrap.into());
+            set("wrap_y", wrap.into());
+        }
+        if let Some(wrap) = self.wrap_x {
+            set("wrap_x", wrap.into());
+        }
+        if let Some(wrap) = self.wrap_y {
+            set("wrap_y", wrap.into());
+        }
+        if let Some(depth) = self.stack_depth {
+            set("stack_depth", i64::from(depth).into());
+        }
+        if self.no_audio {
+            set("audio_enabled", false.into());
+        }
+        if self.muted {
+            set("audio_muted", true.into());
+        }
+        if let Some(frequency) = self.sound_frequency_hz {
+            set("sound_frequency_hz", f64::from(frequency).into());
+        }
+        if let Some(volume) = self.sound_volume {
+            set("sound_volume", f64::from(volume).into());
+        }
+        if let Some(transform) = &self.display_transform {
+            set("display_transform", transform.as_str().into());
+        }
+        if let Some(terminal_color) = &self.terminal_color {
+            set("terminal_color", terminal_color.as_str().into());
+        }
+        if let Some(waveform) = &self.sound_waveform {
+            set("sound_waveform", waveform.as_str().into());
+        }
+        if let Some(sound_file) = &self.sound_file {
+            set("sound_file", sound_file.to_string_lossy().as_ref().into());
+        }
+        if let Some(on_error) = &self.on_error {
+            set("on_error", on_error.as_str().into());
+        }
+        if let Some(per_frame) = self.poll_keys_per_frame {
+            set("poll_keys_per_frame", per_frame.into());
+        }
+        if let Some(synchronous) = self.synchronous_timers {
+            set("synchronous_timers", synchronous.into());
+        }
+        if let Some(preset) = &self.keymap_preset {
+            set("keymap_preset", preset.as_str().into());
+        }
+        if let Some(seconds) = self.autosave_interval_seconds {
+            set("autosave_interval_seconds", seconds.into());
+        }
+        Ok(settings)
+    }
+
+    /// The limits on how long to run for
+    pub fn limits(&self) -> Result<RunLimits> {
+        let max_duration = match self.max_seconds {
+            Some(seconds) => Some(
+                Duration::try_from_secs_f64(seconds)
+                    .with_context(|| format!("Invalid --max-seconds {seconds}"))?,
+            ),
+            None => None,
+        };
+        Ok(RunLimits {
+            max_cycles: self.max_cycles,
+            max_duration,
+            ..RunLimits::default()
+        })
+    }
+
+    /// Override the values in `config` with those given on the command line
+    pub fn apply(&self, config: &mut EmulatorConfig) -> Result<()> {
+        *config = config.merge(&self.overrides()?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use anyhow::{Result, bail};
+
+/// The CSS basic color names, and their (r, g, b) values
+pub const NAMED_COLORS: [(&str, (u8, u8, u8)); 16] = [
+    ("black", (0x00, 0x00, 0x00)),
+    ("silver", (0xC0, 0xC0, 0xC0)),
+    ("gray", (0x80, 0x80, 0x80)),
+    ("white", (0xFF, 0xFF, 0xFF)),
+    ("maroon", (0x80, 0x00, 0x00)),
+    ("red", (0xFF, 0x00, 0x00)),
+    ("purple", (0x80, 0x00, 0x80)),
+    ("fuchsia", (0xFF, 0x00, 0xFF)),
+    ("green", (0x00, 0x80, 0x00)),
+    ("lime", (0x00, 0xFF, 0x00)),
+    ("olive", (0x80, 0x80, 0x00)),
+    ("yellow", (0xFF, 0xFF, 0x00)),
+    ("navy", (0x00, 0x00, 0x80)),
+    ("blue", (0x00, 0x00, 0xFF)),
+    ("teal", (0x00, 0x80, 0x80)),
+    ("aqua", (0x00, 0xFF, 0xFF)),
+];
+
+/// Parse a color into an (r, g, b) triple
+///
+/// Accepts hex colors as `RRGGBB` or `RGB` (either optionally prefixed with `#`),
+/// or one of the CSS basic color names (see [NAMED_COLORS]), case-insensitively.
+pub fn parse_color(color: &str) -> Result<(u8, u8, u8)> {
+    let trimmed = color.trim();
+    if let Some((_, rgb)) = NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(trimmed))
+    {
+        return Ok(*rgb);
+    }
+
+    let hex = trimmed.strip_prefix('#').unwrap_or(trimmed);
+    if hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        // Hex digits are all ascii, so indexing by byte is fine
+        let digit = |index: usize| u8::from_str_radix(&hex[index..index + 1], 16).unwrap();
+        match hex.len() {
+            6 => {
+                return Ok((
+                    digit(0) << 4 | digit(1),
+                    digit(2) << 4 | digit(3),
+                    digit(4) << 4 | digit(5),
+                ));
+            }
+            // Shorthand, each digit is doubled (f80 -> ff8800)
+            3 => return Ok((digit(0) * 0x11, digit(1) * 0x11, digit(2) * 0x11)),
+            _ => {}
+        }
+    }
+    bail!(
+        "Invalid color {color:?}, expected RRGGBB, #RRGGBB, RGB, #RGB, or a color name \
+        (e.g. FF8800, #ff8800, f80, or navy)"
+    )
+}
+
+/// Format a color as 6 uppercase hex digits (e.g. FF8800)
+pub fn to_hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("{r:02X}{g:02X}{b:02X}")
+}
+
+/// Black or white, whichever stands out more against `color`
+pub fn contrasting((r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+    // Perceived brightness, weighting green the most and blue the least
+    let brightness = 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+    if brightness >= 128 * 1000 {
+        (0x00, 0x00, 0x00)
+    } else {
+        (0xFF, 0xFF, 0xFF)
+    }
+}
+
+/// Opacity a second bit plane is drawn with over the first, when blending the
+/// planes of a pixel lit on both
+pub const PLANE_BLEND_ALPHA: f32 = 0.5;
+
+/// `over` drawn over `under` with opacity `alpha` (0.0 to 1.0), e.g. the color
+/// of a pixel lit on both bit planes when the second plane is blended over the
+/// first
+pub fn blend(under: (u8, u8, u8), over: (u8, u8, u8), alpha: f32) -> (u8, u8, u8) {
+    let alpha = alpha.clamp(0.0, 1.0);
+    // Rounded by adding a half before truncating, as the mix isn't negative
+    // (f32::round needs std)
+    let mix = |under: u8, over: u8| {
+        (f32::from(under) * (1.0 - alpha) + f32::from(over) * alpha + 0.5) as u8
+    };
+    (
+        mix(under.0, over.0),
+        mix(under.1, over.1),
+        mix(under.2, over.2),
+    )
+}
+
+/// `palette` (indexed by [crate::display::Display::color_index]) with the
+/// colors of pixels lit on several planes replaced by the colors of each of
+/// those planes, blended over each other with [PLANE_BLEND_ALPHA]
+pub fn blend_planes(palette: &[(u8, u8, u8)]) -> Vec<(u8, u8, u8)> {
+    (0..palette.len())
+        .map(|index| {
+            if index.count_ones() < 2 {
+                return palette[index];
+            }
+            (0..usize::BITS)
+                .filter(|plane| index & (1 << plane) != 0)
+                .filter_map(|plane| palette.get(1 << plane).copied())
+                .reduce(|under, over| blend(under, over, PLANE_BLEND_ALPHA))
+                .unwrap_or(palette[index])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_color {
+    use super::*;
+
+    #[test]
+    /// Test parsing six digit hex colors
+    fn test_parse_hex() -> Result<()> {
+        assert_eq!(parse_color("000000")?, (0x00, 0x00, 0x00));
+        assert_eq!(parse_color("FFFFFF")?, (0xFF, 0xFF, 0xFF));
+        assert_eq!(parse_color("12ab9F")?, (0x12, 0xAB, 0x9F));
+        assert_eq!(parse_color("#00FF00")?, (0x00, 0xFF, 0x00));
+        assert_eq!(parse_color("#a0b0c0")?, (0xA0, 0xB0, 0xC0));
+        assert_eq!(parse_color("  FF8800 ")?, (0xFF, 0x88, 0x00));
+        Ok(())
+    }
+
+    #[test]
+    /// Test parsing three digit shorthand hex colors
+    fn test_parse_shorthand() -> Result<()> {
+        assert_eq!(parse_color("f80")?, (0xFF, 0x88, 0x00));
+        assert_eq!(parse_color("#FFF")?, (0xFF, 0xFF, 0xFF));
+        assert_eq!(parse_color("#000")?, (0x00, 0x00, 0x00));
+        assert_eq!(parse_color("1a9")?, (0x11, 0xAA, 0x99));
+        Ok(())
+    }
+
+    #[test]
+    /// Test parsing every named color, in several cases
+    fn test_parse_names() -> Result<()> {
+        for (name, rgb) in NAMED_COLORS {
+            assert_eq!(parse_color(name)?, rgb);
+            assert_eq!(parse_color(&name.to_uppercase())?, rgb);
+        }
+        assert_eq!(parse_color("White")?, (0xFF, 0xFF, 0xFF));
+        assert_eq!(parse_color("lime")?, (0x00, 0xFF, 0x00));
+        Ok(())
+    }
+
+    #[test]
+    /// Test replacing the colors of pixels lit on both planes in a palette
+    fn test_blend_planes() {
+        let palette = [
+            (0xFF, 0xFF, 0xFF),
+            (0xFF, 0x00, 0x00),
+            (0x00, 0x00, 0xFF),
+            (0x12, 0x34, 0x56),
+        ];
+        assert_eq!(
+            blend_planes(&palette),
+            [palette[0], palette[1], palette[2], (0x80, 0x00, 0x80)]
+        );
+        // A single plane has nothing to blend
+        assert_eq!(blend_planes(&palette[..2]), palette[..2]);
+    }
+
+    #[test]
+    /// Test blending a second plane's color over the first's
+    fn test_blend() {
+        let first = (0xFF, 0x00, 0x00);
+        let second = (0x00, 0x00, 0xFF);
+        // A pixel lit on both planes, half of each
+        assert_eq!(blend(first, second, PLANE_BLEND_ALPHA), (0x80, 0x00, 0x80));
+        assert_eq!(
+            blend((0x20, 0x40, 0x60), (0xA0, 0xC0, 0xE0), 0.25),
+            (0x40, 0x60, 0x80)
+        );
+        // Opaque and invisible, and out of range opacities
+        assert_eq!(blend(first, second, 1.0), second);
+        assert_eq!(blend(first, second, 0.0), first);
+        assert_eq!(blend(first, second, 2.0), second);
+        assert_eq!(blend(first, second, -1.0), first);
+    }
+
+    #[test]
+    /// Test that invalid colors are rejected
+    fn test_parse_invalid() {
+        for invalid in [
+            "",
+            "#",
+            "F",
+            "FF",
+            "FFFF",
+            "FFFFF",
+            "FFFFFFF",
+            "FFFFFFFF",
+            "##FFFFFF",
+            "GGGGGG",
+            "#GGG",
+            "0xFFFFFF",
+            "FF FF FF",
+            "rgb(0,0,0)",
+            "grey",
+            "whitee",
+            "#white",
+            "+FFFFF",
+        ] {
+            assert!(
+                parse_color(invalid).is_err(),
+                "{invalid:?} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    /// Test formatting colors as hex, and parsing them back
+    fn test_to_hex() -> Result<()> {
+        assert_eq!(to_hex((0xFF, 0x88, 0x00)), "FF8800");
+        assert_eq!(to_hex((0x01, 0x02, 0x03)), "010203");
+        for (_, rgb) in NAMED_COLORS {
+            assert_eq!(parse_color(&to_hex(rgb))?, rgb);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test picking a contrasting color
+    fn test_contrasting() {
+        assert_eq!(contrasting((0xFF, 0xFF, 0xFF)), (0x00, 0x00, 0x00));
+        assert_eq!(contrasting((0xFF, 0xFF, 0x00)), (0x00, 0x00, 0x00));
+        assert_eq!(contrasting((0x00, 0x00, 0x00)), (0xFF, 0xFF, 0xFF));
+        assert_eq!(contrasting((0x00, 0x00, 0x80)), (0xFF, 0xFF, 0xFF));
+        // Middle gray would be barely visible inverted, but isn't with black
+        assert_eq!(contrasting((0x80, 0x80, 0x80)), (0x00, 0x00, 0x00));
+    }
+
+    #[test]
+    /// Test that the error echoes the color, and gives examples
+    fn test_parse_error_message() {
+        let message = parse_color("#00FF0").unwrap_err().to_string();
+        assert!(message.contains("\"#00FF0\""));
+        assert!(message.contains("#ff8800"));
+        assert!(message.contains("navy"));
+    }
+}
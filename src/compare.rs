@@ -0,0 +1,317 @@
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use anyhow::{Result, bail};
+
+use crate::config::EmulatorConfig;
+use crate::disasm::disassemble;
+use crate::emulator::{Emulator, TIMER_TICK};
+use crate::host_frontend::{HostFrontend, HostKeys};
+use crate::input_script::InputScript;
+
+/// What is compared between the two runs after each instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineState {
+    pub program_counter: usize,
+    pub index_register: u16,
+    pub registers: Vec<u8>,
+    pub stack: Vec<u16>,
+    /// Hash of the display, to compare it without keeping every pixel
+    pub display_hash: u64,
+    pub halted: bool,
+    /// What the last instruction failed with, if it failed
+    pub error: Option<String>,
+}
+
+impl MachineState {
+    /// The state of `emulator`, after an instruction which failed with `error`
+    /// if it did
+    fn of(emulator: &Emulator<'_>, error: Option<anyhow::Error>) -> Self {
+        let state = emulator.save_state();
+        let mut hasher = DefaultHasher::new();
+        emulator.display().snapshot().hash(&mut hasher);
+        Self {
+            program_counter: state.program_counter,
+            index_register: state.index_register,
+            registers: state.registers,
+            stack: state.stack,
+            display_hash: hasher.finish(),
+            halted: state.halted,
+            error: error.map(|err| format!("{err:#}")),
+        }
+    }
+}
+
+impl fmt::Display for MachineState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registers: Vec<String> = self
+            .registers
+            .iter()
+            .map(|value| format!("{value:02X}"))
+            .collect();
+        let stack: Vec<String> = self
+            .stack
+            .iter()
+            .map(|address| format!("{address:#05x}"))
+            .collect();
+        writeln!(
+            f,
+            "  PC {:#05x}  I {:#05x}  stack [{}]{}",
+            self.program_counter,
+            self.index_register,
+            stack.join(", "),
+            if self.halted { "  halted" } else { "" }
+        )?;
+        writeln!(f, "  V0-VF {}", registers.join(" "))?;
+        write!(f, "  display hash {:016x}", self.display_hash)?;
+        if let Some(error) = &self.error {
+            write!(f, "\n  error: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the two runs first differed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Instructions which left both runs in the same state, before this one
+    pub matched: u64,
+    /// Address of the instruction after which the runs differed
+    pub address: usize,
+    pub opcode: u16,
+    /// The state of each run after the instruction
+    pub a: MachineState,
+    pub b: MachineState,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Diverged after {} matching instructions, at {:#05x}: {:04X} ({})",
+            self.matched,
+            self.address,
+            self.opcode,
+            disassemble(self.opcode).unwrap_or_else(|| "unknown".to_string())
+        )?;
+        writeln!(f, "A:\n{}", self.a)?;
+        write!(f, "B:\n{}", self.b)
+    }
+}
+
+/// How a lockstep comparison ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    /// The runs differed
+    Diverged(Box<Divergence>),
+    /// Every instruction matched, until both programs exited (`halted`) or
+    /// the instruction limit was reached
+    Matched { instructions: u64, halted: bool },
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comparison::Diverged(divergence) => write!(f, "{divergence}"),
+            Comparison::Matched {
+                instructions,
+                halted,
+            } => write!(
+                f,
+                "All {instructions} instructions matched, {}",
+                if *halted {
+                    "until both programs exited"
+                } else {
+                    "stopping at the limit"
+                }
+            ),
+        }
+    }
+}
+
+/// One of the two runs, with the keys its frontend reports as down
+struct Run {
+    emulator: Emulator<'static>,
+    keys: HostKeys,
+}
+
+impl Run {
+    /// A run of `rom` with `config`, drawing random numbers seeded with `seed`
+    fn new(rom: &[u8], config: EmulatorConfig, seed: u64) -> Result<Self> {
+        let config = EmulatorConfig {
+            // Count the timers down with the instructions run, rather than in real time
+            synchronous_timers: true,
+            audio_enabled: false,
+            ..config
+        };
+        let keys = HostKeys::default();
+        let mut emulator = Emulator::new(Box::new(HostFrontend::new(keys.clone())), config)?;
+        emulator.load_rom(rom)?;
+        emulator.seed_rng(seed);
+        Ok(Self { emulator, keys })
+    }
+
+    /// Start a frame with the keys in `keys` (bit N for key N) held down
+    fn start_frame(&mut self, keys: u16) {
+        self.keys.set_all(keys);
+        self.emulator.tick_timers(TIMER_TICK);
+    }
+
+    /// Execute an instruction, returning the state after it
+    fn step(&mut self) -> MachineState {
+        let error = self.emulator.step().err();
+        MachineState::of(&self.emulator, error)
+    }
+}
+
+/// Run `rom` with configurations `a` and `b` in lockstep, instruction by
+/// instruction, until their states first differ, both programs exit, or
+/// `max_instructions` have run
+///
+/// Both runs draw the same random numbers (from `seed`), and get the same keys
+/// from `input`. Frames are as long as `a`'s instructions per second make them,
+/// in both runs, each counting the timers down by one.
+pub fn compare(
+    rom: &[u8],
+    a: EmulatorConfig,
+    b: EmulatorConfig,
+    input: &InputScript,
+    seed: u64,
+    max_instructions: u64,
+) -> Result<Comparison> {
+    let mut a = Run::new(rom, a, seed)?;
+    let mut b = Run::new(rom, b, seed)?;
+    let per_frame = a.emulator.instructions_per_frame();
+    let mut matched = 0;
+    loop {
+        if a.emulator.is_halted() && b.emulator.is_halted() {
+            return Ok(Comparison::Matched {
+                instructions: matched,
+                halted: true,
+            });
+        }
+        if matched >= max_instructions {
+            return Ok(Comparison::Matched {
+                instructions: matched,
+                halted: false,
+            });
+        }
+        if matched % per_frame == 0 {
+            let keys = input.keys_at(matched / per_frame);
+            a.start_frame(keys);
+            b.start_frame(keys);
+        }
+        // The runs have matched so far, so they are at the same instruction
+        let address = a.emulator.program_counter();
+        let opcode = a
+            .emulator
+            .peek_instruction()
+            .map_or(0, |(opcode, _)| opcode);
+        let a_state = a.step();
+        let b_state = b.step();
+        if a_state != b_state {
+            return Ok(Comparison::Diverged(Box::new(Divergence {
+                matched,
+                address,
+                opcode,
+                a: a_state,
+                b: b_state,
+            })));
+        }
+        if let Some(error) = a_state.error {
+            bail!("Both runs failed the same way after {matched} matching instructions: {error}");
+        }
+        matched += 1;
+    }
+}
+
+#[cfg(test)]
+mod test_compare {
+    use super::*;
+
+    use crate::rom::Platform;
+
+    /// The default configuration with the quirks of `platform`
+    fn profile(platform: Platform) -> Result<EmulatorConfig> {
+        EmulatorConfig::default().merge(&platform.quirks())
+    }
+
+    #[test]
+    /// Test the shift quirk is found at the shift, with what came before it
+    /// counted as matching
+    fn test_shift_divergence() -> Result<()> {
+        // V0 = 1, V1 = 4, V0 >>= V1 (8016), V2 = 7, exit
+        let rom = [0x60, 0x01, 0x61, 0x04, 0x80, 0x16, 0x62, 0x07, 0x00, 0xFD];
+        let comparison = compare(
+            &rom,
+            profile(Platform::Chip8)?,
+            profile(Platform::SuperChip)?,
+            &InputScript::default(),
+            0,
+            1000,
+        )?;
+        let Comparison::Diverged(divergence) = comparison else {
+            panic!("Expected a divergence, got {comparison:?}");
+        };
+        assert_eq!(divergence.matched, 2);
+        assert_eq!(divergence.address, 0x204);
+        assert_eq!(divergence.opcode, 0x8016);
+        // The original shifts VY into VX, SUPER-CHIP shifts VX in place
+        assert_eq!(divergence.a.registers[..2], [2, 4]);
+        assert_eq!(divergence.b.registers[..2], [0, 4]);
+        // Only VF differs (the bit shifted out)
+        assert_eq!(divergence.a.registers[0xF], 0);
+        assert_eq!(divergence.b.registers[0xF], 1);
+        let report = divergence.to_string();
+        assert!(
+            report.starts_with("Diverged after 2 matching instructions, at 0x204: 8016"),
+            "{report}"
+        );
+        assert!(report.contains("V0-VF 02 04"), "{report}");
+        Ok(())
+    }
+
+    #[test]
+    /// Test identical runs match until the program exits, with the same random
+    /// numbers and keys in both
+    fn test_matching_runs() -> Result<()> {
+        // V0 = random, wait for a key into V1, exit
+        let rom = [0xC0, 0xFF, 0xF1, 0x0A, 0x00, 0xFD];
+        let input = InputScript::parse("2 7\n4 -")?;
+        let comparison = compare(
+            &rom,
+            profile(Platform::Chip8)?,
+            profile(Platform::Chip8)?,
+            &input,
+            42,
+            100_000,
+        )?;
+        assert!(
+            matches!(comparison, Comparison::Matched { halted: true, .. }),
+            "{comparison:?}"
+        );
+        assert!(
+            comparison
+                .to_string()
+                .ends_with("until both programs exited")
+        );
+
+        // A program which never exits stops at the limit
+        let comparison = compare(
+            &[0x12, 0x00],
+            profile(Platform::Chip8)?,
+            profile(Platform::XoChip)?,
+            &input,
+            42,
+            50,
+        )?;
+        assert_eq!(
+            comparison,
+            Comparison::Matched {
+                instructions: 50,
+                halted: false
+            }
+        );
+        Ok(())
+    }
+}
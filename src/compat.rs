@@ -0,0 +1,237 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::{MEMORY_SIZE, Quirk};
+use crate::rom::{PROGRAM_START, quirk_dependency};
+
+/// What a ROM did while running that matters for how well it works on different
+/// interpreters, collected with `--compat-report`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompatReport {
+    /// Number of times each kind of instruction whose behavior differs between
+    /// interpreters executed, keyed by the instructions (see [quirk_dependency])
+    pub quirk_instructions: BTreeMap<String, u64>,
+    /// Number of times each quirk setting changed an instruction's result,
+    /// keyed by the setting
+    pub quirk_hits: BTreeMap<String, u64>,
+    /// Number of times each unknown opcode was executed, keyed by the opcode
+    pub unknown_opcodes: BTreeMap<String, u64>,
+    /// Most entries on the stack at once
+    pub max_stack_depth: usize,
+    /// Number of bytes the program wrote below [PROGRAM_START]
+    pub writes_below_program: u64,
+    /// Address of the first of those writes
+    pub first_write_below_program: Option<usize>,
+    /// Number of bytes read (as sprites or into registers) which were never
+    /// loaded or written
+    pub uninitialized_reads: u64,
+    /// Address of the first of those reads
+    pub first_uninitialized_read: Option<usize>,
+    /// Number of times a sprite drawn past an edge wrapped around it
+    pub sprites_wrapped: u64,
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Compatibility report:")?;
+        if self.quirk_instructions.is_empty() {
+            writeln!(f, "  Quirk sensitive instructions: none")?;
+        } else {
+            writeln!(f, "  Quirk sensitive instructions:")?;
+            for (instructions, count) in &self.quirk_instructions {
+                writeln!(f, "    {instructions}: {count}")?;
+            }
+        }
+        if self.quirk_hits.is_empty() {
+            writeln!(f, "  Results changed by quirk settings: none")?;
+        } else {
+            writeln!(f, "  Results changed by quirk settings:")?;
+            for (setting, count) in &self.quirk_hits {
+                writeln!(f, "    {setting}: {count}")?;
+            }
+        }
+        if self.unknown_opcodes.is_empty() {
+            writeln!(f, "  Unknown opcodes: none")?;
+        } else {
+            writeln!(f, "  Unknown opcodes:")?;
+            for (opcode, count) in &self.unknown_opcodes {
+                writeln!(f, "    {opcode}: {count}")?;
+            }
+        }
+        writeln!(f, "  Max stack depth: {}", self.max_stack_depth)?;
+        write!(
+            f,
+            "  Writes below {PROGRAM_START:#05x}: {}",
+            self.writes_below_program
+        )?;
+        match self.first_write_below_program {
+            Some(address) => writeln!(f, " (first at {address:#05x})")?,
+            None => writeln!(f)?,
+        }
+        write!(
+            f,
+            "  Reads of uninitialized memory: {}",
+            self.uninitialized_reads
+        )?;
+        match self.first_uninitialized_read {
+            Some(address) => writeln!(f, " (first at {address:#05x})")?,
+            None => writeln!(f)?,
+        }
+        write!(f, "  Sprites wrapped: {}", self.sprites_wrapped)
+    }
+}
+
+/// Collects a [CompatReport] from the emulator's hooks while it runs
+#[derive(Debug, Clone)]
+pub struct CompatTracker {
+    report: CompatReport,
+    /// Whether each byte of memory has been loaded or written
+    initialized: Vec<bool>,
+}
+
+impl Default for CompatTracker {
+    fn default() -> Self {
+        Self {
+            report: CompatReport::default(),
+            initialized: vec![false; MEMORY_SIZE],
+        }
+    }
+}
+
+impl CompatTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The report so far, with the deepest the stack got (tracked by the
+    /// emulator's statistics) as `max_stack_depth`
+    pub fn report(&self, max_stack_depth: usize) -> CompatReport {
+        CompatReport {
+            max_stack_depth,
+            ..self.report.clone()
+        }
+    }
+
+    /// Forget what was loaded into memory, when it is cleared
+    pub fn clear_memory(&mut self) {
+        self.initialized.fill(false);
+    }
+
+    /// Record that `range` of memory was loaded (e.g. with the font or ROM)
+    pub fn record_load(&mut self, range: Range<usize>) {
+        let end = range.end.min(MEMORY_SIZE);
+        if let Some(bytes) = self.initialized.get_mut(range.start..end) {
+            bytes.fill(true);
+        }
+    }
+
+    /// Record that the program wrote to `address`
+    pub fn record_write(&mut self, address: usize) {
+        if address < PROGRAM_START {
+            self.report.writes_below_program += 1;
+            self.report.first_write_below_program.get_or_insert(address);
+        }
+        self.record_load(address..address + 1);
+    }
+
+    /// Record that the program read `address`
+    pub fn record_read(&mut self, address: usize) {
+        if !self.initialized.get(address).copied().unwrap_or(true) {
+            self.report.uninitialized_reads += 1;
+            self.report.first_uninitialized_read.get_or_insert(address);
+        }
+    }
+
+    /// Record the execution of `opcode`
+    pub fn record_instruction(&mut self, opcode: u16) {
+        if let Some((instructions, _)) = quirk_dependency(opcode) {
+            *self
+                .report
+                .quirk_instructions
+                .entry(instructions.to_string())
+                .or_default() += 1;
+        }
+    }
+
+    /// Record the execution of an unknown `opcode`
+    pub fn record_unknown_opcode(&mut self, opcode: u16) {
+        *self
+            .report
+            .unknown_opcodes
+            .entry(format!("{opcode:04X}"))
+            .or_default() += 1;
+    }
+
+    /// Record that `quirk` (`enabled` or not) changed an instruction's result
+    pub fn record_quirk_hit(&mut self, quirk: Quirk, enabled: bool) {
+        *self
+            .report
+            .quirk_hits
+            .entry(quirk.name().to_string())
+            .or_default() += 1;
+        if enabled && matches!(quirk, Quirk::WrapX | Quirk::WrapY) {
+            self.report.sprites_wrapped += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_compat {
+    use super::*;
+
+    #[test]
+    /// Test each kind of record ends up in the report
+    fn test_tracker() {
+        let mut tracker = CompatTracker::new();
+        tracker.record_load(0x200..0x204);
+        tracker.record_read(0x202);
+        tracker.record_read(0x300);
+        tracker.record_read(0x301);
+        tracker.record_write(0x300);
+        tracker.record_read(0x300);
+        tracker.record_write(0x050);
+        tracker.record_instruction(0x8016);
+        tracker.record_instruction(0x812E);
+        tracker.record_instruction(0x6000);
+        tracker.record_unknown_opcode(0xE1FF);
+        tracker.record_quirk_hit(Quirk::ShiftUseVy, true);
+        tracker.record_quirk_hit(Quirk::WrapX, false);
+        tracker.record_quirk_hit(Quirk::WrapY, true);
+
+        let report = tracker.report(3);
+        assert_eq!(report.quirk_instructions["8XY6/8XYE (shift)"], 2);
+        assert_eq!(report.quirk_instructions.len(), 1);
+        assert_eq!(report.quirk_hits["wrap_x"], 1);
+        assert_eq!(report.unknown_opcodes["E1FF"], 1);
+        assert_eq!(
+            (
+                report.writes_below_program,
+                report.first_write_below_program
+            ),
+            (1, Some(0x050))
+        );
+        assert_eq!(
+            (report.uninitialized_reads, report.first_uninitialized_read),
+            (2, Some(0x300))
+        );
+        assert_eq!(report.sprites_wrapped, 1);
+        assert_eq!(report.max_stack_depth, 3);
+        let text = report.to_string();
+        assert!(text.contains("    8XY6/8XYE (shift): 2"), "{text}");
+        assert!(
+            text.contains("Writes below 0x200: 1 (first at 0x050)"),
+            "{text}"
+        );
+
+        tracker.clear_memory();
+        tracker.record_read(0x202);
+        assert_eq!(tracker.report(0).uninitialized_reads, 3);
+    }
+}
@@ -1,27 +1,1938 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+#[cfg(feature = "std")]
+use anyhow::{anyhow, bail};
+use log::warn;
+#[cfg(feature = "std")]
+use log::{debug, info};
 use serde::{Deserialize, Serialize};
 
+use crate::cheats::Cheat;
+use crate::color::{blend_planes, contrasting, parse_color, to_hex};
+use crate::display::{DISPLAY_COLS, DISPLAY_ROWS, DisplayTransform, MAX_PLANES};
+use crate::emulator::OnError;
+use crate::hotkeys::HotkeyMap;
+use crate::keymap::{KeymapPreset, normalize_key_name, parse_keypad_key};
+#[cfg(feature = "std")]
+use crate::rom::sha1_hex;
+use crate::sound::{
+    DEFAULT_MIN_BEEP_MS, DEFAULT_SOUND_RAMP_MS, MAX_FREQUENCY_HZ, MIN_FREQUENCY_HZ, Waveform,
+};
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::terminal_frontend::TerminalColor;
+use crate::timing;
+
+/// Largest allowed value for instructions_per_second
+pub const MAX_INSTRUCTIONS_PER_SECOND: u64 = 1_000_000;
+
+/// Largest allowed stack_depth
+pub const MAX_STACK_DEPTH: usize = 1024;
+
+/// Colors of the pixels lit on only the second plane, and on both planes, when
+/// no palette is given (Octo's defaults)
+pub const DEFAULT_PLANE_COLORS: [&str; 2] = ["FF6600", "662200"];
+
+/// Largest allowed window width or height (in pixels)
+pub const MAX_WINDOW_SIZE: u32 = 16_384;
+
+/// Prefix marking a `[roms]` entry as a ROM SHA-1 prefix, rather than a filename glob
+pub const ROM_HASH_PREFIX: &str = "sha1:";
+
+/// Version of the config file format, bumped when settings change in a way that
+/// needs old config files migrating
+pub const CONFIG_VERSION: u32 = 1;
+
+/// The default config file, with every setting commented (written by `config init`)
+pub const CONFIG_TEMPLATE: &str = include_str!("../resources/config/emul8rs.toml");
+
 /// Configuration of the emulator
 ///
-/// Includes settings for dealing with some ambigous instructions.
-#[derive(Serialize, Deserialize)]
+/// Includes settings for dealing with some ambigous instructions. Settings
+/// missing from a config file take their default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct EmulatorConfig {
+    /// Version of the config file format (see [CONFIG_VERSION]), files from before
+    /// it was added are version 1
+    pub config_version: u32,
     pub instructions_per_second: u64,
     pub shift_use_vy: bool,
     pub jump_offset_use_v0: bool,
     pub store_memory_update_index: bool,
+    /// Wrap sprites drawn past the right edge of the display around to the left
+    /// edge, rather than clipping them
+    pub wrap_x: bool,
+    /// Wrap sprites drawn past the bottom edge of the display around to the top
+    /// edge, rather than clipping them
+    pub wrap_y: bool,
+    /// Most subroutine calls which can be nested before a stack overflow, the
+    /// original interpreter had room for 12 and SUPER-CHIP for 16
+    pub stack_depth: usize,
     pub foreground: String,
     pub background: String,
+    /// Number of bit planes the display has, 1 or (for XO-CHIP's four colors) 2
+    pub num_planes: usize,
+    /// Colors of the pixels lit on each combination of planes, as RRGGBB, from
+    /// none lit up to all of them (so 2^num_planes colors). Empty to use the
+    /// background and foreground, with [DEFAULT_PLANE_COLORS] for a second plane
+    pub palette: Vec<String>,
+    /// Whether pixels lit on several planes are drawn in the colors of each of
+    /// those planes blended together, rather than their own palette color
+    pub plane_blend: bool,
+    /// Width of the window in pixels
+    pub window_width: u32,
+    /// Height of the window in pixels
+    pub window_height: u32,
+    /// If not 0, size the window to this multiple of the display resolution
+    /// instead of using window_width and window_height
+    pub window_scale: u32,
+    /// How the display is mirrored or rotated when drawn (none, flip_h, flip_v,
+    /// or rotate_180)
+    pub display_transform: String,
+    /// Whether to open an audio device and play sounds
+    pub audio_enabled: bool,
+    /// Whether to start muted, the mute hotkey toggles it
+    pub audio_muted: bool,
+    /// Frequency of the beep (in Hz)
+    pub sound_frequency_hz: f32,
+    /// Volume of the beep, between 0.0 and 1.0
+    pub sound_volume: f32,
+    /// Shape of the beep (square, sine, or triangle)
+    pub sound_waveform: String,
+    /// How the terminal frontend colors the display (auto, truecolor, or
+    /// monochrome)
+    pub terminal_color: String,
+    /// Whether frontends without real audio should ring the terminal bell
+    /// when the beep starts
+    pub bell_on_beep: bool,
+    /// Sound file (wav or ogg) to use for the beep instead of the synthesized
+    /// tone, empty to use the synthesized tone. Relative paths are relative to
+    /// the config file.
+    pub sound_file: String,
+    /// Shortest time (in milliseconds) the beep plays for once started, so very
+    /// short beeps can still be heard
+    pub min_beep_ms: u64,
+    /// Time (in milliseconds) the beep takes to fade in and out, so it doesn't
+    /// pop when starting and stopping
+    pub sound_ramp_ms: u64,
+    /// Whether frontends should show when the beep is sounding (e.g. by
+    /// flashing a border), for when it can't be heard
+    pub sound_indicator: bool,
+    /// Color of the sound indicator
+    pub sound_indicator_color: String,
+    /// Whether to log every change to a register's value (slow)
+    pub trace_registers: bool,
+    /// Whether to log each instruction whose result depended on a quirk setting,
+    /// with what the quirk the other way would have given
+    pub log_quirk_hits: bool,
+    /// Whether to log how many sprite draws collided (set VF) in each frame
+    /// which had any
+    pub log_collisions: bool,
+    /// What to do when an instruction fails (abort, skip_instruction, or halt)
+    pub on_error: String,
+    /// Whether to check the keys once per frame, rather than every time an
+    /// instruction needs them
+    pub poll_keys_per_frame: bool,
+    /// Whether the frontend is only given complete frames, copied from the
+    /// display at the end of each 60Hz frame, rather than the display as each
+    /// instruction leaves it
+    pub double_buffer: bool,
+    /// Whether the timers are decremented by the main loop at the start of each
+    /// frame, rather than by a background thread, so reading them is deterministic
+    pub synchronous_timers: bool,
+    /// Longest a run may take in seconds before the watchdog stops it, not
+    /// counting time spent paused, 0 for no limit (see [EmulatorConfig::max_runtime])
+    pub max_runtime_seconds: f64,
+    /// Whether the watchdog stopping a run is an error, rather than a clean stop
+    pub timeout_is_error: bool,
+    /// Seconds between save states written while running, for `--resume`, 0 to
+    /// not autosave (see [EmulatorConfig::autosave_interval])
+    pub autosave_interval_seconds: f64,
+    /// Whether an error stopping the run is shown in the window until a key is
+    /// pressed, rather than the window closing straight away
+    pub error_screen: bool,
+    /// Whether to pace the run loop by the relative cost of each instruction
+    /// (see [crate::timing::CycleCosts]) rather than treating them all the same
+    pub cycle_accurate: bool,
+    /// Whether to pause while the window doesn't have the focus, for frontends
+    /// which can tell
+    pub pause_on_focus_loss: bool,
+    /// How long to show the splash (the emulator's name) for before the ROM
+    /// starts, in seconds, 0 to not show it (see [EmulatorConfig::splash_duration])
+    pub splash_seconds: f64,
+    /// Whether to start with the FPS / IPS counter visible, it can be toggled
+    /// with the toggle_fps hotkey
+    pub show_fps: bool,
+    /// Whether to start with the keypad overlay (showing which keys are down)
+    /// visible, it can be toggled with the toggle_overlay hotkey
+    pub keypad_overlay: bool,
+    /// Whether to start with the debug grid (gridlines every 8 pixels, with
+    /// their coordinates) visible, it can be toggled with the toggle_grid hotkey
+    pub debug_grid: bool,
+    /// Whether to draw each frame blended 50/50 with the frame before, smoothing
+    /// scrolling at low frame rates (only changes what is drawn)
+    pub frame_blend: bool,
+    /// Whether to show the game being played in Discord (with the `discord`
+    /// feature)
+    pub discord_presence: bool,
+    /// Application ID of the Discord application the presence is shown as
+    /// (from the Discord developer portal), its name is what Discord shows
+    /// as being played
+    pub discord_client_id: String,
+    /// Directory of ROMs for the library (searched recursively), empty for none.
+    /// Relative paths are relative to the config file.
+    pub rom_directory: String,
+    /// The CHIP-8 database's programs.json, for ROM titles, empty to use the
+    /// small copy built in. Relative paths are relative to the config file.
+    pub rom_database: String,
+    /// Name of the built in keymap to start from
+    pub keymap_preset: String,
+    /// Individual keypad key overrides (keypad key -> physical key name),
+    /// applied on top of the preset
+    pub keymap: BTreeMap<String, String>,
+    /// Keys bound to emulator actions (action name -> physical key name),
+    /// actions not listed use their default key
+    pub hotkeys: BTreeMap<String, String>,
+    /// Relative cost overrides (instruction pattern -> cost) for
+    /// `cycle_accurate`, applied on top of the defaults
+    pub cycle_costs: BTreeMap<String, u32>,
+    /// Cheats writing values into memory (cheat name -> e.g.
+    /// "freeze 0x3A2 = 0x05"), see [crate::cheats::Cheat::parse]
+    pub cheats: BTreeMap<String, String>,
+    /// Per ROM settings, keyed by a filename glob (e.g. "pong*.ch8") or
+    /// a SHA-1 prefix of the ROM (e.g. "sha1:0b4e3a"), each entry can
+    /// override any of the other settings (only with std, where there are ROM
+    /// files to match)
+    #[cfg(feature = "std")]
+    pub roms: BTreeMap<String, toml::Table>,
 }
 
 impl Default for EmulatorConfig {
     fn default() -> Self {
         Self {
+            config_version: CONFIG_VERSION,
             instructions_per_second: 700,
             shift_use_vy: true,
             jump_offset_use_v0: true,
             store_memory_update_index: false,
+            wrap_x: false,
+            wrap_y: false,
+            stack_depth: 128,
             foreground: "000000".to_string(),
             background: "FFFFFF".to_string(),
+            num_planes: 1,
+            palette: Vec::new(),
+            plane_blend: false,
+            window_width: 640,
+            window_height: 320,
+            window_scale: 0,
+            display_transform: "none".to_string(),
+            terminal_color: "auto".to_string(),
+            audio_enabled: true,
+            audio_muted: false,
+            sound_frequency_hz: 440.0,
+            sound_volume: 0.5,
+            sound_waveform: "square".to_string(),
+            bell_on_beep: true,
+            sound_file: String::new(),
+            min_beep_ms: DEFAULT_MIN_BEEP_MS,
+            sound_ramp_ms: DEFAULT_SOUND_RAMP_MS,
+            sound_indicator: false,
+            sound_indicator_color: "FF0000".to_string(),
+            trace_registers: false,
+            log_quirk_hits: false,
+            log_collisions: false,
+            on_error: "abort".to_string(),
+            poll_keys_per_frame: false,
+            double_buffer: false,
+            synchronous_timers: false,
+            max_runtime_seconds: 0.0,
+            timeout_is_error: false,
+            autosave_interval_seconds: 0.0,
+            error_screen: false,
+            cycle_accurate: false,
+            pause_on_focus_loss: true,
+            splash_seconds: 0.0,
+            show_fps: false,
+            keypad_overlay: false,
+            debug_grid: false,
+            frame_blend: false,
+            discord_presence: false,
+            discord_client_id: String::new(),
+            rom_directory: String::new(),
+            rom_database: String::new(),
+            keymap_preset: "qwerty".to_string(),
+            keymap: BTreeMap::new(),
+            hotkeys: BTreeMap::new(),
+            cycle_costs: BTreeMap::new(),
+            cheats: BTreeMap::new(),
+            #[cfg(feature = "std")]
+            roms: BTreeMap::new(),
+        }
+    }
+}
+
+/// A problem with one of the configuration settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// Name of the setting
+    pub field: String,
+    /// The offending value
+    pub value: String,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(field: impl Into<String>, value: impl fmt::Display, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            value: value.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {:?}: {}", self.field, self.value, self.message)
+    }
+}
+
+impl core::error::Error for ConfigError {}
+
+impl EmulatorConfig {
+    /// Set both [wrap_x](EmulatorConfig::wrap_x) and
+    /// [wrap_y](EmulatorConfig::wrap_y), wrapping sprites at every edge or none
+    pub fn set_sprite_wrap(&mut self, wrap: bool) {
+        self.wrap_x = wrap;
+        self.wrap_y = wrap;
+    }
+
+    /// Check all of the settings, returning every problem found
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        // Colors
+        for (field, value) in [
+            ("foreground", &self.foreground),
+            ("background", &self.background),
+            ("sound_indicator_color", &self.sound_indicator_color),
+        ] {
+            if let Err(err) = parse_color(value) {
+                errors.push(ConfigError::new(field, value, err.to_string()));
+            }
+        }
+
+        for (index, value) in self.palette.iter().enumerate() {
+            if let Err(err) = parse_color(value) {
+                errors.push(ConfigError::new(
+                    format!("palette[{index}]"),
+                    value,
+                    err.to_string(),
+                ));
+            }
+        }
+
+        // Planes
+        if !(1..=MAX_PLANES).contains(&self.num_planes) {
+            errors.push(ConfigError::new(
+                "num_planes",
+                self.num_planes,
+                format!("must be between 1 and {MAX_PLANES}"),
+            ));
+        } else {
+            let colors = 1usize << self.num_planes;
+            let default_colors = 2 + DEFAULT_PLANE_COLORS.len();
+            if self.palette.is_empty() && colors > default_colors {
+                errors.push(ConfigError::new(
+                    "palette",
+                    "[]",
+                    format!(
+                        "must be given, with {colors} colors, for a display with {} planes",
+                        self.num_planes
+                    ),
+                ));
+            } else if !self.palette.is_empty() && self.palette.len() != colors {
+                errors.push(ConfigError::new(
+                    "palette",
+                    self.palette.join(", "),
+                    format!(
+                        "must have {colors} colors (or none) for a display with {} planes",
+                        self.num_planes
+                    ),
+                ));
+            }
+        }
+
+        // Speed
+        if self.instructions_per_second == 0
+            || self.instructions_per_second > MAX_INSTRUCTIONS_PER_SECOND
+        {
+            errors.push(ConfigError::new(
+                "instructions_per_second",
+                self.instructions_per_second,
+                format!("must be between 1 and {MAX_INSTRUCTIONS_PER_SECOND}"),
+            ));
         }
+
+        // Stack
+        if self.stack_depth == 0 || self.stack_depth > MAX_STACK_DEPTH {
+            errors.push(ConfigError::new(
+                "stack_depth",
+                self.stack_depth,
+                format!("must be between 1 and {MAX_STACK_DEPTH}"),
+            ));
+        }
+
+        // Window
+        for (field, value) in [
+            ("window_width", self.window_width),
+            ("window_height", self.window_height),
+        ] {
+            if value == 0 || value > MAX_WINDOW_SIZE {
+                errors.push(ConfigError::new(
+                    field,
+                    value,
+                    format!("must be between 1 and {MAX_WINDOW_SIZE}"),
+                ));
+            }
+        }
+        let max_scale = MAX_WINDOW_SIZE / DISPLAY_COLS.max(DISPLAY_ROWS) as u32;
+        if self.window_scale > max_scale {
+            errors.push(ConfigError::new(
+                "window_scale",
+                self.window_scale,
+                format!("must be at most {max_scale} (or 0 to use window_width and window_height)"),
+            ));
+        }
+        if let Err(err) = DisplayTransform::from_name(&self.display_transform) {
+            errors.push(ConfigError::new(
+                "display_transform",
+                &self.display_transform,
+                err.to_string(),
+            ));
+        }
+        // WebAssembly (and no_std targets) have no terminal to draw in
+        #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+        if let Err(err) = TerminalColor::from_name(&self.terminal_color) {
+            errors.push(ConfigError::new(
+                "terminal_color",
+                &self.terminal_color,
+                err.to_string(),
+            ));
+        }
+        if self.discord_presence
+            && (self.discord_client_id.is_empty()
+                || !self
+                    .discord_client_id
+                    .bytes()
+                    .all(|byte| byte.is_ascii_digit()))
+        {
+            errors.push(ConfigError::new(
+                "discord_client_id",
+                &self.discord_client_id,
+                "must be the application ID of a Discord application when discord_presence is on",
+            ));
+        }
+
+        // Sound
+        if !(MIN_FREQUENCY_HZ..=MAX_FREQUENCY_HZ).contains(&self.sound_frequency_hz) {
+            errors.push(ConfigError::new(
+                "sound_frequency_hz",
+                self.sound_frequency_hz,
+                format!("must be between {MIN_FREQUENCY_HZ} and {MAX_FREQUENCY_HZ}"),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.sound_volume) {
+            errors.push(ConfigError::new(
+                "sound_volume",
+                self.sound_volume,
+                "must be between 0.0 and 1.0",
+            ));
+        }
+        if let Err(err) = Waveform::from_name(&self.sound_waveform) {
+            errors.push(ConfigError::new(
+                "sound_waveform",
+                &self.sound_waveform,
+                err.to_string(),
+            ));
+        }
+
+        if let Err(err) = OnError::from_name(&self.on_error) {
+            errors.push(ConfigError::new(
+                "on_error",
+                &self.on_error,
+                err.to_string(),
+            ));
+        }
+
+        if !(self.max_runtime_seconds.is_finite() && self.max_runtime_seconds >= 0.0) {
+            errors.push(ConfigError::new(
+                "max_runtime_seconds",
+                self.max_runtime_seconds,
+                "must be a number of seconds, at least 0 (0 for no limit)",
+            ));
+        }
+
+        if !(self.autosave_interval_seconds.is_finite() && self.autosave_interval_seconds >= 0.0) {
+            errors.push(ConfigError::new(
+                "autosave_interval_seconds",
+                self.autosave_interval_seconds,
+                "must be a number of seconds, at least 0 (0 to not autosave)",
+            ));
+        }
+
+        if !(self.splash_seconds.is_finite() && self.splash_seconds >= 0.0) {
+            errors.push(ConfigError::new(
+                "splash_seconds",
+                self.splash_seconds,
+                "must be a number of seconds, at least 0 (0 for no splash)",
+            ));
+        }
+
+        // Keys
+        if let Err(err) = KeymapPreset::from_name(&self.keymap_preset) {
+            errors.push(ConfigError::new(
+                "keymap_preset",
+                &self.keymap_preset,
+                err.to_string(),
+            ));
+        }
+        for (keypad_key, key_name) in &self.keymap {
+            let field = format!("keymap.{keypad_key}");
+            if let Err(err) = parse_keypad_key(keypad_key) {
+                errors.push(ConfigError::new(&field, keypad_key, err.to_string()));
+            }
+            if let Err(err) = normalize_key_name(key_name) {
+                errors.push(ConfigError::new(&field, key_name, err.to_string()));
+            }
+        }
+        for (action, key_name) in &self.hotkeys {
+            let single = BTreeMap::from([(action.clone(), key_name.clone())]);
+            if let Err(err) = HotkeyMap::resolve(&single) {
+                errors.push(ConfigError::new(
+                    format!("hotkeys.{action}"),
+                    key_name,
+                    format!("{err:#}"),
+                ));
+            }
+        }
+
+        for (pattern, cost) in &self.cycle_costs {
+            if let Err(err) = timing::check_override(pattern, *cost) {
+                errors.push(ConfigError::new(
+                    format!("cycle_costs.{pattern}"),
+                    cost,
+                    err.to_string(),
+                ));
+            }
+        }
+
+        for (name, spec) in &self.cheats {
+            if let Err(err) = Cheat::parse(name, spec) {
+                errors.push(ConfigError::new(
+                    format!("cheats.{name}"),
+                    spec,
+                    format!("{err:#}"),
+                ));
+            }
+        }
+
+        // Per ROM settings
+        #[cfg(feature = "std")]
+        for key in self.roms.keys() {
+            let field = format!("roms.{key:?}");
+            match key.strip_prefix(ROM_HASH_PREFIX) {
+                Some(prefix) => {
+                    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+                        errors.push(ConfigError::new(
+                            field,
+                            key,
+                            "expected sha1: followed by hex digits",
+                        ));
+                    }
+                }
+                None => {
+                    if let Err(err) = glob::Pattern::new(key) {
+                        errors.push(ConfigError::new(
+                            field,
+                            key,
+                            format!("invalid filename glob: {err}"),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Find the `[roms]` entry which best matches a ROM, returning its key and settings
+    ///
+    /// An entry matching the ROM's hash always beats a filename glob, with longer
+    /// hash prefixes beating shorter ones. Between globs, the most specific
+    /// (the one with the most non-wildcard characters) wins.
+    #[cfg(feature = "std")]
+    pub fn find_rom_override(
+        &self,
+        rom_path: &Path,
+        rom_hash: &str,
+    ) -> Result<Option<(&str, &toml::Table)>> {
+        let file_name = rom_path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let full_path = rom_path.to_string_lossy();
+        // Best match so far, as (is hash match, specificity, key, settings)
+        let mut best: Option<(bool, usize, &str, &toml::Table)> = None;
+        for (key, settings) in &self.roms {
+            let (is_hash, specificity) = match key.strip_prefix(ROM_HASH_PREFIX) {
+                Some(prefix) => {
+                    let prefix = prefix.to_lowercase();
+                    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+                        bail!("Invalid ROM hash {key:?} in [roms], expected sha1:<hex digits>");
+                    }
+                    if !rom_hash.to_lowercase().starts_with(&prefix) {
+                        continue;
+                    }
+                    (true, prefix.len())
+                }
+                None => {
+                    let pattern = glob::Pattern::new(key)
+                        .with_context(|| format!("Invalid filename glob {key:?} in [roms]"))?;
+                    // Patterns with a path separator are matched against the whole path
+                    let candidate = if key.contains('/') {
+                        &full_path
+                    } else {
+                        &file_name
+                    };
+                    if !pattern.matches(candidate) {
+                        continue;
+                    }
+                    (false, key.chars().filter(|&c| c != '*' && c != '?').count())
+                }
+            };
+            debug!("ROM matches [roms] entry {key:?}");
+            // Earlier (in key order) entries win ties
+            if best.is_none_or(|(best_is_hash, best_specificity, ..)| {
+                (is_hash, specificity) > (best_is_hash, best_specificity)
+            }) {
+                best = Some((is_hash, specificity, key, settings));
+            }
+        }
+        Ok(best.map(|(.., key, settings)| (key, settings)))
+    }
+
+    /// Load a config from the contents of a config file
+    ///
+    /// Missing settings take their default, and unknown settings (likely typos, or
+    /// from a newer version of emul8rs) are ignored with a warning.
+    #[cfg(feature = "std")]
+    pub fn from_table(file: &toml::Table) -> Result<Self> {
+        let unknown = unknown_keys(file);
+        if !unknown.is_empty() {
+            warn!(
+                "Ignoring unknown settings in the config file (misspelled, or from a \
+                newer version?): {}",
+                unknown.join(", ")
+            );
+        }
+        let config: Self = match file.clone().try_into() {
+            Ok(config) => config,
+            Err(err) => match wrong_type_message(file) {
+                Some(message) => bail!("{message}"),
+                None => {
+                    return Err(err).context("Settings have the wrong type for the configuration");
+                }
+            },
+        };
+        if config.config_version > CONFIG_VERSION {
+            warn!(
+                "Config file is version {}, newer than the latest version this build \
+                understands ({CONFIG_VERSION}), some settings may be ignored",
+                config.config_version
+            );
+        }
+        Ok(config)
+    }
+
+    /// Create a new config with `settings` merged over this one
+    ///
+    /// Keys in `settings` replace the setting of the same name, except for
+    /// tables (like the keymap) which have their entries merged.
+    #[cfg(feature = "std")]
+    pub fn merge(&self, settings: &toml::Table) -> Result<Self> {
+        let mut merged =
+            toml::Table::try_from(self).context("Converting configuration into a table")?;
+        for (key, value) in settings {
+            if key == "roms" {
+                bail!("ROM overrides can't contain a [roms] section");
+            }
+            match (merged.get_mut(key), value) {
+                (Some(toml::Value::Table(existing)), toml::Value::Table(entries)) => {
+                    existing.extend(entries.clone());
+                }
+                (Some(existing), _) => *existing = value.clone(),
+                (None, _) => bail!("Unknown setting {key:?}"),
+            }
+        }
+        merged
+            .try_into()
+            .context("Settings have the wrong type for the configuration")
+    }
+
+    /// Longest a run may take before the watchdog stops it, None for no limit
+    ///
+    /// Invalid values (see [EmulatorConfig::validate]) are treated as no limit.
+    pub fn max_runtime(&self) -> Option<Duration> {
+        if self.max_runtime_seconds > 0.0 {
+            Duration::try_from_secs_f64(self.max_runtime_seconds).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Time between autosaves, None to not autosave
+    ///
+    /// Invalid values (see [EmulatorConfig::validate]) are treated as not
+    /// autosaving.
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        if self.autosave_interval_seconds > 0.0 {
+            Duration::try_from_secs_f64(self.autosave_interval_seconds).ok()
+        } else {
+            None
+        }
+    }
+
+    /// How long to show the splash for before the ROM starts, None to not show it
+    ///
+    /// Invalid values (see [EmulatorConfig::validate]) are treated as no splash.
+    pub fn splash_duration(&self) -> Option<Duration> {
+        if self.splash_seconds > 0.0 {
+            Duration::try_from_secs_f64(self.splash_seconds).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Colors of the pixels lit on each combination of planes (see
+    /// [EmulatorConfig::palette]), indexed by
+    /// [crate::display::Display::color_index], blended with `plane_blend`
+    pub fn palette_colors(&self) -> Result<Vec<(u8, u8, u8)>> {
+        Ok(self.blend_palette(self.unblended_palette_colors()?))
+    }
+
+    /// The [Self::palette_colors] of the inverted theme, with the colors of
+    /// unlit pixels and of pixels lit on just the first plane swapped
+    pub fn inverted_palette_colors(&self) -> Result<Vec<(u8, u8, u8)>> {
+        let mut colors = self.unblended_palette_colors()?;
+        colors.swap(0, 1);
+        Ok(self.blend_palette(colors))
+    }
+
+    /// The colors of [Self::palette_colors], before blending
+    fn unblended_palette_colors(&self) -> Result<Vec<(u8, u8, u8)>> {
+        Ok(if self.palette.is_empty() {
+            let background = parse_color(&self.background).context("Parsing background color")?;
+            let foreground = parse_color(&self.foreground).context("Parsing foreground color")?;
+            let mut colors = vec![background, foreground];
+            if self.num_planes > 1 {
+                for color in DEFAULT_PLANE_COLORS {
+                    colors.push(parse_color(color)?);
+                }
+            }
+            colors
+        } else {
+            self.palette
+                .iter()
+                .map(|color| parse_color(color).context("Parsing palette color"))
+                .collect::<Result<_>>()?
+        })
+    }
+
+    /// `colors` blended with [blend_planes] if `plane_blend` is set
+    fn blend_palette(&self, colors: Vec<(u8, u8, u8)>) -> Vec<(u8, u8, u8)> {
+        if self.plane_blend {
+            blend_planes(&colors)
+        } else {
+            colors
+        }
+    }
+
+    /// Size of the window (width, height) in pixels, see [resolve_window_size]
+    pub fn window_size(&self) -> (u32, u32) {
+        resolve_window_size(
+            self.window_scale,
+            (self.window_width, self.window_height),
+            (DISPLAY_COLS as u32, DISPLAY_ROWS as u32),
+        )
+    }
+
+    /// Resolve the paths in the config (`sound_file`, `rom_directory`, and
+    /// `rom_database`) which are relative to the config file at `config_file`,
+    /// expanding a leading `~` to the home directory
+    #[cfg(feature = "std")]
+    pub fn resolve_paths(&mut self, config_file: &Path) {
+        let home_dir = std::env::home_dir();
+        for (name, path) in [
+            ("sound_file", &mut self.sound_file),
+            ("rom_directory", &mut self.rom_directory),
+            ("rom_database", &mut self.rom_database),
+        ] {
+            if !path.is_empty() {
+                let resolved = resolve_path(path, config_file.parent(), home_dir.as_deref());
+                debug!("Resolved {name} {path:?} to {resolved:?}");
+                *path = resolved.to_string_lossy().into_owned();
+            }
+        }
+    }
+
+    /// Make sure the display will be visible, if the foreground and background are the
+    /// same color (easy to do by accident), warn and replace the background with black
+    /// or white, whichever contrasts with the foreground
+    ///
+    /// Returns true if the background was replaced. Colors that don't parse are left
+    /// for [EmulatorConfig::validate] to report.
+    pub fn fix_invisible_colors(&mut self) -> bool {
+        let (Ok(foreground), Ok(background)) =
+            (parse_color(&self.foreground), parse_color(&self.background))
+        else {
+            return false;
+        };
+        if foreground != background {
+            return false;
+        }
+        let replacement = to_hex(contrasting(foreground));
+        warn!(
+            "Foreground ({}) and background ({}) are the same color, so nothing would be \
+            visible, using {replacement} for the background instead",
+            self.foreground, self.background
+        );
+        self.background = replacement;
+        true
+    }
+
+    /// Apply the `[roms]` entry best matching the ROM at `rom_path` with contents `rom`
+    #[cfg(feature = "std")]
+    pub fn with_rom_overrides(self, rom_path: &Path, rom: &[u8]) -> Result<Self> {
+        let rom_hash = sha1_hex(rom);
+        debug!("ROM SHA-1 hash: {rom_hash}");
+        match self.find_rom_override(rom_path, &rom_hash)? {
+            Some((key, settings)) => {
+                info!(
+                    "Applying [roms] entry {key:?} to {rom_path:?}, overriding: {}",
+                    settings.keys().cloned().collect::<Vec<_>>().join(", ")
+                );
+                self.merge(settings)
+                    .with_context(|| format!("Applying [roms] entry {key:?}"))
+            }
+            None => {
+                debug!("No [roms] entry matches {rom_path:?}");
+                Ok(self)
+            }
+        }
+    }
+}
+
+/// Load a config from `contents`, the contents of the config file at `path`,
+/// returning it along with the settings actually in the file
+///
+/// Errors give the path, and the line and column of the problem, see
+/// [EmulatorConfig::from_table] for how the settings are loaded.
+#[cfg(feature = "std")]
+pub fn parse_config_file(path: &Path, contents: &str) -> Result<(EmulatorConfig, toml::Table)> {
+    let file: toml::Table = toml::from_str(contents)
+        .map_err(|err| anyhow!("Invalid TOML in config file {}:\n{err}", path.display()))?;
+    match EmulatorConfig::from_table(&file) {
+        Ok(config) => Ok((config, file)),
+        Err(err) => {
+            // Parsing straight from the text gives the location of the problem
+            let location = toml::from_str::<EmulatorConfig>(contents)
+                .err()
+                .map(|err| format!("\n{err}"))
+                .unwrap_or_default();
+            bail!(
+                "Invalid setting in config file {}: {err:#}{location}",
+                path.display()
+            )
+        }
+    }
+}
+
+/// Describe which setting in `file` has the wrong type, with the type it should be
+/// and an example of a valid value
+#[cfg(feature = "std")]
+fn wrong_type_message(file: &toml::Table) -> Option<String> {
+    let defaults = toml::Table::try_from(EmulatorConfig::default()).ok()?;
+    file.iter().find_map(|(key, value)| {
+        let default = defaults.get(key)?;
+        let mut settings = defaults.clone();
+        settings.insert(key.clone(), value.clone());
+        if settings.try_into::<EmulatorConfig>().is_ok() {
+            return None;
+        }
+        Some(match (default, value) {
+            (toml::Value::Table(_), toml::Value::Table(_)) => {
+                format!("`{key}` has an entry of the wrong type (the [{key}] section)")
+            }
+            (toml::Value::Table(_), _) => {
+                format!("`{key}` should be a table (a [{key}] section)")
+            }
+            _ => format!(
+                "`{key}` should be {} (e.g. {key} = {default}), not {}",
+                type_description(default),
+                type_description(value)
+            ),
+        })
+    })
+}
+
+/// The type of a TOML value, with an article (e.g. "a boolean")
+#[cfg(feature = "std")]
+fn type_description(value: &toml::Value) -> &'static str {
+    match value {
+        toml::Value::String(_) => "a string",
+        toml::Value::Integer(_) => "an integer",
+        toml::Value::Float(_) => "a number",
+        toml::Value::Boolean(_) => "a boolean",
+        toml::Value::Datetime(_) => "a date",
+        toml::Value::Array(_) => "an array",
+        toml::Value::Table(_) => "a table",
+    }
+}
+
+/// The top level keys of `file` which aren't settings, in key order
+#[cfg(feature = "std")]
+pub fn unknown_keys(file: &toml::Table) -> Vec<String> {
+    // The default config has every setting
+    let known = toml::Table::try_from(EmulatorConfig::default()).unwrap_or_default();
+    file.keys()
+        .filter(|key| !known.contains_key(*key))
+        .cloned()
+        .collect()
+}
+
+/// Write the commented default config ([CONFIG_TEMPLATE]) to `path`, creating any
+/// missing directories
+///
+/// An existing file is only replaced if `force` is true.
+#[cfg(feature = "std")]
+pub fn write_config_template(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        bail!(
+            "Configuration file {} already exists, pass --force to overwrite it",
+            path.display()
+        );
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Creating directory {}", parent.display()))?;
+    }
+    std::fs::write(path, CONFIG_TEMPLATE)
+        .with_context(|| format!("Writing configuration file {}", path.display()))
+}
+
+/// The command (program and arguments) to edit files with, taken from `visual` or
+/// `editor` (the VISUAL and EDITOR environment variables), falling back to vi
+pub fn editor_command(visual: Option<&str>, editor: Option<&str>) -> Vec<String> {
+    [visual, editor]
+        .into_iter()
+        .flatten()
+        .map(|command| {
+            command
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .find(|command| !command.is_empty())
+        .unwrap_or_else(|| vec!["vi".to_string()])
+}
+
+/// Config files that can sit next to the ROM at `rom`, in the order they are
+/// looked for: the ROM's file name with `.toml` added (`pong.ch8.toml`), then with
+/// its extension replaced (`pong.toml`)
+#[cfg(feature = "std")]
+pub fn rom_config_candidates(rom: &Path) -> Vec<PathBuf> {
+    let mut appended = rom.as_os_str().to_owned();
+    appended.push(".toml");
+    let mut candidates = vec![PathBuf::from(appended)];
+    let replaced = rom.with_extension("toml");
+    if replaced != rom && !candidates.contains(&replaced) {
+        candidates.push(replaced);
+    }
+    candidates
+}
+
+/// Pick the config file to use: `cli_config` (from `--config`) if given, then a
+/// config file next to the ROM at `rom` (see [rom_config_candidates]), and
+/// finally the global config file from `global`
+#[cfg(feature = "std")]
+pub fn choose_config_path(
+    cli_config: Option<PathBuf>,
+    rom: Option<&Path>,
+    global: impl FnOnce() -> Result<PathBuf>,
+) -> Result<PathBuf> {
+    if let Some(path) = cli_config {
+        return Ok(path);
+    }
+    if let Some(path) = rom
+        .into_iter()
+        .flat_map(rom_config_candidates)
+        .find(|path| path.is_file())
+    {
+        info!("Using the config file next to the ROM, {path:?}");
+        return Ok(path);
+    }
+    global()
+}
+
+/// Find the window size (width, height) in pixels
+///
+/// A non-zero `scale` wins, giving `scale` times the `native` display resolution
+/// (width, height), otherwise the explicit `size` is used.
+pub fn resolve_window_size(scale: u32, size: (u32, u32), native: (u32, u32)) -> (u32, u32) {
+    if scale == 0 {
+        size
+    } else {
+        (native.0 * scale, native.1 * scale)
+    }
+}
+
+/// Resolve a path from the config, expanding a leading `~` to `home_dir`, and
+/// making relative paths relative to `base_dir`
+#[cfg(feature = "std")]
+pub fn resolve_path(path: &str, base_dir: Option<&Path>, home_dir: Option<&Path>) -> PathBuf {
+    if let Some(home_dir) = home_dir {
+        if path == "~" {
+            return home_dir.to_path_buf();
+        }
+        if let Some(rest) = path.strip_prefix("~/") {
+            return home_dir.join(rest);
+        }
+    }
+    let path = PathBuf::from(path);
+    match base_dir {
+        Some(base_dir) if path.is_relative() => base_dir.join(path),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod test_config {
+    use super::*;
+    use crate::color::{PLANE_BLEND_ALPHA, blend};
+
+    /// Get the names of the fields with errors
+    fn error_fields(config: &EmulatorConfig) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|err| err.field).collect(),
+        }
+    }
+
+    #[test]
+    /// Test that the default config is valid
+    fn test_validate_default() {
+        assert_eq!(EmulatorConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    /// Test validating the colors
+    fn test_validate_colors() {
+        let config = EmulatorConfig {
+            foreground: "00FF0".to_string(),
+            background: "GGGGGG".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), vec!["foreground", "background"]);
+        let config = EmulatorConfig {
+            foreground: "#00FF00".to_string(),
+            background: "white".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    /// Test validating the instructions per second
+    fn test_validate_instructions_per_second() {
+        let zero = EmulatorConfig {
+            instructions_per_second: 0,
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&zero), vec!["instructions_per_second"]);
+        let too_fast = EmulatorConfig {
+            instructions_per_second: MAX_INSTRUCTIONS_PER_SECOND + 1,
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&too_fast), vec!["instructions_per_second"]);
+    }
+
+    #[test]
+    /// Test validating the stack depth
+    fn test_stack_depth() {
+        for (depth, valid) in [
+            (0, false),
+            (1, true),
+            (12, true),
+            (MAX_STACK_DEPTH + 1, false),
+        ] {
+            let config = EmulatorConfig {
+                stack_depth: depth,
+                ..Default::default()
+            };
+            let expected: Vec<&str> = if valid { vec![] } else { vec!["stack_depth"] };
+            assert_eq!(error_fields(&config), expected, "{depth}");
+        }
+    }
+
+    #[test]
+    /// Test validating the number of planes, and the palette they need
+    fn test_num_planes() -> Result<()> {
+        for (num_planes, palette_len, valid) in [
+            (0, 0, false),
+            (1, 0, true),
+            (2, 0, true),
+            (3, 0, false),
+            (2, 4, true),
+            (2, 2, false),
+            (3, 8, true),
+            (MAX_PLANES + 1, 0, false),
+        ] {
+            let config = EmulatorConfig {
+                num_planes,
+                palette: vec!["123456".to_string(); palette_len],
+                ..Default::default()
+            };
+            let expected = match (valid, num_planes) {
+                (true, _) => vec![],
+                (false, 1..=MAX_PLANES) => vec!["palette"],
+                (false, _) => vec!["num_planes"],
+            };
+            assert_eq!(
+                error_fields(&config),
+                expected,
+                "{num_planes} {palette_len}"
+            );
+        }
+
+        // The default palette covers the second plane
+        let config = EmulatorConfig {
+            num_planes: 2,
+            ..Default::default()
+        };
+        let colors = config.palette_colors()?;
+        assert_eq!(colors.len(), 4);
+        assert_eq!(colors[..2], [(0xFF, 0xFF, 0xFF), (0x00, 0x00, 0x00)]);
+        assert_eq!(EmulatorConfig::default().palette_colors()?.len(), 2);
+
+        // Blending replaces the color of pixels lit on both planes
+        let blended = EmulatorConfig {
+            plane_blend: true,
+            ..config.clone()
+        };
+        let blended_colors = blended.palette_colors()?;
+        assert_eq!(blended_colors[..3], colors[..3]);
+        assert_eq!(
+            blended_colors[3],
+            blend(colors[1], colors[2], PLANE_BLEND_ALPHA)
+        );
+
+        // The inverted theme swaps the first two colors before blending
+        let inverted = blended.inverted_palette_colors()?;
+        assert_eq!(inverted[..3], [colors[1], colors[0], colors[2]]);
+        assert_eq!(inverted[3], blend(colors[0], colors[2], PLANE_BLEND_ALPHA));
+        Ok(())
+    }
+
+    #[test]
+    /// Test validating and converting the autosave interval
+    fn test_autosave_interval() {
+        assert_eq!(EmulatorConfig::default().autosave_interval(), None);
+        let config = EmulatorConfig {
+            autosave_interval_seconds: 30.0,
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), Vec::<String>::new());
+        assert_eq!(config.autosave_interval(), Some(Duration::from_secs(30)));
+        for invalid in [-1.0, f64::NAN, f64::INFINITY] {
+            let config = EmulatorConfig {
+                autosave_interval_seconds: invalid,
+                ..Default::default()
+            };
+            assert_eq!(error_fields(&config), vec!["autosave_interval_seconds"]);
+            assert_eq!(config.autosave_interval(), None);
+        }
+    }
+
+    #[test]
+    /// Test validating and converting the maximum run time
+    fn test_max_runtime() {
+        assert_eq!(EmulatorConfig::default().max_runtime(), None);
+        let config = EmulatorConfig {
+            max_runtime_seconds: 1.5,
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), Vec::<String>::new());
+        assert_eq!(config.max_runtime(), Some(Duration::from_millis(1500)));
+        for invalid in [-1.0, f64::NAN, f64::INFINITY] {
+            let config = EmulatorConfig {
+                max_runtime_seconds: invalid,
+                ..Default::default()
+            };
+            assert_eq!(error_fields(&config), vec!["max_runtime_seconds"]);
+            assert_eq!(config.max_runtime(), None);
+        }
+    }
+
+    #[test]
+    /// Test validating and converting the splash duration
+    fn test_splash_duration() {
+        assert_eq!(EmulatorConfig::default().splash_duration(), None);
+        let config = EmulatorConfig {
+            splash_seconds: 0.25,
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), Vec::<String>::new());
+        assert_eq!(config.splash_duration(), Some(Duration::from_millis(250)));
+        for invalid in [-1.0, f64::NAN, f64::INFINITY] {
+            let config = EmulatorConfig {
+                splash_seconds: invalid,
+                ..Default::default()
+            };
+            assert_eq!(error_fields(&config), vec!["splash_seconds"]);
+            assert_eq!(config.splash_duration(), None);
+        }
+    }
+
+    #[test]
+    /// Test replacing the background when it matches the foreground
+    fn test_fix_invisible_colors() {
+        // Different colors are left alone
+        let mut config = EmulatorConfig::default();
+        assert!(!config.fix_invisible_colors());
+        assert_eq!(config.background, "FFFFFF");
+
+        // The same color, even when written differently, is replaced
+        let mut config = EmulatorConfig {
+            foreground: "white".to_string(),
+            background: "#fff".to_string(),
+            ..Default::default()
+        };
+        assert!(config.fix_invisible_colors());
+        assert_eq!(config.foreground, "white");
+        assert_eq!(config.background, "000000");
+        assert!(!config.fix_invisible_colors());
+
+        let mut config = EmulatorConfig {
+            foreground: "navy".to_string(),
+            background: "000080".to_string(),
+            ..Default::default()
+        };
+        assert!(config.fix_invisible_colors());
+        assert_eq!(config.background, "FFFFFF");
+
+        // Invalid colors are left for validation
+        let mut config = EmulatorConfig {
+            foreground: "nope".to_string(),
+            background: "nope".to_string(),
+            ..Default::default()
+        };
+        assert!(!config.fix_invisible_colors());
+    }
+
+    #[test]
+    /// Test setting both sprite wrap quirks at once
+    fn test_set_sprite_wrap() {
+        let mut config = EmulatorConfig::default();
+        assert!(!config.wrap_x && !config.wrap_y);
+        config.set_sprite_wrap(true);
+        assert!(config.wrap_x && config.wrap_y);
+        config.wrap_y = false;
+        config.set_sprite_wrap(false);
+        assert!(!config.wrap_x && !config.wrap_y);
+    }
+
+    #[test]
+    /// Test working out the window size
+    fn test_resolve_window_size() {
+        // Explicit sizes are used when there is no scale
+        assert_eq!(resolve_window_size(0, (640, 320), (64, 32)), (640, 320));
+        assert_eq!(resolve_window_size(0, (800, 600), (64, 32)), (800, 600));
+        // The scale wins over explicit sizes
+        assert_eq!(resolve_window_size(8, (800, 600), (64, 32)), (512, 256));
+        assert_eq!(resolve_window_size(1, (800, 600), (64, 32)), (64, 32));
+        // And is relative to the native resolution
+        assert_eq!(resolve_window_size(8, (800, 600), (128, 64)), (1024, 512));
+
+        // The defaults from the config
+        assert_eq!(EmulatorConfig::default().window_size(), (640, 320));
+        let config = EmulatorConfig {
+            window_scale: 15,
+            ..Default::default()
+        };
+        assert_eq!(config.window_size(), (960, 480));
+    }
+
+    #[test]
+    /// Test validating the window size
+    fn test_validate_window() {
+        let config = EmulatorConfig {
+            window_width: 0,
+            window_height: MAX_WINDOW_SIZE + 1,
+            window_scale: 1_000,
+            display_transform: "rotate_90".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            error_fields(&config),
+            vec![
+                "window_width",
+                "window_height",
+                "window_scale",
+                "display_transform"
+            ]
+        );
+        let config = EmulatorConfig {
+            window_width: MAX_WINDOW_SIZE,
+            window_height: 1,
+            window_scale: MAX_WINDOW_SIZE / 64,
+            display_transform: "FLIP_H".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    /// Test resolving paths from the config
+    fn test_resolve_path() {
+        let base = Path::new("/etc/emul8rs");
+        let home = Path::new("/home/chip");
+        assert_eq!(
+            resolve_path("beep.wav", Some(base), Some(home)),
+            PathBuf::from("/etc/emul8rs/beep.wav")
+        );
+        assert_eq!(
+            resolve_path("sounds/beep.ogg", Some(base), Some(home)),
+            PathBuf::from("/etc/emul8rs/sounds/beep.ogg")
+        );
+        assert_eq!(
+            resolve_path("/tmp/beep.wav", Some(base), Some(home)),
+            PathBuf::from("/tmp/beep.wav")
+        );
+        assert_eq!(
+            resolve_path("~/beep.wav", Some(base), Some(home)),
+            PathBuf::from("/home/chip/beep.wav")
+        );
+        assert_eq!(
+            resolve_path("~", Some(base), Some(home)),
+            PathBuf::from("/home/chip")
+        );
+        // Only a leading ~ (as a whole component) is expanded
+        assert_eq!(
+            resolve_path("~chip/beep.wav", Some(base), Some(home)),
+            PathBuf::from("/etc/emul8rs/~chip/beep.wav")
+        );
+        // Without a home or base directory, paths are left alone
+        assert_eq!(
+            resolve_path("~/beep.wav", None, None),
+            PathBuf::from("~/beep.wav")
+        );
+        assert_eq!(
+            resolve_path("beep.wav", None, Some(home)),
+            PathBuf::from("beep.wav")
+        );
+    }
+
+    #[test]
+    /// Test resolving the sound file and ROM directory relative to the config file
+    fn test_resolve_paths() {
+        let mut config = EmulatorConfig {
+            sound_file: "beep.wav".to_string(),
+            rom_directory: "roms".to_string(),
+            ..Default::default()
+        };
+        config.resolve_paths(Path::new("/etc/emul8rs/emul8rs.toml"));
+        assert_eq!(config.sound_file, "/etc/emul8rs/beep.wav");
+        assert_eq!(config.rom_directory, "/etc/emul8rs/roms");
+
+        // An unset sound file stays unset
+        let mut config = EmulatorConfig::default();
+        config.resolve_paths(Path::new("/etc/emul8rs/emul8rs.toml"));
+        assert_eq!(config.sound_file, "");
+    }
+
+    #[test]
+    /// Test validating the sound settings
+    fn test_validate_sound() {
+        let config = EmulatorConfig {
+            sound_frequency_hz: 5.0,
+            sound_volume: 1.5,
+            sound_waveform: "sawtooth".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            error_fields(&config),
+            vec!["sound_frequency_hz", "sound_volume", "sound_waveform"]
+        );
+        let config = EmulatorConfig {
+            sound_frequency_hz: f32::NAN,
+            sound_volume: -0.1,
+            ..Default::default()
+        };
+        assert_eq!(
+            error_fields(&config),
+            vec!["sound_frequency_hz", "sound_volume"]
+        );
+        let config = EmulatorConfig {
+            sound_frequency_hz: 880.0,
+            sound_volume: 0.0,
+            sound_waveform: "Triangle".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    /// Test validating the error handling mode
+    fn test_validate_on_error() {
+        for on_error in ["abort", "skip_instruction", "Halt"] {
+            let config = EmulatorConfig {
+                on_error: on_error.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(error_fields(&config), Vec::<String>::new());
+        }
+        let config = EmulatorConfig {
+            on_error: "ignore".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), vec!["on_error"]);
+    }
+
+    #[test]
+    /// Test validating the terminal color mode
+    fn test_validate_terminal_color() {
+        for terminal_color in ["auto", "TRUECOLOR", "monochrome"] {
+            let config = EmulatorConfig {
+                terminal_color: terminal_color.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(error_fields(&config), Vec::<String>::new());
+        }
+        let config = EmulatorConfig {
+            terminal_color: "256".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), vec!["terminal_color"]);
+    }
+
+    #[test]
+    /// Test the Discord application ID is only needed with the presence on
+    fn test_validate_discord_client_id() {
+        let mut config = EmulatorConfig {
+            discord_presence: true,
+            discord_client_id: "1234567890".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), Vec::<String>::new());
+        for invalid in ["", "emul8rs"] {
+            config.discord_client_id = invalid.to_string();
+            assert_eq!(error_fields(&config), vec!["discord_client_id"]);
+        }
+        config.discord_presence = false;
+        assert_eq!(error_fields(&config), Vec::<String>::new());
+    }
+
+    #[test]
+    /// Test validating the keymap
+    fn test_validate_keymap() {
+        let config = EmulatorConfig {
+            keymap_preset: "bepo".to_string(),
+            keymap: BTreeMap::from([
+                ("G".to_string(), "A".to_string()),
+                ("1".to_string(), "NOT_A_KEY".to_string()),
+                ("2".to_string(), "space".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            error_fields(&config),
+            vec!["keymap_preset", "keymap.1", "keymap.G"]
+        );
+    }
+
+    #[test]
+    /// Test validating the instruction cost overrides
+    fn test_validate_cycle_costs() {
+        let config = EmulatorConfig {
+            cycle_costs: BTreeMap::from([
+                ("DXYN".to_string(), 60),
+                ("8XY9".to_string(), 2),
+                ("FX33".to_string(), 0),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            error_fields(&config),
+            vec!["cycle_costs.8XY9", "cycle_costs.FX33"]
+        );
+    }
+
+    #[test]
+    /// Test validating the cheats
+    fn test_validate_cheats() {
+        let config = EmulatorConfig {
+            cheats: BTreeMap::from([
+                ("lives".to_string(), "freeze 0x3A2 = 0x05".to_string()),
+                ("level".to_string(), "freeze 0x3A3".to_string()),
+                ("score".to_string(), "write-once 0x3A4 = 0x1000".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(error_fields(&config), vec!["cheats.level", "cheats.score"]);
+    }
+
+    #[test]
+    /// Test validating the hotkeys
+    fn test_validate_hotkeys() {
+        let config = EmulatorConfig {
+            hotkeys: BTreeMap::from([
+                ("rewind".to_string(), "R".to_string()),
+                ("pause".to_string(), "NOT_A_KEY".to_string()),
+                ("reset".to_string(), "F4".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            error_fields(&config),
+            vec!["hotkeys.pause", "hotkeys.rewind"]
+        );
+    }
+
+    #[test]
+    /// Test validating the per ROM keys
+    fn test_validate_roms() {
+        let config = config_with_roms(
+            r#"
+            ["sha1:xyz"]
+            shift_use_vy = false
+            ["[pong.ch8"]
+            shift_use_vy = false
+            ["sha1:0A1b"]
+            shift_use_vy = false
+            "#,
+        );
+        assert_eq!(
+            error_fields(&config),
+            vec!["roms.\"[pong.ch8\"", "roms.\"sha1:xyz\""]
+        );
+    }
+
+    #[test]
+    /// Test that every problem is reported, with the field and value
+    fn test_validate_all_errors() {
+        let config = EmulatorConfig {
+            instructions_per_second: 0,
+            foreground: "reddish".to_string(),
+            ..Default::default()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].value, "reddish");
+        assert!(
+            errors[0]
+                .to_string()
+                .starts_with("foreground = \"reddish\"")
+        );
+    }
+
+    /// Create a config with the given `[roms]` section
+    fn config_with_roms(roms: &str) -> EmulatorConfig {
+        EmulatorConfig {
+            roms: toml::from_str(roms).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    /// Test merging settings over the base config
+    fn test_merge() -> Result<()> {
+        let mut base = EmulatorConfig::default();
+        base.keymap.insert("0".to_string(), "SPACE".to_string());
+        let settings: toml::Table = toml::from_str(
+            r#"
+            instructions_per_second = 1000
+            shift_use_vy = false
+            foreground = "00FF00"
+            keymap = { F = "ENTER" }
+            "#,
+        )?;
+        let merged = base.merge(&settings)?;
+
+        assert_eq!(merged.instructions_per_second, 1000);
+        assert!(!merged.shift_use_vy);
+        assert_eq!(merged.foreground, "00FF00");
+        // Settings not in the override come from the base
+        assert_eq!(merged.background, base.background);
+        assert_eq!(merged.jump_offset_use_v0, base.jump_offset_use_v0);
+        // Tables are merged entry by entry
+        assert_eq!(merged.keymap.get("0").map(String::as_str), Some("SPACE"));
+        assert_eq!(merged.keymap.get("F").map(String::as_str), Some("ENTER"));
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that bad override settings are rejected
+    fn test_merge_invalid() -> Result<()> {
+        let base = EmulatorConfig::default();
+        let unknown: toml::Table = toml::from_str("instructions_per_secnod = 10")?;
+        assert!(base.merge(&unknown).is_err());
+        let wrong_type: toml::Table = toml::from_str("shift_use_vy = \"yes\"")?;
+        assert!(base.merge(&wrong_type).is_err());
+        let nested: toml::Table = toml::from_str("[roms.\"*.ch8\"]\nshift_use_vy = false")?;
+        assert!(base.merge(&nested).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test matching ROMs by filename glob
+    fn test_find_glob() -> Result<()> {
+        let config = config_with_roms(
+            r#"
+            ["*.ch8"]
+            instructions_per_second = 1
+            ["pong*.ch8"]
+            instructions_per_second = 2
+            ["*ong*"]
+            instructions_per_second = 3
+            ["roms/games/*.ch8"]
+            instructions_per_second = 4
+            "#,
+        );
+        let hash = sha1_hex(b"pong");
+
+        // The most specific matching glob wins
+        let (key, _) = config
+            .find_rom_override(Path::new("pong2.ch8"), &hash)?
+            .unwrap();
+        assert_eq!(key, "pong*.ch8");
+        let (key, _) = config
+            .find_rom_override(Path::new("/home/me/tetris.ch8"), &hash)?
+            .unwrap();
+        assert_eq!(key, "*.ch8");
+        let (key, _) = config
+            .find_rom_override(Path::new("song.c8"), &hash)?
+            .unwrap();
+        assert_eq!(key, "*ong*");
+        // Globs containing a separator match the whole path
+        let (key, _) = config
+            .find_rom_override(Path::new("roms/games/brix.ch8"), &hash)?
+            .unwrap();
+        assert_eq!(key, "roms/games/*.ch8");
+        // Nothing matches
+        assert!(
+            config
+                .find_rom_override(Path::new("tetris.rom"), &hash)?
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that hash matches beat filename matches
+    fn test_find_hash() -> Result<()> {
+        let hash = sha1_hex(b"pong");
+        let config = config_with_roms(&format!(
+            r#"
+            ["pong.ch8"]
+            instructions_per_second = 1
+            ["sha1:{}"]
+            instructions_per_second = 2
+            ["sha1:{}"]
+            instructions_per_second = 3
+            ["sha1:{}"]
+            instructions_per_second = 4
+            "#,
+            &hash[..4],
+            &hash[..10].to_uppercase(),
+            "0000",
+        ));
+
+        let (key, _) = config
+            .find_rom_override(Path::new("pong.ch8"), &hash)?
+            .unwrap();
+        assert_eq!(key, format!("sha1:{}", &hash[..10].to_uppercase()));
+
+        // Invalid hashes are reported
+        let bad = config_with_roms("[\"sha1:xyz\"]\nshift_use_vy = true");
+        assert!(bad.find_rom_override(Path::new("pong.ch8"), &hash).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test applying the best matching override to the config
+    fn test_with_rom_overrides() -> Result<()> {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        let config = config_with_roms(&format!(
+            r#"
+            ["*.ch8"]
+            instructions_per_second = 1000
+            foreground = "FF0000"
+            ["sha1:{}"]
+            instructions_per_second = 2000
+            "#,
+            &sha1_hex(&rom)[..8],
+        ));
+
+        // Only the hash entry is applied, not the glob
+        let applied = config.with_rom_overrides(Path::new("game.ch8"), &rom)?;
+        assert_eq!(applied.instructions_per_second, 2000);
+        assert_eq!(applied.foreground, EmulatorConfig::default().foreground);
+
+        // A different ROM only gets the glob
+        let config = applied;
+        let applied = config.with_rom_overrides(Path::new("game.ch8"), &[0x00])?;
+        assert_eq!(applied.instructions_per_second, 1000);
+        assert_eq!(applied.foreground, "FF0000");
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that the commented template is exactly the default config
+    fn test_config_template() -> Result<()> {
+        let template: EmulatorConfig = toml::from_str(CONFIG_TEMPLATE)?;
+        assert_eq!(template, EmulatorConfig::default());
+        template.validate().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    /// Test writing the template, refusing to overwrite without force
+    fn test_write_config_template() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("nested").join("emul8rs.toml");
+
+        write_config_template(&path, false)?;
+        assert_eq!(std::fs::read_to_string(&path)?, CONFIG_TEMPLATE);
+
+        std::fs::write(&path, "instructions_per_second = 1")?;
+        let message = write_config_template(&path, false).unwrap_err().to_string();
+        assert!(message.contains("--force"));
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "instructions_per_second = 1"
+        );
+
+        write_config_template(&path, true)?;
+        assert_eq!(std::fs::read_to_string(&path)?, CONFIG_TEMPLATE);
+        Ok(())
+    }
+
+    #[test]
+    /// Test picking the editor command
+    fn test_editor_command() {
+        assert_eq!(editor_command(None, None), ["vi"]);
+        assert_eq!(editor_command(None, Some("nano")), ["nano"]);
+        assert_eq!(
+            editor_command(Some("code -w"), Some("nano")),
+            ["code", "-w"]
+        );
+        assert_eq!(editor_command(Some(" "), Some("nano")), ["nano"]);
+        assert_eq!(editor_command(Some(""), Some("")), ["vi"]);
+    }
+
+    #[test]
+    /// Test the names of the config files looked for next to a ROM
+    fn test_rom_config_candidates() {
+        assert_eq!(
+            rom_config_candidates(Path::new("roms/pong.ch8")),
+            [
+                PathBuf::from("roms/pong.ch8.toml"),
+                PathBuf::from("roms/pong.toml")
+            ]
+        );
+        // Without an extension, both are the same
+        assert_eq!(
+            rom_config_candidates(Path::new("pong")),
+            [PathBuf::from("pong.toml")]
+        );
+    }
+
+    #[test]
+    /// Test the order config files are picked in, --config then next to the ROM
+    /// then the global one
+    fn test_choose_config_path() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rom = dir.path().join("pong.ch8");
+        let global = || Ok(PathBuf::from("global.toml"));
+        let cli = Some(PathBuf::from("cli.toml"));
+
+        // Nothing next to the ROM yet
+        assert_eq!(
+            choose_config_path(None, Some(&rom), global)?,
+            PathBuf::from("global.toml")
+        );
+        assert_eq!(
+            choose_config_path(None, None, global)?,
+            PathBuf::from("global.toml")
+        );
+
+        std::fs::write(dir.path().join("pong.toml"), "")?;
+        assert_eq!(
+            choose_config_path(None, Some(&rom), global)?,
+            dir.path().join("pong.toml")
+        );
+        std::fs::write(dir.path().join("pong.ch8.toml"), "")?;
+        assert_eq!(
+            choose_config_path(None, Some(&rom), global)?,
+            dir.path().join("pong.ch8.toml")
+        );
+        // --config always wins
+        assert_eq!(
+            choose_config_path(cli.clone(), Some(&rom), global)?,
+            PathBuf::from("cli.toml")
+        );
+        // A directory of that name isn't a config file
+        let other = dir.path().join("other.ch8");
+        std::fs::create_dir(dir.path().join("other.toml"))?;
+        assert_eq!(
+            choose_config_path(None, Some(&other), global)?,
+            PathBuf::from("global.toml")
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test loading a config file with only some of the settings
+    fn test_from_table_partial() -> Result<()> {
+        let config = EmulatorConfig::from_table(&toml::from_str("foreground = \"navy\"")?)?;
+        assert_eq!(
+            config,
+            EmulatorConfig {
+                foreground: "navy".to_string(),
+                ..EmulatorConfig::default()
+            }
+        );
+        assert_eq!(config.config_version, CONFIG_VERSION);
+
+        // Wrong types are still errors
+        let wrong = toml::from_str("instructions_per_second = \"fast\"")?;
+        assert!(EmulatorConfig::from_table(&wrong).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test that misspelled settings are ignored, and reported
+    fn test_from_table_misspelled() -> Result<()> {
+        let file = toml::from_str(
+            r#"
+            foregound = "navy"
+            instructions_per_second = 900
+            "#,
+        )?;
+        assert_eq!(unknown_keys(&file), ["foregound"]);
+        let config = EmulatorConfig::from_table(&file)?;
+        assert_eq!(config.foreground, EmulatorConfig::default().foreground);
+        assert_eq!(config.instructions_per_second, 900);
+        Ok(())
+    }
+
+    #[test]
+    /// Test loading a config file from a newer version, with extra settings and sections
+    fn test_from_table_future() -> Result<()> {
+        let file = toml::from_str(
+            r#"
+            config_version = 7
+            background = "teal"
+            crt_glow = 0.5
+
+            [keymap]
+            0 = "SPACE"
+
+            [debugger]
+            breakpoints = [0x200, 0x2A4]
+            "#,
+        )?;
+        assert_eq!(unknown_keys(&file), ["crt_glow", "debugger"]);
+        let config = EmulatorConfig::from_table(&file)?;
+        assert_eq!(config.config_version, 7);
+        assert_eq!(config.background, "teal");
+        assert_eq!(config.keymap.get("0").map(String::as_str), Some("SPACE"));
+        assert_eq!(config.instructions_per_second, 700);
+        Ok(())
+    }
+
+    #[test]
+    /// Test that TOML syntax errors give the file and line
+    fn test_parse_config_file_syntax() {
+        let path = Path::new("/config/emul8rs.toml");
+        for (contents, line) in [
+            ("foreground = \"navy\"\nbackground = \"teal\n", "line 2"),
+            ("shift_use_vy = true\n\n[keymap\n0 = \"SPACE\"", "line 3"),
+            ("foreground = navy", "line 1"),
+            ("window_width = 640\nwindow_width = 800", "line 2"),
+        ] {
+            let message = parse_config_file(path, contents).unwrap_err().to_string();
+            assert!(message.contains("/config/emul8rs.toml"), "{message}");
+            assert!(message.contains(line), "{message}");
+        }
+    }
+
+    #[test]
+    /// Test that settings of the wrong type give the key, expected type, and line
+    fn test_parse_config_file_types() {
+        let path = Path::new("/config/emul8rs.toml");
+        for (contents, line, expected) in [
+            (
+                "foreground = \"navy\"\nshift_use_vy = \"yes\"",
+                "line 2",
+                "`shift_use_vy` should be a boolean (e.g. shift_use_vy = true), not a string",
+            ),
+            (
+                "\n\n\ninstructions_per_second = 7.5",
+                "line 4",
+                "`instructions_per_second` should be an integer (e.g. \
+                instructions_per_second = 700), not a number",
+            ),
+            ("keymap = 5", "line 1", "`keymap` should be a table"),
+            (
+                "[hotkeys]\npause = 5",
+                "line 2",
+                "`hotkeys` has an entry of the wrong type",
+            ),
+        ] {
+            let message = parse_config_file(path, contents).unwrap_err().to_string();
+            assert!(message.contains("/config/emul8rs.toml"), "{message}");
+            assert!(message.contains(line), "{message}");
+            assert!(message.contains(expected), "{message}");
+        }
+    }
+
+    #[test]
+    /// Test loading a valid config file
+    fn test_parse_config_file() -> Result<()> {
+        let (config, file) = parse_config_file(Path::new("emul8rs.toml"), CONFIG_TEMPLATE)?;
+        assert_eq!(config, EmulatorConfig::default());
+        assert!(file.contains_key("keymap"));
+        Ok(())
     }
 }
@@ -0,0 +1,31 @@
+//! Sizes of the emulated machine, gathered in one place for embedders sizing
+//! their own buffers and checking addresses
+//!
+//! Settings which can change at runtime have accessors on the emulator instead:
+//! [Emulator::start_address](crate::emulator::Emulator::start_address) and
+//! [Emulator::stack_depth](crate::emulator::Emulator::stack_depth).
+//!
+//! ```
+//! use emul8rs::constants::{
+//!     DISPLAY_COLS, DISPLAY_ROWS, HIGH_RES_COLS, HIGH_RES_ROWS, KEYPAD_SIZE, MAX_CHIP8_ROM_SIZE,
+//!     MEMORY_SIZE, NUM_REGISTERS, PROGRAM_START,
+//! };
+//!
+//! // One byte per pixel, as returned by the Python and JavaScript bindings, in
+//! // low and high resolution
+//! let frame = vec![0u8; DISPLAY_ROWS * DISPLAY_COLS];
+//! assert_eq!(frame.len(), 2048);
+//! let high_res_frame = vec![0u8; HIGH_RES_ROWS * HIGH_RES_COLS];
+//! assert_eq!(high_res_frame.len(), 8192);
+//!
+//! // Check a ROM fits before loading it
+//! let rom = [0x12, 0x00];
+//! assert!(PROGRAM_START + rom.len() <= MEMORY_SIZE);
+//! assert_eq!(MAX_CHIP8_ROM_SIZE, MEMORY_SIZE - PROGRAM_START);
+//! assert_eq!((NUM_REGISTERS, KEYPAD_SIZE), (16, 16));
+//! ```
+
+pub use crate::display::{DISPLAY_COLS, DISPLAY_ROWS, HIGH_RES_COLS, HIGH_RES_ROWS};
+pub use crate::emulator::{MEMORY_SIZE, NUM_REGISTERS};
+pub use crate::keymap::KEYPAD_SIZE;
+pub use crate::rom::{MAX_CHIP8_ROM_SIZE, PROGRAM_START};
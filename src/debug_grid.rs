@@ -0,0 +1,143 @@
+use crate::display::DisplayTransform;
+
+/// Pixels of the display between gridlines
+pub const GRID_SPACING: usize = 8;
+
+/// A coordinate label of the debug grid, placed in window pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridLabel {
+    pub text: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Offsets (in window pixels) of the gridlines across `cells` display pixels of
+/// `cell_size` window pixels each, one every [GRID_SPACING] pixels (not
+/// including the edges of the window)
+pub fn grid_lines(cells: usize, cell_size: i32) -> Vec<i32> {
+    (GRID_SPACING..cells)
+        .step_by(GRID_SPACING)
+        .map(|cell| cell as i32 * cell_size)
+        .collect()
+}
+
+/// Size of the label text for display pixels of `cell_width` by `cell_height`
+/// window pixels, small enough for a label to fit between two gridlines
+pub fn grid_font_size(cell_width: i32, cell_height: i32) -> i32 {
+    (cell_width.min(cell_height) * 2).clamp(6, 20)
+}
+
+/// The labels of the debug grid over a display of `rows` by `cols` pixels:
+/// column numbers along the top and row numbers down the left side, each just
+/// inside the gridline it labels
+///
+/// Labels are the display coordinate of the pixel just after their gridline,
+/// so they still match the sprite positions a program uses when the display is
+/// mirrored or rotated. The top left corner has only a column label, as a row
+/// label there would overlap it.
+pub fn grid_labels(
+    transform: DisplayTransform,
+    rows: usize,
+    cols: usize,
+    cell_width: i32,
+    cell_height: i32,
+    font_size: i32,
+) -> Vec<GridLabel> {
+    let padding = (font_size / 4).max(1);
+    // Every transform is its own inverse, so it also finds the display pixel
+    // drawn at a position on the screen
+    let column_labels = (0..cols).step_by(GRID_SPACING).map(|col| GridLabel {
+        text: transform.apply(0, col, rows, cols).1.to_string(),
+        x: col as i32 * cell_width + padding,
+        y: padding,
+    });
+    let row_labels = (GRID_SPACING..rows)
+        .step_by(GRID_SPACING)
+        .map(|row| GridLabel {
+            text: transform.apply(row, 0, rows, cols).0.to_string(),
+            x: padding,
+            y: row as i32 * cell_height + padding,
+        });
+    column_labels.chain(row_labels).collect()
+}
+
+#[cfg(test)]
+mod test_debug_grid {
+    use super::*;
+    use crate::display::{DISPLAY_COLS, DISPLAY_ROWS, HIGH_RES_COLS, HIGH_RES_ROWS};
+
+    #[test]
+    /// Test the gridlines are every 8 display pixels, scaled to the window
+    fn test_grid_lines() {
+        assert_eq!(
+            grid_lines(DISPLAY_COLS, 10),
+            [80, 160, 240, 320, 400, 480, 560]
+        );
+        assert_eq!(grid_lines(DISPLAY_ROWS, 3), [24, 48, 72]);
+        assert_eq!(grid_font_size(10, 12), 20);
+        assert_eq!(grid_font_size(2, 2), 6);
+    }
+
+    #[test]
+    /// Test placing the column and row numbers, scaled with the window
+    fn test_grid_labels() {
+        let labels = grid_labels(
+            DisplayTransform::None,
+            DISPLAY_ROWS,
+            DISPLAY_COLS,
+            10,
+            12,
+            8,
+        );
+        let texts: Vec<&str> = labels.iter().map(|label| label.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "0", "8", "16", "24", "32", "40", "48", "56", "8", "16", "24"
+            ]
+        );
+        assert_eq!(
+            labels[1],
+            GridLabel {
+                text: "8".to_string(),
+                x: 82,
+                y: 2
+            }
+        );
+        assert_eq!(
+            labels[8],
+            GridLabel {
+                text: "8".to_string(),
+                x: 2,
+                y: 98
+            }
+        );
+
+        // Mirrored, the labels count down from the other side
+        let labels = grid_labels(
+            DisplayTransform::Rotate180,
+            DISPLAY_ROWS,
+            DISPLAY_COLS,
+            10,
+            12,
+            8,
+        );
+        assert_eq!(labels[0].text, "63");
+        assert_eq!(labels[1].text, "55");
+        assert_eq!(labels[8].text, "23");
+        assert_eq!((labels[8].x, labels[8].y), (2, 98));
+
+        // In high resolution there are twice as many labels each way
+        let labels = grid_labels(
+            DisplayTransform::Rotate180,
+            HIGH_RES_ROWS,
+            HIGH_RES_COLS,
+            5,
+            6,
+            8,
+        );
+        assert_eq!(labels.len(), 16 + 7);
+        assert_eq!(labels[0].text, "127");
+        assert_eq!(labels[16].text, "55");
+    }
+}
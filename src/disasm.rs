@@ -0,0 +1,518 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::cell::RefCell;
+
+#[cfg(feature = "std")]
+use anyhow::Result;
+#[cfg(feature = "std")]
+use log::warn;
+
+#[cfg(feature = "std")]
+use crate::config::EmulatorConfig;
+#[cfg(feature = "std")]
+use crate::emulator::Emulator;
+use crate::emulator::{AccessKind, MemoryAccess};
+#[cfg(feature = "std")]
+use crate::headless_frontend::HeadlessFrontend;
+use crate::instruction::Instruction;
+use crate::stats::opcode_pattern;
+
+/// Decode a single instruction into its mnemonic (e.g. `LD V3, 0x12`), or None if
+/// the opcode isn't a known instruction
+///
+/// Uses the common CHIP-8 mnemonics, along with the SUPER-CHIP and XO-CHIP
+/// extensions.
+pub fn disassemble(opcode: u16) -> Option<String> {
+    Instruction::decode(opcode).map(mnemonic)
+}
+
+/// The mnemonic of a decoded instruction (see [disassemble])
+pub fn mnemonic(instruction: Instruction) -> String {
+    use Instruction::*;
+    match instruction {
+        Clear => "CLS".to_string(),
+        Return => "RET".to_string(),
+        ScrollDown { n } => format!("SCD {n}"),
+        ScrollUp { n } => format!("SCU {n}"),
+        ScrollRight => "SCR".to_string(),
+        ScrollLeft => "SCL".to_string(),
+        Exit => "EXIT".to_string(),
+        LowResolution => "LOW".to_string(),
+        HighResolution => "HIGH".to_string(),
+        MachineCode { nnn } => format!("SYS {nnn:#05X}"),
+        Jump { nnn } => format!("JP {nnn:#05X}"),
+        Call { nnn } => format!("CALL {nnn:#05X}"),
+        SkipIfEqual { x, nn } => format!("SE V{x:X}, {nn:#04X}"),
+        SkipIfNotEqual { x, nn } => format!("SNE V{x:X}, {nn:#04X}"),
+        SkipIfRegistersEqual { x, y } => format!("SE V{x:X}, V{y:X}"),
+        StoreRange { x, y } => format!("SAVE V{x:X}-V{y:X}"),
+        LoadRange { x, y } => format!("LOAD V{x:X}-V{y:X}"),
+        Set { x, nn } => format!("LD V{x:X}, {nn:#04X}"),
+        Add { x, nn } => format!("ADD V{x:X}, {nn:#04X}"),
+        SetRegister { x, y } => format!("LD V{x:X}, V{y:X}"),
+        Or { x, y } => format!("OR V{x:X}, V{y:X}"),
+        And { x, y } => format!("AND V{x:X}, V{y:X}"),
+        Xor { x, y } => format!("XOR V{x:X}, V{y:X}"),
+        AddRegister { x, y } => format!("ADD V{x:X}, V{y:X}"),
+        Subtract { x, y } => format!("SUB V{x:X}, V{y:X}"),
+        ShiftRight { x, y } => format!("SHR V{x:X}, V{y:X}"),
+        SubtractReversed { x, y } => format!("SUBN V{x:X}, V{y:X}"),
+        ShiftLeft { x, y } => format!("SHL V{x:X}, V{y:X}"),
+        SkipIfRegistersNotEqual { x, y } => format!("SNE V{x:X}, V{y:X}"),
+        SetIndex { nnn } => format!("LD I, {nnn:#05X}"),
+        JumpWithOffset { nnn, .. } => format!("JP V0, {nnn:#05X}"),
+        Random { x, nn } => format!("RND V{x:X}, {nn:#04X}"),
+        Draw { x, y, n } => format!("DRW V{x:X}, V{y:X}, {n}"),
+        SkipIfKey { x } => format!("SKP V{x:X}"),
+        SkipIfNotKey { x } => format!("SKNP V{x:X}"),
+        LoadLongIndex => "LD I, long".to_string(),
+        SelectPlanes { mask } => format!("PLANE {mask}"),
+        LoadAudioPattern => "AUDIO".to_string(),
+        GetDelayTimer { x } => format!("LD V{x:X}, DT"),
+        WaitForKey { x } => format!("LD V{x:X}, K"),
+        SetDelayTimer { x } => format!("LD DT, V{x:X}"),
+        SetSoundTimer { x } => format!("LD ST, V{x:X}"),
+        AddToIndex { x } => format!("ADD I, V{x:X}"),
+        FontCharacter { x } => format!("LD F, V{x:X}"),
+        BigFontCharacter { x } => format!("LD HF, V{x:X}"),
+        BinaryCodedDecimal { x } => format!("LD B, V{x:X}"),
+        SetPitch { x } => format!("PITCH V{x:X}"),
+        StoreRegisters { x } => format!("LD [I], V{x:X}"),
+        LoadRegisters { x } => format!("LD V{x:X}, [I]"),
+        SaveFlags { x } => format!("LD R, V{x:X}"),
+        LoadFlags { x } => format!("LD V{x:X}, R"),
+    }
+}
+
+/// What an opcode decodes to, for showing the instruction a debugger is at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeKind {
+    /// The instruction's pattern (e.g. `8XY4`, see [opcode_pattern])
+    pub pattern: String,
+    /// The decoded instruction, None if the opcode isn't a known instruction
+    pub instruction: Option<Instruction>,
+    /// The instruction's mnemonic (see [disassemble]), None if the opcode
+    /// isn't a known instruction
+    pub mnemonic: Option<String>,
+}
+
+impl OpcodeKind {
+    /// Decode `opcode`
+    pub fn decode(opcode: u16) -> Self {
+        let instruction = Instruction::decode(opcode);
+        Self {
+            pattern: opcode_pattern(opcode),
+            instruction,
+            mnemonic: instruction.map(mnemonic),
+        }
+    }
+}
+
+/// Disassemble `bytes`, one instruction per line
+///
+/// Each line has the address (with the first instruction at `start_addr`) and
+/// the mnemonic, unknown opcodes are written as `; unknown`.
+pub fn disassemble_program(bytes: &[u8], start_addr: usize) -> String {
+    listing(bytes, start_addr, false)
+}
+
+/// Disassemble `bytes` as an objdump style listing, one instruction per line
+///
+/// Each line has the address (with the first instruction at `start_addr`), the
+/// two raw bytes of the instruction in hex, and the mnemonic. Unknown opcodes
+/// (and a trailing odd byte) still show their bytes, with a `; unknown` comment.
+pub fn disassemble_listing(bytes: &[u8], start_addr: usize) -> String {
+    listing(bytes, start_addr, true)
+}
+
+/// Disassemble `bytes`, including the raw bytes of each instruction if `with_bytes`
+fn listing(bytes: &[u8], start_addr: usize, with_bytes: bool) -> String {
+    let mut output = String::new();
+    write_instructions(&mut output, bytes, start_addr, with_bytes);
+    output
+}
+
+/// Write the disassembly of `bytes` to `output`, one instruction per line
+fn write_instructions(output: &mut String, bytes: &[u8], start_addr: usize, with_bytes: bool) {
+    for (index, chunk) in bytes.chunks(2).enumerate() {
+        let address = start_addr + 2 * index;
+        let _ = write!(output, "{address:03X}:");
+        if with_bytes {
+            // Both bytes take 6 columns, even when there is only one
+            let raw = chunk
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let _ = write!(output, " {raw:<6}");
+        }
+        let mnemonic = match chunk {
+            [high, low] => disassemble(u16::from_be_bytes([*high, *low])),
+            _ => None,
+        };
+        let _ = writeln!(
+            output,
+            " {}",
+            mnemonic.unwrap_or_else(|| "; unknown".to_string())
+        );
+    }
+}
+
+/// Most bytes on one `db` line of data
+const DATA_BYTES_PER_LINE: usize = 8;
+
+/// What a byte of a program was used for in a traced run (see [CodeMap])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ByteUse {
+    /// Never executed, read, or written
+    Unknown,
+    /// Read or written as data (FX33, FX55, and FX65)
+    Data,
+    /// Drawn as a sprite (DXYN)
+    Sprite,
+    /// Executed as (part of) an instruction
+    Code,
+}
+
+impl ByteUse {
+    /// Name of the use, as shown in the listing
+    pub fn name(&self) -> &'static str {
+        match self {
+            ByteUse::Unknown => "unknown, never executed or read",
+            ByteUse::Data => "data",
+            ByteUse::Sprite => "sprite",
+            ByteUse::Code => "code",
+        }
+    }
+}
+
+/// What each byte of a program was used for while it ran, to tell its code
+/// from its data
+///
+/// A byte used more than one way is counted as the use highest in [ByteUse]
+/// (so code drawn as a sprite is still code).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeMap {
+    /// Address of the first byte of the program
+    start_addr: usize,
+    uses: Vec<ByteUse>,
+}
+
+impl CodeMap {
+    /// A map of `len` bytes from `start_addr`, none of them used yet
+    pub fn new(start_addr: usize, len: usize) -> Self {
+        Self {
+            start_addr,
+            uses: vec![ByteUse::Unknown; len],
+        }
+    }
+
+    /// Record an access, ignoring ones outside of the program
+    pub fn record(&mut self, access: MemoryAccess) {
+        let byte_use = match access.kind {
+            AccessKind::Execute => ByteUse::Code,
+            AccessKind::Sprite => ByteUse::Sprite,
+            AccessKind::Load | AccessKind::Store => ByteUse::Data,
+        };
+        if let Some(current) = access
+            .address
+            .checked_sub(self.start_addr)
+            .and_then(|offset| self.uses.get_mut(offset))
+        {
+            *current = (*current).max(byte_use);
+        }
+    }
+
+    /// How the byte at `address` was used, None if it isn't part of the program
+    pub fn byte_use(&self, address: usize) -> Option<ByteUse> {
+        let offset = address.checked_sub(self.start_addr)?;
+        self.uses.get(offset).copied()
+    }
+
+    /// The runs of bytes used the same way, as ranges of addresses
+    pub fn regions(&self) -> Vec<(Range<usize>, ByteUse)> {
+        let mut regions: Vec<(Range<usize>, ByteUse)> = Vec::new();
+        for (offset, byte_use) in self.uses.iter().enumerate() {
+            let address = self.start_addr + offset;
+            match regions.last_mut() {
+                Some((range, last)) if last == byte_use => range.end = address + 1,
+                _ => regions.push((address..address + 1, *byte_use)),
+            }
+        }
+        regions
+    }
+}
+
+/// Run `rom` headless for up to `max_cycles` instructions with `config`,
+/// recording what each of its bytes was used for
+///
+/// The run stops early if the program exits or an instruction fails (which is
+/// logged), keeping what was recorded until then. Nothing presses any keys.
+#[cfg(feature = "std")]
+pub fn trace_program(rom: &[u8], config: EmulatorConfig, max_cycles: u64) -> Result<CodeMap> {
+    let map = RefCell::new(CodeMap::new(crate::rom::PROGRAM_START, rom.len()));
+    {
+        let config = EmulatorConfig {
+            // Count the timers down with the frames run, rather than in real time
+            synchronous_timers: true,
+            audio_enabled: false,
+            ..config
+        };
+        let mut emulator = Emulator::new(Box::new(HeadlessFrontend::new()), config)?;
+        emulator.load_rom(rom)?;
+        emulator.set_access_sink(|access| map.borrow_mut().record(access));
+        while emulator.cycles() < max_cycles && !emulator.is_halted() {
+            if let Err(err) = emulator.run_frame() {
+                warn!(
+                    "Stopped tracing after {} instructions: {err:#}",
+                    emulator.cycles()
+                );
+                break;
+            }
+        }
+    }
+    Ok(map.into_inner())
+}
+
+/// Disassemble `bytes` using what a traced run found each byte was used for
+///
+/// Each run of bytes used the same way starts with a comment naming the use.
+/// Code is disassembled as by [disassemble_program] (or [disassemble_listing]
+/// if `with_bytes`), sprites are shown a byte per line with a picture of the
+/// row, and data and bytes which were never used are shown as `db` lines.
+pub fn disassemble_traced(bytes: &[u8], map: &CodeMap, with_bytes: bool) -> String {
+    let mut output = String::new();
+    for (range, byte_use) in map.regions() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        let _ = writeln!(
+            output,
+            "; {} ({:03X}-{:03X})",
+            byte_use.name(),
+            range.start,
+            range.end - 1
+        );
+        let offsets = range.start - map.start_addr..range.end - map.start_addr;
+        let region = &bytes[offsets];
+        if byte_use == ByteUse::Code {
+            write_instructions(&mut output, region, range.start, with_bytes);
+            continue;
+        }
+        // A sprite is a byte per row
+        let per_line = if byte_use == ByteUse::Sprite {
+            1
+        } else {
+            DATA_BYTES_PER_LINE
+        };
+        for (index, chunk) in region.chunks(per_line).enumerate() {
+            let address = range.start + index * per_line;
+            let _ = write!(output, "{address:03X}:");
+            if with_bytes {
+                // Line up with the mnemonics of the instructions
+                let _ = write!(output, " {:6}", "");
+            }
+            let values = chunk
+                .iter()
+                .map(|byte| format!("0x{byte:02X}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = write!(output, " db {values}");
+            if byte_use == ByteUse::Sprite {
+                let row: String = (0..8)
+                    .map(|bit| {
+                        if chunk[0] & (0x80 >> bit) != 0 {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                let _ = write!(output, "  ; {row}");
+            }
+            output.push('\n');
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test_disasm {
+    use super::*;
+
+    #[test]
+    /// Test decoding instructions
+    fn test_disassemble() {
+        for (opcode, mnemonic) in [
+            (0x00E0, "CLS"),
+            (0x00EE, "RET"),
+            (0x00FD, "EXIT"),
+            (0x0123, "SYS 0x123"),
+            (0x1228, "JP 0x228"),
+            (0x2ABC, "CALL 0xABC"),
+            (0x3A0F, "SE VA, 0x0F"),
+            (0x5120, "SE V1, V2"),
+            (0x6312, "LD V3, 0x12"),
+            (0x7F01, "ADD VF, 0x01"),
+            (0x8AB4, "ADD VA, VB"),
+            (0x8AB6, "SHR VA, VB"),
+            (0xA22A, "LD I, 0x22A"),
+            (0xB300, "JP V0, 0x300"),
+            (0xD01F, "DRW V0, V1, 15"),
+            (0xE29E, "SKP V2"),
+            (0xF40A, "LD V4, K"),
+            (0xF733, "LD B, V7"),
+            (0xF565, "LD V5, [I]"),
+        ] {
+            assert_eq!(disassemble(opcode).as_deref(), Some(mnemonic));
+        }
+        for opcode in [0x5121, 0x8AB8, 0x9121, 0xE2A2, 0xF0FF, 0xFFFF] {
+            assert_eq!(disassemble(opcode), None, "{opcode:04X}");
+        }
+    }
+
+    #[test]
+    /// Test the objdump style listing, and that its columns line up
+    fn test_disassemble_listing() {
+        let program = [
+            0x00, 0xE0, // CLS
+            0x61, 0x0A, // LD V1, 0x0A
+            0xFF, 0xFF, // (unknown)
+            0xD0, 0x15, // DRW V0, V1, 5
+            0x12, // (trailing byte)
+        ];
+        let listing = disassemble_listing(&program, 0x200);
+        assert_eq!(
+            listing,
+            "200: 00 E0  CLS\n\
+             202: 61 0A  LD V1, 0x0A\n\
+             204: FF FF  ; unknown\n\
+             206: D0 15  DRW V0, V1, 5\n\
+             208: 12     ; unknown\n"
+        );
+        // The bytes and mnemonics start in the same column on every line
+        for line in listing.lines() {
+            assert_eq!(line.find(':'), Some(3), "{line:?}");
+            assert_eq!(&line[4..5], " ", "{line:?}");
+            assert!(
+                line[5..7].chars().all(|c| c.is_ascii_hexdigit()),
+                "{line:?}"
+            );
+            assert_eq!(&line[11..12], " ", "{line:?}");
+            assert_ne!(&line[12..13], " ", "{line:?}");
+        }
+    }
+
+    /// A program which draws a sprite from a table after its code and loads two
+    /// bytes of data, with four bytes between them which are never used
+    const SPRITE_TABLE_ROM: [u8; 19] = [
+        0xA2, 0x0E, // 200: LD I, 0x20E
+        0xD0, 0x03, // 202: DRW V0, V0, 3
+        0xA2, 0x11, // 204: LD I, 0x211
+        0xF1, 0x65, // 206: LD V1, [I]
+        0x12, 0x08, // 208: JP 0x208
+        0x12, 0x34, 0x00, 0x00, // 20A: never used
+        0x3C, 0x42, 0x81, // 20E: sprite
+        0x05, 0x07, // 211: data
+    ];
+
+    #[test]
+    /// Test telling code from sprites and data by tracing a run
+    fn test_trace_program() -> Result<()> {
+        let map = trace_program(&SPRITE_TABLE_ROM, EmulatorConfig::default(), 1000)?;
+        assert_eq!(
+            map.regions(),
+            vec![
+                (0x200..0x20A, ByteUse::Code),
+                (0x20A..0x20E, ByteUse::Unknown),
+                (0x20E..0x211, ByteUse::Sprite),
+                (0x211..0x213, ByteUse::Data),
+            ]
+        );
+        assert_eq!(map.byte_use(0x20F), Some(ByteUse::Sprite));
+        assert_eq!(map.byte_use(0x213), None);
+        assert_eq!(map.byte_use(0x1FF), None);
+
+        assert_eq!(
+            disassemble_traced(&SPRITE_TABLE_ROM, &map, false),
+            "; code (200-209)\n\
+             200: LD I, 0x20E\n\
+             202: DRW V0, V0, 3\n\
+             204: LD I, 0x211\n\
+             206: LD V1, [I]\n\
+             208: JP 0x208\n\
+             \n\
+             ; unknown, never executed or read (20A-20D)\n\
+             20A: db 0x12, 0x34, 0x00, 0x00\n\
+             \n\
+             ; sprite (20E-210)\n\
+             20E: db 0x3C  ; ..####..\n\
+             20F: db 0x42  ; .#....#.\n\
+             210: db 0x81  ; #......#\n\
+             \n\
+             ; data (211-212)\n\
+             211: db 0x05, 0x07\n"
+        );
+        // With the raw bytes, db lines line up with the mnemonics
+        let listing = disassemble_traced(&SPRITE_TABLE_ROM, &map, true);
+        assert!(listing.contains("200: A2 0E  LD I, 0x20E\n"), "{listing}");
+        assert!(listing.contains("211:        db 0x05, 0x07\n"), "{listing}");
+        Ok(())
+    }
+
+    #[test]
+    /// Test code is code however else it is used, and the trace stops at a
+    /// failing instruction
+    fn test_code_map() -> Result<()> {
+        let mut map = CodeMap::new(0x200, 4);
+        for (address, kind) in [
+            (0x200, AccessKind::Execute),
+            (0x200, AccessKind::Sprite),
+            (0x201, AccessKind::Store),
+            (0x201, AccessKind::Execute),
+            (0x202, AccessKind::Load),
+            (0x203, AccessKind::Store),
+            (0x203, AccessKind::Sprite),
+            (0x300, AccessKind::Execute),
+        ] {
+            map.record(MemoryAccess { address, kind });
+        }
+        assert_eq!(
+            map.regions(),
+            vec![
+                (0x200..0x202, ByteUse::Code),
+                (0x202..0x203, ByteUse::Data),
+                (0x203..0x204, ByteUse::Sprite),
+            ]
+        );
+
+        // LD V0, 1 then an unknown opcode
+        let map = trace_program(
+            &[0x60, 0x01, 0xFF, 0xFF, 0x00],
+            EmulatorConfig::default(),
+            1000,
+        )?;
+        assert_eq!(
+            map.regions(),
+            vec![
+                (0x200..0x204, ByteUse::Code),
+                (0x204..0x205, ByteUse::Unknown)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test the listing without the raw bytes
+    fn test_disassemble_program() {
+        assert_eq!(
+            disassemble_program(&[0x00, 0xE0, 0x12, 0x00], 0x300),
+            "300: CLS\n302: JP 0x200\n"
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Discord Rich Presence (with the `discord` feature), showing the game being
+//! played in Discord
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use discord_rich_presence::activity::{Activity, Timestamps};
+use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+use log::{debug, info};
+
+use crate::display::Display;
+use crate::frontend::Frontend;
+use crate::save_state::unix_millis;
+
+/// How long to wait between attempts to connect to Discord, while it isn't
+/// running or after the connection drops
+const RETRY_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Longest to wait on exit for the presence to be cleared
+const CLEAR_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The emulator's name and version, shown under the game
+const EMULATOR: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// What Discord shows while a ROM is running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Presence {
+    /// Title of the game (from the ROM database, or the file name)
+    pub title: String,
+    /// When the game started, in milliseconds since the Unix epoch, for
+    /// Discord's elapsed time
+    pub started: u64,
+}
+
+impl Presence {
+    /// The presence after loading the ROM titled `title` at `now`, following
+    /// `previous`
+    ///
+    /// Reloading the same game (e.g. with `--watch`) keeps counting its play
+    /// time, a new game starts again from zero.
+    pub fn after_load(previous: Option<&Presence>, title: &str, now: SystemTime) -> Self {
+        match previous {
+            Some(previous) if previous.title == title => previous.clone(),
+            _ => Self {
+                title: title.to_string(),
+                started: unix_millis(now),
+            },
+        }
+    }
+
+    /// The activity sent to Discord
+    pub fn activity(&self) -> Activity<'_> {
+        Activity::new()
+            .details(self.title.as_str())
+            .state(EMULATOR)
+            .timestamps(Timestamps::new().start(self.started as i64))
+    }
+}
+
+/// Keeps the presence up to date over `client`'s connection, sending each
+/// presence received, until `updates` disconnects, then clears it
+///
+/// Failing to connect (e.g. Discord isn't running) or a dropped connection is
+/// only logged, and connecting is tried again every `retry` while there is a
+/// presence to show.
+fn run_connection(
+    mut client: impl DiscordIpc,
+    updates: Receiver<Option<Presence>>,
+    retry: Duration,
+) {
+    let mut wanted: Option<Presence> = None;
+    // What Discord is showing, None if not connected
+    let mut shown: Option<Option<Presence>> = None;
+    loop {
+        let timeout = if shown.is_none() && wanted.is_some() {
+            retry
+        } else {
+            Duration::MAX
+        };
+        match updates.recv_timeout(timeout) {
+            Ok(presence) => wanted = presence,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if shown.is_none() && wanted.is_some() {
+            match client.connect() {
+                Ok(()) => {
+                    info!("Connected to Discord");
+                    shown = Some(None);
+                }
+                Err(err) => debug!("Couldn't connect to Discord: {err}"),
+            }
+        }
+        if let Some(current) = &shown
+            && *current != wanted
+        {
+            let sent = match &wanted {
+                Some(presence) => client.set_activity(presence.activity()),
+                None => client.clear_activity(),
+            };
+            match sent {
+                Ok(()) => shown = Some(wanted.clone()),
+                Err(err) => {
+                    info!("Lost the connection to Discord: {err}");
+                    let _ = client.close();
+                    shown = None;
+                }
+            }
+        }
+    }
+    if shown.is_some() {
+        let _ = client.clear_activity();
+        let _ = client.close();
+    }
+}
+
+/// A frontend showing the title of each ROM loaded in Discord, for pairing with
+/// the frontend being played in (see [crate::tee_frontend::TeeFrontend])
+///
+/// The connection to Discord is handled by a thread of its own, so Discord not
+/// running, or going away, never holds up the emulator.
+pub struct DiscordPresence {
+    /// The current presence, sent to the connection thread when it changes
+    presence: Option<Presence>,
+    updates: Option<Sender<Option<Presence>>>,
+    /// Disconnects when the connection thread has finished
+    finished: Receiver<()>,
+}
+
+impl DiscordPresence {
+    /// Start showing the presence for the Discord application `client_id`
+    pub fn new(client_id: &str) -> Self {
+        Self::with_client(DiscordIpcClient::new(client_id), RETRY_INTERVAL)
+    }
+
+    /// Start showing the presence over `client`, connecting again every `retry`
+    /// until it succeeds
+    pub fn with_client(client: impl DiscordIpc + Send + 'static, retry: Duration) -> Self {
+        let (updates, receiver) = mpsc::channel();
+        let (finished_sender, finished) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            run_connection(client, receiver, retry);
+            drop(finished_sender);
+        });
+        Self {
+            presence: None,
+            updates: Some(updates),
+            finished,
+        }
+    }
+}
+
+impl Drop for DiscordPresence {
+    /// Clear the presence, waiting (briefly) for it to be cleared
+    fn drop(&mut self) {
+        self.updates = None;
+        let _ = self.finished.recv_timeout(CLEAR_TIMEOUT);
+    }
+}
+
+impl Frontend for DiscordPresence {
+    fn draw(&mut self, _display: &Display) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_key(&mut self, _key: u8) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        let presence = Presence::after_load(self.presence.as_ref(), title, SystemTime::now());
+        if self.presence.as_ref() != Some(&presence) {
+            if let Some(updates) = &self.updates {
+                // The thread only stops once this is dropped
+                let _ = updates.send(Some(presence.clone()));
+            }
+            self.presence = Some(presence);
+        }
+        Ok(())
+    }
+
+    fn play_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn should_stop(&mut self) -> bool {
+        false
+    }
+
+    fn step(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_discord {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use discord_rich_presence::error::Error;
+    use serde_json::Value;
+
+    /// A connection to Discord, recording the messages sent over it
+    struct MockIpc {
+        /// Payloads sent (other than the handshake), shared with the test
+        sent: Arc<Mutex<Vec<Value>>>,
+        /// Connection attempts to fail before one succeeds
+        failures: usize,
+        /// Whether the last write was a message's header
+        after_header: bool,
+    }
+
+    impl DiscordIpc for MockIpc {
+        fn get_client_id(&self) -> &str {
+            "1234"
+        }
+
+        fn connect_ipc(&mut self) -> Result<(), Error> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(Error::IPCNotFound);
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+            // Each message is written as an 8 byte header, then its JSON
+            if !self.after_header {
+                self.after_header = true;
+                return Ok(());
+            }
+            self.after_header = false;
+            let payload: Value = serde_json::from_slice(data).unwrap();
+            if payload.get("cmd").is_some() {
+                self.sent.lock().unwrap().push(payload);
+            }
+            Ok(())
+        }
+
+        /// The handshake's reply, an empty object
+        fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+            if buffer.len() == 8 {
+                buffer[..4].copy_from_slice(&1u32.to_le_bytes());
+                buffer[4..].copy_from_slice(&2u32.to_le_bytes());
+            } else {
+                buffer.copy_from_slice(b"{}");
+            }
+            Ok(())
+        }
+
+        fn close(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    /// The activity in each message sent, Null for clearing it
+    fn activities(sent: &Mutex<Vec<Value>>) -> Vec<Value> {
+        sent.lock()
+            .unwrap()
+            .iter()
+            .map(|message| message["args"]["activity"].clone())
+            .collect()
+    }
+
+    #[test]
+    /// Test the play time carries on when the same game is loaded again
+    fn test_presence_after_load() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let later = start + Duration::from_secs(60);
+        let presence = Presence::after_load(None, "Pong", start);
+        assert_eq!(presence.started, 100_000);
+        assert_eq!(
+            Presence::after_load(Some(&presence), "Pong", later),
+            presence
+        );
+        let next = Presence::after_load(Some(&presence), "Tetris", later);
+        assert_eq!(next.title, "Tetris");
+        assert_eq!(next.started, 160_000);
+
+        let activity = serde_json::to_value(presence.activity()).unwrap();
+        assert_eq!(activity["details"], "Pong");
+        assert_eq!(activity["state"], EMULATOR);
+        assert_eq!(activity["timestamps"]["start"], 100_000);
+    }
+
+    #[test]
+    /// Test each game loaded is shown, then cleared on exit, retrying until
+    /// Discord is there
+    fn test_discord_presence() -> Result<()> {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let client = MockIpc {
+            sent: sent.clone(),
+            failures: 2,
+            after_header: false,
+        };
+        let mut presence = DiscordPresence::with_client(client, Duration::from_millis(1));
+        presence.set_title("Pong")?;
+        presence.set_title("Pong")?;
+        // Wait until connected, after the failed attempts
+        while sent.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        presence.set_title("Tetris")?;
+        drop(presence);
+
+        let activities = activities(&sent);
+        let titles: Vec<&Value> = activities
+            .iter()
+            .map(|activity| &activity["details"])
+            .collect();
+        assert_eq!(
+            titles,
+            [&Value::from("Pong"), &Value::from("Tetris"), &Value::Null]
+        );
+        assert_eq!(activities[2], Value::Null);
+        Ok(())
+    }
+}
@@ -1,17 +1,105 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
 use anyhow::{Context, Result, bail};
 
 // Display Constants
+/// Rows of the display in low resolution (the usual CHIP-8 display)
 pub const DISPLAY_ROWS: usize = 32;
+/// Columns of the display in low resolution (the usual CHIP-8 display)
 pub const DISPLAY_COLS: usize = 64;
-const COL_STRIDE: usize = 1;
-const ROW_STRIDE: usize = DISPLAY_COLS;
+/// Rows of the display in SUPER-CHIP's high resolution mode (00FF)
+pub const HIGH_RES_ROWS: usize = 64;
+/// Columns of the display in SUPER-CHIP's high resolution mode (00FF)
+pub const HIGH_RES_COLS: usize = 128;
+/// Bit of a plane mask (as used by XO-CHIP) selecting the first plane
+pub const PLANE_0: u8 = 0b01;
+/// Most planes a display can have, one per bit of a plane mask
+pub const MAX_PLANES: usize = u8::BITS as usize;
+
+/// How the display is mirrored or rotated when drawn, without changing the
+/// display itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayTransform {
+    None,
+    /// Mirrored left to right
+    FlipH,
+    /// Mirrored top to bottom
+    FlipV,
+    /// Turned upside down (both flips at once)
+    Rotate180,
+}
+
+impl DisplayTransform {
+    /// All of the transforms
+    pub const ALL: [DisplayTransform; 4] = [
+        DisplayTransform::None,
+        DisplayTransform::FlipH,
+        DisplayTransform::FlipV,
+        DisplayTransform::Rotate180,
+    ];
+
+    /// Name of the transform as used in the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            DisplayTransform::None => "none",
+            DisplayTransform::FlipH => "flip_h",
+            DisplayTransform::FlipV => "flip_v",
+            DisplayTransform::Rotate180 => "rotate_180",
+        }
+    }
+
+    /// Find the transform with the given name (case-insensitive)
+    pub fn from_name(name: &str) -> Result<Self> {
+        let lower = name.trim().to_lowercase();
+        match Self::ALL.iter().find(|transform| transform.name() == lower) {
+            Some(transform) => Ok(*transform),
+            None => bail!(
+                "Unknown display transform {name:?}, available transforms are: {}",
+                Self::ALL.map(|transform| transform.name()).join(", ")
+            ),
+        }
+    }
+
+    /// Where the cell at (`row`, `col`) of a display `rows` by `cols` cells
+    /// (see [Display::rows] and [Display::cols]) is drawn, as a (row, column)
+    /// on the screen
+    pub fn apply(&self, row: usize, col: usize, rows: usize, cols: usize) -> (usize, usize) {
+        let flip_row = rows - 1 - row;
+        let flip_col = cols - 1 - col;
+        match self {
+            DisplayTransform::None => (row, col),
+            DisplayTransform::FlipH => (row, flip_col),
+            DisplayTransform::FlipV => (flip_row, col),
+            DisplayTransform::Rotate180 => (flip_row, flip_col),
+        }
+    }
+}
 
 // NOTE: This may be replaces with underlying bitvec to save space eventually
 
 /// A boolean array representing the state of the display
+///
+/// The display has one or more bit planes (XO-CHIP has 2), each pixel's color
+/// is picked from a palette of `2^num_planes` colors by its bit in each plane.
+/// Everything but the plane methods works on the first plane.
+///
+/// It starts in low resolution ([DISPLAY_ROWS] by [DISPLAY_COLS]), and can be
+/// switched to SUPER-CHIP's high resolution ([HIGH_RES_ROWS] by
+/// [HIGH_RES_COLS]) with [Display::set_high_res].
+#[derive(Clone)]
 pub struct Display {
-    /// Underlying data representing the display (row major matrix)
-    data: [bool; DISPLAY_ROWS * DISPLAY_COLS],
+    /// Underlying data representing the first plane (row major matrix)
+    data: Vec<bool>,
+    /// Any planes after the first, kept apart so the usual single plane display
+    /// is no slower for them
+    extra_planes: Vec<Vec<bool>>,
+    /// Whether the display is in high resolution
+    high_res: bool,
+    /// Mask of the planes drawn to and cleared (bit N for plane N)
+    selected_planes: u8,
     /// Whether the display needs to be redrawn
     pub needs_redraw: bool,
 }
@@ -23,22 +111,196 @@ impl Default for Display {
 }
 
 impl Display {
-    /// Create an empty display
+    /// Create an empty display, with a single plane
     pub fn new() -> Self {
         Display {
-            data: [false; DISPLAY_ROWS * DISPLAY_COLS],
+            data: vec![false; DISPLAY_ROWS * DISPLAY_COLS],
+            extra_planes: Vec::new(),
+            high_res: false,
+            selected_planes: PLANE_0,
             needs_redraw: false,
         }
     }
 
+    /// Create an empty display with `num_planes` planes (1 to [MAX_PLANES]),
+    /// with only the first selected
+    pub fn with_planes(num_planes: usize) -> Result<Self> {
+        if !(1..=MAX_PLANES).contains(&num_planes) {
+            bail!("A display has 1 to {MAX_PLANES} planes, not {num_planes}");
+        }
+        Ok(Display {
+            extra_planes: vec![vec![false; DISPLAY_ROWS * DISPLAY_COLS]; num_planes - 1],
+            ..Self::new()
+        })
+    }
+
+    /// Number of rows, [HIGH_RES_ROWS] in high resolution and [DISPLAY_ROWS]
+    /// otherwise
+    pub fn rows(&self) -> usize {
+        if self.high_res {
+            HIGH_RES_ROWS
+        } else {
+            DISPLAY_ROWS
+        }
+    }
+
+    /// Number of columns, [HIGH_RES_COLS] in high resolution and [DISPLAY_COLS]
+    /// otherwise
+    pub fn cols(&self) -> usize {
+        if self.high_res {
+            HIGH_RES_COLS
+        } else {
+            DISPLAY_COLS
+        }
+    }
+
+    /// Whether the display is in SUPER-CHIP's high resolution mode
+    pub fn is_high_res(&self) -> bool {
+        self.high_res
+    }
+
+    /// Switch to high resolution (00FF) or back to low resolution (00FE),
+    /// returning whether the resolution changed
+    ///
+    /// Changing the resolution clears every plane, as the cells no longer line
+    /// up with the new size. The selected planes are kept.
+    pub fn set_high_res(&mut self, high_res: bool) -> bool {
+        if high_res == self.high_res {
+            return false;
+        }
+        self.high_res = high_res;
+        let cells = self.rows() * self.cols();
+        for data in core::iter::once(&mut self.data).chain(&mut self.extra_planes) {
+            data.clear();
+            data.resize(cells, false);
+        }
+        self.needs_redraw = true;
+        true
+    }
+
+    /// Index of the cell at `row` and `col` in the (row major) plane data
+    fn index(&self, row: usize, col: usize) -> usize {
+        row * self.cols() + col
+    }
+
+    /// Check `row` and `col` are on the display, `action` saying what was
+    /// being done for the error
+    fn check_bounds(&self, row: usize, col: usize, action: &str) -> Result<()> {
+        if row >= self.rows() || col >= self.cols() {
+            bail!("Tried to {action} outside display bounds!")
+        }
+        Ok(())
+    }
+
+    /// Number of planes
+    pub fn num_planes(&self) -> usize {
+        1 + self.extra_planes.len()
+    }
+
+    /// Number of colors in the palette for the display, one per combination
+    /// of planes
+    pub fn palette_len(&self) -> usize {
+        1 << self.num_planes()
+    }
+
+    /// Check `palette` has a color for every combination of planes
+    pub fn check_palette<T>(&self, palette: &[T]) -> Result<()> {
+        if palette.len() != self.palette_len() {
+            bail!(
+                "A display with {} planes needs {} colors in its palette, not {}",
+                self.num_planes(),
+                self.palette_len(),
+                palette.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Check `mask` only selects planes the display has
+    fn check_mask(&self, mask: u8) -> Result<()> {
+        let missing = mask.checked_shr(self.num_planes() as u32).unwrap_or(0);
+        if missing != 0 {
+            bail!(
+                "Plane mask {mask:#04b} selects planes the display doesn't have, it has {}",
+                self.num_planes()
+            );
+        }
+        Ok(())
+    }
+
+    /// Select the planes drawn to and cleared with `mask` (bit N for plane N),
+    /// as XO-CHIP's FN01 does
+    pub fn select_planes(&mut self, mask: u8) -> Result<()> {
+        self.check_mask(mask)?;
+        self.selected_planes = mask;
+        Ok(())
+    }
+
+    /// Mask of the selected planes
+    pub fn selected_planes(&self) -> u8 {
+        self.selected_planes
+    }
+
+    /// The cells of plane `plane`
+    fn plane_data(&self, plane: usize) -> Result<&[bool]> {
+        match plane {
+            0 => Ok(&self.data),
+            _ => self
+                .extra_planes
+                .get(plane - 1)
+                .map(Vec::as_slice)
+                .with_context(|| format!("The display has no plane {plane}")),
+        }
+    }
+
+    /// The cells of plane `plane`, to change
+    fn plane_data_mut(&mut self, plane: usize) -> Result<&mut [bool]> {
+        match plane {
+            0 => Ok(&mut self.data),
+            _ => self
+                .extra_planes
+                .get_mut(plane - 1)
+                .map(Vec::as_mut_slice)
+                .with_context(|| format!("The display has no plane {plane}")),
+        }
+    }
+
+    /// Get the element of plane `plane` at the specified row and column
+    pub fn get_plane(&self, plane: usize, row: usize, col: usize) -> Result<bool> {
+        self.check_bounds(row, col, "get")?;
+        let index = self.index(row, col);
+        Ok(self.plane_data(plane)?[index])
+    }
+
+    /// XOR the element of plane `plane` at the specified row and column,
+    /// returns true if value was turned from set to unset
+    pub fn xor_plane(&mut self, plane: usize, row: usize, col: usize, val: bool) -> Result<bool> {
+        self.check_bounds(row, col, "xor")?;
+        let index = self.index(row, col);
+        let el = &mut self.plane_data_mut(plane)?[index];
+        let flip = *el & val;
+        *el ^= val;
+        Ok(flip)
+    }
+
+    /// Index into the palette of the pixel at the specified row and column,
+    /// with bit N set if it is lit on plane N
+    pub fn color_index(&self, row: usize, col: usize) -> Result<usize> {
+        let mut color = usize::from(self.get(row, col)?);
+        let index = self.index(row, col);
+        for (plane, data) in self.extra_planes.iter().enumerate() {
+            color |= usize::from(data[index]) << (plane + 1);
+        }
+        Ok(color)
+    }
+
     /// Set a value in the display
     pub fn set(&mut self, row: usize, col: usize, val: bool) -> Result<()> {
-        if row >= DISPLAY_ROWS || col >= DISPLAY_COLS {
-            bail!("Tried to set outside display bounds!")
-        }
+        self.check_bounds(row, col, "set")?;
+        let index = self.index(row, col);
         let el = self
             .data
-            .get_mut(row * ROW_STRIDE + col * COL_STRIDE)
+            .get_mut(index)
             .context("Tried to index past display bounds!")?;
         *el = val;
         Ok(())
@@ -46,24 +308,21 @@ impl Display {
 
     /// Get the element of the display at the specified row and column
     pub fn get(&self, row: usize, col: usize) -> Result<bool> {
-        if row >= DISPLAY_ROWS || col >= DISPLAY_COLS {
-            bail!("Tried to get outside display bounds!")
-        }
+        self.check_bounds(row, col, "get")?;
         return Ok(*(self
             .data
-            .get(row * ROW_STRIDE + col * COL_STRIDE)
+            .get(self.index(row, col))
             .context("Tried to index past display bounds!")?));
     }
 
     /// XOR the element at the specified row and column
     /// returns true if value was turned from set to unset
     pub fn xor(&mut self, row: usize, col: usize, val: bool) -> Result<bool> {
-        if row >= DISPLAY_ROWS || col >= DISPLAY_COLS {
-            bail!("Tried to xor outside display bounds!")
-        }
+        self.check_bounds(row, col, "xor")?;
+        let index = self.index(row, col);
         let el = self
             .data
-            .get_mut(row * ROW_STRIDE + col * COL_STRIDE)
+            .get_mut(index)
             .context("Tried to index past display bounds!")?;
         let flip = *el & val;
         *el ^= val;
@@ -71,13 +330,137 @@ impl Display {
     }
 
     /// Return an iterator over the elements of the display
-    pub fn iter_cells(&self) -> std::slice::Iter<'_, bool> {
+    pub fn iter_cells(&self) -> core::slice::Iter<'_, bool> {
         self.data.iter()
     }
 
-    /// Clear the display (set every pixel to 0)
+    /// Copy the current state of the display (row major order)
+    pub fn snapshot(&self) -> Vec<bool> {
+        self.data.to_vec()
+    }
+
+    /// Restore the display from a snapshot created by [Display::snapshot]
+    pub fn restore(&mut self, snapshot: &[bool]) -> Result<()> {
+        if snapshot.len() != self.data.len() {
+            bail!(
+                "Snapshot has {} cells, but display has {}",
+                snapshot.len(),
+                self.data.len()
+            )
+        }
+        self.data.copy_from_slice(snapshot);
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    /// Copy every plane of the display (each in row major order), for
+    /// [Display::restore_planes]
+    pub fn snapshot_planes(&self) -> Vec<Vec<bool>> {
+        core::iter::once(&self.data)
+            .chain(&self.extra_planes)
+            .map(|data| data.to_vec())
+            .collect()
+    }
+
+    /// Restore every plane of the display from a snapshot created by
+    /// [Display::snapshot_planes], and select the planes in `selected`
+    ///
+    /// Fails, leaving the display as it was, if the snapshot has a different
+    /// number of planes than the display, or `selected` names planes it doesn't have.
+    pub fn restore_planes(&mut self, planes: &[Vec<bool>], selected: u8) -> Result<()> {
+        if planes.len() != self.num_planes() {
+            bail!(
+                "Snapshot has {} planes, but display has {}",
+                planes.len(),
+                self.num_planes()
+            )
+        }
+        self.check_mask(selected)?;
+        for (plane, cells) in planes.iter().enumerate() {
+            if cells.len() != self.data.len() {
+                bail!(
+                    "Snapshot of plane {plane} has {} cells, but display has {}",
+                    cells.len(),
+                    self.data.len()
+                )
+            }
+        }
+        for (plane, cells) in planes.iter().enumerate() {
+            self.plane_data_mut(plane)?.copy_from_slice(cells);
+        }
+        self.selected_planes = selected;
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    /// The (row, column) of every cell which differs between the display and
+    /// `other`, a snapshot created by [Display::snapshot], in row major order
+    pub fn diff(&self, other: &[bool]) -> Result<Vec<(usize, usize)>> {
+        if other.len() != self.data.len() {
+            bail!(
+                "Snapshot has {} cells, but display has {}",
+                other.len(),
+                self.data.len()
+            )
+        }
+        Ok(self
+            .data
+            .iter()
+            .zip(other)
+            .enumerate()
+            .filter(|(_, (cell, other_cell))| cell != other_cell)
+            .map(|(index, _)| (index / self.cols(), index % self.cols()))
+            .collect())
+    }
+
+    /// Render the display as text, one line per row, with `#` for set pixels
+    /// and `.` for unset pixels
+    pub fn to_ascii(&self) -> String {
+        self.data
+            .chunks(self.cols())
+            .map(|row| {
+                row.iter()
+                    .map(|&cell| if cell { '#' } else { '.' })
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
+    }
+
+    /// Render the display as a binary PPM image, one image pixel per display
+    /// pixel, in `colors[0]` where no plane is set and `colors[1]` elsewhere
+    pub fn to_ppm(&self, colors: [(u8, u8, u8); 2]) -> Result<Vec<u8>> {
+        let (rows, cols) = (self.rows(), self.cols());
+        let mut image = format!("P6\n{cols} {rows}\n255\n").into_bytes();
+        for row in 0..rows {
+            for col in 0..cols {
+                let (red, green, blue) = colors[usize::from(self.color_index(row, col)? != 0)];
+                image.extend([red, green, blue]);
+            }
+        }
+        Ok(image)
+    }
+
+    /// Clear the display (set every pixel of every plane to 0)
     pub fn clear(&mut self) -> Result<()> {
         self.data.fill(false);
+        for data in &mut self.extra_planes {
+            data.fill(false);
+        }
+        Ok(())
+    }
+
+    /// Clear only the planes selected by `mask` (bit N for plane N), as XO-CHIP's
+    /// 00E0 does, leaving the others intact
+    ///
+    /// Fails if `mask` selects planes the display doesn't have.
+    pub fn clear_planes(&mut self, mask: u8) -> Result<()> {
+        self.check_mask(mask)?;
+        for plane in 0..self.num_planes() {
+            if mask & (1 << plane) != 0 {
+                self.plane_data_mut(plane)?.fill(false);
+            }
+        }
         Ok(())
     }
 }
@@ -85,6 +468,78 @@ impl Display {
 #[cfg(test)]
 mod test_display {
     use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    /// Test where a single lit pixel is drawn with each transform
+    fn test_display_transform() -> Result<()> {
+        let mut test_display = Display::new();
+        test_display.set(1, 2, true)?;
+        let before = test_display.snapshot();
+        for (transform, expected) in [
+            (DisplayTransform::None, (1, 2)),
+            (DisplayTransform::FlipH, (1, DISPLAY_COLS - 3)),
+            (DisplayTransform::FlipV, (DISPLAY_ROWS - 2, 2)),
+            (
+                DisplayTransform::Rotate180,
+                (DISPLAY_ROWS - 2, DISPLAY_COLS - 3),
+            ),
+        ] {
+            let drawn: Vec<_> = test_display
+                .iter_cells()
+                .enumerate()
+                .filter(|(_, cell)| **cell)
+                .map(|(index, _)| {
+                    transform.apply(
+                        index / DISPLAY_COLS,
+                        index % DISPLAY_COLS,
+                        DISPLAY_ROWS,
+                        DISPLAY_COLS,
+                    )
+                })
+                .collect();
+            assert_eq!(drawn, [expected], "{transform:?}");
+        }
+        // The display itself is left alone
+        assert_eq!(test_display.snapshot(), before);
+
+        // Rotating is both flips, and each is undone by applying it again
+        let (rows, cols) = (DISPLAY_ROWS, DISPLAY_COLS);
+        for row in [0, 5, rows - 1] {
+            for col in [0, 17, cols - 1] {
+                let (flip_row, flip_col) = DisplayTransform::FlipV.apply(row, col, rows, cols);
+                assert_eq!(
+                    DisplayTransform::FlipH.apply(flip_row, flip_col, rows, cols),
+                    DisplayTransform::Rotate180.apply(row, col, rows, cols)
+                );
+                for transform in DisplayTransform::ALL {
+                    let (new_row, new_col) = transform.apply(row, col, rows, cols);
+                    assert_eq!(transform.apply(new_row, new_col, rows, cols), (row, col));
+                }
+            }
+        }
+
+        // In high resolution the flips are across the larger display
+        assert_eq!(
+            DisplayTransform::Rotate180.apply(1, 2, HIGH_RES_ROWS, HIGH_RES_COLS),
+            (HIGH_RES_ROWS - 2, HIGH_RES_COLS - 3)
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test the names of the display transforms
+    fn test_display_transform_names() -> Result<()> {
+        for transform in DisplayTransform::ALL {
+            assert_eq!(DisplayTransform::from_name(transform.name())?, transform);
+        }
+        assert_eq!(
+            DisplayTransform::from_name(" Rotate_180 ")?,
+            DisplayTransform::Rotate180
+        );
+        assert!(DisplayTransform::from_name("rotate_90").is_err());
+        Ok(())
+    }
 
     #[test]
     /// Test creating a display
@@ -189,6 +644,124 @@ mod test_display {
         Ok(())
     }
 
+    #[test]
+    /// Test taking a snapshot of the display and restoring it
+    fn test_snapshot_restore() -> Result<()> {
+        let mut test_display = Display::new();
+
+        // Draw a few cells
+        test_display.set(0, 0, true)?;
+        test_display.set(5, 7, true)?;
+        test_display.set(DISPLAY_ROWS - 1, DISPLAY_COLS - 1, true)?;
+        let before: Vec<bool> = test_display.iter_cells().copied().collect();
+
+        // Snapshot, clear, then restore
+        let snapshot = test_display.snapshot();
+        test_display.clear()?;
+        assert!(test_display.iter_cells().all(|&cell| !cell));
+        test_display.restore(&snapshot)?;
+
+        let after: Vec<bool> = test_display.iter_cells().copied().collect();
+        assert_eq!(before, after);
+
+        // Snapshots of the wrong size are rejected
+        assert!(test_display.restore(&snapshot[1..]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test snapshotting and restoring every plane, and the selected planes
+    fn test_snapshot_restore_planes() -> Result<()> {
+        let mut test_display = Display::with_planes(2)?;
+        test_display.xor_plane(0, 1, 2, true)?;
+        test_display.xor_plane(1, 3, 4, true)?;
+        test_display.select_planes(0b10)?;
+        let planes = test_display.snapshot_planes();
+        assert_eq!(planes.len(), 2);
+
+        let mut restored = Display::with_planes(2)?;
+        restored.restore_planes(&planes, 0b10)?;
+        assert_eq!(restored.snapshot_planes(), planes);
+        assert_eq!(restored.selected_planes(), 0b10);
+
+        // Snapshots of another number of planes, or of the wrong size, and
+        // planes the display doesn't have are rejected
+        assert!(Display::new().restore_planes(&planes, PLANE_0).is_err());
+        assert!(restored.restore_planes(&planes[..1], PLANE_0).is_err());
+        let mut short = planes.clone();
+        short[1].pop();
+        assert!(restored.restore_planes(&short, PLANE_0).is_err());
+        assert!(restored.restore_planes(&planes, 0b100).is_err());
+        assert_eq!(restored.snapshot_planes(), planes);
+        assert_eq!(restored.selected_planes(), 0b10);
+        Ok(())
+    }
+
+    #[test]
+    /// Test finding the cells which changed between two displays
+    fn test_diff() -> Result<()> {
+        let mut before = Display::new();
+        before.set(0, 0, true)?;
+        before.set(3, 10, true)?;
+        before.set(DISPLAY_ROWS - 1, DISPLAY_COLS - 1, true)?;
+        let mut after = Display::new();
+        after.set(0, 0, true)?;
+        after.set(3, 11, true)?;
+        after.set(DISPLAY_ROWS - 1, DISPLAY_COLS - 1, true)?;
+        after.set(DISPLAY_ROWS - 1, 0, true)?;
+
+        assert_eq!(
+            after.diff(&before.snapshot())?,
+            vec![(3, 10), (3, 11), (DISPLAY_ROWS - 1, 0)]
+        );
+        // The diff is the same either way round
+        assert_eq!(
+            before.diff(&after.snapshot())?,
+            after.diff(&before.snapshot())?
+        );
+        assert!(after.diff(&after.snapshot())?.is_empty());
+
+        // Snapshots of the wrong size are rejected
+        assert!(after.diff(&before.snapshot()[1..]).is_err());
+        assert!(after.diff(&[]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test rendering the display as an image, in the given colors
+    fn test_to_ppm() -> Result<()> {
+        let mut test_display = Display::new();
+        test_display.set(0, 1, true)?;
+        let image = test_display.to_ppm([(1, 2, 3), (4, 5, 6)])?;
+        let header = b"P6\n64 32\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        let pixels = &image[header.len()..];
+        assert_eq!(pixels.len(), DISPLAY_ROWS * DISPLAY_COLS * 3);
+        assert_eq!(&pixels[..6], [1, 2, 3, 4, 5, 6]);
+        assert_eq!(&pixels[pixels.len() - 3..], [1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    /// Test rendering the display as text
+    fn test_to_ascii() -> Result<()> {
+        let mut test_display = Display::new();
+        test_display.set(0, 0, true)?;
+        test_display.set(DISPLAY_ROWS - 1, DISPLAY_COLS - 1, true)?;
+        let ascii = test_display.to_ascii();
+        let lines: Vec<&str> = ascii.lines().collect();
+
+        assert_eq!(lines.len(), DISPLAY_ROWS);
+        assert!(lines.iter().all(|line| line.len() == DISPLAY_COLS));
+        assert!(lines[0].starts_with("#."));
+        assert!(lines[DISPLAY_ROWS - 1].ends_with(".#"));
+        assert_eq!(ascii.matches('#').count(), 2);
+
+        Ok(())
+    }
+
     #[test]
     /// Test that the bound are as expected/error returned when accessing outside of them
     fn test_bounds() -> Result<()> {
@@ -203,4 +776,111 @@ mod test_display {
 
         Ok(())
     }
+
+    #[test]
+    /// Test switching between low and high resolution
+    fn test_high_res() -> Result<()> {
+        let mut test_display = Display::with_planes(2)?;
+        assert!(!test_display.is_high_res());
+        assert_eq!((test_display.rows(), test_display.cols()), (32, 64));
+        assert!(test_display.get(0, DISPLAY_COLS).is_err());
+
+        // Switching clears every plane, and keeps the selected planes
+        test_display.set(3, 7, true)?;
+        test_display.select_planes(0b11)?;
+        assert!(test_display.set_high_res(true));
+        assert!(test_display.is_high_res());
+        assert_eq!((test_display.rows(), test_display.cols()), (64, 128));
+        assert!(test_display.iter_cells().all(|cell| !cell));
+        assert_eq!(test_display.selected_planes(), 0b11);
+
+        // The whole high resolution display can be drawn on, each plane
+        test_display.set(HIGH_RES_ROWS - 1, HIGH_RES_COLS - 1, true)?;
+        test_display.xor_plane(1, 40, 100, true)?;
+        assert_eq!(test_display.color_index(40, 100)?, 0b10);
+        assert!(test_display.get(HIGH_RES_ROWS, 0).is_err());
+        let lines: Vec<String> = test_display.to_ascii().lines().map(String::from).collect();
+        assert_eq!(lines.len(), HIGH_RES_ROWS);
+        assert!(lines[HIGH_RES_ROWS - 1].ends_with(".#"));
+        // A low resolution snapshot can't be compared with it
+        assert!(test_display.diff(&Display::new().snapshot()).is_err());
+        assert_eq!(
+            test_display.diff(&vec![false; HIGH_RES_ROWS * HIGH_RES_COLS])?,
+            [(HIGH_RES_ROWS - 1, HIGH_RES_COLS - 1)]
+        );
+
+        // Asking for the resolution it already has changes nothing
+        assert!(!test_display.set_high_res(true));
+        assert!(test_display.get(HIGH_RES_ROWS - 1, HIGH_RES_COLS - 1)?);
+        assert!(test_display.set_high_res(false));
+        assert_eq!(test_display.snapshot(), Display::new().snapshot());
+        Ok(())
+    }
+
+    #[test]
+    /// Test clearing only the selected planes
+    fn test_clear_planes() -> Result<()> {
+        let mut test_display = Display::new();
+        test_display.set(3, 7, true)?;
+
+        // Selecting a missing plane is an error, leaving the display intact
+        assert!(test_display.clear_planes(0b10).is_err());
+        assert!(test_display.get(3, 7)?);
+        test_display.clear_planes(0)?;
+        assert!(test_display.get(3, 7)?);
+
+        test_display.clear_planes(PLANE_0)?;
+        assert!(!test_display.get(3, 7)?);
+
+        // With two planes, each is cleared on its own
+        let mut test_display = Display::with_planes(2)?;
+        test_display.set(3, 7, true)?;
+        test_display.xor_plane(1, 3, 7, true)?;
+        test_display.clear_planes(0b10)?;
+        assert!(test_display.get(3, 7)? && !test_display.get_plane(1, 3, 7)?);
+        test_display.xor_plane(1, 3, 7, true)?;
+        test_display.clear_planes(0b11)?;
+        assert_eq!(test_display.color_index(3, 7)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    /// Test a display with two planes, and the palette it needs
+    fn test_planes() -> Result<()> {
+        assert_eq!(Display::new().num_planes(), 1);
+        assert_eq!(Display::new().palette_len(), 2);
+        assert!(Display::with_planes(0).is_err());
+        assert!(Display::with_planes(MAX_PLANES + 1).is_err());
+        assert_eq!(Display::with_planes(MAX_PLANES)?.palette_len(), 256);
+
+        let mut test_display = Display::with_planes(2)?;
+        assert_eq!(test_display.num_planes(), 2);
+        assert_eq!(test_display.palette_len(), 4);
+        test_display.check_palette(&["000000", "FFFFFF", "FF0000", "00FF00"])?;
+        let err = test_display
+            .check_palette(&["000000", "FFFFFF"])
+            .unwrap_err();
+        assert!(err.to_string().contains("needs 4 colors"), "{err}");
+        assert!(test_display.check_palette::<&str>(&[]).is_err());
+
+        // The color index has a bit per plane
+        test_display.set(0, 0, true)?;
+        test_display.xor_plane(1, 0, 1, true)?;
+        test_display.set(0, 2, true)?;
+        test_display.xor_plane(1, 0, 2, true)?;
+        let indices: Vec<usize> = (0..4)
+            .map(|col| test_display.color_index(0, col))
+            .collect::<Result<_>>()?;
+        assert_eq!(indices, [0b01, 0b10, 0b11, 0b00]);
+        assert!(test_display.xor_plane(1, 0, 1, true)?);
+        assert!(test_display.get_plane(2, 0, 0).is_err());
+
+        // Plane masks are checked against the planes there are
+        assert_eq!(test_display.selected_planes(), PLANE_0);
+        test_display.select_planes(0b11)?;
+        assert_eq!(test_display.selected_planes(), 0b11);
+        assert!(test_display.select_planes(0b100).is_err());
+        assert_eq!(test_display.selected_planes(), 0b11);
+        Ok(())
+    }
 }
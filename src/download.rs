@@ -0,0 +1,212 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use log::{info, warn};
+
+use crate::library::data_dir;
+use crate::rom::{self, sha1_hex};
+
+/// Directory in the [data_dir] downloaded ROMs are cached in
+const DOWNLOADS_DIR: &str = "downloads";
+
+/// Longest a download may take, in seconds
+#[cfg(feature = "http")]
+const DOWNLOAD_TIMEOUT_SECONDS: u64 = 30;
+
+/// Whether `program` is an HTTP(S) URL rather than a path
+pub fn is_url(program: &str) -> bool {
+    ["http://", "https://"].iter().any(|scheme| {
+        program
+            .get(..scheme.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(scheme))
+    })
+}
+
+/// The directory downloaded ROMs are cached in
+pub fn cache_dir() -> Result<PathBuf> {
+    Ok(data_dir()?.join(DOWNLOADS_DIR))
+}
+
+/// Where the ROM downloaded from `url` is cached in `cache_dir`, named by the
+/// hash of the URL
+pub fn cache_path(cache_dir: &Path, url: &str) -> PathBuf {
+    cache_dir.join(format!("{}.ch8", sha1_hex(url.as_bytes())))
+}
+
+/// Read all of `reader`, failing as soon as it's more than `max_size` bytes
+pub fn read_limited(reader: impl Read, max_size: usize) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut bytes)
+        .context("Reading the download")?;
+    if bytes.len() > max_size {
+        bail!("The download is too large for a ROM, at most {max_size} bytes fit in memory");
+    }
+    Ok(bytes)
+}
+
+/// Whether `bytes` look like a web page rather than a ROM
+fn looks_like_html(bytes: &[u8]) -> bool {
+    let start = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    let start = start.trim_ascii_start();
+    ["<!doctype html", "<html", "<head", "<body"]
+        .iter()
+        .any(|tag| {
+            start
+                .get(..tag.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(tag.as_bytes()))
+        })
+}
+
+/// Check the download from `url` could be a ROM
+pub fn check_download(url: &str, bytes: &[u8]) -> Result<()> {
+    if bytes.is_empty() {
+        bail!("{url} is empty");
+    }
+    if looks_like_html(bytes) {
+        bail!("{url} is a web page rather than a ROM, is it a link to a download page?");
+    }
+    Ok(())
+}
+
+/// Fetch `url`, at most [rom::MAX_CHIP8_ROM_SIZE] bytes of it, following
+/// redirects
+#[cfg(feature = "http")]
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    use std::time::Duration;
+
+    use ureq::tls::{RootCerts, TlsConfig};
+
+    use crate::rom::MAX_CHIP8_ROM_SIZE;
+
+    info!("Downloading {url}");
+    // Trust the certificates the system does, like a browser would
+    let tls = TlsConfig::builder()
+        .root_certs(RootCerts::PlatformVerifier)
+        .build();
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(DOWNLOAD_TIMEOUT_SECONDS)))
+        .tls_config(tls)
+        .build()
+        .into();
+    // Error statuses are errors too, so a missing ROM isn't read as its 404 page
+    let mut response = agent
+        .get(url)
+        .call()
+        .with_context(|| format!("Failed to download {url}"))?;
+    read_limited(response.body_mut().as_reader(), MAX_CHIP8_ROM_SIZE)
+        .with_context(|| format!("Failed to download {url}"))
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    bail!("Can't download {url}, emul8rs was built without the http feature")
+}
+
+/// Download the ROM at `url`, or use the copy cached in `cache_dir` (if given)
+/// from an earlier download
+pub fn download_rom(url: &str, cache_dir: Option<&Path>) -> Result<Vec<u8>> {
+    let cached = cache_dir.map(|cache_dir| cache_path(cache_dir, url));
+    if let Some(cached) = &cached
+        && let Ok(bytes) = std::fs::read(cached)
+    {
+        info!("Using the copy of {url} downloaded before, {cached:?}");
+        return Ok(bytes);
+    }
+    let bytes = fetch(url)?;
+    check_download(url, &bytes)?;
+    if let Some(cached) = &cached {
+        let saved = cached
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(cached, &bytes));
+        if let Err(err) = saved {
+            warn!("Failed to cache the download of {url} in {cached:?}: {err}");
+        }
+    }
+    Ok(bytes)
+}
+
+/// Read the program given on the command line, downloading it if it is a URL
+/// (cached in the [cache_dir] if `use_cache`), otherwise reading the file
+/// (see [rom::open])
+pub fn read_program(program: &Path, use_cache: bool) -> Result<Vec<u8>> {
+    match program.to_str() {
+        Some(url) if is_url(url) => {
+            let cache_dir = if use_cache { Some(cache_dir()?) } else { None };
+            download_rom(url, cache_dir.as_deref())
+        }
+        _ => rom::open(program),
+    }
+}
+
+#[cfg(test)]
+mod test_download {
+    use super::*;
+    use crate::rom::MAX_CHIP8_ROM_SIZE;
+    use std::io::Cursor;
+
+    #[test]
+    /// Test telling URLs from paths
+    fn test_is_url() {
+        assert!(is_url("https://example.com/pong.ch8"));
+        assert!(is_url("HTTP://example.com/pong.ch8"));
+        assert!(!is_url("pong.ch8"));
+        assert!(!is_url("roms/https://pong.ch8"));
+        assert!(!is_url("ftp://example.com/pong.ch8"));
+        assert!(!is_url("http"));
+        assert!(!is_url(""));
+    }
+
+    #[test]
+    /// Test downloads are limited to what fits in memory
+    fn test_read_limited() -> Result<()> {
+        let rom = vec![0x12; MAX_CHIP8_ROM_SIZE];
+        assert_eq!(read_limited(Cursor::new(&rom), MAX_CHIP8_ROM_SIZE)?, rom);
+        let too_large = vec![0x12; MAX_CHIP8_ROM_SIZE + 1];
+        let err = read_limited(Cursor::new(&too_large), MAX_CHIP8_ROM_SIZE).unwrap_err();
+        assert!(err.to_string().contains("too large"), "{err}");
+        // Only a little more than the limit is read
+        let mut endless = std::io::repeat(0x12);
+        assert!(read_limited(&mut endless, 16).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test web pages are told apart from ROMs
+    fn test_check_download() {
+        let url = "https://example.com/pong.ch8";
+        assert!(check_download(url, &[0x6A, 0x02, 0x12, 0x00]).is_ok());
+        assert!(check_download(url, &[]).is_err());
+        for page in [
+            "<!DOCTYPE html><html><body>Download</body></html>",
+            "\n  <html lang=\"en\">",
+            "\u{FEFF}<!doctype HTML>",
+        ] {
+            let err = check_download(url, page.as_bytes()).unwrap_err();
+            assert!(err.to_string().contains("web page"), "{page:?}: {err}");
+        }
+        // A ROM can start with the byte for < (a skip instruction)
+        assert!(check_download(url, b"<\x01\x12\x00").is_ok());
+    }
+
+    #[test]
+    /// Test cached downloads are used rather than downloading again
+    fn test_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let url = "https://example.com/pong.ch8";
+        let cached = cache_path(dir.path(), url);
+        assert_eq!(cached, cache_path(dir.path(), url));
+        assert_ne!(
+            cached,
+            cache_path(dir.path(), "https://example.com/tetris.ch8")
+        );
+        assert_eq!(cached.parent(), Some(dir.path()));
+
+        std::fs::write(&cached, [0x12, 0x00])?;
+        assert_eq!(download_rom(url, Some(dir.path()))?, [0x12, 0x00]);
+        Ok(())
+    }
+}
@@ -1,37 +1,103 @@
 // Std uses
-use std::path::Path;
-use std::sync::{Arc, Mutex, mpsc};
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "std")]
+use std::sync::mpsc;
+#[cfg(feature = "std")]
 use std::thread;
-use std::time::{Duration, Instant};
+#[cfg(feature = "std")]
+use std::time::{Instant, SystemTime};
 
 // External uses
-use anyhow::{Context, Result, bail};
-use log::{debug, trace, warn};
-use rand::{self, RngCore};
+use anyhow::{Context, Result, anyhow, bail};
+use log::{debug, error, info, trace, warn};
+use rand::rngs::StdRng;
+use rand::{self, RngCore, SeedableRng};
 
 // Crate uses
+use crate::cheats::{Cheat, Cheats, parse_cheats};
+#[cfg(feature = "std")]
+use crate::color::parse_color;
+use crate::compat::{CompatReport, CompatTracker};
 use crate::config;
-use crate::display::{DISPLAY_COLS, DISPLAY_ROWS, Display};
-use crate::frontend::Frontend;
+use crate::disasm::OpcodeKind;
+use crate::display::Display;
+use crate::error_screen::{ERROR_SCREEN_COLUMNS, error_screen_text};
+use crate::frontend::{Frontend, FrontendCapabilities};
+#[cfg(feature = "std")]
+use crate::hotkeys::HotkeyAction;
+use crate::keymap::{KEYPAD_SIZE, Keymap, parse_keypad_key};
+#[cfg(feature = "std")]
+use crate::memory_layout::MemoryLayout;
+use crate::memory_layout::MemoryRegion;
+#[cfg(feature = "std")]
+use crate::menu::{MenuItem, PauseMenu};
+use crate::pause::PauseState;
+#[cfg(feature = "std")]
+use crate::perf::PerfCounter;
+use crate::poke::Poke;
+#[cfg(feature = "std")]
+use crate::remote_control::RemoteControl;
+use crate::rom::{self, PROGRAM_START};
+#[cfg(feature = "std")]
+use crate::rom_database::RomDatabase;
+#[cfg(feature = "std")]
+use crate::save_state::{
+    Autosaver, SAVE_STATE_VERSION, SaveState, emergency_save_path, latest_autosave, unix_millis,
+};
+#[cfg(feature = "std")]
+use crate::sound::{BeepChange, BeepState};
+#[cfg(feature = "std")]
+use crate::splash;
+#[cfg(feature = "std")]
+use crate::stats::HaltReason;
+use crate::stats::RunStats;
+#[cfg(feature = "std")]
+use crate::timing::TickCounter;
+use crate::timing::{CycleCosts, count_down};
+#[cfg(feature = "std")]
+use crate::watch::{self, RomWatcher};
 
 // Emulator constants
-const MAX_STACK_SIZE: usize = 128;
-const MEMORY_SIZE: usize = 4096;
-const NUM_REGISTERS: usize = 16;
+/// Number of stack entries to include when reporting a stack overflow
+const STACK_TRACE_REPORT_LENGTH: usize = 8;
+/// Bytes of memory, including the interpreter area below [PROGRAM_START]
+pub const MEMORY_SIZE: usize = 4096;
+/// Number of general purpose registers (V0 to VF)
+pub const NUM_REGISTERS: usize = 16;
+#[cfg(feature = "std")]
 const MILLIS_PER_SECOND: u64 = 1_000;
+#[cfg(feature = "std")]
 const MICROS_PER_SECOND: u64 = 1_000_000;
 const TIMER_HZ: u64 = 60;
-const GAME_MEMORY_START: usize = 0x200;
+/// Time between ticks of the delay and sound timers
+pub const TIMER_TICK: Duration = Duration::from_nanos(1_000_000_000 / TIMER_HZ);
+const GAME_MEMORY_START: usize = PROGRAM_START;
 const INSTRUCTION_LENGTH: usize = 2;
+#[cfg(feature = "std")]
+const FPS_LOG_INTERVAL: Duration = Duration::from_secs(5);
 
 // Sprite constants
 const SPRITE_WIDTH: usize = 8;
+/// Width and height of the large sprites DXY0 draws in high resolution
+const LARGE_SPRITE_SIZE: usize = 16;
 
 // Font
-const FONT_START_POSITION: usize = 0x50;
+pub(crate) const FONT_START_POSITION: usize = 0x50;
 const FONT_HEIGHT: usize = 5;
 const FONT_CHAR_COUNT: usize = 16;
-const FONT: [u8; FONT_HEIGHT * FONT_CHAR_COUNT] = [
+pub(crate) const FONT: [u8; FONT_HEIGHT * FONT_CHAR_COUNT] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -52,45 +118,417 @@ const FONT: [u8; FONT_HEIGHT * FONT_CHAR_COUNT] = [
 
 //NOTE: For the memory, the programs will be loaded starting at address 512
 
+/// A register which can be traced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// One of the general purpose registers V0-VF
+    V(u8),
+    /// The index register
+    I,
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(register) => write!(f, "V{register:X}"),
+            Register::I => write!(f, "I"),
+        }
+    }
+}
+
+/// A change in the value of a register, recorded when `trace_registers` is enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    /// The register which changed
+    pub register: Register,
+    /// Value before the change
+    pub old: u16,
+    /// Value after the change
+    pub new: u16,
+    /// Address of the instruction which made the change
+    pub pc: u16,
+}
+
+impl fmt::Display for RegisterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PC {:#05x}: {} {:#x} -> {:#x}",
+            self.pc, self.register, self.old, self.new
+        )
+    }
+}
+
+/// The quirks whose hits can be logged (see [QuirkHit]), named after their
+/// config settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    ShiftUseVy,
+    JumpOffsetUseV0,
+    StoreMemoryUpdateIndex,
+    WrapX,
+    WrapY,
+}
+
+impl Quirk {
+    /// All of the quirks
+    pub const ALL: [Quirk; 5] = [
+        Quirk::ShiftUseVy,
+        Quirk::JumpOffsetUseV0,
+        Quirk::StoreMemoryUpdateIndex,
+        Quirk::WrapX,
+        Quirk::WrapY,
+    ];
+
+    /// Name of the config setting for the quirk
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quirk::ShiftUseVy => "shift_use_vy",
+            Quirk::JumpOffsetUseV0 => "jump_offset_use_v0",
+            Quirk::StoreMemoryUpdateIndex => "store_memory_update_index",
+            Quirk::WrapX => "wrap_x",
+            Quirk::WrapY => "wrap_y",
+        }
+    }
+
+    /// Whether the quirk is enabled in `config`
+    pub fn is_enabled(&self, config: &config::EmulatorConfig) -> bool {
+        match self {
+            Quirk::ShiftUseVy => config.shift_use_vy,
+            Quirk::JumpOffsetUseV0 => config.jump_offset_use_v0,
+            Quirk::StoreMemoryUpdateIndex => config.store_memory_update_index,
+            Quirk::WrapX => config.wrap_x,
+            Quirk::WrapY => config.wrap_y,
+        }
+    }
+
+    /// Turn the quirk on or off in `config`
+    pub fn set_enabled(&self, config: &mut config::EmulatorConfig, enabled: bool) {
+        let setting = match self {
+            Quirk::ShiftUseVy => &mut config.shift_use_vy,
+            Quirk::JumpOffsetUseV0 => &mut config.jump_offset_use_v0,
+            Quirk::StoreMemoryUpdateIndex => &mut config.store_memory_update_index,
+            Quirk::WrapX => &mut config.wrap_x,
+            Quirk::WrapY => &mut config.wrap_y,
+        };
+        *setting = enabled;
+    }
+}
+
+/// How a [MemoryAccess] used memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// Fetched as an instruction
+    Execute,
+    /// Read as a row of a sprite (DXYN)
+    Sprite,
+    /// Read into a register (FX65)
+    Load,
+    /// Written by the program (FX33 and FX55)
+    Store,
+}
+
+/// A byte of memory used by the program, sent to the sink set with
+/// [Emulator::set_access_sink]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: usize,
+    pub kind: AccessKind,
+}
+
+/// An instruction whose result depended on a quirk, recorded when
+/// `log_quirk_hits` is enabled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkHit {
+    pub quirk: Quirk,
+    /// Whether the quirk was enabled
+    pub enabled: bool,
+    /// What the instruction did
+    pub result: String,
+    /// What it would have done with the quirk the other way
+    pub alternative: String,
+    /// Address of the instruction
+    pub pc: u16,
+}
+
+impl fmt::Display for QuirkHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PC {:#05x}: {} = {} gave {} ({} would give {})",
+            self.pc,
+            self.quirk.name(),
+            self.enabled,
+            self.result,
+            !self.enabled,
+            self.alternative
+        )
+    }
+}
+
+/// What to do when an instruction fails (e.g. an unknown opcode, an out of
+/// range memory access, or returning with an empty stack)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    /// Stop the emulator, returning the error
+    Abort,
+    /// Log the error and move on to the next instruction
+    SkipInstruction,
+    /// Log the error and halt, as if the program had exited
+    Halt,
+}
+
+impl OnError {
+    /// All of the error handling modes
+    pub const ALL: [OnError; 3] = [OnError::Abort, OnError::SkipInstruction, OnError::Halt];
+
+    /// Name of the mode as used in the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            OnError::Abort => "abort",
+            OnError::SkipInstruction => "skip_instruction",
+            OnError::Halt => "halt",
+        }
+    }
+
+    /// Find the mode with the given name (case-insensitive)
+    pub fn from_name(name: &str) -> Result<Self> {
+        let lower = name.trim().to_lowercase();
+        match Self::ALL.iter().find(|on_error| on_error.name() == lower) {
+            Some(on_error) => Ok(*on_error),
+            None => bail!(
+                "Unknown error handling mode {name:?}, available modes are: {}",
+                Self::ALL.map(|on_error| on_error.name()).join(", ")
+            ),
+        }
+    }
+}
+
+/// Check that `rom` fits in the memory after 0x200
+fn check_rom_fits(rom: &[u8]) -> Result<()> {
+    let max_size = MEMORY_SIZE - GAME_MEMORY_START;
+    if rom.len() > max_size {
+        bail!(
+            "ROM is too large, it is {} bytes but at most {max_size} bytes fit in memory",
+            rom.len()
+        );
+    }
+    Ok(())
+}
+
+/// Limits on how long [Emulator::run_for] runs, unlimited by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunLimits {
+    /// Stop after executing this many instructions
+    pub max_cycles: Option<u64>,
+    /// Stop after running for this long
+    pub max_duration: Option<Duration>,
+    /// Stop as soon as a keypad key is pressed, before the program sees it (for
+    /// attract mode, where a key takes over the game)
+    pub stop_on_key: bool,
+}
+
+impl RunLimits {
+    /// Whether any limit is set
+    pub fn is_limited(&self) -> bool {
+        self.max_cycles.is_some() || self.max_duration.is_some()
+    }
+}
+
+/// Why [Emulator::run_for] stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The frontend asked to stop (e.g. the window was closed)
+    Stopped,
+    /// The program exited (00FD), or halted on an error (see [OnError])
+    Halted,
+    /// One of the [RunLimits] was reached
+    LimitReached,
+    /// A keypad key was pressed, with [RunLimits::stop_on_key]
+    KeyPressed,
+    /// The watchdog (the `max_runtime_seconds` config) stopped the run
+    TimedOut,
+}
+
+/// Error returned when the watchdog stops a run, if `timeout_is_error` is set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout {
+    /// The maximum run time which was exceeded
+    pub max_runtime: Duration,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Timeout: run took longer than the maximum run time of {:?}",
+            self.max_runtime
+        )
+    }
+}
+
+impl core::error::Error for Timeout {}
+
+/// Context marking an error which can't be recovered from by skipping the
+/// instruction, whatever the [OnError] mode (e.g. a frontend failure)
+#[derive(Debug)]
+struct Unrecoverable(&'static str);
+
+impl fmt::Display for Unrecoverable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Context marking an error from the frontend (e.g. a lost GPU context or an
+/// unplugged audio device), which is unrecoverable, and saves the game before
+/// stopping so it can be resumed (see [Emulator::enable_emergency_save])
+#[derive(Debug)]
+struct FrontendFailure(&'static str);
+
+impl fmt::Display for FrontendFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Whether `err` can't be recovered from by skipping the instruction (see
+/// [Unrecoverable] and [FrontendFailure])
+fn is_unrecoverable(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<Unrecoverable>().is_some() || err.downcast_ref::<FrontendFailure>().is_some()
+}
+
 /// Chip8 Emulator
 pub struct Emulator<'a> {
     /// Memory including program memory and ram
     memory: [u8; MEMORY_SIZE],
     /// Representation of the display (actual drawing handled in [crate::artist])
     display: Display,
+    /// The last complete frame, drawn instead of the display when
+    /// [EmulatorConfig::double_buffer] is on (None when it's off)
+    presented: Option<Display>,
     /// Pointer to current instruction (indexes memory)
     program_counter: usize,
     /// Index register (indexes memory)
     index_register: u16,
-    /// Stack used to call subroutines/functions and return from them
-    stack: [u16; MAX_STACK_SIZE],
-    /// Current top of the stack (indexes stack)
-    stack_top: usize,
+    /// Stack used to call subroutines/functions and return from them, holding
+    /// at most [EmulatorConfig::stack_depth] entries
+    stack: Vec<u16>,
     /// Timer decremented at 60Hz until it reaches 0
-    delay_timer: Arc<Mutex<u8>>,
+    delay_timer: Arc<AtomicU8>,
     /// Timer decremented at 60Hz until it reaches 0,
     /// gives off beeping sound while not 0
-    sound_timer: Arc<Mutex<u8>>,
+    sound_timer: Arc<AtomicU8>,
     /// General purpose registers (V0-VF)
     registers: [u8; NUM_REGISTERS],
+    /// Time passed towards the next timer tick, see [Emulator::tick_timers]
+    timer_remainder: Duration,
+    /// Whether the ticker thread should leave the timers alone (while paused)
+    timers_paused: Arc<AtomicBool>,
+    /// Whether (and why) the run loop is paused, executing no instructions
+    pause: PauseState,
+    /// Whether the beep is muted
+    muted: bool,
+    /// Whether the FPS / IPS counter is shown
+    #[cfg(feature = "std")]
+    show_perf: bool,
+    /// Measures the rates for the FPS / IPS counter
+    #[cfg(feature = "std")]
+    perf: PerfCounter,
+    /// How long to show the splash for, until it has been shown on the first run
+    #[cfg(feature = "std")]
+    splash: Option<Duration>,
+    /// State of the pause menu
+    #[cfg(feature = "std")]
+    menu: PauseMenu,
+    /// The most recently loaded ROM, for resetting
+    rom: Vec<u8>,
+    /// Other files loaded alongside the ROM (see [MemoryLayout]), for resetting
+    regions: Vec<MemoryRegion>,
+    /// Address execution starts at
+    start_address: usize,
+    /// Values set before the program starts (see [MemoryLayout]), for resetting
+    pokes: Vec<Poke>,
+    /// Cheats written into memory at the end of each frame
+    cheats: Cheats,
+    /// Database of ROM titles from the config, None to use the embedded one
+    #[cfg(feature = "std")]
+    rom_database: Option<RomDatabase>,
     /// Handle of thread used for ticking the delay timers
+    #[cfg(feature = "std")]
     ticker_handle: Option<thread::JoinHandle<()>>,
     /// Channel to the ticker thread
+    #[cfg(feature = "std")]
     ticker_channel: Option<mpsc::Sender<()>>,
     /// Handle for performing Raylib operations
     frontend: Box<dyn Frontend + 'a>,
+    /// What the frontend supports, queried once when the emulator is created
+    capabilities: FrontendCapabilities,
     /// Configuration object
     config: config::EmulatorConfig,
     /// Random number generator
-    rng: rand::prelude::ThreadRng,
-    /// Whether the emulator is currently playing sound
-    playing_sound: bool,
-    /// The length of time each instruction loop should take
+    rng: StdRng,
+    /// Whether the emulator is currently playing sound, held for a minimum duration
+    #[cfg(feature = "std")]
+    beep: BeepState,
+    /// The length of time each instruction loop should take (for an instruction
+    /// costing 1 with `cycle_accurate`)
+    #[cfg(feature = "std")]
     step_duration: Duration,
+    /// Relative cost of each kind of instruction, None to treat them all the same
+    cycle_costs: Option<CycleCosts>,
+    /// Cost of the most recently executed instruction
+    last_cost: u64,
     /// Whether the emulator is waiting for
     waiting_for_key_release: Option<u8>,
+    /// Number of pixels turned off by the most recent sprite draw
+    last_collision_count: u32,
+    /// Number of sprite draws which collided (set VF) this frame
+    collisions_this_frame: u32,
+    /// Collects the compatibility report, when enabled
+    compat: Option<CompatTracker>,
+    /// Whether the program has exited (00FD), once halted no more instructions run
+    halted: bool,
+    /// Address of the instruction currently being executed
+    instruction_address: usize,
+    /// Number of instructions executed
+    cycles: u64,
+    /// What to do when an instruction fails
+    on_error: OnError,
+    /// Keys down at the start of the frame (bit N for key N), when polling the
+    /// keys once per frame
+    frame_keys: Option<u16>,
+    /// Longest a run may take before the watchdog stops it
+    #[cfg(feature = "std")]
+    max_runtime: Option<Duration>,
+    /// Statistics collected while running
+    stats: RunStats,
+    /// Receives register changes when `trace_registers` is enabled
+    register_sink: Option<Box<dyn FnMut(RegisterChange) + 'a>>,
+    /// Receives quirk hits when `log_quirk_hits` is enabled
+    quirk_sink: Option<Box<dyn FnMut(QuirkHit) + 'a>>,
+    /// Where every memory access is sent, if anywhere (see [Emulator::set_access_sink])
+    access_sink: Option<Box<dyn FnMut(MemoryAccess) + 'a>>,
+    /// Watches the ROM file, to reload it when it changes
+    #[cfg(feature = "std")]
+    rom_watcher: Option<RomWatcher>,
+    /// Addresses [Emulator::step_n] stops at before executing
+    breakpoints: BTreeSet<usize>,
+    /// Writes save states while running, when autosaving is enabled
+    #[cfg(feature = "std")]
+    autosaver: Option<Autosaver>,
+    /// Where the game is saved if the frontend fails, when enabled
+    #[cfg(feature = "std")]
+    emergency_save_dir: Option<PathBuf>,
+    /// Where commands from remote control clients come from, if enabled
+    #[cfg(feature = "std")]
+    remote_control: Option<RemoteControl>,
+    /// Keypad keys held down by remote control (bit N for key N), on top of
+    /// the frontend's
+    held_keys: u16,
 }
 
+#[cfg(feature = "std")]
 impl<'a> Drop for Emulator<'a> {
     /// Drop the emulator (just stops the counter thread)
     fn drop(&mut self) {
@@ -109,56 +547,72 @@ impl<'a> Drop for Emulator<'a> {
 impl<'a> Emulator<'a> {
     /// Create a new Emulator with zeroed fields
     pub fn new(frontend: Box<dyn Frontend + 'a>, config: config::EmulatorConfig) -> Result<Self> {
+        // Make sure the configuration is usable
+        debug!("Validating configuration");
+        if let Err(errors) = config.validate() {
+            bail!(
+                "Invalid configuration:\n{}",
+                errors
+                    .iter()
+                    .map(|err| format!("  {err}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
+        let on_error = OnError::from_name(&config.on_error)?;
+        #[cfg(feature = "std")]
+        let max_runtime = config.max_runtime();
+
         // Create the sound and delay timers
         debug!("Creating timers");
-        let delay_timer = Arc::new(Mutex::new(0u8));
-        let sound_timer = Arc::new(Mutex::new(0u8));
-
-        // Create the ticker which will decrement the delay and sound timer
-        // Create the channel for sending th stop command
-        debug!("Creating channel for stopping the timer");
-        let (sender, receiver) = mpsc::channel();
-
-        // Clone the delay and sound timer references to move them into the other thread
-        debug!("Starting timer thread");
-        let tickers_delay_timer_ref = delay_timer.clone();
-        let tickers_sound_timer_ref = sound_timer.clone();
-        let ticker_handle = thread::spawn(move || {
-            // Create an Instant reference which will track when the ticker needs to fire
-            let mut ticker = Instant::now();
-            // Also track the previous tick so that the thread can sleep till it needs to fire again
-            let mut previous_tick = Instant::now();
-            // Find the period (based on the desired hertz) for ticking
-            let period = Duration::from_millis(MILLIS_PER_SECOND / TIMER_HZ);
-
-            loop {
-                // Check if the thread has received a message (all messages are stops)
-                match receiver.try_recv() {
-                    Ok(_) => return, // Stop signal received
-                    Err(mpsc::TryRecvError::Empty) => {
-                        // No message received, fire the ticker
-                        if ticker.elapsed() >= period {
-                            // Decrement the timers
-                            {
-                                let mut delay_timer = tickers_delay_timer_ref.lock().unwrap();
-                                *delay_timer = (*delay_timer).saturating_sub(1);
-                            }
-                            {
-                                let mut sound_timer = tickers_sound_timer_ref.lock().unwrap();
-                                *sound_timer = (*sound_timer).saturating_sub(1);
+        let delay_timer = Arc::new(AtomicU8::new(0));
+        let sound_timer = Arc::new(AtomicU8::new(0));
+
+        let timers_paused = Arc::new(AtomicBool::new(false));
+        // Without std there are no threads, so the timers are always
+        // decremented each frame (see [Emulator::start_frame])
+        #[cfg(feature = "std")]
+        let (ticker_handle, ticker_channel) = if config.synchronous_timers {
+            debug!("Using synchronous timers, decremented by the main loop each frame");
+            (None, None)
+        } else {
+            // Create the ticker which will decrement the delay and sound timer
+            // Create the channel for sending th stop command
+            debug!("Creating channel for stopping the timer");
+            let (sender, receiver) = mpsc::channel();
+
+            // Clone the delay and sound timer references to move them into the other thread
+            debug!("Starting timer thread");
+            let tickers_delay_timer_ref = delay_timer.clone();
+            let tickers_sound_timer_ref = sound_timer.clone();
+            let tickers_timers_paused_ref = timers_paused.clone();
+            let ticker_handle = thread::spawn(move || {
+                // Counts every tick due, including any missed while the thread
+                // wasn't running
+                let mut ticker = TickCounter::new(TIMER_TICK, Instant::now());
+
+                loop {
+                    // Check if the thread has received a message (all messages are stops)
+                    match receiver.try_recv() {
+                        Ok(_) => return, // Stop signal received
+                        Err(mpsc::TryRecvError::Empty) => {
+                            // No message received, decrement the timers for every
+                            // tick due, unless paused
+                            let ticks = ticker.ticks(Instant::now());
+                            if ticks > 0 && !tickers_timers_paused_ref.load(Ordering::Relaxed) {
+                                count_down(&tickers_delay_timer_ref, ticks);
+                                count_down(&tickers_sound_timer_ref, ticks);
                             }
-                            // Track the previous time (for sleeping the thread)
-                            previous_tick = ticker;
-                            // Set the current to the current timer
-                            ticker = Instant::now();
                         }
+                        Err(_) => return, // Channel has been disconnected
                     }
-                    Err(_) => return, // Channel has been disconnected
+                    // Sleep until the next time tick is needed
+                    thread::sleep(ticker.next_tick().saturating_duration_since(Instant::now()));
                 }
-                // Sleep until the next time tick is needed
-                thread::sleep((previous_tick + period) - ticker);
-            }
-        });
+            });
+            (Some(ticker_handle), Some(sender))
+        };
 
         // Create the empty memory, initialized to 0
         debug!("Initializing memory");
@@ -166,14 +620,26 @@ impl<'a> Emulator<'a> {
 
         // Create the empty display
         debug!("Creating emulator internal display");
-        let display = Display::new();
+        let display = Display::with_planes(config.num_planes)?;
 
         // Create the RNG to use for randomness
         debug!("Creating the RNG");
-        let rng = rand::rng();
+        #[cfg(feature = "std")]
+        let rng = StdRng::from_rng(&mut rand::rng());
+        // There is no OS to seed it from without std, the host can seed it with
+        // [Emulator::seed_rng] (e.g. from a hardware RNG)
+        #[cfg(not(feature = "std"))]
+        let rng = StdRng::seed_from_u64(0);
+
+        let cycle_costs = CycleCosts::from_config(&config)?;
+        #[cfg(feature = "std")]
+        let rom_database = RomDatabase::from_config(&config)?;
 
         // Determine how long the execution steps should take
-        let step_duration = Duration::from_micros(MICROS_PER_SECOND / 700);
+        #[cfg(feature = "std")]
+        let step_duration =
+            Duration::from_micros(MICROS_PER_SECOND / config.instructions_per_second);
+        #[cfg(feature = "std")]
         debug!(
             "Determined step duration to be {:?} microseconds",
             step_duration
@@ -182,246 +648,1527 @@ impl<'a> Emulator<'a> {
         debug!("Creating emulator object");
         let mut emulator = Self {
             memory,
+            presented: config.double_buffer.then(|| display.clone()),
             display,
             program_counter: GAME_MEMORY_START,
             index_register: 0,
-            stack: [0u16; MAX_STACK_SIZE],
-            stack_top: 0,
+            stack: Vec::with_capacity(config.stack_depth),
             registers: [0u8; NUM_REGISTERS],
             delay_timer,
             sound_timer,
-            ticker_handle: Some(ticker_handle),
-            ticker_channel: Some(sender),
+            timer_remainder: Duration::ZERO,
+            timers_paused,
+            pause: PauseState::new(),
+            #[cfg(feature = "std")]
+            menu: PauseMenu::new(),
+            muted: config.audio_muted,
+            #[cfg(feature = "std")]
+            show_perf: config.show_fps,
+            #[cfg(feature = "std")]
+            perf: PerfCounter::new(),
+            #[cfg(feature = "std")]
+            splash: config.splash_duration(),
+            rom: Vec::new(),
+            regions: Vec::new(),
+            start_address: GAME_MEMORY_START,
+            pokes: Vec::new(),
+            cheats: Cheats::new(parse_cheats(&config.cheats)?),
+            #[cfg(feature = "std")]
+            rom_database,
+            #[cfg(feature = "std")]
+            ticker_handle,
+            #[cfg(feature = "std")]
+            ticker_channel,
+            capabilities: frontend.capabilities(),
             frontend,
+            #[cfg(feature = "std")]
+            beep: BeepState::from_config(&config),
             config,
-            playing_sound: false,
             rng,
+            #[cfg(feature = "std")]
             step_duration,
+            cycle_costs,
+            last_cost: 1,
             waiting_for_key_release: None,
+            last_collision_count: 0,
+            collisions_this_frame: 0,
+            compat: None,
+            halted: false,
+            instruction_address: GAME_MEMORY_START,
+            cycles: 0,
+            #[cfg(feature = "std")]
+            max_runtime,
+            stats: RunStats::default(),
+            on_error,
+            frame_keys: None,
+            register_sink: None,
+            quirk_sink: None,
+            access_sink: None,
+            #[cfg(feature = "std")]
+            rom_watcher: None,
+            breakpoints: BTreeSet::new(),
+            #[cfg(feature = "std")]
+            autosaver: None,
+            #[cfg(feature = "std")]
+            emergency_save_dir: None,
+            #[cfg(feature = "std")]
+            remote_control: None,
+            held_keys: 0,
         };
         debug!("Loading font into emulator");
         emulator.load_font().context("Trying to load font")?;
+        emulator.show_cheats();
         Ok(emulator)
     }
 
-    /// Run the emulator
+    /// Run the emulator, until the frontend asks to stop or the program exits
+    #[cfg(feature = "std")]
     pub fn run(&mut self) -> Result<()> {
+        self.run_for(RunLimits::default())?;
+        Ok(())
+    }
+
+    /// Run the emulator until the frontend asks to stop, the program exits, one
+    /// of the `limits` is reached, or the run takes longer than the configured
+    /// `max_runtime_seconds`
+    ///
+    /// The time spent and the reason for stopping are recorded in the [Emulator::stats].
+    #[cfg(feature = "std")]
+    pub fn run_for(&mut self, limits: RunLimits) -> Result<RunOutcome> {
+        let run_start = Instant::now();
+        let result = self
+            .run_loop(limits)
+            .map_err(|err| self.save_after_frontend_failure(err));
+        self.stats.wall_time_seconds += run_start.elapsed().as_secs_f64();
+        match &result {
+            Ok(outcome) => self.stats.halt_reason = Some((*outcome).into()),
+            Err(err) => {
+                self.stats.halt_reason = Some(HaltReason::Error);
+                self.stats.error = Some(format!("{err:#}"));
+            }
+        }
+        result
+    }
+
+    /// If the run stopped because the frontend failed with `err`, stop the beep
+    /// and save the game (when enabled, see [Emulator::enable_emergency_save]),
+    /// returning `err` with where it was saved
+    #[cfg(feature = "std")]
+    fn save_after_frontend_failure(&mut self, err: anyhow::Error) -> anyhow::Error {
+        if err.downcast_ref::<FrontendFailure>().is_none() {
+            return err;
+        }
+        error!("The frontend failed: {err:?}");
+        // The sound may be what failed, so this is only worth a try
+        if let Err(sound_err) = self.frontend.stop_sound() {
+            warn!("Failed to stop the beep: {sound_err:#}");
+        }
+        let Some(dir) = &self.emergency_save_dir else {
+            return err;
+        };
+        let state = self.save_state();
+        let path = emergency_save_path(dir, &state.rom_sha1);
+        match state.write(&path) {
+            Ok(()) => {
+                info!("Saved the game to {path:?} after the frontend failed");
+                err.context(format!(
+                    "The game was saved to {}, carry on from it with --resume",
+                    path.display()
+                ))
+            }
+            Err(save_err) => {
+                error!("Failed to save the game after the frontend failed: {save_err:#}");
+                err
+            }
+        }
+    }
+
+    /// The main emulation loop of [Emulator::run_for]
+    #[cfg(feature = "std")]
+    fn run_loop(&mut self, limits: RunLimits) -> Result<RunOutcome> {
         debug!("Starting main emulation loop");
-        while !self.frontend.should_stop() {
+        let run_start = Instant::now();
+        let start_cycles = self.cycles;
+        // Time spent paused, which the watchdog doesn't count
+        let mut paused_for = Duration::ZERO;
+        let mut last_fps_log = run_start;
+        // Cost of the instructions run so far this frame
+        let mut frame_cost = 0;
+        if let Some(duration) = self.splash.take()
+            && !self.show_splash(duration)?
+        {
+            return Ok(RunOutcome::Stopped);
+        }
+        loop {
+            let iteration_start = Instant::now();
+            if self.halted && !self.menu.is_open() {
+                // A bounded run has somewhere to go next, rather than the menu
+                if !self.frontend.has_menu() || limits.is_limited() || limits.stop_on_key {
+                    info!("Program exited, stopping the emulator");
+                    return Ok(RunOutcome::Halted);
+                }
+                // Let the player reset, load another ROM, or quit
+                info!("Program exited, opening the menu");
+                self.menu.open();
+                self.set_paused(true)?;
+            }
+            if self.frontend.should_stop() {
+                return Ok(RunOutcome::Stopped);
+            }
+            if self.config.pause_on_focus_loss && self.capabilities.reports_focus {
+                let focused = self.frontend.is_focused();
+                self.set_focused(focused)?;
+            }
+            if let Some(outcome) = self.handle_controls()? {
+                return Ok(outcome);
+            }
+            self.reload_watched_rom()?;
+            let cycles_reached = limits
+                .max_cycles
+                .is_some_and(|max_cycles| self.cycles - start_cycles >= max_cycles);
+            let duration_reached = limits
+                .max_duration
+                .is_some_and(|max_duration| run_start.elapsed() >= max_duration);
+            if let Some(max_runtime) = self.max_runtime
+                && run_start.elapsed().saturating_sub(paused_for) >= max_runtime
+            {
+                if self.config.timeout_is_error {
+                    return Err(Timeout { max_runtime }.into());
+                }
+                warn!("Run took longer than {max_runtime:?}, stopping the emulator");
+                return Ok(RunOutcome::TimedOut);
+            }
+            if cycles_reached || duration_reached {
+                info!(
+                    "Run limit reached after {} instructions, stopping the emulator",
+                    self.cycles - start_cycles
+                );
+                return Ok(RunOutcome::LimitReached);
+            }
+            if limits.stop_on_key && !self.pause.is_paused() && self.any_key_down()? {
+                info!("Keypad key pressed, stopping the emulator");
+                return Ok(RunOutcome::KeyPressed);
+            }
+            if self.pause.is_paused() {
+                // Keep showing the last frame, without running anything or beeping
+                self.frontend.set_sound_active(false);
+                let display = self.presented.as_ref().unwrap_or(&self.display);
+                if self.menu.is_open() {
+                    self.frontend
+                        .draw_menu(display, &self.menu)
+                        .context(FrontendFailure("Frontend failed drawing the menu"))?;
+                } else {
+                    self.frontend
+                        .draw(display)
+                        .context(FrontendFailure("Frontend failed drawing"))?;
+                }
+                self.frontend
+                    .step()
+                    .context(FrontendFailure("Frontend failed stepping"))?;
+                thread::sleep(self.step_duration);
+                paused_for += iteration_start.elapsed();
+                continue;
+            }
+            if frame_cost == 0 {
+                self.start_frame()?;
+            }
             // get the time at the start of the loop
             let start_time = Instant::now();
-            self.frontend.draw(&self.display)?;
-            self.execute()?;
+            // Periodically report how fast the frontend is actually drawing
+            if last_fps_log.elapsed() >= FPS_LOG_INTERVAL {
+                if let Some(fps) = self.frontend.achieved_fps() {
+                    debug!("Frontend achieving {fps:.1} FPS");
+                }
+                last_fps_log = start_time;
+            }
+            self.frontend
+                .draw(self.presented.as_ref().unwrap_or(&self.display))
+                .context(FrontendFailure("Frontend failed drawing"))?;
+            self.execute_instruction()?;
+            frame_cost += self.last_cost;
+            if frame_cost >= self.instructions_per_frame() {
+                frame_cost = 0;
+                self.cheats.apply(&mut self.memory);
+                self.present_frame();
+                self.stats.frames_presented += 1;
+                self.autosave();
+            }
             let sound_timer: u8;
             {
-                sound_timer = *self.sound_timer.lock().unwrap();
+                sound_timer = self.sound_timer.load(Ordering::Relaxed);
+            }
+            self.frontend.set_sound_active(sound_timer > 0);
+            // Frontends without audio have no sound to start or stop
+            if self.capabilities.has_audio {
+                match self
+                    .beep
+                    .update(sound_timer > 0 && !self.muted, Instant::now())
+                {
+                    Some(BeepChange::Start) => self
+                        .frontend
+                        .play_sound()
+                        .context(FrontendFailure("Frontend failed playing the beep"))?,
+                    Some(BeepChange::Stop) => self
+                        .frontend
+                        .stop_sound()
+                        .context(FrontendFailure("Frontend failed stopping the beep"))?,
+                    None => {}
+                }
             }
-            if sound_timer > 0 && !self.playing_sound {
-                self.frontend.play_sound()?;
-                self.playing_sound = true;
-            } else if sound_timer == 0 && self.playing_sound {
-                self.frontend.play_sound()?;
-                self.playing_sound = false;
+            self.frontend
+                .step()
+                .context(FrontendFailure("Frontend failed stepping"))?;
+            if self.show_perf
+                && let Some(text) = self.perf.update(
+                    Instant::now(),
+                    self.stats.frames_presented,
+                    self.cycles,
+                    self.frontend.achieved_fps(),
+                )
+            {
+                self.frontend.set_perf_text(Some(text));
             }
             let stop_time = Instant::now();
-            // Sleep long enough to match the instructions per second
-            thread::sleep(self.step_duration.saturating_sub(stop_time - start_time));
+            // Sleep long enough to match the instructions per second, with costlier
+            // instructions taking longer (saturating, so a huge cost in the
+            // config can't overflow)
+            thread::sleep(
+                self.step_duration
+                    .saturating_mul(self.last_cost as u32)
+                    .saturating_sub(stop_time.saturating_duration_since(start_time)),
+            );
+        }
+    }
+
+    /// Execute a single instruction, without drawing or waiting
+    ///
+    /// Does nothing if the emulator is halted.
+    pub fn step(&mut self) -> Result<()> {
+        if self.halted {
+            return Ok(());
+        }
+        self.execute_instruction()
+    }
+
+    /// Execute up to `count` instructions (see [Emulator::step]), returning how
+    /// many were executed
+    ///
+    /// Stops early if the emulator halts, or when the program counter reaches a
+    /// breakpoint (see [Emulator::add_breakpoint]). The instruction it starts on
+    /// runs even if it has a breakpoint, so calling this again continues from one.
+    pub fn step_n(&mut self, count: usize) -> Result<usize> {
+        for executed in 0..count {
+            if self.halted || (executed > 0 && self.breakpoints.contains(&self.program_counter)) {
+                return Ok(executed);
+            }
+            self.execute_instruction()?;
+        }
+        Ok(count)
+    }
+
+    /// Stop [Emulator::step_n] before executing the instruction at `address`
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Remove the breakpoint at `address`, returning whether there was one
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Addresses with breakpoints, in order
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Execute one 60Hz frame's worth of instructions, without drawing or waiting
+    ///
+    /// Useful for running the emulator headless (e.g. in tests), the number of
+    /// instructions is the configured instructions per second divided by 60
+    /// (with `cycle_accurate`, instructions run until their total cost reaches
+    /// that). Stops early if the emulator halts during the frame.
+    pub fn run_frame(&mut self) -> Result<()> {
+        self.start_frame()?;
+        let mut frame_cost = 0;
+        while frame_cost < self.instructions_per_frame() {
+            if self.halted {
+                break;
+            }
+            self.execute_instruction()?;
+            frame_cost += self.last_cost;
         }
+        self.cheats.apply(&mut self.memory);
+        self.present_frame();
         Ok(())
     }
 
-    /// Read a file, loads into memory starting at position 0x200 (512)
-    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
-        let contents = std::fs::read(path).context("Failed to read input file")?;
-        self.load_bytes(&contents, GAME_MEMORY_START)?;
+    /// Copy the display into the frame drawn by the frontend, at the end of a
+    /// frame when [EmulatorConfig::double_buffer] is on
+    fn present_frame(&mut self) {
+        if let Some(presented) = self.presented.as_mut() {
+            presented.clone_from(&self.display);
+        }
+    }
+
+    /// Switch the display to high (128x64) or low (64x32) resolution, telling
+    /// the frontend the new size if it changed
+    ///
+    /// Switching clears the display, and it is shown cleared straight away.
+    fn set_high_res(&mut self, high_res: bool) -> Result<()> {
+        if self.display.set_high_res(high_res) {
+            self.present_frame();
+            self.frontend
+                .on_resolution_change(self.display.rows(), self.display.cols())?;
+        }
         Ok(())
     }
 
-    /// Execute a single instruction
-    fn execute(&mut self) -> Result<()> {
-        // Gets the instruction, increments the program counter
-        let (instruction_byte1, instruction_byte2) = self.fetch()?;
+    /// Show `err`, which stopped the run, in the frontend until it is dismissed
+    ///
+    /// Errors from the frontend itself aren't shown, as it can't be relied on
+    /// to show them.
+    pub fn show_error(&mut self, err: &anyhow::Error) -> Result<()> {
+        if err.downcast_ref::<FrontendFailure>().is_some() {
+            return Ok(());
+        }
+        let lines = error_screen_text(err, self.cycles, ERROR_SCREEN_COLUMNS);
+        self.frontend.show_error(&lines)
+    }
 
-        // Decode the instruction into various nibbles (half bytes), other values
-        let nib1 = (instruction_byte1) >> 4; // Used to determine instruction type
-        let nib_x = instruction_byte1 & 0x0F; // Used for register address
-        let nib_y = (instruction_byte2) >> 4; // Used for register address
-        let nib_n = instruction_byte2 & 0x0F; // 4 bit number
-        debug_assert!(
-            nib_x <= 0xF,
-            "Value of X was greater than the number of registers"
-        );
-        debug_assert!(
-            nib_y <= 0xF,
-            "Value of Y was greater than the number of registers"
-        );
-        debug_assert!(nib_n <= 0xF, "Value of the last half-byte was too large");
-        // Other bit combinations used, not really nibbles but convenient prefix
-        let nib_nn = instruction_byte2; // 8-bit immediate number (not index)
-        let nib_nnn: u16 = ((nib_x as u16) << 8) | ((nib_y as u16) << 4) | (nib_n as u16);
-        // Match on the instruction (breaking it down by half-bytes as that
-        // is how instructions are distinguished)
-        let _: () = match (nib1, nib_x, nib_y, nib_n) {
-            // CLEAR
-            (0x0, 0x0, 0xE, 0x0) => {
-                trace!("Clear instruction");
-                self.display.clear()?;
-                self.display.needs_redraw = true;
+    /// Number of instructions executed so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Handle the pause, step, menu, and reset hotkeys, and input to the menu if it's
+    /// open, returning an outcome if the run should stop
+    #[cfg(feature = "std")]
+    fn handle_controls(&mut self) -> Result<Option<RunOutcome>> {
+        if self.handle_remote_commands() {
+            info!("Quitting at a remote control client's request");
+            return Ok(Some(RunOutcome::Stopped));
+        }
+        let hotkey = |frontend: &mut Box<dyn Frontend + 'a>, action: HotkeyAction| {
+            frontend
+                .check_hotkey(action)
+                .context(FrontendFailure("Frontend failed checking the hotkeys"))
+        };
+        if hotkey(&mut self.frontend, HotkeyAction::Menu)? {
+            if self.menu.is_open() {
+                return self.choose_menu_item(MenuItem::Resume);
             }
-            // JUMP
-            (0x1, ..) => {
-                trace!("Jump instruction");
-                self.jump(nib_nnn as usize)?;
+            self.menu.open();
+            self.set_paused(true)?;
+        } else if hotkey(&mut self.frontend, HotkeyAction::Pause)? && !self.menu.is_open() {
+            self.set_paused(!self.pause.is_manual())?;
+        }
+        if hotkey(&mut self.frontend, HotkeyAction::Step)?
+            && self.pause.is_manual()
+            && !self.menu.is_open()
+        {
+            debug!("Stepping a frame while paused");
+            self.run_frame()?;
+        }
+        if hotkey(&mut self.frontend, HotkeyAction::Mute)? {
+            self.set_muted(!self.muted)?;
+        }
+        if hotkey(&mut self.frontend, HotkeyAction::ToggleFps)? {
+            self.show_perf = !self.show_perf;
+            debug!("FPS counter shown: {}", self.show_perf);
+            if !self.show_perf {
+                self.perf.reset();
+                self.frontend.set_perf_text(None);
             }
-            // SUBROUTINE
-            (0x2, ..) => {
-                trace!("Go to subroutine");
-                // Push pc onto stack for returning from subroutine
-                self.stack_push(self.program_counter as u16)?;
-                // Jump to destination
-                self.jump(nib_nnn as usize)?;
+        }
+        if hotkey(&mut self.frontend, HotkeyAction::Reset)? {
+            return self.choose_menu_item(MenuItem::Reset);
+        }
+        if self.menu.is_open()
+            && let Some(input) = self
+                .frontend
+                .menu_input(&self.menu)
+                .context(FrontendFailure("Frontend failed checking the menu input"))?
+            && let Some(item) = self.menu.handle(input)
+        {
+            return self.choose_menu_item(item);
+        }
+        Ok(None)
+    }
+
+    /// Carry out the menu `item`, returning an outcome if the run should stop
+    #[cfg(feature = "std")]
+    fn choose_menu_item(&mut self, item: MenuItem) -> Result<Option<RunOutcome>> {
+        debug!("Chose {:?} from the menu", item);
+        match item {
+            MenuItem::Resume => {
+                self.menu.close();
+                self.set_paused(false)?;
             }
-            // RETURN
-            (0x0, 0x0, 0xE, 0xE) => {
-                trace!("Return from subroutine");
-                let dest = self.stack_pop()? as usize;
-                self.jump(dest)?;
+            MenuItem::Reset => {
+                self.menu.close();
+                self.reset()?;
+                self.set_paused(false)?;
             }
-            // CONDITIONAL JUMPS
-            (0x3, x, ..) => {
-                trace!("Jump if VX==NN");
-                // If value of register VX is equal to NN, skip next instruction
-                if self.get_reg(x)? == nib_nn {
-                    self.program_counter += INSTRUCTION_LENGTH;
-                }
+            MenuItem::LoadRom => match self.frontend.choose_rom()? {
+                Some(path) => match self.switch_rom(&path) {
+                    Ok(()) => {
+                        self.menu.close();
+                        self.set_paused(false)?;
+                    }
+                    Err(err) => error!("Failed to load {path:?}: {err:#}"),
+                },
+                None => info!("No ROM chosen to load"),
+            },
+            MenuItem::ToggleTheme => self.frontend.cycle_theme()?,
+            MenuItem::Quit => {
+                info!("Quitting from the menu");
+                return Ok(Some(RunOutcome::Stopped));
             }
-            (0x4, x, ..) => {
-                trace!("Jump if VX!=NN");
-                // If value of register VX is NOT equal to NN, skip next instruction
-                if self.get_reg(x)? != nib_nn {
-                    self.program_counter += INSTRUCTION_LENGTH;
-                }
+        }
+        Ok(None)
+    }
+
+    /// Carry out the commands sent by remote control clients since the last
+    /// call, returning whether one asked to quit
+    #[cfg(feature = "std")]
+    fn handle_remote_commands(&mut self) -> bool {
+        let Some(control) = self.remote_control.take() else {
+            return false;
+        };
+        let mut quit = false;
+        while !quit && let Some(request) = control.next_request() {
+            quit = request.respond(self);
+        }
+        self.remote_control = Some(control);
+        quit
+    }
+
+    /// Show the splash for `duration`, or until a keypad key is pressed, returning
+    /// false if the frontend asked to stop while it was showing
+    ///
+    /// The display is left as it was before the splash.
+    #[cfg(feature = "std")]
+    fn show_splash(&mut self, duration: Duration) -> Result<bool> {
+        debug!("Showing the splash");
+        let before = self.display.snapshot();
+        splash::draw_splash(&mut self.display)?;
+        let start = Instant::now();
+        let mut carry_on = true;
+        while start.elapsed() < duration {
+            if self.frontend.should_stop() {
+                carry_on = false;
+                break;
             }
-            (0x5, x, y, ..) => {
-                trace!("Jump if VX==VY");
-                // If value at VX == value at VY, skip next instruction
-                if self.get_reg(x)? == self.get_reg(y)? {
-                    self.program_counter += INSTRUCTION_LENGTH;
-                }
+            self.frontend.draw(&self.display)?;
+            self.stats.frames_presented += 1;
+            self.frontend.step()?;
+            if self.any_key_down()? {
+                debug!("Splash skipped");
+                break;
             }
-            (0x9, x, y, ..) => {
-                trace!("Jump if VX!=VY");
-                // If value at VX != value at VY, skip next instruction
-                if self.get_reg(x)? != self.get_reg(y)? {
-                    self.program_counter += INSTRUCTION_LENGTH;
-                }
+            thread::sleep(Duration::from_millis(MILLIS_PER_SECOND / TIMER_HZ));
+        }
+        self.display.restore(&before)?;
+        Ok(carry_on)
+    }
+
+    /// Restart the most recently loaded ROM from the beginning, clearing the
+    /// memory, display, registers, stack, and timers
+    pub fn reset(&mut self) -> Result<()> {
+        info!("Resetting the emulator");
+        self.memory = [0u8; MEMORY_SIZE];
+        if let Some(compat) = self.compat.as_mut() {
+            compat.clear_memory();
+        }
+        self.set_high_res(false)?;
+        self.display = Display::with_planes(self.config.num_planes)?;
+        self.present_frame();
+        self.program_counter = self.start_address;
+        self.index_register = 0;
+        self.stack.clear();
+        self.registers = [0u8; NUM_REGISTERS];
+        self.delay_timer.store(0, Ordering::Relaxed);
+        self.sound_timer.store(0, Ordering::Relaxed);
+        #[cfg(feature = "std")]
+        if self.beep.stop() {
+            self.frontend.stop_sound()?;
+        }
+        self.waiting_for_key_release = None;
+        self.last_collision_count = 0;
+        self.collisions_this_frame = 0;
+        self.halted = false;
+        self.instruction_address = self.start_address;
+        self.frame_keys = None;
+        self.load_font()?;
+        let rom = mem::take(&mut self.rom);
+        let loaded = self.load_bytes(&rom, GAME_MEMORY_START);
+        self.rom = rom;
+        loaded?;
+        let regions = mem::take(&mut self.regions);
+        let loaded = regions
+            .iter()
+            .try_for_each(|region| self.load_bytes(&region.bytes, region.address));
+        self.regions = regions;
+        loaded?;
+        let pokes = mem::take(&mut self.pokes);
+        let poked = pokes.iter().try_for_each(|poke| poke.apply(self));
+        self.pokes = pokes;
+        poked?;
+        self.cheats.reset();
+        self.cheats.apply(&mut self.memory);
+        Ok(())
+    }
+
+    /// Whether any keypad key is down, straight from the frontend, or held by
+    /// remote control
+    #[cfg(feature = "std")]
+    fn any_key_down(&mut self) -> Result<bool> {
+        if self.held_keys != 0 {
+            return Ok(true);
+        }
+        for key in 0x0..=0xF {
+            if self.frontend.check_key(key)? {
+                return Ok(true);
             }
-            // SET REGISTER
-            (0x6, x, ..) => {
-                trace!("Set register");
-                self.set_reg(x as usize, nib_nn)?;
+        }
+        Ok(false)
+    }
+
+    /// Pause or resume the run loop
+    ///
+    /// While paused [Emulator::run_for] executes no instructions and the timers
+    /// stop, but the frontend keeps drawing the last frame and can still stop
+    /// the emulator. Any beep stops, and starts again on resuming if the sound
+    /// timer hasn't run out. [Emulator::step] and [Emulator::run_frame] still
+    /// execute instructions while paused.
+    ///
+    /// This is separate from pausing while the window doesn't have the focus
+    /// (see `pause_on_focus_loss`), resuming here while unfocused stays paused
+    /// until the focus returns, and the focus returning doesn't resume a pause
+    /// made here.
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        if self.pause.set_manual(paused) {
+            info!("{}", if paused { "Paused" } else { "Resumed" });
+            self.pause_changed()?;
+        }
+        Ok(())
+    }
+
+    /// Pause or resume as the window loses or regains the focus
+    #[cfg(feature = "std")]
+    fn set_focused(&mut self, focused: bool) -> Result<()> {
+        if self.pause.set_focused(focused) {
+            info!(
+                "{}",
+                if focused {
+                    "Focus regained, resumed"
+                } else {
+                    "Focus lost, paused"
+                }
+            );
+            self.pause_changed()?;
+        }
+        Ok(())
+    }
+
+    /// Stop or restart the timers after pausing or resuming, stopping any beep
+    /// on pausing
+    fn pause_changed(&mut self) -> Result<()> {
+        let paused = self.pause.is_paused();
+        self.timers_paused.store(paused, Ordering::Relaxed);
+        #[cfg(feature = "std")]
+        if paused && self.beep.stop() {
+            self.frontend
+                .stop_sound()
+                .context(FrontendFailure("Frontend failed stopping the beep"))?;
+        }
+        Ok(())
+    }
+
+    /// Mute or unmute the beep
+    ///
+    /// A beep which is playing stops straight away, and starts again on
+    /// unmuting if the sound timer hasn't run out.
+    pub fn set_muted(&mut self, muted: bool) -> Result<()> {
+        if muted == self.muted {
+            return Ok(());
+        }
+        info!("{}", if muted { "Muted" } else { "Unmuted" });
+        self.muted = muted;
+        #[cfg(feature = "std")]
+        if muted && self.beep.stop() {
+            self.frontend
+                .stop_sound()
+                .context(FrontendFailure("Frontend failed stopping the beep"))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the beep is muted (see [Emulator::set_muted])
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Whether the run loop is paused (see [Emulator::set_paused])
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+
+    /// What the frontend supports
+    pub fn capabilities(&self) -> FrontendCapabilities {
+        self.capabilities
+    }
+
+    /// Start collecting a [CompatReport] of what the program does which matters
+    /// for compatibility, included in the [stats](Emulator::stats)
+    pub fn enable_compat_report(&mut self) {
+        let mut compat = CompatTracker::new();
+        compat.record_load(FONT_START_POSITION..FONT_START_POSITION + FONT.len());
+        self.compat = Some(compat);
+    }
+
+    /// The compatibility report so far, None unless it was enabled with
+    /// [Emulator::enable_compat_report]
+    pub fn compat_report(&self) -> Option<CompatReport> {
+        let depth = self.stats.max_stack_depth;
+        self.compat.as_ref().map(|compat| compat.report(depth))
+    }
+
+    /// Statistics about the run so far
+    pub fn stats(&self) -> RunStats {
+        let mut stats = self.stats.clone();
+        stats.compat = self.compat_report();
+        stats.instructions = self.cycles;
+        stats.collisions_this_frame = self.collisions_this_frame;
+        if stats.wall_time_seconds > 0.0 {
+            stats.achieved_ips = self.cycles as f64 / stats.wall_time_seconds;
+        }
+        stats
+    }
+
+    /// Whether the program has exited (with 00FD), a halted emulator won't
+    /// execute any more instructions
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Number of instructions executed by [Emulator::run_frame] (at least 1),
+    /// or their total cost with `cycle_accurate`
+    pub fn instructions_per_frame(&self) -> u64 {
+        (self.config.instructions_per_second / TIMER_HZ).max(1)
+    }
+
+    /// Current state of the display
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// The display as the frontend is shown it: the last complete frame with
+    /// [EmulatorConfig::double_buffer], otherwise the current display
+    pub fn presented_display(&self) -> &Display {
+        self.presented.as_ref().unwrap_or(&self.display)
+    }
+
+    /// Read a file, loads into memory starting at position 0x200 (512)
+    #[cfg(feature = "std")]
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let contents = rom::open(path.as_ref()).context("Failed to read input file")?;
+        self.load_rom_from(&contents, path.as_ref())
+    }
+
+    /// Load `rom`, read from `path` (e.g. a file or URL), into memory starting at
+    /// position 0x200 (512), showing its title in the frontend
+    #[cfg(feature = "std")]
+    pub fn load_rom_from(&mut self, rom: &[u8], path: &Path) -> Result<()> {
+        self.load_rom(rom)?;
+        self.show_title(path)
+    }
+
+    /// Load the program and other files in `layout` (the program read from
+    /// `path`), and reset to start it
+    #[cfg(feature = "std")]
+    pub fn load_layout(&mut self, layout: &MemoryLayout, path: &Path) -> Result<()> {
+        check_rom_fits(&layout.rom)?;
+        self.rom = layout.rom.clone();
+        self.regions = layout.regions.clone();
+        self.start_address = layout.start_address;
+        self.pokes = layout.pokes.clone();
+        self.reset()?;
+        self.show_title(path)
+    }
+
+    /// Replace the ROM with `rom` (read from `path`), and reset to start it
+    ///
+    /// Fails, leaving the current ROM, if `rom` doesn't fit in memory.
+    #[cfg(feature = "std")]
+    pub fn replace_rom(&mut self, rom: Vec<u8>, path: &Path) -> Result<()> {
+        check_rom_fits(&rom)?;
+        self.rom = rom;
+        self.reset()?;
+        self.show_title(path)
+    }
+
+    /// Load the ROM at `path` in place of the current one, without the files
+    /// loaded along with it, and reset to start it
+    ///
+    /// Fails, leaving the current ROM, if the file can't be read or doesn't fit.
+    #[cfg(feature = "std")]
+    pub fn switch_rom(&mut self, path: &Path) -> Result<()> {
+        info!("Loading {path:?}");
+        let rom = rom::open(path)?;
+        check_rom_fits(&rom)?;
+        // Files loaded with the last ROM don't belong to this one
+        self.regions.clear();
+        self.start_address = GAME_MEMORY_START;
+        self.pokes.clear();
+        self.replace_rom(rom, path)
+    }
+
+    /// Show the title of the loaded ROM (from the ROM database, or its file name)
+    /// in the frontend
+    #[cfg(feature = "std")]
+    fn show_title(&mut self, path: &Path) -> Result<()> {
+        let title = RomDatabase::or_embedded(self.rom_database.as_ref()).title(&self.rom, path);
+        info!("Loaded {title}");
+        self.frontend.set_title(&title)
+    }
+
+    /// Load a ROM (e.g. one embedded with `include_bytes!`) into memory starting
+    /// at position 0x200 (512)
+    ///
+    /// Fails, leaving memory untouched, if the ROM doesn't fit in the memory
+    /// after 0x200.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<()> {
+        check_rom_fits(rom)?;
+        self.rom = rom.to_vec();
+        self.load_bytes(rom, GAME_MEMORY_START)?;
+        self.cheats.reset();
+        self.cheats.apply(&mut self.memory);
+        Ok(())
+    }
+
+    /// The whole memory, including the interpreter area
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Set the byte at `address` in memory
+    pub fn poke_memory(&mut self, address: usize, value: u8) -> Result<()> {
+        *self
+            .memory
+            .get_mut(address)
+            .with_context(|| format!("Address {address:#05x} is past the end of memory"))? = value;
+        if let Some(compat) = self.compat.as_mut() {
+            compat.record_load(address..address + 1);
+        }
+        Ok(())
+    }
+
+    /// The value in register V`register`
+    pub fn register(&self, register: u8) -> Result<u8> {
+        self.get_reg(register)
+    }
+
+    /// Set register V`register` to `value`
+    pub fn set_register(&mut self, register: u8, value: u8) -> Result<()> {
+        self.set_reg(register.into(), value)
+    }
+
+    /// The value in the index register
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    /// Set the index register to `value`
+    pub fn set_index_register(&mut self, value: u16) -> Result<()> {
+        self.set_index(value)
+    }
+
+    /// Address of the next instruction to execute
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// The next instruction to execute, as its opcode and what it decodes to,
+    /// without executing it or moving the program counter
+    pub fn peek_instruction(&self) -> Result<(u16, OpcodeKind)> {
+        let bytes = self
+            .memory
+            .get(self.program_counter..self.program_counter + INSTRUCTION_LENGTH)
+            .with_context(|| {
+                format!(
+                    "No instruction at {:#05x}, outside of memory",
+                    self.program_counter
+                )
+            })?;
+        let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+        Ok((opcode, OpcodeKind::decode(opcode)))
+    }
+
+    /// Continue execution from `address`
+    pub fn set_program_counter(&mut self, address: usize) -> Result<()> {
+        if address + INSTRUCTION_LENGTH > MEMORY_SIZE {
+            bail!("Address {address:#05x} is past the end of memory");
+        }
+        self.jump(address)
+    }
+
+    /// The value of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer.load(Ordering::Relaxed)
+    }
+
+    /// Set the delay timer to `value`
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer.store(value, Ordering::Relaxed);
+    }
+
+    /// The value of the sound timer
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer.load(Ordering::Relaxed)
+    }
+
+    /// Set the sound timer to `value`
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer.store(value, Ordering::Relaxed);
+    }
+
+    /// Switch the frontend to `keymap`, e.g. to try another mapping while running
+    ///
+    /// The next key check uses it. Fails, keeping the old keymap, if the
+    /// frontend doesn't support one of its keys.
+    pub fn set_keymap(&mut self, keymap: &Keymap) -> Result<()> {
+        self.frontend
+            .set_keymap(keymap)
+            .context("Switching to the new keymap")
+    }
+
+    /// Bind keypad key `key` to the physical key `key_name` while running, on
+    /// top of the configured keymap and any earlier remaps (see [Keymap::remap])
+    ///
+    /// The binding is kept in the configuration, so it lasts through a reset.
+    pub fn remap_key(&mut self, key: u8, key_name: &str) -> Result<()> {
+        let mut keymap = Keymap::resolve(&self.config.keymap_preset, &self.config.keymap)?;
+        keymap.remap(&format!("{key:X}"), key_name)?;
+        self.set_keymap(&keymap)?;
+        // Replacing any override of the key written another way (e.g. "a" or "0xA")
+        self.config
+            .keymap
+            .retain(|keypad_key, _| parse_keypad_key(keypad_key).ok() != Some(key));
+        self.config
+            .keymap
+            .insert(format!("{key:X}"), key_name.to_string());
+        Ok(())
+    }
+
+    /// Enable `cheat`, on top of those from the config, written at the end of
+    /// the current frame
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        info!("Enabling cheat {cheat}");
+        self.cheats.add(cheat);
+        self.show_cheats();
+    }
+
+    /// List the enabled cheats in the frontend's debug overlay
+    fn show_cheats(&mut self) {
+        self.frontend
+            .set_cheats_text(self.cheats.summary().as_deref());
+    }
+
+    /// The enabled cheats
+    pub fn cheats(&self) -> &[Cheat] {
+        self.cheats.cheats()
+    }
+
+    /// Active entries on the stack (the return addresses of the current
+    /// subroutine calls), oldest first
+    pub fn stack_trace(&self) -> Vec<u16> {
+        self.stack.clone()
+    }
+
+    /// Send every register change to `sink` (as well as the log) when the
+    /// `trace_registers` config is enabled
+    pub fn set_register_sink(&mut self, sink: impl FnMut(RegisterChange) + 'a) {
+        self.register_sink = Some(Box::new(sink));
+    }
+
+    /// Reload the ROM from `path` (resetting the emulator) whenever the file
+    /// changes while running, e.g. when reassembling a ROM being worked on
+    #[cfg(feature = "std")]
+    pub fn watch_rom(&mut self, path: &Path) {
+        info!("Watching {path:?} for changes");
+        self.rom_watcher = Some(RomWatcher::new(path, watch::DEFAULT_DEBOUNCE));
+    }
+
+    /// Write a save state to a rotating set of files in `dir` every `interval`
+    /// while running, which `--resume` can carry on from
+    #[cfg(feature = "std")]
+    pub fn enable_autosave(&mut self, dir: PathBuf, interval: Duration) {
+        info!("Autosaving to {dir:?} every {interval:?}");
+        self.autosaver = Some(Autosaver::new(
+            dir,
+            &self.rom_sha1(),
+            interval,
+            Instant::now(),
+        ));
+    }
+
+    /// Save the game to a file in `dir` if the frontend fails mid-game (e.g. the
+    /// GPU context is lost), which `--resume` can carry on from
+    #[cfg(feature = "std")]
+    pub fn enable_emergency_save(&mut self, dir: PathBuf) {
+        self.emergency_save_dir = Some(dir);
+    }
+
+    /// Address execution starts at on reset, [PROGRAM_START] unless the memory
+    /// layout (e.g. `--start-address`) moved it
+    pub fn start_address(&self) -> usize {
+        self.start_address
+    }
+
+    /// Most entries the stack holds before a call overflows it (the
+    /// `stack_depth` setting)
+    pub fn stack_depth(&self) -> usize {
+        self.config.stack_depth
+    }
+
+    /// Seed the random numbers CXNN draws from, so runs with the same seed
+    /// (and inputs) draw the same numbers
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Take commands from the clients of `control` while running (see
+    /// [crate::remote_control])
+    #[cfg(feature = "std")]
+    pub fn enable_remote_control(&mut self, control: RemoteControl) {
+        self.remote_control = Some(control);
+    }
+
+    /// Hold keypad key `key` (0 to 15) down, or let it go, on top of the keys
+    /// down in the frontend
+    pub fn set_key_held(&mut self, key: u8, held: bool) -> Result<()> {
+        if usize::from(key) >= KEYPAD_SIZE {
+            bail!(
+                "Invalid keypad key {key}, expected 0 to {}",
+                KEYPAD_SIZE - 1
+            );
+        }
+        let bit = 1 << key;
+        if held {
+            self.held_keys |= bit;
+        } else {
+            self.held_keys &= !bit;
+        }
+        Ok(())
+    }
+
+    /// Write the display to `path` as a PPM image, one image pixel per display
+    /// pixel, in the configured background and foreground colors
+    #[cfg(feature = "std")]
+    pub fn write_screenshot(&self, path: &Path) -> Result<()> {
+        let colors = [
+            parse_color(&self.config.background)?,
+            parse_color(&self.config.foreground)?,
+        ];
+        let image = self
+            .presented
+            .as_ref()
+            .unwrap_or(&self.display)
+            .to_ppm(colors)?;
+        std::fs::write(path, image).with_context(|| format!("Writing screenshot {path:?}"))
+    }
+
+    /// SHA-1 of the loaded ROM, as hex
+    pub fn rom_sha1(&self) -> String {
+        rom::sha1_hex(&self.rom)
+    }
+
+    /// Carry on from the most recent autosave in `dir` of the loaded ROM,
+    /// returning whether there was one
+    #[cfg(feature = "std")]
+    pub fn resume_autosave(&mut self, dir: &Path) -> Result<bool> {
+        let Some(state) = latest_autosave(dir, &self.rom_sha1()) else {
+            return Ok(false);
+        };
+        self.load_state(&state).context("Loading the autosave")?;
+        Ok(true)
+    }
+
+    /// Write an autosave if one is due, only logging a failure so a full disk
+    /// doesn't stop the game
+    #[cfg(feature = "std")]
+    fn autosave(&mut self) {
+        let now = Instant::now();
+        if !self
+            .autosaver
+            .as_ref()
+            .is_some_and(|saver| saver.is_due(now))
+        {
+            return;
+        }
+        let state = self.save_state();
+        if let Some(saver) = self.autosaver.as_mut() {
+            // A ROM reloaded since autosaving started gets autosaves of its own
+            if saver.rom_sha1() != state.rom_sha1 {
+                *saver = Autosaver::new(saver.dir(), &state.rom_sha1, saver.interval(), now);
             }
-            // ADD TO REGISTER
-            (0x7, x, ..) => {
-                trace!("Add to register");
-                let vx = self.get_reg(x)?;
-                let (res, _) = vx.overflowing_add(nib_nn);
-                self.set_reg(x as usize, res)?;
+            match saver.save(&state, now) {
+                Ok(path) => debug!("Autosaved to {path:?}"),
+                Err(err) => warn!("Failed to autosave: {err:#}"),
             }
-            // ARITHMETIC/LOGICAL OPERATIONS
-            // SET
-            (0x8, x, y, 0x0) => {
-                trace!("Set VX to VY");
-                let vy = self.get_reg(y)?;
-                self.set_reg(x as usize, vy)?;
+        }
+    }
+
+    /// The state of the running program, to carry on from later with
+    /// [Emulator::load_state]
+    #[cfg(feature = "std")]
+    pub fn save_state(&self) -> SaveState {
+        self.save_state_at(unix_millis(SystemTime::now()))
+    }
+
+    /// The state of the running program, saved at `saved_at` (milliseconds
+    /// since the Unix epoch), for hosts where the system clock can't be read
+    /// (e.g. WebAssembly)
+    #[cfg(feature = "std")]
+    pub fn save_state_at(&self, saved_at: u64) -> SaveState {
+        SaveState {
+            version: SAVE_STATE_VERSION,
+            rom_sha1: self.rom_sha1(),
+            saved_at,
+            memory: self.memory.to_vec(),
+            registers: self.registers.to_vec(),
+            index_register: self.index_register,
+            program_counter: self.program_counter,
+            stack: self.stack.clone(),
+            stack_depth: self.config.stack_depth,
+            delay_timer: self.delay_timer.load(Ordering::Relaxed),
+            sound_timer: self.sound_timer.load(Ordering::Relaxed),
+            planes: self.display.snapshot_planes(),
+            selected_planes: self.display.selected_planes(),
+            high_res: self.display.is_high_res(),
+            waiting_for_key_release: self.waiting_for_key_release,
+            halted: self.halted,
+            cycles: self.cycles,
+        }
+    }
+
+    /// Carry on from a state saved by [Emulator::save_state]
+    ///
+    /// The ROM which was running isn't checked, the caller picks a state saved
+    /// from the loaded ROM.
+    #[cfg(feature = "std")]
+    pub fn load_state(&mut self, state: &SaveState) -> Result<()> {
+        if state.memory.len() != MEMORY_SIZE {
+            bail!(
+                "Save state has {} bytes of memory, expected {MEMORY_SIZE}",
+                state.memory.len()
+            );
+        }
+        let registers: [u8; NUM_REGISTERS] =
+            state.registers.as_slice().try_into().with_context(|| {
+                format!(
+                    "Save state has {} registers, expected {NUM_REGISTERS}",
+                    state.registers.len()
+                )
+            })?;
+        if state.stack.len() > state.stack_depth {
+            bail!(
+                "Save state has {} entries on the stack, but its stack held at most {}",
+                state.stack.len(),
+                state.stack_depth
+            );
+        }
+        if state.stack_depth != self.config.stack_depth {
+            warn!(
+                "Save state was saved with a stack depth of {}, but it is now {}",
+                state.stack_depth, self.config.stack_depth
+            );
+        }
+        if state.program_counter >= MEMORY_SIZE {
+            bail!(
+                "Save state's program counter {:#05x} is outside memory",
+                state.program_counter
+            );
+        }
+        self.set_high_res(state.high_res)?;
+        self.display
+            .restore_planes(&state.planes, state.selected_planes)
+            .context("Restoring the display from the save state")?;
+        self.memory.copy_from_slice(&state.memory);
+        self.registers = registers;
+        self.index_register = state.index_register;
+        self.program_counter = state.program_counter;
+        self.instruction_address = state.program_counter;
+        self.stack = state.stack.clone();
+        self.delay_timer.store(state.delay_timer, Ordering::Relaxed);
+        self.sound_timer.store(state.sound_timer, Ordering::Relaxed);
+        self.waiting_for_key_release = state.waiting_for_key_release;
+        self.halted = state.halted;
+        self.cycles = state.cycles;
+        self.present_frame();
+        Ok(())
+    }
+
+    /// Reload the watched ROM if it has changed, in the same way as loading a
+    /// ROM from the menu
+    #[cfg(feature = "std")]
+    fn reload_watched_rom(&mut self) -> Result<()> {
+        let Some(watcher) = self.rom_watcher.as_mut() else {
+            return Ok(());
+        };
+        let Some(rom) = watcher.poll(Instant::now()) else {
+            return Ok(());
+        };
+        let path = watcher.path().to_path_buf();
+        info!("{path:?} changed, reloading it");
+        // A paused game stays paused, to be resumed when the player is ready
+        if let Err(err) = self.replace_rom(rom, &path) {
+            error!("Failed to reload {path:?}: {err:#}");
+        }
+        Ok(())
+    }
+
+    /// Turn `quirk` on or off, taking effect from the next instruction
+    pub fn set_quirk(&mut self, quirk: Quirk, enabled: bool) {
+        quirk.set_enabled(&mut self.config, enabled);
+    }
+
+    /// Send every quirk hit to `sink` (as well as the log) when the
+    /// `log_quirk_hits` config is enabled
+    pub fn set_quirk_sink(&mut self, sink: impl FnMut(QuirkHit) + 'a) {
+        self.quirk_sink = Some(Box::new(sink));
+    }
+
+    /// Send every byte of memory the program executes, reads, or writes to `sink`,
+    /// e.g. to tell its code from its data
+    pub fn set_access_sink(&mut self, sink: impl FnMut(MemoryAccess) + 'a) {
+        self.access_sink = Some(Box::new(sink));
+    }
+
+    /// Number of pixels turned off (collided) by the most recent sprite draw
+    ///
+    /// VF only records whether any collision happened, this gives the full count.
+    pub fn last_collision_count(&self) -> u32 {
+        self.last_collision_count
+    }
+
+    /// Number of sprite draws which collided (set VF) so far this frame, also
+    /// in the [stats](Emulator::stats) with the total for the run
+    pub fn collisions_this_frame(&self) -> u32 {
+        self.collisions_this_frame
+    }
+
+    /// Prepare for the next frame's instructions, ticking the timers if they are
+    /// synchronous, and polling the keys if they are polled once per frame
+    fn start_frame(&mut self) -> Result<()> {
+        if self.config.log_collisions && self.collisions_this_frame > 0 {
+            debug!(
+                "{} sprite draws collided in the frame ({} in total)",
+                self.collisions_this_frame, self.stats.total_collisions
+            );
+        }
+        self.collisions_this_frame = 0;
+        // Without std there is no ticker thread to decrement them
+        if self.config.synchronous_timers || cfg!(not(feature = "std")) {
+            self.tick_timers(TIMER_TICK);
+        }
+        if self.config.poll_keys_per_frame {
+            self.frame_keys = Some(
+                self.frontend
+                    .current_keys()
+                    .context(FrontendFailure("Frontend failed checking the keys"))?
+                    | self.held_keys,
+            );
+        }
+        Ok(())
+    }
+
+    /// Decrement the delay and sound timers by the number of 60Hz ticks in
+    /// `elapsed`, carrying any time left over to the next call
+    ///
+    /// For hosts driving the emulator themselves (e.g. with [Emulator::run_frame])
+    /// with `synchronous_timers` set, so there is no ticker thread, and the
+    /// timers only move when this is called.
+    pub fn tick_timers(&mut self, elapsed: Duration) {
+        let elapsed = self.timer_remainder + elapsed;
+        let ticks = elapsed.as_nanos() / TIMER_TICK.as_nanos();
+        self.timer_remainder =
+            Duration::from_nanos((elapsed.as_nanos() % TIMER_TICK.as_nanos()) as u64);
+        let ticks = ticks.min(u32::MAX.into()) as u32;
+        for timer in [&self.delay_timer, &self.sound_timer] {
+            count_down(timer, ticks);
+        }
+    }
+
+    /// Execute a single instruction, handling any failure as configured by `on_error`
+    fn execute_instruction(&mut self) -> Result<()> {
+        self.cycles += 1;
+        let Err(err) = self.execute() else {
+            return Ok(());
+        };
+        if is_unrecoverable(&err) {
+            return Err(err);
+        }
+        let address = self.instruction_address;
+        match self.on_error {
+            OnError::Abort => Err(err),
+            OnError::SkipInstruction => {
+                warn!("Skipping the instruction at {address:#05x}, which failed: {err:#}");
+                self.program_counter = address + INSTRUCTION_LENGTH;
+                Ok(())
             }
-            // BINARY REGISTER OPS
-            (0x8, x, y, n) => {
-                trace!("Binary register operation");
-                let vx = self.get_reg(x)?;
-                let vy = self.get_reg(y)?;
-                match n {
-                    0x1 => {
-                        trace!("Binary OR");
-                        self.set_reg(x as usize, vx | vy)?;
-                    }
-                    0x2 => {
-                        trace!("Binary AND");
-                        self.set_reg(x as usize, vx & vy)?;
-                    }
-                    0x3 => {
-                        trace!("Binary XOR");
-                        self.set_reg(x as usize, vx ^ vy)?;
-                    }
-                    0x4 => {
-                        trace!("Add with overflow");
-                        let (res, carry) = vx.overflowing_add(vy);
-                        self.set_reg(x as usize, res)?;
-                        self.set_reg(0xF, carry.into())?;
-                    }
-                    0x5 => {
-                        trace!("Sub with overflow VX - VY");
-                        let (res, carry) = vx.overflowing_sub(vy);
-                        self.set_reg(x as usize, res)?;
-                        self.set_reg(0xF, (!carry).into())?;
-                    }
-                    0x7 => {
-                        trace!("Sub with overflow VY - VX");
-                        let (res, carry) = vy.overflowing_sub(vx);
-                        self.set_reg(x as usize, res)?;
-                        self.set_reg(0xF, (!carry).into())?;
-                    }
-                    0x6 | 0xE => {
-                        trace!("Shift operations");
-                        // NOTE: Setting VX to VY is different between COSMAC and CHIP-48
-                        if self.config.shift_use_vy {
-                            self.set_reg(x as usize, self.get_reg(y)?)?;
-                        }
-                        let vx = self.get_reg(x)?;
-                        match n {
-                            0x6 => {
-                                self.set_reg(x as usize, vx >> 1)?;
-                                self.set_reg(0xF, vx & 0x1)?;
-                            }
-                            0xE => {
-                                self.set_reg(x as usize, vx << 1)?;
-                                self.set_reg(0xF, vx >> 7)?;
-                            }
-                            _ => {
-                                unreachable!()
-                            }
-                        }
-                    }
-                    _ => bail!("Unimplemented binary register operation {:#x}", n),
-                }
+            OnError::Halt => {
+                error!("Halting, the instruction at {address:#05x} failed: {err:#}");
+                self.halted = true;
+                Ok(())
             }
-            // SET INDEX REGISTER
-            (0xA, ..) => {
-                trace!("Setting index register");
-                self.set_index(nib_nnn)?;
+        }
+    }
+
+    /// Execute a single instruction
+    fn execute(&mut self) -> Result<()> {
+        // Remember where the instruction is, for tracing
+        self.instruction_address = self.program_counter;
+        // Gets the instruction, increments the program counter, a program counter
+        // outside of memory has no instruction to skip
+        let (instruction_byte1, instruction_byte2) = self
+            .fetch()
+            .context(Unrecoverable("Fetching the next instruction"))?;
+        let opcode = u16::from_be_bytes([instruction_byte1, instruction_byte2]);
+        self.stats.record_opcode(opcode);
+        if let Some(compat) = self.compat.as_mut() {
+            compat.record_instruction(opcode);
+        }
+        if let Some(sink) = self.access_sink.as_mut() {
+            for address in self.instruction_address..self.instruction_address + INSTRUCTION_LENGTH {
+                sink(MemoryAccess {
+                    address,
+                    kind: AccessKind::Execute,
+                });
             }
-            // JUMP WITH OFFSET
+        }
+        self.last_cost = self
+            .cycle_costs
+            .as_ref()
+            .map_or(1, |costs| costs.cost(opcode).into());
+
+        // Decode the instruction into various nibbles (half bytes), other values
+        let nib1 = (instruction_byte1) >> 4; // Used to determine instruction type
+        let nib_x = instruction_byte1 & 0x0F; // Used for register address
+        let nib_y = (instruction_byte2) >> 4; // Used for register address
+        let nib_n = instruction_byte2 & 0x0F; // 4 bit number
+        debug_assert!(
+            nib_x <= 0xF,
+            "Value of X was greater than the number of registers"
+        );
+        debug_assert!(
+            nib_y <= 0xF,
+            "Value of Y was greater than the number of registers"
+        );
+        debug_assert!(nib_n <= 0xF, "Value of the last half-byte was too large");
+        // Other bit combinations used, not really nibbles but convenient prefix
+        let nib_nn = instruction_byte2; // 8-bit immediate number (not index)
+        let nib_nnn: u16 = ((nib_x as u16) << 8) | ((nib_y as u16) << 4) | (nib_n as u16);
+        // Match on the instruction (breaking it down by half-bytes as that
+        // is how instructions are distinguished)
+        let _: () = match (nib1, nib_x, nib_y, nib_n) {
+            // CLEAR, only the selected planes (see XO-CHIP's plane FN01)
+            (0x0, 0x0, 0xE, 0x0) => {
+                trace!("Clear instruction");
+                let selected = self.display.selected_planes();
+                self.display.clear_planes(selected)?;
+                self.display.needs_redraw = true;
+            }
+            // JUMP
+            (0x1, ..) => {
+                trace!("Jump instruction");
+                self.jump(nib_nnn as usize)?;
+            }
+            // SUBROUTINE
+            (0x2, ..) => {
+                trace!("Go to subroutine");
+                // Push pc onto stack for returning from subroutine
+                self.stack_push(self.program_counter as u16)?;
+                // Jump to destination
+                self.jump(nib_nnn as usize)?;
+            }
+            // RETURN
+            (0x0, 0x0, 0xE, 0xE) => {
+                trace!("Return from subroutine");
+                let dest = self
+                    .stack_pop()
+                    .context("Returning (00EE) without a subroutine to return from")?
+                    as usize;
+                self.jump(dest)?;
+            }
+            // EXIT (SUPER-CHIP)
+            (0x0, 0x0, 0xF, 0xD) => {
+                trace!("Exit instruction");
+                self.halted = true;
+            }
+            // LOW RESOLUTION (SUPER-CHIP)
+            (0x0, 0x0, 0xF, 0xE) => {
+                trace!("Low resolution instruction");
+                self.set_high_res(false)?;
+            }
+            // HIGH RESOLUTION (SUPER-CHIP)
+            (0x0, 0x0, 0xF, 0xF) => {
+                trace!("High resolution instruction");
+                self.set_high_res(true)?;
+            }
+            // CONDITIONAL JUMPS
+            (0x3, x, ..) => {
+                trace!("Jump if VX==NN");
+                // If value of register VX is equal to NN, skip next instruction
+                if self.get_reg(x)? == nib_nn {
+                    self.program_counter += INSTRUCTION_LENGTH;
+                }
+            }
+            (0x4, x, ..) => {
+                trace!("Jump if VX!=NN");
+                // If value of register VX is NOT equal to NN, skip next instruction
+                if self.get_reg(x)? != nib_nn {
+                    self.program_counter += INSTRUCTION_LENGTH;
+                }
+            }
+            (0x5, x, y, ..) => {
+                trace!("Jump if VX==VY");
+                // If value at VX == value at VY, skip next instruction
+                if self.get_reg(x)? == self.get_reg(y)? {
+                    self.program_counter += INSTRUCTION_LENGTH;
+                }
+            }
+            (0x9, x, y, ..) => {
+                trace!("Jump if VX!=VY");
+                // If value at VX != value at VY, skip next instruction
+                if self.get_reg(x)? != self.get_reg(y)? {
+                    self.program_counter += INSTRUCTION_LENGTH;
+                }
+            }
+            // SET REGISTER
+            (0x6, x, ..) => {
+                trace!("Set register");
+                self.set_reg(x as usize, nib_nn)?;
+            }
+            // ADD TO REGISTER
+            (0x7, x, ..) => {
+                trace!("Add to register");
+                let vx = self.get_reg(x)?;
+                let (res, _) = vx.overflowing_add(nib_nn);
+                self.set_reg(x as usize, res)?;
+            }
+            // ARITHMETIC/LOGICAL OPERATIONS
+            // SET
+            (0x8, x, y, 0x0) => {
+                trace!("Set VX to VY");
+                let vy = self.get_reg(y)?;
+                self.set_reg(x as usize, vy)?;
+            }
+            // BINARY REGISTER OPS
+            (0x8, x, y, n) => {
+                trace!("Binary register operation");
+                let vx = self.get_reg(x)?;
+                let vy = self.get_reg(y)?;
+                match n {
+                    0x1 => {
+                        trace!("Binary OR");
+                        self.set_reg(x as usize, vx | vy)?;
+                    }
+                    0x2 => {
+                        trace!("Binary AND");
+                        self.set_reg(x as usize, vx & vy)?;
+                    }
+                    0x3 => {
+                        trace!("Binary XOR");
+                        self.set_reg(x as usize, vx ^ vy)?;
+                    }
+                    0x4 => {
+                        trace!("Add with overflow");
+                        let (res, carry) = vx.overflowing_add(vy);
+                        self.set_reg(x as usize, res)?;
+                        self.set_reg(0xF, carry.into())?;
+                    }
+                    0x5 => {
+                        trace!("Sub with overflow VX - VY");
+                        let (res, carry) = vx.overflowing_sub(vy);
+                        self.set_reg(x as usize, res)?;
+                        self.set_reg(0xF, (!carry).into())?;
+                    }
+                    0x7 => {
+                        trace!("Sub with overflow VY - VX");
+                        let (res, carry) = vy.overflowing_sub(vx);
+                        self.set_reg(x as usize, res)?;
+                        self.set_reg(0xF, (!carry).into())?;
+                    }
+                    0x6 | 0xE => {
+                        trace!("Shift operations");
+                        // Shifted value and flag
+                        let shift = |value: u8| match n {
+                            0x6 => (value >> 1, value & 0x1),
+                            _ => (value << 1, value >> 7),
+                        };
+                        // NOTE: Setting VX to VY is different between COSMAC and CHIP-48
+                        let (source, other) = if self.config.shift_use_vy {
+                            (vy, vx)
+                        } else {
+                            (vx, vy)
+                        };
+                        let (res, flag) = shift(source);
+                        if source != other {
+                            self.quirk_hit(Quirk::ShiftUseVy, || {
+                                let (other_res, other_flag) = shift(other);
+                                (
+                                    format!("V{x:X} = {res:#x}, VF = {flag:#x}"),
+                                    format!("V{x:X} = {other_res:#x}, VF = {other_flag:#x}"),
+                                )
+                            });
+                        }
+                        self.set_reg(x as usize, res)?;
+                        self.set_reg(0xF, flag)?;
+                    }
+                    // 8XY8 to 8XYD and 8XYF aren't instructions
+                    _ => return Err(self.unknown_instruction(opcode)),
+                }
+            }
+            // SET INDEX REGISTER
+            (0xA, ..) => {
+                trace!("Setting index register");
+                self.set_index(nib_nnn)?;
+            }
+            // JUMP WITH OFFSET
             (0xB, x, ..) => {
                 trace!("Jumping with offset");
                 // COSMAC jumped to NNN+V0, later jumped to NN+VX
-                let dest = if self.config.jump_offset_use_v0 {
-                    nib_nnn + self.get_reg(0x0)? as u16
+                let (dest, other) = if self.config.jump_offset_use_v0 {
+                    (self.get_reg(0x0)?, self.get_reg(x)?)
                 } else {
-                    nib_nnn + self.get_reg(x)? as u16
+                    (self.get_reg(x)?, self.get_reg(0x0)?)
                 };
+                let dest = nib_nnn + dest as u16;
+                let other = nib_nnn + other as u16;
+                if dest != other {
+                    self.quirk_hit(Quirk::JumpOffsetUseV0, || {
+                        (
+                            format!("a jump to {dest:#05x}"),
+                            format!("a jump to {other:#05x}"),
+                        )
+                    });
+                }
                 self.program_counter = dest as usize;
             }
             // RAND
             (0xC, x, ..) => {
                 trace!("Getting random number");
                 // Get a random u8
-                let rand: u8 = (self.rng.next_u32() >> (32 - 8)).try_into()?;
+                let rand = (self.rng.next_u32() >> (32 - 8)) as u8;
                 // AND with the value NN
                 self.set_reg(x as usize, rand & nib_nn)?;
             }
             // DISPLAY
             (0xD, x, y, n) => {
                 trace!("Drawing sprite");
+                self.stats.draws += 1;
                 self.draw_sprite(
                     self.get_index()?.into(),
                     n as usize,
@@ -450,7 +2197,7 @@ impl<'a> Emulator<'a> {
                 let current_timer: u8;
                 // Lock and release as fast as possible, just grab the value
                 {
-                    current_timer = self.delay_timer.lock().unwrap().to_owned();
+                    current_timer = self.delay_timer.load(Ordering::Relaxed);
                 }
                 self.set_reg(x.into(), current_timer)?;
             }
@@ -459,7 +2206,7 @@ impl<'a> Emulator<'a> {
                 trace!("Set delay timer");
                 let new_delay = self.get_reg(x)?;
                 {
-                    *self.delay_timer.lock().unwrap() = new_delay;
+                    self.delay_timer.store(new_delay, Ordering::Relaxed);
                 }
             }
             // SET SOUND TIMER
@@ -467,7 +2214,7 @@ impl<'a> Emulator<'a> {
                 trace!("Set sound timer");
                 let new_delay = self.get_reg(x)?;
                 {
-                    *self.sound_timer.lock().unwrap() = new_delay;
+                    self.sound_timer.store(new_delay, Ordering::Relaxed);
                 }
             }
             // ADD TO INDEX
@@ -486,7 +2233,7 @@ impl<'a> Emulator<'a> {
                 match self.waiting_for_key_release {
                     Some(key) => {
                         // Check if key is being pressed
-                        if self.frontend.check_key(key)? {
+                        if self.check_key(key)? {
                             // Still waiting on release, don't step yet
                             self.program_counter -= INSTRUCTION_LENGTH;
                         } else {
@@ -501,7 +2248,7 @@ impl<'a> Emulator<'a> {
                         let mut key_pressed = None;
                         // Check if any of the keys are pressed
                         for key in 0x0..=0xF {
-                            if self.frontend.check_key(key)? {
+                            if self.check_key(key)? {
                                 key_pressed = Some(key);
                                 break;
                             }
@@ -532,11 +2279,13 @@ impl<'a> Emulator<'a> {
                 let idx = self.get_index()?;
                 // Extract decimal
                 for i in 0..3 {
+                    let dest = idx as usize + 2 - (i as usize);
                     *(self
                         .memory
-                        .get_mut(idx as usize + 2 - (i as usize))
+                        .get_mut(dest)
                         .context("Memory access during binary decimal conversion")?) =
                         ((vx as u32 % 10u32.pow(i + 1)) / (10u32.pow(i))) as u8;
+                    self.record_write(dest);
                 }
             }
             // STORE REGISTERS
@@ -549,650 +2298,3103 @@ impl<'a> Emulator<'a> {
                         "Trying to store register {:#x} into memory at invalid address {:#x}",
                         x, dest,
                     ))?) = self.get_reg(reg)?;
+                    self.record_write(dest);
+                }
+                self.update_index_after_memory(idx as u16, x)?;
+            }
+            // SELECT PLANES (XO-CHIP), the mask of the planes drawn to and
+            // cleared
+            (0xF, mask, 0x0, 0x1) => {
+                trace!("Select planes");
+                self.display.select_planes(mask)?;
+            }
+            // LOAD REGISTERS
+            (0xF, x, 0x6, 0x5) => {
+                trace!("Load registers");
+                let idx = self.get_index()? as usize;
+                for reg in 0..=x {
+                    let source = idx + reg as usize;
+                    self.record_read(source, AccessKind::Load);
+                    self.set_reg(
+                        reg.into(),
+                        *(self.memory.get(source).context(format!(
+                            "Trying to load memory at invalid address {:#x} into register {:#x}",
+                            source, x,
+                        ))?),
+                    )?;
+                }
+                self.update_index_after_memory(idx as u16, x)?;
+            }
+            _ => return Err(self.unknown_instruction(opcode)),
+        };
+        Ok(())
+    }
+    /// Move the index register past the `x + 1` registers stored or loaded at
+    /// `idx`, with the store_memory_update_index quirk
+    fn update_index_after_memory(&mut self, idx: u16, x: u8) -> Result<()> {
+        let updated = idx + x as u16 + 1;
+        let enabled = self.config.store_memory_update_index;
+        self.quirk_hit(Quirk::StoreMemoryUpdateIndex, || {
+            let (result, alternative) = (format!("I = {updated:#05x}"), format!("I = {idx:#05x}"));
+            if enabled {
+                (result, alternative)
+            } else {
+                (alternative, result)
+            }
+        });
+        if enabled {
+            self.set_index(updated)?;
+        }
+        Ok(())
+    }
+
+    /// Add a value to the stack
+    fn stack_push(&mut self, value: u16) -> Result<()> {
+        if self.stack.len() >= self.config.stack_depth {
+            let recent = self.stack[self.stack.len().saturating_sub(STACK_TRACE_REPORT_LENGTH)..]
+                .iter()
+                .map(|address| format!("{address:#05x}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!(
+                "Stack overflow! Calling {value:#05x} with {} entries on the stack (the \
+                stack_depth setting), most recent calls returning to (oldest first): {recent}",
+                self.stack.len()
+            );
+        }
+        self.stack.push(value);
+        self.stats.max_stack_depth = self.stats.max_stack_depth.max(self.stack.len());
+        Ok(())
+    }
+
+    /// Pop the value off the top of the stack
+    fn stack_pop(&mut self) -> Result<u16> {
+        self.stack.pop().context("Trying to pop from empty stack")
+    }
+
+    /// Load the font into memory starting at FONT_START_POSITION
+    fn load_font(&mut self) -> Result<()> {
+        self.load_bytes(&FONT, FONT_START_POSITION)
+            .context("Loading font into memory")
+    }
+
+    fn load_bytes(&mut self, bytes: &[u8], start_position: usize) -> Result<()> {
+        // Iterate through the file, moving each byte into memory
+        for (memory_index, &byte) in (start_position..).zip(bytes) {
+            *(self
+                .memory
+                .get_mut(memory_index)
+                .context("Insufficient memory to hold game file")?) = byte;
+        }
+        if let Some(compat) = self.compat.as_mut() {
+            compat.record_load(start_position..start_position + bytes.len());
+        }
+        Ok(())
+    }
+
+    /// Draw a sprite to the screen
+    ///
+    /// Starting from the byte in memory at sprite_index, with length/height sprite_length,
+    /// draw the sprite at the row given by y_pos, and the columns given by x_pos.
+    /// Parts of the sprite past the right or bottom edge are clipped, or wrap around
+    /// to the other side with the wrap_x and wrap_y quirks.
+    ///
+    /// In high resolution a sprite_length of 0 (DXY0) draws a 16x16 sprite, two
+    /// bytes per row.
+    fn draw_sprite(
+        &mut self,
+        sprite_index: usize,
+        sprite_length: usize,
+        x_pos: usize,
+        y_pos: usize,
+    ) -> Result<()> {
+        let (rows, cols) = (self.display.rows(), self.display.cols());
+        // The x and y coordinates are allowed to wrap
+        let x_pos = x_pos % cols;
+        let y_pos = y_pos % rows;
+        let (sprite_rows, row_bytes) = if sprite_length == 0 && self.display.is_high_res() {
+            (LARGE_SPRITE_SIZE, LARGE_SPRITE_SIZE / SPRITE_WIDTH)
+        } else {
+            (sprite_length, 1)
+        };
+        // Track how many bits were turned OFF
+        let mut turned_off: u32 = 0;
+        // Whether the sprite reached the bottom or right edge, where the wrap
+        // quirks matter
+        let (mut crossed_bottom, mut crossed_right) = (false, false);
+
+        // Each selected plane (see FN01) is drawn with the next sprite's worth
+        // of bytes, a single plane display just draws the one
+        let selected = self.display.selected_planes();
+        let planes = (0..self.display.num_planes()).filter(|plane| selected & (1 << plane) != 0);
+        let plane_sprites = (sprite_index..).step_by((sprite_rows * row_bytes).max(1));
+        for (plane_sprite, plane) in plane_sprites.zip(planes) {
+            // Loop through the sprite, XORing with the display bits
+            let sprite_row_indices = (plane_sprite..).step_by(row_bytes);
+            for (cur_index, row_offset) in sprite_row_indices.zip(0..sprite_rows) {
+                // If off bottom of screen, wrap to the top, or stop trying to draw
+                let row = y_pos + row_offset;
+                crossed_bottom |= row >= rows;
+                let row = if row < rows {
+                    row
+                } else if self.config.wrap_y {
+                    row % rows
+                } else {
+                    break;
+                };
+                // Get the bytes for the current row of the sprite, the first
+                // byte in the top bits
+                let mut sprite_bits: u16 = 0;
+                for byte_index in cur_index..cur_index + row_bytes {
+                    self.record_read(byte_index, AccessKind::Sprite);
+                    let byte = self
+                        .memory
+                        .get(byte_index)
+                        .context("Trying to get byte in sprite")?;
+                    sprite_bits = (sprite_bits << SPRITE_WIDTH) | u16::from(*byte);
                 }
-                if self.config.store_memory_update_index {
-                    self.set_index(idx as u16 + x as u16 + 1)?;
+                let sprite_width = row_bytes * SPRITE_WIDTH;
+                sprite_bits <<= u16::BITS as usize - sprite_width;
+                for col_offset in 0..sprite_width {
+                    // Wrap to the left edge, or stop trying to draw, if going off-screen
+                    let col = x_pos + col_offset;
+                    crossed_right |= col >= cols;
+                    let col = if col < cols {
+                        col
+                    } else if self.config.wrap_x {
+                        col % cols
+                    } else {
+                        break;
+                    };
+                    // XOR the display bit with the value of the sprite at this index
+                    // offset (tracked by shifting the sprite bits to the left)
+                    if self
+                        .display
+                        .xor_plane(plane, row, col, (sprite_bits & 0x8000) == 0x8000)?
+                    {
+                        turned_off += 1;
+                    }
+                    // Shift the sprite_bits, which will result in the bit of interest being
+                    // at the most significant position
+                    sprite_bits <<= 1;
                 }
             }
-            // LOAD REGISTERS
-            (0xF, x, 0x6, 0x5) => {
-                trace!("Load registers");
-                let idx = self.get_index()? as usize;
-                for reg in 0..=x {
-                    let source = idx + reg as usize;
-                    self.set_reg(
-                        reg.into(),
-                        *(self.memory.get(source).context(format!(
-                            "Trying to load memory at invalid address {:#x} into register {:#x}",
-                            source, x,
-                        ))?),
-                    )?;
-                }
-                if self.config.store_memory_update_index {
-                    self.set_index(idx as u16 + x as u16 + 1)?;
-                }
+        }
+        for (quirk, crossed, wrapped, clipped) in [
+            (
+                Quirk::WrapY,
+                crossed_bottom,
+                "wrapping to the top",
+                "clipped at the bottom",
+            ),
+            (
+                Quirk::WrapX,
+                crossed_right,
+                "wrapping to the left",
+                "clipped at the right",
+            ),
+        ] {
+            if crossed {
+                let enabled = quirk.is_enabled(&self.config);
+                self.quirk_hit(quirk, || {
+                    let sprite = format!("a sprite at ({x_pos}, {y_pos})");
+                    let (result, alternative) = if enabled {
+                        (wrapped, clipped)
+                    } else {
+                        (clipped, wrapped)
+                    };
+                    (
+                        format!("{sprite} {result}"),
+                        format!("{sprite} {alternative}"),
+                    )
+                });
+            }
+        }
+        self.last_collision_count = turned_off;
+        if turned_off > 0 {
+            self.collisions_this_frame += 1;
+            self.stats.total_collisions += 1;
+        }
+        self.set_reg(0xF, (turned_off > 0).into())?;
+        Ok(())
+    }
+
+    /// Check if the `key` is currently pressed (or was at the start of the frame,
+    /// when polling the keys once per frame)
+    ///
+    /// Values past the keypad (VX above 0xF in EX9E/EXA1) are never pressed,
+    /// frontends only get asked about keys 0x0 to 0xF.
+    fn check_key(&mut self, key: u8) -> Result<bool> {
+        if usize::from(key) >= KEYPAD_SIZE {
+            return Ok(false);
+        }
+        if let Some(keys) = self.frame_keys {
+            return Ok(keys & (1 << key) != 0);
+        }
+        if self.held_keys & (1 << key) != 0 {
+            return Ok(true);
+        }
+        self.frontend
+            .check_key(key)
+            .context(FrontendFailure("Frontend failed checking a key"))
+    }
+
+    /// Jump to provided destination
+    fn jump(&mut self, dest: usize) -> Result<()> {
+        self.program_counter = dest;
+        Ok(())
+    }
+
+    /// Get the value in register `register`
+    fn get_reg(&self, register: u8) -> Result<u8> {
+        Ok(self
+            .registers
+            .get(register as usize)
+            .context(format!("Trying to get value at register {register:#x}"))?
+            .to_owned())
+    }
+
+    /// Set the value in register `register` to `value`
+    fn set_reg(&mut self, register: usize, value: u8) -> Result<()> {
+        // Bounds check to indicate panic
+        if register >= NUM_REGISTERS {
+            bail!("Trying to get value at register {register:#x}")
+        }
+        let old = self.registers[register];
+        self.registers[register] = value;
+        if old != value {
+            self.trace_register(Register::V(register as u8), old.into(), value.into());
+        }
+        Ok(())
+    }
+
+    // /// Add the value in register `register` to `value`
+    // fn add_reg(&mut self, register: usize, value: u8) -> Result<()> {
+    //     // Bounds check to indicate panic
+    //     if register >= NUM_REGISTERS {
+    //         bail!("Trying to get value at register {register:#x}")
+    //     };
+    //     self.registers[register] += value;
+    //     Ok(())
+    // }
+
+    /// Set the value of the index register
+    fn set_index(&mut self, value: u16) -> Result<()> {
+        let old = self.index_register;
+        self.index_register = value;
+        if old != value {
+            self.trace_register(Register::I, old, value);
+        }
+        Ok(())
+    }
+
+    /// Record a change in a register's value, if `trace_registers` is enabled
+    fn trace_register(&mut self, register: Register, old: u16, new: u16) {
+        if !self.config.trace_registers {
+            return;
+        }
+        let change = RegisterChange {
+            register,
+            old,
+            new,
+            pc: self.instruction_address as u16,
+        };
+        debug!("Register change {change}");
+        if let Some(sink) = self.register_sink.as_mut() {
+            sink(change);
+        }
+    }
+
+    /// Record that the current instruction's result depended on `quirk`, in the
+    /// log and the stats if `log_quirk_hits` is enabled, with `describe` giving
+    /// what it did and what it would have done with the quirk the other way
+    fn quirk_hit(&mut self, quirk: Quirk, describe: impl FnOnce() -> (String, String)) {
+        if let Some(compat) = self.compat.as_mut() {
+            compat.record_quirk_hit(quirk, quirk.is_enabled(&self.config));
+        }
+        if !self.config.log_quirk_hits {
+            return;
+        }
+        *self
+            .stats
+            .quirk_hits
+            .entry(quirk.name().to_string())
+            .or_default() += 1;
+        let (result, alternative) = describe();
+        let hit = QuirkHit {
+            quirk,
+            enabled: quirk.is_enabled(&self.config),
+            result,
+            alternative,
+            pc: self.instruction_address as u16,
+        };
+        debug!("Quirk hit {hit}");
+        if let Some(sink) = self.quirk_sink.as_mut() {
+            sink(hit);
+        }
+    }
+
+    /// Record a write to `address` in the compatibility report and the access
+    /// sink, if enabled
+    fn record_write(&mut self, address: usize) {
+        if let Some(compat) = self.compat.as_mut() {
+            compat.record_write(address);
+        }
+        if let Some(sink) = self.access_sink.as_mut() {
+            sink(MemoryAccess {
+                address,
+                kind: AccessKind::Store,
+            });
+        }
+    }
+
+    /// Record a read of `address` (as a `kind` of access) in the compatibility
+    /// report and the access sink, if enabled
+    fn record_read(&mut self, address: usize, kind: AccessKind) {
+        if let Some(compat) = self.compat.as_mut() {
+            compat.record_read(address);
+        }
+        if let Some(sink) = self.access_sink.as_mut() {
+            sink(MemoryAccess { address, kind });
+        }
+    }
+
+    /// The error for the unknown `opcode` at the current instruction, recorded
+    /// for the compatibility report, handled by the [OnError] mode like any other
+    /// failed instruction
+    fn unknown_instruction(&mut self, opcode: u16) -> anyhow::Error {
+        self.record_unknown_opcode(opcode);
+        anyhow!(
+            "Unknown instruction {opcode:04X} at {:#05x}",
+            self.instruction_address
+        )
+    }
+
+    /// Record an unknown `opcode` in the compatibility report, if enabled
+    fn record_unknown_opcode(&mut self, opcode: u16) {
+        if let Some(compat) = self.compat.as_mut() {
+            compat.record_unknown_opcode(opcode);
+        }
+    }
+
+    /// Get the value of the index register
+    fn get_index(&self) -> Result<u16> {
+        Ok(self.index_register)
+    }
+
+    /// Fetch the current instruction (incrementing the program counter appropriately)
+    fn fetch(&mut self) -> Result<(u8, u8)> {
+        let b1 = self
+            .memory
+            .get(self.program_counter)
+            .context("Trying to fetch first byte of instruction")?
+            .to_owned();
+        let b2 = self
+            .memory
+            .get(self.program_counter + 1)
+            .context("Trying to fetch second byte of instruction")?
+            .to_owned();
+        self.program_counter += INSTRUCTION_LENGTH;
+        Ok((b1, b2))
+    }
+}
+
+#[cfg(test)]
+mod test_emulator {
+    use super::*;
+
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use crate::display::{DISPLAY_COLS, DISPLAY_ROWS, HIGH_RES_COLS, HIGH_RES_ROWS};
+    use crate::headless_frontend::HeadlessFrontend;
+    use crate::menu::MenuInput;
+    use crate::{config::EmulatorConfig, noop_frontend::NoOpFrontend};
+
+    #[test]
+    /// Test creating the emulator
+    fn test_create() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let _test_eml8r = Emulator::new(Box::new(test_frontend), test_config)?;
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that the emulator refuses an invalid configuration
+    fn test_create_invalid_config() {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig {
+            instructions_per_second: 0,
+            ..Default::default()
+        };
+        assert!(Emulator::new(Box::new(test_frontend), test_config).is_err());
+    }
+
+    #[test]
+    /// Test clearing the display
+    fn test_clear() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+
+        // Artifically set some cells of the display
+        test_emul8r.display.set(0, 0, true)?;
+        test_emul8r.display.set(10, 20, true)?;
+        test_emul8r.display.set(3, 5, true)?;
+
+        // Set the first instruction to be clear
+        #[allow(clippy::identity_op)]
+        {
+            test_emul8r.memory[test_emul8r.program_counter] = (0x0 << 4) | 0x0;
+            test_emul8r.memory[test_emul8r.program_counter + 1] = (0xE << 4) | 0x0;
+        }
+        // Run the single instruction
+        test_emul8r.execute()?;
+
+        // Check that the display has been cleared
+        for &cell in test_emul8r.display.iter_cells() {
+            assert!(!cell);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test selecting planes (FN01) to draw on and clear with num_planes set
+    fn test_select_planes() -> Result<()> {
+        let test_config = EmulatorConfig {
+            num_planes: 2,
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        assert_eq!(test_emul8r.display.num_planes(), 2);
+        // Select both planes and draw the two sprite bytes after the code, one
+        // per plane, then select just the second plane and clear it
+        test_emul8r.load_rom(&[
+            0xA2, 0x0C, 0xF3, 0x01, 0xD0, 0x11, 0xF2, 0x01, 0x00, 0xE0, 0x12, 0x0A, 0x80, 0x40,
+        ])?;
+        for _ in 0..3 {
+            test_emul8r.step()?;
+        }
+        assert_eq!(test_emul8r.display.color_index(0, 0)?, 0b01);
+        assert_eq!(test_emul8r.display.color_index(0, 1)?, 0b10);
+        for _ in 0..2 {
+            test_emul8r.step()?;
+        }
+        assert_eq!(test_emul8r.display.color_index(0, 0)?, 0b01);
+        assert_eq!(test_emul8r.display.color_index(0, 1)?, 0);
+
+        // Planes the display doesn't have can't be selected
+        test_emul8r.load_rom(&[0xF4, 0x01])?;
+        test_emul8r.jump(GAME_MEMORY_START)?;
+        assert!(test_emul8r.step().is_err());
+        // Resetting keeps the configured planes
+        test_emul8r.reset()?;
+        assert_eq!(test_emul8r.display.num_planes(), 2);
+        Ok(())
+    }
+
+    #[test]
+    /// Test clearing the screen only clears the selected plane of a display
+    /// with two planes
+    fn test_clear_selected_plane() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        test_emul8r.display = Display::with_planes(2)?;
+        test_emul8r.display.xor_plane(0, 3, 5, true)?;
+        test_emul8r.display.xor_plane(1, 10, 20, true)?;
+        test_emul8r.display.select_planes(0b10)?;
+
+        test_emul8r.load_rom(&[0x00, 0xE0])?;
+        test_emul8r.execute()?;
+
+        assert!(test_emul8r.display.get_plane(0, 3, 5)?);
+        assert!(!test_emul8r.display.get_plane(1, 10, 20)?);
+        Ok(())
+    }
+
+    #[test]
+    /// Test the stack memory
+    fn test_stack() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+
+        // Check that the stack is empty
+        assert!(test_emul8r.stack.is_empty());
+
+        // Push some numbers onto the stack
+        test_emul8r.stack_push(5)?;
+        test_emul8r.stack_push(10)?;
+        test_emul8r.stack_push(1)?;
+        test_emul8r.stack_push(0)?;
+        test_emul8r.stack_push(50)?;
+
+        // Check that stack top has moved forward/up
+        assert_eq!(test_emul8r.stack.len(), 5);
+
+        // Check popping is correct
+        assert_eq!(test_emul8r.stack_pop()?, 50);
+        assert_eq!(test_emul8r.stack_pop()?, 0);
+        assert_eq!(test_emul8r.stack_pop()?, 1);
+        assert_eq!(test_emul8r.stack_pop()?, 10);
+        assert_eq!(test_emul8r.stack_pop()?, 5);
+
+        // Make sure the stack pointer has gone back to 0
+        assert!(test_emul8r.stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test the stack overflows at the configured depth, and the deepest it got
+    /// is tracked
+    fn test_stack_depth() -> Result<()> {
+        let call_depth = |stack_depth| -> Result<usize> {
+            let test_config = EmulatorConfig {
+                stack_depth,
+                ..EmulatorConfig::default()
+            };
+            let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+            // Call 0x200 forever
+            test_emul8r.load_rom(&[0x22, 0x00])?;
+            let err = loop {
+                if let Err(err) = test_emul8r.step() {
+                    break err;
+                }
+            };
+            assert!(format!("{err:#}").contains("Stack overflow"), "{err:#}");
+            assert_eq!(test_emul8r.stack_trace().len(), stack_depth);
+            Ok(test_emul8r.stats().max_stack_depth)
+        };
+        assert_eq!(call_depth(12)?, 12);
+        assert_eq!(call_depth(128)?, 128);
+
+        // The high-water mark stays after returning
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        for address in [0x202, 0x204, 0x206] {
+            test_emul8r.stack_push(address)?;
+        }
+        test_emul8r.stack_pop()?;
+        test_emul8r.stack_pop()?;
+        test_emul8r.stack_push(0x208)?;
+        assert_eq!(test_emul8r.stats().max_stack_depth, 3);
+        test_emul8r.reset()?;
+        assert!(test_emul8r.stack_trace().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    /// Test getting the active stack entries
+    fn test_stack_trace() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        assert!(test_emul8r.stack_trace().is_empty());
+
+        test_emul8r.stack_push(0x202)?;
+        test_emul8r.stack_push(0x30A)?;
+        test_emul8r.stack_push(0x456)?;
+        assert_eq!(test_emul8r.stack_trace(), vec![0x202, 0x30A, 0x456]);
+
+        // Popped entries are no longer part of the trace
+        test_emul8r.stack_pop()?;
+        assert_eq!(test_emul8r.stack_trace(), vec![0x202, 0x30A]);
+
+        // Overflowing reports the most recent entries
+        let depth = test_emul8r.config.stack_depth;
+        for address in 0..(depth - 2) as u16 {
+            test_emul8r.stack_push(0x200 + address * 2)?;
+        }
+        let message = test_emul8r.stack_push(0xABC).unwrap_err().to_string();
+        assert!(message.contains("Stack overflow"), "{message}");
+        assert!(message.contains("0xabc"), "{message}");
+        assert!(message.contains(&format!("{:#05x}", 0x200 + (depth - 3) * 2)));
+        assert_eq!(test_emul8r.stack_trace().len(), depth);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test jump instruction
+    fn test_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let jump_dest = 1012u16;
+
+        // Set the first instruction to be clear
+        #[allow(clippy::identity_op)]
+        {
+            let instruction1 = (0x1 << 4) | jump_dest >> 8;
+            let instruction2 = jump_dest & 0xFF;
+
+            test_emul8r.memory[test_emul8r.program_counter] = instruction1 as u8;
+            test_emul8r.memory[test_emul8r.program_counter + 1] = instruction2 as u8;
+        }
+        // Run the single instruction
+        test_emul8r.execute()?;
+
+        // Check that the program counter has been set to 1012
+        assert_eq!(test_emul8r.program_counter, jump_dest as usize);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test subroutines
+    fn test_subroutines() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let jump_dest = 1012u16;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the first instruction to be subroutine jump
+        #[allow(clippy::identity_op)]
+        {
+            let instruction1 = (0x2 << 4) | jump_dest >> 8;
+            let instruction2 = jump_dest & 0xFF;
+
+            test_emul8r.memory[test_emul8r.program_counter] = instruction1 as u8;
+            test_emul8r.memory[test_emul8r.program_counter + 1] = instruction2 as u8;
+        }
+        // Run the single instruction
+        test_emul8r.execute()?;
+
+        // Check that the emulator did jump
+        assert_eq!(test_emul8r.program_counter, jump_dest as usize);
+        // Check that the previous position was put onto the stack
+        assert_eq!(
+            test_emul8r.stack[test_emul8r.stack.len() - 1],
+            initial_position as u16 + 2 // NOTE: Advanced due to stepping through instruction
+        );
+
+        // Set the instruction currently pointed to to be return
+        #[allow(clippy::identity_op)]
+        {
+            let instruction1 = (0x0 << 4) | 0x0;
+            let instruction2 = (0xE << 4) | 0xE;
+
+            test_emul8r.memory[test_emul8r.program_counter] = instruction1 as u8;
+            test_emul8r.memory[test_emul8r.program_counter + 1] = instruction2 as u8;
+        }
+        test_emul8r.execute()?;
+
+        // Check that the emulator did return
+        // // NOTE:+2 due to stepping counter
+        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+
+        // Check that the stack has been emptied
+        assert!(test_emul8r.stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test conditional jump instruction 0x3 (jump if equal)
+    fn test_unary_conditional_jump_equal_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the current instruction to be a conditional jump (version 0x3)
+        let register: u8 = 0x5;
+        let check_val = 0x9;
+        let byte1 = (0x3 << 4) | register;
+        let byte2 = check_val;
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        // Set the register to match the check_val
+        test_emul8r.set_reg(register.into(), check_val)?;
+
+        // Execute the instruction
+        test_emul8r.execute()?;
+
+        // Check that a jump occured
+        assert_eq!(test_emul8r.program_counter, initial_position + 4);
+        Ok(())
+    }
+    #[test]
+    /// Test conditional jump instruction 0x3 (jump if equal)
+    fn test_unary_conditional_jump_equal_no_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the current instruction to be a conditional jump (version 0x3)
+        let register: u8 = 0x5;
+        let check_val = 0x9;
+        let byte1 = (0x3 << 4) | register;
+        let byte2 = check_val;
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        // Set the register to NOT match the check_val
+        test_emul8r.set_reg(register.into(), check_val + 1)?;
+
+        // Execute the instruction
+        test_emul8r.execute()?;
+
+        // Check that a jump didn't occur
+        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+        Ok(())
+    }
+    #[test]
+    /// Test conditional jump instruction 0x4 (jump if not equal)
+    fn test_unary_conditional_jump_not_equal_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the current instruction to be a conditional jump (version 0x3)
+        let register: u8 = 0x5;
+        let check_val = 0x9;
+        let byte1 = (0x4 << 4) | register;
+        let byte2 = check_val;
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        // Set the register to NOT match the check_val
+        test_emul8r.set_reg(register.into(), check_val + 1)?;
+
+        // Execute the instruction
+        test_emul8r.execute()?;
+
+        // Check that a jump occured
+        assert_eq!(test_emul8r.program_counter, initial_position + 4);
+        Ok(())
+    }
+    #[test]
+    /// Test conditional jump instruction 0x4 (jump if equal)
+    fn test_unary_conditional_jump_not_equal_no_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the current instruction to be a conditional jump (version 0x3)
+        let register: u8 = 0x5;
+        let check_val = 0x9;
+        let byte1 = (0x4 << 4) | register;
+        let byte2 = check_val;
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        // Set the register to match the check_val
+        test_emul8r.set_reg(register.into(), check_val)?;
+
+        // Execute the instruction
+        test_emul8r.execute()?;
+
+        // Check that a jump didn't occur
+        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+        Ok(())
+    }
+
+    #[test]
+    /// Test condition jump instruction 0x5 (check if registers equal)
+    fn test_binary_conditional_jump_equal_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the current instruction to be a conditional jump (version 0x3)
+        let register1: u8 = 0x5;
+        let register2: u8 = 0x8;
+        let check_val = 0x9;
+        let byte1 = (0x5 << 4) | register1;
+        let byte2 = register2 << 4;
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        // Set the registers to match the check_val
+        test_emul8r.set_reg(register1.into(), check_val)?;
+        test_emul8r.set_reg(register2.into(), check_val)?;
+
+        // Execute the instruction
+        test_emul8r.execute()?;
+
+        // Check that a jump occured
+        assert_eq!(test_emul8r.program_counter, initial_position + 4);
+        Ok(())
+    }
+
+    #[test]
+    /// Test condition jump instruction 0x5 (check if registers equal)
+    fn test_binary_conditional_jump_equal_no_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the current instruction to be a conditional jump (version 0x3)
+        let register1: u8 = 0x5;
+        let register2: u8 = 0x8;
+        let check_val = 0x9;
+        let byte1 = (0x5 << 4) | register1;
+        let byte2 = register2 << 4;
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        // Set the registers to not match
+        test_emul8r.set_reg(register1.into(), check_val)?;
+        test_emul8r.set_reg(register2.into(), check_val + 1)?;
+
+        // Execute the instruction
+        test_emul8r.execute()?;
+
+        // Check that a jump didn't occur
+        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+        Ok(())
+    }
+
+    #[test]
+    /// Test condition jump instruction 0x9 (check if registers NOT equal)
+    fn test_binary_conditional_jump_not_equal_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the current instruction to be a conditional jump (version 0x3)
+        let register1: u8 = 0x5;
+        let register2: u8 = 0x8;
+        let check_val = 0x9;
+        let byte1 = (0x9 << 4) | register1;
+        let byte2 = register2 << 4;
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        // Set the registers to NOT match
+        test_emul8r.set_reg(register1.into(), check_val)?;
+        test_emul8r.set_reg(register2.into(), check_val + 1)?;
+
+        // Execute the instruction
+        test_emul8r.execute()?;
+
+        // Check that a jump occured
+        assert_eq!(test_emul8r.program_counter, initial_position + 4);
+        Ok(())
+    }
+
+    #[test]
+    /// Test condition jump instruction 0x9 (check if registers NOT equal)
+    fn test_binary_conditional_jump_not_equal_no_jump() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let initial_position = test_emul8r.program_counter;
+
+        // Set the current instruction to be a conditional jump (version 0x3)
+        let register1: u8 = 0x5;
+        let register2: u8 = 0x8;
+        let check_val = 0x9;
+        let byte1 = (0x9 << 4) | register1;
+        let byte2 = register2 << 4;
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        // Set the registers to match
+        test_emul8r.set_reg(register1.into(), check_val)?;
+        test_emul8r.set_reg(register2.into(), check_val)?;
+
+        // Execute the instruction
+        test_emul8r.execute()?;
+
+        // Check that a jump didn't occur
+        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+        Ok(())
+    }
+
+    #[test]
+    /// Test setting a register
+    fn test_set_register() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let register: u8 = 0x5;
+        let value: u8 = 0xF;
+
+        let byte1 = (0x6 << 4) | register;
+        let byte2 = value;
+
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        test_emul8r.execute()?;
+
+        assert_eq!(test_emul8r.get_reg(register)?, value);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test adding a value to a register
+    fn test_add_register() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let register: u8 = 0x5;
+        let value: u8 = 0x2;
+        let to_add: u8 = 0x3;
+
+        test_emul8r.set_reg(register as usize, value)?;
+
+        let byte1 = (0x7 << 4) | register;
+        let byte2 = to_add;
+
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        test_emul8r.execute()?;
+
+        assert_eq!(test_emul8r.get_reg(register)?, value + to_add);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test setting one register to the value of another
+    fn test_set_register_to_register() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+
+        let x: u8 = 0x2;
+        let y: u8 = 0xF;
+        let vx: u8 = 0x9;
+        let vy: u8 = 0x2;
+
+        let byte1 = (0x8 << 4) | x;
+        let byte2 = y << 4;
+
+        test_emul8r.memory[test_emul8r.program_counter] = byte1;
+        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+
+        test_emul8r.registers[x as usize] = vx;
+        test_emul8r.registers[y as usize] = vy;
+
+        test_emul8r.execute()?;
+
+        assert_eq!(test_emul8r.registers[x as usize], vy);
+        assert_eq!(test_emul8r.registers[y as usize], vy);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test counting the pixels that collide when drawing sprites
+    fn test_collision_count() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+
+        // Draw the font character 0, nothing to collide with yet
+        test_emul8r.draw_sprite(FONT_START_POSITION, FONT_HEIGHT, 0, 0)?;
+        assert_eq!(test_emul8r.last_collision_count(), 0);
+        assert_eq!(test_emul8r.get_reg(0xF)?, 0);
+
+        // Draw the font character 1 over it, which shares 4 lit pixels
+        // (1 in the top row, and 3 in the bottom row)
+        test_emul8r.draw_sprite(FONT_START_POSITION + FONT_HEIGHT, FONT_HEIGHT, 0, 0)?;
+        assert_eq!(test_emul8r.last_collision_count(), 4);
+        assert_eq!(test_emul8r.get_reg(0xF)?, 1);
+
+        // Drawing somewhere empty resets the count, and VF
+        test_emul8r.draw_sprite(FONT_START_POSITION, FONT_HEIGHT, 20, 10)?;
+        assert_eq!(test_emul8r.last_collision_count(), 0);
+        assert_eq!(test_emul8r.get_reg(0xF)?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test counting the draws which collide in each frame, and in the run
+    fn test_collisions_per_frame() -> Result<()> {
+        let test_config = EmulatorConfig {
+            synchronous_timers: true,
+            log_collisions: true,
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        // Draw the font character 0 four times, each second draw erasing it
+        // (colliding), then loop
+        test_emul8r.load_rom(&[
+            0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05, 0xD0, 0x05, 0xD0, 0x05, 0x12, 0x0C,
+        ])?;
+        test_emul8r.run_frame()?;
+        assert_eq!(test_emul8r.collisions_this_frame(), 2);
+        assert_eq!(test_emul8r.stats().collisions_this_frame, 2);
+        assert_eq!(test_emul8r.stats().total_collisions, 2);
+
+        // Only the loop runs in the next frame
+        test_emul8r.run_frame()?;
+        assert_eq!(test_emul8r.collisions_this_frame(), 0);
+        assert_eq!(test_emul8r.stats().collisions_this_frame, 0);
+        assert_eq!(test_emul8r.stats().total_collisions, 2);
+        Ok(())
+    }
+
+    #[test]
+    /// Test the compatibility report records what a program did which matters
+    /// for compatibility
+    fn test_compat_report() -> Result<()> {
+        let test_config = EmulatorConfig {
+            synchronous_timers: true,
+            on_error: "skip_instruction".to_string(),
+            wrap_x: true,
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        assert_eq!(test_emul8r.compat_report(), None);
+        test_emul8r.enable_compat_report();
+        test_emul8r.load_rom(&[
+            0x60, 0x01, // 0x200: V0 = 1
+            0x61, 0x02, // 0x202: V1 = 2
+            0x80, 0x16, // 0x204: V0 = V1 >> 1 (which the shift quirk changes)
+            0xA0, 0x50, // 0x206: I = 0x050
+            0xF1, 0x55, // 0x208: store V0-V1 below the program
+            0xA3, 0x00, // 0x20A: I = 0x300
+            0xD0, 0x02, // 0x20C: draw a sprite from memory never written
+            0x62, 0x3C, // 0x20E: V2 = 60
+            0xF0, 0x29, // 0x210: I = font character 0
+            0xD2, 0x25, // 0x212: draw over the bottom right corner
+            0x22, 0x18, // 0x214: call 0x218
+            0x12, 0x16, // 0x216: loop
+            0xE1, 0xFF, // 0x218: unknown
+            0x00, 0xEE, // 0x21A: return
+        ])?;
+        test_emul8r.run_frame()?;
+        test_emul8r.run_frame()?;
+
+        let report = test_emul8r.compat_report().unwrap();
+        assert_eq!(report.quirk_instructions["8XY6/8XYE (shift)"], 1);
+        assert_eq!(
+            report.quirk_instructions["FX55/FX65 (store/load registers)"],
+            1
+        );
+        assert_eq!(report.quirk_hits["shift_use_vy"], 1);
+        assert_eq!(report.unknown_opcodes["E1FF"], 1);
+        assert_eq!(report.max_stack_depth, 1);
+        assert_eq!(
+            (
+                report.writes_below_program,
+                report.first_write_below_program
+            ),
+            (2, Some(0x050))
+        );
+        assert_eq!(
+            (report.uninitialized_reads, report.first_uninitialized_read),
+            (2, Some(0x300))
+        );
+        // Wrapped at the right edge, clipped at the bottom
+        assert_eq!(report.sprites_wrapped, 1);
+        assert_eq!(report.quirk_hits["wrap_y"], 1);
+        assert_eq!(test_emul8r.stats().compat, Some(report));
+
+        // Memory loaded again on a reset is initialized
+        test_emul8r.reset()?;
+        test_emul8r.run_frame()?;
+        let report = test_emul8r.compat_report().unwrap();
+        assert_eq!(report.uninitialized_reads, 4);
+        Ok(())
+    }
+
+    #[test]
+    /// Test clipping and wrapping a sprite drawn over the bottom right corner,
+    /// with each combination of the wrap quirks
+    fn test_sprite_wrap() -> Result<()> {
+        // A solid 8x4 sprite, drawn 3 columns from the right edge and 2 rows from
+        // the bottom, so it overruns both
+        let sprite = [0xFF; 4];
+        let (x_pos, y_pos) = (DISPLAY_COLS - 3, DISPLAY_ROWS - 2);
+        for (wrap_x, wrap_y) in [(false, false), (true, false), (false, true), (true, true)] {
+            let test_config = EmulatorConfig {
+                wrap_x,
+                wrap_y,
+                ..EmulatorConfig::default()
+            };
+            let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+            test_emul8r.memory[0x300..0x304].copy_from_slice(&sprite);
+            test_emul8r.draw_sprite(0x300, sprite.len(), x_pos, y_pos)?;
+
+            let lit = test_emul8r
+                .display
+                .iter_cells()
+                .filter(|pixel| **pixel)
+                .count();
+            let cols = if wrap_x { 8 } else { 3 };
+            let rows = if wrap_y { 4 } else { 2 };
+            assert_eq!(lit, cols * rows, "wrap_x {wrap_x}, wrap_y {wrap_y}");
+            // The part on screen is always drawn
+            assert!(
+                test_emul8r
+                    .display
+                    .get(DISPLAY_ROWS - 1, DISPLAY_COLS - 1)?
+            );
+            // The columns wrapped onto the left edge, and rows onto the top
+            assert_eq!(test_emul8r.display.get(DISPLAY_ROWS - 1, 4)?, wrap_x);
+            assert!(!test_emul8r.display.get(DISPLAY_ROWS - 1, 5)?);
+            assert_eq!(test_emul8r.display.get(1, DISPLAY_COLS - 1)?, wrap_y);
+            assert!(!test_emul8r.display.get(2, DISPLAY_COLS - 1)?);
+            assert_eq!(test_emul8r.display.get(0, 0)?, wrap_x && wrap_y);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test stepping several instructions stops when the program halts, or at a
+    /// breakpoint
+    fn test_step_n() -> Result<()> {
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        // Four instructions setting registers, then exit
+        let rom = [0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04, 0x00, 0xFD];
+        test_emul8r.load_rom(&rom)?;
+        assert_eq!(test_emul8r.step_n(100)?, 5);
+        assert!(test_emul8r.is_halted());
+        assert_eq!(test_emul8r.get_reg(3)?, 4);
+        assert_eq!(test_emul8r.step_n(100)?, 0);
+
+        test_emul8r.reset()?;
+        test_emul8r.load_rom(&rom)?;
+        test_emul8r.add_breakpoint(GAME_MEMORY_START + 4);
+        test_emul8r.add_breakpoint(GAME_MEMORY_START + 6);
+        assert_eq!(test_emul8r.step_n(100)?, 2);
+        assert_eq!(test_emul8r.program_counter, GAME_MEMORY_START + 4);
+        // Continuing runs the instruction at the breakpoint
+        assert_eq!(test_emul8r.step_n(100)?, 1);
+        assert!(test_emul8r.remove_breakpoint(GAME_MEMORY_START + 6));
+        assert!(!test_emul8r.remove_breakpoint(GAME_MEMORY_START + 6));
+        assert_eq!(
+            test_emul8r.breakpoints().collect::<Vec<_>>(),
+            [GAME_MEMORY_START + 4]
+        );
+        assert_eq!(test_emul8r.step_n(1)?, 1);
+        assert_eq!(test_emul8r.step_n(100)?, 1);
+        assert!(test_emul8r.is_halted());
+        Ok(())
+    }
+
+    #[test]
+    /// Test peeking at the next instruction decodes it without executing it
+    fn test_peek_instruction() -> Result<()> {
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        test_emul8r.load_rom(&[0x60, 0x01, 0x8A, 0xB4])?;
+        test_emul8r.step()?;
+        let (opcode, kind) = test_emul8r.peek_instruction()?;
+        assert_eq!(opcode, 0x8AB4);
+        assert_eq!(kind.pattern, "8XY4");
+        assert_eq!(kind.mnemonic.as_deref(), Some("ADD VA, VB"));
+        // Nothing moved, and peeking again sees the same instruction
+        assert_eq!(test_emul8r.program_counter(), GAME_MEMORY_START + 2);
+        assert_eq!(test_emul8r.cycles(), 1);
+        assert_eq!(test_emul8r.peek_instruction()?.0, 0x8AB4);
+
+        // Unknown opcodes still have a pattern, just no mnemonic
+        test_emul8r.poke_memory(GAME_MEMORY_START + 2, 0x5A)?;
+        test_emul8r.poke_memory(GAME_MEMORY_START + 3, 0xB9)?;
+        let (opcode, kind) = test_emul8r.peek_instruction()?;
+        assert_eq!(opcode, 0x5AB9);
+        assert_eq!(kind, OpcodeKind::decode(0x5AB9));
+        assert_eq!(kind.mnemonic, None);
+
+        // Half an instruction at the end of memory isn't one
+        test_emul8r.program_counter = MEMORY_SIZE - 1;
+        assert!(test_emul8r.peek_instruction().is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test emulators seeded the same draw the same random numbers
+    fn test_seed_rng() -> Result<()> {
+        // V0 to V7 = random bytes
+        let rom: Vec<u8> = (0..8)
+            .flat_map(|register| [0xC0 | register, 0xFF])
+            .collect();
+        let draws = |seed| -> Result<Vec<u8>> {
+            let mut test_emul8r =
+                Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+            test_emul8r.load_rom(&rom)?;
+            test_emul8r.seed_rng(seed);
+            test_emul8r.step_n(8)?;
+            (0..8)
+                .map(|register| test_emul8r.register(register))
+                .collect()
+        };
+        assert_eq!(draws(1)?, draws(1)?);
+        assert_ne!(draws(1)?, draws(2)?);
+        Ok(())
+    }
+
+    #[test]
+    /// Test changing quirks while running
+    fn test_set_quirk() -> Result<()> {
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        for quirk in Quirk::ALL {
+            for enabled in [true, false] {
+                test_emul8r.set_quirk(quirk, enabled);
+                assert_eq!(quirk.is_enabled(&test_emul8r.config), enabled);
+            }
+        }
+        // V1 = 3, V0 = V1 >> 1 (shifting VY with the quirk, otherwise V0)
+        let rom = [0x61, 0x03, 0x80, 0x16];
+        test_emul8r.load_rom(&rom)?;
+        test_emul8r.set_quirk(Quirk::ShiftUseVy, true);
+        test_emul8r.step_n(2)?;
+        assert_eq!(test_emul8r.get_reg(0)?, 1);
+        Ok(())
+    }
+
+    #[test]
+    /// Test carrying on from a save state, and refusing a broken one
+    fn test_save_state() -> Result<()> {
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        // V0 = 1, call 0x206, (exit), V1 = 2, V0 += 1, draw
+        let rom = [
+            0x60, 0x01, 0x22, 0x06, 0x00, 0xFD, 0x61, 0x02, 0x70, 0x01, 0xD1, 0x15,
+        ];
+        test_emul8r.load_rom(&rom)?;
+        test_emul8r.step_n(3)?;
+        let state = test_emul8r.save_state();
+        assert_eq!(state.rom_sha1, rom::sha1_hex(&rom));
+        assert_eq!(state.stack, [0x204]);
+        test_emul8r.step_n(2)?;
+        let finished = test_emul8r.save_state();
+
+        let mut resumed = Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        resumed.load_rom(&rom)?;
+        resumed.load_state(&state)?;
+        assert_eq!(resumed.get_reg(1)?, 2);
+        assert_eq!(resumed.cycles(), 3);
+        resumed.step_n(2)?;
+        assert_eq!(
+            SaveState {
+                saved_at: finished.saved_at,
+                ..resumed.save_state()
+            },
+            finished
+        );
+
+        let mut broken = state.clone();
+        broken.memory.pop();
+        assert!(resumed.load_state(&broken).is_err());
+        let mut broken = state.clone();
+        broken.stack = vec![0x200; 129];
+        assert!(resumed.load_state(&broken).is_err());
+        // States saved with a deeper stack load, as long as their stack fits
+        // the depth they were saved with
+        let shallow_config = EmulatorConfig {
+            stack_depth: 12,
+            ..EmulatorConfig::default()
+        };
+        let mut shallow = Emulator::new(Box::new(NoOpFrontend::new()), shallow_config)?;
+        shallow.load_rom(&rom)?;
+        let mut deep = state.clone();
+        deep.stack = vec![0x204; 20];
+        shallow.load_state(&deep)?;
+        assert_eq!(shallow.save_state().stack.len(), 20);
+        deep.stack_depth = 16;
+        assert!(shallow.load_state(&deep).is_err());
+        let mut broken = state.clone();
+        broken.planes[0].clear();
+        assert!(resumed.load_state(&broken).is_err());
+        let mut broken = state;
+        broken.planes.push(broken.planes[0].clone());
+        assert!(resumed.load_state(&broken).is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test every plane, and the selected planes, are saved and loaded
+    fn test_save_state_planes() -> Result<()> {
+        let config = EmulatorConfig {
+            num_planes: 2,
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), config.clone())?;
+        // Select both planes and draw the two sprite bytes after the code, one
+        // per plane, then select just the second plane
+        let rom = [
+            0xA2, 0x0A, 0xF3, 0x01, 0xD0, 0x11, 0xF2, 0x01, 0x12, 0x08, 0x80, 0x40,
+        ];
+        test_emul8r.load_rom(&rom)?;
+        test_emul8r.step_n(4)?;
+        let state = test_emul8r.save_state();
+        assert_eq!(state.planes.len(), 2);
+        assert_eq!(state.selected_planes, 0b10);
+
+        let mut resumed = Emulator::new(Box::new(NoOpFrontend::new()), config)?;
+        resumed.load_rom(&rom)?;
+        resumed.load_state(&state)?;
+        assert_eq!(resumed.display.color_index(0, 0)?, 0b01);
+        assert_eq!(resumed.display.color_index(0, 1)?, 0b10);
+        assert_eq!(resumed.display.selected_planes(), 0b10);
+
+        // A display with a different number of planes can't load it
+        let mut single = Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        single.load_rom(&rom)?;
+        let message = format!("{:#}", single.load_state(&state).unwrap_err());
+        assert!(message.contains("2 planes"), "{message}");
+        Ok(())
+    }
+
+    #[test]
+    /// Test that the exit instruction halts the emulator
+    fn test_exit_halts() -> Result<()> {
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        assert!(!test_emul8r.is_halted());
+
+        // Exit, followed by an instruction which would set V0
+        test_emul8r.load_rom(&[0x00, 0xFD, 0x60, 0x12])?;
+        test_emul8r.step()?;
+        assert!(test_emul8r.is_halted());
+        let halted_pc = test_emul8r.program_counter;
+
+        // Stepping, or running a frame, doesn't execute anything
+        test_emul8r.step()?;
+        assert_eq!(test_emul8r.program_counter, halted_pc);
+        test_emul8r.run_frame()?;
+        assert_eq!(test_emul8r.program_counter, halted_pc);
+        assert_eq!(test_emul8r.get_reg(0x0)?, 0);
+
+        // And the run loop returns straight away
+        test_emul8r.run()?;
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that register changes are traced when enabled
+    fn test_trace_registers() -> Result<()> {
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig {
+            trace_registers: true,
+            ..Default::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let sink = changes.clone();
+        test_emul8r.set_register_sink(move |change| sink.borrow_mut().push(change));
+
+        // Set V3 to 0x12 (6312)
+        test_emul8r.load_rom(&[0x63, 0x12, 0x63, 0x12, 0xA3, 0x45])?;
+        test_emul8r.step()?;
+        assert_eq!(
+            *changes.borrow(),
+            vec![RegisterChange {
+                register: Register::V(3),
+                old: 0x00,
+                new: 0x12,
+                pc: 0x200,
+            }]
+        );
+        assert_eq!(changes.borrow()[0].to_string(), "PC 0x200: V3 0x0 -> 0x12");
+
+        // Writing the same value isn't a change
+        test_emul8r.step()?;
+        assert_eq!(changes.borrow().len(), 1);
+
+        // Set I to 0x345 (A345)
+        test_emul8r.step()?;
+        assert_eq!(
+            changes.borrow()[1],
+            RegisterChange {
+                register: Register::I,
+                old: 0x000,
+                new: 0x345,
+                pc: 0x204,
             }
-            (other, ..) => {
-                warn!("Instruction {other:#x} not implemented");
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that register changes aren't traced by default
+    fn test_trace_registers_disabled() -> Result<()> {
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let test_frontend = NoOpFrontend::new();
+        let test_config = EmulatorConfig::default();
+        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+        let sink = changes.clone();
+        test_emul8r.set_register_sink(move |change| sink.borrow_mut().push(change));
+
+        test_emul8r.set_reg(0x3, 0x12)?;
+        test_emul8r.set_index(0x345)?;
+        assert!(changes.borrow().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that instructions depending on a quirk are logged when enabled
+    fn test_log_quirk_hits() -> Result<()> {
+        // V1 = 0x03, V2 = 0x10, shift V1 right (8126) twice, then shift V3 (all
+        // zeroes, so the quirk doesn't matter)
+        let rom = [0x61, 0x03, 0x62, 0x10, 0x81, 0x26, 0x81, 0x26, 0x83, 0x36];
+        let run = |log_quirk_hits, shift_use_vy| -> Result<(Vec<QuirkHit>, RunStats)> {
+            let hits = Rc::new(RefCell::new(Vec::new()));
+            let test_config = EmulatorConfig {
+                log_quirk_hits,
+                shift_use_vy,
+                ..Default::default()
+            };
+            let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+            let sink = hits.clone();
+            test_emul8r.set_quirk_sink(move |hit| sink.borrow_mut().push(hit));
+            test_emul8r.load_rom(&rom)?;
+            for _ in 0..5 {
+                test_emul8r.step()?;
             }
+            Ok((hits.take(), test_emul8r.stats()))
         };
+
+        let (hits, stats) = run(true, true)?;
+        assert_eq!(
+            hits[0],
+            QuirkHit {
+                quirk: Quirk::ShiftUseVy,
+                enabled: true,
+                result: "V1 = 0x8, VF = 0x0".to_string(),
+                alternative: "V1 = 0x1, VF = 0x1".to_string(),
+                pc: 0x204,
+            }
+        );
+        assert_eq!(
+            hits[0].to_string(),
+            "PC 0x204: shift_use_vy = true gave V1 = 0x8, VF = 0x0 \
+             (false would give V1 = 0x1, VF = 0x1)"
+        );
+        // V1 is now 0x08, still different from V2
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].pc, 0x206);
+        assert_eq!(
+            stats.quirk_hits,
+            std::collections::BTreeMap::from([("shift_use_vy".to_string(), 2)])
+        );
+
+        let (hits, _) = run(true, false)?;
+        assert_eq!(hits.len(), 2);
+        assert!(!hits[0].enabled);
+        assert_eq!(hits[0].result, "V1 = 0x1, VF = 0x1");
+
+        // Nothing is logged (or counted) by default
+        let (hits, stats) = run(false, true)?;
+        assert!(hits.is_empty() && stats.quirk_hits.is_empty());
         Ok(())
     }
-    /// Add a value to the stack
-    fn stack_push(&mut self, value: u16) -> Result<()> {
-        *(self
-            .stack
-            .get_mut(self.stack_top)
-            .context("Stack overflow!")?) = value;
-        self.stack_top += 1;
+
+    /// Create an emulator with `on_error` set, running a ROM with an unknown
+    /// opcode followed by an instruction setting V0
+    fn bad_opcode_emulator(on_error: &str) -> Result<Emulator<'static>> {
+        let test_config = EmulatorConfig {
+            on_error: on_error.to_string(),
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        test_emul8r.load_rom(&[0xFF, 0xFF, 0x60, 0x12])?;
+        Ok(test_emul8r)
+    }
+
+    #[test]
+    /// Test that unknown opcodes are errors by default
+    fn test_on_error_abort() -> Result<()> {
+        let mut test_emul8r = bad_opcode_emulator("abort")?;
+        let message = test_emul8r.step().unwrap_err().to_string();
+        assert!(message.contains("FFFF"), "{message}");
         Ok(())
     }
 
-    /// Pop the value off the top of the stack
-    fn stack_pop(&mut self) -> Result<u16> {
-        if self.stack_top == 0 {
-            bail!("Trying to pop from empty stack");
-        }
-        self.stack_top -= 1;
-        Ok(*(self
-            .stack
-            .get(self.stack_top)
-            .context("Invalid stack pointer")?))
+    #[test]
+    /// Test that execution continues past a bad instruction when skipping
+    fn test_on_error_skip_instruction() -> Result<()> {
+        let mut test_emul8r = bad_opcode_emulator("skip_instruction")?;
+        test_emul8r.step()?;
+        assert_eq!(test_emul8r.program_counter, GAME_MEMORY_START + 2);
+        test_emul8r.step()?;
+        assert_eq!(test_emul8r.get_reg(0)?, 0x12);
+        assert!(!test_emul8r.is_halted());
+
+        // Errors part way through an instruction still skip just that instruction
+        test_emul8r.load_rom(&[0x00, 0xEE, 0x61, 0x34])?;
+        test_emul8r.jump(GAME_MEMORY_START)?;
+        test_emul8r.step()?;
+        test_emul8r.step()?;
+        assert_eq!(test_emul8r.get_reg(1)?, 0x34);
+        Ok(())
     }
 
-    /// Load the font into memory starting at FONT_START_POSITION
-    fn load_font(&mut self) -> Result<()> {
-        self.load_bytes(&FONT, FONT_START_POSITION)
-            .context("Loading font into memory")
+    #[test]
+    /// Test halting on a bad instruction
+    fn test_on_error_halt() -> Result<()> {
+        let mut test_emul8r = bad_opcode_emulator("halt")?;
+        test_emul8r.step()?;
+        assert!(test_emul8r.is_halted());
+        test_emul8r.run_frame()?;
+        assert_eq!(test_emul8r.get_reg(0)?, 0);
+        Ok(())
     }
 
-    fn load_bytes(&mut self, bytes: &[u8], start_position: usize) -> Result<()> {
-        let mut memory_index = start_position;
-        // Iterate through the file, moving each byte into memory
-        for &byte in bytes {
-            *(self
-                .memory
-                .get_mut(memory_index)
-                .context("Insufficient memory to hold game file")?) = byte;
-            memory_index += 1;
+    #[test]
+    /// Test that returning with an empty stack follows the error handling mode
+    fn test_on_error_empty_stack_return() -> Result<()> {
+        for on_error in OnError::ALL {
+            let test_config = EmulatorConfig {
+                on_error: on_error.name().to_string(),
+                ..EmulatorConfig::default()
+            };
+            let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+            // A bare RET, followed by an instruction setting V0
+            test_emul8r.load_rom(&[0x00, 0xEE, 0x60, 0x12])?;
+            let result = test_emul8r.step();
+            match on_error {
+                OnError::Abort => {
+                    let message = format!("{:#}", result.unwrap_err());
+                    assert!(message.contains("00EE"), "{message}");
+                    assert!(message.contains("empty stack"), "{message}");
+                }
+                OnError::SkipInstruction => {
+                    result?;
+                    test_emul8r.step()?;
+                    assert_eq!(test_emul8r.get_reg(0)?, 0x12);
+                    assert!(!test_emul8r.is_halted());
+                }
+                OnError::Halt => {
+                    result?;
+                    assert!(test_emul8r.is_halted());
+                    test_emul8r.step()?;
+                    assert_eq!(test_emul8r.get_reg(0)?, 0);
+                }
+            }
+            assert!(test_emul8r.stack.is_empty());
         }
         Ok(())
     }
 
-    /// Draw a sprite to the screen
-    ///
-    /// Starting from the byte in memory at sprite_index, with length/height sprite_length,
-    /// draw the sprite at the row given by y_pos, and the columns given by x_pos.
-    fn draw_sprite(
-        &mut self,
-        sprite_index: usize,
-        sprite_length: usize,
-        x_pos: usize,
-        y_pos: usize,
-    ) -> Result<()> {
-        let mut cur_index = sprite_index;
-        // The x and y coordinates are allowed to wrap
-        let x_pos = x_pos % DISPLAY_COLS;
-        let y_pos = y_pos % DISPLAY_ROWS;
-        // Track if any bits were turned OFF
-        let mut turned_off = false;
-
-        // Loop through the sprite, XORing with the display bits
-        for row_offset in 0..sprite_length {
-            // If off bottom of screen, stop trying to draw
-            if y_pos + row_offset >= DISPLAY_ROWS {
-                break;
+    #[test]
+    /// Test that an unknown arithmetic instruction (8XY8) follows the error
+    /// handling mode, naming the instruction and its address
+    fn test_on_error_unknown_arithmetic() -> Result<()> {
+        for on_error in OnError::ALL {
+            let test_config = EmulatorConfig {
+                on_error: on_error.name().to_string(),
+                ..EmulatorConfig::default()
             };
-            // Get the byte for the current row of the sprite
-            let mut sprite_byte = self
-                .memory
-                .get(cur_index)
-                .context("Trying to get byte in sprite")?
-                .to_owned();
-            for col_offset in 0..SPRITE_WIDTH {
-                // Stop trying to draw if going off-screen
-                if x_pos + col_offset >= DISPLAY_COLS {
-                    break;
-                };
-                // XOR the display bit with the value of the sprite at this index
-                // offset (tracked by shifting the sprite byte to the left)
-                if self.display.xor(
-                    y_pos + row_offset,
-                    x_pos + col_offset,
-                    (sprite_byte & 0b10000000) == 0b10000000,
-                )? {
-                    turned_off = true;
+            let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+            test_emul8r.enable_compat_report();
+            // V1 = 5, 8128 (unknown), V0 = 0x12
+            test_emul8r.load_rom(&[0x61, 0x05, 0x81, 0x28, 0x60, 0x12])?;
+            test_emul8r.step()?;
+            let result = test_emul8r.step();
+            match on_error {
+                OnError::Abort => {
+                    let message = format!("{:#}", result.unwrap_err());
+                    assert!(message.contains("8128 at 0x202"), "{message}");
+                }
+                OnError::SkipInstruction => {
+                    result?;
+                    assert_eq!(test_emul8r.program_counter, GAME_MEMORY_START + 4);
+                    test_emul8r.step()?;
+                    assert_eq!(test_emul8r.get_reg(0)?, 0x12);
+                    assert!(!test_emul8r.is_halted());
+                }
+                OnError::Halt => {
+                    result?;
+                    assert!(test_emul8r.is_halted());
+                    test_emul8r.step()?;
+                    assert_eq!(test_emul8r.get_reg(0)?, 0);
                 }
-                // Shift the sprite_byte, which will result in the bit of interest being
-                // at the most significant position
-                sprite_byte <<= 1;
             }
-            // Increment the memory index
-            cur_index += 1;
-        }
-        if turned_off {
-            self.set_reg(0xF, 1)?;
+            // Registers are left alone
+            assert_eq!(test_emul8r.get_reg(1)?, 5);
+            assert_eq!(test_emul8r.get_reg(0xF)?, 0);
+            let report = test_emul8r.compat_report().unwrap();
+            assert_eq!(report.unknown_opcodes["8128"], 1);
         }
         Ok(())
     }
 
-    /// Check if the `key` is currently pressed
-    fn check_key(&mut self, key: u8) -> Result<bool> {
-        // If bounds check guaranteed by the u8 passed in
-        self.frontend.check_key(key)
+    #[test]
+    /// Test that a program counter outside of memory can't be skipped
+    fn test_on_error_unrecoverable() -> Result<()> {
+        let mut test_emul8r = bad_opcode_emulator("skip_instruction")?;
+        test_emul8r.jump(MEMORY_SIZE)?;
+        assert!(test_emul8r.step().is_err());
+        Ok(())
     }
 
-    /// Jump to provided destination
-    fn jump(&mut self, dest: usize) -> Result<()> {
-        self.program_counter = dest;
+    #[test]
+    /// Test the error handling mode names
+    fn test_on_error_names() -> Result<()> {
+        for on_error in OnError::ALL {
+            assert_eq!(OnError::from_name(on_error.name())?, on_error);
+        }
+        assert_eq!(
+            OnError::from_name(" SKIP_INSTRUCTION ")?,
+            OnError::SkipInstruction
+        );
+        assert!(OnError::from_name("skip").is_err());
         Ok(())
     }
 
-    /// Get the value in register `register`
-    fn get_reg(&self, register: u8) -> Result<u8> {
-        Ok(self
-            .registers
-            .get(register as usize)
-            .context(format!("Trying to get value at register {register:#x}"))?
-            .to_owned())
+    /// What the emulator asked of a [ScriptedFrontend], kept where the test can
+    /// read it once the emulator owns the frontend
+    #[derive(Debug, Default)]
+    struct FrontendLog {
+        /// Each frame drawn, as ASCII
+        frames: Vec<String>,
+        /// Number of times the menu was drawn
+        menu_draws: u32,
+        /// Whether each play_sound (true) or stop_sound (false) call was made
+        sound_events: Vec<bool>,
+        /// Each change to whether the beep is sounding
+        sound_active: Vec<bool>,
+        /// The titles given
+        titles: Vec<String>,
+        /// The resolutions (rows, columns) switched to
+        resolutions: Vec<(usize, usize)>,
+        /// The lines of each error shown
+        errors: Vec<Vec<String>>,
+        /// The enabled cheats last listed in the debug overlay
+        cheats_text: Option<String>,
     }
 
-    /// Set the value in register `register` to `value`
-    fn set_reg(&mut self, register: usize, value: u8) -> Result<()> {
-        // Bounds check to indicate panic
-        if register >= NUM_REGISTERS {
-            bail!("Trying to get value at register {register:#x}")
-        }
-        self.registers[register] = value;
-        Ok(())
+    /// Frontend for the tests, pressing keys and hotkeys, sending menu input,
+    /// and losing the focus on scripted loop iterations (counted by
+    /// should_stop), and recording what it is asked to do in its `log`
+    #[derive(Default)]
+    struct ScriptedFrontend {
+        /// Number of loop iterations so far
+        iterations: u32,
+        /// Number of iterations to run for, or None to never ask to stop
+        stop_after: Option<u32>,
+        /// Keypad keys held down (bit N for key N), which the test can change
+        keys: Rc<Cell<u16>>,
+        /// Keypad keys held down from an iteration on, on top of `keys`
+        keys_from: Option<(u32, u16)>,
+        /// Physical key held down, found on the keypad through `keymap`
+        physical_key: Option<&'static str>,
+        keymap: Keymap,
+        /// Hotkeys pressed on each iteration
+        hotkeys: Vec<(u32, HotkeyAction)>,
+        /// Whether the frontend has a pause menu
+        menu: bool,
+        /// Menu input sent on each iteration
+        menu_inputs: Vec<(u32, MenuInput)>,
+        /// Whether the frontend can play sound
+        audio: bool,
+        /// Ranges of iterations during which the window doesn't have the focus,
+        /// or None if the frontend doesn't report the focus
+        unfocused: Option<Vec<std::ops::Range<u32>>>,
+        /// Draw from which on draws fail, counting from 1
+        fail_draws_from: Option<usize>,
+        log: Rc<RefCell<FrontendLog>>,
     }
 
-    // /// Add the value in register `register` to `value`
-    // fn add_reg(&mut self, register: usize, value: u8) -> Result<()> {
-    //     // Bounds check to indicate panic
-    //     if register >= NUM_REGISTERS {
-    //         bail!("Trying to get value at register {register:#x}")
-    //     };
-    //     self.registers[register] += value;
-    //     Ok(())
-    // }
+    impl Frontend for ScriptedFrontend {
+        fn draw(&mut self, display: &Display) -> Result<()> {
+            let mut log = self.log.borrow_mut();
+            log.frames.push(display.to_ascii());
+            if self
+                .fail_draws_from
+                .is_some_and(|from| log.frames.len() >= from)
+            {
+                bail!("GPU context lost");
+            }
+            Ok(())
+        }
+
+        fn check_key(&mut self, key: u8) -> Result<bool> {
+            let mut keys = self.keys.get();
+            if let Some((from, held)) = self.keys_from
+                && self.iterations >= from
+            {
+                keys |= held;
+            }
+            let physical =
+                self.physical_key.is_some() && self.keymap.key_name(key) == self.physical_key;
+            Ok(physical
+                || 1u16
+                    .checked_shl(key.into())
+                    .is_some_and(|bit| keys & bit != 0))
+        }
+
+        fn check_hotkey(&mut self, action: HotkeyAction) -> Result<bool> {
+            Ok(self.hotkeys.contains(&(self.iterations, action)))
+        }
+
+        fn capabilities(&self) -> FrontendCapabilities {
+            FrontendCapabilities {
+                has_audio: self.audio,
+                reports_focus: self.unfocused.is_some(),
+                ..FrontendCapabilities::default()
+            }
+        }
+
+        fn is_focused(&mut self) -> bool {
+            !self
+                .unfocused
+                .iter()
+                .flatten()
+                .any(|range| range.contains(&self.iterations))
+        }
+
+        fn has_menu(&self) -> bool {
+            self.menu
+        }
+
+        fn draw_menu(&mut self, _display: &Display, _menu: &PauseMenu) -> Result<()> {
+            self.log.borrow_mut().menu_draws += 1;
+            Ok(())
+        }
+
+        fn menu_input(&mut self, _menu: &PauseMenu) -> Result<Option<MenuInput>> {
+            Ok(self
+                .menu_inputs
+                .iter()
+                .find(|(iteration, _)| *iteration == self.iterations)
+                .map(|(_, input)| *input))
+        }
+
+        fn set_sound_active(&mut self, active: bool) {
+            let changes = &mut self.log.borrow_mut().sound_active;
+            if changes.last() != Some(&active) {
+                changes.push(active);
+            }
+        }
+
+        fn set_title(&mut self, title: &str) -> Result<()> {
+            self.log.borrow_mut().titles.push(title.to_string());
+            Ok(())
+        }
+
+        fn on_resolution_change(&mut self, rows: usize, cols: usize) -> Result<()> {
+            self.log.borrow_mut().resolutions.push((rows, cols));
+            Ok(())
+        }
+
+        fn set_keymap(&mut self, keymap: &Keymap) -> Result<()> {
+            self.keymap = keymap.clone();
+            Ok(())
+        }
+
+        fn show_error(&mut self, lines: &[String]) -> Result<()> {
+            self.log.borrow_mut().errors.push(lines.to_vec());
+            Ok(())
+        }
+
+        fn set_cheats_text(&mut self, text: Option<&str>) {
+            self.log.borrow_mut().cheats_text = text.map(str::to_string);
+        }
 
-    /// Set the value of the index register
-    fn set_index(&mut self, value: u16) -> Result<()> {
-        self.index_register = value;
-        Ok(())
-    }
+        fn play_sound(&mut self) -> Result<()> {
+            self.log.borrow_mut().sound_events.push(true);
+            Ok(())
+        }
 
-    /// Get the value of the index register
-    fn get_index(&self) -> Result<u16> {
-        Ok(self.index_register)
+        fn stop_sound(&mut self) -> Result<()> {
+            self.log.borrow_mut().sound_events.push(false);
+            Ok(())
+        }
+
+        fn should_stop(&mut self) -> bool {
+            self.iterations += 1;
+            self.stop_after
+                .is_some_and(|stop_after| self.iterations > stop_after)
+        }
+
+        fn step(&mut self) -> Result<()> {
+            Ok(())
+        }
     }
 
-    /// Fetch the current instruction (incrementing the program counter appropriately)
-    fn fetch(&mut self) -> Result<(u8, u8)> {
-        let b1 = self
-            .memory
-            .get(self.program_counter)
-            .context("Trying to fetch first byte of instruction")?
-            .to_owned();
-        let b2 = self
-            .memory
-            .get(self.program_counter + 1)
-            .context("Trying to fetch second byte of instruction")?
-            .to_owned();
-        self.program_counter += INSTRUCTION_LENGTH;
-        Ok((b1, b2))
+    /// Run a frame where key 0 is pressed after the first instruction, returning
+    /// whether the skip if key 0 pressed (E09E) in the second instruction saw it
+    fn key_seen_mid_frame(poll_keys_per_frame: bool) -> Result<bool> {
+        let keys = Rc::new(Cell::new(0));
+        let test_config = EmulatorConfig {
+            poll_keys_per_frame,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            stop_after: Some(0),
+            keys: keys.clone(),
+            ..ScriptedFrontend::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        // V0 = 0, skip if key V0 pressed, V1 = 1 (skipped if key 0 was seen), loop
+        test_emul8r.load_rom(&[0x60, 0x00, 0xE0, 0x9E, 0x61, 0x01, 0x12, 0x06])?;
+        test_emul8r.start_frame()?;
+        test_emul8r.step()?;
+        keys.set(1);
+        test_emul8r.step()?;
+        test_emul8r.step()?;
+        Ok(test_emul8r.get_reg(1)? == 0)
     }
-}
 
-#[cfg(test)]
-mod test_emulator {
-    use super::*;
+    #[test]
+    /// Test that keys polled once per frame don't change part way through a frame
+    fn test_poll_keys_per_frame() -> Result<()> {
+        assert!(!key_seen_mid_frame(true)?);
+        assert!(key_seen_mid_frame(false)?);
 
-    use crate::{config::EmulatorConfig, noop_frontend::NoOpFrontend};
+        // The next frame sees the key
+        let keys = Rc::new(Cell::new(0));
+        let test_config = EmulatorConfig {
+            poll_keys_per_frame: true,
+            instructions_per_second: 60,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            stop_after: Some(0),
+            keys: keys.clone(),
+            ..ScriptedFrontend::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_rom(&[0x60, 0x00, 0xE0, 0x9E, 0x61, 0x01, 0x12, 0x06])?;
+        test_emul8r.run_frame()?;
+        keys.set(1);
+        test_emul8r.run_frame()?;
+        assert_eq!(test_emul8r.program_counter, GAME_MEMORY_START + 6);
+        Ok(())
+    }
 
     #[test]
-    /// Test creating the emulator
-    fn test_create() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let _test_eml8r = Emulator::new(Box::new(test_frontend), test_config)?;
+    /// Test key checks use the keymap switched to while running
+    fn test_set_keymap() -> Result<()> {
+        let frontend = ScriptedFrontend {
+            physical_key: Some("W"),
+            ..ScriptedFrontend::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(frontend), EmulatorConfig::default())?;
+        // W is keypad key 5 on the default (QWERTY) keymap
+        assert!(test_emul8r.check_key(0x5)?);
+        assert!(!test_emul8r.check_key(0xA)?);
 
+        let mut keymap = Keymap::default();
+        keymap.remap("A", "w")?;
+        keymap.remap("5", "P")?;
+        test_emul8r.set_keymap(&keymap)?;
+        assert!(!test_emul8r.check_key(0x5)?);
+        assert!(test_emul8r.check_key(0xA)?);
         Ok(())
     }
 
     #[test]
-    /// Test clearing the display
-    fn test_clear() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-
-        // Artifically set some cells of the display
-        test_emul8r.display.set(0, 0, true)?;
-        test_emul8r.display.set(10, 20, true)?;
-        test_emul8r.display.set(3, 5, true)?;
+    /// Test remapping single keys while running, on top of the configured keymap
+    fn test_remap_key() -> Result<()> {
+        let frontend = ScriptedFrontend {
+            physical_key: Some("W"),
+            ..ScriptedFrontend::default()
+        };
+        let test_config = EmulatorConfig {
+            keymap: std::collections::BTreeMap::from([("0xa".to_string(), "Q".to_string())]),
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.remap_key(0xA, "w")?;
+        test_emul8r.remap_key(0x5, "P")?;
+        assert!(test_emul8r.check_key(0xA)?);
+        assert!(!test_emul8r.check_key(0x5)?);
+        // The configured override is replaced rather than kept alongside
+        assert_eq!(
+            test_emul8r.config.keymap,
+            std::collections::BTreeMap::from([
+                ("5".to_string(), "P".to_string()),
+                ("A".to_string(), "w".to_string()),
+            ])
+        );
 
-        // Set the first instruction to be clear
-        #[allow(clippy::identity_op)]
-        {
-            test_emul8r.memory[test_emul8r.program_counter] = (0x0 << 4) | 0x0;
-            test_emul8r.memory[test_emul8r.program_counter + 1] = (0xE << 4) | 0x0;
-        }
-        // Run the single instruction
-        test_emul8r.execute()?;
+        // Bad names change nothing
+        assert!(test_emul8r.remap_key(0x10, "Q").is_err());
+        assert!(test_emul8r.remap_key(0x1, "NOT_A_KEY").is_err());
+        assert!(test_emul8r.check_key(0xA)?);
+        assert_eq!(test_emul8r.config.keymap.len(), 2);
+        Ok(())
+    }
 
-        // Check that the display has been cleared
-        for &cell in test_emul8r.display.iter_cells() {
-            assert!(!cell);
+    #[test]
+    /// Test values past the keypad are never pressed, whether the keys are polled
+    /// once per frame or not (polled, it was a shift overflow panic)
+    fn test_key_past_keypad() -> Result<()> {
+        for poll_keys_per_frame in [false, true] {
+            let test_config = EmulatorConfig {
+                poll_keys_per_frame,
+                ..EmulatorConfig::default()
+            };
+            let frontend = ScriptedFrontend {
+                stop_after: Some(0),
+                keys: Rc::new(Cell::new(0xFFFF)),
+                ..ScriptedFrontend::default()
+            };
+            let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+            // V0 = 0x20, skip if key V0 pressed, V1 = 1, skip if key V0 not
+            // pressed, V2 = 1
+            test_emul8r.load_rom(&[0x60, 0x20, 0xE0, 0x9E, 0x61, 0x01, 0xE0, 0xA1, 0x62, 0x01])?;
+            test_emul8r.start_frame()?;
+            for _ in 0..4 {
+                test_emul8r.step()?;
+            }
+            assert_eq!(test_emul8r.get_reg(1)?, 1);
+            assert_eq!(test_emul8r.get_reg(2)?, 0);
         }
-
         Ok(())
     }
 
     #[test]
-    /// Test the stack memory
-    fn test_stack() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
+    /// Test that running with a cycle limit executes exactly that many instructions
+    fn test_run_for_max_cycles() -> Result<()> {
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(HeadlessFrontend::new()), test_config)?;
+        // Count up in V0 forever
+        test_emul8r.load_rom(&[0x70, 0x01, 0x12, 0x00])?;
+        let limits = RunLimits {
+            max_cycles: Some(1001),
+            ..RunLimits::default()
+        };
 
-        // Check that the stack is empty
-        assert!(test_emul8r.stack_top == 0);
+        assert_eq!(test_emul8r.run_for(limits)?, RunOutcome::LimitReached);
+        assert_eq!(test_emul8r.cycles(), 1001);
+        // 501 adds, wrapping around
+        assert_eq!(test_emul8r.get_reg(0)?, (501 % 256) as u8);
 
-        // Push some numbers onto the stack
-        test_emul8r.stack_push(5)?;
-        test_emul8r.stack_push(10)?;
-        test_emul8r.stack_push(1)?;
-        test_emul8r.stack_push(0)?;
-        test_emul8r.stack_push(50)?;
+        // The limit is per run
+        assert_eq!(test_emul8r.run_for(limits)?, RunOutcome::LimitReached);
+        assert_eq!(test_emul8r.cycles(), 2002);
+        Ok(())
+    }
 
-        // Check that stack top has moved forward/up
-        assert_eq!(test_emul8r.stack_top, 5);
+    #[test]
+    /// Test the other ways a limited run can stop
+    fn test_run_for_outcomes() -> Result<()> {
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            ..EmulatorConfig::default()
+        };
+        let limits = RunLimits {
+            max_cycles: Some(100),
+            ..RunLimits::default()
+        };
 
-        // Check popping is correct
-        assert_eq!(test_emul8r.stack_pop()?, 50);
-        assert_eq!(test_emul8r.stack_pop()?, 0);
-        assert_eq!(test_emul8r.stack_pop()?, 1);
-        assert_eq!(test_emul8r.stack_pop()?, 10);
-        assert_eq!(test_emul8r.stack_pop()?, 5);
+        // Exiting halts before the limit
+        let mut test_emul8r =
+            Emulator::new(Box::new(HeadlessFrontend::new()), test_config.clone())?;
+        test_emul8r.load_rom(&[0x60, 0x01, 0x00, 0xFD])?;
+        assert_eq!(test_emul8r.run_for(limits)?, RunOutcome::Halted);
+        assert_eq!(test_emul8r.cycles(), 2);
 
-        // Make sure the stack pointer has gone back to 0
-        assert_eq!(test_emul8r.stack_top, 0);
+        // Crashing is an error
+        let mut test_emul8r =
+            Emulator::new(Box::new(HeadlessFrontend::new()), test_config.clone())?;
+        test_emul8r.load_rom(&[0x60, 0x01, 0xFF, 0xFF])?;
+        assert!(test_emul8r.run_for(limits).is_err());
+
+        // A frontend asking to stop stops before anything runs
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config.clone())?;
+        assert_eq!(test_emul8r.run_for(limits)?, RunOutcome::Stopped);
+        assert_eq!(test_emul8r.cycles(), 0);
 
+        // Time limits
+        let mut test_emul8r = Emulator::new(Box::new(HeadlessFrontend::new()), test_config)?;
+        test_emul8r.load_rom(&[0x12, 0x00])?;
+        let limits = RunLimits {
+            max_duration: Some(Duration::from_millis(20)),
+            ..RunLimits::default()
+        };
+        assert!(limits.is_limited());
+        assert!(!RunLimits::default().is_limited());
+        assert_eq!(test_emul8r.run_for(limits)?, RunOutcome::LimitReached);
+        assert!(test_emul8r.cycles() > 0);
         Ok(())
     }
 
     #[test]
-    /// Test jump instruction
-    fn test_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let jump_dest = 1012u16;
-
-        // Set the first instruction to be clear
-        #[allow(clippy::identity_op)]
-        {
-            let instruction1 = (0x1 << 4) | jump_dest >> 8;
-            let instruction2 = jump_dest & 0xFF;
+    /// Test that the watchdog stops a self looping ROM soon after the maximum run time
+    fn test_max_runtime() -> Result<()> {
+        for timeout_is_error in [false, true] {
+            let test_config = EmulatorConfig {
+                max_runtime_seconds: 0.05,
+                timeout_is_error,
+                ..EmulatorConfig::default()
+            };
+            let mut test_emul8r = Emulator::new(Box::new(HeadlessFrontend::new()), test_config)?;
+            // Jump to itself forever
+            test_emul8r.load_rom(&[0x12, 0x00])?;
 
-            test_emul8r.memory[test_emul8r.program_counter] = instruction1 as u8;
-            test_emul8r.memory[test_emul8r.program_counter + 1] = instruction2 as u8;
+            let start = Instant::now();
+            let result = test_emul8r.run_for(RunLimits::default());
+            let elapsed = start.elapsed();
+            assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+            assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+            if timeout_is_error {
+                let err = result.unwrap_err();
+                assert_eq!(
+                    err.downcast_ref::<Timeout>(),
+                    Some(&Timeout {
+                        max_runtime: Duration::from_millis(50)
+                    })
+                );
+            } else {
+                assert_eq!(result?, RunOutcome::TimedOut);
+                // run stops cleanly as well
+                test_emul8r.run()?;
+            }
+            assert!(test_emul8r.cycles() > 0);
         }
-        // Run the single instruction
-        test_emul8r.execute()?;
-
-        // Check that the program counter has been set to 1012
-        assert_eq!(test_emul8r.program_counter, jump_dest as usize);
-
         Ok(())
     }
 
     #[test]
-    /// Test subroutines
-    fn test_subroutines() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let jump_dest = 1012u16;
-        let initial_position = test_emul8r.program_counter;
+    /// Test that time spent paused doesn't count towards the maximum run time
+    fn test_max_runtime_paused() -> Result<()> {
+        let test_config = EmulatorConfig {
+            max_runtime_seconds: 0.05,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            // Paused for longer than the maximum run time, then run a little
+            hotkeys: vec![(1, HotkeyAction::Pause), (60, HotkeyAction::Pause)],
+            stop_after: Some(70),
+            ..ScriptedFrontend::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_rom(&[0x12, 0x00])?;
 
-        // Set the first instruction to be subroutine jump
-        #[allow(clippy::identity_op)]
-        {
-            let instruction1 = (0x2 << 4) | jump_dest >> 8;
-            let instruction2 = jump_dest & 0xFF;
+        let start = Instant::now();
+        assert_eq!(
+            test_emul8r.run_for(RunLimits::default())?,
+            RunOutcome::Stopped
+        );
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(test_emul8r.cycles() > 0);
+        Ok(())
+    }
 
-            test_emul8r.memory[test_emul8r.program_counter] = instruction1 as u8;
-            test_emul8r.memory[test_emul8r.program_counter + 1] = instruction2 as u8;
-        }
-        // Run the single instruction
-        test_emul8r.execute()?;
+    #[test]
+    /// Test pausing and resuming with the pause hotkey
+    fn test_pause_hotkey() -> Result<()> {
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            // Pause on the 5th iteration, resume on the 30th
+            hotkeys: vec![(5, HotkeyAction::Pause), (30, HotkeyAction::Pause)],
+            stop_after: Some(40),
+            audio: true,
+            ..ScriptedFrontend::default()
+        };
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_rom(&[
+            0x60, 0xFF, // LD V0, 0xFF
+            0xF0, 0x18, // LD ST, V0
+            0x71, 0x01, // ADD V1, 0x01
+            0x12, 0x04, // JP 0x204
+        ])?;
 
-        // Check that the emulator did jump
-        assert_eq!(test_emul8r.program_counter, jump_dest as usize);
-        // Check that the previous position was put onto the stack
         assert_eq!(
-            test_emul8r.stack[test_emul8r.stack_top - 1],
-            initial_position as u16 + 2 // NOTE: Advanced due to stepping through instruction
+            test_emul8r.run_for(RunLimits::default())?,
+            RunOutcome::Stopped
         );
+        // 4 instructions before pausing, none for the 25 paused iterations, then
+        // 11 after resuming
+        assert_eq!(test_emul8r.cycles(), 15);
+        assert!(!test_emul8r.is_paused());
+        // Drawing carried on while paused
+        assert_eq!(log.borrow().frames.len(), 40);
+        // The beep stopped while paused, and started again after
+        assert_eq!(log.borrow().sound_events, [true, false, true]);
+        Ok(())
+    }
 
-        // Set the instruction currently pointed to to be return
-        #[allow(clippy::identity_op)]
-        {
-            let instruction1 = (0x0 << 4) | 0x0;
-            let instruction2 = (0xE << 4) | 0xE;
+    #[test]
+    /// Test the step hotkey runs a single frame while paused, and nothing
+    /// otherwise
+    fn test_step_hotkey() -> Result<()> {
+        let frontend = ScriptedFrontend {
+            hotkeys: vec![
+                (3, HotkeyAction::Pause),
+                // Pressed while running (ignored), then twice while paused
+                (2, HotkeyAction::Step),
+                (5, HotkeyAction::Step),
+                (8, HotkeyAction::Step),
+            ],
+            stop_after: Some(10),
+            audio: true,
+            ..ScriptedFrontend::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(frontend), EmulatorConfig::default())?;
+        test_emul8r.load_rom(&[0x71, 0x01, 0x12, 0x00])?;
+        test_emul8r.run_for(RunLimits::default())?;
+        // 2 instructions before pausing, then a frame for each step
+        let per_frame = test_emul8r.instructions_per_frame();
+        assert_eq!(test_emul8r.cycles(), 2 + 2 * per_frame);
+        assert!(test_emul8r.is_paused());
+        Ok(())
+    }
 
-            test_emul8r.memory[test_emul8r.program_counter] = instruction1 as u8;
-            test_emul8r.memory[test_emul8r.program_counter + 1] = instruction2 as u8;
-        }
-        test_emul8r.execute()?;
+    #[test]
+    /// Test that muting stops the beep straight away, and unmuting starts it again
+    fn test_mute_hotkey() -> Result<()> {
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            // Mute on the 4th iteration, unmute on the 20th
+            hotkeys: vec![(4, HotkeyAction::Mute), (20, HotkeyAction::Mute)],
+            stop_after: Some(30),
+            audio: true,
+            ..ScriptedFrontend::default()
+        };
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_rom(&[
+            0x60, 0xFF, // LD V0, 0xFF
+            0xF0, 0x18, // LD ST, V0
+            0x12, 0x04, // JP 0x204
+        ])?;
 
-        // Check that the emulator did return
-        // // NOTE:+2 due to stepping counter
-        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+        assert_eq!(
+            test_emul8r.run_for(RunLimits::default())?,
+            RunOutcome::Stopped
+        );
+        assert!(!test_emul8r.is_muted());
+        // Started on the 2nd iteration, stopped by muting, no plays while muted,
+        // and started again after unmuting
+        assert_eq!(log.borrow().sound_events, [true, false, true]);
+        // The program carried on the whole time
+        assert_eq!(test_emul8r.cycles(), 30);
+        Ok(())
+    }
 
-        // Check that the stack has been emptied
-        assert_eq!(test_emul8r.stack_top, 0);
+    #[test]
+    /// Test starting muted with the audio_muted config
+    fn test_audio_muted_config() -> Result<()> {
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            audio_muted: true,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            stop_after: Some(1000),
+            audio: true,
+            ..ScriptedFrontend::default()
+        };
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_rom(&[0x60, 0xFF, 0xF0, 0x18, 0x12, 0x04])?;
+        assert!(test_emul8r.is_muted());
+        let limits = |max_cycles| RunLimits {
+            max_cycles: Some(max_cycles),
+            ..RunLimits::default()
+        };
+        test_emul8r.run_for(limits(20))?;
+        assert!(log.borrow().sound_events.is_empty());
 
+        // Unmuting with the sound timer still going starts the beep
+        test_emul8r.set_muted(false)?;
+        test_emul8r.run_for(limits(1))?;
+        assert_eq!(log.borrow().sound_events, [true]);
         Ok(())
     }
 
     #[test]
-    /// Test conditional jump instruction 0x3 (jump if equal)
-    fn test_unary_conditional_jump_equal_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let initial_position = test_emul8r.program_counter;
-
-        // Set the current instruction to be a conditional jump (version 0x3)
-        let register: u8 = 0x5;
-        let check_val = 0x9;
-        let byte1 = (0x3 << 4) | register;
-        let byte2 = check_val;
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+    /// Test that sound handling is skipped for frontends without audio
+    fn test_no_audio_capability() -> Result<()> {
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            stop_after: Some(10),
+            audio: false,
+            ..ScriptedFrontend::default()
+        };
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        assert!(!test_emul8r.capabilities().has_audio);
+        test_emul8r.load_rom(&[0x60, 0xFF, 0xF0, 0x18, 0x12, 0x04])?;
+        assert_eq!(
+            test_emul8r.run_for(RunLimits::default())?,
+            RunOutcome::Stopped
+        );
+        assert_eq!(test_emul8r.cycles(), 10);
+        assert!(log.borrow().sound_events.is_empty());
+        Ok(())
+    }
 
-        // Set the register to match the check_val
-        test_emul8r.set_reg(register.into(), check_val)?;
+    #[test]
+    /// Test that the program counter and timers stay put while paused
+    fn test_pause_freezes_state() -> Result<()> {
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            hotkeys: vec![(1, HotkeyAction::Pause)],
+            stop_after: Some(100),
+            audio: true,
+            ..ScriptedFrontend::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_rom(&[0x71, 0x01, 0x12, 0x00])?;
+        test_emul8r.delay_timer.store(10, Ordering::Relaxed);
 
-        // Execute the instruction
-        test_emul8r.execute()?;
+        assert_eq!(
+            test_emul8r.run_for(RunLimits::default())?,
+            RunOutcome::Stopped
+        );
+        assert!(test_emul8r.is_paused());
+        assert_eq!(test_emul8r.program_counter, GAME_MEMORY_START);
+        assert_eq!(test_emul8r.cycles(), 0);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(test_emul8r.delay_timer.load(Ordering::Relaxed), 10);
 
-        // Check that a jump occured
-        assert_eq!(test_emul8r.program_counter, initial_position + 4);
+        // Resuming lets the timers run down again
+        test_emul8r.set_paused(false)?;
+        thread::sleep(Duration::from_millis(100));
+        assert!(test_emul8r.delay_timer.load(Ordering::Relaxed) < 10);
+        test_emul8r.step()?;
+        assert_eq!(test_emul8r.program_counter, GAME_MEMORY_START + 2);
         Ok(())
     }
+
     #[test]
-    /// Test conditional jump instruction 0x3 (jump if equal)
-    fn test_unary_conditional_jump_equal_no_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let initial_position = test_emul8r.program_counter;
+    /// Test opening the menu, resetting a halted program from it, and quitting
+    fn test_menu() -> Result<()> {
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend {
+            // Open and close the menu before anything runs
+            hotkeys: vec![(1, HotkeyAction::Menu), (3, HotkeyAction::Menu)],
+            menu: true,
+            menu_inputs: vec![
+                // The program exits on iteration 4, opening the menu, reset it
+                (7, MenuInput::Down),
+                (8, MenuInput::Activate),
+                // It exits again on iteration 9, choose Quit
+                (11, MenuInput::Up),
+                (12, MenuInput::Activate),
+            ],
+            stop_after: Some(100),
+            ..ScriptedFrontend::default()
+        };
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_rom(&[
+            0x71, 0x01, // ADD V1, 0x01
+            0x00, 0xFD, // EXIT
+        ])?;
 
-        // Set the current instruction to be a conditional jump (version 0x3)
-        let register: u8 = 0x5;
-        let check_val = 0x9;
-        let byte1 = (0x3 << 4) | register;
-        let byte2 = check_val;
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+        assert_eq!(
+            test_emul8r.run_for(RunLimits::default())?,
+            RunOutcome::Stopped
+        );
+        // The menu was drawn on iterations 1-2, 5-7, and 10-11
+        assert_eq!(log.borrow().menu_draws, 7);
+        // Both runs of the program, with the reset clearing V1 in between
+        assert_eq!(test_emul8r.cycles(), 4);
+        assert_eq!(test_emul8r.get_reg(1)?, 1);
+        assert!(test_emul8r.is_halted());
+        Ok(())
+    }
 
-        // Set the register to NOT match the check_val
-        test_emul8r.set_reg(register.into(), check_val + 1)?;
+    #[test]
+    /// Test resetting the emulator back to the start of the ROM
+    fn test_reset() -> Result<()> {
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        test_emul8r.load_rom(&[0x61, 0x05, 0xA3, 0x00, 0x22, 0x08, 0x00, 0xE0, 0x00, 0xFD])?;
+        for _ in 0..4 {
+            test_emul8r.step()?;
+        }
+        assert!(test_emul8r.is_halted());
+        test_emul8r.delay_timer.store(30, Ordering::Relaxed);
 
-        // Execute the instruction
-        test_emul8r.execute()?;
+        test_emul8r.reset()?;
+        assert!(!test_emul8r.is_halted());
+        assert_eq!(test_emul8r.program_counter, GAME_MEMORY_START);
+        assert_eq!(test_emul8r.get_reg(1)?, 0);
+        assert_eq!(test_emul8r.get_index()?, 0);
+        assert!(test_emul8r.stack_trace().is_empty());
+        assert_eq!(test_emul8r.delay_timer.load(Ordering::Relaxed), 0);
+        // The ROM and font are still loaded
+        assert_eq!(
+            test_emul8r.memory[GAME_MEMORY_START..GAME_MEMORY_START + 2],
+            [0x61, 0x05]
+        );
+        assert_eq!(test_emul8r.memory[FONT_START_POSITION], FONT[0]);
+        test_emul8r.step()?;
+        assert_eq!(test_emul8r.get_reg(1)?, 5);
+        Ok(())
+    }
 
-        // Check that a jump didn't occur
-        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+    #[test]
+    /// Test that synchronous timers count down exactly once per frame
+    fn test_synchronous_timers() -> Result<()> {
+        let test_config = EmulatorConfig {
+            // 10 instructions per frame
+            instructions_per_second: 600,
+            synchronous_timers: true,
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        assert!(test_emul8r.ticker_handle.is_none());
+        test_emul8r.load_rom(&[
+            0x60, 0x1E, // LD V0, 30
+            0xF0, 0x15, // LD DT, V0
+            0xF1, 0x07, // LD V1, DT
+            0x12, 0x04, // JP 0x204
+        ])?;
+
+        // The timer is set during the first frame, and isn't ticked until the next
+        test_emul8r.run_frame()?;
+        assert_eq!(test_emul8r.get_reg(1)?, 30);
+        // Nothing changes between frames, however long it takes
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(test_emul8r.delay_timer.load(Ordering::Relaxed), 30);
+        for _ in 0..5 {
+            test_emul8r.run_frame()?;
+        }
+        assert_eq!(test_emul8r.get_reg(1)?, 25);
+        // Stepping within a frame doesn't tick the timers
+        test_emul8r.step()?;
+        test_emul8r.step()?;
+        assert_eq!(test_emul8r.get_reg(1)?, 25);
+        // Timers stop at 0
+        for _ in 0..40 {
+            test_emul8r.run_frame()?;
+        }
+        assert_eq!(test_emul8r.get_reg(1)?, 0);
         Ok(())
     }
+
     #[test]
-    /// Test conditional jump instruction 0x4 (jump if not equal)
-    fn test_unary_conditional_jump_not_equal_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let initial_position = test_emul8r.program_counter;
+    /// Test that the frontend is told when the beep starts and stops, even muted
+    fn test_sound_active() -> Result<()> {
+        let test_config = EmulatorConfig {
+            // 10 instructions per frame, with the timers ticked each frame
+            instructions_per_second: 600,
+            synchronous_timers: true,
+            audio_muted: true,
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend::default();
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_rom(&[
+            0x60, 0x03, // LD V0, 3
+            0x71, 0x01, // ADD V1, 1
+            0x31, 0x05, // SE V1, 5
+            0x12, 0x02, // JP 0x202
+            0xF0, 0x18, // LD ST, V0
+            0x12, 0x0A, // JP 0x20A
+        ])?;
+        let limits = RunLimits {
+            max_cycles: Some(20),
+            ..RunLimits::default()
+        };
 
-        // Set the current instruction to be a conditional jump (version 0x3)
-        let register: u8 = 0x5;
-        let check_val = 0x9;
-        let byte1 = (0x4 << 4) | register;
-        let byte2 = check_val;
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+        // The beep starts part way through the first frame
+        test_emul8r.run_for(limits)?;
+        assert_eq!(log.borrow().sound_active, [false, true]);
+        // And stops after 3 frames
+        test_emul8r.run_for(limits)?;
+        assert_eq!(log.borrow().sound_active, [false, true]);
+        test_emul8r.run_for(limits)?;
+        assert_eq!(log.borrow().sound_active, [false, true, false]);
+        Ok(())
+    }
 
-        // Set the register to NOT match the check_val
-        test_emul8r.set_reg(register.into(), check_val + 1)?;
+    #[test]
+    /// Test loading a ROM from memory
+    fn test_load_rom() -> Result<()> {
+        const ROM: &[u8] = &[0x00, 0xE0, 0x61, 0x05, 0x12, 0x02];
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        test_emul8r.load_rom(ROM)?;
+        assert_eq!(
+            &test_emul8r.memory[GAME_MEMORY_START..GAME_MEMORY_START + ROM.len()],
+            ROM
+        );
+        assert_eq!(test_emul8r.memory[GAME_MEMORY_START + ROM.len()], 0);
 
-        // Execute the instruction
-        test_emul8r.execute()?;
+        // The largest ROM that fits
+        let largest = vec![0xAB; MEMORY_SIZE - GAME_MEMORY_START];
+        test_emul8r.load_rom(&largest)?;
+        assert_eq!(test_emul8r.memory[MEMORY_SIZE - 1], 0xAB);
 
-        // Check that a jump occured
-        assert_eq!(test_emul8r.program_counter, initial_position + 4);
+        // Anything larger is rejected, without touching memory
+        test_emul8r.load_rom(ROM)?;
+        let message = test_emul8r
+            .load_rom(&vec![0xCD; MEMORY_SIZE - GAME_MEMORY_START + 1])
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("too large"), "{message}");
+        assert_eq!(test_emul8r.memory[GAME_MEMORY_START], 0x00);
+        assert_eq!(test_emul8r.memory[MEMORY_SIZE - 1], 0xAB);
         Ok(())
     }
+
     #[test]
-    /// Test conditional jump instruction 0x4 (jump if equal)
-    fn test_unary_conditional_jump_not_equal_no_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let initial_position = test_emul8r.program_counter;
+    /// Test the title shown for a loaded ROM, from the database or the file name
+    fn test_rom_title() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let known = dir.path().join("logo.ch8");
+        std::fs::write(&known, include_bytes!("../tests/roms/ibm_logo.ch8"))?;
+        let unknown = dir.path().join("mystery.ch8");
+        std::fs::write(&unknown, [0x12, 0x00])?;
 
-        // Set the current instruction to be a conditional jump (version 0x3)
-        let register: u8 = 0x5;
-        let check_val = 0x9;
-        let byte1 = (0x4 << 4) | register;
-        let byte2 = check_val;
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+        let frontend = ScriptedFrontend::default();
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), EmulatorConfig::default())?;
+        test_emul8r.load_file(&known)?;
+        test_emul8r.load_file(&unknown)?;
+        assert_eq!(log.borrow().titles, ["IBM Logo", "mystery.ch8"]);
 
-        // Set the register to match the check_val
-        test_emul8r.set_reg(register.into(), check_val)?;
+        // A database from the config replaces the embedded one
+        let database = dir.path().join("programs.json");
+        std::fs::write(
+            &database,
+            format!(
+                r#"[{{"title": "Mystery", "roms": {{"{}": {{}}}}}}]"#,
+                crate::rom::sha1_hex(&[0x12, 0x00])
+            ),
+        )?;
+        let frontend = ScriptedFrontend::default();
+        let log = frontend.log.clone();
+        let test_config = EmulatorConfig {
+            rom_database: database.to_string_lossy().into_owned(),
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        test_emul8r.load_file(&unknown)?;
+        test_emul8r.load_file(&known)?;
+        assert_eq!(log.borrow().titles, ["Mystery", "logo.ch8"]);
 
-        // Execute the instruction
-        test_emul8r.execute()?;
+        // A missing database is an error up front
+        let test_config = EmulatorConfig {
+            rom_database: dir
+                .path()
+                .join("missing.json")
+                .to_string_lossy()
+                .into_owned(),
+            ..EmulatorConfig::default()
+        };
+        assert!(Emulator::new(Box::new(NoOpFrontend::new()), test_config).is_err());
+        Ok(())
+    }
 
-        // Check that a jump didn't occur
-        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+    #[test]
+    /// Test reloading a watched ROM that changed keeps the game paused
+    fn test_reload_keeps_pause() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("game.ch8");
+        std::fs::write(&path, [0x60, 0x01, 0x12, 0x02])?;
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        test_emul8r.load_rom(&std::fs::read(&path)?)?;
+        test_emul8r.rom_watcher = Some(RomWatcher::new(&path, Duration::ZERO));
+        test_emul8r.set_paused(true)?;
+
+        let rom = [0x60, 0x02, 0x61, 0x03, 0x12, 0x04];
+        std::fs::write(&path, rom)?;
+        // Once to see the change, then (after the poll interval) to load it
+        test_emul8r.reload_watched_rom()?;
+        thread::sleep(Duration::from_millis(150));
+        test_emul8r.reload_watched_rom()?;
+        assert_eq!(test_emul8r.rom, rom);
+        assert!(test_emul8r.pause.is_manual());
         Ok(())
     }
 
     #[test]
-    /// Test condition jump instruction 0x5 (check if registers equal)
-    fn test_binary_conditional_jump_equal_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let initial_position = test_emul8r.program_counter;
+    /// Test errors which stopped the run are shown in the frontend, even ones
+    /// that can't be skipped, but not the frontend's own failures
+    fn test_show_error() -> Result<()> {
+        let frontend = ScriptedFrontend::default();
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), EmulatorConfig::default())?;
+        test_emul8r.jump(MEMORY_SIZE)?;
+        let err = test_emul8r.step().unwrap_err();
+        assert!(is_unrecoverable(&err));
+        test_emul8r.show_error(&err)?;
+        assert_eq!(log.borrow().errors.len(), 1);
 
-        // Set the current instruction to be a conditional jump (version 0x3)
-        let register1: u8 = 0x5;
-        let register2: u8 = 0x8;
-        let check_val = 0x9;
-        let byte1 = (0x5 << 4) | register1;
-        let byte2 = register2 << 4;
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+        let err = anyhow!("GPU context lost").context(FrontendFailure("Frontend failed drawing"));
+        test_emul8r.show_error(&err)?;
+        assert_eq!(log.borrow().errors.len(), 1);
+        Ok(())
+    }
 
-        // Set the registers to match the check_val
-        test_emul8r.set_reg(register1.into(), check_val)?;
-        test_emul8r.set_reg(register2.into(), check_val)?;
+    #[test]
+    /// Test the game is saved when the frontend fails, and can be resumed
+    fn test_emergency_save() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let test_config = EmulatorConfig {
+            instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+            ..EmulatorConfig::default()
+        };
+        // V1 += 1 forever
+        let rom = [0x71, 0x01, 0x12, 0x00];
+        let frontend = ScriptedFrontend {
+            fail_draws_from: Some(10),
+            ..ScriptedFrontend::default()
+        };
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config.clone())?;
+        test_emul8r.load_rom(&rom)?;
+        test_emul8r.enable_emergency_save(dir.path().to_path_buf());
+        let err = test_emul8r.run_for(RunLimits::default()).unwrap_err();
+        let path = emergency_save_path(dir.path(), &test_emul8r.rom_sha1());
+        let message = format!("{err:#}");
+        assert!(
+            message.starts_with(&format!("The game was saved to {}", path.display())),
+            "{message}"
+        );
+        assert!(message.contains("GPU context lost"), "{message}");
+        assert_eq!(log.borrow().sound_events, [false]);
+        // Nine draws, each followed by an instruction
+        assert_eq!(test_emul8r.cycles(), 9);
 
-        // Execute the instruction
-        test_emul8r.execute()?;
+        let mut resumed = Emulator::new(Box::new(NoOpFrontend::new()), test_config.clone())?;
+        resumed.load_rom(&rom)?;
+        assert!(resumed.resume_autosave(dir.path())?);
+        assert_eq!(resumed.cycles(), 9);
+        assert_eq!(resumed.get_reg(1)?, test_emul8r.get_reg(1)?);
+        assert_eq!(
+            SaveState::read(&path)?.registers,
+            resumed.save_state().registers
+        );
 
-        // Check that a jump occured
-        assert_eq!(test_emul8r.program_counter, initial_position + 4);
+        // Nothing is saved for errors from the program itself
+        let mut test_emul8r = Emulator::new(Box::new(ScriptedFrontend::default()), test_config)?;
+        test_emul8r.load_rom(&[0xFF, 0xFF])?;
+        test_emul8r.enable_emergency_save(dir.path().to_path_buf());
+        let err = test_emul8r.run_for(RunLimits::default()).unwrap_err();
+        assert!(!format!("{err:#}").contains("saved"));
+        assert!(!emergency_save_path(dir.path(), &test_emul8r.rom_sha1()).exists());
         Ok(())
     }
 
     #[test]
-    /// Test condition jump instruction 0x5 (check if registers equal)
-    fn test_binary_conditional_jump_equal_no_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let initial_position = test_emul8r.program_counter;
+    /// Test the splash is drawn before the ROM starts when enabled, and skipped
+    /// by a keypress
+    fn test_splash() -> Result<()> {
+        let mut splash_display = Display::new();
+        splash::draw_splash(&mut splash_display)?;
+        let splash_frame = splash_display.to_ascii();
+        let blank_frame = Display::new().to_ascii();
+        // Clear the screen, then loop
+        let rom = [0x00, 0xE0, 0x12, 0x02];
 
-        // Set the current instruction to be a conditional jump (version 0x3)
-        let register1: u8 = 0x5;
-        let register2: u8 = 0x8;
-        let check_val = 0x9;
-        let byte1 = (0x5 << 4) | register1;
-        let byte2 = register2 << 4;
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+        let run = |splash_seconds, key_down_from: Option<u32>| -> Result<(Vec<String>, u64)> {
+            let test_config = EmulatorConfig {
+                instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+                splash_seconds,
+                ..EmulatorConfig::default()
+            };
+            let frontend = ScriptedFrontend {
+                // Keypad key 5
+                keys_from: key_down_from.map(|from| (from, 1 << 5)),
+                stop_after: Some(10),
+                ..ScriptedFrontend::default()
+            };
+            let log = frontend.log.clone();
+            let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+            test_emul8r.load_rom(&rom)?;
+            test_emul8r.run()?;
+            let cycles = test_emul8r.cycles();
+            Ok((log.take().frames, cycles))
+        };
 
-        // Set the registers to not match
-        test_emul8r.set_reg(register1.into(), check_val)?;
-        test_emul8r.set_reg(register2.into(), check_val + 1)?;
+        // Disabled by default
+        let (frames, cycles) = run(0.0, None)?;
+        assert!(frames.iter().all(|frame| *frame == blank_frame));
+        assert_eq!(cycles, 10);
 
-        // Execute the instruction
-        test_emul8r.execute()?;
+        // The splash shows until the key goes down on the 3rd iteration, then
+        // the ROM runs from a blank display
+        let (frames, cycles) = run(60.0, Some(3))?;
+        assert!(frames[..3].iter().all(|frame| *frame == splash_frame));
+        assert!(frames[3..].iter().all(|frame| *frame == blank_frame));
+        assert_eq!(cycles, 7);
 
-        // Check that a jump didn't occur
-        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+        // Stopping while the splash shows stops before the ROM starts
+        let (frames, cycles) = run(60.0, None)?;
+        assert_eq!(frames.len(), 10);
+        assert!(frames.iter().all(|frame| *frame == splash_frame));
+        assert_eq!(cycles, 0);
         Ok(())
     }
 
     #[test]
-    /// Test condition jump instruction 0x9 (check if registers NOT equal)
-    fn test_binary_conditional_jump_not_equal_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let initial_position = test_emul8r.program_counter;
+    /// Test the frontend only sees complete frames with double buffering, never
+    /// a sprite drawn and erased within a frame
+    fn test_double_buffer() -> Result<()> {
+        let blank_frame = Display::new().to_ascii();
+        let run = |double_buffer, rom: &[u8]| -> Result<Vec<String>> {
+            let test_config = EmulatorConfig {
+                // Three instructions per frame
+                instructions_per_second: 3 * TIMER_HZ,
+                double_buffer,
+                ..EmulatorConfig::default()
+            };
+            let frontend = ScriptedFrontend {
+                stop_after: Some(12),
+                ..ScriptedFrontend::default()
+            };
+            let log = frontend.log.clone();
+            let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+            test_emul8r.load_rom(rom)?;
+            test_emul8r.run()?;
+            Ok(log.take().frames)
+        };
+        // Point I at the 0 glyph, then draw and erase it every frame
+        let flicker = [0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05, 0x12, 0x02];
+        let frames = run(false, &flicker)?;
+        assert!(frames.iter().any(|frame| *frame != blank_frame));
+        let frames = run(true, &flicker)?;
+        assert_eq!(frames.len(), 12);
+        assert!(frames.iter().all(|frame| *frame == blank_frame));
 
-        // Set the current instruction to be a conditional jump (version 0x3)
-        let register1: u8 = 0x5;
-        let register2: u8 = 0x8;
-        let check_val = 0x9;
-        let byte1 = (0x9 << 4) | register1;
-        let byte2 = register2 << 4;
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+        // Draw it once, it shows once the first frame is complete
+        let draw_once = [0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04];
+        let frames = run(true, &draw_once)?;
+        assert!(frames[..3].iter().all(|frame| *frame == blank_frame));
+        assert_ne!(frames[3], blank_frame);
+        assert!(frames[3..].iter().all(|frame| *frame == frames[3]));
+        Ok(())
+    }
 
-        // Set the registers to NOT match
-        test_emul8r.set_reg(register1.into(), check_val)?;
-        test_emul8r.set_reg(register2.into(), check_val + 1)?;
+    #[test]
+    /// Test stopping on a keypress, before the program sees the key
+    fn test_stop_on_key() -> Result<()> {
+        let run = |key_down_from: Option<u32>| -> Result<(RunOutcome, u64)> {
+            let test_config = EmulatorConfig {
+                instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+                ..EmulatorConfig::default()
+            };
+            let frontend = ScriptedFrontend {
+                keys_from: key_down_from.map(|from| (from, 1 << 5)),
+                stop_after: Some(10),
+                ..ScriptedFrontend::default()
+            };
+            let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+            // Loop forever
+            test_emul8r.load_rom(&[0x12, 0x00])?;
+            let limits = RunLimits {
+                stop_on_key: true,
+                ..RunLimits::default()
+            };
+            let outcome = test_emul8r.run_for(limits)?;
+            Ok((outcome, test_emul8r.cycles()))
+        };
 
-        // Execute the instruction
+        assert_eq!(run(Some(4))?, (RunOutcome::KeyPressed, 3));
+        assert_eq!(run(None)?, (RunOutcome::Stopped, 10));
+        Ok(())
+    }
+
+    #[test]
+    /// Test loading files at several addresses and poking registers, starting
+    /// at the chosen address again on a reset
+    fn test_load_layout() -> Result<()> {
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        let layout = MemoryLayout {
+            rom: vec![0x12, 0x00],
+            regions: vec![MemoryRegion {
+                name: "code.ch8".to_string(),
+                address: 0x600,
+                // V0 = 0x42, then loop
+                bytes: vec![0x60, 0x42, 0x16, 0x02],
+            }],
+            start_address: 0x600,
+            pokes: vec!["V1=5".parse()?],
+        };
+        test_emul8r.load_layout(&layout, Path::new("main.ch8"))?;
+        assert_eq!(test_emul8r.program_counter, 0x600);
+        assert_eq!(test_emul8r.memory[0x200..0x202], [0x12, 0x00]);
+        assert_eq!(test_emul8r.memory[0x600..0x604], [0x60, 0x42, 0x16, 0x02]);
         test_emul8r.execute()?;
+        assert_eq!(test_emul8r.get_reg(0x0)?, 0x42);
 
-        // Check that a jump occured
-        assert_eq!(test_emul8r.program_counter, initial_position + 4);
+        test_emul8r.memory[0x601] = 0x00;
+        test_emul8r.reset()?;
+        assert_eq!(test_emul8r.program_counter, 0x600);
+        assert_eq!(test_emul8r.memory[0x601], 0x42);
+        assert_eq!(test_emul8r.get_reg(0x0)?, 0);
+        // Pokes are applied again too
+        assert_eq!(test_emul8r.get_reg(0x1)?, 5);
         Ok(())
     }
 
     #[test]
-    /// Test condition jump instruction 0x9 (check if registers NOT equal)
-    fn test_binary_conditional_jump_not_equal_no_jump() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let initial_position = test_emul8r.program_counter;
-
-        // Set the current instruction to be a conditional jump (version 0x3)
-        let register1: u8 = 0x5;
-        let register2: u8 = 0x8;
-        let check_val = 0x9;
-        let byte1 = (0x9 << 4) | register1;
-        let byte2 = register2 << 4;
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+    /// Test frozen memory is written back at the end of every frame, and
+    /// write-once cheats only after loading
+    fn test_cheats() -> Result<()> {
+        let test_config = EmulatorConfig {
+            cheats: std::collections::BTreeMap::from([
+                ("lives".to_string(), "freeze 0x300 = 5".to_string()),
+                ("level".to_string(), "write-once 0x301 = 9".to_string()),
+            ]),
+            ..EmulatorConfig::default()
+        };
+        let frontend = ScriptedFrontend::default();
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+        assert_eq!(
+            log.borrow().cheats_text.as_deref(),
+            Some("Cheats: level, lives")
+        );
+        // Decrement the bytes at 0x300 and 0x301, then loop
+        test_emul8r.load_rom(&[
+            0xA3, 0x00, 0xF1, 0x65, 0x70, 0xFF, 0x71, 0xFF, 0xF1, 0x55, 0x12, 0x00,
+        ])?;
+        assert_eq!(test_emul8r.memory[0x300..0x302], [5, 9]);
+        for _ in 0..3 {
+            test_emul8r.run_frame()?;
+            assert_eq!(test_emul8r.memory[0x300], 5);
+        }
+        assert_ne!(test_emul8r.memory[0x301], 9);
 
-        // Set the registers to match
-        test_emul8r.set_reg(register1.into(), check_val)?;
-        test_emul8r.set_reg(register2.into(), check_val)?;
+        test_emul8r.add_cheat(Cheat::parse("score", "write-once 0x302 = 0x99")?);
+        test_emul8r.run_frame()?;
+        assert_eq!(test_emul8r.memory[0x302], 0x99);
+        assert_eq!(test_emul8r.cheats().len(), 3);
+        assert_eq!(
+            log.borrow().cheats_text.as_deref(),
+            Some("Cheats: level, lives, score")
+        );
+        test_emul8r.reset()?;
+        assert_eq!(test_emul8r.memory[0x300..0x303], [5, 9, 0x99]);
+        Ok(())
+    }
 
-        // Execute the instruction
+    #[test]
+    /// Test the frontend is told the resolution when a mode switch changes it
+    fn test_resolution_change() -> Result<()> {
+        let frontend = ScriptedFrontend::default();
+        let log = frontend.log.clone();
+        let mut test_emul8r = Emulator::new(Box::new(frontend), EmulatorConfig::default())?;
+        // Low resolution (already the resolution), high resolution twice, then
+        // low resolution
+        test_emul8r.load_rom(&[0x00, 0xFE, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFE])?;
+        test_emul8r.execute()?;
+        assert!(log.borrow().resolutions.is_empty());
+        test_emul8r.execute()?;
+        assert_eq!(log.borrow().resolutions, [(HIGH_RES_ROWS, HIGH_RES_COLS)]);
+        assert_eq!(
+            (test_emul8r.display.rows(), test_emul8r.display.cols()),
+            (HIGH_RES_ROWS, HIGH_RES_COLS)
+        );
         test_emul8r.execute()?;
+        assert_eq!(log.borrow().resolutions.len(), 1);
+        test_emul8r.execute()?;
+        assert_eq!(
+            log.borrow().resolutions,
+            [(HIGH_RES_ROWS, HIGH_RES_COLS), (DISPLAY_ROWS, DISPLAY_COLS)]
+        );
 
-        // Check that a jump didn't occur
-        assert_eq!(test_emul8r.program_counter, initial_position + 2);
+        // Resetting, and loading a state saved in the other resolution, switch
+        // back too
+        test_emul8r.load_rom(&[0x00, 0xFF])?;
+        test_emul8r.reset()?;
+        test_emul8r.execute()?;
+        let high_res_state = test_emul8r.save_state();
+        assert!(high_res_state.high_res);
+        test_emul8r.reset()?;
+        assert!(!test_emul8r.display.is_high_res());
+        test_emul8r.load_state(&high_res_state)?;
+        assert!(test_emul8r.display.is_high_res());
+        assert_eq!(log.borrow().resolutions.len(), 5);
+        assert_eq!(log.borrow().resolutions[4], (HIGH_RES_ROWS, HIGH_RES_COLS));
         Ok(())
     }
 
     #[test]
-    /// Test setting a register
-    fn test_set_register() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let register: u8 = 0x5;
-        let value: u8 = 0xF;
-
-        let byte1 = (0x6 << 4) | register;
-        let byte2 = value;
-
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+    /// Test drawing across the whole high resolution display, clearing it on
+    /// each switch
+    fn test_resolution_switch_at_runtime() -> Result<()> {
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        // Draw a line, switch to high resolution, draw a line at the right
+        // edge of the larger display, then draw it again one pixel lower
+        test_emul8r.load_rom(&[
+            0xA2, 0x12, 0xD0, 0x11, 0x00, 0xFF, 0x60, 0x7C, 0x61, 0x3F, 0xD0, 0x11, 0x71, 0x01,
+            0xD0, 0x11, 0x12, 0x10, 0xFF,
+        ])?;
+        test_emul8r.step_n(2)?;
+        assert!(test_emul8r.display.get(0, 0)?);
+        test_emul8r.step_n(4)?;
+        // The switch cleared the display, and the line is clipped at the new
+        // right edge
+        assert!(!test_emul8r.display.get(0, 0)?);
+        assert!(
+            test_emul8r
+                .display
+                .get(HIGH_RES_ROWS - 1, HIGH_RES_COLS - 1)?
+        );
+        assert!(!test_emul8r.display.get(HIGH_RES_ROWS - 1, 0)?);
+        let lit = |test_emul8r: &Emulator| {
+            test_emul8r
+                .display
+                .iter_cells()
+                .filter(|cell| **cell)
+                .count()
+        };
+        assert_eq!(lit(&test_emul8r), 4);
+        // Past the bottom of the larger display, wraps to its top
+        test_emul8r.step_n(2)?;
+        assert_eq!(test_emul8r.get_reg(1)?, 0x40);
+        assert!(test_emul8r.display.get(0, HIGH_RES_COLS - 1)?);
+        assert_eq!(lit(&test_emul8r), 8);
+        Ok(())
+    }
 
-        test_emul8r.execute()?;
+    #[test]
+    /// Test DXY0 draws a 16x16 sprite in high resolution, and nothing in low
+    /// resolution
+    fn test_large_sprite() -> Result<()> {
+        let mut test_emul8r =
+            Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        // The sprite is a box: a full top and bottom row, and the sides
+        let mut sprite = vec![0xFF, 0xFF];
+        for _ in 0..14 {
+            sprite.extend([0x80, 0x01]);
+        }
+        sprite.extend([0xFF, 0xFF]);
+        test_emul8r.memory[0x300..0x320].copy_from_slice(&sprite);
+        test_emul8r.draw_sprite(0x300, 0, 0, 0)?;
+        assert!(test_emul8r.display.iter_cells().all(|cell| !cell));
 
-        assert_eq!(test_emul8r.get_reg(register)?, value);
+        test_emul8r.set_high_res(true)?;
+        test_emul8r.draw_sprite(0x300, 0, 120, 2)?;
+        for col in 120..HIGH_RES_COLS {
+            assert!(test_emul8r.display.get(2, col)?);
+            assert!(test_emul8r.display.get(17, col)?);
+        }
+        assert!(test_emul8r.display.get(10, 120)?);
+        assert!(!test_emul8r.display.get(10, 121)?);
+        // The right half of the sprite is clipped, so its right side isn't drawn
+        assert!(!test_emul8r.display.get(10, 7)?);
+        assert_eq!(
+            test_emul8r
+                .display
+                .iter_cells()
+                .filter(|cell| **cell)
+                .count(),
+            8 * 2 + 14
+        );
+        assert_eq!(test_emul8r.get_reg(0xF)?, 0);
 
+        // Drawing it again turns it all off, with a collision
+        test_emul8r.draw_sprite(0x300, 0, 120, 2)?;
+        assert!(test_emul8r.display.iter_cells().all(|cell| !cell));
+        assert_eq!(test_emul8r.get_reg(0xF)?, 1);
         Ok(())
     }
 
     #[test]
-    /// Test adding a value to a register
-    fn test_add_register() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-        let register: u8 = 0x5;
-        let value: u8 = 0x2;
-        let to_add: u8 = 0x3;
-
-        test_emul8r.set_reg(register as usize, value)?;
+    /// Test pausing while the window doesn't have the focus, and that the focus
+    /// returning doesn't resume a manual pause
+    fn test_pause_on_focus_loss() -> Result<()> {
+        let run = |pause_on_focus_loss| -> Result<(u64, Vec<bool>)> {
+            let test_config = EmulatorConfig {
+                instructions_per_second: config::MAX_INSTRUCTIONS_PER_SECOND,
+                pause_on_focus_loss,
+                ..EmulatorConfig::default()
+            };
+            let frontend = ScriptedFrontend {
+                // Pause on the 20th iteration, resume on the 35th
+                hotkeys: vec![(20, HotkeyAction::Pause), (35, HotkeyAction::Pause)],
+                stop_after: Some(45),
+                audio: true,
+                // Unfocused for iterations 5-14, and 25-29 (while manually paused)
+                unfocused: Some(vec![5..15, 25..30]),
+                ..ScriptedFrontend::default()
+            };
+            let log = frontend.log.clone();
+            let mut test_emul8r = Emulator::new(Box::new(frontend), test_config)?;
+            test_emul8r.load_rom(&[
+                0x60, 0xFF, // LD V0, 0xFF
+                0xF0, 0x18, // LD ST, V0
+                0x71, 0x01, // ADD V1, 0x01
+                0x12, 0x04, // JP 0x204
+            ])?;
+            assert_eq!(
+                test_emul8r.run_for(RunLimits::default())?,
+                RunOutcome::Stopped
+            );
+            assert!(!test_emul8r.is_paused());
+            Ok((test_emul8r.cycles(), log.take().sound_events))
+        };
 
-        let byte1 = (0x7 << 4) | register;
-        let byte2 = to_add;
+        // 4 iterations, paused while unfocused, 5 more, manually paused through
+        // the rest of the focus loss, then 11 after resuming
+        let (cycles, sound_events) = run(true)?;
+        assert_eq!(cycles, 20);
+        // The beep stops whenever paused
+        assert_eq!(sound_events, [true, false, true, false, true]);
 
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+        // Without pause_on_focus_loss only the manual pause applies
+        let (cycles, sound_events) = run(false)?;
+        assert_eq!(cycles, 4 + 10 + 5 + 11);
+        assert_eq!(sound_events, [true, false, true]);
+        Ok(())
+    }
 
-        test_emul8r.execute()?;
+    #[test]
+    /// Test that with cycle_accurate a draw uses up more of a frame than a
+    /// register op
+    fn test_cycle_accurate() -> Result<()> {
+        // 20 register ops
+        let registers = [0x6A, 0x05].repeat(20);
+        // A draw, then 19 register ops
+        let mut draw = vec![0xD0, 0x11];
+        draw.extend([0x6A, 0x05].repeat(19));
 
-        assert_eq!(test_emul8r.get_reg(register)?, value + to_add);
+        let frame_cycles = |cycle_accurate, rom: &[u8]| -> Result<u64> {
+            let test_config = EmulatorConfig {
+                instructions_per_second: 600,
+                cycle_accurate,
+                ..EmulatorConfig::default()
+            };
+            let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+            test_emul8r.load_rom(rom)?;
+            test_emul8r.run_frame()?;
+            Ok(test_emul8r.cycles())
+        };
+        assert_eq!(frame_cycles(false, &registers)?, 10);
+        assert_eq!(frame_cycles(false, &draw)?, 10);
+        // Register ops cost 1, so the budget of 10 still fits 10 of them, but the
+        // draw alone uses it all up
+        assert_eq!(frame_cycles(true, &registers)?, 10);
+        assert_eq!(frame_cycles(true, &draw)?, 1);
 
+        // A cheaper draw leaves room for some register ops
+        let test_config = EmulatorConfig {
+            instructions_per_second: 600,
+            cycle_accurate: true,
+            cycle_costs: std::collections::BTreeMap::from([("DXYN".to_string(), 4)]),
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        test_emul8r.load_rom(&draw)?;
+        test_emul8r.run_frame()?;
+        assert_eq!(test_emul8r.cycles(), 7);
         Ok(())
     }
 
     #[test]
-    /// Test setting one register to the value of another
-    fn test_set_register_to_register() -> Result<()> {
-        let test_frontend = NoOpFrontend::new();
-        let test_config = EmulatorConfig::default();
-        let mut test_emul8r = Emulator::new(Box::new(test_frontend), test_config)?;
-
-        let x: u8 = 0x2;
-        let y: u8 = 0xF;
-        let vx: u8 = 0x9;
-        let vy: u8 = 0x2;
+    /// Test ticking the timers by hand, carrying over partial ticks
+    fn test_tick_timers() -> Result<()> {
+        let test_config = EmulatorConfig {
+            synchronous_timers: true,
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        test_emul8r.delay_timer.store(5, Ordering::Relaxed);
+        test_emul8r.sound_timer.store(10, Ordering::Relaxed);
 
-        let byte1 = (0x8 << 4) | x;
-        let byte2 = y << 4;
+        // 50ms is 3 ticks (and a bit)
+        test_emul8r.tick_timers(Duration::from_millis(50));
+        assert_eq!(test_emul8r.delay_timer.load(Ordering::Relaxed), 2);
+        assert_eq!(test_emul8r.sound_timer.load(Ordering::Relaxed), 7);
 
-        test_emul8r.memory[test_emul8r.program_counter] = byte1;
-        test_emul8r.memory[test_emul8r.program_counter + 1] = byte2;
+        // Partial ticks add up
+        test_emul8r.tick_timers(Duration::from_millis(10));
+        assert_eq!(test_emul8r.delay_timer.load(Ordering::Relaxed), 2);
+        test_emul8r.tick_timers(Duration::from_millis(10));
+        assert_eq!(test_emul8r.delay_timer.load(Ordering::Relaxed), 1);
 
-        test_emul8r.registers[x as usize] = vx;
-        test_emul8r.registers[y as usize] = vy;
+        // The timers stop at 0
+        test_emul8r.tick_timers(Duration::from_secs(10));
+        assert_eq!(test_emul8r.delay_timer.load(Ordering::Relaxed), 0);
+        assert_eq!(test_emul8r.sound_timer.load(Ordering::Relaxed), 0);
+        Ok(())
+    }
 
-        test_emul8r.execute()?;
+    #[test]
+    /// Test the statistics collected by a run, and that they survive a JSON round trip
+    fn test_stats() -> Result<()> {
+        // Ten instructions a frame, so the run is a single frame
+        let test_config = EmulatorConfig {
+            instructions_per_second: 600,
+            ..EmulatorConfig::default()
+        };
+        let mut test_emul8r = Emulator::new(Box::new(HeadlessFrontend::new()), test_config)?;
+        test_emul8r.load_rom(&[
+            0x22, 0x06, // CALL 0x206
+            0xD0, 0x15, // DRW V0, V0, 5
+            0x12, 0x04, // JP 0x204
+            0x60, 0x01, // LD V0, 0x01
+            0x00, 0xEE, // RET
+        ])?;
+        let limits = RunLimits {
+            max_cycles: Some(10),
+            ..RunLimits::default()
+        };
+        assert_eq!(test_emul8r.run_for(limits)?, RunOutcome::LimitReached);
 
-        assert_eq!(test_emul8r.registers[x as usize], vy);
-        assert_eq!(test_emul8r.registers[y as usize], vy);
+        let json = serde_json::to_string(&test_emul8r.stats())?;
+        let stats: RunStats = serde_json::from_str(&json)?;
+        assert_eq!(stats.version, crate::stats::STATS_VERSION);
+        assert_eq!(stats.instructions, 10);
+        assert_eq!(stats.draws, 1);
+        assert_eq!(stats.frames_presented, 1);
+        assert_eq!(stats.max_stack_depth, 1);
+        assert_eq!(stats.halt_reason, Some(HaltReason::LimitReached));
+        assert_eq!(stats.error, None);
+        assert!(stats.wall_time_seconds > 0.0 && stats.achieved_ips > 0.0);
+        let histogram: Vec<(&str, u64)> = stats
+            .opcode_histogram
+            .iter()
+            .map(|(pattern, count)| (pattern.as_str(), *count))
+            .collect();
+        assert_eq!(
+            histogram,
+            [
+                ("00EE", 1),
+                ("1NNN", 6),
+                ("2NNN", 1),
+                ("6XNN", 1),
+                ("DXYN", 1)
+            ]
+        );
 
+        // Crashes record the error
+        test_emul8r.load_rom(&[0xFF, 0xFF])?;
+        test_emul8r.program_counter = 0x200;
+        assert!(test_emul8r.run_for(limits).is_err());
+        let stats = test_emul8r.stats();
+        assert_eq!(stats.instructions, 11);
+        assert_eq!(stats.halt_reason, Some(HaltReason::Error));
+        assert!(stats.error.is_some_and(|error| error.contains("FFFF")));
         Ok(())
     }
 }
@@ -0,0 +1,107 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Widest line of an error screen, in characters
+pub const ERROR_SCREEN_COLUMNS: usize = 48;
+
+/// Split `text` into lines of at most `columns` characters, breaking between
+/// words where possible
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        // Words too long for a line of their own are broken up
+        let chars: Vec<char> = word.chars().collect();
+        for piece in chars.chunks(columns.max(1)) {
+            let piece: String = piece.iter().collect();
+            let length = line.chars().count();
+            if length > 0 && length + 1 + piece.chars().count() > columns {
+                lines.push(core::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&piece);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The text describing a run stopped by `error` after `cycles` instructions,
+/// wrapped to `columns` characters
+///
+/// Each cause of the error starts a new line, with the outermost first.
+pub fn error_lines(error: &anyhow::Error, cycles: u64, columns: usize) -> Vec<String> {
+    let mut lines = wrap(
+        &format!("The emulator stopped after {cycles} instructions:"),
+        columns,
+    );
+    lines.push(String::new());
+    for (index, cause) in error.chain().enumerate() {
+        let text = if index == 0 {
+            cause.to_string()
+        } else {
+            format!("Caused by: {cause}")
+        };
+        lines.extend(wrap(&text, columns));
+    }
+    lines
+}
+
+/// The text shown on screen when a run stops with `error` after `cycles`
+/// instructions, wrapped to `columns` characters (see [error_lines])
+pub fn error_screen_text(error: &anyhow::Error, cycles: u64, columns: usize) -> Vec<String> {
+    let mut lines = error_lines(error, cycles, columns);
+    lines.push(String::new());
+    lines.extend(wrap("Press any key or close the window to quit", columns));
+    lines
+}
+
+#[cfg(test)]
+mod test_error_screen {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    /// Test wrapping between words, and breaking up words too long for a line
+    fn test_wrap() {
+        assert_eq!(
+            wrap("Trying to pop from  empty stack", 12),
+            ["Trying to", "pop from", "empty stack"]
+        );
+        assert_eq!(
+            wrap("0123456789abcdef ok", 8),
+            ["01234567", "89abcdef", "ok"]
+        );
+        assert!(wrap("   ", 8).is_empty());
+    }
+
+    #[test]
+    /// Test the error screen shows every cause, within the width
+    fn test_error_screen_text() {
+        let error = Err::<(), _>(anyhow!("Unknown opcode 0xFFFF at 0x204"))
+            .context("Executing the instruction at 0x204")
+            .unwrap_err();
+        let lines = error_screen_text(&error, 1234, 24);
+        assert_eq!(
+            lines,
+            [
+                "The emulator stopped",
+                "after 1234 instructions:",
+                "",
+                "Executing the",
+                "instruction at 0x204",
+                "Caused by: Unknown",
+                "opcode 0xFFFF at 0x204",
+                "",
+                "Press any key or close",
+                "the window to quit",
+            ]
+        );
+        assert!(lines.iter().all(|line| line.chars().count() <= 24));
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::{Duration, Instant};
+
+use crate::color::blend;
+
+/// How long each frame lasts, the display shown one frame ago is blended with
+/// the current one
+pub const FRAME_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// The color of each pixel, the straight average of its colors in the
+/// `current` and `previous` frames (row major, like [crate::display::Display::snapshot])
+pub fn blend_frames(current: &[(u8, u8, u8)], previous: &[(u8, u8, u8)]) -> Vec<(u8, u8, u8)> {
+    current
+        .iter()
+        .zip(previous)
+        .map(|(&current, &previous)| blend(previous, current, 0.5))
+        .collect()
+}
+
+/// Blends each frame drawn with the one shown a frame earlier, smoothing
+/// scrolling shown at low frame rates (the `frame_blend` setting)
+///
+/// Frontends draw far more often than once a frame, so the previous frame is
+/// whatever was drawn last before the current frame started.
+#[derive(Debug, Clone)]
+pub struct FrameBlender {
+    /// Last frame drawn before the current frame started
+    previous: Vec<(u8, u8, u8)>,
+    /// Last frame drawn
+    latest: Vec<(u8, u8, u8)>,
+    frame_start: Instant,
+}
+
+impl FrameBlender {
+    /// A blender with nothing drawn before `now`
+    pub fn new(now: Instant) -> Self {
+        Self {
+            previous: Vec::new(),
+            latest: Vec::new(),
+            frame_start: now,
+        }
+    }
+
+    /// The color to draw each pixel of `frame` (the color of each pixel),
+    /// drawn at `now` (see [blend_frames])
+    ///
+    /// Frames of a new size (e.g. switching resolution) aren't blended until
+    /// the frame after.
+    pub fn blend(&mut self, frame: &[(u8, u8, u8)], now: Instant) -> Vec<(u8, u8, u8)> {
+        if now.saturating_duration_since(self.frame_start) >= FRAME_PERIOD {
+            self.previous = std::mem::take(&mut self.latest);
+            self.frame_start = now;
+        }
+        self.latest = frame.to_vec();
+        if self.previous.len() == frame.len() {
+            blend_frames(frame, &self.previous)
+        } else {
+            frame.to_vec()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_frame_blend {
+    use super::*;
+
+    const OFF: (u8, u8, u8) = (0, 0, 0);
+    const ON: (u8, u8, u8) = (255, 255, 255);
+    const HALF: (u8, u8, u8) = (128, 128, 128);
+    const RED: (u8, u8, u8) = (255, 0, 0);
+
+    #[test]
+    /// Test a pixel lit in only one of the frames is lit halfway, and pixels
+    /// changing color are halfway between
+    fn test_blend_frames() {
+        assert_eq!(
+            blend_frames(&[ON, OFF, ON, OFF, RED], &[OFF, ON, ON, OFF, ON]),
+            [HALF, HALF, ON, OFF, (255, 128, 128)]
+        );
+    }
+
+    #[test]
+    /// Test the frames are blended with the one from the frame before
+    fn test_frame_blender() {
+        let start = Instant::now();
+        let mut blender = FrameBlender::new(start);
+        // Nothing before the first frame to blend with
+        assert_eq!(blender.blend(&[ON, OFF], start), [ON, OFF]);
+        // Drawn again within the frame, still nothing before it
+        assert_eq!(blender.blend(&[OFF, ON], start), [OFF, ON]);
+        // The next frame blends with the last drawn in the one before
+        let next = start + FRAME_PERIOD;
+        assert_eq!(blender.blend(&[ON, ON], next), [HALF, ON]);
+        assert_eq!(blender.blend(&[ON, RED], next), [HALF, (255, 128, 128)]);
+        // A new resolution isn't blended with the old one
+        assert_eq!(
+            blender.blend(&[ON, OFF, OFF], next + FRAME_PERIOD),
+            [ON, OFF, OFF]
+        );
+    }
+}
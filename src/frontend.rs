@@ -1,6 +1,27 @@
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
 use anyhow::Result;
 
 use crate::display::Display;
+use crate::hotkeys::HotkeyAction;
+use crate::keymap::Keymap;
+use crate::menu::{MenuInput, PauseMenu};
+
+/// Optional features a frontend may support, so the emulator can adapt to it
+/// rather than assuming
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrontendCapabilities {
+    /// Can play the beep, without it the emulator skips sound handling entirely
+    pub has_audio: bool,
+    /// Can show displays at a higher resolution than 64x32
+    pub supports_hires: bool,
+    /// Can tell whether its window has the focus
+    pub reports_focus: bool,
+    /// Can have files dropped onto it
+    pub supports_file_drop: bool,
+}
 
 /// Trait for implementing a front-end to the compiler,
 /// will essentially need a way to draw the display,
@@ -23,6 +44,136 @@ pub trait Frontend {
     /// 0x0 and 0xF, how these are mapped to an actual
     /// input is up to the frontend to decide.
     fn check_key(&mut self, key: u8) -> Result<bool>;
+    /// The keys which are currently down, as a bitmask with bit N set
+    /// when key N is down
+    ///
+    /// The default checks each key in turn with [Frontend::check_key].
+    fn current_keys(&mut self) -> Result<u16> {
+        let mut keys = 0;
+        for key in 0..16 {
+            if self.check_key(key)? {
+                keys |= 1 << key;
+            }
+        }
+        Ok(keys)
+    }
+    /// Check if the key bound to a hotkey action was just pressed
+    ///
+    /// Frontends without hotkey support can rely on the default,
+    /// which never reports a press.
+    fn check_hotkey(&mut self, _action: HotkeyAction) -> Result<bool> {
+        Ok(false)
+    }
+    /// The optional features the frontend supports
+    ///
+    /// Frontends can rely on the default, which supports none of them.
+    fn capabilities(&self) -> FrontendCapabilities {
+        FrontendCapabilities::default()
+    }
+    /// Whether the frontend can show the pause menu (see [Frontend::draw_menu])
+    ///
+    /// When it can, the menu opens when the program halts rather than the
+    /// emulator stopping, so it can be reset or quit. The default is false.
+    fn has_menu(&self) -> bool {
+        false
+    }
+    /// Tell the frontend whether the beep is sounding (the sound timer is
+    /// running), called every loop before [Frontend::draw]
+    ///
+    /// Frontends can use this to show the beep visually. The default ignores it.
+    fn set_sound_active(&mut self, _active: bool) {}
+    /// Draw the display with the pause menu over it
+    ///
+    /// Frontends without a menu can rely on the default, which just draws
+    /// the display.
+    fn draw_menu(&mut self, display: &Display, _menu: &PauseMenu) -> Result<()> {
+        self.draw(display)
+    }
+    /// Draw two displays side by side, each with a label above it (see
+    /// [crate::split_view]), and the lines of the error which stopped a half
+    /// (empty while it's running) in place of its display
+    ///
+    /// Frontends which can't fit two displays can rely on the default, which
+    /// just draws the first.
+    fn draw_split(
+        &mut self,
+        displays: [&Display; 2],
+        _labels: [&str; 2],
+        _errors: [&[String]; 2],
+    ) -> Result<()> {
+        self.draw(displays[0])
+    }
+    /// Navigation input for the pause menu since the last check, if any
+    ///
+    /// Frontends without a menu can rely on the default, which never
+    /// reports any input.
+    fn menu_input(&mut self, _menu: &PauseMenu) -> Result<Option<MenuInput>> {
+        Ok(None)
+    }
+    /// Whether the frontend's window has the focus, only called if it
+    /// [FrontendCapabilities::reports_focus]
+    ///
+    /// The default always reports having the focus.
+    fn is_focused(&mut self) -> bool {
+        true
+    }
+    /// Show the FPS / IPS counter with this text, or hide it if None
+    ///
+    /// Only called when the text changes. Frontends which can't show it can
+    /// rely on the default, which ignores it.
+    fn set_perf_text(&mut self, _text: Option<&str>) {}
+    /// List the enabled cheats with this text in the debug overlay (drawn with
+    /// the debug grid), or nothing if None
+    ///
+    /// Called when the cheats change. Frontends without a debug overlay can
+    /// rely on the default, which ignores it.
+    fn set_cheats_text(&mut self, _text: Option<&str>) {}
+    /// Show the title of the loaded ROM (e.g. in the window title)
+    ///
+    /// Frontends without anywhere to show it can rely on the default, which
+    /// ignores it.
+    fn set_title(&mut self, _title: &str) -> Result<()> {
+        Ok(())
+    }
+    /// Switch to the next color theme
+    ///
+    /// Frontends without colors can rely on the default, which does nothing.
+    fn cycle_theme(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Ask for a ROM to load in place of the running one, None if there isn't one
+    ///
+    /// Frontends which can't pick files can rely on the default, which returns None.
+    #[cfg(feature = "std")]
+    fn choose_rom(&mut self) -> Result<Option<PathBuf>> {
+        Ok(None)
+    }
+    /// The display switched to `rows` by `cols` pixels, e.g. to resize anything
+    /// drawn at the old resolution
+    ///
+    /// Called when 00FE or 00FF changes the resolution, and when resetting or
+    /// loading a save state switches back to the other one.
+    ///
+    /// Frontends which draw any resolution the same way can rely on the
+    /// default, which does nothing.
+    fn on_resolution_change(&mut self, _rows: usize, _cols: usize) -> Result<()> {
+        Ok(())
+    }
+    /// Show `lines` of text explaining why the run stopped, until the player
+    /// dismisses them (by pressing a key or closing the window)
+    ///
+    /// Frontends without a window can rely on the default, which returns
+    /// straight away.
+    fn show_error(&mut self, _lines: &[String]) -> Result<()> {
+        Ok(())
+    }
+    /// Use `keymap` for the keypad from now on, in place of the one the
+    /// frontend was created with
+    ///
+    /// Frontends without a keyboard can rely on the default, which ignores it.
+    fn set_keymap(&mut self, _keymap: &Keymap) -> Result<()> {
+        Ok(())
+    }
     /// Play a tone until [stop_sound] is called
     ///
     /// The tone can be anything that the frontend wants it to be.
@@ -31,6 +182,13 @@ pub trait Frontend {
     fn stop_sound(&mut self) -> Result<()>;
     /// Check if the emulator should exit
     fn should_stop(&mut self) -> bool;
+    /// The rate (in frames per second) the frontend is actually presenting at
+    ///
+    /// Frontends which don't track this (e.g. headless ones) can rely on the
+    /// default, which returns None.
+    fn achieved_fps(&self) -> Option<f32> {
+        None
+    }
     /// Function called during every instruction loop
     ///
     /// Mainly a workaround to allow raylib front end to keep the audio playing
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::config::EmulatorConfig;
+use crate::emulator::Emulator;
+use crate::headless_frontend::HeadlessFrontend;
+
+/// Extension of the golden image kept next to each ROM
+pub const GOLDEN_EXTENSION: &str = "txt";
+
+/// The golden image of the ROM at `rom`: the display expected once it has run,
+/// rendered as ASCII (see [crate::display::Display::to_ascii]), in a file next
+/// to it with the [GOLDEN_EXTENSION]
+pub fn golden_path(rom: &Path) -> PathBuf {
+    rom.with_extension(GOLDEN_EXTENSION)
+}
+
+/// The ROMs to test in `paths`: files are tested as they are, and directories
+/// for each ROM in them (or the directories inside them) with a golden image
+pub fn find_roms(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            find_in_dir(path, &mut roms)?;
+        } else {
+            roms.push(path.clone());
+        }
+    }
+    Ok(roms)
+}
+
+/// Add the ROMs with a golden image in `dir`, and the directories inside it, to
+/// `roms` in order of their paths
+fn find_in_dir(dir: &Path, roms: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Reading directory {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("Reading directory {}", dir.display()))?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_in_dir(&path, roms)?;
+        } else if path.extension().is_none_or(|ext| ext != GOLDEN_EXTENSION)
+            && golden_path(&path).exists()
+        {
+            roms.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The display of `rom` rendered as ASCII after running it headless for
+/// `frames` frames with `config`
+pub fn render(rom: &[u8], config: EmulatorConfig, frames: u64) -> Result<String> {
+    let config = EmulatorConfig {
+        synchronous_timers: true,
+        audio_enabled: false,
+        ..config
+    };
+    let mut emulator = Emulator::new(Box::new(HeadlessFrontend::new()), config)?;
+    emulator.load_rom(rom)?;
+    for _ in 0..frames {
+        emulator.run_frame()?;
+    }
+    Ok(emulator.display().to_ascii())
+}
+
+/// How a ROM compared against its golden image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The display matched the golden image
+    Passed,
+    /// The display didn't match, it was `rendered` instead
+    Failed { rendered: String },
+    /// The golden image was written from the display, rather than compared
+    Updated,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Passed => write!(f, "PASS"),
+            Self::Failed { .. } => write!(f, "FAIL"),
+            Self::Updated => write!(f, "UPDATED"),
+        }
+    }
+}
+
+/// Compare the display of `rom` (read from `rom_path`) after `frames` frames
+/// against its golden image, or write the golden image if `update`
+pub fn check(
+    rom_path: &Path,
+    rom: &[u8],
+    config: EmulatorConfig,
+    frames: u64,
+    update: bool,
+) -> Result<Outcome> {
+    let rendered =
+        render(rom, config, frames).with_context(|| format!("Running {}", rom_path.display()))?;
+    let path = golden_path(rom_path);
+    if update {
+        std::fs::write(&path, &rendered)
+            .with_context(|| format!("Writing golden image {}", path.display()))?;
+        return Ok(Outcome::Updated);
+    }
+    let expected = std::fs::read_to_string(&path)
+        .with_context(|| format!("Reading golden image {}", path.display()))?;
+    if rendered == expected {
+        Ok(Outcome::Passed)
+    } else {
+        Ok(Outcome::Failed { rendered })
+    }
+}
+
+#[cfg(test)]
+mod test_golden {
+    use super::*;
+
+    /// Clears the screen, draws the 0 font sprite at (0, 0), and loops
+    const DRAW_ZERO: [u8; 10] = [0x00, 0xE0, 0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x08];
+
+    #[test]
+    /// Test writing a golden image, then passing and failing against it
+    fn test_check() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rom_path = dir.path().join("zero.ch8");
+        std::fs::write(&rom_path, DRAW_ZERO)?;
+        let config = EmulatorConfig::default;
+
+        // Without a golden image
+        assert!(check(&rom_path, &DRAW_ZERO, config(), 1, false).is_err());
+
+        assert_eq!(
+            check(&rom_path, &DRAW_ZERO, config(), 1, true)?,
+            Outcome::Updated
+        );
+        let golden = std::fs::read_to_string(golden_path(&rom_path))?;
+        assert_eq!(golden, render(&DRAW_ZERO, config(), 1)?);
+        assert!(golden.starts_with("####"), "{golden}");
+        assert_eq!(
+            check(&rom_path, &DRAW_ZERO, config(), 1, false)?,
+            Outcome::Passed
+        );
+
+        // A different ROM doesn't match
+        let blank = [0x00, 0xE0, 0x12, 0x02];
+        let Outcome::Failed { rendered } = check(&rom_path, &blank, config(), 1, false)? else {
+            panic!("A blank display matched the golden image");
+        };
+        assert!(!rendered.contains('#'), "{rendered}");
+        Ok(())
+    }
+
+    #[test]
+    /// Test directories are searched for ROMs with golden images
+    fn test_find_roms() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let nested = dir.path().join("suite");
+        std::fs::create_dir(&nested)?;
+        for rom in ["a.ch8", "b.ch8", "suite/c.ch8"] {
+            std::fs::write(dir.path().join(rom), DRAW_ZERO)?;
+        }
+        for golden in ["a.txt", "suite/c.txt"] {
+            std::fs::write(dir.path().join(golden), "")?;
+        }
+        let file = dir.path().join("b.ch8");
+        assert_eq!(
+            find_roms(&[dir.path().to_path_buf(), file.clone()])?,
+            [
+                dir.path().join("a.ch8"),
+                nested.join("c.ch8"),
+                // Files are tested whether or not they have a golden image
+                file,
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+
+use crate::display::Display;
+use crate::frontend::Frontend;
+
+/// A frontend without a window, keyboard, or sound, for running the emulator
+/// headless (e.g. for scripted runs with [crate::emulator::RunLimits])
+///
+/// Never asks the emulator to stop, so runs until the program exits or a limit
+/// is reached.
+#[derive(Debug, Default)]
+pub struct HeadlessFrontend {}
+
+impl HeadlessFrontend {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Frontend for HeadlessFrontend {
+    fn draw(&mut self, _display: &Display) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_key(&mut self, _key: u8) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn play_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn should_stop(&mut self) -> bool {
+        false
+    }
+
+    fn step(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use anyhow::{Result, bail};
+
+use crate::display::Display;
+use crate::frontend::Frontend;
+use crate::keymap::KEYPAD_SIZE;
+
+/// Keypad keys held down by the program embedding the emulator (e.g. the
+/// Python or JavaScript bindings), shared with its [HostFrontend]
+#[derive(Debug, Clone, Default)]
+pub struct HostKeys {
+    /// Keys down (bit N for key N)
+    keys: Rc<Cell<u16>>,
+}
+
+impl HostKeys {
+    /// Press (`down`) or release keypad key `key` (0 to 15)
+    pub fn set(&self, key: usize, down: bool) -> Result<()> {
+        if key >= KEYPAD_SIZE {
+            bail!(
+                "Invalid keypad key {key}, expected 0 to {}",
+                KEYPAD_SIZE - 1
+            );
+        }
+        let keys = self.keys.get();
+        let bit = 1 << key;
+        self.keys.set(if down { keys | bit } else { keys & !bit });
+        Ok(())
+    }
+
+    /// Hold down exactly the keys in `keys` (bit N for key N)
+    pub fn set_all(&self, keys: u16) {
+        self.keys.set(keys);
+    }
+
+    /// Whether keypad key `key` is down
+    pub fn is_down(&self, key: u8) -> bool {
+        usize::from(key) < KEYPAD_SIZE && self.keys.get() & (1 << key) != 0
+    }
+}
+
+/// A frontend whose keys are set by the program embedding the emulator, with
+/// nothing to draw to, as the host reads the display itself
+pub struct HostFrontend {
+    keys: HostKeys,
+}
+
+impl HostFrontend {
+    /// A frontend with the keys in `keys` held down
+    pub fn new(keys: HostKeys) -> Self {
+        Self { keys }
+    }
+}
+
+impl Frontend for HostFrontend {
+    fn draw(&mut self, _display: &Display) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_key(&mut self, key: u8) -> Result<bool> {
+        Ok(self.keys.is_down(key))
+    }
+
+    fn play_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn should_stop(&mut self) -> bool {
+        false
+    }
+
+    fn step(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_host_frontend {
+    use super::*;
+
+    #[test]
+    /// Test the frontend sees the keys the host sets
+    fn test_host_keys() -> Result<()> {
+        let keys = HostKeys::default();
+        let mut frontend = HostFrontend::new(keys.clone());
+        keys.set(5, true)?;
+        keys.set(15, true)?;
+        assert!(frontend.check_key(5)?);
+        assert!(frontend.check_key(15)?);
+        keys.set(5, false)?;
+        assert!(!frontend.check_key(5)?);
+        assert!(!frontend.check_key(16)?);
+        assert!(keys.set(16, true).is_err());
+        keys.set_all(1 << 3 | 1 << 7);
+        assert!(frontend.check_key(3)? && frontend.check_key(7)?);
+        assert!(!frontend.check_key(15)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use anyhow::{Context, Result, bail};
+
+use crate::keymap::{Keymap, normalize_key_name};
+
+/// Emulator actions (as opposed to keypad keys) which can be bound to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HotkeyAction {
+    Pause,
+    Reset,
+    SaveState,
+    LoadState,
+    Screenshot,
+    Turbo,
+    ToggleOverlay,
+    Menu,
+    Mute,
+    ToggleFps,
+    ToggleGrid,
+    Step,
+}
+
+impl HotkeyAction {
+    /// All of the hotkey actions
+    pub const ALL: [HotkeyAction; 12] = [
+        HotkeyAction::Pause,
+        HotkeyAction::Reset,
+        HotkeyAction::SaveState,
+        HotkeyAction::LoadState,
+        HotkeyAction::Screenshot,
+        HotkeyAction::Turbo,
+        HotkeyAction::ToggleOverlay,
+        HotkeyAction::Menu,
+        HotkeyAction::Mute,
+        HotkeyAction::ToggleFps,
+        HotkeyAction::ToggleGrid,
+        HotkeyAction::Step,
+    ];
+
+    /// Name of the action as used in the `[hotkeys]` config table
+    pub fn name(&self) -> &'static str {
+        match self {
+            HotkeyAction::Pause => "pause",
+            HotkeyAction::Reset => "reset",
+            HotkeyAction::SaveState => "save_state",
+            HotkeyAction::LoadState => "load_state",
+            HotkeyAction::Screenshot => "screenshot",
+            HotkeyAction::Turbo => "turbo",
+            HotkeyAction::ToggleOverlay => "toggle_overlay",
+            HotkeyAction::Menu => "menu",
+            HotkeyAction::Mute => "mute",
+            HotkeyAction::ToggleFps => "toggle_fps",
+            HotkeyAction::ToggleGrid => "toggle_grid",
+            HotkeyAction::Step => "step",
+        }
+    }
+
+    /// Find the action with the given name
+    pub fn from_name(name: &str) -> Result<Self> {
+        let lower = name.trim().to_lowercase();
+        match Self::ALL.iter().find(|action| action.name() == lower) {
+            Some(action) => Ok(*action),
+            None => bail!(
+                "Unknown hotkey action {name:?}, available actions are: {}",
+                Self::ALL.map(|action| action.name()).join(", ")
+            ),
+        }
+    }
+
+    /// Key the action is bound to when not set in the config
+    pub fn default_key(&self) -> &'static str {
+        match self {
+            HotkeyAction::Pause => "P",
+            HotkeyAction::Reset => "F5",
+            HotkeyAction::SaveState => "F6",
+            HotkeyAction::LoadState => "F7",
+            HotkeyAction::Screenshot => "F12",
+            HotkeyAction::Turbo => "TAB",
+            HotkeyAction::ToggleOverlay => "F1",
+            HotkeyAction::Menu => "ESCAPE",
+            HotkeyAction::Mute => "M",
+            HotkeyAction::ToggleFps => "F2",
+            HotkeyAction::ToggleGrid => "F3",
+            HotkeyAction::Step => "F10",
+        }
+    }
+}
+
+/// A hotkey bound to the same physical key as a keypad key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotkeyConflict {
+    pub action: HotkeyAction,
+    pub keypad_key: u8,
+    pub key_name: &'static str,
+}
+
+/// Mapping from each hotkey action to the name of a physical key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotkeyMap {
+    /// Canonical key names, indexed in the same order as [HotkeyAction::ALL],
+    /// None if the action is unbound
+    keys: [Option<&'static str>; HotkeyAction::ALL.len()],
+}
+
+impl Default for HotkeyMap {
+    fn default() -> Self {
+        Self {
+            keys: HotkeyAction::ALL.map(|action| Some(action.default_key())),
+        }
+    }
+}
+
+impl HotkeyMap {
+    /// Resolve the hotkeys from the `[hotkeys]` config table (action -> key name)
+    ///
+    /// Actions missing from the table keep their default key, and an action can be
+    /// unbound by setting it to an empty string or "none".
+    pub fn resolve(bindings: &BTreeMap<String, String>) -> Result<Self> {
+        let mut hotkeys = Self::default();
+        for (action, key_name) in bindings {
+            let action = HotkeyAction::from_name(action).context("Parsing hotkeys")?;
+            let key_name = key_name.trim();
+            hotkeys.keys[action as usize] =
+                if key_name.is_empty() || key_name.eq_ignore_ascii_case("none") {
+                    None
+                } else {
+                    Some(
+                        normalize_key_name(key_name)
+                            .with_context(|| format!("Parsing hotkey for {}", action.name()))?,
+                    )
+                };
+        }
+        Ok(hotkeys)
+    }
+
+    /// Get the name of the key bound to `action`, if any
+    pub fn key_name(&self, action: HotkeyAction) -> Option<&'static str> {
+        self.keys[action as usize]
+    }
+
+    /// Iterate through the bound actions and their key names
+    pub fn iter(&self) -> impl Iterator<Item = (HotkeyAction, &'static str)> + '_ {
+        HotkeyAction::ALL
+            .into_iter()
+            .filter_map(|action| self.key_name(action).map(|key| (action, key)))
+    }
+
+    /// Find any hotkeys bound to the same key as a keypad key in `keymap`
+    pub fn conflicts(&self, keymap: &Keymap) -> Vec<HotkeyConflict> {
+        let mut conflicts = Vec::new();
+        for (action, key_name) in self.iter() {
+            for (keypad_key, keypad_name) in keymap.iter().enumerate() {
+                if keypad_name == key_name {
+                    conflicts.push(HotkeyConflict {
+                        action,
+                        keypad_key: keypad_key as u8,
+                        key_name,
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod test_hotkeys {
+    use super::*;
+    use crate::keymap::KeymapPreset;
+
+    #[test]
+    /// Test that the defaults are used when there is no hotkeys table
+    fn test_defaults() -> Result<()> {
+        let hotkeys = HotkeyMap::resolve(&BTreeMap::new())?;
+        assert_eq!(hotkeys, HotkeyMap::default());
+        for action in HotkeyAction::ALL {
+            assert_eq!(hotkeys.key_name(action), Some(action.default_key()));
+            assert_eq!(HotkeyAction::from_name(action.name())?, action);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test parsing the hotkeys table
+    fn test_parse() -> Result<()> {
+        let mut bindings = BTreeMap::new();
+        bindings.insert("pause".to_string(), "space".to_string());
+        bindings.insert("Screenshot".to_string(), "f9".to_string());
+        bindings.insert("turbo".to_string(), "none".to_string());
+        bindings.insert("reset".to_string(), "".to_string());
+        let hotkeys = HotkeyMap::resolve(&bindings)?;
+
+        assert_eq!(hotkeys.key_name(HotkeyAction::Pause), Some("SPACE"));
+        assert_eq!(hotkeys.key_name(HotkeyAction::Screenshot), Some("F9"));
+        assert_eq!(hotkeys.key_name(HotkeyAction::Turbo), None);
+        assert_eq!(hotkeys.key_name(HotkeyAction::Reset), None);
+        // Unset actions keep their defaults
+        assert_eq!(hotkeys.key_name(HotkeyAction::SaveState), Some("F6"));
+        assert_eq!(hotkeys.iter().count(), HotkeyAction::ALL.len() - 2);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that unknown actions and keys are rejected
+    fn test_parse_invalid() {
+        let mut bad_action = BTreeMap::new();
+        bad_action.insert("rewind".to_string(), "R".to_string());
+        assert!(HotkeyMap::resolve(&bad_action).is_err());
+
+        let mut bad_key = BTreeMap::new();
+        bad_key.insert("pause".to_string(), "NOT_A_KEY".to_string());
+        assert!(HotkeyMap::resolve(&bad_key).is_err());
+    }
+
+    #[test]
+    /// Test detecting hotkeys bound to keypad keys
+    fn test_conflicts() -> Result<()> {
+        // The default hotkeys don't overlap the qwerty keypad
+        let qwerty = KeymapPreset::Qwerty.keymap();
+        assert!(HotkeyMap::default().conflicts(&qwerty).is_empty());
+
+        // But P is a keypad key in colemak
+        let colemak = KeymapPreset::Colemak.keymap();
+        assert_eq!(
+            HotkeyMap::default().conflicts(&colemak),
+            vec![HotkeyConflict {
+                action: HotkeyAction::Pause,
+                keypad_key: 0xD,
+                key_name: "P",
+            }]
+        );
+
+        // Binding a hotkey onto a qwerty keypad key
+        let mut bindings = BTreeMap::new();
+        bindings.insert("reset".to_string(), "x".to_string());
+        let conflicts = HotkeyMap::resolve(&bindings)?.conflicts(&qwerty);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].action, HotkeyAction::Reset);
+        assert_eq!(conflicts[0].keypad_key, 0x0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::keymap::KEYPAD_SIZE;
+
+/// Keypad keys to hold down frame by frame, for replaying the same input to
+/// headless runs
+///
+/// Written as text, one line per change: the frame it happens on, then the
+/// keys (as hex digits) held from that frame on, or `-` for none. Blank lines
+/// and lines starting with `#` are skipped.
+///
+/// ```text
+/// # Hold 5 for half a second, then 5 and 6 together, then let go
+/// 30 5
+/// 60 5 6
+/// 90 -
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputScript {
+    /// The frame each change happens on (in order), and the keys held from
+    /// then on (bit N for key N)
+    changes: Vec<(u64, u16)>,
+}
+
+impl InputScript {
+    /// Parse a script from its text
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut changes: Vec<(u64, u16)> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let change = parse_line(line).with_context(|| format!("Line {}", index + 1))?;
+            if let Some((last_frame, _)) = changes.last()
+                && change.0 <= *last_frame
+            {
+                bail!(
+                    "Line {}: frame {} isn't after frame {last_frame}",
+                    index + 1,
+                    change.0
+                );
+            }
+            changes.push(change);
+        }
+        Ok(Self { changes })
+    }
+
+    /// Read a script from the file at `path`
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Reading input script {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Parsing input script {}", path.display()))
+    }
+
+    /// The keys held during `frame` (bit N for key N)
+    pub fn keys_at(&self, frame: u64) -> u16 {
+        self.changes
+            .iter()
+            .take_while(|(start, _)| *start <= frame)
+            .last()
+            .map_or(0, |(_, keys)| *keys)
+    }
+}
+
+/// Parse a line of a script, a frame and the keys held from then on
+fn parse_line(line: &str) -> Result<(u64, u16)> {
+    let mut words = line.split_whitespace();
+    let frame = words.next().unwrap_or_default();
+    let frame: u64 = frame
+        .parse()
+        .with_context(|| format!("Invalid frame {frame:?}"))?;
+    let mut keys = 0;
+    let mut none = false;
+    for word in words {
+        if word == "-" {
+            none = true;
+            continue;
+        }
+        match u8::from_str_radix(word, 16) {
+            Ok(key) if word.len() == 1 && usize::from(key) < KEYPAD_SIZE => keys |= 1 << key,
+            _ => bail!("Invalid key {word:?}, expected a hex digit (0 to F)"),
+        }
+    }
+    if none == (keys != 0) {
+        bail!("Expected the keys held, or - for none");
+    }
+    Ok((frame, keys))
+}
+
+#[cfg(test)]
+mod test_input_script {
+    use super::*;
+
+    #[test]
+    /// Test the keys held change on the frames given
+    fn test_keys_at() -> Result<()> {
+        let script = InputScript::parse("# Comment\n\n30 5\n60 5 a\n90 -\n")?;
+        assert_eq!(script.keys_at(0), 0);
+        assert_eq!(script.keys_at(29), 0);
+        assert_eq!(script.keys_at(30), 1 << 5);
+        assert_eq!(script.keys_at(75), 1 << 5 | 1 << 0xA);
+        assert_eq!(script.keys_at(90), 0);
+        assert_eq!(script.keys_at(1000), 0);
+        assert_eq!(InputScript::parse("")?.keys_at(0), 0);
+        Ok(())
+    }
+
+    #[test]
+    /// Test mistakes are reported with their line
+    fn test_parse_errors() {
+        for (text, expected) in [
+            ("ten 5", "Invalid frame \"ten\""),
+            ("10 G", "Invalid key \"G\""),
+            ("10 10", "Invalid key \"10\""),
+            ("10", "Expected the keys held"),
+            ("10 - 5", "Expected the keys held"),
+            ("10 5\n10 6", "frame 10 isn't after frame 10"),
+        ] {
+            let message = format!("{:#}", InputScript::parse(text).unwrap_err());
+            assert!(message.contains(expected), "{text:?}: {message}");
+            assert!(message.starts_with("Line "), "{text:?}: {message}");
+        }
+    }
+}
@@ -0,0 +1,281 @@
+/// A CHIP-8 instruction, including the SUPER-CHIP and XO-CHIP extensions
+///
+/// Opcodes are decoded into these once, for the emulator to execute and for the
+/// disassembler, statistics, and ROM analysis to describe, so they all agree on
+/// what each opcode is. `x` and `y` are register numbers, and `n`, `nn`, and
+/// `nnn` the values of the last 4, 8, and 12 bits of the opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0NNN, call a machine code routine (of the original interpreter's CPU)
+    MachineCode { nnn: u16 },
+    /// 00E0, clear the display
+    Clear,
+    /// 00EE, return from a subroutine
+    Return,
+    /// 00CN, scroll the display down N rows (SUPER-CHIP)
+    ScrollDown { n: u8 },
+    /// 00DN, scroll the display up N rows (XO-CHIP)
+    ScrollUp { n: u8 },
+    /// 00FB, scroll the display right 4 columns (SUPER-CHIP)
+    ScrollRight,
+    /// 00FC, scroll the display left 4 columns (SUPER-CHIP)
+    ScrollLeft,
+    /// 00FD, exit the interpreter (SUPER-CHIP)
+    Exit,
+    /// 00FE, switch to low resolution (SUPER-CHIP)
+    LowResolution,
+    /// 00FF, switch to high resolution (SUPER-CHIP)
+    HighResolution,
+    /// 1NNN, jump to NNN
+    Jump { nnn: u16 },
+    /// 2NNN, call the subroutine at NNN
+    Call { nnn: u16 },
+    /// 3XNN, skip the next instruction if VX == NN
+    SkipIfEqual { x: u8, nn: u8 },
+    /// 4XNN, skip the next instruction if VX != NN
+    SkipIfNotEqual { x: u8, nn: u8 },
+    /// 5XY0, skip the next instruction if VX == VY
+    SkipIfRegistersEqual { x: u8, y: u8 },
+    /// 5XY2, store VX to VY in memory from I (XO-CHIP)
+    StoreRange { x: u8, y: u8 },
+    /// 5XY3, load VX to VY from memory from I (XO-CHIP)
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN, set VX to NN
+    Set { x: u8, nn: u8 },
+    /// 7XNN, add NN to VX, without a carry
+    Add { x: u8, nn: u8 },
+    /// 8XY0, set VX to VY
+    SetRegister { x: u8, y: u8 },
+    /// 8XY1, VX |= VY
+    Or { x: u8, y: u8 },
+    /// 8XY2, VX &= VY
+    And { x: u8, y: u8 },
+    /// 8XY3, VX ^= VY
+    Xor { x: u8, y: u8 },
+    /// 8XY4, VX += VY, with the carry in VF
+    AddRegister { x: u8, y: u8 },
+    /// 8XY5, VX -= VY, with VF set if there was no borrow
+    Subtract { x: u8, y: u8 },
+    /// 8XY6, shift right (VY or VX, by the shift_use_vy quirk) into VX
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7, VX = VY - VX, with VF set if there was no borrow
+    SubtractReversed { x: u8, y: u8 },
+    /// 8XYE, shift left (VY or VX, by the shift_use_vy quirk) into VX
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0, skip the next instruction if VX != VY
+    SkipIfRegistersNotEqual { x: u8, y: u8 },
+    /// ANNN, set I to NNN
+    SetIndex { nnn: u16 },
+    /// BNNN, jump to NNN plus V0 (or VX, by the jump_offset_use_v0 quirk)
+    JumpWithOffset { x: u8, nnn: u16 },
+    /// CXNN, set VX to a random number ANDed with NN
+    Random { x: u8, nn: u8 },
+    /// DXYN, draw the N row sprite at I at (VX, VY), or a 16x16 sprite if N is
+    /// 0 (SUPER-CHIP)
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E, skip the next instruction if key VX is down
+    SkipIfKey { x: u8 },
+    /// EXA1, skip the next instruction if key VX is up
+    SkipIfNotKey { x: u8 },
+    /// F000 NNNN, set I to the 16 bit address after it (XO-CHIP)
+    LoadLongIndex,
+    /// FN01, select the display planes in the mask N (XO-CHIP)
+    SelectPlanes { mask: u8 },
+    /// F002, load the audio pattern at I (XO-CHIP)
+    LoadAudioPattern,
+    /// FX07, set VX to the delay timer
+    GetDelayTimer { x: u8 },
+    /// FX0A, wait for a key to be pressed and released, and put it in VX
+    WaitForKey { x: u8 },
+    /// FX15, set the delay timer to VX
+    SetDelayTimer { x: u8 },
+    /// FX18, set the sound timer to VX
+    SetSoundTimer { x: u8 },
+    /// FX1E, add VX to I
+    AddToIndex { x: u8 },
+    /// FX29, point I at the font character for VX
+    FontCharacter { x: u8 },
+    /// FX30, point I at the big font character for VX (SUPER-CHIP)
+    BigFontCharacter { x: u8 },
+    /// FX33, store VX in memory at I as 3 decimal digits
+    BinaryCodedDecimal { x: u8 },
+    /// FX3A, set the pitch of the audio pattern to VX (XO-CHIP)
+    SetPitch { x: u8 },
+    /// FX55, store V0 to VX in memory from I
+    StoreRegisters { x: u8 },
+    /// FX65, load V0 to VX from memory from I
+    LoadRegisters { x: u8 },
+    /// FX75, save V0 to VX in the flag registers (SUPER-CHIP)
+    SaveFlags { x: u8 },
+    /// FX85, load V0 to VX from the flag registers (SUPER-CHIP)
+    LoadFlags { x: u8 },
+}
+
+impl Instruction {
+    /// Decode `opcode`, None if it isn't a known instruction
+    pub fn decode(opcode: u16) -> Option<Self> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+        let instruction = match (opcode >> 12, x, y, n) {
+            (0x0, 0x0, 0xE, 0x0) => Self::Clear,
+            (0x0, 0x0, 0xE, 0xE) => Self::Return,
+            (0x0, 0x0, 0xC, n) => Self::ScrollDown { n },
+            (0x0, 0x0, 0xD, n) => Self::ScrollUp { n },
+            (0x0, 0x0, 0xF, 0xB) => Self::ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => Self::ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Self::Exit,
+            (0x0, 0x0, 0xF, 0xE) => Self::LowResolution,
+            (0x0, 0x0, 0xF, 0xF) => Self::HighResolution,
+            (0x0, ..) => Self::MachineCode { nnn },
+            (0x1, ..) => Self::Jump { nnn },
+            (0x2, ..) => Self::Call { nnn },
+            (0x3, ..) => Self::SkipIfEqual { x, nn },
+            (0x4, ..) => Self::SkipIfNotEqual { x, nn },
+            (0x5, _, _, 0x0) => Self::SkipIfRegistersEqual { x, y },
+            (0x5, _, _, 0x2) => Self::StoreRange { x, y },
+            (0x5, _, _, 0x3) => Self::LoadRange { x, y },
+            (0x6, ..) => Self::Set { x, nn },
+            (0x7, ..) => Self::Add { x, nn },
+            (0x8, _, _, 0x0) => Self::SetRegister { x, y },
+            (0x8, _, _, 0x1) => Self::Or { x, y },
+            (0x8, _, _, 0x2) => Self::And { x, y },
+            (0x8, _, _, 0x3) => Self::Xor { x, y },
+            (0x8, _, _, 0x4) => Self::AddRegister { x, y },
+            (0x8, _, _, 0x5) => Self::Subtract { x, y },
+            (0x8, _, _, 0x6) => Self::ShiftRight { x, y },
+            (0x8, _, _, 0x7) => Self::SubtractReversed { x, y },
+            (0x8, _, _, 0xE) => Self::ShiftLeft { x, y },
+            (0x9, _, _, 0x0) => Self::SkipIfRegistersNotEqual { x, y },
+            (0xA, ..) => Self::SetIndex { nnn },
+            (0xB, ..) => Self::JumpWithOffset { x, nnn },
+            (0xC, ..) => Self::Random { x, nn },
+            (0xD, ..) => Self::Draw { x, y, n },
+            (0xE, _, 0x9, 0xE) => Self::SkipIfKey { x },
+            (0xE, _, 0xA, 0x1) => Self::SkipIfNotKey { x },
+            (0xF, 0x0, 0x0, 0x0) => Self::LoadLongIndex,
+            (0xF, _, 0x0, 0x1) => Self::SelectPlanes { mask: x },
+            (0xF, 0x0, 0x0, 0x2) => Self::LoadAudioPattern,
+            (0xF, _, 0x0, 0x7) => Self::GetDelayTimer { x },
+            (0xF, _, 0x0, 0xA) => Self::WaitForKey { x },
+            (0xF, _, 0x1, 0x5) => Self::SetDelayTimer { x },
+            (0xF, _, 0x1, 0x8) => Self::SetSoundTimer { x },
+            (0xF, _, 0x1, 0xE) => Self::AddToIndex { x },
+            (0xF, _, 0x2, 0x9) => Self::FontCharacter { x },
+            (0xF, _, 0x3, 0x0) => Self::BigFontCharacter { x },
+            (0xF, _, 0x3, 0x3) => Self::BinaryCodedDecimal { x },
+            (0xF, _, 0x3, 0xA) => Self::SetPitch { x },
+            (0xF, _, 0x5, 0x5) => Self::StoreRegisters { x },
+            (0xF, _, 0x6, 0x5) => Self::LoadRegisters { x },
+            (0xF, _, 0x7, 0x5) => Self::SaveFlags { x },
+            (0xF, _, 0x8, 0x5) => Self::LoadFlags { x },
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    /// The opcode of the instruction, which decodes back to it
+    pub fn encode(self) -> u16 {
+        let x_bits = |x: u8| u16::from(x) << 8;
+        let xy_bits = |x: u8, y: u8| x_bits(x) | (u16::from(y) << 4);
+        match self {
+            Self::MachineCode { nnn } => nnn,
+            Self::Clear => 0x00E0,
+            Self::Return => 0x00EE,
+            Self::ScrollDown { n } => 0x00C0 | u16::from(n),
+            Self::ScrollUp { n } => 0x00D0 | u16::from(n),
+            Self::ScrollRight => 0x00FB,
+            Self::ScrollLeft => 0x00FC,
+            Self::Exit => 0x00FD,
+            Self::LowResolution => 0x00FE,
+            Self::HighResolution => 0x00FF,
+            Self::Jump { nnn } => 0x1000 | nnn,
+            Self::Call { nnn } => 0x2000 | nnn,
+            Self::SkipIfEqual { x, nn } => 0x3000 | x_bits(x) | u16::from(nn),
+            Self::SkipIfNotEqual { x, nn } => 0x4000 | x_bits(x) | u16::from(nn),
+            Self::SkipIfRegistersEqual { x, y } => 0x5000 | xy_bits(x, y),
+            Self::StoreRange { x, y } => 0x5002 | xy_bits(x, y),
+            Self::LoadRange { x, y } => 0x5003 | xy_bits(x, y),
+            Self::Set { x, nn } => 0x6000 | x_bits(x) | u16::from(nn),
+            Self::Add { x, nn } => 0x7000 | x_bits(x) | u16::from(nn),
+            Self::SetRegister { x, y } => 0x8000 | xy_bits(x, y),
+            Self::Or { x, y } => 0x8001 | xy_bits(x, y),
+            Self::And { x, y } => 0x8002 | xy_bits(x, y),
+            Self::Xor { x, y } => 0x8003 | xy_bits(x, y),
+            Self::AddRegister { x, y } => 0x8004 | xy_bits(x, y),
+            Self::Subtract { x, y } => 0x8005 | xy_bits(x, y),
+            Self::ShiftRight { x, y } => 0x8006 | xy_bits(x, y),
+            Self::SubtractReversed { x, y } => 0x8007 | xy_bits(x, y),
+            Self::ShiftLeft { x, y } => 0x800E | xy_bits(x, y),
+            Self::SkipIfRegistersNotEqual { x, y } => 0x9000 | xy_bits(x, y),
+            Self::SetIndex { nnn } => 0xA000 | nnn,
+            Self::JumpWithOffset { nnn, .. } => 0xB000 | nnn,
+            Self::Random { x, nn } => 0xC000 | x_bits(x) | u16::from(nn),
+            Self::Draw { x, y, n } => 0xD000 | xy_bits(x, y) | u16::from(n),
+            Self::SkipIfKey { x } => 0xE09E | x_bits(x),
+            Self::SkipIfNotKey { x } => 0xE0A1 | x_bits(x),
+            Self::LoadLongIndex => 0xF000,
+            Self::SelectPlanes { mask } => 0xF001 | x_bits(mask),
+            Self::LoadAudioPattern => 0xF002,
+            Self::GetDelayTimer { x } => 0xF007 | x_bits(x),
+            Self::WaitForKey { x } => 0xF00A | x_bits(x),
+            Self::SetDelayTimer { x } => 0xF015 | x_bits(x),
+            Self::SetSoundTimer { x } => 0xF018 | x_bits(x),
+            Self::AddToIndex { x } => 0xF01E | x_bits(x),
+            Self::FontCharacter { x } => 0xF029 | x_bits(x),
+            Self::BigFontCharacter { x } => 0xF030 | x_bits(x),
+            Self::BinaryCodedDecimal { x } => 0xF033 | x_bits(x),
+            Self::SetPitch { x } => 0xF03A | x_bits(x),
+            Self::StoreRegisters { x } => 0xF055 | x_bits(x),
+            Self::LoadRegisters { x } => 0xF065 | x_bits(x),
+            Self::SaveFlags { x } => 0xF075 | x_bits(x),
+            Self::LoadFlags { x } => 0xF085 | x_bits(x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_instruction {
+    use super::*;
+
+    #[test]
+    /// Test decoding the operands of instructions
+    fn test_decode() {
+        for (opcode, instruction) in [
+            (0x00E0, Instruction::Clear),
+            (0x00C4, Instruction::ScrollDown { n: 4 }),
+            (0x00FB, Instruction::ScrollRight),
+            (0x0123, Instruction::MachineCode { nnn: 0x123 }),
+            (0x1228, Instruction::Jump { nnn: 0x228 }),
+            (0x3A0F, Instruction::SkipIfEqual { x: 0xA, nn: 0x0F }),
+            (0x5123, Instruction::LoadRange { x: 1, y: 2 }),
+            (0x8AB6, Instruction::ShiftRight { x: 0xA, y: 0xB }),
+            (0xB300, Instruction::JumpWithOffset { x: 3, nnn: 0x300 }),
+            (0xD01F, Instruction::Draw { x: 0, y: 1, n: 0xF }),
+            (0xE29E, Instruction::SkipIfKey { x: 2 }),
+            (0xF301, Instruction::SelectPlanes { mask: 3 }),
+            (0xF565, Instruction::LoadRegisters { x: 5 }),
+        ] {
+            assert_eq!(
+                Instruction::decode(opcode),
+                Some(instruction),
+                "{opcode:04X}"
+            );
+        }
+        for opcode in [0x5121, 0x8AB8, 0x9121, 0xE2A2, 0xF100, 0xF102, 0xF0FF] {
+            assert_eq!(Instruction::decode(opcode), None, "{opcode:04X}");
+        }
+    }
+
+    #[test]
+    /// Test every known opcode encodes back to itself
+    fn test_encode() {
+        for opcode in 0..=u16::MAX {
+            if let Some(instruction) = Instruction::decode(opcode) {
+                assert_eq!(instruction.encode(), opcode, "{instruction:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,662 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use anyhow::{Context, Result, bail};
+
+// Layout of the CHIP-8 hex keypad, the physical 4x4 block of keys
+// used by each preset is mapped onto these positions
+//
+// 1  2  3  C
+// 4  5  6  D
+// 7  8  9  E
+// A  0  B  F
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Number of keys on the CHIP-8 keypad
+pub const KEYPAD_SIZE: usize = 16;
+
+/// The keypad laid out as in [KEYPAD_LAYOUT], with whether each key is down
+///
+/// `pressed` is a bitmask of the keys which are down, with bit N set when
+/// keypad key N is (see [crate::frontend::Frontend::current_keys]).
+pub fn keypad_grid(pressed: u16) -> [[(u8, bool); 4]; 4] {
+    KEYPAD_LAYOUT.map(|row| row.map(|key| (key, pressed & (1 << key) != 0)))
+}
+
+/// A key of the keypad overlay, positioned as in [KEYPAD_LAYOUT]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeypadLabel {
+    /// Row of the keypad (0 at the top)
+    pub row: usize,
+    /// Column of the keypad (0 on the left)
+    pub col: usize,
+    /// The CHIP-8 keypad key
+    pub keypad_key: u8,
+    /// Name of the physical key bound to it
+    pub key_name: &'static str,
+    /// Whether the key is down
+    pub pressed: bool,
+}
+
+/// Names of the physical keys that a keypad key can be bound to
+///
+/// Names are matched case-insensitively, see [normalize_key_name].
+pub const KEY_NAMES: &[&str] = &[
+    "0",
+    "1",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6",
+    "7",
+    "8",
+    "9",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "APOSTROPHE",
+    "COMMA",
+    "MINUS",
+    "PERIOD",
+    "SLASH",
+    "SEMICOLON",
+    "EQUAL",
+    "LEFT_BRACKET",
+    "BACKSLASH",
+    "RIGHT_BRACKET",
+    "GRAVE",
+    "SPACE",
+    "ESCAPE",
+    "ENTER",
+    "TAB",
+    "BACKSPACE",
+    "INSERT",
+    "DELETE",
+    "RIGHT",
+    "LEFT",
+    "DOWN",
+    "UP",
+    "PAGE_UP",
+    "PAGE_DOWN",
+    "HOME",
+    "END",
+    "F1",
+    "F2",
+    "F3",
+    "F4",
+    "F5",
+    "F6",
+    "F7",
+    "F8",
+    "F9",
+    "F10",
+    "F11",
+    "F12",
+    "KP_0",
+    "KP_1",
+    "KP_2",
+    "KP_3",
+    "KP_4",
+    "KP_5",
+    "KP_6",
+    "KP_7",
+    "KP_8",
+    "KP_9",
+];
+
+// Single character aliases for the punctuation keys
+const KEY_ALIASES: [(&str, &str); 11] = [
+    ("'", "APOSTROPHE"),
+    (",", "COMMA"),
+    ("-", "MINUS"),
+    (".", "PERIOD"),
+    ("/", "SLASH"),
+    (";", "SEMICOLON"),
+    ("=", "EQUAL"),
+    ("[", "LEFT_BRACKET"),
+    ("\\", "BACKSLASH"),
+    ("]", "RIGHT_BRACKET"),
+    ("`", "GRAVE"),
+];
+
+/// Convert a user supplied key name into its canonical form
+///
+/// Key names are case-insensitive, and the punctuation keys can also
+/// be given as the character they produce (e.g. `;` for `SEMICOLON`).
+pub fn normalize_key_name(name: &str) -> Result<&'static str> {
+    let name = name.trim();
+    if let Some((_, canonical)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Ok(canonical);
+    }
+    let upper = name.to_uppercase();
+    match KEY_NAMES.iter().find(|&&key| key == upper) {
+        Some(key) => Ok(key),
+        None => bail!("Unknown key name {name:?}"),
+    }
+}
+
+/// Parse the name of a CHIP-8 keypad key (a single hex digit, optionally 0x prefixed)
+pub fn parse_keypad_key(key: &str) -> Result<u8> {
+    let key = key.trim();
+    let digits = key
+        .strip_prefix("0x")
+        .or_else(|| key.strip_prefix("0X"))
+        .unwrap_or(key);
+    match u8::from_str_radix(digits, 16) {
+        Ok(value) if (value as usize) < KEYPAD_SIZE => Ok(value),
+        _ => bail!("Invalid keypad key {key:?}, expected a hex digit between 0 and F"),
+    }
+}
+
+/// Built in keymaps for common keyboard layouts
+///
+/// Each preset binds the same physical 4x4 block of keys
+/// (the 1234/QWER/ASDF/ZXCV block on a QWERTY keyboard) to the keypad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeymapPreset {
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Colemak,
+    Dvorak,
+}
+
+impl KeymapPreset {
+    /// All of the available presets
+    pub const ALL: [KeymapPreset; 5] = [
+        KeymapPreset::Qwerty,
+        KeymapPreset::Azerty,
+        KeymapPreset::Qwertz,
+        KeymapPreset::Colemak,
+        KeymapPreset::Dvorak,
+    ];
+
+    /// Name of the preset as used in the config and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeymapPreset::Qwerty => "qwerty",
+            KeymapPreset::Azerty => "azerty",
+            KeymapPreset::Qwertz => "qwertz",
+            KeymapPreset::Colemak => "colemak",
+            KeymapPreset::Dvorak => "dvorak",
+        }
+    }
+
+    /// Find the preset with the given name
+    pub fn from_name(name: &str) -> Result<Self> {
+        let lower = name.trim().to_lowercase();
+        match Self::ALL.iter().find(|preset| preset.name() == lower) {
+            Some(preset) => Ok(*preset),
+            None => bail!(
+                "Unknown keymap preset {name:?}, available presets are: {}",
+                Self::ALL.map(|preset| preset.name()).join(", ")
+            ),
+        }
+    }
+
+    /// Physical keys of the preset, laid out to match [KEYPAD_LAYOUT]
+    fn rows(&self) -> [[&'static str; 4]; 4] {
+        match self {
+            KeymapPreset::Qwerty => [
+                ["1", "2", "3", "4"],
+                ["Q", "W", "E", "R"],
+                ["A", "S", "D", "F"],
+                ["Z", "X", "C", "V"],
+            ],
+            KeymapPreset::Azerty => [
+                ["1", "2", "3", "4"],
+                ["A", "Z", "E", "R"],
+                ["Q", "S", "D", "F"],
+                ["W", "X", "C", "V"],
+            ],
+            KeymapPreset::Qwertz => [
+                ["1", "2", "3", "4"],
+                ["Q", "W", "E", "R"],
+                ["A", "S", "D", "F"],
+                ["Y", "X", "C", "V"],
+            ],
+            KeymapPreset::Colemak => [
+                ["1", "2", "3", "4"],
+                ["Q", "W", "F", "P"],
+                ["A", "R", "S", "T"],
+                ["Z", "X", "C", "V"],
+            ],
+            KeymapPreset::Dvorak => [
+                ["1", "2", "3", "4"],
+                ["APOSTROPHE", "COMMA", "PERIOD", "P"],
+                ["A", "O", "E", "U"],
+                ["SEMICOLON", "Q", "J", "K"],
+            ],
+        }
+    }
+
+    /// Create the keymap for this preset
+    pub fn keymap(&self) -> Keymap {
+        let mut keys = [""; KEYPAD_SIZE];
+        for (layout_row, preset_row) in KEYPAD_LAYOUT.iter().zip(self.rows()) {
+            for (&key, name) in layout_row.iter().zip(preset_row) {
+                keys[key as usize] = name;
+            }
+        }
+        Keymap::new(keys)
+    }
+}
+
+/// Mapping from each CHIP-8 keypad key to the name of a physical key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    /// Canonical key names, indexed by the keypad key
+    keys: [&'static str; KEYPAD_SIZE],
+    /// The keypad key bound to each physical key (the reverse of `keys`)
+    keypad_keys: BTreeMap<&'static str, u8>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        KeymapPreset::Qwerty.keymap()
+    }
+}
+
+impl Keymap {
+    /// Create a keymap binding each keypad key to the key in `keys`
+    fn new(keys: [&'static str; KEYPAD_SIZE]) -> Self {
+        let mut keypad_keys = BTreeMap::new();
+        for (keypad_key, name) in keys.iter().enumerate() {
+            keypad_keys.entry(*name).or_insert(keypad_key as u8);
+        }
+        Self { keys, keypad_keys }
+    }
+
+    /// Resolve a keymap from a preset name and a set of individual overrides
+    ///
+    /// The preset is resolved first, then each override (keypad key -> key name)
+    /// replaces the binding for that keypad key.
+    pub fn resolve(preset: &str, overrides: &BTreeMap<String, String>) -> Result<Self> {
+        let mut keys = KeymapPreset::from_name(preset)?.keymap().keys;
+        for (keypad_key, key_name) in overrides {
+            let keypad_key = parse_keypad_key(keypad_key).context("Parsing keymap override")?;
+            keys[keypad_key as usize] = normalize_key_name(key_name)
+                .with_context(|| format!("Parsing keymap override for key {keypad_key:X}"))?;
+        }
+        Ok(Keymap::new(keys))
+    }
+
+    /// Bind the keypad key `keypad_key` (a hex digit, see [parse_keypad_key]) to
+    /// the physical key `key_name`, e.g. to try another mapping while running
+    ///
+    /// Both names are checked before anything changes.
+    pub fn remap(&mut self, keypad_key: &str, key_name: &str) -> Result<()> {
+        let keypad_key = parse_keypad_key(keypad_key)?;
+        let name = normalize_key_name(key_name)
+            .with_context(|| format!("Remapping keypad key {keypad_key:X}"))?;
+        let mut keys = self.keys;
+        keys[keypad_key as usize] = name;
+        *self = Keymap::new(keys);
+        Ok(())
+    }
+
+    /// Get the name of the physical key bound to the keypad key `key`
+    pub fn key_name(&self, key: u8) -> Option<&'static str> {
+        self.keys.get(key as usize).copied()
+    }
+
+    /// Get the keypad key bound to the physical key `name` (any name or alias
+    /// accepted by [normalize_key_name]), for translating key events
+    ///
+    /// If several keypad keys are bound to the same physical key, the lowest of
+    /// them is returned.
+    pub fn keypad_key(&self, name: &str) -> Option<u8> {
+        let name = normalize_key_name(name).ok()?;
+        self.keypad_keys.get(name).copied()
+    }
+
+    /// The labels of the keypad overlay, a row at a time, with the physical key
+    /// bound to each keypad key and whether it is down (bit N of `pressed` set
+    /// for keypad key N)
+    pub fn keypad_labels(&self, pressed: u16) -> Vec<KeypadLabel> {
+        let mut labels = Vec::with_capacity(KEYPAD_SIZE);
+        for (row, keys) in keypad_grid(pressed).iter().enumerate() {
+            for (col, (keypad_key, pressed)) in keys.iter().enumerate() {
+                labels.push(KeypadLabel {
+                    row,
+                    col,
+                    keypad_key: *keypad_key,
+                    key_name: self.keys[*keypad_key as usize],
+                    pressed: *pressed,
+                });
+            }
+        }
+        labels
+    }
+
+    /// Iterate through the physical key names, in keypad key order
+    pub fn iter(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.keys.iter().copied()
+    }
+
+    /// Render the keymap as an ASCII diagram of the keypad
+    pub fn diagram(&self) -> String {
+        let width = self.keys.iter().map(|key| key.len()).max().unwrap_or(1);
+        let border = format!("+{}\n", format!("{}+", "-".repeat(width + 2)).repeat(4));
+        let mut diagram = border.clone();
+        for layout_row in KEYPAD_LAYOUT {
+            diagram.push('|');
+            for key in layout_row {
+                let _ = write!(diagram, " {:^width$} |", self.keys[key as usize]);
+            }
+            diagram.push('\n');
+            diagram.push_str(&border);
+        }
+        diagram
+    }
+
+    /// Render the keymap as the keypad, with each keypad key next to the
+    /// physical key bound to it (e.g. `C: 4`)
+    pub fn key_grid(&self) -> String {
+        let width = self.keys.iter().map(|key| key.len()).max().unwrap_or(1);
+        let border = format!("+{}\n", format!("{}+", "-".repeat(width + 5)).repeat(4));
+        let mut grid = border.clone();
+        for layout_row in KEYPAD_LAYOUT {
+            grid.push('|');
+            for key in layout_row {
+                let _ = write!(grid, " {key:X}: {:<width$} |", self.keys[key as usize]);
+            }
+            grid.push('\n');
+            grid.push_str(&border);
+        }
+        grid
+    }
+}
+
+#[cfg(test)]
+mod test_keymap {
+    use super::*;
+
+    #[test]
+    /// Test laying out the pressed keys on the keypad grid
+    fn test_keypad_grid() {
+        let grid = keypad_grid(0);
+        assert_eq!(
+            grid[0],
+            [(0x1, false), (0x2, false), (0x3, false), (0xC, false)]
+        );
+        assert!(grid.iter().flatten().all(|(_, pressed)| !pressed));
+
+        // Keys 0, 5, and F
+        let grid = keypad_grid(1 << 0x0 | 1 << 0x5 | 1 << 0xF);
+        assert_eq!(grid[3][1], (0x0, true));
+        assert_eq!(grid[1][1], (0x5, true));
+        assert_eq!(grid[3][3], (0xF, true));
+        assert_eq!(
+            grid.iter()
+                .flatten()
+                .filter(|(_, pressed)| *pressed)
+                .count(),
+            3
+        );
+
+        let grid = keypad_grid(u16::MAX);
+        assert!(grid.iter().flatten().all(|(_, pressed)| *pressed));
+        for (row, layout_row) in grid.iter().zip(KEYPAD_LAYOUT) {
+            for ((key, _), layout_key) in row.iter().zip(layout_row) {
+                assert_eq!(*key, layout_key);
+            }
+        }
+    }
+
+    #[test]
+    /// Test that the qwerty preset matches the classic keymap
+    fn test_qwerty_preset() {
+        let keymap = KeymapPreset::Qwerty.keymap();
+        let expected = [
+            "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V",
+        ];
+        for (key, name) in expected.iter().enumerate() {
+            assert_eq!(keymap.key_name(key as u8), Some(*name));
+        }
+    }
+
+    #[test]
+    /// Test the physical layouts of the other presets
+    fn test_other_presets() {
+        let azerty = KeymapPreset::Azerty.keymap();
+        assert_eq!(azerty.key_name(0x4), Some("A"));
+        assert_eq!(azerty.key_name(0x5), Some("Z"));
+        assert_eq!(azerty.key_name(0x7), Some("Q"));
+        assert_eq!(azerty.key_name(0xA), Some("W"));
+
+        let qwertz = KeymapPreset::Qwertz.keymap();
+        assert_eq!(qwertz.key_name(0xA), Some("Y"));
+        assert_eq!(qwertz.key_name(0x4), Some("Q"));
+
+        let colemak = KeymapPreset::Colemak.keymap();
+        assert_eq!(colemak.key_name(0x6), Some("F"));
+        assert_eq!(colemak.key_name(0xD), Some("P"));
+        assert_eq!(colemak.key_name(0x8), Some("R"));
+        assert_eq!(colemak.key_name(0xE), Some("T"));
+
+        let dvorak = KeymapPreset::Dvorak.keymap();
+        assert_eq!(dvorak.key_name(0x4), Some("APOSTROPHE"));
+        assert_eq!(dvorak.key_name(0xA), Some("SEMICOLON"));
+        assert_eq!(dvorak.key_name(0xF), Some("K"));
+    }
+
+    #[test]
+    /// Test that every preset binds 16 distinct, valid keys
+    fn test_presets_valid() -> Result<()> {
+        for preset in KeymapPreset::ALL {
+            let keymap = preset.keymap();
+            let mut names: Vec<&str> = keymap.iter().collect();
+            for name in &names {
+                normalize_key_name(name)?;
+            }
+            names.sort();
+            names.dedup();
+            assert_eq!(
+                names.len(),
+                KEYPAD_SIZE,
+                "{} has duplicate keys",
+                preset.name()
+            );
+            assert_eq!(KeymapPreset::from_name(preset.name())?, preset);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test that the keypad key for each physical key matches the keymap
+    fn test_keypad_key() -> Result<()> {
+        for preset in KeymapPreset::ALL {
+            let keymap = preset.keymap();
+            for key in 0..KEYPAD_SIZE as u8 {
+                let name = keymap.key_name(key).unwrap();
+                assert_eq!(keymap.keypad_key(name), Some(key), "{}", preset.name());
+            }
+        }
+
+        let keymap = Keymap::default();
+        // Names are normalized, and unbound or unknown keys have no keypad key
+        assert_eq!(keymap.keypad_key("q"), Some(0x4));
+        assert_eq!(keymap.keypad_key("ENTER"), None);
+        assert_eq!(keymap.keypad_key("NOT_A_KEY"), None);
+
+        // Overrides move the physical key, and the lowest keypad key wins when
+        // two share one
+        let overrides = BTreeMap::from([
+            ("1".to_string(), "SPACE".to_string()),
+            ("2".to_string(), "SPACE".to_string()),
+        ]);
+        let keymap = Keymap::resolve("qwerty", &overrides)?;
+        assert_eq!(keymap.keypad_key("SPACE"), Some(0x1));
+        assert_eq!(keymap.keypad_key("2"), None);
+        Ok(())
+    }
+
+    #[test]
+    /// Test the overlay labels for a custom keymap, and highlighting the keys
+    /// which are down
+    fn test_keypad_labels() -> Result<()> {
+        let overrides = BTreeMap::from([
+            ("B".to_string(), "SPACE".to_string()),
+            ("1".to_string(), "KP_7".to_string()),
+        ]);
+        let keymap = Keymap::resolve("azerty", &overrides)?;
+        let labels = keymap.keypad_labels(1 << 0xB | 1 << 0x5);
+        let names: Vec<Vec<&str>> = labels
+            .chunks(4)
+            .map(|row| row.iter().map(|label| label.key_name).collect())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ["KP_7", "2", "3", "4"],
+                ["A", "Z", "E", "R"],
+                ["Q", "S", "D", "F"],
+                ["W", "X", "SPACE", "V"],
+            ]
+        );
+        for label in &labels {
+            assert_eq!(label.keypad_key, KEYPAD_LAYOUT[label.row][label.col]);
+            assert_eq!(label.pressed, [0xB, 0x5].contains(&label.keypad_key));
+        }
+        assert_eq!(
+            labels[14],
+            KeypadLabel {
+                row: 3,
+                col: 2,
+                keypad_key: 0xB,
+                key_name: "SPACE",
+                pressed: true,
+            }
+        );
+        assert!(keymap.keypad_labels(0).iter().all(|label| !label.pressed));
+        Ok(())
+    }
+
+    #[test]
+    /// Test that overrides are applied on top of the preset
+    fn test_resolve_overrides() -> Result<()> {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("0".to_string(), "space".to_string());
+        overrides.insert("0xF".to_string(), ";".to_string());
+        overrides.insert("a".to_string(), "kp_0".to_string());
+        let keymap = Keymap::resolve("AZERTY", &overrides)?;
+
+        assert_eq!(keymap.key_name(0x0), Some("SPACE"));
+        assert_eq!(keymap.key_name(0xF), Some("SEMICOLON"));
+        assert_eq!(keymap.key_name(0xA), Some("KP_0"));
+        // Keys without an override come from the preset
+        assert_eq!(keymap.key_name(0x4), Some("A"));
+        assert_eq!(keymap.key_name(0x1), Some("1"));
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test that invalid presets and overrides are rejected
+    fn test_resolve_invalid() {
+        assert!(Keymap::resolve("bepo", &BTreeMap::new()).is_err());
+
+        let mut bad_keypad_key = BTreeMap::new();
+        bad_keypad_key.insert("10".to_string(), "A".to_string());
+        assert!(Keymap::resolve("qwerty", &bad_keypad_key).is_err());
+
+        let mut bad_key_name = BTreeMap::new();
+        bad_key_name.insert("1".to_string(), "NOT_A_KEY".to_string());
+        assert!(Keymap::resolve("qwerty", &bad_key_name).is_err());
+    }
+
+    #[test]
+    /// Test remapping single keys, and that bad names leave the keymap as it was
+    fn test_remap() -> Result<()> {
+        let mut keymap = Keymap::default();
+        keymap.remap("0x5", "up")?;
+        assert_eq!(keymap.key_name(0x5), Some("UP"));
+        assert_eq!(keymap.keypad_key("UP"), Some(0x5));
+        assert_eq!(keymap.keypad_key("W"), None);
+
+        let before = keymap.clone();
+        assert!(keymap.remap("10", "A").is_err());
+        assert!(keymap.remap("5", "NOT_A_KEY").is_err());
+        assert_eq!(keymap, before);
+        Ok(())
+    }
+
+    #[test]
+    /// Test the ASCII diagram of a keymap
+    fn test_diagram() {
+        let diagram = KeymapPreset::Qwerty.keymap().diagram();
+        let expected = "\
++---+---+---+---+
+| 1 | 2 | 3 | 4 |
++---+---+---+---+
+| Q | W | E | R |
++---+---+---+---+
+| A | S | D | F |
++---+---+---+---+
+| Z | X | C | V |
++---+---+---+---+
+";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    /// Test the keypad keys are shown next to the keys bound to them
+    fn test_key_grid() -> Result<()> {
+        let grid = KeymapPreset::Qwerty.keymap().key_grid();
+        let expected = "\
++------+------+------+------+
+| 1: 1 | 2: 2 | 3: 3 | C: 4 |
++------+------+------+------+
+| 4: Q | 5: W | 6: E | D: R |
++------+------+------+------+
+| 7: A | 8: S | 9: D | E: F |
++------+------+------+------+
+| A: Z | 0: X | B: C | F: V |
++------+------+------+------+
+";
+        assert_eq!(grid, expected);
+
+        // Columns are as wide as the longest key name
+        let overrides = BTreeMap::from([("0".to_string(), "SPACE".to_string())]);
+        let grid = Keymap::resolve("qwerty", &overrides)?.key_grid();
+        assert!(grid.starts_with("+----------+"), "{grid}");
+        assert!(
+            grid.contains("| A: Z     | 0: SPACE | B: C     |"),
+            "{grid}"
+        );
+        Ok(())
+    }
+}
@@ -1,6 +1,67 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+// The cdylib still needs std's panic handler and allocator on targets which
+// have it, the bare-metal ones (see no_std_check/) are where std is checked out
+#[cfg(all(not(feature = "std"), not(target_os = "none")))]
+extern crate std;
+
+// Without std, the emulator core, for no_std targets with an allocator (the
+// parts needing threads, the clock, or files are behind std in each module)
+pub mod cheats;
+pub mod color;
+pub mod compat;
 pub mod config;
+pub mod constants;
+pub mod disasm;
 pub mod display;
 pub mod emulator;
+pub mod error_screen;
 pub mod frontend;
-#[cfg(test)]
-mod noop_frontend;
+pub mod hotkeys;
+pub mod instruction;
+pub mod keymap;
+pub mod memory_layout;
+pub mod menu;
+pub mod pause;
+pub mod poke;
+pub mod rom;
+pub mod sound;
+pub mod stats;
+pub mod timing;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "std")] {
+        pub mod cli;
+        pub mod compare;
+        pub mod debug_grid;
+        #[cfg(feature = "discord")]
+        pub mod discord;
+        pub mod download;
+        pub mod frame_blend;
+        pub mod golden;
+        pub mod headless_frontend;
+        pub mod host_frontend;
+        pub mod input_script;
+        pub mod library;
+        pub mod logging;
+        #[cfg(test)]
+        mod noop_frontend;
+        pub mod octo;
+        pub mod perf;
+        pub mod playlist;
+        pub mod provenance;
+        #[cfg(feature = "pyo3")]
+        pub mod python;
+        pub mod random_program;
+        pub mod remote_control;
+        pub mod rom_database;
+        pub mod save_state;
+        pub mod splash;
+        pub mod split_view;
+        pub mod tee_frontend;
+        #[cfg(not(target_arch = "wasm32"))]
+        pub mod terminal_frontend;
+        pub mod watch;
+    }
+}
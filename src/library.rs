@@ -0,0 +1,411 @@
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use etcetera::BaseStrategy;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::rom::{MAX_CHIP8_ROM_SIZE, sha1_hex};
+
+/// File extensions (lower case) of the ROMs picked up when scanning the library
+pub const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+/// Version of the recently played list's JSON schema
+pub const RECENTS_VERSION: u32 = 1;
+
+/// Most games kept in the recently played list
+pub const MAX_RECENTS: usize = 20;
+
+/// Name of the recently played list, in the [data_dir]
+const RECENTS_FILE: &str = "recent.json";
+
+/// The directory emul8rs keeps its data (e.g. the recently played list) in
+#[cfg(not(target_arch = "wasm32"))]
+pub fn data_dir() -> Result<PathBuf> {
+    let strategy = etcetera::choose_base_strategy().context("Finding the data directory")?;
+    Ok(strategy.data_dir().join("emul8rs"))
+}
+
+/// The directory emul8rs keeps its data in, which WebAssembly doesn't have
+#[cfg(target_arch = "wasm32")]
+pub fn data_dir() -> Result<PathBuf> {
+    anyhow::bail!("There is no data directory in WebAssembly")
+}
+
+/// Path of the recently played list
+pub fn recents_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join(RECENTS_FILE))
+}
+
+/// A ROM found by [scan]
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    /// Size of the ROM in bytes
+    pub size: u64,
+    /// SHA-1 hash of the ROM, worked out the first time it's needed
+    hash: OnceCell<String>,
+}
+
+impl LibraryEntry {
+    pub fn new(path: PathBuf, size: u64) -> Self {
+        Self {
+            path,
+            size,
+            hash: OnceCell::new(),
+        }
+    }
+
+    /// The SHA-1 hash of the ROM, as hex, reading it the first time it's asked for
+    pub fn hash(&self) -> Result<&str> {
+        if let Some(hash) = self.hash.get() {
+            return Ok(hash);
+        }
+        let rom =
+            fs::read(&self.path).with_context(|| format!("Reading ROM {}", self.path.display()))?;
+        Ok(self.hash.get_or_init(|| sha1_hex(&rom)))
+    }
+}
+
+/// Whether a file at `path`, `size` bytes long, looks like a ROM
+///
+/// It needs one of the [ROM_EXTENSIONS], and to fit in memory without being empty.
+pub fn is_rom(path: &Path, size: u64) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    extension.is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.as_str()))
+        && size > 0
+        && size <= MAX_CHIP8_ROM_SIZE as u64
+}
+
+/// Find the ROMs in `dir` and the directories inside it, sorted by path
+///
+/// Files and directories that can't be read are logged and skipped, and
+/// directories reached again through a symlink are only scanned once.
+pub fn scan(dir: &Path) -> Result<Vec<LibraryEntry>> {
+    let mut entries = Vec::new();
+    let mut visited = HashSet::new();
+    // Only the top directory has to be readable
+    fs::read_dir(dir).with_context(|| format!("Reading ROM directory {}", dir.display()))?;
+    scan_dir(dir, &mut visited, &mut entries);
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Add the ROMs in `dir` to `entries`, skipping it if it's already in `visited`
+fn scan_dir(dir: &Path, visited: &mut HashSet<PathBuf>, entries: &mut Vec<LibraryEntry>) {
+    // Symlinks are followed, so a directory can be reached more than once
+    match fs::canonicalize(dir) {
+        Ok(canonical) => {
+            if !visited.insert(canonical) {
+                debug!("Skipping {dir:?}, it has already been scanned");
+                return;
+            }
+        }
+        Err(err) => {
+            warn!("Skipping {dir:?}: {err}");
+            return;
+        }
+    }
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) => {
+            warn!("Skipping {dir:?}: {err}");
+            return;
+        }
+    };
+    for entry in read_dir {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => {
+                warn!("Skipping an entry of {dir:?}: {err}");
+                continue;
+            }
+        };
+        // Follows symlinks, a broken one is skipped
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                debug!("Skipping {path:?}: {err}");
+                continue;
+            }
+        };
+        if metadata.is_dir() {
+            scan_dir(&path, visited, entries);
+        } else if metadata.is_file() && is_rom(&path, metadata.len()) {
+            entries.push(LibraryEntry::new(path, metadata.len()));
+        }
+    }
+}
+
+/// A game in the recently played list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentGame {
+    pub path: PathBuf,
+    /// SHA-1 hash of the ROM when it was last played
+    pub hash: String,
+    /// When it was last played, in seconds since the Unix epoch
+    pub last_played: u64,
+    /// Number of times it has been played
+    pub play_count: u32,
+}
+
+/// The recently played list, most recent first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recents {
+    /// Version of the schema, see [RECENTS_VERSION]
+    pub version: u32,
+    pub games: Vec<RecentGame>,
+}
+
+impl Default for Recents {
+    fn default() -> Self {
+        Self {
+            version: RECENTS_VERSION,
+            games: Vec::new(),
+        }
+    }
+}
+
+impl Recents {
+    /// Read the list from `path`, an empty list if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Reading recently played list {}", path.display()));
+            }
+        };
+        serde_json::from_str(&json)
+            .with_context(|| format!("Parsing recently played list {}", path.display()))
+    }
+
+    /// Write the list to `path`, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Creating directory {}", dir.display()))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .context("Converting recently played list to JSON")?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("Writing recently played list {}", path.display()))
+    }
+
+    /// Record playing the ROM at `path` (with hash `hash`) at `now`, moving it to
+    /// the top of the list
+    ///
+    /// Only the [MAX_RECENTS] most recent games are kept.
+    pub fn record(&mut self, path: &Path, hash: &str, now: SystemTime) {
+        let play_count = match self.games.iter().position(|game| game.path == path) {
+            Some(index) => self.games.remove(index).play_count,
+            None => 0,
+        };
+        self.games.insert(
+            0,
+            RecentGame {
+                path: path.to_path_buf(),
+                hash: hash.to_string(),
+                last_played: unix_seconds(now),
+                play_count: play_count.saturating_add(1),
+            },
+        );
+        self.games.truncate(MAX_RECENTS);
+    }
+
+    /// The `count` most recently played games
+    pub fn recent(&self, count: usize) -> &[RecentGame] {
+        &self.games[..count.min(self.games.len())]
+    }
+}
+
+/// Seconds from the Unix epoch to `time`, 0 for times before it
+pub fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// How long ago something at `then` happened, as of `now` (both in seconds since
+/// the Unix epoch), e.g. "5 minutes ago"
+pub fn format_age(then: u64, now: u64) -> String {
+    let seconds = now.saturating_sub(then);
+    let (count, unit) = match seconds {
+        0..60 => return "just now".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+#[cfg(test)]
+mod test_library {
+    use super::*;
+    use std::time::Duration;
+
+    /// Write a file of `size` bytes at `path`, creating its directory
+    fn write_file(path: &Path, size: usize) -> Result<()> {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, vec![0x12; size])?;
+        Ok(())
+    }
+
+    #[test]
+    /// Test which files count as ROMs
+    fn test_is_rom() {
+        assert!(is_rom(Path::new("pong.ch8"), 246));
+        assert!(is_rom(Path::new("games/PONG.CH8"), 246));
+        assert!(is_rom(Path::new("octo.xo8"), 3584));
+        assert!(!is_rom(Path::new("pong.txt"), 246));
+        assert!(!is_rom(Path::new("ch8"), 246));
+        assert!(!is_rom(Path::new("empty.ch8"), 0));
+        assert!(!is_rom(Path::new("huge.ch8"), 3585));
+    }
+
+    #[test]
+    /// Test scanning a directory tree for ROMs
+    fn test_scan() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        write_file(&root.join("pong.ch8"), 246)?;
+        write_file(&root.join("readme.txt"), 100)?;
+        write_file(&root.join("empty.ch8"), 0)?;
+        write_file(&root.join("schip/Car.SC8"), 300)?;
+        write_file(&root.join("schip/deeper/tetris.c8"), 494)?;
+        write_file(&root.join("too_big.ch8"), 5000)?;
+
+        let entries = scan(root)?;
+        let paths: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.path.strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("pong.ch8"),
+                PathBuf::from("schip/Car.SC8"),
+                PathBuf::from("schip/deeper/tetris.c8"),
+            ]
+        );
+        assert_eq!(entries[0].size, 246);
+        assert_eq!(entries[0].hash()?, sha1_hex(&[0x12; 246]));
+
+        // A missing directory is an error
+        assert!(scan(&root.join("missing")).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    /// Test that symlink loops and broken symlinks don't stop a scan
+    fn test_scan_symlinks() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        write_file(&root.join("games/pong.ch8"), 246)?;
+        // A link back up the tree, and one to nowhere
+        symlink(root, root.join("games/loop"))?;
+        symlink(root.join("missing.ch8"), root.join("broken.ch8"))?;
+
+        let entries = scan(root)?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, root.join("games/pong.ch8"));
+        Ok(())
+    }
+
+    #[test]
+    /// Test that the hash is only read once, even if the file changes
+    fn test_lazy_hash() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("pong.ch8");
+        let entry = LibraryEntry::new(path.clone(), 2);
+        // Not read until needed
+        assert!(entry.hash().is_err());
+        fs::write(&path, [0x00, 0xE0])?;
+        let hash = entry.hash()?.to_string();
+        assert_eq!(hash, sha1_hex(&[0x00, 0xE0]));
+        fs::write(&path, [0x12, 0x00])?;
+        assert_eq!(entry.hash()?, hash);
+        Ok(())
+    }
+
+    #[test]
+    /// Test recording games in the recently played list
+    fn test_record() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut recents = Recents::default();
+        recents.record(Path::new("pong.ch8"), "aa", start);
+        recents.record(
+            Path::new("tetris.ch8"),
+            "bb",
+            start + Duration::from_secs(60),
+        );
+        recents.record(
+            Path::new("pong.ch8"),
+            "cc",
+            start + Duration::from_secs(120),
+        );
+
+        let games = recents.recent(5);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].path, Path::new("pong.ch8"));
+        assert_eq!(games[0].hash, "cc");
+        assert_eq!(games[0].play_count, 2);
+        assert_eq!(games[0].last_played, 1_000_120);
+        assert_eq!(games[1].path, Path::new("tetris.ch8"));
+        assert_eq!(games[1].play_count, 1);
+        assert_eq!(recents.recent(1).len(), 1);
+
+        // Only the most recent games are kept
+        for index in 0..MAX_RECENTS {
+            recents.record(Path::new(&format!("{index}.ch8")), "dd", start);
+        }
+        assert_eq!(recents.games.len(), MAX_RECENTS);
+        assert_eq!(recents.games[0].path, Path::new("19.ch8"));
+        assert!(recents.games.iter().all(|game| game.hash == "dd"));
+    }
+
+    #[test]
+    /// Test saving the recently played list, and loading it back
+    fn test_save_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("data").join(RECENTS_FILE);
+        // A missing list is empty
+        assert_eq!(Recents::load(&path)?, Recents::default());
+
+        let mut recents = Recents::default();
+        recents.record(Path::new("/roms/pong.ch8"), "aa", SystemTime::now());
+        recents.save(&path)?;
+        assert_eq!(Recents::load(&path)?, recents);
+
+        // A corrupt list names the file
+        fs::write(&path, "not json")?;
+        let message = format!("{:#}", Recents::load(&path).unwrap_err());
+        assert!(message.contains(RECENTS_FILE), "{message}");
+        Ok(())
+    }
+
+    #[test]
+    /// Test describing how long ago a game was played
+    fn test_format_age() {
+        assert_eq!(format_age(100, 130), "just now");
+        assert_eq!(format_age(100, 160), "1 minute ago");
+        assert_eq!(format_age(0, 3599), "59 minutes ago");
+        assert_eq!(format_age(0, 7200), "2 hours ago");
+        assert_eq!(format_age(0, 86400), "1 day ago");
+        assert_eq!(format_age(0, 10 * 86400 + 5), "10 days ago");
+        // Clocks going backwards
+        assert_eq!(format_age(200, 100), "just now");
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+use colog::basic_builder;
+use env_logger::{Builder, Logger, Target, WriteStyle};
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// A log filter directive, giving the level for a module and the modules inside
+/// it, or for everything
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogDirective {
+    /// Module path (e.g. `emul8rs::emulator`), None for everything
+    pub module: Option<String>,
+    pub level: LevelFilter,
+}
+
+/// Parse a comma separated list of log filter directives, in the env_logger
+/// syntax (e.g. `emul8rs::emulator=warn,emul8rs::raylib_frontend=trace`)
+///
+/// Each directive is `module=level`, a bare `level` for everything, or a bare
+/// `module` to log everything from it.
+pub fn parse_log_filter(filter: &str) -> Result<Vec<LogDirective>> {
+    let mut directives = Vec::new();
+    for directive in filter.split(',').map(str::trim) {
+        if directive.is_empty() {
+            continue;
+        }
+        let (module, level) = match directive.split_once('=') {
+            Some((module, level)) => (Some(module.trim()), level.trim()),
+            None => match LevelFilter::from_str(directive) {
+                Ok(level) => {
+                    directives.push(LogDirective {
+                        module: None,
+                        level,
+                    });
+                    continue;
+                }
+                Err(_) => (Some(directive), "trace"),
+            },
+        };
+        if module.is_some_and(|module| {
+            module.is_empty()
+                || !module.split("::").all(|part| {
+                    !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+                })
+        }) {
+            bail!("Invalid module {module:?} in log filter directive {directive:?}");
+        }
+        let Ok(level) = LevelFilter::from_str(level) else {
+            bail!(
+                "Invalid level {level:?} in log filter directive {directive:?}, expected one \
+                of off, error, warn, info, debug, or trace"
+            );
+        };
+        directives.push(LogDirective {
+            module: module.map(str::to_string),
+            level,
+        });
+    }
+    Ok(directives)
+}
+
+/// A builder logging messages up to `level`, adjusted by `directives`
+fn builder(level: LevelFilter, directives: &[LogDirective]) -> Builder {
+    let mut builder = basic_builder();
+    builder.default_format().filter_level(level);
+    for directive in directives {
+        builder.filter(directive.module.as_deref(), directive.level);
+    }
+    builder
+}
+
+/// Open the log file at `path`, appending to it if `append` is true, otherwise
+/// replacing any previous contents
+pub fn open_log_file(path: &Path, append: bool) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.create(true);
+    if append {
+        options.append(true);
+    } else {
+        options.write(true).truncate(true);
+    }
+    options
+        .open(path)
+        .with_context(|| format!("Opening log file {}", path.display()))
+}
+
+/// A logger writing messages up to `level` (adjusted by `directives`) to stderr
+pub fn stderr_logger(level: LevelFilter, directives: &[LogDirective]) -> Logger {
+    builder(level, directives).target(Target::Stderr).build()
+}
+
+/// A logger writing messages up to `level` (adjusted by `directives`) to `file`,
+/// without any colors
+pub fn file_logger(level: LevelFilter, directives: &[LogDirective], file: File) -> Logger {
+    builder(level, directives)
+        .target(Target::Pipe(Box::new(file)))
+        .write_style(WriteStyle::Never)
+        .build()
+}
+
+/// Sends log messages to several loggers, each with its own level
+/// (e.g. everything to a file, and just errors to stderr)
+pub struct MultiLogger {
+    loggers: Vec<Logger>,
+}
+
+impl MultiLogger {
+    pub fn new(loggers: Vec<Logger>) -> Self {
+        Self { loggers }
+    }
+
+    /// The most verbose level any of the loggers accepts
+    pub fn max_level(&self) -> LevelFilter {
+        self.loggers
+            .iter()
+            .map(Logger::filter)
+            .max()
+            .unwrap_or(LevelFilter::Off)
+    }
+
+    /// Install as the global logger
+    pub fn init(self) -> Result<()> {
+        let max_level = self.max_level();
+        log::set_boxed_logger(Box::new(self)).context("Setting up logging")?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for MultiLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.loggers.iter().any(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        for logger in &self.loggers {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        for logger in &self.loggers {
+            logger.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_logging {
+    use super::*;
+    use log::Level;
+
+    /// Log `message` at `level` to `logger`
+    fn log_message(logger: &impl Log, level: Level, message: &str) {
+        logger.log(
+            &Record::builder()
+                .args(format_args!("{message}"))
+                .level(level)
+                .target("emul8rs")
+                .build(),
+        );
+        logger.flush();
+    }
+
+    #[test]
+    /// Test logging a line to a file, and reading it back
+    fn test_file_logger() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("emul8rs.log");
+
+        let logger = file_logger(LevelFilter::Info, &[], open_log_file(&path, false)?);
+        log_message(&logger, Level::Info, "loaded the ROM");
+        log_message(&logger, Level::Debug, "too verbose");
+        let contents = std::fs::read_to_string(&path)?;
+        assert!(contents.contains("loaded the ROM"), "{contents:?}");
+        assert!(!contents.contains("too verbose"));
+        // No color escape codes
+        assert!(!contents.contains('\x1b'));
+
+        // Appending keeps the earlier lines, truncating doesn't
+        let logger = file_logger(LevelFilter::Info, &[], open_log_file(&path, true)?);
+        log_message(&logger, Level::Warn, "second run");
+        let contents = std::fs::read_to_string(&path)?;
+        assert!(contents.contains("loaded the ROM") && contents.contains("second run"));
+        let logger = file_logger(LevelFilter::Info, &[], open_log_file(&path, false)?);
+        log_message(&logger, Level::Warn, "third run");
+        let contents = std::fs::read_to_string(&path)?;
+        assert!(!contents.contains("second run") && contents.contains("third run"));
+        Ok(())
+    }
+
+    #[test]
+    /// Test that each logger only gets the messages at its level
+    fn test_multi_logger() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let everything = dir.path().join("everything.log");
+        let errors = dir.path().join("errors.log");
+        let logger = MultiLogger::new(vec![
+            file_logger(LevelFilter::Debug, &[], open_log_file(&everything, false)?),
+            file_logger(LevelFilter::Error, &[], open_log_file(&errors, false)?),
+        ]);
+        assert_eq!(logger.max_level(), LevelFilter::Debug);
+
+        log_message(&logger, Level::Debug, "a debug message");
+        log_message(&logger, Level::Error, "an error message");
+        let contents = std::fs::read_to_string(&everything)?;
+        assert!(contents.contains("a debug message") && contents.contains("an error message"));
+        let contents = std::fs::read_to_string(&errors)?;
+        assert!(!contents.contains("a debug message") && contents.contains("an error message"));
+        Ok(())
+    }
+
+    #[test]
+    /// Test that failing to open the log file names the file
+    fn test_open_log_file_error() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("missing").join("emul8rs.log");
+        let message = open_log_file(&path, false).unwrap_err().to_string();
+        assert!(message.contains("emul8rs.log"));
+        Ok(())
+    }
+
+    #[test]
+    /// Test parsing log filter directives
+    fn test_parse_log_filter() -> Result<()> {
+        let directive = |module: Option<&str>, level| LogDirective {
+            module: module.map(str::to_string),
+            level,
+        };
+        assert_eq!(
+            parse_log_filter("emul8rs::emulator=warn, emul8rs::raylib_frontend=TRACE")?,
+            [
+                directive(Some("emul8rs::emulator"), LevelFilter::Warn),
+                directive(Some("emul8rs::raylib_frontend"), LevelFilter::Trace),
+            ]
+        );
+        assert_eq!(
+            parse_log_filter("info,emul8rs::config")?,
+            [
+                directive(None, LevelFilter::Info),
+                directive(Some("emul8rs::config"), LevelFilter::Trace),
+            ]
+        );
+        assert_eq!(parse_log_filter("")?, []);
+        assert_eq!(
+            parse_log_filter("emul8rs=off,")?,
+            [directive(Some("emul8rs"), LevelFilter::Off)]
+        );
+
+        for invalid in [
+            "emul8rs=loud",
+            "=warn",
+            "emul8rs::=warn",
+            "emul 8rs=warn",
+            "a=b=c",
+        ] {
+            assert!(
+                parse_log_filter(invalid).is_err(),
+                "{invalid:?} should be invalid"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test the levels each module ends up with, layered over the -l level
+    fn test_log_filter_levels() -> Result<()> {
+        let directives = parse_log_filter("emul8rs::emulator=warn,emul8rs::raylib_frontend=trace")?;
+        let logger = stderr_logger(LevelFilter::Debug, &directives);
+        let enabled = |target: &str, level: Level| {
+            logger.enabled(&Metadata::builder().target(target).level(level).build())
+        };
+
+        assert!(enabled("emul8rs::emulator", Level::Warn));
+        assert!(!enabled("emul8rs::emulator", Level::Info));
+        assert!(!enabled("emul8rs::emulator", Level::Trace));
+        assert!(enabled("emul8rs::raylib_frontend", Level::Trace));
+        // Everything else keeps the -l level
+        assert!(enabled("emul8rs::config", Level::Debug));
+        assert!(!enabled("emul8rs::config", Level::Trace));
+        assert_eq!(logger.filter(), LevelFilter::Trace);
+
+        // Without directives, the -l level applies everywhere
+        let logger = stderr_logger(LevelFilter::Warn, &[]);
+        assert!(
+            !logger.enabled(
+                &Metadata::builder()
+                    .target("emul8rs::emulator")
+                    .level(Level::Info)
+                    .build()
+            )
+        );
+        assert_eq!(logger.filter(), LevelFilter::Warn);
+        Ok(())
+    }
+}
@@ -6,74 +6,65 @@ mod raylib_frontend;
 use raylib::core::audio;
 
 // Standard Library Use
-use std::path::PathBuf;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant, SystemTime};
 
 // External crate uses
-use anyhow::Result;
-use clap::Parser;
-use colog::basic_builder;
-use log::{LevelFilter, debug, info};
+use anyhow::{Context, Result, bail};
+use log::{LevelFilter, debug, error, info, warn};
 
 // Internal crate uses
-use emul8rs::config::EmulatorConfig;
-use emul8rs::emulator;
+use emul8rs::cli::{
+    Cli, Command, CompareArgs, ConfigCommand, ConfigFormat, FrontendKind, LibraryCommand, PlayArgs,
+    RunArgs, TestArgs,
+};
+use emul8rs::compare::compare;
+use emul8rs::config::{
+    EmulatorConfig, choose_config_path, editor_command, parse_config_file, write_config_template,
+};
+use emul8rs::disasm::{
+    disassemble_listing, disassemble_program, disassemble_traced, trace_program,
+};
+#[cfg(feature = "discord")]
+use emul8rs::discord::DiscordPresence;
+use emul8rs::download::{is_url, read_program};
+use emul8rs::emulator::{Emulator, RunOutcome};
+use emul8rs::frontend::Frontend;
+use emul8rs::golden::{self, Outcome};
+use emul8rs::headless_frontend::HeadlessFrontend;
+use emul8rs::hotkeys::HotkeyMap;
+use emul8rs::input_script::InputScript;
+use emul8rs::keymap::{Keymap, KeymapPreset};
+use emul8rs::library::{Recents, format_age, recents_path, scan, unix_seconds};
+use emul8rs::logging::{MultiLogger, file_logger, open_log_file, parse_log_filter, stderr_logger};
+use emul8rs::memory_layout::{LoadSpec, MemoryLayout};
+use emul8rs::octo;
+use emul8rs::playlist::{Playlist, run_attract};
+use emul8rs::provenance::{ConfigSource, ResolvedConfig};
+use emul8rs::remote_control::RemoteControl;
+use emul8rs::rom::{self, PROGRAM_START, Platform, RomInfo};
+use emul8rs::rom_database::RomDatabase;
+use emul8rs::save_state::autosave_dir;
+use emul8rs::split_view::SplitView;
+#[cfg(feature = "raylib")]
+use emul8rs::split_view::split_window_config;
+#[cfg(feature = "discord")]
+use emul8rs::tee_frontend::TeeFrontend;
+use emul8rs::terminal_frontend::TerminalFrontend;
 
-// CLI struct
-#[derive(Parser)]
-#[command(version, about, long_about = None)]
-/// A simple chip8 emulator with multiple possible frontends
-///
-/// Command line arguments override values from the config. In Chip8 each instruction
-/// is two bytes, with 4 half-byte nibbles that are meaningful,
-/// i.e.  each instruction is of the form SXYN, with S determining the instruction,
-/// X/Y being registers to get values from, and N being an immediate u8 number. VX
-/// and VY are used to refer to the values in the X and Y registers respectively.
-struct Cli {
-    /// Path to chip8 program to load
-    program: PathBuf,
-
-    /// Sets a custom configuration file
-    #[arg(short, long, value_name = "CONFIG")]
-    config: Option<PathBuf>,
-
-    /// Turn on logging
-    #[arg(short, long, action = clap::ArgAction::Count)]
-    logging: u8,
-
-    /// Foreground color (as unprefixed hexstring, e.g. FFFFFF)
-    #[arg(short, long)]
-    foreground: Option<String>,
-
-    /// background color (as unprefixed hexstring, e.g. FFFFFF)
-    #[arg(short, long)]
-    background: Option<String>,
-
-    /// Number of chip8 instructions to try and execute per second
-    #[arg(long)]
-    instructions_per_second: Option<u64>,
-
-    /// Whether to shift value in Y register and move result into
-    /// X register, or shift X inplace
-    #[arg(long)]
-    shift_use_vy: Option<bool>,
-
-    /// Whether to use value from 0 register when performing jump with
-    /// offset, or to use value from the X register instead.
-    #[arg(long)]
-    jump_offset_use_v0: Option<bool>,
-
-    /// Whether to update the Index register when storing/loading
-    /// registers into memory
-    #[arg(long)]
-    store_memory_update_index: Option<bool>,
-}
-
-fn main() -> Result<()> {
+/// Exit code when a run with limits halts before reaching them
+const EXIT_HALTED: u8 = 2;
+
+/// Exit code when the emulator crashes
+const EXIT_CRASHED: u8 = 3;
+
+fn main() -> Result<ExitCode> {
     // Get command line arguments
-    let args = Cli::parse();
+    let cli = Cli::parse_env();
 
     // Setup logging
-    let level_filter = match args.logging {
+    let level_filter = match cli.logging {
         0 => LevelFilter::Error,
         1 => LevelFilter::Warn,
         2 => LevelFilter::Info,
@@ -81,71 +72,729 @@ fn main() -> Result<()> {
         _ => LevelFilter::Trace,
     };
 
-    basic_builder()
-        .default_format()
-        .filter_level(level_filter)
-        .init();
+    // The log file is opened before anything else, so a bad path is reported
+    // straight away
+    let directives = match &cli.log_filter {
+        Some(filter) => parse_log_filter(filter).context("Parsing --log-filter")?,
+        None => Vec::new(),
+    };
+    let loggers = match &cli.log_file {
+        Some(path) => {
+            let file = open_log_file(path, cli.log_append)?;
+            let mut loggers = vec![file_logger(level_filter, &directives, file)];
+            if cli.log_errors_to_stderr {
+                loggers.push(stderr_logger(LevelFilter::Error, &[]));
+            }
+            loggers
+        }
+        None => vec![stderr_logger(level_filter, &directives)],
+    };
+    MultiLogger::new(loggers).init()?;
 
-    // Get configuration
+    // A config file next to the ROM is used in place of the global one
+    let rom = match &cli.command {
+        Command::Run(args) => args.program.as_deref(),
+        Command::Config(ConfigCommand::Show { rom, .. }) => rom.as_deref(),
+        _ => None,
+    };
+    let config_path = choose_config_path(cli.config.clone(), rom, || {
+        Ok(confy::get_configuration_file_path("emul8rs", None)?)
+    })?;
+    info!("Config file path: {config_path:?}");
+
+    let result = match cli.command {
+        // The only command with exit codes of its own
+        Command::Run(args) => return run(&config_path, args),
+        Command::Play(args) => play(&config_path, args),
+        Command::Info { program } => {
+            let rom = read_program(&program, true).context("Failed to read ROM")?;
+            let database = load_rom_database(&config_path)?;
+            let database = RomDatabase::or_embedded(database.as_ref());
+            println!("Title: {}", database.title(&rom, &program));
+            let info = RomInfo::analyse(&rom);
+            if let Some(metadata) = database.lookup(&info.sha1) {
+                if !metadata.authors.is_empty() {
+                    println!("Authors: {}", metadata.authors.join(", "));
+                }
+                if let Some(release) = &metadata.release {
+                    println!("Released: {release}");
+                }
+                if let Some(description) = &metadata.description {
+                    println!("Description: {description}");
+                }
+            }
+            println!("{info}");
+            Ok(())
+        }
+        Command::Disasm {
+            program,
+            listing,
+            trace_first,
+            trace_cycles,
+        } => {
+            let rom = read_program(&program, true).context("Failed to read ROM")?;
+            if trace_first {
+                // Traced with the settings the ROM would run with
+                let mut resolved = load_config(&config_path)?;
+                resolved.apply_rom_overrides(&program, &rom)?;
+                resolved.config.resolve_paths(&config_path);
+                let map = trace_program(&rom, resolved.config, trace_cycles)?;
+                print!("{}", disassemble_traced(&rom, &map, listing));
+            } else if listing {
+                print!("{}", disassemble_listing(&rom, PROGRAM_START));
+            } else {
+                print!("{}", disassemble_program(&rom, PROGRAM_START));
+            }
+            Ok(())
+        }
+        Command::Compare(args) => compare_profiles(&config_path, args),
+        Command::Asm { source, output } => {
+            let text = std::fs::read_to_string(&source)
+                .with_context(|| format!("Failed to read {}", source.display()))?;
+            let rom = octo::compile(&text)
+                .with_context(|| format!("Failed to compile {}", source.display()))?;
+            let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+            std::fs::write(&output, &rom)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+            println!("Wrote {} ({} bytes)", output.display(), rom.len());
+            Ok(())
+        }
+        Command::Config(ConfigCommand::Path) => {
+            println!("{}", config_path.display());
+            Ok(())
+        }
+        Command::Config(ConfigCommand::Init { path, force }) => {
+            let path = path.unwrap_or(config_path);
+            write_config_template(&path, force)?;
+            println!("Wrote {}", path.display());
+            Ok(())
+        }
+        Command::Config(ConfigCommand::Edit) => edit_config(&config_path),
+        Command::Config(ConfigCommand::Show { rom, format }) => {
+            let mut resolved = load_config(&config_path)?;
+            if let Some(rom_path) = rom {
+                let rom = rom::open(&rom_path).context("Failed to read ROM")?;
+                resolved.apply_rom_overrides(&rom_path, &rom)?;
+            }
+            resolved.config.resolve_paths(&config_path);
+            match format {
+                ConfigFormat::Toml => print!("{}", resolved.to_annotated_toml()?),
+                ConfigFormat::Json => println!("{}", resolved.to_json()?),
+            }
+            Ok(())
+        }
+        Command::Test(args) => test_roms(&config_path, args),
+        Command::Library(LibraryCommand::List { hashes, titles }) => {
+            list_library(&config_path, hashes, titles)
+        }
+        Command::Library(LibraryCommand::Recent { count, paths }) => {
+            let recents = Recents::load(&recents_path()?)?;
+            let now = unix_seconds(SystemTime::now());
+            for game in recents.recent(count) {
+                if paths {
+                    println!("{}", game.path.display());
+                } else {
+                    let times = if game.play_count == 1 {
+                        "time"
+                    } else {
+                        "times"
+                    };
+                    println!(
+                        "{}  (played {} {times}, last {})",
+                        game.path.display(),
+                        game.play_count,
+                        format_age(game.last_played, now)
+                    );
+                }
+            }
+            Ok(())
+        }
+    };
+    result.map(|()| ExitCode::SUCCESS)
+}
+
+/// List the ROMs in the configured ROM directory (the `library list` subcommand),
+/// with their hashes and titles if asked for
+///
+/// The ROMs are only read to hash them when one of those is asked for, so a
+/// large library lists quickly.
+fn list_library(config_path: &Path, hashes: bool, titles: bool) -> Result<()> {
+    let mut resolved = load_config(config_path)?;
+    resolved.config.resolve_paths(config_path);
+    let rom_directory = &resolved.config.rom_directory;
+    if rom_directory.is_empty() {
+        bail!(
+            "No rom_directory set in the configuration file {}",
+            config_path.display()
+        );
+    }
+    let database = if titles {
+        RomDatabase::from_config(&resolved.config)?
+    } else {
+        None
+    };
+    for entry in scan(Path::new(rom_directory))? {
+        if !hashes && !titles {
+            println!("{}", entry.path.display());
+            continue;
+        }
+        // Looking up the title needs the hash anyway
+        let hash = match entry.hash() {
+            Ok(hash) => hash,
+            Err(err) => {
+                warn!("{err:#}");
+                continue;
+            }
+        };
+        let title = if titles {
+            RomDatabase::or_embedded(database.as_ref())
+                .lookup(hash)
+                .map(|metadata| format!("  ({})", metadata.title))
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        if hashes {
+            // Same format as sha1sum, with the title after it
+            println!("{hash}  {}{title}", entry.path.display());
+        } else {
+            println!("{}{title}", entry.path.display());
+        }
+    }
+    Ok(())
+}
+
+/// The ROM database from the config file, None to use the embedded one
+fn load_rom_database(config_path: &Path) -> Result<Option<RomDatabase>> {
+    let mut resolved = load_config(config_path)?;
+    resolved.config.resolve_paths(config_path);
+    RomDatabase::from_config(&resolved.config)
+}
+
+/// Add the ROM at `program` to the recently played list, only logging any failure
+/// as it shouldn't stop the game
+fn record_played(program: &Path, rom: &[u8]) {
+    let result = recents_path().and_then(|path| {
+        let mut recents = Recents::load(&path)?;
+        let program = std::fs::canonicalize(program).unwrap_or_else(|_| program.to_path_buf());
+        recents.record(&program, &emul8rs::rom::sha1_hex(rom), SystemTime::now());
+        recents.save(&path)
+    });
+    if let Err(err) = result {
+        warn!("Failed to update the recently played list: {err:#}");
+    }
+}
+
+/// Open the configuration file in the user's editor (the `config edit` subcommand)
+fn edit_config(config_path: &Path) -> Result<()> {
+    if !config_path.exists() {
+        info!("Creating configuration file {config_path:?}");
+        write_config_template(config_path, false)?;
+    }
+    let visual = std::env::var("VISUAL").ok();
+    let editor = std::env::var("EDITOR").ok();
+    let command = editor_command(visual.as_deref(), editor.as_deref());
+    debug!("Editing configuration with {command:?}");
+    let status = std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .arg(config_path)
+        .status()
+        .with_context(|| format!("Running editor {:?}", command[0]))?;
+    if !status.success() {
+        bail!("Editor {:?} exited with {status}", command[0]);
+    }
+    Ok(())
+}
+
+/// Load the configuration file, remembering which settings it contains
+fn load_config(config_path: &Path) -> Result<ResolvedConfig> {
     info!("Getting configuration from file");
-    let mut emulator_config: EmulatorConfig;
-    match args.config {
-        Some(path) => {
-            emulator_config = confy::load_path(path)?;
+    // Start a missing config file from the commented template
+    if !config_path.exists() {
+        info!("Creating configuration file {config_path:?}");
+        write_config_template(config_path, false)?;
+    }
+    let contents = std::fs::read_to_string(config_path)
+        .with_context(|| format!("Reading configuration file {}", config_path.display()))?;
+    let (config, file) = parse_config_file(config_path, &contents)?;
+    Ok(ResolvedConfig::from_file(config, &file))
+}
+
+/// Run a program under two profiles in lockstep, printing where they first
+/// differ (the `compare` subcommand)
+fn compare_profiles(config_path: &Path, args: CompareArgs) -> Result<()> {
+    let rom = read_program(&args.program, true).context("Failed to read ROM")?;
+    let input = match &args.input {
+        Some(path) => InputScript::read(path)?,
+        None => InputScript::default(),
+    };
+    // Both start from the settings the ROM would run with, the profiles
+    // deciding the quirks
+    let mut resolved = load_config(config_path)?;
+    resolved.apply_rom_overrides(&args.program, &rom)?;
+    resolved.config.resolve_paths(config_path);
+    let config_with = |profile: &str| -> Result<EmulatorConfig> {
+        let platform = Platform::from_name(profile)?;
+        resolved.config.merge(&platform.quirks())
+    };
+    let comparison = compare(
+        &rom,
+        config_with(&args.a_profile)?,
+        config_with(&args.b_profile)?,
+        &input,
+        args.seed,
+        args.max_cycles,
+    )?;
+    println!("{comparison}");
+    Ok(())
+}
+
+/// Compare ROMs against their golden images, printing how each did (the `test`
+/// subcommand)
+fn test_roms(config_path: &Path, args: TestArgs) -> Result<()> {
+    let roms = golden::find_roms(&args.roms)?;
+    if roms.is_empty() {
+        bail!("There are no ROMs with golden images to test");
+    }
+    let mut failed = 0;
+    for rom_path in &roms {
+        let outcome = std::fs::read(rom_path)
+            .context("Failed to read ROM")
+            .and_then(|rom| {
+                // The settings the ROM would run with
+                let mut resolved = load_config(config_path)?;
+                resolved.apply_rom_overrides(rom_path, &rom)?;
+                resolved.config.resolve_paths(config_path);
+                golden::check(rom_path, &rom, resolved.config, args.frames, args.update)
+            });
+        match outcome {
+            Ok(Outcome::Failed { rendered }) => {
+                failed += 1;
+                println!("FAIL {}, the display was:\n{rendered}", rom_path.display());
+            }
+            Ok(outcome) => println!("{outcome} {}", rom_path.display()),
+            Err(err) => {
+                failed += 1;
+                println!("ERROR {}: {err:#}", rom_path.display());
+            }
         }
+    }
+    if failed > 0 {
+        bail!("{failed} of {} ROMs failed", roms.len());
+    }
+    if !args.update {
+        println!("Passed all {} ROMs tested", roms.len());
+    }
+    Ok(())
+}
+
+/// Run a chip8 program (the `run` subcommand)
+#[cfg_attr(not(feature = "raylib"), allow(unused_variables))]
+fn run(config_path: &Path, args: RunArgs) -> Result<ExitCode> {
+    if args.list_keymaps {
+        for preset in KeymapPreset::ALL {
+            println!("{}:\n{}", preset.name(), preset.keymap().diagram());
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.list_keys {
+        let mut resolved = load_config(config_path)?;
+        if let Some(program) = &args.program {
+            let rom = read_program(program, !args.no_cache).context("Failed to read input file")?;
+            resolved.apply_rom_overrides(program, &rom)?;
+        }
+        resolved.apply(&args.overrides()?, ConfigSource::Cli)?;
+        let keymap = Keymap::resolve(&resolved.config.keymap_preset, &resolved.config.keymap)
+            .context("Resolving keymap from configuration")?;
+        print!("{}", keymap.key_grid());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Get configuration
+    let mut resolved = load_config(config_path)?;
+
+    // Apply any per ROM settings
+    let regions = args
+        .load
+        .iter()
+        .map(LoadSpec::read)
+        .collect::<Result<Vec<_>>>()?;
+    let (program, rom) = match &args.program {
+        Some(program) => {
+            if args.watch && program.to_str().is_some_and(is_url) {
+                bail!("--watch needs a ROM file, it can't watch a URL");
+            }
+            let rom = read_program(program, !args.no_cache).context("Failed to read input file")?;
+            (program.clone(), rom)
+        }
+        // Only files loaded with --load, named after the one lowest in memory
         None => {
-            emulator_config = confy::load("emul8rs", None)?;
+            if args.watch {
+                bail!("--watch needs a program to watch, not just files to --load");
+            }
+            let (spec, region) = args
+                .load
+                .iter()
+                .zip(&regions)
+                .min_by_key(|(spec, _)| spec.address)
+                .context("No program provided")?;
+            (spec.path.clone(), region.bytes.clone())
         }
     };
-    info!(
-        "Default config file path: {:?}",
-        confy::get_configuration_file_path("emul8rs", None)?
-    );
+    let layout = MemoryLayout {
+        pokes: args.poke.clone(),
+        ..MemoryLayout::new(
+            args.program
+                .as_ref()
+                .map(|program| (program.display().to_string(), rom.clone())),
+            regions,
+            args.start_address,
+        )?
+    };
+    if args.auto_profile {
+        let database = load_rom_database(config_path)?;
+        resolved.apply_auto_profile(RomDatabase::or_embedded(database.as_ref()), &rom)?;
+    }
+    debug!("Checking for per ROM configuration");
+    resolved.apply_rom_overrides(&program, &rom)?;
+    // Paths in the config file are relative to it
+    resolved.config.resolve_paths(config_path);
 
-    // Update config values if needed
+    // Update config values if needed, command line arguments override everything else
     debug!("Updating config values with command line arguments");
-    if let Some(foreground) = args.foreground.as_deref() {
-        emulator_config.foreground = foreground.to_string();
-    }
-    if let Some(background) = args.background.as_deref() {
-        emulator_config.background = background.to_string();
+    resolved.apply(&args.overrides()?, ConfigSource::Cli)?;
+    if !args.quiet_compat
+        && let Some(warning) = resolved.quirk_warning(&rom)
+    {
+        warn!("{warning}");
     }
-    if let Some(ips) = args.instructions_per_second {
-        emulator_config.instructions_per_second = ips;
+    if args.dump_config {
+        print!("{}", resolved.to_annotated_toml()?);
+        return Ok(ExitCode::SUCCESS);
     }
-    if let Some(use_vy) = args.shift_use_vy {
-        emulator_config.shift_use_vy = use_vy;
+    let mut emulator_config = resolved.config;
+    let (keymap, hotkeys) = prepare_config(&mut emulator_config)?;
+
+    info!("Setting up frontend");
+    match args.frontend {
+        FrontendKind::None => {
+            info!("Running headless");
+            run_with(
+                Box::new(HeadlessFrontend::new()),
+                emulator_config,
+                &program,
+                &layout,
+                &args,
+            )
+        }
+        FrontendKind::Terminal => {
+            info!("Drawing in the terminal");
+            record_played(&program, &rom);
+            run_with(
+                Box::new(TerminalFrontend::new(&emulator_config, &keymap, &hotkeys)?),
+                emulator_config,
+                &program,
+                &layout,
+                &args,
+            )
+        }
+        FrontendKind::Raylib => {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "raylib")]{
+                    info!("Setting up raylib");
+                    // Create the audio device the front end will use
+                    let raylib_audio = if emulator_config.audio_enabled {
+                        info!("Intializing the audio device");
+                        Some(audio::RaylibAudio::init_audio_device()?)
+                    } else {
+                        info!("Audio disabled, skipping audio device");
+                        None
+                    };
+                    // Create the actual raylib frontend
+                    debug!("Initializing the raylib frontend");
+                    record_played(&program, &rom);
+                    // The split view needs room for two displays
+                    let window_config = match args.compare_profile {
+                        Some(_) => split_window_config(&emulator_config),
+                        None => emulator_config.clone(),
+                    };
+                    let frontend = raylib_frontend::RaylibFrontend::new(
+                        &window_config,
+                        &keymap,
+                        &hotkeys,
+                        raylib_audio.as_ref(),
+                    )?;
+                    run_with(
+                        Box::new(frontend),
+                        emulator_config,
+                        &program,
+                        &layout,
+                        &args,
+                    )
+                } else {
+                    warn!("Raylib frontend not available, unable to run {program:?}, exiting");
+                    println!("No Available Frontends! (try --frontend none)");
+                    Ok(ExitCode::SUCCESS)
+                }
+            }
+        }
     }
-    if let Some(use_v0) = args.jump_offset_use_v0 {
-        emulator_config.jump_offset_use_v0 = use_v0;
+}
+
+/// Check the final configuration, and resolve its keymap and hotkeys, so any
+/// problem is reported before a frontend is created
+fn prepare_config(emulator_config: &mut EmulatorConfig) -> Result<(Keymap, HotkeyMap)> {
+    // Check the final configuration, reporting every problem at once
+    debug!("Validating configuration");
+    if let Err(errors) = emulator_config.validate() {
+        let errors = errors
+            .iter()
+            .map(|err| format!("  {err}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        bail!("Invalid configuration:\n{errors}");
     }
-    if let Some(update_index) = args.store_memory_update_index {
-        emulator_config.store_memory_update_index = update_index;
+    // Same foreground and background would leave a blank window
+    emulator_config.fix_invisible_colors();
+
+    debug!("Resolving keymap");
+    let keymap = Keymap::resolve(&emulator_config.keymap_preset, &emulator_config.keymap)
+        .context("Resolving keymap from configuration")?;
+    debug!("Using keymap:\n{}", keymap.diagram());
+    debug!("Resolving hotkeys");
+    let hotkeys = HotkeyMap::resolve(&emulator_config.hotkeys)
+        .context("Resolving hotkeys from configuration")?;
+    for conflict in hotkeys.conflicts(&keymap) {
+        warn!(
+            "Hotkey for {} is bound to {}, which is also keypad key {:X}",
+            conflict.action.name(),
+            conflict.key_name,
+            conflict.keypad_key
+        );
     }
+    Ok((keymap, hotkeys))
+}
+
+/// Show the ROMs of a playlist in attract mode (the `play` subcommand)
+fn play(config_path: &Path, args: PlayArgs) -> Result<()> {
+    let playlist = Playlist::load(&args.playlist)?;
+    let each = args.each()?;
+    // Per ROM settings aren't applied, as the ROM changes under the frontend
+    let mut resolved = load_config(config_path)?;
+    resolved.config.resolve_paths(config_path);
+    let mut emulator_config = resolved.config;
+    let (keymap, hotkeys) = prepare_config(&mut emulator_config)?;
 
     info!("Setting up frontend");
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "raylib")]{
-            info!("Setting up raylib");
-            // Create the audio device the front end will use
-            info!("Intializing the audio device");
-            let raylib_audio = audio::RaylibAudio::init_audio_device()?;
-            // Create the actual raylib frontend
-            debug!("Initializing the raylib frontend");
-            let frontend = raylib_frontend::RaylibFrontend::new(&emulator_config, &raylib_audio)?;
-            // Create the emulator using the raylib front end
-            info!("Initializing emulator");
-            let mut emulator = emulator::Emulator::new(Box::new(frontend), emulator_config)?;
-            info!("Loading game file");
-            emulator.load_file(args.program)?;
-            // Actually run the emulator using the raylib front end
-            info!("Running the emulator");
-            emulator.run()?;
+    match args.frontend {
+        FrontendKind::None => {
+            info!("Running headless");
+            run_playlist(
+                Box::new(HeadlessFrontend::new()),
+                emulator_config,
+                &playlist,
+                each,
+            )
+        }
+        FrontendKind::Terminal => {
+            info!("Drawing in the terminal");
+            run_playlist(
+                Box::new(TerminalFrontend::new(&emulator_config, &keymap, &hotkeys)?),
+                emulator_config,
+                &playlist,
+                each,
+            )
+        }
+        FrontendKind::Raylib => {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "raylib")]{
+                    let raylib_audio = if emulator_config.audio_enabled {
+                        info!("Intializing the audio device");
+                        Some(audio::RaylibAudio::init_audio_device()?)
+                    } else {
+                        None
+                    };
+                    let frontend = raylib_frontend::RaylibFrontend::new(
+                        &emulator_config,
+                        &keymap,
+                        &hotkeys,
+                        raylib_audio.as_ref(),
+                    )?;
+                    run_playlist(Box::new(frontend), emulator_config, &playlist, each)
+                } else {
+                    warn!("Raylib frontend not available, unable to run the playlist, exiting");
+                    println!("No Available Frontends! (try --frontend none)");
+                    Ok(())
+                }
+            }
+        }
+    }
+}
 
+/// Run attract mode with `frontend`, showing each ROM of `playlist` for `each`
+fn run_playlist<'a>(
+    frontend: Box<dyn Frontend + 'a>,
+    config: EmulatorConfig,
+    playlist: &Playlist,
+    each: Duration,
+) -> Result<()> {
+    info!("Initializing emulator");
+    let mut emulator = Emulator::new(frontend, config)?;
+    info!("Starting attract mode");
+    run_attract(&mut emulator, playlist, each, Instant::now)?;
+    Ok(())
+}
+
+/// Show the game being played in Discord alongside `frontend`, if enabled
+#[cfg(feature = "discord")]
+fn with_discord_presence<'a>(
+    frontend: Box<dyn Frontend + 'a>,
+    config: &EmulatorConfig,
+) -> Box<dyn Frontend + 'a> {
+    if !config.discord_presence {
+        return frontend;
+    }
+    info!("Showing the game being played in Discord");
+    let presence = DiscordPresence::new(&config.discord_client_id);
+    Box::new(TeeFrontend::new(frontend, vec![Box::new(presence)]))
+}
+
+/// Show the game being played in Discord alongside `frontend`, if enabled
+#[cfg(not(feature = "discord"))]
+fn with_discord_presence<'a>(
+    frontend: Box<dyn Frontend + 'a>,
+    config: &EmulatorConfig,
+) -> Box<dyn Frontend + 'a> {
+    if config.discord_presence {
+        warn!("discord_presence is on, but emul8rs was built without the discord feature");
+    }
+    frontend
+}
+
+/// Run the program in `layout`, read from `program`, with `frontend`, side by
+/// side with the `--compare-profile` if one is given (see [run_emulator] and
+/// [run_split_view]), and shown in Discord if enabled
+fn run_with<'a>(
+    frontend: Box<dyn Frontend + 'a>,
+    config: EmulatorConfig,
+    program: &Path,
+    layout: &MemoryLayout,
+    args: &RunArgs,
+) -> Result<ExitCode> {
+    let frontend = with_discord_presence(frontend, &config);
+    match &args.compare_profile {
+        Some(profile) => {
+            run_split_view(frontend, config, profile, program, layout)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        None => run_emulator(frontend, config, program, layout, args),
+    }
+}
+
+/// Show the program in `layout`, read from `program`, running with `config`
+/// and with the quirks of `profile` side by side in `frontend`, until it stops
+fn run_split_view<'a>(
+    frontend: Box<dyn Frontend + 'a>,
+    config: EmulatorConfig,
+    profile: &str,
+    program: &Path,
+    layout: &MemoryLayout,
+) -> Result<()> {
+    let platform = Platform::from_name(profile).context("Parsing --compare-profile")?;
+    let compared = config.merge(&platform.quirks())?;
+    // Any seed will do, as long as both halves draw the same random numbers
+    let seed = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    info!("Comparing with the {} profile", platform.name());
+    let mut view = SplitView::new(
+        frontend,
+        [
+            (config, "configured".to_string()),
+            (compared, format!("{} profile", platform.name())),
+        ],
+        layout,
+        program,
+        seed,
+    )?;
+    view.run()
+}
+
+/// Run the program in `layout`, read from `program`, with `frontend`, until it
+/// stops or one of the limits in `args` is reached
+///
+/// When limits are given, returns [EXIT_HALTED] if the program halts first, and
+/// any crash returns [EXIT_CRASHED]. Statistics about the run are written
+/// to `--stats-out` (if given), and the `--compat-report` printed, however it
+/// ends. With `--watch` the program is reloaded whenever its file changes.
+fn run_emulator<'a>(
+    frontend: Box<dyn Frontend + 'a>,
+    config: EmulatorConfig,
+    program: &Path,
+    layout: &MemoryLayout,
+    args: &RunArgs,
+) -> Result<ExitCode> {
+    let limits = args.limits()?;
+    let error_screen = config.error_screen;
+    let autosave_interval = config.autosave_interval();
+    info!("Initializing emulator");
+    let mut emulator = Emulator::new(frontend, config)?;
+    if args.compat_report {
+        emulator.enable_compat_report();
+    }
+    info!("Loading game file");
+    emulator.load_layout(layout, program)?;
+    if args.watch {
+        emulator.watch_rom(program);
+    }
+    if args.resume {
+        if emulator.resume_autosave(&autosave_dir()?)? {
+            info!("Resumed from the latest autosave");
         } else {
-            warn!("No available fronends, exiting");
-            println!("No Available Frontends!")
+            warn!("No autosave of {program:?} to resume from, starting from the beginning");
+        }
+    }
+    match autosave_dir() {
+        Ok(dir) => {
+            emulator.enable_emergency_save(dir.clone());
+            if let Some(interval) = autosave_interval {
+                emulator.enable_autosave(dir, interval);
+            }
+        }
+        Err(err) => warn!("Not autosaving, or saving if the frontend fails: {err:#}"),
+    }
+    if let Some(address) = &args.control_socket {
+        emulator.enable_remote_control(RemoteControl::listen(address)?);
+    }
+    info!("Running the emulator");
+    let result = emulator.run_for(limits);
+    if let Some(path) = &args.stats_out {
+        info!("Writing run statistics to {path:?}");
+        if let Err(err) = emulator.stats().write(path) {
+            error!("{err:#}");
+        }
+    }
+    if let Some(report) = emulator.compat_report() {
+        println!("{report}");
+    }
+    match result {
+        Ok(RunOutcome::Halted) if limits.is_limited() => {
+            eprintln!(
+                "Program halted after {} instructions, before reaching the limit",
+                emulator.cycles()
+            );
+            Ok(ExitCode::from(EXIT_HALTED))
+        }
+        Ok(_) => Ok(ExitCode::SUCCESS),
+        Err(err) => {
+            eprintln!(
+                "Error: emulator crashed after {} instructions: {err:?}",
+                emulator.cycles()
+            );
+            if error_screen && let Err(show_err) = emulator.show_error(&err) {
+                error!("Failed to show the error: {show_err:#}");
+            }
+            Ok(ExitCode::from(EXIT_CRASHED))
         }
     }
-    Ok(())
 }
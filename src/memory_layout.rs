@@ -0,0 +1,295 @@
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(feature = "std")]
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+
+use crate::emulator::{FONT, FONT_START_POSITION, MEMORY_SIZE};
+use crate::poke::Poke;
+#[cfg(feature = "std")]
+use crate::rom;
+use crate::rom::PROGRAM_START;
+
+/// Parse a number, in hex with a `0x` prefix or in decimal
+pub fn parse_number(text: &str) -> Result<usize> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .with_context(|| format!("Invalid number {text:?}, expected hex (e.g. 0x600) or decimal"))
+}
+
+/// Parse a memory address (see [parse_number])
+pub fn parse_address(text: &str) -> Result<usize> {
+    let address = parse_number(text)?;
+    if address >= MEMORY_SIZE {
+        bail!("Address {address:#05x} is past the end of memory ({MEMORY_SIZE:#x})");
+    }
+    Ok(address)
+}
+
+/// A file to load into memory, given as `ADDRESS:PATH` on the command line
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadSpec {
+    /// Address the file is loaded at
+    pub address: usize,
+    /// The file to load
+    pub path: PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl FromStr for LoadSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let Some((address, path)) = spec.split_once(':') else {
+            bail!("Expected ADDRESS:PATH (e.g. 0x600:data.bin), got {spec:?}");
+        };
+        if path.is_empty() {
+            bail!("No file given to load at {address} in {spec:?}");
+        }
+        Ok(Self {
+            address: parse_address(address)?,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl LoadSpec {
+    /// Read the file, as the region of memory it fills
+    pub fn read(&self) -> Result<MemoryRegion> {
+        let bytes =
+            rom::open(&self.path).with_context(|| format!("Failed to read {:?}", self.path))?;
+        Ok(MemoryRegion {
+            name: self.path.display().to_string(),
+            address: self.address,
+            bytes,
+        })
+    }
+}
+
+/// Bytes loaded into memory at an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// Name of the file the bytes came from, for errors
+    pub name: String,
+    /// Address of the first byte
+    pub address: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl MemoryRegion {
+    /// Address just past the last byte
+    fn end(&self) -> usize {
+        self.address + self.bytes.len()
+    }
+}
+
+impl fmt::Display for MemoryRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:#05x}-{:#05x})",
+            self.name,
+            self.address,
+            self.end().max(self.address + 1) - 1
+        )
+    }
+}
+
+/// Check `regions` all fit in memory, without overlapping each other or the
+/// font
+pub fn check_regions(regions: &[MemoryRegion]) -> Result<()> {
+    let font_end = FONT_START_POSITION + FONT.len();
+    let mut filled: Vec<&MemoryRegion> = regions
+        .iter()
+        .filter(|region| !region.bytes.is_empty())
+        .collect();
+    for region in &filled {
+        if region.end() > MEMORY_SIZE {
+            bail!(
+                "{} is {} bytes, too large to fit in memory (which ends at {MEMORY_SIZE:#x})",
+                region.name,
+                region.bytes.len()
+            );
+        }
+        if region.address < font_end && FONT_START_POSITION < region.end() {
+            bail!(
+                "{region} overlaps the font ({FONT_START_POSITION:#05x}-{:#05x})",
+                font_end - 1
+            );
+        }
+    }
+    filled.sort_by_key(|region| region.address);
+    for pair in filled.windows(2) {
+        if pair[1].address < pair[0].end() {
+            bail!("{} overlaps {}", pair[0], pair[1]);
+        }
+    }
+    Ok(())
+}
+
+/// What is loaded into memory before a program starts, where it starts, and
+/// any other values set first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// The program loaded at 0x200, empty when only other files are loaded
+    pub rom: Vec<u8>,
+    /// Other files loaded, each at its own address
+    pub regions: Vec<MemoryRegion>,
+    /// Address execution starts at
+    pub start_address: usize,
+    /// Values set, in order, once everything is loaded
+    pub pokes: Vec<Poke>,
+}
+
+impl MemoryLayout {
+    /// The program (its name and contents) if there is one, with `regions`,
+    /// starting at `start_address` or else the lowest address loaded
+    ///
+    /// Fails if anything overlaps or doesn't fit in memory.
+    pub fn new(
+        program: Option<(String, Vec<u8>)>,
+        regions: Vec<MemoryRegion>,
+        start_address: Option<usize>,
+    ) -> Result<Self> {
+        let program = program.map(|(name, bytes)| MemoryRegion {
+            name,
+            address: PROGRAM_START,
+            bytes,
+        });
+        let all: Vec<MemoryRegion> = program.iter().chain(&regions).cloned().collect();
+        check_regions(&all)?;
+        let start_address = match start_address {
+            Some(address) if address + 1 >= MEMORY_SIZE => {
+                bail!("Start address {address:#05x} is past the end of memory ({MEMORY_SIZE:#x})")
+            }
+            Some(address) => address,
+            None => all
+                .iter()
+                .map(|region| region.address)
+                .min()
+                .unwrap_or(PROGRAM_START),
+        };
+        Ok(Self {
+            rom: program.map(|program| program.bytes).unwrap_or_default(),
+            regions,
+            start_address,
+            pokes: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_memory_layout {
+    use super::*;
+
+    fn region(name: &str, address: usize, size: usize) -> MemoryRegion {
+        MemoryRegion {
+            name: name.to_string(),
+            address,
+            bytes: vec![0x12; size],
+        }
+    }
+
+    #[test]
+    /// Test parsing numbers, addresses, and ADDRESS:PATH specs
+    fn test_parse() -> Result<()> {
+        // Numbers aren't limited to memory, the callers check their range
+        assert_eq!(parse_number("0x1000")?, 0x1000);
+        assert_eq!(parse_number("65536")?, 0x10000);
+        assert_eq!(parse_address("0x600")?, 0x600);
+        assert_eq!(parse_address("0XFFF")?, 0xFFF);
+        assert_eq!(parse_address("1536")?, 0x600);
+        for invalid in ["", "0x", "six", "0x1000", "4096", "-1"] {
+            assert!(parse_address(invalid).is_err(), "{invalid:?}");
+        }
+
+        assert_eq!(
+            "0x600:data.bin".parse::<LoadSpec>()?,
+            LoadSpec {
+                address: 0x600,
+                path: PathBuf::from("data.bin")
+            }
+        );
+        // Only the first colon separates the address
+        assert_eq!(
+            "512:C:/roms/main.ch8".parse::<LoadSpec>()?.path,
+            PathBuf::from("C:/roms/main.ch8")
+        );
+        for invalid in ["data.bin", "0x600:", "data:0x600", ":data.bin"] {
+            assert!(invalid.parse::<LoadSpec>().is_err(), "{invalid:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test overlapping and out of range regions are errors naming the files
+    fn test_check_regions() {
+        let main = region("main.ch8", 0x200, 0x100);
+        let data = region("data.bin", 0x600, 0x20);
+        assert!(check_regions(&[data.clone(), main.clone()]).is_ok());
+        // Touching, but not overlapping
+        assert!(check_regions(&[main.clone(), region("next.bin", 0x300, 1)]).is_ok());
+        assert!(check_regions(&[region("last.bin", 0xFFF, 1)]).is_ok());
+        // Empty files don't take up any memory
+        assert!(check_regions(&[main.clone(), region("empty.bin", 0x250, 0)]).is_ok());
+
+        let err = check_regions(&[main.clone(), data, region("sprites.bin", 0x2F0, 0x20)])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "main.ch8 (0x200-0x2ff) overlaps sprites.bin (0x2f0-0x30f)"
+        );
+        let err = check_regions(&[region("font.bin", 0x90, 0x20)])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("font.bin") && err.contains("font"), "{err}");
+        let err = check_regions(&[region("big.bin", 0xF00, 0x101)])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("big.bin") && err.contains("too large"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    /// Test where execution starts
+    fn test_start_address() -> Result<()> {
+        let data = region("data.bin", 0x600, 0x20);
+        let code = region("code.ch8", 0x400, 0x20);
+        let layout = MemoryLayout::new(
+            Some(("main.ch8".to_string(), vec![0x12, 0x00])),
+            vec![data.clone()],
+            None,
+        )?;
+        assert_eq!(layout.start_address, 0x200);
+        assert_eq!(layout.rom, [0x12, 0x00]);
+        // Without the program, the lowest file loaded
+        let layout = MemoryLayout::new(None, vec![data.clone(), code.clone()], None)?;
+        assert_eq!((layout.start_address, layout.rom.len()), (0x400, 0));
+        let layout = MemoryLayout::new(None, vec![data.clone(), code], Some(0x600))?;
+        assert_eq!(layout.start_address, 0x600);
+        assert!(MemoryLayout::new(None, vec![data.clone()], Some(0xFFF)).is_err());
+        // The program counts when checking for overlaps
+        let err = MemoryLayout::new(
+            Some(("main.ch8".to_string(), vec![0; 0x500])),
+            vec![data],
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("main.ch8"), "{err}");
+        Ok(())
+    }
+}
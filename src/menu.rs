@@ -0,0 +1,212 @@
+/// Entries of the pause menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    /// Close the menu and carry on
+    Resume,
+    /// Restart the ROM from the beginning
+    Reset,
+    /// Replace the running ROM with another one
+    LoadRom,
+    /// Switch to the next color theme
+    ToggleTheme,
+    /// Stop the emulator
+    Quit,
+}
+
+impl MenuItem {
+    /// All of the menu items, in the order they are shown
+    pub const ALL: [MenuItem; 5] = [
+        MenuItem::Resume,
+        MenuItem::Reset,
+        MenuItem::LoadRom,
+        MenuItem::ToggleTheme,
+        MenuItem::Quit,
+    ];
+
+    /// Text shown for the item
+    pub fn label(&self) -> &'static str {
+        match self {
+            MenuItem::Resume => "Resume",
+            MenuItem::Reset => "Reset ROM",
+            MenuItem::LoadRom => "Load ROM...",
+            MenuItem::ToggleTheme => "Toggle theme",
+            MenuItem::Quit => "Quit",
+        }
+    }
+}
+
+/// Input for navigating the menu, from the keyboard or mouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuInput {
+    /// Move the selection up, wrapping around to the bottom
+    Up,
+    /// Move the selection down, wrapping around to the top
+    Down,
+    /// Choose the selected item
+    Activate,
+    /// Close the menu (the same as choosing Resume)
+    Close,
+    /// The mouse moved over the item at this index
+    Hover(usize),
+    /// The item at this index was clicked
+    Click(usize),
+}
+
+/// State of the pause menu, independent of how a frontend draws it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PauseMenu {
+    open: bool,
+    /// Index of the selected item in [MenuItem::ALL]
+    selected: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the menu is being shown
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Show the menu, with the first item (Resume) selected
+    pub fn open(&mut self) {
+        self.open = true;
+        self.selected = 0;
+    }
+
+    /// Hide the menu
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// The items to show
+    pub fn items(&self) -> &'static [MenuItem] {
+        &MenuItem::ALL
+    }
+
+    /// Index of the selected item in [PauseMenu::items]
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Handle a navigation input, returning the item chosen (if any)
+    ///
+    /// Choosing an item closes the menu, except for Load ROM (which may be
+    /// cancelled) and Toggle theme (so more themes can be tried).
+    pub fn handle(&mut self, input: MenuInput) -> Option<MenuItem> {
+        if !self.open {
+            return None;
+        }
+        let count = self.items().len();
+        let chosen = match input {
+            MenuInput::Up => {
+                self.selected = (self.selected + count - 1) % count;
+                None
+            }
+            MenuInput::Down => {
+                self.selected = (self.selected + 1) % count;
+                None
+            }
+            MenuInput::Activate => Some(self.items()[self.selected]),
+            MenuInput::Close => Some(MenuItem::Resume),
+            MenuInput::Hover(index) => {
+                if index < count {
+                    self.selected = index;
+                }
+                None
+            }
+            MenuInput::Click(index) => {
+                let item = self.items().get(index).copied();
+                if item.is_some() {
+                    self.selected = index;
+                }
+                item
+            }
+        };
+        if chosen.is_some_and(|item| !matches!(item, MenuItem::LoadRom | MenuItem::ToggleTheme)) {
+            self.close();
+        }
+        chosen
+    }
+}
+
+#[cfg(test)]
+mod test_menu {
+    use super::*;
+
+    #[test]
+    /// Test moving the selection with the keyboard, wrapping at both ends
+    fn test_navigation() {
+        let mut menu = PauseMenu::new();
+        // Nothing happens while the menu is closed
+        assert_eq!(menu.handle(MenuInput::Activate), None);
+        menu.open();
+        assert!(menu.is_open());
+        assert_eq!(menu.selected(), 0);
+
+        menu.handle(MenuInput::Down);
+        menu.handle(MenuInput::Down);
+        assert_eq!(menu.items()[menu.selected()], MenuItem::LoadRom);
+        menu.handle(MenuInput::Up);
+        assert_eq!(menu.items()[menu.selected()], MenuItem::Reset);
+        menu.handle(MenuInput::Up);
+        menu.handle(MenuInput::Up);
+        assert_eq!(menu.items()[menu.selected()], MenuItem::Quit);
+        menu.handle(MenuInput::Down);
+        assert_eq!(menu.items()[menu.selected()], MenuItem::Resume);
+
+        // Reopening starts from the top again
+        menu.handle(MenuInput::Down);
+        menu.close();
+        menu.open();
+        assert_eq!(menu.selected(), 0);
+    }
+
+    #[test]
+    /// Test choosing items, and which of them close the menu
+    fn test_activate() {
+        let mut menu = PauseMenu::new();
+        menu.open();
+        menu.handle(MenuInput::Down);
+        assert_eq!(menu.handle(MenuInput::Activate), Some(MenuItem::Reset));
+        assert!(!menu.is_open());
+
+        menu.open();
+        menu.handle(MenuInput::Up);
+        menu.handle(MenuInput::Up);
+        assert_eq!(
+            menu.handle(MenuInput::Activate),
+            Some(MenuItem::ToggleTheme)
+        );
+        assert!(menu.is_open());
+        menu.handle(MenuInput::Down);
+        assert_eq!(menu.handle(MenuInput::Activate), Some(MenuItem::Quit));
+        assert!(!menu.is_open());
+
+        menu.open();
+        assert_eq!(menu.handle(MenuInput::Close), Some(MenuItem::Resume));
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    /// Test selecting and choosing items with the mouse
+    fn test_mouse() {
+        let mut menu = PauseMenu::new();
+        menu.open();
+        menu.handle(MenuInput::Hover(3));
+        assert_eq!(menu.selected(), 3);
+        // Positions outside the menu are ignored
+        menu.handle(MenuInput::Hover(10));
+        assert_eq!(menu.selected(), 3);
+        assert_eq!(menu.handle(MenuInput::Click(10)), None);
+        assert!(menu.is_open());
+
+        assert_eq!(menu.handle(MenuInput::Click(2)), Some(MenuItem::LoadRom));
+        assert_eq!(menu.selected(), 2);
+        assert!(menu.is_open());
+        assert_eq!(menu.handle(MenuInput::Click(0)), Some(MenuItem::Resume));
+        assert!(!menu.is_open());
+    }
+}
@@ -34,3 +34,34 @@ impl Frontend for NoOpFrontend {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test_noop_frontend {
+    use super::*;
+
+    #[test]
+    /// Test that the frontend doesn't report an FPS by default
+    fn test_achieved_fps_default() {
+        let frontend = NoOpFrontend::new();
+        assert_eq!(frontend.achieved_fps(), None);
+    }
+
+    #[test]
+    /// Test that no keys are reported as down
+    fn test_current_keys_default() -> anyhow::Result<()> {
+        let mut frontend = NoOpFrontend::new();
+        assert_eq!(frontend.current_keys()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    /// Test that the frontend reports no capabilities by default
+    fn test_capabilities_default() {
+        let frontend = NoOpFrontend::new();
+        assert_eq!(
+            frontend.capabilities(),
+            crate::frontend::FrontendCapabilities::default()
+        );
+        assert!(!frontend.capabilities().has_audio);
+    }
+}
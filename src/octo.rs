@@ -0,0 +1,987 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::rom::PROGRAM_START;
+
+/// Extension of Octo source files, which are compiled when loaded
+pub const OCTO_EXTENSION: &str = "8o";
+
+/// Whether `path` is Octo source (by its extension)
+pub fn is_octo_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(OCTO_EXTENSION))
+}
+
+/// A word of the source, and the line it is on (counting from 1)
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+/// Split `source` into whitespace separated words, leaving out `#` comments
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        for text in line.split_whitespace() {
+            if text.starts_with('#') {
+                break;
+            }
+            tokens.push(Token {
+                text,
+                line: index + 1,
+            });
+        }
+    }
+    tokens
+}
+
+/// Parse a number literal, in decimal, hex (`0x`), or binary (`0b`), possibly
+/// negative
+fn parse_literal(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Parse a register name, `v0` to `vf`
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// Operators assigning to a register
+const ASSIGNMENTS: &[&str] = &[":=", "+=", "-=", "=-", "|=", "&=", "^=", ">>=", "<<="];
+
+/// Words with a meaning of their own, which can't be used as names
+const KEYWORDS: &[&str] = &[
+    "==",
+    "!=",
+    "<",
+    ">",
+    "<=",
+    ">=",
+    "-",
+    ";",
+    "i",
+    "return",
+    "clear",
+    "hires",
+    "lores",
+    "exit",
+    "scroll-left",
+    "scroll-right",
+    "scroll-down",
+    "scroll-up",
+    "audio",
+    "plane",
+    "bcd",
+    "save",
+    "load",
+    "saveflags",
+    "loadflags",
+    "sprite",
+    "jump",
+    "jump0",
+    "native",
+    "delay",
+    "buzzer",
+    "pitch",
+    "key",
+    "-key",
+    "random",
+    "hex",
+    "bighex",
+    "long",
+    "if",
+    "then",
+    "begin",
+    "else",
+    "end",
+    "loop",
+    "while",
+    "again",
+];
+
+/// A name which can be given to a label, constant, or alias
+fn is_name(text: &str) -> bool {
+    !text.starts_with(':')
+        && parse_literal(text).is_none()
+        && parse_register(text).is_none()
+        && !ASSIGNMENTS.contains(&text)
+        && !KEYWORDS.contains(&text)
+}
+
+/// How a forward reference to a label is filled in once it is defined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fixup {
+    /// The low 12 bits of the instruction at the offset
+    Address,
+    /// The 16 bit word at the offset (after `i := long`)
+    Long,
+}
+
+/// A use of a label before its definition
+#[derive(Debug)]
+struct ForwardReference<'a> {
+    name: &'a str,
+    line: usize,
+    /// Offset into the program of the instruction to fill in
+    offset: usize,
+    fixup: Fixup,
+}
+
+/// An open `loop`, waiting for its `again`
+#[derive(Debug)]
+struct OpenLoop {
+    start: u16,
+    line: usize,
+    /// Offsets of the jumps out of the loop from each `while`
+    exits: Vec<usize>,
+}
+
+/// The two ways a condition can skip the following instruction
+#[derive(Debug, Clone, Copy)]
+struct Condition {
+    /// Skips when the condition is false, so the next instruction only runs
+    /// when it is true
+    skip_if_false: u16,
+    /// Skips when the condition is true
+    skip_if_true: u16,
+}
+
+struct Compiler<'a> {
+    tokens: Vec<Token<'a>>,
+    /// Index of the next token
+    position: usize,
+    program: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u8>,
+    forward_references: Vec<ForwardReference<'a>>,
+    loops: Vec<OpenLoop>,
+    /// Offsets of the jumps from each open `if ... begin` (or `else`), with the
+    /// line it started on
+    branches: Vec<(usize, usize)>,
+    /// Whether the program starts with a jump to main (which is left out when
+    /// main is the first thing in the program)
+    jump_to_main: bool,
+}
+
+/// An error at `line` of the source
+fn error_at(line: usize, message: impl std::fmt::Display) -> anyhow::Error {
+    anyhow!("Line {line}: {message}")
+}
+
+impl<'a> Compiler<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            tokens: tokenize(source),
+            position: 0,
+            // The jump to main is filled in at the end
+            program: vec![0, 0],
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            forward_references: Vec::new(),
+            loops: Vec::new(),
+            branches: Vec::new(),
+            jump_to_main: true,
+        }
+    }
+
+    /// Address the next byte is written to
+    fn here(&self) -> Result<u16> {
+        let address = PROGRAM_START + self.program.len();
+        u16::try_from(address).map_err(|_| anyhow!("The program is too large, past {address:#x}"))
+    }
+
+    fn next(&mut self) -> Result<Token<'a>> {
+        let token = self.tokens.get(self.position).copied().ok_or_else(|| {
+            let line = self.tokens.last().map_or(1, |token| token.line);
+            error_at(line, "Unexpected end of file")
+        })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(|token| token.text)
+    }
+
+    /// Take the next token, which must be `expected`
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        let token = self.next()?;
+        if token.text != expected {
+            return Err(error_at(
+                token.line,
+                format!("Expected {expected:?}, found {:?}", token.text),
+            ));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, instruction: u16) {
+        self.program.extend(instruction.to_be_bytes());
+    }
+
+    /// The register a token names, directly or through an alias
+    fn lookup_register(&self, text: &str) -> Option<u8> {
+        parse_register(text).or_else(|| self.aliases.get(text).copied())
+    }
+
+    fn register(&mut self) -> Result<u16> {
+        let token = self.next()?;
+        self.lookup_register(token.text)
+            .map(u16::from)
+            .ok_or_else(|| {
+                error_at(
+                    token.line,
+                    format!("Expected a register, found {:?}", token.text),
+                )
+            })
+    }
+
+    /// The value of a number, constant, or (already defined) label
+    fn lookup_value(&self, text: &str) -> Option<i64> {
+        parse_literal(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|address| i64::from(*address)))
+    }
+
+    /// A value between `min` and `max`
+    fn value(&mut self, min: i64, max: i64) -> Result<u16> {
+        let token = self.next()?;
+        let value = self
+            .lookup_value(token.text)
+            .ok_or_else(|| error_at(token.line, format!("Unknown value {:?}", token.text)))?;
+        if value < min || value > max {
+            return Err(error_at(
+                token.line,
+                format!("{value} is out of range, expected {min} to {max}"),
+            ));
+        }
+        Ok(value as u16)
+    }
+
+    /// A byte, where negative values count down from 256
+    fn byte(&mut self) -> Result<u16> {
+        Ok(self.value(-128, 255)? & 0xFF)
+    }
+
+    /// An address for an instruction, which can be a label defined later (filled
+    /// in with `fixup` once it is)
+    fn address(&mut self, fixup: Fixup) -> Result<u16> {
+        let token = self.next()?;
+        let max = match fixup {
+            Fixup::Address => 0xFFF,
+            Fixup::Long => 0xFFFF,
+        };
+        match self.lookup_value(token.text) {
+            Some(value) if (0..=max).contains(&value) => Ok(value as u16),
+            Some(value) => Err(error_at(
+                token.line,
+                format!("Address {value:#x} is out of range, at most {max:#x}"),
+            )),
+            None if is_name(token.text) => {
+                // Instructions are written right after their address is read
+                let offset = self.program.len() + if fixup == Fixup::Long { 2 } else { 0 };
+                self.forward_references.push(ForwardReference {
+                    name: token.text,
+                    line: token.line,
+                    offset,
+                    fixup,
+                });
+                Ok(0)
+            }
+            None => Err(error_at(
+                token.line,
+                format!("Expected an address, found {:?}", token.text),
+            )),
+        }
+    }
+
+    /// A name being defined, which mustn't already be in use
+    fn new_name(&mut self) -> Result<Token<'a>> {
+        let token = self.next()?;
+        if !is_name(token.text) {
+            return Err(error_at(
+                token.line,
+                format!("{:?} can't be used as a name", token.text),
+            ));
+        }
+        if self.labels.contains_key(token.text) || self.constants.contains_key(token.text) {
+            return Err(error_at(
+                token.line,
+                format!("{:?} is already defined", token.text),
+            ));
+        }
+        Ok(token)
+    }
+
+    /// The condition of an `if` or `while`
+    fn condition(&mut self) -> Result<Condition> {
+        let x = self.register()? << 8;
+        let operator = self.next()?;
+        let (skip_if_false, skip_if_true) = match operator.text {
+            "key" => (0xE0A1 | x, 0xE09E | x),
+            "-key" => (0xE09E | x, 0xE0A1 | x),
+            "==" | "!=" => {
+                let (equal_skip, not_equal_skip) =
+                    match self.peek().and_then(|text| self.lookup_register(text)) {
+                        Some(y) => {
+                            self.position += 1;
+                            let y = u16::from(y) << 4;
+                            // 9XY0 skips when they differ, 5XY0 when they match
+                            (0x9000 | x | y, 0x5000 | x | y)
+                        }
+                        None => {
+                            let nn = self.byte()?;
+                            // 4XNN skips when they differ, 3XNN when they match
+                            (0x4000 | x | nn, 0x3000 | x | nn)
+                        }
+                    };
+                if operator.text == "==" {
+                    (equal_skip, not_equal_skip)
+                } else {
+                    (not_equal_skip, equal_skip)
+                }
+            }
+            "<" | ">" | "<=" | ">=" => {
+                return Err(error_at(
+                    operator.line,
+                    format!(
+                        "Comparing with {} isn't supported, only ==, !=, key, and -key",
+                        operator.text
+                    ),
+                ));
+            }
+            text => {
+                return Err(error_at(
+                    operator.line,
+                    format!("Expected ==, !=, key, or -key, found {text:?}"),
+                ));
+            }
+        };
+        Ok(Condition {
+            skip_if_false,
+            skip_if_true,
+        })
+    }
+
+    /// Emit a jump to be filled in later, returning its offset
+    fn placeholder_jump(&mut self) -> usize {
+        let offset = self.program.len();
+        self.emit(0x1000);
+        offset
+    }
+
+    /// Point the jump at `offset` to the next address
+    fn patch_jump(&mut self, offset: usize) -> Result<()> {
+        let target = self.here()?;
+        if target > 0xFFF {
+            bail!("Jump target {target:#x} is out of range, at most 0xfff");
+        }
+        let [high, low] = (0x1000 | target).to_be_bytes();
+        self.program[offset] = high;
+        self.program[offset + 1] = low;
+        Ok(())
+    }
+
+    /// Compile the statement starting with `token`
+    fn statement(&mut self, token: Token<'a>) -> Result<()> {
+        let line = token.line;
+        match token.text {
+            ":" => {
+                let name = self.new_name()?;
+                // Leave out the jump when main is the first thing in the program
+                if name.text == "main" && self.program.len() == 2 && self.labels.is_empty() {
+                    self.program.clear();
+                    self.jump_to_main = false;
+                }
+                let address = self.here()?;
+                self.labels.insert(name.text, address);
+            }
+            ":const" => {
+                let name = self.new_name()?;
+                let value = self.next()?;
+                let value = self.lookup_value(value.text).ok_or_else(|| {
+                    error_at(value.line, format!("Unknown value {:?}", value.text))
+                })?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.new_name()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register as u8);
+            }
+            ":call" => {
+                let address = self.address(Fixup::Address)?;
+                self.emit(0x2000 | address);
+            }
+            ":breakpoint" => {
+                // Only meaningful to Octo's debugger
+                self.new_name()?;
+            }
+            "return" | ";" => self.emit(0x00EE),
+            "clear" => self.emit(0x00E0),
+            "hires" => self.emit(0x00FF),
+            "lores" => self.emit(0x00FE),
+            "exit" => self.emit(0x00FD),
+            "scroll-left" => self.emit(0x00FC),
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-down" => {
+                let n = self.value(0, 15)?;
+                self.emit(0x00C0 | n);
+            }
+            "scroll-up" => {
+                let n = self.value(0, 15)?;
+                self.emit(0x00D0 | n);
+            }
+            "audio" => self.emit(0xF002),
+            "plane" => {
+                let n = self.value(0, 15)?;
+                self.emit(0xF001 | n << 8);
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(0xF033 | x << 8);
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.peek() == Some("-") {
+                    // XO-CHIP range of registers
+                    self.position += 1;
+                    let y = self.register()?;
+                    let n = if token.text == "save" { 0x2 } else { 0x3 };
+                    self.emit(0x5000 | x << 8 | y << 4 | n);
+                } else {
+                    let nn = if token.text == "save" { 0x55 } else { 0x65 };
+                    self.emit(0xF000 | x << 8 | nn);
+                }
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(0xF075 | x << 8);
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(0xF085 | x << 8);
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.value(0, 15)?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            }
+            "jump" => {
+                let address = self.address(Fixup::Address)?;
+                self.emit(0x1000 | address);
+            }
+            "jump0" => {
+                let address = self.address(Fixup::Address)?;
+                self.emit(0xB000 | address);
+            }
+            "native" => {
+                let address = self.address(Fixup::Address)?;
+                self.emit(address);
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let nn = match token.text {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.emit(0xF000 | x << 8 | nn);
+            }
+            "i" => self.index_statement()?,
+            "if" => {
+                let condition = self.condition()?;
+                let then = self.next()?;
+                match then.text {
+                    "then" => self.emit(condition.skip_if_false),
+                    "begin" => {
+                        self.emit(condition.skip_if_true);
+                        let jump = self.placeholder_jump();
+                        self.branches.push((jump, line));
+                    }
+                    text => {
+                        return Err(error_at(
+                            then.line,
+                            format!("Expected then or begin, found {text:?}"),
+                        ));
+                    }
+                }
+            }
+            "else" => {
+                let (jump, start) = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| error_at(line, "else without an if ... begin"))?;
+                let end_jump = self.placeholder_jump();
+                self.patch_jump(jump).map_err(|err| error_at(line, err))?;
+                self.branches.push((end_jump, start));
+            }
+            "end" => {
+                let (jump, _) = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| error_at(line, "end without an if ... begin"))?;
+                self.patch_jump(jump).map_err(|err| error_at(line, err))?;
+            }
+            "loop" => {
+                let start = self.here()?;
+                self.loops.push(OpenLoop {
+                    start,
+                    line,
+                    exits: Vec::new(),
+                });
+            }
+            "while" => {
+                let condition = self.condition()?;
+                self.emit(condition.skip_if_true);
+                let jump = self.placeholder_jump();
+                self.loops
+                    .last_mut()
+                    .ok_or_else(|| error_at(line, "while outside of a loop"))?
+                    .exits
+                    .push(jump);
+            }
+            "again" => {
+                let open = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| error_at(line, "again without a loop"))?;
+                if open.start > 0xFFF {
+                    return Err(error_at(line, "Loop start is out of range, past 0xfff"));
+                }
+                self.emit(0x1000 | open.start);
+                for exit in open.exits {
+                    self.patch_jump(exit).map_err(|err| error_at(line, err))?;
+                }
+            }
+            text if self.lookup_register(text).is_some() => self.register_statement(token)?,
+            text if text.starts_with(':') => {
+                return Err(error_at(line, format!("{text} isn't supported")));
+            }
+            text => {
+                if let Some(value) =
+                    parse_literal(text).or_else(|| self.constants.get(text).copied())
+                {
+                    // Data
+                    if !(-128..=255).contains(&value) {
+                        return Err(error_at(
+                            line,
+                            format!("{value} is out of range for a byte, expected -128 to 255"),
+                        ));
+                    }
+                    self.program.push(value as u8);
+                } else if self.peek().is_some_and(|next| ASSIGNMENTS.contains(&next)) {
+                    return Err(error_at(
+                        line,
+                        format!("Unknown register {text:?}, expected v0 to vf or an alias"),
+                    ));
+                } else if is_name(text) {
+                    // Calling a subroutine by its name
+                    self.position -= 1;
+                    let address = self.address(Fixup::Address)?;
+                    self.emit(0x2000 | address);
+                } else {
+                    return Err(error_at(line, format!("Unexpected {text:?}")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compile a statement assigning to the index register
+    fn index_statement(&mut self) -> Result<()> {
+        let operator = self.next()?;
+        match operator.text {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.position += 1;
+                    let x = self.register()?;
+                    self.emit(0xF029 | x << 8);
+                }
+                Some("bighex") => {
+                    self.position += 1;
+                    let x = self.register()?;
+                    self.emit(0xF030 | x << 8);
+                }
+                Some("long") => {
+                    self.position += 1;
+                    let address = self.address(Fixup::Long)?;
+                    self.emit(0xF000);
+                    self.emit(address);
+                }
+                _ => {
+                    let address = self.address(Fixup::Address)?;
+                    self.emit(0xA000 | address);
+                }
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.emit(0xF01E | x << 8);
+            }
+            text => {
+                return Err(error_at(
+                    operator.line,
+                    format!("Expected := or += after i, found {text:?}"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compile a statement assigning to the register named by `token`
+    fn register_statement(&mut self, token: Token<'a>) -> Result<()> {
+        let x = u16::from(self.lookup_register(token.text).unwrap_or_default()) << 8;
+        let operator = self.next()?;
+        let y = self.peek().and_then(|text| self.lookup_register(text));
+        if let Some(y) = y {
+            let n = match operator.text {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                text => {
+                    return Err(error_at(
+                        operator.line,
+                        format!("Unknown operator {text:?} between registers"),
+                    ));
+                }
+            };
+            self.position += 1;
+            self.emit(0x8000 | x | u16::from(y) << 4 | n);
+            return Ok(());
+        }
+        match (operator.text, self.peek()) {
+            (":=", Some("delay")) => {
+                self.position += 1;
+                self.emit(0xF007 | x);
+            }
+            (":=", Some("key")) => {
+                self.position += 1;
+                self.emit(0xF00A | x);
+            }
+            (":=", Some("random")) => {
+                self.position += 1;
+                let nn = self.byte()?;
+                self.emit(0xC000 | x | nn);
+            }
+            (":=", _) => {
+                let nn = self.byte()?;
+                self.emit(0x6000 | x | nn);
+            }
+            ("+=", _) => {
+                let nn = self.byte()?;
+                self.emit(0x7000 | x | nn);
+            }
+            ("-=", _) => {
+                // Adding the two's complement
+                let nn = self.byte()?;
+                self.emit(0x7000 | x | (nn.wrapping_neg() & 0xFF));
+            }
+            (text, _) => {
+                return Err(error_at(
+                    operator.line,
+                    format!("Unknown operator {text:?} with a number"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn compile(mut self) -> Result<Vec<u8>> {
+        while let Some(token) = self.tokens.get(self.position).copied() {
+            self.position += 1;
+            self.statement(token)?;
+        }
+        if let Some(open) = self.loops.first() {
+            return Err(error_at(open.line, "loop without an again"));
+        }
+        if let Some((_, line)) = self.branches.first() {
+            return Err(error_at(*line, "if ... begin without an end"));
+        }
+        for reference in &self.forward_references {
+            let address = *self.labels.get(reference.name).ok_or_else(|| {
+                error_at(
+                    reference.line,
+                    format!("Undefined name {:?}", reference.name),
+                )
+            })?;
+            let offset = reference.offset;
+            match reference.fixup {
+                Fixup::Address => {
+                    if address > 0xFFF {
+                        return Err(error_at(
+                            reference.line,
+                            format!(
+                                "{} ({address:#x}) is out of range, at most 0xfff",
+                                reference.name
+                            ),
+                        ));
+                    }
+                    self.program[offset] |= (address >> 8) as u8;
+                    self.program[offset + 1] = address as u8;
+                }
+                Fixup::Long => {
+                    self.program[offset..offset + 2].copy_from_slice(&address.to_be_bytes());
+                }
+            }
+        }
+        let main = *self
+            .labels
+            .get("main")
+            .ok_or_else(|| anyhow!("The program has no main label (: main)"))?;
+        if self.jump_to_main {
+            if main > 0xFFF {
+                bail!("main ({main:#x}) is out of range, at most 0xfff");
+            }
+            self.program[..2].copy_from_slice(&(0x1000 | main).to_be_bytes());
+        }
+        Ok(self.program)
+    }
+}
+
+/// Compile Octo assembly `source` into a program to load at [PROGRAM_START]
+///
+/// Supports labels (`: name`), `:const`, `:alias`, `:call`, registers `v0` to
+/// `vf`, numbers in decimal, hex, and binary, raw bytes of data, the CHIP-8,
+/// SUPER-CHIP, and XO-CHIP statements (including `i := long NNN`), `if ... then`,
+/// `if ... begin ... else ... end`, and `loop ... while ... again`. Macros,
+/// `:calc`, `:org`, `:next`, `:unpack`, `:byte`, strings, and the `<`, `>`, `<=`,
+/// and `>=` comparisons aren't supported, and are errors naming their line.
+pub fn compile(source: &str) -> Result<Vec<u8>> {
+    Compiler::new(source).compile()
+}
+
+#[cfg(test)]
+mod test_octo {
+    use super::*;
+    use crate::config::EmulatorConfig;
+    use crate::emulator::Emulator;
+    use crate::noop_frontend::NoOpFrontend;
+
+    fn words(program: &[u8]) -> Vec<u16> {
+        program
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+            .collect()
+    }
+
+    #[test]
+    /// Test compiling the first example from Octo's beginner's guide
+    fn test_beginners_guide() -> Result<()> {
+        let source = "
+            : person
+                0x70 0x70 0x20 0x70 0xA8 0x20 0x50 0x50
+
+            : main
+                i := person
+                v0 := 10
+                v1 := 20
+                sprite v0 v1 8
+                loop again
+        ";
+        assert_eq!(
+            compile(source)?,
+            [
+                0x12, 0x0A, 0x70, 0x70, 0x20, 0x70, 0xA8, 0x20, 0x50, 0x50, 0xA2, 0x02, 0x60, 0x0A,
+                0x61, 0x14, 0xD0, 0x18, 0x12, 0x12
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test each kind of statement compiles to the right instruction
+    fn test_statements() -> Result<()> {
+        let source = "
+            :const speed 3
+            :alias px v4
+            : main     # main first, so there's no jump to it
+                clear
+                px := speed
+                px += 1  px -= 1
+                v1 := v2  v1 |= v2  v1 &= v2  v1 ^= v2  v1 += v2  v1 -= v2
+                v1 >>= v2  v1 =- v2  v1 <<= v2
+                va := random 0x0F  vb := delay  vc := key
+                delay := va  buzzer := vb
+                i := 0x300  i += v3  i := hex v5  i := bighex v6  i := long 0x1234
+                bcd v1  save v7  load v8  save v1 - v3  load v2 - v4
+                saveflags v2  loadflags v2
+                sprite v0 v1 0
+                hires lores scroll-down 4 scroll-up 2 scroll-left scroll-right exit
+                plane 3 audio pitch := v9
+                jump0 0x210  native 0x123  :call 0x400
+                return ;
+        ";
+        assert_eq!(
+            words(&compile(source)?),
+            [
+                0x00E0, 0x6403, 0x7401, 0x74FF, 0x8120, 0x8121, 0x8122, 0x8123, 0x8124, 0x8125,
+                0x8126, 0x8127, 0x812E, 0xCA0F, 0xFB07, 0xFC0A, 0xFA15, 0xFB18, 0xA300, 0xF31E,
+                0xF529, 0xF630, 0xF000, 0x1234, 0xF133, 0xF755, 0xF865, 0x5132, 0x5243, 0xF275,
+                0xF285, 0xD010, 0x00FF, 0x00FE, 0x00C4, 0x00D2, 0x00FC, 0x00FB, 0x00FD, 0xF301,
+                0xF002, 0xF93A, 0xB210, 0x0123, 0x2400, 0x00EE, 0x00EE
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test the scroll statements agree with the opcode descriptions in rom
+    fn test_scroll_directions() -> Result<()> {
+        for (statement, description) in [
+            ("scroll-left", "scroll left"),
+            ("scroll-right", "scroll right"),
+            ("scroll-down 1", "scroll down"),
+            ("scroll-up 1", "scroll up"),
+        ] {
+            let opcode = words(&compile(&format!(": main {statement}"))?)[0];
+            let (_, actual) = crate::rom::extension_opcode(opcode).unwrap();
+            assert_eq!(actual, description, "{statement} compiled to {opcode:04X}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test conditions, branches, loops, and calls to labels defined later
+    fn test_control_flow() -> Result<()> {
+        let source = "
+            : main
+                if v0 == 5 then v1 := 1
+                if v0 != v2 then v1 := 2
+                if v3 key then v1 := 3
+                if v3 -key begin
+                    v1 := 4
+                else
+                    v1 := 5
+                end
+                loop
+                    v0 += 1
+                    while v0 != 10
+                    draw
+                again
+                jump main
+            : draw
+                ;
+        ";
+        assert_eq!(
+            words(&compile(source)?),
+            [
+                // 0x200
+                0x4005, 0x6101, 0x5020, 0x6102, 0xE3A1, 0x6103,
+                // 0x20C if v3 -key begin
+                0xE3A1, 0x1214, 0x6104, 0x1216, 0x6105, // 0x216 loop
+                0x7001, 0x400A, 0x1220, 0x2222, 0x1216, // 0x220
+                0x1200, 0x00EE
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test compiled programs run in the emulator
+    fn test_run() -> Result<()> {
+        // Sum 1 to 10 into v1, then store it with bcd
+        let source = "
+            : main
+                v0 := 0
+                v1 := 0
+                loop
+                    v0 += 1
+                    v1 += v0
+                    while v0 != 10
+                again
+                i := result
+                bcd v1
+                load v2
+            : done
+                jump done
+            : result
+                0 0 0
+        ";
+        let program = compile(source)?;
+        let test_config = EmulatorConfig {
+            synchronous_timers: true,
+            ..EmulatorConfig::default()
+        };
+        let mut emulator = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        emulator.load_rom(&program)?;
+        for _ in 0..100 {
+            emulator.step()?;
+        }
+        // 55, as three digits
+        assert_eq!(
+            [
+                emulator.register(0x0)?,
+                emulator.register(0x1)?,
+                emulator.register(0x2)?
+            ],
+            [0, 5, 5]
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test mistakes and unsupported features are errors naming their line
+    fn test_errors() {
+        for (source, line, message) in [
+            (": main\n  jump nowhere", 2, "Undefined name \"nowhere\""),
+            (": main\n\n  :macro twice { }", 3, ":macro isn't supported"),
+            (": main\n  if v0 < 3 then v1 := 2", 2, "isn't supported"),
+            (": main\n  loop\n  v0 += 1", 2, "loop without an again"),
+            (": main\n  if v0 == 1 begin", 2, "without an end"),
+            (": main\n  vg := 1", 2, "Unknown register \"vg\""),
+            (": main\n  v0 := 256", 2, "out of range"),
+            (": main\n: main", 2, "already defined"),
+            (": main\n  sprite v0 v1", 2, "Unexpected end of file"),
+            (": main\n  again", 2, "again without a loop"),
+        ] {
+            let err = compile(source).unwrap_err().to_string();
+            assert!(
+                err.starts_with(&format!("Line {line}: ")),
+                "{source:?}: {err}"
+            );
+            assert!(err.contains(message), "{source:?}: {err}");
+        }
+        let err = compile(": start\n  jump start").unwrap_err().to_string();
+        assert!(err.contains("no main label"), "{err}");
+    }
+
+    #[test]
+    /// Test telling Octo source from ROMs
+    fn test_is_octo_source() {
+        assert!(is_octo_source(Path::new("games/pong.8o")));
+        assert!(is_octo_source(Path::new("PONG.8O")));
+        assert!(!is_octo_source(Path::new("pong.ch8")));
+        assert!(!is_octo_source(Path::new("8o")));
+    }
+}
@@ -0,0 +1,105 @@
+/// Why the emulator is paused, the user pausing it and the window losing the
+/// focus are tracked separately so each only undoes its own pause
+///
+/// The emulator is paused while either applies, so regaining the focus doesn't
+/// resume a game the user paused themselves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PauseState {
+    /// Paused by the user (the pause hotkey or the menu)
+    manual: bool,
+    /// Paused because the window lost the focus
+    unfocused: bool,
+}
+
+impl PauseState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the emulator is paused, for any reason
+    pub fn is_paused(&self) -> bool {
+        self.manual || self.unfocused
+    }
+
+    /// Whether the user paused the emulator
+    pub fn is_manual(&self) -> bool {
+        self.manual
+    }
+
+    /// Whether the emulator is paused because the window lost the focus
+    pub fn is_unfocused(&self) -> bool {
+        self.unfocused
+    }
+
+    /// Pause (or resume) at the user's request, returning whether
+    /// [PauseState::is_paused] changed
+    pub fn set_manual(&mut self, paused: bool) -> bool {
+        let before = self.is_paused();
+        self.manual = paused;
+        before != self.is_paused()
+    }
+
+    /// Record whether the window has the focus, returning whether
+    /// [PauseState::is_paused] changed
+    pub fn set_focused(&mut self, focused: bool) -> bool {
+        let before = self.is_paused();
+        self.unfocused = !focused;
+        before != self.is_paused()
+    }
+}
+
+#[cfg(test)]
+mod test_pause {
+    use super::*;
+
+    #[test]
+    /// Test losing and regaining the focus pauses and resumes
+    fn test_focus_pause() {
+        let mut pause = PauseState::new();
+        assert!(!pause.is_paused());
+        assert!(pause.set_focused(false));
+        assert!(pause.is_paused() && pause.is_unfocused() && !pause.is_manual());
+        // Still unfocused, nothing changes
+        assert!(!pause.set_focused(false));
+        assert!(pause.set_focused(true));
+        assert!(!pause.is_paused());
+        assert!(!pause.set_focused(true));
+    }
+
+    #[test]
+    /// Test regaining the focus doesn't resume a manual pause
+    fn test_manual_then_focus_loss() {
+        let mut pause = PauseState::new();
+        assert!(pause.set_manual(true));
+        // Already paused, so losing the focus changes nothing
+        assert!(!pause.set_focused(false));
+        assert!(!pause.set_focused(true));
+        assert!(pause.is_paused() && pause.is_manual());
+        // Only resuming manually resumes
+        assert!(pause.set_manual(false));
+        assert!(!pause.is_paused());
+    }
+
+    #[test]
+    /// Test pausing manually while unfocused, and resuming manually while
+    /// still unfocused
+    fn test_focus_loss_then_manual() {
+        let mut pause = PauseState::new();
+        assert!(pause.set_focused(false));
+        // Pausing on top of the focus pause changes nothing yet
+        assert!(!pause.set_manual(true));
+        // The manual pause outlasts the focus pause
+        assert!(!pause.set_focused(true));
+        assert!(pause.is_paused());
+        assert!(pause.set_manual(false));
+        assert!(!pause.is_paused());
+
+        // Resuming manually while unfocused stays paused until the focus returns
+        pause.set_focused(false);
+        pause.set_manual(true);
+        assert!(!pause.set_manual(false));
+        assert!(pause.is_paused() && pause.is_unfocused());
+        assert!(pause.set_focused(true));
+        assert!(!pause.is_paused());
+    }
+}
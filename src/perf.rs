@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+/// Shortest time between updates of the FPS / IPS counter, so it stays readable
+pub const PERF_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Format the FPS / IPS counter, e.g. `60 FPS  700 IPS` or `60 FPS  12.5k IPS`
+pub fn format_perf(fps: f64, ips: f64) -> String {
+    let ips = if ips >= 1_000_000.0 {
+        format!("{:.2}M", ips / 1_000_000.0)
+    } else if ips >= 10_000.0 {
+        format!("{:.1}k", ips / 1_000.0)
+    } else {
+        format!("{ips:.0}")
+    };
+    format!("{fps:.0} FPS  {ips} IPS")
+}
+
+/// Measures the frames drawn and instructions executed per second, for the FPS /
+/// IPS counter, updating at most once every [PERF_UPDATE_INTERVAL]
+#[derive(Debug, Clone, Default)]
+pub struct PerfCounter {
+    /// When the rates were last measured, and the totals at that time
+    /// (time, frames, instructions)
+    last: Option<(Instant, u64, u64)>,
+    /// Text of the counter, None until the first measurement
+    text: Option<String>,
+}
+
+impl PerfCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update with the total `frames` drawn and `instructions` executed at time
+    /// `now`, returning the new text of the counter if it changed
+    ///
+    /// The FPS is `reported_fps` (as measured by the frontend) if given,
+    /// otherwise it is measured from `frames`. The first update only starts
+    /// measuring.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        frames: u64,
+        instructions: u64,
+        reported_fps: Option<f32>,
+    ) -> Option<&str> {
+        let Some((last_time, last_frames, last_instructions)) = self.last else {
+            self.last = Some((now, frames, instructions));
+            return None;
+        };
+        let elapsed = now.saturating_duration_since(last_time);
+        if elapsed < PERF_UPDATE_INTERVAL {
+            return None;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let fps = match reported_fps {
+            Some(fps) => fps as f64,
+            None => frames.saturating_sub(last_frames) as f64 / seconds,
+        };
+        let ips = instructions.saturating_sub(last_instructions) as f64 / seconds;
+        self.last = Some((now, frames, instructions));
+        self.text = Some(format_perf(fps, ips));
+        self.text.as_deref()
+    }
+
+    /// Text of the counter, None before the first measurement
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    /// Start measuring again from scratch (e.g. after the counter was hidden)
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod test_perf {
+    use super::*;
+
+    #[test]
+    /// Test formatting the counter
+    fn test_format_perf() {
+        assert_eq!(format_perf(60.0, 700.0), "60 FPS  700 IPS");
+        assert_eq!(format_perf(59.6, 699.5), "60 FPS  700 IPS");
+        assert_eq!(format_perf(30.0, 9_999.0), "30 FPS  9999 IPS");
+        assert_eq!(format_perf(60.0, 12_500.0), "60 FPS  12.5k IPS");
+        assert_eq!(format_perf(0.0, 1_000_000.0), "0 FPS  1.00M IPS");
+    }
+
+    #[test]
+    /// Test the counter only updates once per interval, measuring the rates
+    /// since the last update
+    fn test_update_throttling() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut counter = PerfCounter::new();
+        // The first update starts measuring
+        assert_eq!(counter.update(start, 0, 0, None), None);
+        assert_eq!(counter.text(), None);
+        // Too soon
+        assert_eq!(counter.update(ms(100), 6, 70, None), None);
+        assert_eq!(counter.update(ms(499), 30, 349, None), None);
+
+        assert_eq!(
+            counter.update(ms(500), 30, 350, None),
+            Some("60 FPS  700 IPS")
+        );
+        // The next update measures from the last one
+        assert_eq!(counter.update(ms(900), 40, 1_000, None), None);
+        assert_eq!(counter.text(), Some("60 FPS  700 IPS"));
+        assert_eq!(
+            counter.update(ms(1500), 45, 1_350, Some(58.7)),
+            Some("59 FPS  1000 IPS")
+        );
+
+        counter.reset();
+        assert_eq!(counter.text(), None);
+        assert_eq!(counter.update(ms(2000), 50, 2_000, None), None);
+    }
+}
@@ -0,0 +1,318 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use log::{error, info};
+
+use crate::emulator::{Emulator, RunLimits, RunOutcome};
+use crate::library::scan;
+use crate::rom;
+
+/// The ROMs shown in attract mode, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playlist {
+    roms: Vec<PathBuf>,
+}
+
+impl Playlist {
+    /// A playlist of `roms`, which can't be empty
+    pub fn new(roms: Vec<PathBuf>) -> Result<Self> {
+        if roms.is_empty() {
+            bail!("The playlist has no ROMs in it");
+        }
+        Ok(Self { roms })
+    }
+
+    /// A playlist of the ROMs in a directory (see [scan]), or listed in a file
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            let roms = scan(path)?.into_iter().map(|entry| entry.path).collect();
+            Self::new(roms).with_context(|| format!("Finding ROMs in {}", path.display()))
+        } else {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("Reading playlist {}", path.display()))?;
+            Self::parse(&contents, path.parent())
+                .with_context(|| format!("Reading playlist {}", path.display()))
+        }
+    }
+
+    /// A playlist from a list of ROMs, one path per line (relative to `base_dir`),
+    /// ignoring blank lines and comments starting with `#`
+    pub fn parse(contents: &str, base_dir: Option<&Path>) -> Result<Self> {
+        let roms = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match base_dir {
+                Some(base_dir) => base_dir.join(line),
+                None => PathBuf::from(line),
+            })
+            .collect();
+        Self::new(roms)
+    }
+
+    pub fn roms(&self) -> &[PathBuf] {
+        &self.roms
+    }
+}
+
+/// What attract mode needs from the emulator, so it can be driven without one
+pub trait AttractRunner {
+    /// Load the ROM at `path` in place of the current one, and reset
+    fn load(&mut self, path: &Path) -> Result<()>;
+    /// Run the loaded ROM until one of the `limits` is reached
+    fn run(&mut self, limits: RunLimits) -> Result<RunOutcome>;
+}
+
+impl AttractRunner for Emulator<'_> {
+    fn load(&mut self, path: &Path) -> Result<()> {
+        self.replace_rom(rom::open(path)?, path)
+    }
+
+    fn run(&mut self, limits: RunLimits) -> Result<RunOutcome> {
+        self.run_for(limits)
+    }
+}
+
+/// Show each ROM of `playlist` for `each` (less if it halts), over and over,
+/// until a keypad key is pressed, then carry on running that ROM normally
+///
+/// The time is taken from `now`. A ROM which fails to load or crashes is logged
+/// and skipped, and the show only ends with an error if every ROM fails in a row.
+/// Returns how the final run ended, e.g. stopped by closing the window.
+pub fn run_attract(
+    runner: &mut impl AttractRunner,
+    playlist: &Playlist,
+    each: Duration,
+    mut now: impl FnMut() -> Instant,
+) -> Result<RunOutcome> {
+    let mut failures = 0;
+    for rom in playlist.roms().iter().cycle() {
+        if failures >= playlist.roms().len() {
+            bail!("Every ROM in the playlist failed");
+        }
+        info!("Attract mode showing {rom:?}");
+        if let Err(err) = runner.load(rom) {
+            error!("Skipping {rom:?}: {err:#}");
+            failures += 1;
+            continue;
+        }
+        let started = now();
+        loop {
+            let remaining = each.saturating_sub(now().saturating_duration_since(started));
+            if remaining.is_zero() {
+                failures = 0;
+                break;
+            }
+            let limits = RunLimits {
+                max_duration: Some(remaining),
+                stop_on_key: true,
+                ..RunLimits::default()
+            };
+            match runner.run(limits) {
+                // Check the time again, the run may have stopped a little early
+                Ok(RunOutcome::LimitReached) => {}
+                Ok(RunOutcome::Halted) => {
+                    info!("{rom:?} halted, moving on");
+                    failures = 0;
+                    break;
+                }
+                Ok(RunOutcome::KeyPressed) => {
+                    info!("Taking over {rom:?}");
+                    return runner.run(RunLimits::default());
+                }
+                Ok(outcome @ (RunOutcome::Stopped | RunOutcome::TimedOut)) => return Ok(outcome),
+                Err(err) => {
+                    error!("Skipping {rom:?}, it crashed: {err:#}");
+                    failures += 1;
+                    break;
+                }
+            }
+        }
+    }
+    unreachable!("The playlist is never empty, so cycling it never ends")
+}
+
+#[cfg(test)]
+mod test_playlist {
+    use super::*;
+    use anyhow::anyhow;
+    use std::cell::Cell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    /// Runner following a script of outcomes, advancing a fake clock by the
+    /// length of each run
+    struct ScriptedRunner {
+        /// Outcome of each run, with how long it took
+        script: VecDeque<(Result<RunOutcome>, Duration)>,
+        /// ROMs which fail to load
+        unloadable: Vec<PathBuf>,
+        /// Each ROM loaded, and the limits of each run
+        loaded: Vec<PathBuf>,
+        runs: Vec<RunLimits>,
+        clock: Rc<Cell<Instant>>,
+    }
+
+    impl ScriptedRunner {
+        fn new(script: Vec<(Result<RunOutcome>, Duration)>, clock: Rc<Cell<Instant>>) -> Self {
+            Self {
+                script: script.into(),
+                unloadable: Vec::new(),
+                loaded: Vec::new(),
+                runs: Vec::new(),
+                clock,
+            }
+        }
+    }
+
+    impl AttractRunner for ScriptedRunner {
+        fn load(&mut self, path: &Path) -> Result<()> {
+            if self.unloadable.iter().any(|rom| rom == path) {
+                bail!("Can't load {path:?}");
+            }
+            self.loaded.push(path.to_path_buf());
+            Ok(())
+        }
+
+        fn run(&mut self, limits: RunLimits) -> Result<RunOutcome> {
+            self.runs.push(limits);
+            let (outcome, took) = self
+                .script
+                .pop_front()
+                .unwrap_or((Ok(RunOutcome::Stopped), Duration::ZERO));
+            self.clock.set(self.clock.get() + took);
+            outcome
+        }
+    }
+
+    /// A playlist of `names`
+    fn playlist(names: &[&str]) -> Playlist {
+        Playlist::new(names.iter().map(PathBuf::from).collect()).unwrap()
+    }
+
+    /// Run attract mode with `runner`, showing each ROM for a minute
+    fn run(runner: &mut ScriptedRunner, playlist: &Playlist) -> Result<RunOutcome> {
+        let clock = runner.clock.clone();
+        run_attract(runner, playlist, Duration::from_secs(60), || clock.get())
+    }
+
+    #[test]
+    /// Test cycling through the ROMs until a key takes over
+    fn test_cycle_and_take_over() -> Result<()> {
+        let clock = Rc::new(Cell::new(Instant::now()));
+        let minute = Duration::from_secs(60);
+        let mut runner = ScriptedRunner::new(
+            vec![
+                (Ok(RunOutcome::LimitReached), minute),
+                // Stopping a little early runs again for the rest of the time
+                (Ok(RunOutcome::LimitReached), Duration::from_secs(59)),
+                (Ok(RunOutcome::LimitReached), Duration::from_secs(1)),
+                // Halting moves straight on
+                (Ok(RunOutcome::Halted), Duration::from_secs(5)),
+                (Ok(RunOutcome::LimitReached), minute),
+                (Ok(RunOutcome::KeyPressed), Duration::from_secs(10)),
+                // The game carries on until the window is closed
+                (Ok(RunOutcome::Stopped), Duration::from_secs(600)),
+            ],
+            clock,
+        );
+        let outcome = run(&mut runner, &playlist(&["a.ch8", "b.ch8", "c.ch8"]))?;
+        assert_eq!(outcome, RunOutcome::Stopped);
+        assert_eq!(
+            runner.loaded,
+            ["a.ch8", "b.ch8", "c.ch8", "a.ch8", "b.ch8"].map(PathBuf::from)
+        );
+        let limits: Vec<_> = runner
+            .runs
+            .iter()
+            .map(|limits| limits.max_duration)
+            .collect();
+        assert_eq!(
+            limits,
+            [
+                Some(minute),
+                Some(minute),
+                Some(Duration::from_secs(1)),
+                Some(minute),
+                Some(minute),
+                Some(minute),
+                None,
+            ]
+        );
+        // The shown games stop on a key, the taken over one doesn't
+        assert!(runner.runs[..6].iter().all(|limits| limits.stop_on_key));
+        assert_eq!(runner.runs[6], RunLimits::default());
+        Ok(())
+    }
+
+    #[test]
+    /// Test that ROMs which fail are skipped, and the show ends if they all do
+    fn test_skip_failures() -> Result<()> {
+        let clock = Rc::new(Cell::new(Instant::now()));
+        let mut runner = ScriptedRunner::new(
+            vec![
+                (Err(anyhow!("Unknown opcode")), Duration::from_secs(2)),
+                (Ok(RunOutcome::LimitReached), Duration::from_secs(60)),
+                (Ok(RunOutcome::Stopped), Duration::ZERO),
+            ],
+            clock.clone(),
+        );
+        runner.unloadable.push(PathBuf::from("missing.ch8"));
+        let outcome = run(
+            &mut runner,
+            &playlist(&["bad.ch8", "missing.ch8", "good.ch8"]),
+        )?;
+        assert_eq!(outcome, RunOutcome::Stopped);
+        assert_eq!(
+            runner.loaded,
+            ["bad.ch8", "good.ch8", "bad.ch8"].map(PathBuf::from)
+        );
+
+        // Every ROM failing in a row ends the show, rather than spinning forever
+        let mut runner = ScriptedRunner::new(
+            vec![
+                (Err(anyhow!("Unknown opcode")), Duration::ZERO),
+                (Err(anyhow!("Stack overflow")), Duration::ZERO),
+            ],
+            clock,
+        );
+        runner.unloadable.push(PathBuf::from("missing.ch8"));
+        let err = run(&mut runner, &playlist(&["a.ch8", "missing.ch8", "b.ch8"])).unwrap_err();
+        assert!(err.to_string().contains("Every ROM"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    /// Test reading playlists from a list file and a directory
+    fn test_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let list = dir.path().join("cabinet.txt");
+        std::fs::write(
+            &list,
+            "# Favourites\npong.ch8\n\n  games/tetris.ch8  \n/abs/car.ch8\n",
+        )?;
+        assert_eq!(
+            Playlist::load(&list)?.roms(),
+            [
+                dir.path().join("pong.ch8"),
+                dir.path().join("games/tetris.ch8"),
+                PathBuf::from("/abs/car.ch8"),
+            ]
+        );
+        std::fs::write(&list, "# Nothing yet\n")?;
+        assert!(Playlist::load(&list).is_err());
+
+        let roms = dir.path().join("roms");
+        std::fs::create_dir(&roms)?;
+        assert!(Playlist::load(&roms).is_err());
+        std::fs::write(roms.join("b.ch8"), [0x12, 0x00])?;
+        std::fs::write(roms.join("a.ch8"), [0x12, 0x00])?;
+        std::fs::write(roms.join("notes.txt"), "not a ROM")?;
+        assert_eq!(
+            Playlist::load(&roms)?.roms(),
+            [roms.join("a.ch8"), roms.join("b.ch8")]
+        );
+        Ok(())
+    }
+}
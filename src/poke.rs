@@ -0,0 +1,214 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::emulator::{Emulator, MEMORY_SIZE};
+use crate::memory_layout::{parse_address, parse_number};
+
+/// What a [Poke] sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PokeTarget {
+    /// The byte of memory at an address
+    Memory(usize),
+    /// One of the general purpose registers V0-VF
+    Register(u8),
+    /// The index register
+    Index,
+    /// The program counter, where execution starts
+    ProgramCounter,
+    DelayTimer,
+    SoundTimer,
+}
+
+impl PokeTarget {
+    /// Parse the part of a poke before the `=`, e.g. `mem:0x3F0`, `reg:V3`, or `I`
+    fn parse(target: &str) -> Result<Self> {
+        if let Some(address) = target.strip_prefix("mem:") {
+            return Ok(Self::Memory(parse_address(address)?));
+        }
+        let register = target.strip_prefix("reg:").unwrap_or(target);
+        Ok(match register.to_ascii_uppercase().as_str() {
+            "I" => Self::Index,
+            "PC" => Self::ProgramCounter,
+            "DT" => Self::DelayTimer,
+            "ST" => Self::SoundTimer,
+            name => match name.strip_prefix('V').map(|x| u8::from_str_radix(x, 16)) {
+                Some(Ok(x)) if x <= 0xF && name.len() == 2 => Self::Register(x),
+                _ => bail!(
+                    "Unknown target {target:?}, expected mem:ADDRESS, a register (V0-VF, I, or \
+                     PC), or a timer (DT or ST)"
+                ),
+            },
+        })
+    }
+
+    /// Largest value the target can be set to
+    fn max_value(&self) -> usize {
+        match self {
+            Self::Memory(_) | Self::Register(_) | Self::DelayTimer | Self::SoundTimer => {
+                u8::MAX.into()
+            }
+            Self::Index => MEMORY_SIZE - 1,
+            // Leaving room for the two bytes of the instruction
+            Self::ProgramCounter => MEMORY_SIZE - 2,
+        }
+    }
+}
+
+/// A value to set in memory or a register before the program starts, given as
+/// `TARGET=VALUE` on the command line (e.g. `mem:0x3F0=0x1A` or `reg:V3=7`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Poke {
+    pub target: PokeTarget,
+    pub value: usize,
+    /// The poke as it was given, for errors
+    spec: String,
+}
+
+impl FromStr for Poke {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let parse = || -> Result<Self> {
+            let Some((target, value)) = spec.split_once('=') else {
+                bail!("Expected TARGET=VALUE, e.g. mem:0x3F0=0x1A or reg:V3=7");
+            };
+            let target = PokeTarget::parse(target.trim())?;
+            let value = parse_number(value.trim())?;
+            if value > target.max_value() {
+                bail!(
+                    "{value:#x} is too large, at most {:#x} fits",
+                    target.max_value()
+                );
+            }
+            Ok(Self {
+                target,
+                value,
+                spec: spec.to_string(),
+            })
+        };
+        // The reason is part of the message, as clap only shows the outermost one
+        parse().map_err(|err| anyhow!("Invalid poke {spec:?}: {err:#}"))
+    }
+}
+
+impl fmt::Display for Poke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spec)
+    }
+}
+
+impl Poke {
+    /// Set the value in `emulator`
+    pub fn apply(&self, emulator: &mut Emulator) -> Result<()> {
+        // Values are checked to fit when parsed
+        let byte = self.value as u8;
+        match self.target {
+            PokeTarget::Memory(address) => emulator.poke_memory(address, byte),
+            PokeTarget::Register(register) => emulator.set_register(register, byte),
+            PokeTarget::Index => emulator.set_index_register(self.value as u16),
+            PokeTarget::ProgramCounter => emulator.set_program_counter(self.value),
+            PokeTarget::DelayTimer => {
+                emulator.set_delay_timer(byte);
+                Ok(())
+            }
+            PokeTarget::SoundTimer => {
+                emulator.set_sound_timer(byte);
+                Ok(())
+            }
+        }
+        .with_context(|| format!("Failed to apply poke {:?}", self.spec))
+    }
+}
+
+#[cfg(test)]
+mod test_poke {
+    use super::*;
+    use crate::config::EmulatorConfig;
+    use crate::noop_frontend::NoOpFrontend;
+
+    #[test]
+    /// Test parsing each kind of poke
+    fn test_parse() -> Result<()> {
+        for (spec, target, value) in [
+            ("mem:0x3F0=0x1A", PokeTarget::Memory(0x3F0), 0x1A),
+            ("mem:1008=26", PokeTarget::Memory(0x3F0), 0x1A),
+            ("reg:V3=7", PokeTarget::Register(0x3), 7),
+            ("vf=0xff", PokeTarget::Register(0xF), 0xFF),
+            ("I=0x300", PokeTarget::Index, 0x300),
+            ("reg:pc=0x400", PokeTarget::ProgramCounter, 0x400),
+            ("DT=60", PokeTarget::DelayTimer, 60),
+            ("ST = 0x10", PokeTarget::SoundTimer, 0x10),
+        ] {
+            let poke: Poke = spec.parse()?;
+            assert_eq!((poke.target, poke.value), (target, value), "{spec}");
+            assert_eq!(poke.to_string(), spec);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test malformed and out of range pokes are errors quoting the poke
+    fn test_parse_errors() {
+        for spec in [
+            "",
+            "mem:0x3F0",
+            "mem:0x1000=1",
+            "mem:zero=1",
+            "mem:0x3F0=0x100",
+            "reg:V3=",
+            "reg:VG=1",
+            "reg:V10=1",
+            "V=1",
+            "X=1",
+            "I=0x1000",
+            "PC=0xFFF",
+            "DT=256",
+            "V3=-1",
+        ] {
+            let err = spec.parse::<Poke>().unwrap_err();
+            assert!(
+                format!("{err:#}").contains(&format!("{spec:?}")),
+                "{spec}: {err:#}"
+            );
+        }
+    }
+
+    #[test]
+    /// Test applying pokes, in order, including moving where execution starts
+    fn test_apply() -> Result<()> {
+        let test_config = EmulatorConfig {
+            synchronous_timers: true,
+            ..EmulatorConfig::default()
+        };
+        let mut emulator = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+        // V0 = V0 + 1 at 0x200, V0 = V0 + 2 at 0x202
+        emulator.load_rom(&[0x70, 0x01, 0x70, 0x02])?;
+        for spec in [
+            "mem:0x3F0=0x1A",
+            "V0=0x10",
+            "reg:V3=7",
+            "V3=8",
+            "I=0x300",
+            "DT=60",
+            "ST=2",
+            "PC=0x202",
+        ] {
+            spec.parse::<Poke>()?.apply(&mut emulator)?;
+        }
+        assert_eq!(emulator.memory()[0x3F0], 0x1A);
+        assert_eq!(emulator.register(0x3)?, 8);
+        assert_eq!(emulator.index_register(), 0x300);
+        assert_eq!(emulator.delay_timer(), 60);
+        assert_eq!(emulator.sound_timer(), 2);
+        assert_eq!(emulator.program_counter(), 0x202);
+        // Execution starts at the new address, skipping the first instruction
+        emulator.step()?;
+        assert_eq!(emulator.register(0x0)?, 0x12);
+        assert_eq!(emulator.program_counter(), 0x204);
+        Ok(())
+    }
+}
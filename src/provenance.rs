@@ -0,0 +1,430 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde::Serialize;
+
+use crate::config::{EmulatorConfig, unknown_keys};
+use crate::rom::{Platform, quirk_dependencies, sha1_hex};
+use crate::rom_database::RomDatabase;
+
+/// Where the value of a setting came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigSource {
+    /// The built in default
+    Default,
+    /// The config file
+    File,
+    /// The profile the ROM database recommends for the ROM (with `--auto-profile`)
+    RomDatabase,
+    /// A `[roms]` entry matching the ROM
+    RomOverride,
+    /// A command line argument
+    Cli,
+}
+
+impl ConfigSource {
+    /// Name of the source, as shown in annotations
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigSource::Default => "default",
+            ConfigSource::File => "file",
+            ConfigSource::RomDatabase => "rom-database",
+            ConfigSource::RomOverride => "rom-override",
+            ConfigSource::Cli => "cli",
+        }
+    }
+}
+
+/// A configuration built up in layers, remembering which layer each setting came from
+///
+/// Sources are tracked per setting, and per entry for the table settings
+/// (e.g. `keymap.0` for the override of keypad key 0).
+pub struct ResolvedConfig {
+    /// The resolved configuration
+    pub config: EmulatorConfig,
+    /// Sources of every setting (or table entry) which isn't a default
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl ResolvedConfig {
+    /// Start from a config loaded from a file, where `file` holds the settings
+    /// actually present in the file (anything else is a default, and unknown
+    /// settings are ignored)
+    pub fn from_file(config: EmulatorConfig, file: &toml::Table) -> Self {
+        let mut resolved = Self {
+            config,
+            sources: BTreeMap::new(),
+        };
+        let mut file = file.clone();
+        for key in unknown_keys(&file) {
+            file.remove(&key);
+        }
+        resolved.mark(&file, ConfigSource::File);
+        resolved
+    }
+
+    /// Layer `settings` over the current configuration (see [EmulatorConfig::merge])
+    pub fn apply(&mut self, settings: &toml::Table, source: ConfigSource) -> Result<()> {
+        self.config = self.config.merge(settings)?;
+        self.mark(settings, source);
+        Ok(())
+    }
+
+    /// Layer the quirk settings of the platform `database` recommends for `rom`
+    /// (see [Platform::quirks]), returning the platform
+    ///
+    /// ROMs the database doesn't know keep the configured settings.
+    pub fn apply_auto_profile(
+        &mut self,
+        database: &RomDatabase,
+        rom: &[u8],
+    ) -> Result<Option<Platform>> {
+        let rom_hash = sha1_hex(rom);
+        let Some(platform) = database.platform(&rom_hash) else {
+            info!("ROM {rom_hash} has no profile in the ROM database, using the configured one");
+            return Ok(None);
+        };
+        let quirks = platform.quirks();
+        info!(
+            "Using the {} profile from the ROM database: {}",
+            platform.name(),
+            quirks
+                .iter()
+                .map(|(key, value)| format!("{key} = {value}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        self.apply(&quirks, ConfigSource::RomDatabase)?;
+        Ok(Some(platform))
+    }
+
+    /// Layer the `[roms]` entry best matching the ROM at `rom_path` with contents `rom`
+    pub fn apply_rom_overrides(&mut self, rom_path: &Path, rom: &[u8]) -> Result<()> {
+        let rom_hash = sha1_hex(rom);
+        debug!("ROM SHA-1 hash: {rom_hash}");
+        let Some((key, settings)) = self.config.find_rom_override(rom_path, &rom_hash)? else {
+            debug!("No [roms] entry matches {rom_path:?}");
+            return Ok(());
+        };
+        info!(
+            "Applying [roms] entry {key:?} to {rom_path:?}, overriding: {}",
+            settings.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+        let (key, settings) = (key.to_string(), settings.clone());
+        self.apply(&settings, ConfigSource::RomOverride)
+            .with_context(|| format!("Applying [roms] entry {key:?}"))
+    }
+
+    /// A warning listing the quirk settings in effect for the instructions in
+    /// `rom` which depend on them, None if it has none or the settings came from
+    /// the ROM database
+    ///
+    /// Found by static analysis, so it's only a hint of why a ROM misbehaves.
+    pub fn quirk_warning(&self, rom: &[u8]) -> Option<String> {
+        if self
+            .sources
+            .values()
+            .any(|source| *source == ConfigSource::RomDatabase)
+        {
+            return None;
+        }
+        let dependencies = quirk_dependencies(rom);
+        if dependencies.is_empty() {
+            return None;
+        }
+        let mut warning = "The ROM uses instructions which behave differently between CHIP-8 \
+            interpreters, if it misbehaves try --auto-profile or changing these settings:"
+            .to_string();
+        for (instructions, (quirk, address)) in dependencies {
+            let _ = write!(warning, "\n  {instructions} at {address:#05x}: ");
+            match quirk {
+                Some(quirk) => {
+                    let (setting, value) = (quirk.name(), quirk.is_enabled(&self.config));
+                    let flag = setting.replace('_', "-");
+                    let _ = write!(warning, "{setting} = {value}, try --{flag} {}", !value);
+                }
+                None => warning.push_str("always sets VF when I passes 0xFFF"),
+            }
+        }
+        Some(warning)
+    }
+
+    /// Where the setting `key` (or table entry, e.g. `keymap.0`) came from
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .copied()
+            .unwrap_or(ConfigSource::Default)
+    }
+
+    /// Record `source` as the source of every setting in `settings`
+    fn mark(&mut self, settings: &toml::Table, source: ConfigSource) {
+        for (key, value) in settings {
+            match value {
+                toml::Value::Table(entries) => {
+                    for entry in entries.keys() {
+                        self.sources.insert(format!("{key}.{entry}"), source);
+                    }
+                }
+                _ => {
+                    self.sources.insert(key.clone(), source);
+                }
+            }
+        }
+    }
+
+    /// The configuration as TOML, with a comment after each setting saying where
+    /// it came from
+    pub fn to_annotated_toml(&self) -> Result<String> {
+        let table =
+            toml::Table::try_from(&self.config).context("Converting configuration into TOML")?;
+        let mut output = String::new();
+        // Plain settings have to come before any tables
+        for (key, value) in table.iter().filter(|(_, value)| !value.is_table()) {
+            writeln!(output, "{key} = {value} # {}", self.source(key).name())?;
+        }
+        for (key, value) in table.iter().filter(|(_, value)| value.is_table()) {
+            let Some(entries) = value.as_table() else {
+                continue;
+            };
+            writeln!(output, "\n[{key}]")?;
+            for (entry, value) in entries {
+                // Nested tables (the [roms] entries) are written as inline tables
+                let source = self.source(&format!("{key}.{entry}")).name();
+                writeln!(output, "{} = {value} # {source}", toml_key(entry))?;
+            }
+        }
+        Ok(output)
+    }
+
+    /// The configuration as JSON, as an object with the resolved `config`, and the
+    /// `sources` of every setting which isn't a default
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&serde_json::json!({
+            "config": self.config,
+            "sources": self.sources,
+        }))
+        .context("Converting configuration into JSON")
+    }
+}
+
+/// Quote a TOML key if it isn't a valid bare key
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod test_provenance {
+    use super::*;
+
+    /// Build a layered config, with a file, a ROM override, and CLI settings
+    fn layered() -> Result<ResolvedConfig> {
+        let file: toml::Table = toml::from_str(
+            r#"
+            instructions_per_second = 900
+            foreground = "green"
+
+            [keymap]
+            0 = "SPACE"
+
+            [roms."game*.ch8"]
+            instructions_per_second = 1500
+            background = "navy"
+            keymap = { F = "ENTER" }
+            "#,
+        )?;
+        // Loading the file, [roms] can't be merged so is set separately
+        let mut settings = file.clone();
+        let roms = settings.remove("roms").unwrap().try_into()?;
+        let config = EmulatorConfig {
+            roms,
+            ..EmulatorConfig::default().merge(&settings)?
+        };
+        let mut resolved = ResolvedConfig::from_file(config, &file);
+        resolved.apply_rom_overrides(Path::new("game1.ch8"), &[0x00, 0xE0])?;
+        resolved.apply(
+            &toml::from_str("background = \"white\"")?,
+            ConfigSource::Cli,
+        )?;
+        Ok(resolved)
+    }
+
+    #[test]
+    /// Test the source of each setting is tracked through the layers
+    fn test_sources() -> Result<()> {
+        let resolved = layered()?;
+
+        assert_eq!(resolved.config.foreground, "green");
+        assert_eq!(resolved.source("foreground"), ConfigSource::File);
+        assert_eq!(resolved.config.instructions_per_second, 1500);
+        assert_eq!(
+            resolved.source("instructions_per_second"),
+            ConfigSource::RomOverride
+        );
+        assert_eq!(resolved.config.background, "white");
+        assert_eq!(resolved.source("background"), ConfigSource::Cli);
+        assert_eq!(resolved.source("shift_use_vy"), ConfigSource::Default);
+        // Table entries are tracked individually
+        assert_eq!(resolved.source("keymap.0"), ConfigSource::File);
+        assert_eq!(resolved.source("keymap.F"), ConfigSource::RomOverride);
+        assert_eq!(resolved.source("roms.game*.ch8"), ConfigSource::File);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test the profile from the ROM database is layered under `[roms]` entries,
+    /// and unknown ROMs keep the configured settings
+    fn test_auto_profile() -> Result<()> {
+        let rom = [0x00, 0xE0];
+        let database = RomDatabase::parse(&format!(
+            r#"[{{"title": "Fake", "roms": {{"{}": {{"platforms": ["superchip"]}}}}}}]"#,
+            sha1_hex(&rom)
+        ))?;
+        let file: toml::Table = toml::from_str(
+            "shift_use_vy = true
+wrap_x = true",
+        )?;
+        let config = EmulatorConfig {
+            roms: toml::from_str("[\"fake.ch8\"]\nwrap_x = true")?,
+            ..EmulatorConfig::from_table(&file)?
+        };
+        let mut resolved = ResolvedConfig::from_file(config, &file);
+
+        assert_eq!(
+            resolved.apply_auto_profile(&database, &rom)?,
+            Some(Platform::SuperChip)
+        );
+        assert!(!resolved.config.shift_use_vy);
+        assert!(!resolved.config.jump_offset_use_v0);
+        assert_eq!(resolved.config.stack_depth, 16);
+        assert_eq!(resolved.source("shift_use_vy"), ConfigSource::RomDatabase);
+        resolved.apply_rom_overrides(Path::new("fake.ch8"), &rom)?;
+        assert!(resolved.config.wrap_x && !resolved.config.wrap_y);
+        assert_eq!(resolved.source("wrap_x"), ConfigSource::RomOverride);
+
+        let before = resolved.config.clone();
+        assert_eq!(resolved.apply_auto_profile(&database, &[0x12, 0x00])?, None);
+        assert_eq!(resolved.config, before);
+        Ok(())
+    }
+
+    #[test]
+    /// Test which ROMs are warned about depending on quirks, and that the
+    /// settings from the ROM database aren't
+    fn test_quirk_warning() -> Result<()> {
+        let file = toml::from_str("jump_offset_use_v0 = false")?;
+        let mut resolved = ResolvedConfig::from_file(EmulatorConfig::from_table(&file)?, &file);
+        assert_eq!(resolved.quirk_warning(&[0x00, 0xE0, 0x12, 0x02]), None);
+
+        // Shift, then a computed jump
+        let warning = resolved.quirk_warning(&[0x80, 0x16, 0xB3, 0x00]).unwrap();
+        assert!(
+            warning.contains(
+                "8XY6/8XYE (shift) at 0x200: shift_use_vy = true, try --shift-use-vy false"
+            ),
+            "{warning}"
+        );
+        assert!(
+            warning.contains(
+                "BNNN (jump with offset) at 0x202: jump_offset_use_v0 = false, \
+                try --jump-offset-use-v0 true"
+            ),
+            "{warning}"
+        );
+        assert!(
+            !warning.contains("FX55") && !warning.contains("FX1E"),
+            "{warning}"
+        );
+        let warning = resolved.quirk_warning(&[0xF1, 0x1E, 0xF2, 0x65]).unwrap();
+        assert!(
+            warning.contains("store_memory_update_index = false"),
+            "{warning}"
+        );
+        assert!(
+            warning.contains("FX1E (add to I) at 0x200: always sets VF"),
+            "{warning}"
+        );
+
+        let rom = [0x80, 0x16, 0x12, 0x02];
+        let database = RomDatabase::parse(&format!(
+            r#"[{{"title": "Fake", "roms": {{"{}": {{"platforms": ["superchip"]}}}}}}]"#,
+            sha1_hex(&rom)
+        ))?;
+        resolved.apply_auto_profile(&database, &rom)?;
+        assert_eq!(resolved.quirk_warning(&rom), None);
+        Ok(())
+    }
+
+    #[test]
+    /// Test that unknown settings in the file aren't tracked
+    fn test_unknown_settings() -> Result<()> {
+        let file = toml::from_str("foregound = \"navy\"\nbackground = \"teal\"")?;
+        let resolved = ResolvedConfig::from_file(EmulatorConfig::from_table(&file)?, &file);
+        assert_eq!(resolved.source("background"), ConfigSource::File);
+        assert!(!resolved.sources.contains_key("foregound"));
+        Ok(())
+    }
+
+    #[test]
+    /// Test the annotated TOML output
+    fn test_annotated_toml() -> Result<()> {
+        let toml = layered()?.to_annotated_toml()?;
+        let lines: Vec<&str> = toml.lines().collect();
+
+        assert!(lines.contains(&"instructions_per_second = 1500 # rom-override"));
+        assert!(lines.contains(&"foreground = \"green\" # file"));
+        assert!(lines.contains(&"background = \"white\" # cli"));
+        assert!(lines.contains(&"shift_use_vy = true # default"));
+        assert!(lines.contains(&"[keymap]"));
+        assert!(lines.contains(&"0 = \"SPACE\" # file"));
+        assert!(lines.contains(&"F = \"ENTER\" # rom-override"));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("\"game*.ch8\" = {") && line.ends_with("# file"))
+        );
+
+        // Stripping the annotations gives back the same configuration
+        let parsed: EmulatorConfig = toml::from_str(&toml)?;
+        assert_eq!(parsed.instructions_per_second, 1500);
+        assert_eq!(parsed.keymap.len(), 2);
+        assert_eq!(parsed.roms.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test the JSON output
+    fn test_json() -> Result<()> {
+        let json: serde_json::Value = serde_json::from_str(&layered()?.to_json()?)?;
+        assert_eq!(json["config"]["background"], "white");
+        assert_eq!(json["sources"]["background"], "cli");
+        assert_eq!(json["sources"]["instructions_per_second"], "rom-override");
+        assert_eq!(json["sources"]["keymap.0"], "file");
+        // Defaults aren't listed
+        assert!(json["sources"].get("shift_use_vy").is_none());
+        Ok(())
+    }
+
+    #[test]
+    /// Test quoting keys
+    fn test_toml_key() {
+        assert_eq!(toml_key("keymap_preset"), "keymap_preset");
+        assert_eq!(toml_key("0"), "0");
+        assert_eq!(toml_key("pong*.ch8"), "\"pong*.ch8\"");
+        assert_eq!(toml_key(""), "\"\"");
+    }
+}
@@ -0,0 +1,343 @@
+//! Python bindings (with the `pyo3` feature), for scripting the emulator
+//!
+//! Built as a Python module with maturin (see pyproject.toml), e.g.
+//! `maturin develop`, then:
+//!
+//! ```python
+//! import emul8rs
+//! emulator = emul8rs.Emulator(open("game.ch8", "rb").read(), {"wrap_x": True})
+//! emulator.set_key(5, True)
+//! emulator.run_frame()
+//! print(emulator.display[0], emulator.registers, emulator.memory[0x200])
+//! state = emulator.save_state()
+//! emulator.load_state(state)
+//! ```
+
+use anyhow::{Context, Result};
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString};
+
+use crate::config::EmulatorConfig;
+use crate::emulator::Emulator;
+use crate::host_frontend::{HostFrontend, HostKeys};
+use crate::save_state::SaveState;
+
+/// Convert an error from the emulator into a Python exception
+fn runtime_error(err: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{err:#}"))
+}
+
+/// Convert a Python value from a config dict into the TOML value the config
+/// file would have
+fn toml_value(value: &Bound<'_, PyAny>) -> PyResult<toml::Value> {
+    // Checked before ints, as Python's bools are ints
+    if value.is_instance_of::<PyBool>() {
+        return Ok(toml::Value::Boolean(value.extract()?));
+    }
+    if value.is_instance_of::<PyInt>() {
+        return Ok(toml::Value::Integer(value.extract()?));
+    }
+    if value.is_instance_of::<PyFloat>() {
+        return Ok(toml::Value::Float(value.extract()?));
+    }
+    if value.is_instance_of::<PyString>() {
+        return Ok(toml::Value::String(value.extract()?));
+    }
+    if let Ok(dict) = value.cast::<PyDict>() {
+        return Ok(toml::Value::Table(toml_table(dict)?));
+    }
+    if let Ok(list) = value.cast::<PyList>() {
+        return list
+            .iter()
+            .map(|item| toml_value(&item))
+            .collect::<PyResult<_>>()
+            .map(toml::Value::Array);
+    }
+    Err(PyTypeError::new_err(format!(
+        "Unsupported config value {value}, expected a bool, number, string, list, or dict"
+    )))
+}
+
+/// Convert a config dict into the table the config file would have
+fn toml_table(dict: &Bound<'_, PyDict>) -> PyResult<toml::Table> {
+    dict.iter()
+        .map(|(key, value)| Ok((key.extract::<String>()?, toml_value(&value)?)))
+        .collect()
+}
+
+/// The emulator, driven from Python
+///
+/// Timers count down once per `run_frame` (synchronous_timers defaults to
+/// true), so runs are repeatable.
+#[pyclass(unsendable, name = "Emulator")]
+pub struct PyEmulator {
+    emulator: Emulator<'static>,
+    /// Keys down, shared with the frontend
+    keys: HostKeys,
+}
+
+#[pymethods]
+impl PyEmulator {
+    /// Create an emulator running `rom`, with the settings in `config` (named
+    /// as in the config file) on top of the defaults
+    #[new]
+    #[pyo3(signature = (rom, config = None))]
+    fn new(rom: &[u8], config: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let mut table = match config {
+            Some(config) => toml_table(config)?,
+            None => toml::Table::new(),
+        };
+        table
+            .entry("synchronous_timers")
+            .or_insert(toml::Value::Boolean(true));
+        let config = EmulatorConfig::from_table(&table).map_err(runtime_error)?;
+        if let Err(errors) = config.validate() {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return Err(PyValueError::new_err(format!(
+                "Invalid configuration: {}",
+                errors.join(", ")
+            )));
+        }
+        let keys = HostKeys::default();
+        let frontend = HostFrontend::new(keys.clone());
+        let mut emulator = Emulator::new(Box::new(frontend), config).map_err(runtime_error)?;
+        emulator.load_rom(rom).map_err(runtime_error)?;
+        Ok(Self { emulator, keys })
+    }
+
+    /// Execute a single instruction
+    fn step(&mut self) -> PyResult<()> {
+        self.emulator.step().map_err(runtime_error)
+    }
+
+    /// Execute up to `count` instructions, returning how many ran (fewer if
+    /// the program halted)
+    fn step_n(&mut self, count: usize) -> PyResult<usize> {
+        self.emulator.step_n(count).map_err(runtime_error)
+    }
+
+    /// Execute one 60Hz frame's worth of instructions
+    fn run_frame(&mut self) -> PyResult<()> {
+        self.emulator.run_frame().map_err(runtime_error)
+    }
+
+    /// Press (`down`) or release keypad key `key` (0 to 15)
+    fn set_key(&mut self, key: usize, down: bool) -> PyResult<()> {
+        self.keys
+            .set(key, down)
+            .map_err(|err| PyValueError::new_err(format!("{err:#}")))
+    }
+
+    /// The display, as rows of palette indices (0 for off, 1 for on on a single
+    /// plane display), e.g. for `numpy.array`
+    ///
+    /// There are 32 rows of 64, or 64 rows of 128 in high resolution.
+    ///
+    /// With `double_buffer` this is the last complete frame.
+    #[getter]
+    fn display(&self) -> PyResult<Vec<Vec<usize>>> {
+        let display = self.emulator.presented_display();
+        (0..display.rows())
+            .map(|row| {
+                (0..display.cols())
+                    .map(|col| display.color_index(row, col))
+                    .collect::<Result<_>>()
+            })
+            .collect::<Result<_>>()
+            .map_err(runtime_error)
+    }
+
+    /// The registers V0 to VF
+    #[getter]
+    fn registers<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let registers = (0..16)
+            .map(|register| self.emulator.register(register))
+            .collect::<Result<Vec<u8>>>()
+            .map_err(runtime_error)?;
+        PyList::new(py, registers)
+    }
+
+    /// A copy of the whole 4KB of memory
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.emulator.memory())
+    }
+
+    /// The index register
+    #[getter]
+    fn index(&self) -> u16 {
+        self.emulator.index_register()
+    }
+
+    /// Address of the next instruction
+    #[getter]
+    fn pc(&self) -> usize {
+        self.emulator.program_counter()
+    }
+
+    /// Number of instructions executed
+    #[getter]
+    fn cycles(&self) -> u64 {
+        self.emulator.cycles()
+    }
+
+    /// Whether the program has exited
+    #[getter]
+    fn halted(&self) -> bool {
+        self.emulator.is_halted()
+    }
+
+    /// The state of the running program (as JSON bytes), to carry on from with
+    /// `load_state`
+    fn save_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let json = self
+            .emulator
+            .save_state()
+            .to_json()
+            .map_err(runtime_error)?;
+        Ok(PyBytes::new(py, json.as_bytes()))
+    }
+
+    /// Carry on from a state from `save_state` of the same ROM
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        let state = std::str::from_utf8(state)
+            .context("Save state isn't text")
+            .and_then(SaveState::from_json)
+            .map_err(|err| PyValueError::new_err(format!("{err:#}")))?;
+        if state.rom_sha1 != self.emulator.rom_sha1() {
+            return Err(PyValueError::new_err("Save state is from a different ROM"));
+        }
+        self.emulator.load_state(&state).map_err(runtime_error)
+    }
+}
+
+/// The `emul8rs` Python module
+#[pymodule]
+fn emul8rs(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyEmulator>()
+}
+
+#[cfg(test)]
+mod test_python {
+    use super::*;
+    use pyo3::ffi::c_str;
+
+    /// Run the Python `code` with the `Emulator` class available
+    fn run_python(code: &std::ffi::CStr) -> PyResult<()> {
+        Python::initialize();
+        Python::attach(|py| {
+            let globals = PyDict::new(py);
+            globals.set_item("Emulator", py.get_type::<PyEmulator>())?;
+            py.run(code, Some(&globals), None)
+        })
+    }
+
+    #[test]
+    /// Test running a ROM from Python, with a key pressed
+    fn test_run_from_python() -> PyResult<()> {
+        run_python(c_str!(
+            r#"
+# I = 0x50 (font 0), V0 = 5, V1 = 1, skip if key V0, V2 = 9, draw, exit
+rom = bytes([0xA0, 0x50, 0x60, 0x05, 0x61, 0x01, 0xE0, 0x9E, 0x62, 0x09,
+             0xD1, 0x15, 0x00, 0xFD])
+emulator = Emulator(rom, {"instructions_per_second": 600, "keymap": {"5": "W"}})
+emulator.set_key(5, True)
+emulator.run_frame()
+assert emulator.halted
+assert emulator.registers[:3] == [5, 1, 0], emulator.registers
+assert emulator.cycles == 6
+assert emulator.memory[0x200] == 0xA0
+assert emulator.index == 0x50
+display = emulator.display
+assert len(display) == 32 and len(display[0]) == 64
+# The top of the 0 in the font is 0xF0, drawn at (1, 1)
+assert display[1][:6] == [0, 1, 1, 1, 1, 0], display[1]
+
+# With double_buffer the display is the last complete frame, so the sprite
+# only shows once the frame it was drawn in ends
+emulator = Emulator(rom, {"keymap": {"5": "W"}, "double_buffer": True})
+emulator.set_key(5, True)
+emulator.step_n(6)
+assert emulator.halted
+assert emulator.display[1][:6] == [0] * 6, emulator.display[1]
+emulator.run_frame()
+assert emulator.display[1][:6] == [0, 1, 1, 1, 1, 0], emulator.display[1]
+
+# High resolution (00FF) doubles both sides of the display
+emulator = Emulator(bytes([0x00, 0xFF, 0x00, 0xFD]))
+emulator.run_frame()
+assert len(emulator.display) == 64 and len(emulator.display[0]) == 128
+"#
+        ))
+    }
+
+    #[test]
+    /// Test stepping, and releasing keys
+    fn test_step_from_python() -> PyResult<()> {
+        run_python(c_str!(
+            r#"
+rom = bytes([0x60, 0x03, 0xE0, 0xA1, 0x00, 0xFD, 0x61, 0x07, 0x00, 0xFD])
+emulator = Emulator(rom)
+emulator.set_key(3, True)
+emulator.set_key(3, False)
+assert emulator.step_n(100) == 4
+assert emulator.registers[1] == 7
+assert emulator.pc == 0x20A
+"#
+        ))
+    }
+
+    #[test]
+    /// Test saving a state and carrying on from it later
+    fn test_save_state_from_python() -> PyResult<()> {
+        run_python(c_str!(
+            r#"
+# V0 += 1, loop
+rom = bytes([0x70, 0x01, 0x12, 0x00])
+emulator = Emulator(rom)
+emulator.step_n(3)
+state = emulator.save_state()
+assert isinstance(state, bytes)
+emulator.step_n(4)
+assert emulator.registers[0] == 4
+emulator.load_state(state)
+assert emulator.registers[0] == 2
+assert emulator.pc == 0x202
+
+# States only load into the ROM they were saved from, and must be valid
+try:
+    Emulator(bytes([0x00, 0xE0])).load_state(state)
+    raise AssertionError("loaded a state from another ROM")
+except ValueError as err:
+    assert "different ROM" in str(err)
+try:
+    emulator.load_state(b"not a state")
+    raise AssertionError("loaded a broken state")
+except ValueError:
+    pass
+"#
+        ))
+    }
+
+    #[test]
+    /// Test bad arguments raise Python exceptions
+    fn test_errors_from_python() -> PyResult<()> {
+        run_python(c_str!(
+            r#"
+def raises(kind, call):
+    try:
+        call()
+    except kind as err:
+        return str(err)
+    raise AssertionError(f"{call} didn't raise {kind}")
+
+assert "stack_depth" in raises(ValueError, lambda: Emulator(b"", {"stack_depth": 0}))
+raises(TypeError, lambda: Emulator(b"", {"wrap_x": None}))
+raises(ValueError, lambda: Emulator(b"").set_key(16, True))
+emulator = Emulator(bytes([0xFF, 0xFF]))
+assert "FFFF" in raises(RuntimeError, emulator.step)
+"#
+        ))
+    }
+}
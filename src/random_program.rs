@@ -0,0 +1,172 @@
+use anyhow::{Result, bail};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::emulator::Emulator;
+use crate::instruction::Instruction;
+use crate::rom::{MAX_CHIP8_ROM_SIZE, PROGRAM_START};
+
+/// Highest address I is pointed at before reading or writing memory, leaving
+/// room below the program for the 16 bytes FX55 can write
+const SCRATCH_END: u16 = 0x1C0;
+
+/// Instructions which neither jump nor touch memory through I
+fn random_simple_instruction(rng: &mut StdRng) -> Instruction {
+    let x = rng.random_range(0..16);
+    let y = rng.random_range(0..16);
+    let nn = rng.random();
+    match rng.random_range(0..12) {
+        0 => Instruction::Clear,
+        1 => Instruction::Set { x, nn },
+        2 => Instruction::Add { x, nn },
+        3 => {
+            let operations = [
+                Instruction::SetRegister { x, y },
+                Instruction::Or { x, y },
+                Instruction::And { x, y },
+                Instruction::Xor { x, y },
+                Instruction::AddRegister { x, y },
+                Instruction::Subtract { x, y },
+                Instruction::ShiftRight { x, y },
+                Instruction::SubtractReversed { x, y },
+                Instruction::ShiftLeft { x, y },
+            ];
+            operations[rng.random_range(0..operations.len())]
+        }
+        4 => Instruction::GetDelayTimer { x },
+        5 => Instruction::SetDelayTimer { x },
+        6 => Instruction::SetSoundTimer { x },
+        7 => Instruction::FontCharacter { x },
+        8 => Instruction::LowResolution,
+        // The arithmetic is most of what real programs run
+        _ => Instruction::AddRegister { x, y },
+    }
+}
+
+/// Instructions which skip the next one
+fn random_skip_instruction(rng: &mut StdRng) -> Instruction {
+    let x = rng.random_range(0..16);
+    let y = rng.random_range(0..16);
+    let nn = rng.random();
+    match rng.random_range(0..6) {
+        0 => Instruction::SkipIfEqual { x, nn },
+        1 => Instruction::SkipIfNotEqual { x, nn },
+        2 => Instruction::SkipIfRegistersEqual { x, y },
+        3 => Instruction::SkipIfRegistersNotEqual { x, y },
+        4 => Instruction::SkipIfKey { x },
+        _ => Instruction::SkipIfNotKey { x },
+    }
+}
+
+/// Instructions which read or write memory at I
+fn random_memory_instruction(rng: &mut StdRng) -> Instruction {
+    let x = rng.random_range(0..16);
+    let y = rng.random_range(0..16);
+    match rng.random_range(0..4) {
+        0 => Instruction::Draw {
+            x,
+            y,
+            n: rng.random_range(1..16),
+        },
+        1 => Instruction::BinaryCodedDecimal { x },
+        2 => Instruction::StoreRegisters { x },
+        _ => Instruction::LoadRegisters { x },
+    }
+}
+
+/// A deterministic stream of `count` valid instructions from `seed`, as bytes
+///
+/// The program runs straight through without jumping, so executing it from the
+/// start reaches the end. To keep it that way:
+/// - every skip is followed by an instruction which is safe to skip
+/// - every instruction using I is just after one setting I to scratch memory
+///   below the program, so the program never overwrites itself
+/// - nothing waits for a key, and nothing is random (CXNN is left out), so
+///   each run executes the same instructions
+pub fn random_program(seed: u64, count: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut instructions = Vec::with_capacity(count);
+    while instructions.len() < count {
+        let room = count - instructions.len();
+        match rng.random_range(0..4) {
+            0 if room >= 2 => {
+                instructions.push(random_skip_instruction(&mut rng));
+                instructions.push(random_simple_instruction(&mut rng));
+            }
+            1 if room >= 2 => {
+                instructions.push(Instruction::SetIndex {
+                    nnn: rng.random_range(0..SCRATCH_END),
+                });
+                instructions.push(random_memory_instruction(&mut rng));
+            }
+            _ => instructions.push(random_simple_instruction(&mut rng)),
+        }
+    }
+    instructions
+        .iter()
+        .flat_map(|instruction| instruction.encode().to_be_bytes())
+        .collect()
+}
+
+/// Load `count` random instructions from `seed` (see [random_program]) into
+/// `emulator`, returning the address just past the end
+///
+/// Running from the start until the program counter reaches that address
+/// executes the whole program, e.g. for measuring how fast instructions are
+/// dispatched apart from any real ROM.
+pub fn fill_random_program(emulator: &mut Emulator, seed: u64, count: usize) -> Result<usize> {
+    let max_count = MAX_CHIP8_ROM_SIZE / 2;
+    if count > max_count {
+        bail!("At most {max_count} instructions fit in memory, not {count}");
+    }
+    let program = random_program(seed, count);
+    emulator.load_rom(&program)?;
+    emulator.set_program_counter(PROGRAM_START)?;
+    Ok(PROGRAM_START + program.len())
+}
+
+#[cfg(test)]
+mod test_random_program {
+    use super::*;
+    use crate::config::EmulatorConfig;
+    use crate::noop_frontend::NoOpFrontend;
+
+    #[test]
+    /// Test the same seed gives the same program, and other seeds other programs
+    fn test_reproducible() {
+        assert_eq!(random_program(7, 500), random_program(7, 500));
+        assert_ne!(random_program(7, 500), random_program(8, 500));
+        assert_eq!(random_program(7, 500).len(), 1000);
+        assert_eq!(random_program(7, 0).len(), 0);
+        assert_eq!(random_program(7, 1).len(), 2);
+    }
+
+    #[test]
+    /// Test random programs run to the end without failing, executing the same
+    /// instructions every time
+    fn test_runs_to_end() -> Result<()> {
+        let run = |seed| -> Result<u64> {
+            let test_config = EmulatorConfig {
+                synchronous_timers: true,
+                ..EmulatorConfig::default()
+            };
+            let mut emulator = Emulator::new(Box::new(NoOpFrontend::new()), test_config)?;
+            let end = fill_random_program(&mut emulator, seed, MAX_CHIP8_ROM_SIZE / 2)?;
+            let program = emulator.memory()[PROGRAM_START..end].to_vec();
+            while emulator.program_counter() < end {
+                emulator.step()?;
+            }
+            // The program didn't overwrite itself
+            assert_eq!(emulator.memory()[PROGRAM_START..end], program);
+            Ok(emulator.cycles())
+        };
+        for seed in 0..20 {
+            let cycles = run(seed)?;
+            assert!(cycles > 1000, "{seed}: {cycles}");
+            assert_eq!(run(seed)?, cycles);
+        }
+        let mut emulator = Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        assert!(fill_random_program(&mut emulator, 0, MAX_CHIP8_ROM_SIZE / 2 + 1).is_err());
+        Ok(())
+    }
+}
@@ -1,163 +1,848 @@
-use log::debug;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
 use raylib::{
     RaylibHandle, RaylibThread,
-    audio::{RaylibAudio, Sound, Wave},
+    audio::{Music, RaylibAudio},
     color::Color,
-    ffi::KeyboardKey,
-    prelude::RaylibDraw,
+    ffi::{ConfigFlags, KeyboardKey, MouseButton},
+    prelude::{Image, RaylibDraw, RaylibTextureModeExt, Rectangle, RenderTexture2D, Vector2},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
 
+use emul8rs::color::parse_color;
 use emul8rs::config;
-use emul8rs::display::{DISPLAY_COLS, DISPLAY_ROWS, Display};
-use emul8rs::frontend::Frontend;
-// Keymap
-// mapped from
-// 1  2  3  4
-// Q  W  E  R
-// A  S  D  F
-// Z  X  C  V
-// to
-// 1  2  3  C
-// 4  5  6  D
-// 7  8  9  E
-// A  0  B  F
-const KEYMAP: [KeyboardKey; 16] = [
-    KeyboardKey::KEY_X,
-    KeyboardKey::KEY_ONE,
-    KeyboardKey::KEY_TWO,
-    KeyboardKey::KEY_THREE,
-    KeyboardKey::KEY_Q,
-    KeyboardKey::KEY_W,
-    KeyboardKey::KEY_E,
-    KeyboardKey::KEY_A,
-    KeyboardKey::KEY_S,
-    KeyboardKey::KEY_D,
-    KeyboardKey::KEY_Z,
-    KeyboardKey::KEY_C,
-    KeyboardKey::KEY_FOUR,
-    KeyboardKey::KEY_R,
-    KeyboardKey::KEY_F,
-    KeyboardKey::KEY_V,
-];
+use emul8rs::debug_grid::{GridLabel, grid_font_size, grid_labels, grid_lines};
+use emul8rs::display::{DISPLAY_COLS, DISPLAY_ROWS, Display, DisplayTransform};
+use emul8rs::frame_blend::FrameBlender;
+use emul8rs::frontend::{Frontend, FrontendCapabilities};
+use emul8rs::hotkeys::{HotkeyAction, HotkeyMap};
+use emul8rs::keymap::{KEYPAD_SIZE, Keymap, KeypadLabel};
+use emul8rs::menu::{MenuInput, PauseMenu};
+use emul8rs::sound::{Beep, Envelope, Waveform};
+use emul8rs::split_view::SplitLayout;
 
-// Sound file to include
-const BEEP_SOUND: &[u8; 63128] = include_bytes!("../resources/sound/beep.wav");
+/// Convert a canonical key name (see [emul8rs::keymap::KEY_NAMES]) into a raylib key
+fn raylib_key(name: &str) -> Option<KeyboardKey> {
+    let key = match name {
+        "0" => KeyboardKey::KEY_ZERO,
+        "1" => KeyboardKey::KEY_ONE,
+        "2" => KeyboardKey::KEY_TWO,
+        "3" => KeyboardKey::KEY_THREE,
+        "4" => KeyboardKey::KEY_FOUR,
+        "5" => KeyboardKey::KEY_FIVE,
+        "6" => KeyboardKey::KEY_SIX,
+        "7" => KeyboardKey::KEY_SEVEN,
+        "8" => KeyboardKey::KEY_EIGHT,
+        "9" => KeyboardKey::KEY_NINE,
+        "A" => KeyboardKey::KEY_A,
+        "B" => KeyboardKey::KEY_B,
+        "C" => KeyboardKey::KEY_C,
+        "D" => KeyboardKey::KEY_D,
+        "E" => KeyboardKey::KEY_E,
+        "F" => KeyboardKey::KEY_F,
+        "G" => KeyboardKey::KEY_G,
+        "H" => KeyboardKey::KEY_H,
+        "I" => KeyboardKey::KEY_I,
+        "J" => KeyboardKey::KEY_J,
+        "K" => KeyboardKey::KEY_K,
+        "L" => KeyboardKey::KEY_L,
+        "M" => KeyboardKey::KEY_M,
+        "N" => KeyboardKey::KEY_N,
+        "O" => KeyboardKey::KEY_O,
+        "P" => KeyboardKey::KEY_P,
+        "Q" => KeyboardKey::KEY_Q,
+        "R" => KeyboardKey::KEY_R,
+        "S" => KeyboardKey::KEY_S,
+        "T" => KeyboardKey::KEY_T,
+        "U" => KeyboardKey::KEY_U,
+        "V" => KeyboardKey::KEY_V,
+        "W" => KeyboardKey::KEY_W,
+        "X" => KeyboardKey::KEY_X,
+        "Y" => KeyboardKey::KEY_Y,
+        "Z" => KeyboardKey::KEY_Z,
+        "APOSTROPHE" => KeyboardKey::KEY_APOSTROPHE,
+        "COMMA" => KeyboardKey::KEY_COMMA,
+        "MINUS" => KeyboardKey::KEY_MINUS,
+        "PERIOD" => KeyboardKey::KEY_PERIOD,
+        "SLASH" => KeyboardKey::KEY_SLASH,
+        "SEMICOLON" => KeyboardKey::KEY_SEMICOLON,
+        "EQUAL" => KeyboardKey::KEY_EQUAL,
+        "LEFT_BRACKET" => KeyboardKey::KEY_LEFT_BRACKET,
+        "BACKSLASH" => KeyboardKey::KEY_BACKSLASH,
+        "RIGHT_BRACKET" => KeyboardKey::KEY_RIGHT_BRACKET,
+        "GRAVE" => KeyboardKey::KEY_GRAVE,
+        "SPACE" => KeyboardKey::KEY_SPACE,
+        "ESCAPE" => KeyboardKey::KEY_ESCAPE,
+        "ENTER" => KeyboardKey::KEY_ENTER,
+        "TAB" => KeyboardKey::KEY_TAB,
+        "BACKSPACE" => KeyboardKey::KEY_BACKSPACE,
+        "INSERT" => KeyboardKey::KEY_INSERT,
+        "DELETE" => KeyboardKey::KEY_DELETE,
+        "RIGHT" => KeyboardKey::KEY_RIGHT,
+        "LEFT" => KeyboardKey::KEY_LEFT,
+        "DOWN" => KeyboardKey::KEY_DOWN,
+        "UP" => KeyboardKey::KEY_UP,
+        "PAGE_UP" => KeyboardKey::KEY_PAGE_UP,
+        "PAGE_DOWN" => KeyboardKey::KEY_PAGE_DOWN,
+        "HOME" => KeyboardKey::KEY_HOME,
+        "END" => KeyboardKey::KEY_END,
+        "F1" => KeyboardKey::KEY_F1,
+        "F2" => KeyboardKey::KEY_F2,
+        "F3" => KeyboardKey::KEY_F3,
+        "F4" => KeyboardKey::KEY_F4,
+        "F5" => KeyboardKey::KEY_F5,
+        "F6" => KeyboardKey::KEY_F6,
+        "F7" => KeyboardKey::KEY_F7,
+        "F8" => KeyboardKey::KEY_F8,
+        "F9" => KeyboardKey::KEY_F9,
+        "F10" => KeyboardKey::KEY_F10,
+        "F11" => KeyboardKey::KEY_F11,
+        "F12" => KeyboardKey::KEY_F12,
+        "KP_0" => KeyboardKey::KEY_KP_0,
+        "KP_1" => KeyboardKey::KEY_KP_1,
+        "KP_2" => KeyboardKey::KEY_KP_2,
+        "KP_3" => KeyboardKey::KEY_KP_3,
+        "KP_4" => KeyboardKey::KEY_KP_4,
+        "KP_5" => KeyboardKey::KEY_KP_5,
+        "KP_6" => KeyboardKey::KEY_KP_6,
+        "KP_7" => KeyboardKey::KEY_KP_7,
+        "KP_8" => KeyboardKey::KEY_KP_8,
+        "KP_9" => KeyboardKey::KEY_KP_9,
+        _ => return None,
+    };
+    Some(key)
+}
 
-// Window size defaults
-const WINDOW_WIDTH: i32 = 640;
-const WINDOW_HEIGHT: i32 = 320;
+/// The beep played by the frontend, which does nothing if audio is disabled
+///
+/// The beep is loaded as a music stream so it loops seamlessly for as long
+/// as the sound timer is running. Its volume follows an [Envelope], so it fades
+/// in and out rather than popping.
+struct RaylibSound<'a> {
+    music: Option<Music<'a>>,
+    playing_sound: bool,
+    /// Volume of the beep once it has faded in
+    volume: f32,
+    envelope: Envelope,
+}
+
+impl<'a> RaylibSound<'a> {
+    /// Load the beep described by the config using the raylib audio device
+    ///
+    /// Falls back to the synthesized beep if the configured sound file can't be decoded.
+    fn new(config: &config::EmulatorConfig, audio: &'a RaylibAudio) -> Result<Self> {
+        let beep = Beep::from_config(config)?;
+        let mut music = match audio.new_music_from_memory(&beep.file_type, &beep.bytes) {
+            Ok(music) => music,
+            Err(err) => {
+                warn!(
+                    "Failed to decode sound file {:?} ({err}), using the built in beep instead",
+                    config.sound_file
+                );
+                let waveform = Waveform::from_name(&config.sound_waveform)?;
+                let beep = Beep::synthesized(waveform, config.sound_frequency_hz);
+                audio.new_music_from_memory(&beep.file_type, &beep.bytes)?
+            }
+        };
+        music.looping = true;
+        music.set_volume(0.0);
+        Ok(Self {
+            music: Some(music),
+            playing_sound: false,
+            volume: config.sound_volume,
+            envelope: Envelope::from_config(config),
+        })
+    }
+
+    /// Create a beep that never plays, for when audio is disabled
+    fn disabled() -> Self {
+        Self {
+            music: None,
+            playing_sound: false,
+            volume: 0.0,
+            envelope: Envelope::new(Duration::ZERO),
+        }
+    }
+
+    fn play(&mut self) {
+        if let Some(music) = &self.music {
+            self.envelope.trigger(true, Instant::now());
+            // Still playing if it was fading out, in which case it fades back in
+            if !self.playing_sound {
+                music.play_stream();
+            }
+            self.playing_sound = true;
+            self.step();
+        }
+    }
+
+    /// Start fading the beep out, it stops once silent (see [RaylibSound::step])
+    fn stop(&mut self) {
+        self.envelope.trigger(false, Instant::now());
+        self.step();
+    }
+
+    fn step(&mut self) {
+        let Some(music) = &self.music else {
+            return;
+        };
+        if !self.playing_sound {
+            return;
+        }
+        let now = Instant::now();
+        if self.envelope.is_silent(now) {
+            if music.is_stream_playing() {
+                music.stop_stream();
+            }
+            self.playing_sound = false;
+            return;
+        }
+        music.set_volume(self.volume * self.envelope.gain(now));
+        // Music streams need their buffers refilling regularly to keep playing
+        music.update_stream();
+    }
+}
 
 /// Fontend using the Raylib library
 pub struct RaylibFrontend<'a> {
+    /// The display drawn a texel per pixel, at the display's resolution, then
+    /// scaled up to the window (declared before the handle, so it is unloaded
+    /// before the window closes)
+    display_texture: RenderTexture2D,
     handle: RaylibHandle,
     thread: RaylibThread,
-    // wave: Wave<'a>,
-    sound: Sound<'a>,
-    playing_sound: bool,
+    sound: RaylibSound<'a>,
     window_width: i32,
     window_height: i32,
     foreground: Color,
     background: Color,
+    /// Color of each combination of lit planes (see
+    /// [config::EmulatorConfig::palette_colors])
+    palette: Vec<Color>,
+    /// The palette [Frontend::cycle_theme] switches to
+    other_palette: Vec<Color>,
+    /// How the display is mirrored or rotated when drawn
+    transform: DisplayTransform,
+    /// Raylib key bound to each keypad key
+    keymap: [KeyboardKey; KEYPAD_SIZE],
+    /// The keymap, for labelling the keypad overlay
+    key_names: Keymap,
+    /// Raylib key bound to each hotkey action (indexed by the action), if any
+    hotkeys: [Option<KeyboardKey>; HotkeyAction::ALL.len()],
+    /// Whether the keypad overlay is being drawn
+    show_overlay: bool,
+    /// Whether the debug grid is being drawn
+    show_grid: bool,
+    /// Blends each frame with the one before, when `frame_blend` is on
+    frame_blend: Option<FrameBlender>,
+    /// Color of the border shown while the beep is sounding, None to not show it
+    sound_indicator: Option<Color>,
+    /// Whether the beep is sounding
+    sound_active: bool,
+    /// The most recent file dropped onto the window, loaded by the menu's Load ROM
+    dropped_rom: Option<PathBuf>,
+    /// Text of the FPS / IPS counter, None to not show it
+    perf_text: Option<String>,
+    /// The enabled cheats, listed in the debug overlay, None if there are none
+    cheats_text: Option<String>,
+}
+
+/// The raylib key bound to each keypad key in `keymap`
+fn raylib_keymap(keymap: &Keymap) -> Result<[KeyboardKey; KEYPAD_SIZE]> {
+    let mut raylib_keymap = [KeyboardKey::KEY_NULL; KEYPAD_SIZE];
+    for (raylib_key_slot, name) in raylib_keymap.iter_mut().zip(keymap.iter()) {
+        *raylib_key_slot =
+            raylib_key(name).ok_or_else(|| anyhow!("Key {name} not supported by raylib"))?;
+    }
+    Ok(raylib_keymap)
 }
 
 impl<'a> RaylibFrontend<'a> {
     /// Create a new raylib frontend struct from a raylib handle
-    pub fn new(config: &config::EmulatorConfig, audio: &'a RaylibAudio) -> Result<Self> {
-        debug!("Creating raylib window");
-        let (handle, thread) = raylib::init()
-            .size(WINDOW_WIDTH, WINDOW_HEIGHT)
+    ///
+    /// If `audio` is None, the frontend is created without sound.
+    pub fn new(
+        config: &config::EmulatorConfig,
+        keymap: &Keymap,
+        hotkeys: &HotkeyMap,
+        audio: Option<&'a RaylibAudio>,
+    ) -> Result<Self> {
+        // Create the colors from the config strings
+        debug!("Creating raylib colors from passed color values");
+        let to_colors = |colors: Vec<(u8, u8, u8)>| -> Vec<Color> {
+            colors
+                .into_iter()
+                .map(|(r, g, b)| Color::new(r, g, b, 255))
+                .collect()
+        };
+        let palette = to_colors(config.palette_colors()?);
+        let other_palette = to_colors(config.inverted_palette_colors()?);
+        // The palette starts with the colors of pixels lit on no planes, then
+        // on just the first
+        let [background, foreground, ..] = palette[..] else {
+            bail!("The palette needs at least two colors");
+        };
+        let (width, height) = config.window_size();
+        debug!("Creating {width}x{height} raylib window");
+        // The builder has no option for it, but the flags set here are combined
+        // with the builder's own when the window is created
+        // SAFETY: only sets a flag read by InitWindow, before any window exists
+        unsafe { raylib::ffi::SetConfigFlags(ConfigFlags::FLAG_WINDOW_HIGHDPI as u32) };
+        let (mut handle, thread) = raylib::init()
+            .size(width as i32, height as i32)
             .title("Emul8rs")
             .build();
+        // Escape opens the menu rather than closing the window
+        handle.set_exit_key(None);
+        handle.set_window_icon(window_icon(foreground, background));
         debug!("Checking actual window size");
-        let window_width = handle.get_screen_width();
-        let window_height = handle.get_screen_height();
+        let (window_width, window_height) = window_logical_size(&handle);
         debug!(
-            "Created window width: {}, height: {}",
-            window_width, window_height
+            "Created window width: {}, height: {} (render size {}x{})",
+            window_width,
+            window_height,
+            handle.get_render_width(),
+            handle.get_render_height()
         );
-        debug!("Loading sound file from memory");
-        let wave: Wave<'a> = audio.new_wave_from_memory(".wav", BEEP_SOUND)?;
-        let sound: Sound<'a> = audio.new_sound_from_wave(&wave)?;
-        // Create the colors form the config hex strings
-        debug!("Creating raylib colors from passed hex values");
-        let foreground = Color::from_hex(&config.foreground)
-            .context("Parsing foreground color from hex string")?;
-        let background = Color::from_hex(&config.background)
-            .context("Parsing backgorund color from hex string")?;
+        let sound = match audio {
+            Some(audio) => RaylibSound::new(config, audio)?,
+            None => {
+                debug!("Audio disabled, sounds will not be played");
+                RaylibSound::disabled()
+            }
+        };
+        let sound_indicator = if config.sound_indicator {
+            let (r, g, b) = parse_color(&config.sound_indicator_color)
+                .context("Parsing sound indicator color")?;
+            Some(Color::new(r, g, b, 255))
+        } else {
+            None
+        };
+        debug!("Converting keymap into raylib keys");
+        let raylib_keymap = raylib_keymap(keymap)?;
+        debug!("Converting hotkeys into raylib keys");
+        let mut raylib_hotkeys = [None; HotkeyAction::ALL.len()];
+        for (action, name) in hotkeys.iter() {
+            raylib_hotkeys[action as usize] = Some(
+                raylib_key(name).ok_or_else(|| anyhow!("Key {name} not supported by raylib"))?,
+            );
+        }
+        let display_texture = display_texture(&mut handle, &thread, DISPLAY_ROWS, DISPLAY_COLS)?;
         debug!("Creating frontend");
         Ok(Self {
+            display_texture,
             handle,
             thread,
-            // wave,
             sound,
-            playing_sound: true,
             window_width,
             window_height,
             foreground,
             background,
+            palette,
+            other_palette,
+            transform: DisplayTransform::from_name(&config.display_transform)?,
+            keymap: raylib_keymap,
+            key_names: keymap.clone(),
+            hotkeys: raylib_hotkeys,
+            show_overlay: config.keypad_overlay,
+            show_grid: config.debug_grid,
+            frame_blend: config
+                .frame_blend
+                .then(|| FrameBlender::new(Instant::now())),
+            sound_indicator,
+            sound_active: false,
+            dropped_rom: None,
+            perf_text: None,
+            cheats_text: None,
         })
     }
 }
 
-impl Frontend for RaylibFrontend<'_> {
-    fn draw(&mut self, display: &Display) -> anyhow::Result<()> {
+/// Draw the keypad in the top right corner of the window, with the physical key
+/// bound to each keypad key, highlighting the keys which are down
+fn draw_keypad_overlay(
+    drawhandle: &mut impl RaylibDraw,
+    labels: &[KeypadLabel],
+    window_width: i32,
+    window_height: i32,
+    foreground: Color,
+    background: Color,
+) {
+    // Each key is a square, with the whole keypad a third of the window height
+    let key_size = (window_width.min(window_height) / 12).max(8);
+    let margin = key_size / 4;
+    let left = window_width - margin - 4 * key_size;
+    drawhandle.draw_rectangle(
+        left - margin,
+        0,
+        4 * key_size + 2 * margin,
+        4 * key_size + 2 * margin,
+        background,
+    );
+    for label in labels {
+        let x = left + label.col as i32 * key_size;
+        let y = margin + label.row as i32 * key_size;
+        let text_color = if label.pressed {
+            drawhandle.draw_rectangle(x, y, key_size, key_size, foreground);
+            background
+        } else {
+            foreground
+        };
+        drawhandle.draw_rectangle_lines(x, y, key_size, key_size, foreground);
+        drawhandle.draw_text(
+            &format!("{:X}", label.keypad_key),
+            x + key_size / 3,
+            y + key_size / 8,
+            key_size / 2,
+            text_color,
+        );
+        drawhandle.draw_text(
+            label.key_name,
+            x + key_size / 10,
+            y + key_size * 2 / 3,
+            (key_size / 5).max(6),
+            text_color,
+        );
+    }
+}
+
+/// A texture to draw a display of `rows` by `cols` pixels into, a texel each
+fn display_texture(
+    handle: &mut RaylibHandle,
+    thread: &RaylibThread,
+    rows: usize,
+    cols: usize,
+) -> Result<RenderTexture2D> {
+    handle
+        .load_render_texture(thread, cols as u32, rows as u32)
+        .with_context(|| format!("Creating the {cols}x{rows} display texture"))
+}
+
+/// Draw faint gridlines every [emul8rs::debug_grid::GRID_SPACING] pixels of a
+/// display `rows` by `cols` pixels, with the coordinate `labels` (each with its
+/// width) on a background
+fn draw_debug_grid(
+    drawhandle: &mut impl RaylibDraw,
+    labels: &[(GridLabel, i32)],
+    (rows, cols): (usize, usize),
+    (cell_width, cell_height): (i32, i32),
+    font_size: i32,
+    foreground: Color,
+    background: Color,
+) {
+    let line_color = Color::new(foreground.r, foreground.g, foreground.b, 64);
+    let width = cell_width * cols as i32;
+    let height = cell_height * rows as i32;
+    for x in grid_lines(cols, cell_width) {
+        drawhandle.draw_line(x, 0, x, height, line_color);
+    }
+    for y in grid_lines(rows, cell_height) {
+        drawhandle.draw_line(0, y, width, y, line_color);
+    }
+    let label_background = Color::new(background.r, background.g, background.b, 160);
+    for (label, text_width) in labels {
+        drawhandle.draw_rectangle(label.x, label.y, *text_width, font_size, label_background);
+        drawhandle.draw_text(&label.text, label.x, label.y, font_size, foreground);
+    }
+}
+
+impl RaylibFrontend<'_> {
+    /// Draw the display, with the keypad overlay and `menu` over it if shown
+    fn draw_frame(&mut self, display: &Display, menu: Option<&PauseMenu>) -> Result<()> {
         // Check window sizing
         if self.handle.is_window_resized() {
-            self.window_width = self.handle.get_screen_width();
-            self.window_height = self.handle.get_screen_height();
+            (self.window_width, self.window_height) = window_logical_size(&self.handle);
         }
+        if let Some(key) = self.hotkeys[HotkeyAction::ToggleOverlay as usize]
+            && self.handle.is_key_pressed(key)
+        {
+            self.show_overlay = !self.show_overlay;
+            debug!("Keypad overlay shown: {}", self.show_overlay);
+        }
+        if let Some(key) = self.hotkeys[HotkeyAction::ToggleGrid as usize]
+            && self.handle.is_key_pressed(key)
+        {
+            self.show_grid = !self.show_grid;
+            debug!("Debug grid shown: {}", self.show_grid);
+        }
+        let keypad_labels = if self.show_overlay {
+            let pressed = self.current_keys()?;
+            self.key_names.keypad_labels(pressed)
+        } else {
+            Vec::new()
+        };
         // Get the sizes of the individual cells
-        let cell_width = self.window_width / (DISPLAY_COLS as i32);
-        let cell_height = self.window_height / (DISPLAY_ROWS as i32);
+        let (rows, cols) = (display.rows(), display.cols());
+        let cell_width = self.window_width / (cols as i32);
+        let cell_height = self.window_height / (rows as i32);
+        let grid_font_size = grid_font_size(cell_width, cell_height);
+        let grid_labels: Vec<(GridLabel, i32)> = if self.show_grid {
+            grid_labels(
+                self.transform,
+                rows,
+                cols,
+                cell_width,
+                cell_height,
+                grid_font_size,
+            )
+            .into_iter()
+            .map(|label| {
+                let width = self.handle.measure_text(&label.text, grid_font_size);
+                (label, width)
+            })
+            .collect()
+        } else {
+            Vec::new()
+        };
+        let perf_font_size = perf_font_size(self.window_height);
+        let perf_width = self
+            .perf_text
+            .as_ref()
+            .map(|text| self.handle.measure_text(text, perf_font_size));
+        let cheats_width = self
+            .cheats_text
+            .as_ref()
+            .filter(|_| self.show_grid)
+            .map(|text| self.handle.measure_text(text, perf_font_size));
+        let background = (self.background.r, self.background.g, self.background.b);
+        let blended = match self.frame_blend {
+            Some(_) => {
+                // The color each pixel would be drawn without blending
+                let frame = (0..rows * cols)
+                    .map(|index| {
+                        Ok(cell_color(&self.palette, display, index)?
+                            .map_or(background, |color| (color.r, color.g, color.b)))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                self.frame_blend
+                    .as_mut()
+                    .map(|blender| blender.blend(&frame, Instant::now()))
+            }
+            None => None,
+        };
+        // Draw the display into its texture, a texel per pixel
+        let mut texture_mode = self
+            .handle
+            .begin_texture_mode(&self.thread, &mut self.display_texture);
+        texture_mode.clear_background(self.background);
+        // Iterate through each cell, and draw it to the texture
+        // NOTE: The display is in row major order
+        for index in 0..rows * cols {
+            // Only draw anything if the cell is lit (or was, when blending)
+            let color = match &blended {
+                Some(blended) if blended[index] != background => {
+                    let (r, g, b) = blended[index];
+                    Color::new(r, g, b, 255)
+                }
+                None => match cell_color(&self.palette, display, index)? {
+                    Some(color) => color,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            // Find which cell is being drawn, and where it goes in the texture
+            let (row, col) = self.transform.apply(index / cols, index % cols, rows, cols);
+            texture_mode.draw_pixel(col as i32, row as i32, color);
+        }
+        drop(texture_mode);
         // Start the drawing
         let mut drawhandle = self.handle.begin_drawing(&self.thread);
-        // Clear to screen and start adding the filled cells
         drawhandle.clear_background(self.background);
-        // Iterate through each cell, and draw it to the screen
-        // NOTE: The display is in row major order
-        let mut row: usize;
-        let mut col: usize;
-
-        for (index, cell) in display.iter_cells().enumerate() {
-            // Only draw anything if the cell is true
-            if *cell {
-                // Find which cell is being drawn
-                row = index / DISPLAY_COLS;
-                col = index % DISPLAY_COLS;
-                // Find the x and y coordinates of the top left corner
-                let x_coord = col as i32 * cell_width;
-                let y_coord = row as i32 * cell_height;
-
-                // Find the
-                drawhandle.draw_rectangle(
-                    x_coord,
-                    y_coord,
-                    cell_width,
-                    cell_height,
-                    self.foreground,
-                );
+        // Scale the texture up to whole window pixels per display pixel, flipped
+        // as render textures are upside down
+        drawhandle.draw_texture_pro(
+            &self.display_texture,
+            Rectangle::new(0.0, 0.0, cols as f32, -(rows as f32)),
+            Rectangle::new(
+                0.0,
+                0.0,
+                (cell_width * cols as i32) as f32,
+                (cell_height * rows as i32) as f32,
+            ),
+            Vector2::new(0.0, 0.0),
+            0.0,
+            Color::WHITE,
+        );
+        if self.show_grid {
+            draw_debug_grid(
+                &mut drawhandle,
+                &grid_labels,
+                (rows, cols),
+                (cell_width, cell_height),
+                grid_font_size,
+                self.foreground,
+                self.background,
+            );
+        }
+        if let (Some(text), Some(width)) = (&self.cheats_text, cheats_width) {
+            draw_cheats_text(
+                &mut drawhandle,
+                text,
+                width,
+                perf_font_size,
+                self.window_height,
+                self.foreground,
+                self.background,
+            );
+        }
+        if let Some(color) = self.sound_indicator
+            && self.sound_active
+        {
+            draw_sound_indicator(
+                &mut drawhandle,
+                self.window_width,
+                self.window_height,
+                color,
+            );
+        }
+        if let (Some(text), Some(width)) = (&self.perf_text, perf_width) {
+            draw_perf_text(
+                &mut drawhandle,
+                text,
+                width,
+                perf_font_size,
+                self.foreground,
+                self.background,
+            );
+        }
+        if self.show_overlay {
+            draw_keypad_overlay(
+                &mut drawhandle,
+                &keypad_labels,
+                self.window_width,
+                self.window_height,
+                self.foreground,
+                self.background,
+            );
+        }
+        if let Some(menu) = menu {
+            draw_menu(
+                &mut drawhandle,
+                menu,
+                self.dropped_rom.is_none(),
+                self.window_width,
+                self.window_height,
+                self.foreground,
+                self.background,
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Frontend for RaylibFrontend<'_> {
+    fn draw(&mut self, display: &Display) -> anyhow::Result<()> {
+        self.draw_frame(display, None)
+    }
+
+    fn capabilities(&self) -> FrontendCapabilities {
+        raylib_capabilities(self.sound.music.is_some())
+    }
+
+    fn is_focused(&mut self) -> bool {
+        self.handle.is_window_focused()
+    }
+
+    fn set_perf_text(&mut self, text: Option<&str>) {
+        self.perf_text = text.map(str::to_string);
+    }
+
+    fn set_cheats_text(&mut self, text: Option<&str>) {
+        self.cheats_text = text.map(str::to_string);
+    }
+
+    fn set_sound_active(&mut self, active: bool) {
+        self.sound_active = active;
+    }
+
+    /// Reload the display texture at the new resolution
+    fn on_resolution_change(&mut self, rows: usize, cols: usize) -> anyhow::Result<()> {
+        debug!("Display switched to {cols}x{rows}");
+        self.display_texture = display_texture(&mut self.handle, &self.thread, rows, cols)?;
+        Ok(())
+    }
+
+    fn has_menu(&self) -> bool {
+        true
+    }
+
+    fn draw_menu(&mut self, display: &Display, menu: &PauseMenu) -> anyhow::Result<()> {
+        if self.handle.is_file_dropped() {
+            let dropped = self.handle.load_dropped_files();
+            if let Some(path) = dropped.paths().first() {
+                debug!("File dropped onto the window: {path}");
+                self.dropped_rom = Some(PathBuf::from(path));
+            }
+        }
+        self.draw_frame(display, Some(menu))
+    }
+
+    fn draw_split(
+        &mut self,
+        displays: [&Display; 2],
+        labels: [&str; 2],
+        errors: [&[String]; 2],
+    ) -> anyhow::Result<()> {
+        if self.handle.is_window_resized() {
+            (self.window_width, self.window_height) = window_logical_size(&self.handle);
+        }
+        let layout = SplitLayout::new(self.window_width, self.window_height);
+        let font_size = (layout.label_height * 3 / 4).max(8);
+        let label_widths = labels.map(|label| self.handle.measure_text(label, font_size));
+        let mut drawhandle = self.handle.begin_drawing(&self.thread);
+        drawhandle.clear_background(self.background);
+        for (half, display) in displays.into_iter().enumerate() {
+            let (x_origin, y_origin) = layout.origin(half);
+            if errors[half].is_empty() {
+                // Each half is drawn at its own resolution
+                let (rows, cols) = (display.rows(), display.cols());
+                let (cell_width, cell_height) = layout.cell_size(rows, cols);
+                for index in 0..rows * cols {
+                    let Some(color) = cell_color(&self.palette, display, index)? else {
+                        continue;
+                    };
+                    let (row, col) = self.transform.apply(index / cols, index % cols, rows, cols);
+                    drawhandle.draw_rectangle(
+                        x_origin + col as i32 * cell_width,
+                        y_origin + row as i32 * cell_height,
+                        cell_width,
+                        cell_height,
+                        color,
+                    );
+                }
+            } else {
+                // The error in place of the display, fitting every line in
+                // the half with a line's gap around them
+                let line_count = errors[half].len() as i32 + 2;
+                let error_font_size =
+                    ((self.window_height - layout.label_height) / line_count).clamp(1, 20);
+                for (index, line) in errors[half].iter().enumerate() {
+                    drawhandle.draw_text(
+                        line,
+                        x_origin + error_font_size,
+                        y_origin + error_font_size * (index as i32 + 1),
+                        error_font_size,
+                        self.foreground,
+                    );
+                }
             }
+            // Centered in the strip above the half
+            drawhandle.draw_text(
+                labels[half],
+                x_origin + (layout.half_width - label_widths[half]) / 2,
+                (layout.label_height - font_size) / 2,
+                font_size,
+                self.foreground,
+            );
+        }
+        // Lines between the halves, and under the labels
+        drawhandle.draw_line(
+            layout.half_width,
+            0,
+            layout.half_width,
+            self.window_height,
+            self.foreground,
+        );
+        drawhandle.draw_line(
+            0,
+            layout.label_height - 1,
+            self.window_width,
+            layout.label_height - 1,
+            self.foreground,
+        );
+        if let Some(color) = self.sound_indicator
+            && self.sound_active
+        {
+            draw_sound_indicator(
+                &mut drawhandle,
+                self.window_width,
+                self.window_height,
+                color,
+            );
         }
         Ok(())
     }
 
+    fn menu_input(&mut self, menu: &PauseMenu) -> anyhow::Result<Option<MenuInput>> {
+        if self.handle.is_key_pressed(KeyboardKey::KEY_UP) {
+            return Ok(Some(MenuInput::Up));
+        }
+        if self.handle.is_key_pressed(KeyboardKey::KEY_DOWN) {
+            return Ok(Some(MenuInput::Down));
+        }
+        if self.handle.is_key_pressed(KeyboardKey::KEY_ENTER)
+            || self.handle.is_key_pressed(KeyboardKey::KEY_KP_ENTER)
+        {
+            return Ok(Some(MenuInput::Activate));
+        }
+        let (left, top, width, item_height) =
+            menu_layout(self.window_width, self.window_height, menu.items().len());
+        let mouse = self.handle.get_mouse_position();
+        let (x, y) = (mouse.x as i32, mouse.y as i32);
+        if x < left || x >= left + width || y < top {
+            return Ok(None);
+        }
+        let index = ((y - top) / item_height) as usize;
+        if self
+            .handle
+            .is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT)
+        {
+            return Ok(Some(MenuInput::Click(index)));
+        }
+        let delta = self.handle.get_mouse_delta();
+        if delta.x != 0.0 || delta.y != 0.0 {
+            return Ok(Some(MenuInput::Hover(index)));
+        }
+        Ok(None)
+    }
+
+    fn cycle_theme(&mut self) -> anyhow::Result<()> {
+        std::mem::swap(&mut self.palette, &mut self.other_palette);
+        self.background = self.palette[0];
+        self.foreground = self.palette[1];
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> anyhow::Result<()> {
+        // Raylib needs a C string, which can't hold NULs
+        let title = title.replace('\0', "");
+        self.handle
+            .set_window_title(&self.thread, &format!("{title} - Emul8rs"));
+        Ok(())
+    }
+
+    fn choose_rom(&mut self) -> anyhow::Result<Option<PathBuf>> {
+        if self.dropped_rom.is_none() {
+            info!("Drop a ROM file onto the window to load it");
+        }
+        Ok(self.dropped_rom.take())
+    }
+
     fn check_key(&mut self, key: u8) -> anyhow::Result<bool> {
-        Ok(self.handle.is_key_down(KEYMAP[key as usize]))
+        // Keys past the keypad are never down
+        Ok(self
+            .keymap
+            .get(key as usize)
+            .is_some_and(|&key| self.handle.is_key_down(key)))
+    }
+
+    fn check_hotkey(&mut self, action: HotkeyAction) -> anyhow::Result<bool> {
+        Ok(match self.hotkeys[action as usize] {
+            Some(key) => self.handle.is_key_pressed(key),
+            None => false,
+        })
     }
 
     fn play_sound(&mut self) -> anyhow::Result<()> {
         self.sound.play();
-        self.playing_sound = true;
         Ok(())
     }
 
     fn stop_sound(&mut self) -> anyhow::Result<()> {
-        if self.sound.is_playing() {
-            self.sound.stop();
-        }
-        self.playing_sound = false;
+        self.sound.stop();
         Ok(())
     }
 
@@ -165,14 +850,351 @@ impl Frontend for RaylibFrontend<'_> {
         self.handle.window_should_close()
     }
 
+    /// Look up the raylib keys straight away, so the next key check uses them
+    fn set_keymap(&mut self, keymap: &Keymap) -> anyhow::Result<()> {
+        self.keymap = raylib_keymap(keymap)?;
+        self.key_names = keymap.clone();
+        Ok(())
+    }
+
+    fn show_error(&mut self, lines: &[String]) -> anyhow::Result<()> {
+        self.sound.stop();
+        // Fit every line in the window, with a line's gap around them
+        let line_count = lines.len() as i32 + 2;
+        let font_size = (self.window_height / line_count).clamp(1, 20);
+        while !self.handle.window_should_close() && self.handle.get_key_pressed().is_none() {
+            let mut drawhandle = self.handle.begin_drawing(&self.thread);
+            drawhandle.clear_background(self.background);
+            for (index, line) in lines.iter().enumerate() {
+                drawhandle.draw_text(
+                    line,
+                    font_size,
+                    font_size * (index as i32 + 1),
+                    font_size,
+                    self.foreground,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn achieved_fps(&self) -> Option<f32> {
+        Some(self.handle.get_fps() as f32)
+    }
+
     fn step(&mut self) -> anyhow::Result<()> {
-        // If we should be playing sound, make sure we are
-        // raylib doesn't(?) allow for just looping the sound
-        // so this checks every loop to ensure the sound is playing
-        if self.playing_sound && !self.sound.is_playing() {
-            self.sound.play();
+        self.sound.step();
+        Ok(())
+    }
+}
+
+/// What the raylib frontend supports, with audio if `has_audio` (it's
+/// created without sound when audio is disabled)
+fn raylib_capabilities(has_audio: bool) -> FrontendCapabilities {
+    FrontendCapabilities {
+        has_audio,
+        supports_hires: true,
+        reports_focus: true,
+        supports_file_drop: true,
+    }
+}
+
+/// Draw a border around the window, showing the beep is sounding
+fn draw_sound_indicator(
+    drawhandle: &mut impl RaylibDraw,
+    window_width: i32,
+    window_height: i32,
+    color: Color,
+) {
+    let thickness = (window_width.min(window_height) / 40).max(2);
+    drawhandle.draw_rectangle(0, 0, window_width, thickness, color);
+    drawhandle.draw_rectangle(0, window_height - thickness, window_width, thickness, color);
+    drawhandle.draw_rectangle(0, 0, thickness, window_height, color);
+    drawhandle.draw_rectangle(window_width - thickness, 0, thickness, window_height, color);
+}
+
+/// Size of the window in logical pixels (the coordinates everything is drawn
+/// in), from its size in physical pixels and the DPI scale
+///
+/// On a HiDPI display a window of 640x320 logical pixels has a render size of
+/// e.g. 1280x640 with a scale of 2.0, some platforms report the screen size in
+/// physical pixels too, so it is always worked out from the render size.
+fn logical_size(render_size: (i32, i32), dpi_scale: (f32, f32)) -> (i32, i32) {
+    let scale = |pixels: i32, scale: f32| {
+        if scale.is_finite() && scale > 0.0 {
+            ((pixels as f32 / scale).round() as i32).max(1)
+        } else {
+            pixels.max(1)
         }
+    };
+    (
+        scale(render_size.0, dpi_scale.0),
+        scale(render_size.1, dpi_scale.1),
+    )
+}
 
+/// Size of the window in logical pixels, see [logical_size]
+fn window_logical_size(handle: &RaylibHandle) -> (i32, i32) {
+    let dpi_scale = handle.get_window_scale_dpi();
+    logical_size(
+        (handle.get_render_width(), handle.get_render_height()),
+        (dpi_scale.x, dpi_scale.y),
+    )
+}
+
+/// Width and height of the window icon, in pixels
+const ICON_SIZE: usize = 32;
+/// The C and 8 glyphs of the CHIP-8 font, drawn on the window icon
+const ICON_GLYPHS: [[u8; 5]; 2] = [
+    [0xF0, 0x80, 0x80, 0x80, 0xF0],
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0],
+];
+/// Size of each glyph pixel on the icon
+const ICON_SCALE: usize = 3;
+
+/// Pixels of the window icon that are on, "C8" in the CHIP-8 font centred on
+/// the icon, indexed by row then column
+fn icon_pixels() -> [[bool; ICON_SIZE]; ICON_SIZE] {
+    // Each glyph is 4 pixels wide, with a gap of one between them
+    let text_width = (ICON_GLYPHS.len() * 5 - 1) * ICON_SCALE;
+    let text_height = 5 * ICON_SCALE;
+    let left = (ICON_SIZE - text_width) / 2;
+    let top = (ICON_SIZE - text_height) / 2;
+    let mut pixels = [[false; ICON_SIZE]; ICON_SIZE];
+    for (index, glyph) in ICON_GLYPHS.iter().enumerate() {
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..4 {
+                if bits & (0x80 >> col) == 0 {
+                    continue;
+                }
+                let x = left + (index * 5 + col) * ICON_SCALE;
+                let y = top + row * ICON_SCALE;
+                for line in &mut pixels[y..y + ICON_SCALE] {
+                    line[x..x + ICON_SCALE].fill(true);
+                }
+            }
+        }
+    }
+    pixels
+}
+
+/// Color of the cell at `index` (in row major order) of `display`, from
+/// `palette`, None if it isn't lit on any plane
+fn cell_color(palette: &[Color], display: &Display, index: usize) -> Result<Option<Color>> {
+    let lit = display.color_index(index / display.cols(), index % display.cols())?;
+    Ok(match lit {
+        0 => None,
+        // The palette was checked against the number of planes
+        _ => palette.get(lit).or(palette.last()).copied(),
+    })
+}
+
+/// The window icon, drawn in the display's colors
+fn window_icon(foreground: Color, background: Color) -> Image {
+    let mut image = Image::gen_image_color(ICON_SIZE as i32, ICON_SIZE as i32, background);
+    for (y, line) in icon_pixels().iter().enumerate() {
+        for (x, _) in line.iter().enumerate().filter(|(_, on)| **on) {
+            image.draw_pixel(x as i32, y as i32, foreground);
+        }
+    }
+    image
+}
+
+/// Size of the FPS / IPS counter's text in a window `window_height` pixels high
+fn perf_font_size(window_height: i32) -> i32 {
+    (window_height / 20).max(10)
+}
+
+/// Draw the FPS / IPS counter `text` (`text_width` pixels wide) in the top left
+/// corner of the window, on a background so it can be read over the display
+fn draw_perf_text(
+    drawhandle: &mut impl RaylibDraw,
+    text: &str,
+    text_width: i32,
+    font_size: i32,
+    foreground: Color,
+    background: Color,
+) {
+    let margin = font_size / 4;
+    drawhandle.draw_rectangle(
+        0,
+        0,
+        text_width + 2 * margin,
+        font_size + 2 * margin,
+        background,
+    );
+    drawhandle.draw_text(text, margin, margin, font_size, foreground);
+}
+
+/// Draw the enabled cheats `text` (`text_width` pixels wide) in the bottom left
+/// corner of the window, as part of the debug overlay
+fn draw_cheats_text(
+    drawhandle: &mut impl RaylibDraw,
+    text: &str,
+    text_width: i32,
+    font_size: i32,
+    window_height: i32,
+    foreground: Color,
+    background: Color,
+) {
+    let margin = font_size / 4;
+    let top = window_height - font_size - 2 * margin;
+    drawhandle.draw_rectangle(
+        0,
+        top,
+        text_width + 2 * margin,
+        font_size + 2 * margin,
+        background,
+    );
+    drawhandle.draw_text(text, margin, top + margin, font_size, foreground);
+}
+
+/// Position of the pause menu with `items` entries, as the left and top of the
+/// first item, and the width and height of each item
+fn menu_layout(window_width: i32, window_height: i32, items: usize) -> (i32, i32, i32, i32) {
+    let item_height = (window_height / 10).max(12);
+    let width = window_width / 2;
+    let left = (window_width - width) / 2;
+    let top = (window_height - item_height * items as i32) / 2;
+    (left, top, width, item_height)
+}
+
+/// Draw the pause menu in the middle of the window, highlighting the selected
+/// item, and explaining how to load a ROM if `drop_hint` is set
+fn draw_menu(
+    drawhandle: &mut impl RaylibDraw,
+    menu: &PauseMenu,
+    drop_hint: bool,
+    window_width: i32,
+    window_height: i32,
+    foreground: Color,
+    background: Color,
+) {
+    let (left, top, width, item_height) =
+        menu_layout(window_width, window_height, menu.items().len());
+    let margin = item_height / 4;
+    let font_size = item_height / 2;
+    drawhandle.draw_rectangle(
+        left - margin,
+        top - margin,
+        width + 2 * margin,
+        item_height * menu.items().len() as i32 + 2 * margin,
+        background,
+    );
+    drawhandle.draw_rectangle_lines(
+        left - margin,
+        top - margin,
+        width + 2 * margin,
+        item_height * menu.items().len() as i32 + 2 * margin,
+        foreground,
+    );
+    for (index, item) in menu.items().iter().enumerate() {
+        let y = top + index as i32 * item_height;
+        let text_color = if index == menu.selected() {
+            drawhandle.draw_rectangle(left, y, width, item_height, foreground);
+            background
+        } else {
+            foreground
+        };
+        drawhandle.draw_text(
+            item.label(),
+            left + margin,
+            y + (item_height - font_size) / 2,
+            font_size,
+            text_color,
+        );
+    }
+    if drop_hint {
+        let y = top + item_height * menu.items().len() as i32 + 2 * margin;
+        drawhandle.draw_rectangle(left - margin, y, width + 2 * margin, font_size, background);
+        drawhandle.draw_text(
+            "Drop a ROM onto the window to load it",
+            left,
+            y,
+            font_size / 2,
+            foreground,
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_raylib_frontend {
+    use super::*;
+
+    #[test]
+    /// Test that the beep can be created, and used, without an audio device
+    fn test_sound_disabled() {
+        let mut sound = RaylibSound::disabled();
+        sound.play();
+        assert!(!sound.playing_sound);
+        sound.step();
+        sound.stop();
+        assert!(!sound.playing_sound);
+    }
+
+    #[test]
+    #[ignore = "needs a display to open the window"]
+    /// Test creating the frontend with audio disabled, without an audio device
+    fn test_new_without_audio() -> Result<()> {
+        let config = config::EmulatorConfig::default();
+        let mut frontend =
+            RaylibFrontend::new(&config, &Keymap::default(), &HotkeyMap::default(), None)?;
+        frontend.play_sound()?;
+        frontend.step()?;
+        assert!(!frontend.sound.playing_sound);
+        frontend.stop_sound()?;
         Ok(())
     }
+
+    #[test]
+    /// Test the capabilities reported, with and without audio
+    fn test_capabilities() {
+        let capabilities = raylib_capabilities(true);
+        assert!(capabilities.has_audio);
+        assert!(capabilities.supports_hires);
+        assert!(capabilities.reports_focus);
+        assert!(!raylib_capabilities(false).has_audio);
+    }
+
+    #[test]
+    /// Test working out the logical window size on normal and HiDPI displays
+    fn test_logical_size() {
+        assert_eq!(logical_size((640, 320), (1.0, 1.0)), (640, 320));
+        assert_eq!(logical_size((1280, 640), (2.0, 2.0)), (640, 320));
+        assert_eq!(logical_size((960, 480), (1.5, 1.5)), (640, 320));
+        // A missing or broken scale leaves the size alone
+        assert_eq!(logical_size((640, 320), (0.0, f32::NAN)), (640, 320));
+        assert_eq!(logical_size((0, 0), (2.0, 2.0)), (1, 1));
+    }
+
+    #[test]
+    /// Test the window icon's "C8" pattern
+    fn test_icon_pixels() {
+        let pixels = icon_pixels();
+        let lit = |row: usize| -> String {
+            pixels[row]
+                .iter()
+                .map(|on| if *on { '#' } else { '.' })
+                .collect()
+        };
+        // The top of both glyphs, then the sides of the C and 8 in the middle
+        assert_eq!(lit(8), "..############...############...");
+        assert_eq!(lit(11), "..###............###......###...");
+        assert_eq!(lit(14), "..###............############...");
+        // Blank margins above and below
+        assert!(pixels[..8].iter().flatten().all(|on| !on));
+        assert!(pixels[23..].iter().flatten().all(|on| !on));
+        assert!(pixels[8..23].iter().all(|line| line[2] && !line[1]));
+    }
+
+    #[test]
+    /// Test that the menu is centered in the window
+    fn test_menu_layout() {
+        let (left, top, width, item_height) = menu_layout(640, 320, 5);
+        assert_eq!((left, width), (160, 320));
+        assert_eq!(item_height, 32);
+        assert_eq!(top, (320 - 5 * 32) / 2);
+        // Tiny windows still get readable items
+        assert_eq!(menu_layout(64, 32, 5).3, 12);
+    }
 }
@@ -0,0 +1,608 @@
+//! Remote control of the running emulator over a Unix domain socket (a TCP port
+//! on localhost on Windows), for scripting and testing the full binary
+//!
+//! Clients send one JSON command per line, e.g. `{"command": "press", "key": 5}`,
+//! and get one JSON reply per line back: `{"ok": true, "state": {...}}` with a
+//! snapshot of the emulator after carrying out the command, or
+//! `{"ok": false, "error": "..."}`. One client is served at a time, any other
+//! is sent an error and disconnected.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use anyhow::{Context, Result, anyhow};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::Emulator;
+
+/// A command from a client, named by its `command` field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    /// Pause the run loop (see [Emulator::set_paused])
+    Pause,
+    /// Resume the run loop
+    Resume,
+    /// Execute `count` instructions (1 if not given), stopping early at a
+    /// breakpoint or if the program exits
+    Step {
+        #[serde(default = "default_step_count")]
+        count: usize,
+    },
+    /// Restart the ROM from the beginning
+    Reset,
+    /// Hold keypad key `key` down until it is released
+    Press { key: u8 },
+    /// Let go of keypad key `key`
+    Release { key: u8 },
+    /// Bind keypad key `key` to the physical key named `to` (see
+    /// [Emulator::remap_key])
+    Remap { key: u8, to: String },
+    /// Load the ROM at `path` in place of the running one
+    LoadRom { path: PathBuf },
+    /// Write a save state to `path`
+    SaveState { path: PathBuf },
+    /// Write the display to `path` as a PPM image
+    Screenshot { path: PathBuf },
+    /// Do nothing, just reply with the state
+    QueryState,
+    /// Stop the emulator
+    Quit,
+}
+
+fn default_step_count() -> usize {
+    1
+}
+
+/// The state of the emulator sent back with each reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// SHA-1 of the loaded ROM, as hex
+    pub rom_sha1: String,
+    pub program_counter: usize,
+    pub index_register: u16,
+    pub registers: Vec<u8>,
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Instructions executed so far
+    pub cycles: u64,
+    pub paused: bool,
+    pub halted: bool,
+}
+
+impl StateSnapshot {
+    /// The current state of `emulator`
+    pub fn of(emulator: &Emulator<'_>) -> Self {
+        let state = emulator.save_state();
+        Self {
+            rom_sha1: state.rom_sha1,
+            program_counter: state.program_counter,
+            index_register: state.index_register,
+            registers: state.registers,
+            stack: state.stack,
+            delay_timer: state.delay_timer,
+            sound_timer: state.sound_timer,
+            cycles: state.cycles,
+            paused: emulator.is_paused(),
+            halted: state.halted,
+        }
+    }
+}
+
+/// The reply to a command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reply {
+    /// Whether the command was carried out
+    pub ok: bool,
+    /// Why the command failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The state after carrying out the command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<StateSnapshot>,
+}
+
+impl Reply {
+    /// The reply to a command which was carried out, leaving the emulator in `state`
+    pub fn success(state: StateSnapshot) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            state: Some(state),
+        }
+    }
+
+    /// The reply to a command which failed with `err`
+    pub fn failure(err: &anyhow::Error) -> Self {
+        Self {
+            ok: false,
+            error: Some(format!("{err:#}")),
+            state: None,
+        }
+    }
+}
+
+/// Carry out `command` on `emulator`, returning whether it asks to quit
+pub fn execute(emulator: &mut Emulator<'_>, command: &Command) -> Result<bool> {
+    match command {
+        Command::Pause => emulator.set_paused(true)?,
+        Command::Resume => emulator.set_paused(false)?,
+        Command::Step { count } => {
+            emulator.step_n(*count)?;
+        }
+        Command::Reset => emulator.reset()?,
+        Command::Press { key } => emulator.set_key_held(*key, true)?,
+        Command::Release { key } => emulator.set_key_held(*key, false)?,
+        Command::Remap { key, to } => emulator.remap_key(*key, to)?,
+        Command::LoadRom { path } => emulator.switch_rom(path)?,
+        Command::SaveState { path } => emulator.save_state().write(path)?,
+        Command::Screenshot { path } => emulator.write_screenshot(path)?,
+        Command::QueryState => {}
+        Command::Quit => return Ok(true),
+    }
+    Ok(false)
+}
+
+/// Carry out `command` on `emulator`, returning the reply and whether it asks
+/// to quit
+pub fn reply_to(emulator: &mut Emulator<'_>, command: &Command) -> (Reply, bool) {
+    debug!("Remote control command {command:?}");
+    match execute(emulator, command) {
+        Ok(quit) => (Reply::success(StateSnapshot::of(emulator)), quit),
+        Err(err) => {
+            warn!("Remote control command {command:?} failed: {err:#}");
+            (Reply::failure(&err), false)
+        }
+    }
+}
+
+/// A command waiting to be carried out by the emulator
+pub struct Request {
+    command: Command,
+    reply: Sender<Reply>,
+}
+
+impl Request {
+    /// Carry out the command on `emulator`, sending the reply back to the
+    /// client, and returning whether it asks to quit
+    pub fn respond(self, emulator: &mut Emulator<'_>) -> bool {
+        let (reply, quit) = reply_to(emulator, &self.command);
+        // The client may have disconnected while waiting
+        let _ = self.reply.send(reply);
+        quit
+    }
+}
+
+/// Write `reply` to `writer` as a line of JSON
+fn write_reply(mut writer: impl Write, reply: &Reply) -> Result<()> {
+    let json = serde_json::to_string(reply).context("Converting reply to JSON")?;
+    writeln!(writer, "{json}").context("Writing reply")?;
+    writer.flush().context("Writing reply")
+}
+
+/// Serve a client until it disconnects, reading its commands from `reader` and
+/// writing the replies to `writer`
+///
+/// `execute` carries out each command, returning None once the emulator has
+/// stopped, which also ends serving the client.
+pub fn serve_client(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    mut execute: impl FnMut(Command) -> Option<Reply>,
+) -> Result<()> {
+    for line in reader.lines() {
+        let line = line.context("Reading command")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let command = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => command,
+            Err(err) => {
+                let err = anyhow!(err).context(format!("Invalid command {line:?}"));
+                write_reply(&mut writer, &Reply::failure(&err))?;
+                continue;
+            }
+        };
+        match execute(command) {
+            Some(reply) => write_reply(&mut writer, &reply)?,
+            None => {
+                let err = anyhow!("The emulator has stopped");
+                return write_reply(&mut writer, &Reply::failure(&err));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Serve the client connected over `stream`, passing its commands on to the
+/// emulator through `requests`
+fn serve_stream(stream: transport::Stream, requests: &Sender<Request>) -> Result<()> {
+    let reader = BufReader::new(stream.try_clone().context("Reading from the client")?);
+    serve_client(reader, stream, |command| {
+        let (reply, receiver) = mpsc::channel();
+        requests.send(Request { command, reply }).ok()?;
+        receiver.recv().ok()
+    })
+}
+
+/// Accept clients on `listener` for as long as the program runs, serving one at
+/// a time on a thread of its own, and turning any others away
+fn accept_clients(listener: transport::Listener, requests: Sender<Request>) {
+    let connected = Arc::new(AtomicBool::new(false));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Failed to accept a remote control client: {err}");
+                continue;
+            }
+        };
+        if connected.swap(true, Ordering::AcqRel) {
+            debug!("Turning away a remote control client, another is connected");
+            let err = anyhow!("Another client is already connected");
+            let _ = write_reply(&mut stream, &Reply::failure(&err));
+            continue;
+        }
+        let requests = requests.clone();
+        let connected = connected.clone();
+        thread::spawn(move || {
+            info!("Remote control client connected");
+            if let Err(err) = serve_stream(stream, &requests) {
+                warn!("Remote control client failed: {err:#}");
+            }
+            info!("Remote control client disconnected");
+            connected.store(false, Ordering::Release);
+        });
+    }
+}
+
+/// Takes commands from remote control clients, for the emulator to carry out
+/// while it runs (see [Emulator::enable_remote_control])
+///
+/// Clients are listened for on a thread of their own, and each command waits
+/// for the emulator to reply to it.
+pub struct RemoteControl {
+    address: String,
+    requests: Receiver<Request>,
+}
+
+impl RemoteControl {
+    /// Listen for clients at `address`, the path of a Unix domain socket (on
+    /// Windows, a port on localhost)
+    pub fn listen(address: &str) -> Result<Self> {
+        let listener = transport::bind(address)?;
+        info!("Listening for remote control clients on {address}");
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || accept_clients(listener, sender));
+        Ok(Self {
+            address: address.to_string(),
+            requests,
+        })
+    }
+
+    /// The next command from the client, if one is waiting
+    pub fn next_request(&self) -> Option<Request> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for RemoteControl {
+    /// Stop new clients connecting, the current one is told the emulator has
+    /// stopped on its next command
+    fn drop(&mut self) {
+        transport::unbind(&self.address);
+    }
+}
+
+#[cfg(unix)]
+mod transport {
+    use std::fs;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    use anyhow::{Context, Result, bail};
+
+    pub type Listener = UnixListener;
+    pub type Stream = UnixStream;
+
+    /// Listen on the Unix domain socket at `path`, replacing a socket left
+    /// behind by an emulator which didn't exit cleanly
+    pub fn bind(path: &str) -> Result<Listener> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                bail!("Can't listen on {path}, it's already a file which isn't a socket");
+            }
+            if UnixStream::connect(path).is_ok() {
+                bail!("Can't listen on {path}, something is already listening on it");
+            }
+            fs::remove_file(path).with_context(|| format!("Removing old socket {path}"))?;
+        }
+        UnixListener::bind(path).with_context(|| format!("Listening on {path}"))
+    }
+
+    /// Remove the socket at `path`
+    pub fn unbind(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(not(unix))]
+mod transport {
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+    use anyhow::{Context, Result};
+
+    pub type Listener = TcpListener;
+    pub type Stream = TcpStream;
+
+    /// Listen on `port` of localhost, as std has no Unix domain sockets here
+    pub fn bind(port: &str) -> Result<Listener> {
+        let port: u16 = port
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid control port {port:?}, expected a port number"))?;
+        TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .with_context(|| format!("Listening on port {port}"))
+    }
+
+    /// Nothing to clean up after a TCP port
+    pub fn unbind(_port: &str) {}
+}
+
+#[cfg(test)]
+mod test_remote_control {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::config::EmulatorConfig;
+    use crate::noop_frontend::NoOpFrontend;
+    use crate::save_state::SaveState;
+
+    /// V0 = 5, skip if key V0 is down, V1 = 1, V2 = 2
+    const KEY_ROM: [u8; 8] = [0x60, 0x05, 0xE0, 0x9E, 0x61, 0x01, 0x62, 0x02];
+
+    /// The replies written, one per line
+    fn replies(output: &[u8]) -> Vec<Reply> {
+        std::str::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// A reply to `command`, carried out on `emulator`, asserting it succeeded
+    fn state_after(emulator: &mut Emulator<'_>, command: Command) -> StateSnapshot {
+        let (reply, quit) = reply_to(emulator, &command);
+        assert!(reply.ok, "{command:?} failed: {reply:?}");
+        assert!(!quit);
+        reply.state.unwrap()
+    }
+
+    #[test]
+    /// Test parsing commands, named by their `command` field
+    fn test_parse_commands() -> Result<()> {
+        let parse = |json: &str| serde_json::from_str::<Command>(json);
+        assert_eq!(parse(r#"{"command": "pause"}"#)?, Command::Pause);
+        assert_eq!(parse(r#"{"command": "step"}"#)?, Command::Step { count: 1 });
+        assert_eq!(
+            parse(r#"{"command": "step", "count": 10}"#)?,
+            Command::Step { count: 10 }
+        );
+        assert_eq!(
+            parse(r#"{"command": "press", "key": 15}"#)?,
+            Command::Press { key: 15 }
+        );
+        assert_eq!(
+            parse(r#"{"command": "load_rom", "path": "pong.ch8"}"#)?,
+            Command::LoadRom {
+                path: PathBuf::from("pong.ch8")
+            }
+        );
+        assert_eq!(
+            parse(r#"{"command": "remap", "key": 10, "to": "W"}"#)?,
+            Command::Remap {
+                key: 10,
+                to: "W".to_string()
+            }
+        );
+        assert_eq!(parse(r#"{"command": "query_state"}"#)?, Command::QueryState);
+        assert!(parse(r#"{"command": "fly"}"#).is_err());
+        assert!(parse(r#"{"command": "press"}"#).is_err());
+        assert!(parse(r#"{"command": "press", "key": 300}"#).is_err());
+        assert!(parse("pause").is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test a client gets one reply per command, including for invalid ones,
+    /// until the emulator stops
+    fn test_serve_client() -> Result<()> {
+        let input = concat!(
+            "{\"command\": \"pause\"}\n",
+            "\n",
+            "not json\n",
+            "{\"command\": \"release\", \"key\": 3}\n",
+            "{\"command\": \"quit\"}\n",
+            "{\"command\": \"resume\"}\n",
+        );
+        let mut output = Vec::new();
+        let mut executed = Vec::new();
+        serve_client(Cursor::new(input), &mut output, |command| {
+            if executed.contains(&Command::Quit) {
+                return None;
+            }
+            executed.push(command);
+            Some(Reply::failure(&anyhow!("Executed {}", executed.len())))
+        })?;
+        assert_eq!(
+            executed,
+            [Command::Pause, Command::Release { key: 3 }, Command::Quit]
+        );
+        let replies = replies(&output);
+        let errors: Vec<&str> = replies
+            .iter()
+            .map(|reply| reply.error.as_deref().unwrap())
+            .collect();
+        assert_eq!(errors.len(), 5);
+        assert_eq!(errors[0], "Executed 1");
+        assert!(errors[1].starts_with("Invalid command \"not json\""));
+        assert_eq!(
+            errors[2..],
+            ["Executed 2", "Executed 3", "The emulator has stopped"]
+        );
+
+        // Replies are single lines of JSON, leaving out what they don't have
+        let mut output = Vec::new();
+        write_reply(&mut output, &Reply::failure(&anyhow!("Failed")))?;
+        assert_eq!(output, b"{\"ok\":false,\"error\":\"Failed\"}\n");
+        Ok(())
+    }
+
+    #[test]
+    /// Test carrying out each command, replying with the state after it
+    fn test_execute() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut emulator = Emulator::new(Box::new(NoOpFrontend::new()), EmulatorConfig::default())?;
+        emulator.load_rom(&KEY_ROM)?;
+
+        let state = state_after(&mut emulator, Command::Pause);
+        assert!(state.paused);
+        assert_eq!(state.program_counter, 0x200);
+        assert!(!state_after(&mut emulator, Command::Resume).paused);
+
+        // The held key makes the program skip setting V1
+        state_after(&mut emulator, Command::Press { key: 5 });
+        let state = state_after(&mut emulator, Command::Step { count: 3 });
+        assert_eq!(state.cycles, 3);
+        assert_eq!(state.registers[..3], [5, 0, 2]);
+        state_after(&mut emulator, Command::Release { key: 5 });
+        state_after(&mut emulator, Command::Reset);
+        let state = state_after(&mut emulator, Command::Step { count: 3 });
+        assert_eq!(state.registers[..3], [5, 1, 0]);
+
+        let save_path = dir.path().join("state.json");
+        state_after(
+            &mut emulator,
+            Command::SaveState {
+                path: save_path.clone(),
+            },
+        );
+        assert_eq!(SaveState::read(&save_path)?.program_counter, 0x206);
+
+        let screenshot_path = dir.path().join("screen.ppm");
+        state_after(
+            &mut emulator,
+            Command::Screenshot {
+                path: screenshot_path.clone(),
+            },
+        );
+        assert!(std::fs::read(&screenshot_path)?.starts_with(b"P6\n64 32\n255\n"));
+
+        let rom_path = dir.path().join("other.ch8");
+        std::fs::write(&rom_path, [0x12, 0x00])?;
+        let state = state_after(&mut emulator, Command::LoadRom { path: rom_path });
+        assert_eq!(state.program_counter, 0x200);
+        assert_eq!(state.rom_sha1, crate::rom::sha1_hex(&[0x12, 0x00]));
+
+        // Failures are replied to without stopping
+        let (reply, quit) = reply_to(&mut emulator, &Command::Press { key: 16 });
+        assert!(!reply.ok && !quit);
+        assert!(reply.error.unwrap().contains("Invalid keypad key 16"));
+        let missing = Command::LoadRom {
+            path: dir.path().join("missing.ch8"),
+        };
+        assert!(!reply_to(&mut emulator, &missing).0.ok);
+        let remap = Command::Remap {
+            key: 5,
+            to: "NOT_A_KEY".to_string(),
+        };
+        let (reply, quit) = reply_to(&mut emulator, &remap);
+        assert!(!reply.ok && !quit);
+        assert_eq!(StateSnapshot::of(&emulator).rom_sha1, state.rom_sha1);
+
+        let (reply, quit) = reply_to(&mut emulator, &Command::Quit);
+        assert!(reply.ok && quit);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    /// Test controlling a running emulator over a real socket, with a second
+    /// client turned away while the first is connected
+    fn test_remote_control_socket() -> Result<()> {
+        use std::os::unix::net::UnixStream;
+        use std::path::Path;
+        use std::time::Duration;
+
+        use crate::emulator::{RunLimits, RunOutcome};
+        use crate::host_frontend::{HostFrontend, HostKeys};
+
+        /// Send `command` over `stream`, returning the reply
+        fn send(stream: &mut BufReader<UnixStream>, command: &str) -> Result<Reply> {
+            writeln!(stream.get_mut(), "{command}")?;
+            let mut line = String::new();
+            stream.read_line(&mut line)?;
+            Ok(serde_json::from_str(&line)?)
+        }
+
+        let dir = tempfile::tempdir()?;
+        let socket = dir.path().join("emul8rs.sock");
+        let address = socket.to_str().unwrap();
+        // A file in the way isn't replaced
+        std::fs::write(&socket, "")?;
+        assert!(RemoteControl::listen(address).is_err());
+        std::fs::remove_file(&socket)?;
+
+        let mut emulator = Emulator::new(
+            Box::new(HostFrontend::new(HostKeys::default())),
+            EmulatorConfig::default(),
+        )?;
+        // Loop forever
+        emulator.load_rom(&[0x12, 0x00])?;
+        emulator.enable_remote_control(RemoteControl::listen(address)?);
+        let client = thread::spawn({
+            let socket = socket.clone();
+            move || -> Result<Vec<Reply>> {
+                let mut first = BufReader::new(UnixStream::connect(&socket)?);
+                let mut replies = vec![send(&mut first, r#"{"command": "pause"}"#)?];
+                // The second client is told why straight away, and disconnected
+                let mut second = BufReader::new(UnixStream::connect(&socket)?);
+                let mut line = String::new();
+                second.read_line(&mut line)?;
+                replies.push(serde_json::from_str(&line)?);
+                assert_eq!(second.read_line(&mut line)?, 0);
+                replies.push(send(&mut first, r#"{"command": "query_state"}"#)?);
+                replies.push(send(&mut first, r#"{"command": "quit"}"#)?);
+                Ok(replies)
+            }
+        });
+        let outcome = emulator.run_for(RunLimits {
+            max_duration: Some(Duration::from_secs(10)),
+            ..RunLimits::default()
+        })?;
+        let replies = client.join().unwrap()?;
+        assert_eq!(outcome, RunOutcome::Stopped);
+
+        assert!(replies[0].ok);
+        assert!(!replies[1].ok);
+        assert_eq!(
+            replies[1].error.as_deref(),
+            Some("Another client is already connected")
+        );
+        let state = replies[2].state.as_ref().unwrap();
+        assert!(state.paused);
+        assert!(replies[3].ok);
+
+        // The socket goes with the emulator
+        drop(emulator);
+        assert!(!Path::new(address).exists());
+        Ok(())
+    }
+}
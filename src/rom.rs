@@ -0,0 +1,693 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "std")]
+use anyhow::bail;
+use anyhow::{Context, Result};
+
+use crate::emulator::{MEMORY_SIZE, Quirk};
+use crate::instruction::Instruction;
+#[cfg(feature = "std")]
+use crate::octo;
+
+#[cfg(feature = "zip")]
+use crate::library::ROM_EXTENSIONS;
+#[cfg(feature = "zip")]
+use std::io::Read;
+#[cfg(feature = "zip")]
+use zip::ZipArchive;
+
+/// Address programs are loaded at
+pub const PROGRAM_START: usize = 0x200;
+
+/// Largest ROM which fits in the 4KB of CHIP-8 memory (after the interpreter area)
+pub const MAX_CHIP8_ROM_SIZE: usize = MEMORY_SIZE - PROGRAM_START;
+
+/// The CHIP-8 variants a ROM can be written for, in order of how much they extend
+/// the original instruction set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    /// The original CHIP-8
+    Chip8,
+    /// SUPER-CHIP, adding a high resolution mode, scrolling, and big sprites
+    SuperChip,
+    /// XO-CHIP, adding 64KB of memory, a second bit plane, and audio patterns
+    XoChip,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 3] = [Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+    /// Name of the platform, as shown in reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xo-chip",
+        }
+    }
+
+    /// The platform named `name` (see [Platform::name], ignoring case), also
+    /// taking `cosmac` for the original CHIP-8 on the COSMAC VIP
+    pub fn from_name(name: &str) -> Result<Self> {
+        let name = name.trim().to_lowercase();
+        if name == "cosmac" {
+            return Ok(Platform::Chip8);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|platform| platform.name() == name)
+            .with_context(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(Platform::name).collect();
+                format!(
+                    "Unknown profile {name:?}, expected cosmac or one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// The platform of a platform id from the CHIP-8 database (e.g.
+    /// `originalChip8` or `superchip`), None for ones emul8rs has no profile for
+    pub fn from_database_id(id: &str) -> Option<Self> {
+        match id {
+            "originalChip8" | "hybridVIP" | "modernChip8" | "chip8x" => Some(Platform::Chip8),
+            "chip48" | "superchip1" | "superchip" => Some(Platform::SuperChip),
+            "xochip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    /// The quirk settings ROMs written for the platform expect, as config
+    /// settings to merge (see [crate::config::EmulatorConfig::merge])
+    #[cfg(feature = "std")]
+    pub fn quirks(&self) -> toml::Table {
+        // SUPER-CHIP shifts VX in place, jumps to XNN + VX, and leaves I alone
+        // when storing or loading registers, the others all follow the original
+        let original = *self != Platform::SuperChip;
+        let wrap = *self == Platform::XoChip;
+        // The original interpreter had room for 12 return addresses, SUPER-CHIP
+        // (and XO-CHIP after it) for 16
+        let stack_depth: i64 = if *self == Platform::Chip8 { 12 } else { 16 };
+        // Only XO-CHIP has a second plane
+        let num_planes: i64 = if *self == Platform::XoChip { 2 } else { 1 };
+        toml::Table::from_iter([
+            ("shift_use_vy".to_string(), original.into()),
+            ("jump_offset_use_v0".to_string(), original.into()),
+            ("store_memory_update_index".to_string(), original.into()),
+            ("wrap_x".to_string(), wrap.into()),
+            ("wrap_y".to_string(), wrap.into()),
+            ("stack_depth".to_string(), stack_depth.into()),
+            ("num_planes".to_string(), num_planes.into()),
+        ])
+    }
+}
+
+/// The extension which introduced `opcode`, and a short description of it, or
+/// None for the original CHIP-8 instructions (and anything unrecognized)
+pub fn extension_opcode(opcode: u16) -> Option<(Platform, &'static str)> {
+    use Instruction::*;
+    let op = match Instruction::decode(opcode)? {
+        ScrollRight => (Platform::SuperChip, "scroll right"),
+        ScrollLeft => (Platform::SuperChip, "scroll left"),
+        Exit => (Platform::SuperChip, "exit"),
+        LowResolution => (Platform::SuperChip, "low resolution"),
+        HighResolution => (Platform::SuperChip, "high resolution"),
+        ScrollDown { .. } => (Platform::SuperChip, "scroll down"),
+        ScrollUp { .. } => (Platform::XoChip, "scroll up"),
+        StoreRange { .. } => (Platform::XoChip, "store register range"),
+        LoadRange { .. } => (Platform::XoChip, "load register range"),
+        Draw { n: 0, .. } => (Platform::SuperChip, "draw 16x16 sprite"),
+        LoadLongIndex => (Platform::XoChip, "load long index"),
+        SelectPlanes { .. } => (Platform::XoChip, "select plane"),
+        LoadAudioPattern => (Platform::XoChip, "load audio pattern"),
+        BigFontCharacter { .. } => (Platform::SuperChip, "big font character"),
+        SetPitch { .. } => (Platform::XoChip, "set pitch"),
+        SaveFlags { .. } => (Platform::SuperChip, "save flags"),
+        LoadFlags { .. } => (Platform::SuperChip, "load flags"),
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Offsets into `rom` of the instructions which can be reached by following the
+/// control flow from the start of the program, and whether a computed jump
+/// (BNNN) was found, past which the control flow can't be followed
+pub fn reachable_instructions(rom: &[u8]) -> (BTreeSet<usize>, bool) {
+    let mut reachable = BTreeSet::new();
+    let mut computed_jump = false;
+    let mut to_visit = vec![0];
+    while let Some(offset) = to_visit.pop() {
+        if offset + 1 >= rom.len() || !reachable.insert(offset) {
+            continue;
+        }
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let target = (opcode & 0x0FFF) as usize;
+        let next = offset + 2;
+        match opcode & 0xF000 {
+            // Return, and exit
+            0x0000 if opcode == 0x00EE || opcode == 0x00FD => {}
+            0x1000 => to_visit.extend(target.checked_sub(PROGRAM_START)),
+            0x2000 => {
+                to_visit.push(next);
+                to_visit.extend(target.checked_sub(PROGRAM_START));
+            }
+            0xB000 => computed_jump = true,
+            // Skips
+            0x3000 | 0x4000 | 0x5000 | 0x9000 => to_visit.extend([next, next + 2]),
+            0xE000 if matches!(opcode & 0x00FF, 0x9E | 0xA1) => to_visit.extend([next, next + 2]),
+            // XO-CHIP's long index load takes 4 bytes
+            0xF000 if opcode == 0xF000 => to_visit.push(next + 2),
+            _ => to_visit.push(next),
+        }
+    }
+    (reachable, computed_jump)
+}
+
+/// The instructions `opcode` is one of, as shown in reports, if their behavior
+/// differs between CHIP-8 interpreters, with the quirk choosing it (None if
+/// emul8rs only has one behavior)
+pub fn quirk_dependency(opcode: u16) -> Option<(&'static str, Option<Quirk>)> {
+    match Instruction::decode(opcode)? {
+        Instruction::ShiftRight { .. } | Instruction::ShiftLeft { .. } => {
+            Some(("8XY6/8XYE (shift)", Some(Quirk::ShiftUseVy)))
+        }
+        Instruction::JumpWithOffset { .. } => {
+            Some(("BNNN (jump with offset)", Some(Quirk::JumpOffsetUseV0)))
+        }
+        Instruction::StoreRegisters { .. } | Instruction::LoadRegisters { .. } => Some((
+            "FX55/FX65 (store/load registers)",
+            Some(Quirk::StoreMemoryUpdateIndex),
+        )),
+        Instruction::AddToIndex { .. } => Some(("FX1E (add to I)", None)),
+        _ => None,
+    }
+}
+
+/// The instructions reachable in `rom` (see [reachable_instructions]) whose
+/// behavior differs between interpreters (see [quirk_dependency]), with the
+/// quirk choosing it and the address of the first of each
+pub fn quirk_dependencies(rom: &[u8]) -> BTreeMap<&'static str, (Option<Quirk>, usize)> {
+    let mut dependencies = BTreeMap::new();
+    for offset in reachable_instructions(rom).0 {
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        if let Some((instructions, quirk)) = quirk_dependency(opcode) {
+            dependencies
+                .entry(instructions)
+                .or_insert((quirk, PROGRAM_START + offset));
+        }
+    }
+    dependencies
+}
+
+/// An instruction from a CHIP-8 extension found in a ROM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionUse {
+    /// Address of the instruction (once loaded at [PROGRAM_START])
+    pub address: usize,
+    pub opcode: u16,
+    pub platform: Platform,
+    pub description: &'static str,
+}
+
+/// Characteristics of a ROM found by static analysis, without running it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    /// Size of the ROM in bytes
+    pub size: usize,
+    /// SHA-1 hash of the ROM, see [sha1_hex]
+    pub sha1: String,
+    /// Instructions from CHIP-8 extensions, in address order
+    pub extension_uses: Vec<ExtensionUse>,
+    /// Whether the ROM uses a computed jump (BNNN), so some of its instructions
+    /// may not have been found
+    pub computed_jump: bool,
+}
+
+impl RomInfo {
+    /// Analyse the ROM `rom`
+    ///
+    /// Only the instructions reachable from the start of the program are checked
+    /// (see [reachable_instructions]), so sprite data isn't mistaken for code.
+    pub fn analyse(rom: &[u8]) -> Self {
+        let (reachable, computed_jump) = reachable_instructions(rom);
+        let extension_uses = reachable
+            .into_iter()
+            .filter_map(|offset| {
+                let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+                extension_opcode(opcode).map(|(platform, description)| ExtensionUse {
+                    address: PROGRAM_START + offset,
+                    opcode,
+                    platform,
+                    description,
+                })
+            })
+            .collect();
+        Self {
+            size: rom.len(),
+            sha1: sha1_hex(rom),
+            extension_uses,
+            computed_jump,
+        }
+    }
+
+    /// Whether the ROM is too large for CHIP-8 memory (only XO-CHIP has more)
+    pub fn too_large(&self) -> bool {
+        self.size > MAX_CHIP8_ROM_SIZE
+    }
+
+    /// The platforms whose instructions the ROM uses, with how many it uses
+    pub fn platforms_used(&self) -> BTreeMap<Platform, usize> {
+        let mut platforms = BTreeMap::new();
+        for extension_use in &self.extension_uses {
+            *platforms.entry(extension_use.platform).or_insert(0) += 1;
+        }
+        platforms
+    }
+
+    /// The platform the ROM most likely targets, the most extended one it
+    /// seems to use
+    pub fn suggested_platform(&self) -> Platform {
+        let used = self.platforms_used().keys().max().copied();
+        let size = self.too_large().then_some(Platform::XoChip);
+        used.max(size).unwrap_or(Platform::Chip8)
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Size: {} bytes", self.size)?;
+        if self.too_large() {
+            writeln!(
+                f,
+                "  Larger than the {MAX_CHIP8_ROM_SIZE} bytes of CHIP-8 memory, \
+                likely an XO-CHIP ROM"
+            )?;
+        }
+        writeln!(f, "SHA-1: {}", self.sha1)?;
+        if self.extension_uses.is_empty() {
+            writeln!(f, "Extension instructions: none")?;
+        } else {
+            writeln!(f, "Extension instructions:")?;
+            for extension_use in &self.extension_uses {
+                writeln!(
+                    f,
+                    "  0x{:03X}: {:04X} {} ({})",
+                    extension_use.address,
+                    extension_use.opcode,
+                    extension_use.description,
+                    extension_use.platform.name()
+                )?;
+            }
+        }
+        if self.computed_jump {
+            writeln!(
+                f,
+                "  (the ROM uses computed jumps, so some instructions may have been missed)"
+            )?;
+        }
+        write!(f, "Suggested profile: {}", self.suggested_platform().name())
+    }
+}
+
+/// Compute the SHA-1 hash of a ROM, as a lowercase hex string
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    sha1_smol::Sha1::from(bytes).digest().to_string()
+}
+
+/// Whether `path` is a zip archive, by its extension
+#[cfg(feature = "std")]
+fn is_zip(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// Split a path to a ROM in a zip archive, like `pack.zip#games/pong.ch8`, into
+/// the archive and the name of the ROM in it
+///
+/// Paths which exist, or aren't in an archive, are returned as they are.
+#[cfg(feature = "std")]
+pub fn split_archive_path(path: &Path) -> (PathBuf, Option<String>) {
+    if !path.exists()
+        && let Some((archive, name)) = path.to_str().and_then(|path| path.rsplit_once('#'))
+        && is_zip(Path::new(archive))
+    {
+        return (PathBuf::from(archive), Some(name.to_string()));
+    }
+    (path.to_path_buf(), None)
+}
+
+/// Read the ROM at `path`, which can be in a zip archive or Octo source to
+/// compile (see [octo::compile])
+///
+/// An archive holding a single ROM can be loaded directly, otherwise the ROM is
+/// chosen with a path like `pack.zip#games/pong.ch8`.
+#[cfg(feature = "std")]
+pub fn open(path: &Path) -> Result<Vec<u8>> {
+    let (file, name) = split_archive_path(path);
+    if is_zip(&file) {
+        return open_zip(&file, name.as_deref());
+    }
+    if octo::is_octo_source(path) {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading Octo source {}", path.display()))?;
+        return octo::compile(&source).with_context(|| format!("Compiling {}", path.display()));
+    }
+    std::fs::read(path).with_context(|| format!("Reading ROM {}", path.display()))
+}
+
+/// Read the ROM `name` from the zip archive at `archive_path`, or its only ROM
+#[cfg(feature = "zip")]
+fn open_zip(archive_path: &Path, name: Option<&str>) -> Result<Vec<u8>> {
+    let context = || format!("Reading ROM archive {}", archive_path.display());
+    let file = std::fs::File::open(archive_path).with_context(context)?;
+    let mut archive = ZipArchive::new(std::io::BufReader::new(file)).with_context(context)?;
+    let mut files = Vec::new();
+    for file in archive.file_names() {
+        let file = file.with_context(context)?;
+        // Directories are entries too, named with a trailing /
+        if !file.ends_with('/') {
+            files.push(file.into_owned());
+        }
+    }
+    let list = |names: &[&String]| {
+        let names: Vec<_> = names.iter().map(|name| name.as_str()).collect();
+        names.join(", ")
+    };
+    let entry = match name {
+        Some(name) => match files.iter().find(|file| *file == name) {
+            Some(file) => file,
+            None => bail!(
+                "There is no {name} in {}, it contains {}",
+                archive_path.display(),
+                list(&files.iter().collect::<Vec<_>>())
+            ),
+        },
+        None => {
+            let roms: Vec<_> = files
+                .iter()
+                .filter(|file| {
+                    Path::new(file)
+                        .extension()
+                        .and_then(|extension| extension.to_str())
+                        .is_some_and(|extension| {
+                            ROM_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+                        })
+                })
+                .collect();
+            match roms.len() {
+                1 => roms[0],
+                0 => bail!("There are no ROMs in {}", archive_path.display()),
+                _ => bail!(
+                    "{} contains several ROMs, choose one with {}#<name>: {}",
+                    archive_path.display(),
+                    archive_path.display(),
+                    list(&roms)
+                ),
+            }
+        }
+    };
+    let file = archive.by_name(entry).with_context(context)?;
+    if file.size() > MAX_CHIP8_ROM_SIZE as u64 {
+        bail!(
+            "{entry} in {} is too large, it is {} bytes but at most {MAX_CHIP8_ROM_SIZE} bytes can be loaded",
+            archive_path.display(),
+            file.size()
+        );
+    }
+    // The size in the archive could be wrong, so don't read past the limit
+    // either way
+    let mut rom = Vec::new();
+    file.take(MAX_CHIP8_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut rom)
+        .with_context(context)?;
+    if rom.len() > MAX_CHIP8_ROM_SIZE {
+        bail!("{entry} in {} is too large", archive_path.display());
+    }
+    Ok(rom)
+}
+
+#[cfg(all(feature = "std", not(feature = "zip")))]
+fn open_zip(archive_path: &Path, _name: Option<&str>) -> Result<Vec<u8>> {
+    bail!(
+        "Can't load {}, emul8rs was built without the zip feature",
+        archive_path.display()
+    )
+}
+
+#[cfg(test)]
+mod test_rom {
+    use super::*;
+
+    #[test]
+    /// Test hashing a ROM
+    fn test_sha1_hex() {
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    /// Test looking up profiles by name
+    fn test_platform_from_name() -> Result<()> {
+        for platform in Platform::ALL {
+            assert_eq!(Platform::from_name(platform.name())?, platform);
+        }
+        assert_eq!(Platform::from_name(" SCHIP ")?, Platform::SuperChip);
+        assert_eq!(Platform::from_name("cosmac")?, Platform::Chip8);
+        let message = Platform::from_name("vip").unwrap_err().to_string();
+        assert!(message.contains("chip8, schip, xo-chip"), "{message}");
+        Ok(())
+    }
+
+    #[test]
+    /// Test classifying the extension opcodes
+    fn test_extension_opcode() {
+        for opcode in [0x00E0, 0x00EE, 0x1234, 0x5120, 0xD125, 0xF129, 0xF00A] {
+            assert_eq!(extension_opcode(opcode), None, "{opcode:04X}");
+        }
+        for opcode in [
+            0x00FF, 0x00FE, 0x00C4, 0x00FB, 0xD120, 0xF330, 0xF575, 0xF585,
+        ] {
+            assert_eq!(
+                extension_opcode(opcode).map(|(platform, _)| platform),
+                Some(Platform::SuperChip),
+                "{opcode:04X}"
+            );
+        }
+        for opcode in [0x00D4, 0x5122, 0x5123, 0xF000, 0xF201, 0xF002, 0xF13A] {
+            assert_eq!(
+                extension_opcode(opcode).map(|(platform, _)| platform),
+                Some(Platform::XoChip),
+                "{opcode:04X}"
+            );
+        }
+    }
+
+    #[test]
+    /// Test that a ROM switching to high resolution is reported as SCHIP
+    fn test_analyse_schip() {
+        let info = RomInfo::analyse(&[0x00, 0xE0, 0x00, 0xFF, 0x12, 0x04]);
+        assert_eq!(info.size, 6);
+        assert_eq!(
+            info.extension_uses,
+            [ExtensionUse {
+                address: 0x202,
+                opcode: 0x00FF,
+                platform: Platform::SuperChip,
+                description: "high resolution",
+            }]
+        );
+        assert_eq!(info.suggested_platform(), Platform::SuperChip);
+        let report = info.to_string();
+        assert!(report.contains("0x202: 00FF high resolution (schip)"));
+        assert!(report.ends_with("Suggested profile: schip"));
+    }
+
+    #[test]
+    /// Test finding the instructions which depend on quirks
+    fn test_quirk_dependencies() {
+        assert!(quirk_dependencies(&[0x00, 0xE0, 0x80, 0x14, 0xF0, 0x15, 0x12, 0x00]).is_empty());
+        let rom = [
+            0x80, 0x16, // 0x200: shift right
+            0xF2, 0x55, // 0x202: store V0-V2
+            0x81, 0x2E, // 0x204: shift left
+            0xF1, 0x1E, // 0x206: add V1 to I
+            0xB3, 0x00, // 0x208: jump to 0x300 + V0
+            0xF2, 0x65, // 0x20A: (never reached)
+        ];
+        assert_eq!(
+            quirk_dependencies(&rom).into_iter().collect::<Vec<_>>(),
+            [
+                ("8XY6/8XYE (shift)", (Some(Quirk::ShiftUseVy), 0x200)),
+                (
+                    "BNNN (jump with offset)",
+                    (Some(Quirk::JumpOffsetUseV0), 0x208)
+                ),
+                ("FX1E (add to I)", (None, 0x206)),
+                (
+                    "FX55/FX65 (store/load registers)",
+                    (Some(Quirk::StoreMemoryUpdateIndex), 0x202)
+                ),
+            ]
+        );
+        // Only reachable instructions count
+        assert!(quirk_dependencies(&[0x12, 0x04, 0x80, 0x16, 0x12, 0x04]).is_empty());
+    }
+
+    #[test]
+    /// Test the suggested platform
+    fn test_suggested_platform() {
+        let info = RomInfo::analyse(&[0x00, 0xE0, 0x12, 0x00]);
+        assert!(info.extension_uses.is_empty());
+        assert_eq!(info.suggested_platform(), Platform::Chip8);
+
+        // XO-CHIP wins over SCHIP
+        let info = RomInfo::analyse(&[0x00, 0xFF, 0xF0, 0x01, 0x12, 0x04]);
+        assert_eq!(info.platforms_used().len(), 2);
+        assert_eq!(info.suggested_platform(), Platform::XoChip);
+
+        // As does not fitting in memory, even without extension instructions
+        let info = RomInfo::analyse(&vec![0x12; MAX_CHIP8_ROM_SIZE + 2]);
+        assert!(info.extension_uses.is_empty());
+        assert!(info.too_large());
+        assert_eq!(info.suggested_platform(), Platform::XoChip);
+        assert!(!RomInfo::analyse(&vec![0x12; MAX_CHIP8_ROM_SIZE]).too_large());
+    }
+
+    #[test]
+    /// Test following the control flow, so data isn't mistaken for instructions
+    fn test_reachable_instructions() {
+        let rom = [
+            0x22, 0x08, // 0x200: call 0x208
+            0x3A, 0x01, // 0x202: skip if VA == 1
+            0x12, 0x0A, // 0x204: jump 0x20A
+            0x00, 0xFF, // 0x206: high resolution
+            0x00, 0xEE, // 0x208: return
+            0x12, 0x0A, // 0x20A: jump 0x20A
+            0x00, 0xFE, // 0x20C: (data)
+        ];
+        let (reachable, computed_jump) = reachable_instructions(&rom);
+        assert_eq!(
+            reachable.into_iter().collect::<Vec<_>>(),
+            [0x0, 0x2, 0x4, 0x6, 0x8, 0xA]
+        );
+        assert!(!computed_jump);
+        // The skip might land on 0x206, so it counts, but 0x20C is never reached
+        let info = RomInfo::analyse(&rom);
+        assert_eq!(info.extension_uses.len(), 1);
+        assert_eq!(info.extension_uses[0].address, 0x206);
+
+        // Control flow stops at a computed jump
+        let (reachable, computed_jump) = reachable_instructions(&[0xB2, 0x00, 0x00, 0xFF]);
+        assert_eq!(reachable.len(), 1);
+        assert!(computed_jump);
+        assert!(
+            RomInfo::analyse(&[0xB2, 0x00])
+                .to_string()
+                .contains("computed jumps")
+        );
+    }
+
+    /// A zip archive of `files` (name and contents), deflating them if `deflate`
+    #[cfg(feature = "zip")]
+    fn build_zip(files: &[(&str, &[u8])], deflate: bool) -> Vec<u8> {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let method = if deflate {
+            zip::CompressionMethod::Deflated
+        } else {
+            zip::CompressionMethod::Stored
+        };
+        let options = SimpleFileOptions::default().compression_method(method);
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    #[cfg(feature = "zip")]
+    /// Test loading ROMs from zip archives
+    fn test_open_zip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let pong = [0x6A, 0x02, 0x12, 0x00];
+        let tetris = [0xA2, 0xB4, 0x12, 0x02];
+
+        // The only ROM is loaded, whatever else is in the archive
+        let single = dir.path().join("single.zip");
+        std::fs::write(
+            &single,
+            build_zip(&[("README.txt", b"Pong"), ("pong.ch8", &pong)], true),
+        )?;
+        assert_eq!(open(&single)?, pong);
+
+        // With several ROMs one has to be chosen
+        let pack = dir.path().join("Pack.ZIP");
+        std::fs::write(
+            &pack,
+            build_zip(
+                &[("games/pong.ch8", &pong), ("games/tetris.c8", &tetris)],
+                false,
+            ),
+        )?;
+        let message = open(&pack).unwrap_err().to_string();
+        assert!(message.contains("several ROMs"), "{message}");
+        assert!(
+            message.contains("games/pong.ch8, games/tetris.c8"),
+            "{message}"
+        );
+        let fragment = |name: &str| PathBuf::from(format!("{}#{name}", pack.display()));
+        assert_eq!(open(&fragment("games/tetris.c8"))?, tetris);
+        assert_eq!(
+            split_archive_path(&fragment("games/pong.ch8")),
+            (pack.clone(), Some("games/pong.ch8".to_string()))
+        );
+        let message = open(&fragment("pong.ch8")).unwrap_err().to_string();
+        assert!(message.contains("There is no pong.ch8"), "{message}");
+
+        // Too large once decompressed
+        let large = dir.path().join("large.zip");
+        let rom = vec![0; MAX_CHIP8_ROM_SIZE + 1];
+        std::fs::write(&large, build_zip(&[("large.ch8", &rom)], true))?;
+        let message = format!("{:#}", open(&large).unwrap_err());
+        assert!(message.contains("too large"), "{message}");
+
+        // Corrupt archives are errors naming the archive
+        let corrupt = dir.path().join("corrupt.zip");
+        std::fs::write(&corrupt, b"PK\x03\x04 not really a zip")?;
+        let message = format!("{:#}", open(&corrupt).unwrap_err());
+        assert!(message.contains("corrupt.zip"), "{message}");
+        let empty = dir.path().join("empty.zip");
+        std::fs::write(&empty, build_zip(&[("notes.txt", b"nothing")], false))?;
+        assert!(open(&empty).is_err());
+        let archive = build_zip(&[("pong.ch8", &pong)], false);
+        let truncated = dir.path().join("truncated.zip");
+        std::fs::write(&truncated, &archive[..archive.len() / 2])?;
+        assert!(open(&truncated).is_err());
+        // Damaged contents fail the checksum
+        let mut damaged = archive.clone();
+        let start = archive
+            .windows(pong.len())
+            .position(|window| window == pong)
+            .unwrap();
+        damaged[start] ^= 0xFF;
+        let path = dir.path().join("damaged.zip");
+        std::fs::write(&path, damaged)?;
+        assert!(open(&path).is_err());
+
+        // Other files are read as they are, even with a # in the name
+        let plain = dir.path().join("a#b.ch8");
+        std::fs::write(&plain, pong)?;
+        assert_eq!(open(&plain)?, pong);
+        assert_eq!(split_archive_path(&plain), (plain.clone(), None));
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::config::EmulatorConfig;
+use crate::rom::{Platform, sha1_hex};
+
+/// The embedded copy of the database, a trimmed copy of the CHIP-8 database's
+/// `programs.json` (with just the fields used here)
+const EMBEDDED_DATABASE: &str = include_str!("../resources/database/programs.json");
+
+/// What is known about a program from the database
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RomMetadata {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// When it was released (usually a year)
+    pub release: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A program in the database's `programs.json`, with the hashes of its ROMs
+#[derive(Deserialize)]
+struct Program {
+    #[serde(flatten)]
+    metadata: RomMetadata,
+    /// Keyed by SHA-1 hash
+    #[serde(default)]
+    roms: BTreeMap<String, Rom>,
+}
+
+/// A ROM of a program in the database, with just the fields used here
+#[derive(Deserialize)]
+struct Rom {
+    /// Ids of the platforms the ROM runs on, best first
+    #[serde(default)]
+    platforms: Vec<String>,
+}
+
+/// ROM titles and metadata, keyed by the SHA-1 hash of the ROM
+///
+/// Read from the `programs.json` of the community CHIP-8 database
+/// (<https://github.com/chip-8/chip-8-database>), a newer version of the file
+/// can be used with the rom_database setting in place of the embedded copy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomDatabase {
+    programs: Vec<RomMetadata>,
+    /// Index into `programs` of each ROM's program, keyed by the lowercase hash
+    hashes: HashMap<String, usize>,
+    /// The platform each ROM is best run as, keyed by the lowercase hash, for
+    /// the ROMs with one emul8rs has a profile for
+    platforms: HashMap<String, Platform>,
+}
+
+impl RomDatabase {
+    /// Parse a database in the format of `programs.json`
+    pub fn parse(json: &str) -> Result<Self> {
+        let parsed: Vec<Program> = serde_json::from_str(json)?;
+        let mut database = Self::default();
+        for program in parsed {
+            for (hash, rom) in &program.roms {
+                let hash = hash.to_ascii_lowercase();
+                let platform = rom
+                    .platforms
+                    .iter()
+                    .find_map(|id| Platform::from_database_id(id));
+                if let Some(platform) = platform {
+                    database.platforms.insert(hash.clone(), platform);
+                }
+                database.hashes.insert(hash, database.programs.len());
+            }
+            database.programs.push(program.metadata);
+        }
+        Ok(database)
+    }
+
+    /// Read the database at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading ROM database {}", path.display()))?;
+        Self::parse(&json).with_context(|| format!("Parsing ROM database {}", path.display()))
+    }
+
+    /// The database embedded in emul8rs, parsed the first time it's used
+    pub fn embedded() -> &'static Self {
+        static EMBEDDED: OnceLock<RomDatabase> = OnceLock::new();
+        EMBEDDED.get_or_init(|| {
+            Self::parse(EMBEDDED_DATABASE).expect("The embedded ROM database is valid")
+        })
+    }
+
+    /// `database`, or the embedded one if None
+    pub fn or_embedded(database: Option<&Self>) -> &Self {
+        database.unwrap_or_else(|| Self::embedded())
+    }
+
+    /// The database from the rom_database setting, None to use the embedded one
+    pub fn from_config(config: &EmulatorConfig) -> Result<Option<Self>> {
+        if config.rom_database.is_empty() {
+            return Ok(None);
+        }
+        Self::load(Path::new(&config.rom_database)).map(Some)
+    }
+
+    /// What is known about the ROM with SHA-1 hash `hash`, None if it isn't in
+    /// the database
+    pub fn lookup(&self, hash: &str) -> Option<&RomMetadata> {
+        let index = self.hashes.get(&hash.to_ascii_lowercase())?;
+        self.programs.get(*index)
+    }
+
+    /// The platform the ROM with SHA-1 hash `hash` is best run as, None if it
+    /// isn't in the database or its platforms aren't known
+    pub fn platform(&self, hash: &str) -> Option<Platform> {
+        self.platforms.get(&hash.to_ascii_lowercase()).copied()
+    }
+
+    /// Number of ROMs in the database
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// The title of `rom` (loaded from `path`), from the database if it's known
+    /// and otherwise the file name
+    pub fn title(&self, rom: &[u8], path: &Path) -> String {
+        match self.lookup(&sha1_hex(rom)) {
+            Some(metadata) if !metadata.title.is_empty() => metadata.title.clone(),
+            _ => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_rom_database {
+    use super::*;
+
+    /// A small database, in the upstream format (with fields that aren't used)
+    const FIXTURE: &str = r#"[
+        {
+            "title": "Pong",
+            "authors": ["Paul Vervalin"],
+            "release": "1990",
+            "description": "A two player game",
+            "origin": {"type": "manual"},
+            "roms": {
+                "AAAA000000000000000000000000000000000000": {"file": "pong.ch8"},
+                "bbbb000000000000000000000000000000000000": {"platforms": ["originalChip8"]}
+            }
+        },
+        {
+            "title": "Untitled demo",
+            "roms": {"cccc000000000000000000000000000000000000": {}}
+        }
+    ]"#;
+
+    #[test]
+    /// Test looking ROMs up by hash
+    fn test_lookup() -> Result<()> {
+        let database = RomDatabase::parse(FIXTURE)?;
+        assert_eq!(database.len(), 3);
+        let pong = database
+            .lookup("aaaa000000000000000000000000000000000000")
+            .unwrap();
+        assert_eq!(pong.title, "Pong");
+        assert_eq!(pong.authors, ["Paul Vervalin"]);
+        assert_eq!(pong.release.as_deref(), Some("1990"));
+        // Every ROM of a program, in either case
+        assert_eq!(
+            database.lookup("BBBB000000000000000000000000000000000000"),
+            Some(pong)
+        );
+        // Missing fields are empty
+        let demo = database
+            .lookup("cccc000000000000000000000000000000000000")
+            .unwrap();
+        assert_eq!(demo.title, "Untitled demo");
+        assert!(demo.authors.is_empty() && demo.description.is_none());
+        // Unknown hashes are just missing
+        assert_eq!(database.lookup("dddd"), None);
+        assert_eq!(database.lookup(""), None);
+        Ok(())
+    }
+
+    #[test]
+    /// Test the platform of each ROM is the first one with a profile
+    fn test_platform() -> Result<()> {
+        let database = RomDatabase::parse(
+            r#"[{
+                "title": "Blinky",
+                "roms": {
+                    "AAAA000000000000000000000000000000000000": {
+                        "platforms": ["megachip8", "superchip", "xochip"]
+                    },
+                    "bbbb000000000000000000000000000000000000": {"platforms": ["megachip8"]},
+                    "cccc000000000000000000000000000000000000": {}
+                }
+            }]"#,
+        )?;
+        assert_eq!(
+            database.platform("aaaa000000000000000000000000000000000000"),
+            Some(Platform::SuperChip)
+        );
+        assert_eq!(
+            database.platform("bbbb000000000000000000000000000000000000"),
+            None
+        );
+        assert_eq!(
+            database.platform("cccc000000000000000000000000000000000000"),
+            None
+        );
+        assert_eq!(database.platform("dddd"), None);
+        assert_eq!(
+            RomDatabase::parse(FIXTURE)?.platform("bbbb000000000000000000000000000000000000"),
+            Some(Platform::Chip8)
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test the title of a ROM, falling back to its file name
+    fn test_title() -> Result<()> {
+        let mut database = RomDatabase::parse(FIXTURE)?;
+        let rom = [0x00, 0xE0];
+        let path = Path::new("roms/my_game.ch8");
+        assert_eq!(database.title(&rom, path), "my_game.ch8");
+        database.hashes.insert(sha1_hex(&rom), 0);
+        assert_eq!(database.title(&rom, path), "Pong");
+        Ok(())
+    }
+
+    #[test]
+    /// Test the embedded database, which knows the ROMs in the repository
+    fn test_embedded() {
+        let database = RomDatabase::embedded();
+        assert!(database.programs.len() > 400);
+        assert_eq!(
+            database
+                .lookup("ea9af3c09b0d9e265fcd92bcc5d51a2939fdf27a")
+                .map(|metadata| metadata.title.as_str()),
+            Some("15 Puzzle")
+        );
+        let ibm_logo = include_bytes!("../tests/roms/ibm_logo.ch8");
+        assert_eq!(
+            database.title(ibm_logo, Path::new("ibm_logo.ch8")),
+            "IBM Logo"
+        );
+    }
+
+    #[test]
+    /// Test loading a database file, and the errors for bad ones
+    fn test_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("programs.json");
+        std::fs::write(&path, FIXTURE)?;
+        assert_eq!(RomDatabase::load(&path)?, RomDatabase::parse(FIXTURE)?);
+
+        std::fs::write(&path, "{\"title\": \"not a list\"}")?;
+        let message = format!("{:#}", RomDatabase::load(&path).unwrap_err());
+        assert!(message.contains("programs.json"), "{message}");
+        assert!(RomDatabase::load(&dir.path().join("missing.json")).is_err());
+        Ok(())
+    }
+}
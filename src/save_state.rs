@@ -0,0 +1,338 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::library::data_dir;
+
+/// Version of the save state format, bumped when it changes incompatibly
+pub const SAVE_STATE_VERSION: u32 = 2;
+
+/// Number of autosave files kept for each ROM, the oldest is overwritten by the
+/// next autosave
+pub const AUTOSAVE_SLOTS: usize = 3;
+
+/// Directory (under the data directory) the autosaves are written to
+const AUTOSAVE_DIR: &str = "autosaves";
+
+/// Everything needed to carry on running a program from where it was saved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveState {
+    /// Version of the format, [SAVE_STATE_VERSION] when written
+    pub version: u32,
+    /// SHA-1 of the ROM the state was saved from, as hex
+    pub rom_sha1: String,
+    /// When the state was saved, in milliseconds since the Unix epoch
+    pub saved_at: u64,
+    pub memory: Vec<u8>,
+    pub registers: Vec<u8>,
+    pub index_register: u16,
+    pub program_counter: usize,
+    pub stack: Vec<u16>,
+    /// Most entries the stack could hold when the state was saved (the
+    /// `stack_depth` setting)
+    pub stack_depth: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Each plane of the display, row by row
+    pub planes: Vec<Vec<bool>>,
+    /// Mask of the planes drawn to and cleared (see XO-CHIP's FN01)
+    pub selected_planes: u8,
+    /// Whether the display was in high resolution (SUPER-CHIP's 00FF), false
+    /// for states saved before it existed
+    #[serde(default)]
+    pub high_res: bool,
+    pub waiting_for_key_release: Option<u8>,
+    pub halted: bool,
+    pub cycles: u64,
+}
+
+impl SaveState {
+    /// Parse a save state from JSON written by [SaveState::to_json]
+    pub fn from_json(json: &str) -> Result<Self> {
+        let state: Self = serde_json::from_str(json)?;
+        if state.version != SAVE_STATE_VERSION {
+            bail!(
+                "Save state is version {}, but only version {SAVE_STATE_VERSION} can be loaded",
+                state.version
+            );
+        }
+        Ok(state)
+    }
+
+    /// The save state as JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).context("Converting save state to JSON")
+    }
+
+    /// Read a save state from the JSON file at `path`
+    pub fn read(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Reading save state {}", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("Parsing save state {}", path.display()))
+    }
+
+    /// Write the save state to `path` as JSON, creating its directory if needed
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Creating directory {}", parent.display()))?;
+        }
+        fs::write(path, self.to_json()? + "\n")
+            .with_context(|| format!("Writing save state {}", path.display()))
+    }
+}
+
+/// Milliseconds since the Unix epoch at `time` (0 for times before it)
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// The directory autosaves are kept in
+pub fn autosave_dir() -> Result<PathBuf> {
+    Ok(data_dir()?.join(AUTOSAVE_DIR))
+}
+
+/// Path of autosave `slot` for the ROM with SHA-1 `rom_sha1`, in `dir`
+pub fn autosave_path(dir: &Path, rom_sha1: &str, slot: usize) -> PathBuf {
+    dir.join(format!("{rom_sha1}.autosave{slot}.json"))
+}
+
+/// Path of the state saved for the ROM with SHA-1 `rom_sha1` in `dir` when the
+/// frontend fails mid-game, resumed from like an autosave (see [latest_autosave])
+pub fn emergency_save_path(dir: &Path, rom_sha1: &str) -> PathBuf {
+    dir.join(format!("{rom_sha1}.emergency.json"))
+}
+
+/// The slot the next autosave goes in, given when each slot was saved (None
+/// for an empty slot): the first empty slot, otherwise the oldest
+pub fn next_slot(saved_at: &[Option<u64>]) -> usize {
+    saved_at
+        .iter()
+        .position(Option::is_none)
+        .or_else(|| {
+            saved_at
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, saved_at)| **saved_at)
+                .map(|(slot, _)| slot)
+        })
+        .unwrap_or(0)
+}
+
+/// Read each of the autosaves for the ROM with SHA-1 `rom_sha1` in `dir`, None
+/// for missing slots, and for files which can't be read or are for another ROM
+fn read_autosaves(dir: &Path, rom_sha1: &str) -> Vec<Option<SaveState>> {
+    (0..AUTOSAVE_SLOTS)
+        .map(|slot| read_saved(&autosave_path(dir, rom_sha1, slot), rom_sha1))
+        .collect()
+}
+
+/// Read the state saved from the ROM with SHA-1 `rom_sha1` at `path`, None if
+/// there isn't one, and for files which can't be read or are for another ROM
+fn read_saved(path: &Path, rom_sha1: &str) -> Option<SaveState> {
+    if !path.exists() {
+        return None;
+    }
+    match SaveState::read(path) {
+        Ok(state) if state.rom_sha1 == rom_sha1 => Some(state),
+        Ok(state) => {
+            warn!(
+                "Ignoring {}, it was saved from ROM {} not {rom_sha1}",
+                path.display(),
+                state.rom_sha1
+            );
+            None
+        }
+        Err(err) => {
+            warn!("Ignoring autosave: {err:#}");
+            None
+        }
+    }
+}
+
+/// The most recent autosave in `dir` of the ROM with SHA-1 `rom_sha1`, or its
+/// emergency save (see [emergency_save_path]) if that is newer, if any
+pub fn latest_autosave(dir: &Path, rom_sha1: &str) -> Option<SaveState> {
+    read_autosaves(dir, rom_sha1)
+        .into_iter()
+        .flatten()
+        .chain(read_saved(&emergency_save_path(dir, rom_sha1), rom_sha1))
+        .max_by_key(|state| state.saved_at)
+}
+
+/// Writes save states of a running ROM at a fixed interval, rotating through
+/// [AUTOSAVE_SLOTS] files so a save interrupted part way through only loses
+/// the newest one
+#[derive(Debug)]
+pub struct Autosaver {
+    dir: PathBuf,
+    rom_sha1: String,
+    interval: Duration,
+    /// When each slot was saved (milliseconds since the Unix epoch), None for
+    /// empty slots
+    saved_at: Vec<Option<u64>>,
+    last_save: Instant,
+}
+
+impl Autosaver {
+    /// Autosave the ROM with SHA-1 `rom_sha1` into `dir` every `interval`,
+    /// starting an interval from `now` and carrying on the rotation of any
+    /// autosaves already there
+    pub fn new(dir: impl Into<PathBuf>, rom_sha1: &str, interval: Duration, now: Instant) -> Self {
+        let dir = dir.into();
+        let saved_at = read_autosaves(&dir, rom_sha1)
+            .iter()
+            .map(|state| state.as_ref().map(|state| state.saved_at))
+            .collect();
+        Self {
+            dir,
+            rom_sha1: rom_sha1.to_string(),
+            interval,
+            saved_at,
+            last_save: now,
+        }
+    }
+
+    /// SHA-1 of the ROM being autosaved
+    pub fn rom_sha1(&self) -> &str {
+        &self.rom_sha1
+    }
+
+    /// The directory autosaves are written to
+    pub fn dir(&self) -> PathBuf {
+        self.dir.clone()
+    }
+
+    /// Time between autosaves
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Whether an interval has passed since the last autosave, as of `now`
+    pub fn is_due(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_save) >= self.interval
+    }
+
+    /// Write `state` into the next slot, returning its path
+    ///
+    /// The interval starts again from `now` even if writing fails, so a full
+    /// disk isn't retried every instruction.
+    pub fn save(&mut self, state: &SaveState, now: Instant) -> Result<PathBuf> {
+        self.last_save = now;
+        let slot = next_slot(&self.saved_at);
+        let path = autosave_path(&self.dir, &self.rom_sha1, slot);
+        state.write(&path)?;
+        self.saved_at[slot] = Some(state.saved_at);
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod test_save_state {
+    use super::*;
+
+    /// A save state of the ROM with SHA-1 `rom_sha1`, saved at `saved_at`
+    fn state(rom_sha1: &str, saved_at: u64) -> SaveState {
+        SaveState {
+            version: SAVE_STATE_VERSION,
+            rom_sha1: rom_sha1.to_string(),
+            saved_at,
+            memory: vec![0; 16],
+            registers: vec![0; 16],
+            index_register: 0,
+            program_counter: 0x200,
+            stack: Vec::new(),
+            stack_depth: 16,
+            delay_timer: 0,
+            sound_timer: 0,
+            planes: Vec::new(),
+            selected_planes: 0,
+            high_res: false,
+            waiting_for_key_release: None,
+            halted: false,
+            cycles: saved_at,
+        }
+    }
+
+    #[test]
+    /// Test autosaves fill the empty slots, then overwrite the oldest
+    fn test_autosave_rotation() -> Result<()> {
+        assert_eq!(next_slot(&[None, None, None]), 0);
+        assert_eq!(next_slot(&[Some(5), None, Some(1)]), 1);
+        assert_eq!(next_slot(&[Some(5), Some(2), Some(9)]), 1);
+        assert_eq!(
+            autosave_path(Path::new("saves"), "abc", 2),
+            Path::new("saves").join("abc.autosave2.json")
+        );
+
+        let dir = tempfile::tempdir()?;
+        let start = Instant::now();
+        let mut autosaver = Autosaver::new(dir.path(), "abc", Duration::from_secs(10), start);
+        assert!(!autosaver.is_due(start + Duration::from_secs(9)));
+        assert!(autosaver.is_due(start + Duration::from_secs(10)));
+        let paths = (1..=4)
+            .map(|saved_at| autosaver.save(&state("abc", saved_at), start))
+            .collect::<Result<Vec<_>>>()?;
+        assert!(!autosaver.is_due(start));
+        // The fourth save overwrites the first
+        assert_eq!(paths[3], paths[0]);
+        assert_eq!(paths[0], autosave_path(dir.path(), "abc", 0));
+        assert_eq!(SaveState::read(&paths[0])?.saved_at, 4);
+        assert_eq!(fs::read_dir(dir.path())?.count(), AUTOSAVE_SLOTS);
+
+        // A new run carries on the rotation, overwriting the oldest
+        let mut autosaver = Autosaver::new(dir.path(), "abc", Duration::from_secs(10), start);
+        assert_eq!(
+            autosaver.save(&state("abc", 5), start)?,
+            autosave_path(dir.path(), "abc", 1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    /// Test resuming picks the newest autosave of the same ROM
+    fn test_latest_autosave() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(latest_autosave(dir.path(), "abc"), None);
+        state("abc", 20).write(&autosave_path(dir.path(), "abc", 0))?;
+        state("abc", 30).write(&autosave_path(dir.path(), "abc", 1))?;
+        state("abc", 10).write(&autosave_path(dir.path(), "abc", 2))?;
+        // Newer, but for other ROMs (one renamed to look like this ROM's)
+        state("def", 40).write(&autosave_path(dir.path(), "def", 0))?;
+        state("def", 50).write(&autosave_path(dir.path(), "abc", 1))?;
+        assert_eq!(
+            latest_autosave(dir.path(), "abc").map(|state| state.saved_at),
+            Some(20)
+        );
+        assert_eq!(
+            latest_autosave(dir.path(), "def").map(|state| state.saved_at),
+            Some(40)
+        );
+
+        // Unreadable files are skipped
+        fs::write(autosave_path(dir.path(), "abc", 0), "not json")?;
+        assert_eq!(
+            latest_autosave(dir.path(), "abc").map(|state| state.saved_at),
+            Some(10)
+        );
+        assert_eq!(latest_autosave(dir.path(), "ghi"), None);
+
+        // An emergency save is resumed from when it is the newest
+        state("abc", 15).write(&emergency_save_path(dir.path(), "abc"))?;
+        assert_eq!(
+            latest_autosave(dir.path(), "abc").map(|state| state.saved_at),
+            Some(15)
+        );
+        state("abc", 5).write(&emergency_save_path(dir.path(), "abc"))?;
+        assert_eq!(
+            latest_autosave(dir.path(), "abc").map(|state| state.saved_at),
+            Some(10)
+        );
+        Ok(())
+    }
+}
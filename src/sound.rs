@@ -0,0 +1,747 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::f32::consts::TAU;
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(feature = "std")]
+use anyhow::Context;
+use anyhow::{Result, bail};
+#[cfg(feature = "std")]
+use log::{debug, warn};
+
+#[cfg(feature = "std")]
+use crate::config::EmulatorConfig;
+
+/// Sample rate of the synthesized beep
+pub const SAMPLE_RATE: u32 = 44_100;
+/// Lowest allowed beep frequency (in Hz)
+pub const MIN_FREQUENCY_HZ: f32 = 20.0;
+/// Highest allowed beep frequency (in Hz)
+pub const MAX_FREQUENCY_HZ: f32 = 20_000.0;
+/// Approximate length of the synthesized beep, frontends loop it while the
+/// sound timer is running
+#[cfg(feature = "std")]
+const TONE_SECONDS: f32 = 1.0;
+
+/// Shortest time between two rings of the terminal bell
+pub const BELL_MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default shortest length of a beep (in milliseconds), see [BeepState]
+pub const DEFAULT_MIN_BEEP_MS: u64 = 50;
+
+/// Default length of the fade in and out of the beep (in milliseconds), see
+/// [Envelope]
+pub const DEFAULT_SOUND_RAMP_MS: u64 = 5;
+
+/// Types of sound file (by extension) which can be used for the beep
+pub const SOUND_FILE_TYPES: [&str; 2] = ["wav", "ogg"];
+
+/// Shape of the synthesized beep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    /// All of the waveforms
+    pub const ALL: [Waveform; 3] = [Waveform::Square, Waveform::Sine, Waveform::Triangle];
+
+    /// Name of the waveform as used in the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            Waveform::Square => "square",
+            Waveform::Sine => "sine",
+            Waveform::Triangle => "triangle",
+        }
+    }
+
+    /// Find the waveform with the given name (case-insensitive)
+    pub fn from_name(name: &str) -> Result<Self> {
+        let lower = name.trim().to_lowercase();
+        match Self::ALL.iter().find(|waveform| waveform.name() == lower) {
+            Some(waveform) => Ok(*waveform),
+            None => bail!(
+                "Unknown waveform {name:?}, available waveforms are: {}",
+                Self::ALL.map(|waveform| waveform.name()).join(", ")
+            ),
+        }
+    }
+
+    /// Value of the waveform (between -1 and 1) at `phase` through a cycle,
+    /// where a phase of 0 is the start of the cycle and 1 is the end
+    #[cfg(feature = "std")]
+    pub fn sample(&self, phase: f32) -> f32 {
+        let phase = phase.rem_euclid(1.0);
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (phase * TAU).sin(),
+            // Rises from -1 to 1 over the first half, then falls back
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+/// Generate `count` signed 16-bit samples of a tone at `sample_rate`
+///
+/// `volume` scales the amplitude, and should be between 0 and 1.
+#[cfg(feature = "std")]
+pub fn tone_samples(
+    waveform: Waveform,
+    frequency_hz: f32,
+    volume: f32,
+    sample_rate: u32,
+    count: usize,
+) -> Vec<i16> {
+    let amplitude = volume.clamp(0.0, 1.0) * i16::MAX as f32;
+    (0..count)
+        .map(|index| {
+            let phase = index as f32 * frequency_hz / sample_rate as f32;
+            (waveform.sample(phase) * amplitude).round() as i16
+        })
+        .collect()
+}
+
+/// Encode a tone as a mono 16-bit WAV file, ready to be loaded by a frontend
+///
+/// The tone is a whole number of cycles long, so it can be looped without clicking.
+#[cfg(feature = "std")]
+pub fn tone_wav(waveform: Waveform, frequency_hz: f32, volume: f32) -> Vec<u8> {
+    let cycles = (frequency_hz * TONE_SECONDS).round().max(1.0);
+    let count = (cycles * SAMPLE_RATE as f32 / frequency_hz).round() as usize;
+    let samples = tone_samples(waveform, frequency_hz, volume, SAMPLE_RATE, count);
+    encode_wav(&samples, SAMPLE_RATE)
+}
+
+/// Encoded audio for the beep, for a frontend to decode and loop while the
+/// sound timer is running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Beep {
+    /// Type of the audio, as a file extension with a leading dot (e.g. ".wav")
+    pub file_type: String,
+    /// Contents of the audio file
+    pub bytes: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl Beep {
+    /// Synthesize a beep with the given waveform and frequency
+    pub fn synthesized(waveform: Waveform, frequency_hz: f32) -> Self {
+        Self {
+            file_type: ".wav".to_string(),
+            bytes: tone_wav(waveform, frequency_hz, 1.0),
+        }
+    }
+
+    /// Read a beep from a sound file (see [SOUND_FILE_TYPES])
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase())
+            .unwrap_or_default();
+        if !SOUND_FILE_TYPES.contains(&extension.as_str()) {
+            bail!(
+                "Unsupported sound file {path:?}, expected one of: {}",
+                SOUND_FILE_TYPES.join(", ")
+            );
+        }
+        let bytes = std::fs::read(path).with_context(|| format!("Reading sound file {path:?}"))?;
+        Ok(Self {
+            file_type: format!(".{extension}"),
+            bytes,
+        })
+    }
+
+    /// The beep described by the config
+    ///
+    /// Uses `sound_file` when it is set, falling back to the synthesized beep
+    /// (with a warning) if the file can't be read.
+    pub fn from_config(config: &EmulatorConfig) -> Result<Self> {
+        let synthesized = || -> Result<Self> {
+            let waveform = Waveform::from_name(&config.sound_waveform)?;
+            debug!(
+                "Synthesizing {} wave beep at {}Hz",
+                waveform.name(),
+                config.sound_frequency_hz
+            );
+            Ok(Self::synthesized(waveform, config.sound_frequency_hz))
+        };
+        if config.sound_file.is_empty() {
+            return synthesized();
+        }
+        match Self::from_file(Path::new(&config.sound_file)) {
+            Ok(beep) => {
+                debug!("Loaded beep from {:?}", config.sound_file);
+                Ok(beep)
+            }
+            Err(err) => {
+                warn!("{err:#}, using the built in beep instead");
+                synthesized()
+            }
+        }
+    }
+}
+
+/// The terminal bell, for frontends without real audio to use as the beep
+///
+/// Rings are rate limited (see [BELL_MIN_INTERVAL]), so a ROM starting the sound
+/// timer over and over doesn't spam the terminal.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct Bell {
+    /// Whether ringing the bell does anything
+    enabled: bool,
+    /// When the bell last rang
+    last_rung: Option<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl Bell {
+    /// Create a bell, which never rings if not `enabled`
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            last_rung: None,
+        }
+    }
+
+    /// Create a bell, enabled by the config's `bell_on_beep` setting
+    pub fn from_config(config: &EmulatorConfig) -> Self {
+        Self::new(config.bell_on_beep)
+    }
+
+    /// Whether ringing the bell does anything
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Ring the bell on stdout, returning whether it actually rang
+    pub fn ring(&mut self) -> Result<bool> {
+        self.ring_to(&mut std::io::stdout(), Instant::now())
+    }
+
+    /// Ring the bell by writing to `out` at time `now`, unless it is disabled
+    /// or rang too recently, returning whether it actually rang
+    pub fn ring_to<W: Write>(&mut self, out: &mut W, now: Instant) -> Result<bool> {
+        if !self.enabled {
+            return Ok(false);
+        }
+        if let Some(last_rung) = self.last_rung
+            && now.saturating_duration_since(last_rung) < BELL_MIN_INTERVAL
+        {
+            return Ok(false);
+        }
+        out.write_all(b"\x07")
+            .context("Ringing the terminal bell")?;
+        out.flush().context("Ringing the terminal bell")?;
+        self.last_rung = Some(now);
+        Ok(true)
+    }
+}
+
+/// A change to make to the beep, returned by [BeepState::update]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeepChange {
+    /// Start playing the beep
+    Start,
+    /// Stop playing the beep
+    Stop,
+}
+
+/// Whether the beep is playing, holding it for a minimum duration
+///
+/// A sound timer of 1 or 2 only lasts 16-33ms, short enough to be inaudible (or
+/// just a click), so once started the beep isn't stopped until at least
+/// `min_duration` has passed. If the sound timer is set again in the meantime
+/// the beep carries on, rather than stopping and starting with a gap.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct BeepState {
+    /// Shortest time the beep plays for
+    min_duration: Duration,
+    /// When the beep started playing, None if it isn't playing
+    started: Option<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl BeepState {
+    /// Create the state, with the beep not playing
+    pub fn new(min_duration: Duration) -> Self {
+        Self {
+            min_duration,
+            started: None,
+        }
+    }
+
+    /// Create the state, with the minimum duration from the config's `min_beep_ms`
+    pub fn from_config(config: &EmulatorConfig) -> Self {
+        Self::new(Duration::from_millis(config.min_beep_ms))
+    }
+
+    /// Whether the beep is playing
+    pub fn is_playing(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Update the state at time `now`, given whether the beep should be
+    /// `sounding` (the sound timer is running), returning whether the beep needs
+    /// to be started or stopped
+    pub fn update(&mut self, sounding: bool, now: Instant) -> Option<BeepChange> {
+        match (self.started, sounding) {
+            (None, true) => {
+                self.started = Some(now);
+                Some(BeepChange::Start)
+            }
+            (Some(started), false)
+                if now.saturating_duration_since(started) >= self.min_duration =>
+            {
+                self.started = None;
+                Some(BeepChange::Stop)
+            }
+            _ => None,
+        }
+    }
+
+    /// Stop straight away, ignoring the minimum duration (e.g. when muting),
+    /// returning whether the beep was playing and needs to be stopped
+    pub fn stop(&mut self) -> bool {
+        self.started.take().is_some()
+    }
+}
+
+/// Amplitude envelope of the beep, fading it in when it starts and out when it
+/// stops, rather than switching it part way through a cycle (which pops)
+///
+/// The gain ramps linearly between 0 and 1, taking `ramp` for a full fade. A
+/// beep started again while fading out ramps back up from wherever it had got
+/// to, rather than dropping to 0 first.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    /// Time taken to fade all the way in or out
+    ramp: Duration,
+    /// Whether the beep is on (fading in or sustaining)
+    on: bool,
+    /// Gain when the envelope last changed direction
+    start_gain: f32,
+    /// When the envelope last changed direction, None if it never has
+    changed: Option<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl Envelope {
+    /// Create an envelope taking `ramp` to fade in or out, starting silent
+    pub fn new(ramp: Duration) -> Self {
+        Self {
+            ramp,
+            on: false,
+            start_gain: 0.0,
+            changed: None,
+        }
+    }
+
+    /// Create an envelope with the ramp time from the config's `sound_ramp_ms`
+    pub fn from_config(config: &EmulatorConfig) -> Self {
+        Self::new(Duration::from_millis(config.sound_ramp_ms))
+    }
+
+    /// Start fading in (`on`) or out at time `now`, from the current gain
+    pub fn trigger(&mut self, on: bool, now: Instant) {
+        if on == self.on {
+            return;
+        }
+        self.start_gain = self.gain(now);
+        self.on = on;
+        self.changed = Some(now);
+    }
+
+    /// Gain (between 0 and 1) to apply to the beep at time `now`
+    pub fn gain(&self, now: Instant) -> f32 {
+        let target = if self.on { 1.0 } else { 0.0 };
+        let Some(changed) = self.changed else {
+            return target;
+        };
+        if self.ramp.is_zero() {
+            return target;
+        }
+        let progress =
+            now.saturating_duration_since(changed).as_secs_f32() / self.ramp.as_secs_f32();
+        if self.on {
+            (self.start_gain + progress).min(1.0)
+        } else {
+            (self.start_gain - progress).max(0.0)
+        }
+    }
+
+    /// Whether the beep has finished fading out at time `now`, so it can be
+    /// stopped entirely
+    pub fn is_silent(&self, now: Instant) -> bool {
+        !self.on && self.gain(now) == 0.0
+    }
+
+    /// Gains for `count` consecutive samples at `sample_rate`, the first at time
+    /// `start`
+    pub fn sample_gains(&self, start: Instant, sample_rate: u32, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|index| {
+                self.gain(start + Duration::from_secs_f64(index as f64 / sample_rate as f64))
+            })
+            .collect()
+    }
+}
+
+/// Encode mono 16-bit samples as a WAV file
+#[cfg(feature = "std")]
+fn encode_wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    const CHANNELS: u16 = 1;
+    const BITS_PER_SAMPLE: u16 = 16;
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_len = (samples.len() * block_align as usize) as u32;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    // RIFF header
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    // Format chunk (PCM)
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&CHANNELS.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    // Data chunk
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod test_sound {
+    use super::*;
+
+    /// Check two floats are (nearly) equal
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    /// Test the gain fades in and out linearly over the ramp
+    fn test_envelope_ramps() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut envelope = Envelope::new(Duration::from_millis(4));
+        assert_close(envelope.gain(start), 0.0);
+        assert!(envelope.is_silent(start));
+
+        envelope.trigger(true, start);
+        assert_close(envelope.gain(ms(1)), 0.25);
+        assert_close(envelope.gain(ms(2)), 0.5);
+        assert_close(envelope.gain(ms(4)), 1.0);
+        assert_close(envelope.gain(ms(100)), 1.0);
+        // Triggering again while on doesn't restart the fade
+        envelope.trigger(true, ms(100));
+        assert_close(envelope.gain(ms(100)), 1.0);
+
+        envelope.trigger(false, ms(100));
+        assert!(!envelope.is_silent(ms(100)));
+        assert_close(envelope.gain(ms(101)), 0.75);
+        assert_close(envelope.gain(ms(103)), 0.25);
+        assert_close(envelope.gain(ms(104)), 0.0);
+        assert!(envelope.is_silent(ms(104)));
+    }
+
+    #[test]
+    /// Test starting the beep again while it fades out ramps up from the
+    /// current gain, rather than dropping to 0
+    fn test_envelope_retrigger() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut envelope = Envelope::new(Duration::from_millis(4));
+        envelope.trigger(true, start);
+        envelope.trigger(false, ms(10));
+        assert_close(envelope.gain(ms(13)), 0.25);
+        envelope.trigger(true, ms(13));
+        assert_close(envelope.gain(ms(13)), 0.25);
+        assert_close(envelope.gain(ms(14)), 0.5);
+        assert_close(envelope.gain(ms(16)), 1.0);
+
+        // Likewise stopping part way through the fade in
+        envelope.trigger(false, ms(20));
+        envelope.trigger(true, ms(22));
+        envelope.trigger(false, ms(23));
+        assert_close(envelope.gain(ms(23)), 0.75);
+        assert_close(envelope.gain(ms(25)), 0.25);
+    }
+
+    #[test]
+    /// Test the gains of individual samples, and switching without a ramp
+    fn test_envelope_samples() {
+        let start = Instant::now();
+        let mut envelope = Envelope::new(Duration::from_millis(1));
+        envelope.trigger(true, start);
+        // 8 samples a millisecond, so the ramp takes 8 samples
+        let gains = envelope.sample_gains(start, 8_000, 10);
+        let expected = [0.0, 0.125, 0.25, 0.375, 0.5, 0.625, 0.75, 0.875, 1.0, 1.0];
+        for (gain, expected) in gains.iter().zip(expected) {
+            assert_close(*gain, expected);
+        }
+
+        let mut envelope = Envelope::new(Duration::ZERO);
+        envelope.trigger(true, start);
+        assert_close(envelope.gain(start), 1.0);
+        envelope.trigger(false, start);
+        assert!(envelope.is_silent(start));
+    }
+
+    #[test]
+    /// Test a beep shorter than the minimum is held until the minimum has passed
+    fn test_beep_state_short() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut beep = BeepState::new(Duration::from_millis(50));
+        assert_eq!(beep.update(false, start), None);
+        assert_eq!(beep.update(true, start), Some(BeepChange::Start));
+        assert!(beep.is_playing());
+        // The sound timer ran out after one tick, but the beep carries on
+        assert_eq!(beep.update(false, ms(17)), None);
+        assert_eq!(beep.update(false, ms(49)), None);
+        assert!(beep.is_playing());
+        assert_eq!(beep.update(false, ms(50)), Some(BeepChange::Stop));
+        assert!(!beep.is_playing());
+        assert_eq!(beep.update(false, ms(60)), None);
+    }
+
+    #[test]
+    /// Test a beep started again before the minimum has passed is extended,
+    /// without a gap
+    fn test_beep_state_back_to_back() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut beep = BeepState::new(Duration::from_millis(50));
+        assert_eq!(beep.update(true, start), Some(BeepChange::Start));
+        assert_eq!(beep.update(false, ms(17)), None);
+        // Set again while being held, it keeps playing
+        assert_eq!(beep.update(true, ms(30)), None);
+        assert_eq!(beep.update(true, ms(70)), None);
+        assert!(beep.is_playing());
+        // Past the minimum, so it stops as soon as the timer runs out
+        assert_eq!(beep.update(false, ms(80)), Some(BeepChange::Stop));
+        // A new beep starts afresh, and is held for the minimum again
+        assert_eq!(beep.update(true, ms(90)), Some(BeepChange::Start));
+        assert_eq!(beep.update(false, ms(100)), None);
+        assert_eq!(beep.update(false, ms(140)), Some(BeepChange::Stop));
+    }
+
+    #[test]
+    /// Test a beep longer than the minimum stops when the timer runs out, and
+    /// stopping early
+    fn test_beep_state_long() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut beep = BeepState::new(Duration::from_millis(50));
+        assert_eq!(beep.update(true, start), Some(BeepChange::Start));
+        assert_eq!(beep.update(true, ms(500)), None);
+        assert_eq!(beep.update(false, ms(1000)), Some(BeepChange::Stop));
+
+        // Stopping ignores the minimum
+        assert_eq!(beep.update(true, ms(1100)), Some(BeepChange::Start));
+        assert!(beep.stop());
+        assert!(!beep.is_playing());
+        assert!(!beep.stop());
+
+        // With no minimum the beep follows the timer exactly
+        let mut beep = BeepState::new(Duration::ZERO);
+        assert_eq!(beep.update(true, start), Some(BeepChange::Start));
+        assert_eq!(beep.update(false, start), Some(BeepChange::Stop));
+    }
+
+    #[test]
+    /// Test parsing the waveform names
+    fn test_waveform_names() -> Result<()> {
+        for waveform in Waveform::ALL {
+            assert_eq!(Waveform::from_name(waveform.name())?, waveform);
+        }
+        assert_eq!(Waveform::from_name("Sine")?, Waveform::Sine);
+        assert!(Waveform::from_name("sawtooth").is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test the square wave is high for the first half of the cycle, then low
+    fn test_square_shape() {
+        assert_close(Waveform::Square.sample(0.0), 1.0);
+        assert_close(Waveform::Square.sample(0.49), 1.0);
+        assert_close(Waveform::Square.sample(0.5), -1.0);
+        assert_close(Waveform::Square.sample(0.99), -1.0);
+        // Phases wrap around
+        assert_close(Waveform::Square.sample(1.25), 1.0);
+    }
+
+    #[test]
+    /// Test the sine wave hits its peaks and zero crossings
+    fn test_sine_shape() {
+        assert_close(Waveform::Sine.sample(0.0), 0.0);
+        assert_close(Waveform::Sine.sample(0.25), 1.0);
+        assert_close(Waveform::Sine.sample(0.75), -1.0);
+        assert!(Waveform::Sine.sample(0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    /// Test the triangle wave ramps linearly between its peaks
+    fn test_triangle_shape() {
+        assert_close(Waveform::Triangle.sample(0.0), -1.0);
+        assert_close(Waveform::Triangle.sample(0.25), 0.0);
+        assert_close(Waveform::Triangle.sample(0.5), 1.0);
+        assert_close(Waveform::Triangle.sample(0.75), 0.0);
+        assert_close(Waveform::Triangle.sample(0.125), -0.5);
+    }
+
+    #[test]
+    /// Test every waveform stays within -1 to 1
+    fn test_waveform_bounds() {
+        for waveform in Waveform::ALL {
+            for step in 0..1000 {
+                let sample = waveform.sample(step as f32 / 1000.0);
+                assert!((-1.0..=1.0).contains(&sample), "{waveform:?}: {sample}");
+            }
+        }
+    }
+
+    #[test]
+    /// Test generating samples at a given frequency and volume
+    fn test_tone_samples() {
+        // 4 samples per cycle
+        let samples = tone_samples(Waveform::Square, 100.0, 1.0, 400, 8);
+        assert_eq!(
+            samples,
+            vec![
+                i16::MAX,
+                i16::MAX,
+                -i16::MAX,
+                -i16::MAX,
+                i16::MAX,
+                i16::MAX,
+                -i16::MAX,
+                -i16::MAX
+            ]
+        );
+
+        // Volume scales the amplitude, and silence is silent
+        let half = tone_samples(Waveform::Square, 100.0, 0.5, 400, 4);
+        assert_eq!(half[0], (i16::MAX as f32 * 0.5).round() as i16);
+        assert!(
+            tone_samples(Waveform::Sine, 440.0, 0.0, SAMPLE_RATE, 100)
+                .iter()
+                .all(|&sample| sample == 0)
+        );
+    }
+
+    #[test]
+    /// Test the config's sound file is used for the beep
+    fn test_beep_from_file() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("emul8rs_beep_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("tone.WAV");
+        std::fs::write(&path, b"not really a wav")?;
+
+        let config = EmulatorConfig {
+            sound_file: path.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let beep = Beep::from_config(&config)?;
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(beep.file_type, ".wav");
+        assert_eq!(beep.bytes, b"not really a wav");
+        Ok(())
+    }
+
+    #[test]
+    /// Test falling back to the synthesized beep when the sound file can't be used
+    fn test_beep_fallback() -> Result<()> {
+        let synthesized = Beep::synthesized(Waveform::Square, 440.0);
+        assert_eq!(Beep::from_config(&EmulatorConfig::default())?, synthesized);
+
+        for bogus in ["/not/a/real/path/beep.wav", "beep.mp3", "beep"] {
+            assert!(Beep::from_file(Path::new(bogus)).is_err());
+            let config = EmulatorConfig {
+                sound_file: bogus.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(Beep::from_config(&config)?, synthesized);
+        }
+        Ok(())
+    }
+
+    #[test]
+    /// Test the bell rings, but not more often than the minimum interval
+    fn test_bell_rate_limit() -> Result<()> {
+        let mut bell = Bell::new(true);
+        let mut out = Vec::new();
+        let start = Instant::now();
+
+        assert!(bell.ring_to(&mut out, start)?);
+        assert_eq!(out, b"\x07");
+        // Repeated rings within the interval are dropped
+        assert!(!bell.ring_to(&mut out, start)?);
+        assert!(!bell.ring_to(&mut out, start + BELL_MIN_INTERVAL / 2)?);
+        assert_eq!(out, b"\x07");
+        // But ring again once it has passed
+        assert!(bell.ring_to(&mut out, start + BELL_MIN_INTERVAL)?);
+        assert_eq!(out, b"\x07\x07");
+        Ok(())
+    }
+
+    #[test]
+    /// Test a disabled bell never rings
+    fn test_bell_disabled() -> Result<()> {
+        let config = EmulatorConfig {
+            bell_on_beep: false,
+            ..Default::default()
+        };
+        let mut bell = Bell::from_config(&config);
+        let mut out = Vec::new();
+        assert!(!bell.ring_to(&mut out, Instant::now())?);
+        assert!(out.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    /// Test the WAV encoding of a tone
+    fn test_tone_wav() {
+        let wav = tone_wav(Waveform::Triangle, 440.0, 1.0);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        let sample_rate = u32::from_le_bytes(wav[24..28].try_into().unwrap());
+        assert_eq!(sample_rate, SAMPLE_RATE);
+        let data_len = u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize;
+        assert_eq!(wav.len(), 44 + data_len);
+        // Roughly a second long
+        let seconds = (data_len / 2) as f32 / SAMPLE_RATE as f32;
+        assert!((seconds - TONE_SECONDS).abs() < 0.01);
+    }
+}
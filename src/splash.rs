@@ -0,0 +1,69 @@
+use anyhow::Result;
+
+use crate::display::{DISPLAY_COLS, DISPLAY_ROWS, Display};
+
+/// Size of each pixel of the splash pattern, in display pixels
+const SPLASH_SCALE: usize = 2;
+
+/// The emulator's name, drawn by [draw_splash] (`#` for pixels that are on)
+const SPLASH: [&str; 5] = [
+    "### #   # # # #   ### ##  ###",
+    "#   ## ## # # #   # # # # #  ",
+    "##  # # # # # #   ### ##  ###",
+    "#   #   # # # #   # # # #   #",
+    "### #   # ### ### ### # # ###",
+];
+
+/// Clear the display and draw the splash pattern (the emulator's name) in the
+/// middle of it
+pub fn draw_splash(display: &mut Display) -> Result<()> {
+    display.clear()?;
+    let height = SPLASH.len() * SPLASH_SCALE;
+    let width = SPLASH[0].len() * SPLASH_SCALE;
+    let top = (DISPLAY_ROWS - height) / 2;
+    let left = (DISPLAY_COLS - width) / 2;
+    for (row, line) in SPLASH.iter().enumerate() {
+        for (col, cell) in line.chars().enumerate() {
+            if cell != '#' {
+                continue;
+            }
+            for dy in 0..SPLASH_SCALE {
+                for dx in 0..SPLASH_SCALE {
+                    display.set(
+                        top + row * SPLASH_SCALE + dy,
+                        left + col * SPLASH_SCALE + dx,
+                        true,
+                    )?;
+                }
+            }
+        }
+    }
+    display.needs_redraw = true;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_splash {
+    use super::*;
+
+    #[test]
+    /// Test the splash is drawn, scaled up, in the middle of the display
+    fn test_draw_splash() -> Result<()> {
+        let mut display = Display::new();
+        display.set(0, 0, true)?;
+        draw_splash(&mut display)?;
+        // Anything drawn before is cleared
+        assert!(!display.get(0, 0)?);
+        // The top left pixel of the E, and its scaled up copies
+        let (top, left) = (11, 3);
+        for (row, col) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            assert!(display.get(top + row, left + col)?);
+        }
+        assert!(!display.get(top - 1, left)?);
+        assert!(!display.get(top, left - 1)?);
+        let lit = display.iter_cells().filter(|cell| **cell).count();
+        let pattern_lit: usize = SPLASH.iter().map(|line| line.matches('#').count()).sum();
+        assert_eq!(lit, pattern_lit * SPLASH_SCALE * SPLASH_SCALE);
+        Ok(())
+    }
+}
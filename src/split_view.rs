@@ -0,0 +1,459 @@
+//! Two emulators running the same program side by side, with different
+//! settings (e.g. two quirk profiles), to see how a program behaves under each
+//!
+//! Both get the same keys, and run a frame each per frame shown, so the halves
+//! stay in step. Pausing, stepping a frame and resetting apply to both. An
+//! error stops just the half it happened in, which shows it until reset.
+
+use std::path::Path;
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use log::{debug, error, info};
+
+use crate::config::EmulatorConfig;
+use crate::emulator::{Emulator, TIMER_TICK};
+use crate::error_screen::{ERROR_SCREEN_COLUMNS, error_lines};
+use crate::frontend::Frontend;
+use crate::host_frontend::{HostFrontend, HostKeys};
+use crate::hotkeys::HotkeyAction;
+use crate::memory_layout::MemoryLayout;
+
+/// Where each half of the split view is drawn in a window, with a strip for
+/// the labels along the top
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitLayout {
+    /// Height of the strip the labels are drawn in
+    pub label_height: i32,
+    /// Width of each half
+    pub half_width: i32,
+    /// Height of each half's display, below the labels
+    pub display_height: i32,
+}
+
+impl SplitLayout {
+    /// The layout of a window `window_width` by `window_height` pixels
+    pub fn new(window_width: i32, window_height: i32) -> Self {
+        let label_height = (window_height / 12).max(10);
+        let half_width = window_width / 2;
+        Self {
+            label_height,
+            half_width,
+            display_height: window_height - label_height,
+        }
+    }
+
+    /// Size of each pixel of a display `rows` by `cols` pixels drawn in a half,
+    /// never smaller than a window pixel
+    pub fn cell_size(&self, rows: usize, cols: usize) -> (i32, i32) {
+        (
+            (self.half_width / cols as i32).max(1),
+            (self.display_height / rows as i32).max(1),
+        )
+    }
+
+    /// Top left corner of the display in half `half` (0 for the left, 1 for
+    /// the right)
+    pub fn origin(&self, half: usize) -> (i32, i32) {
+        (half as i32 * self.half_width, self.label_height)
+    }
+}
+
+/// The config for the window of a split view, twice as wide as that of a
+/// single display in `config`
+pub fn split_window_config(config: &EmulatorConfig) -> EmulatorConfig {
+    let (width, height) = config.window_size();
+    EmulatorConfig {
+        window_scale: 0,
+        window_width: width * 2,
+        window_height: height,
+        ..config.clone()
+    }
+}
+
+/// One half of the split view
+struct Half {
+    emulator: Emulator<'static>,
+    label: String,
+    /// The lines of the error which stopped this half, empty while it runs
+    error: Vec<String>,
+}
+
+/// Two emulators shown side by side by one frontend, which they share the
+/// keys of
+pub struct SplitView<'a> {
+    frontend: Box<dyn Frontend + 'a>,
+    halves: [Half; 2],
+    /// Keys down in the frontend, passed on to both emulators
+    keys: HostKeys,
+    paused: bool,
+    /// Whether the frontend is playing the beep
+    beeping: bool,
+}
+
+impl<'a> SplitView<'a> {
+    /// Show the program in `layout` (read from `program`) running with each of
+    /// `configs` in `frontend`, each with its label
+    ///
+    /// Both halves draw the same random numbers (from `seed`), and count their
+    /// timers down once a frame so they stay in step, with the frontend playing
+    /// the beep while either is beeping.
+    pub fn new(
+        frontend: Box<dyn Frontend + 'a>,
+        configs: [(EmulatorConfig, String); 2],
+        layout: &MemoryLayout,
+        program: &Path,
+        seed: u64,
+    ) -> Result<Self> {
+        let keys = HostKeys::default();
+        let [left, right] = configs.map(|(config, label)| {
+            let config = EmulatorConfig {
+                synchronous_timers: true,
+                audio_enabled: false,
+                ..config
+            };
+            (config, label)
+        });
+        let half = |(config, label): (EmulatorConfig, String)| -> Result<Half> {
+            let mut emulator = Emulator::new(Box::new(HostFrontend::new(keys.clone())), config)
+                .with_context(|| format!("Creating the {label} emulator"))?;
+            emulator
+                .load_layout(layout, program)
+                .with_context(|| format!("Loading {program:?} into the {label} emulator"))?;
+            emulator.seed_rng(seed);
+            Ok(Half {
+                emulator,
+                label,
+                error: Vec::new(),
+            })
+        };
+        let halves = [half(left)?, half(right)?];
+        Ok(Self {
+            frontend,
+            halves,
+            keys,
+            paused: false,
+            beeping: false,
+        })
+    }
+
+    /// The emulators, left then right
+    pub fn emulators(&self) -> [&Emulator<'static>; 2] {
+        [&self.halves[0].emulator, &self.halves[1].emulator]
+    }
+
+    /// Whether both halves are paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The lines of the error which stopped each half, left then right, empty
+    /// for a half which is running
+    pub fn errors(&self) -> [&[String]; 2] {
+        [&self.halves[0].error, &self.halves[1].error]
+    }
+
+    /// Run until the frontend asks to stop, a frame every 60th of a second
+    pub fn run(&mut self) -> Result<()> {
+        info!("Running the split view");
+        while !self.frontend.should_stop() {
+            let start = Instant::now();
+            self.frame()?;
+            thread::sleep(TIMER_TICK.saturating_sub(start.elapsed()));
+        }
+        Ok(())
+    }
+
+    /// Handle the hotkeys, run a frame in both halves (unless paused, or
+    /// stopped by an error), and draw them
+    pub fn frame(&mut self) -> Result<()> {
+        if self.frontend.check_hotkey(HotkeyAction::Pause)? {
+            self.paused = !self.paused;
+            debug!("Split view paused: {}", self.paused);
+        }
+        if self.frontend.check_hotkey(HotkeyAction::Reset)? {
+            for half in &mut self.halves {
+                half.emulator.reset()?;
+                half.error.clear();
+            }
+        }
+        let step = self.frontend.check_hotkey(HotkeyAction::Step)? && self.paused;
+        self.keys.set_all(self.frontend.current_keys()?);
+        if !self.paused || step {
+            for half in self.halves.iter_mut().filter(|half| half.error.is_empty()) {
+                if let Err(err) = half.emulator.run_frame() {
+                    error!("The {} half stopped: {err:#}", half.label);
+                    // Half the width of the error screen, to fit in the half
+                    half.error =
+                        error_lines(&err, half.emulator.cycles(), ERROR_SCREEN_COLUMNS / 2);
+                }
+            }
+        }
+        let beep = !self.paused
+            && self
+                .halves
+                .iter()
+                .any(|half| half.error.is_empty() && half.emulator.sound_timer() > 0);
+        self.frontend.set_sound_active(beep);
+        if self.frontend.capabilities().has_audio && beep != self.beeping {
+            if beep {
+                self.frontend.play_sound()?;
+            } else {
+                self.frontend.stop_sound()?;
+            }
+            self.beeping = beep;
+        }
+        let [left, right] = &self.halves;
+        self.frontend.draw_split(
+            [left.emulator.display(), right.emulator.display()],
+            [&left.label, &right.label],
+            [&left.error, &right.error],
+        )?;
+        self.frontend.step()
+    }
+}
+
+#[cfg(test)]
+mod test_split_view {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use crate::display::{DISPLAY_COLS, DISPLAY_ROWS, Display, HIGH_RES_COLS, HIGH_RES_ROWS};
+    use crate::rom::Platform;
+
+    /// What [SplitFrontend] was asked to draw, the pixels of each half, their
+    /// labels, and their errors
+    type Drawn = Rc<RefCell<Vec<([Vec<bool>; 2], [String; 2], [Vec<String>; 2])>>>;
+
+    /// Frontend holding down scripted keys and pressing scripted hotkeys, by
+    /// frame, and recording what it draws
+    struct SplitFrontend {
+        frame: u32,
+        /// Frames on which each hotkey is pressed
+        hotkeys: Vec<(u32, HotkeyAction)>,
+        /// Keys held down from the given frame on
+        keys: Vec<(u32, u16)>,
+        drawn: Drawn,
+    }
+
+    impl Frontend for SplitFrontend {
+        fn draw(&mut self, _display: &Display) -> Result<()> {
+            panic!("The split view should draw both halves");
+        }
+
+        fn draw_split(
+            &mut self,
+            displays: [&Display; 2],
+            labels: [&str; 2],
+            errors: [&[String]; 2],
+        ) -> Result<()> {
+            let pixels = displays.map(|display| display.iter_cells().copied().collect());
+            self.drawn.borrow_mut().push((
+                pixels,
+                labels.map(str::to_string),
+                errors.map(<[String]>::to_vec),
+            ));
+            Ok(())
+        }
+
+        fn check_key(&mut self, key: u8) -> Result<bool> {
+            let keys = self
+                .keys
+                .iter()
+                .take_while(|(frame, _)| *frame <= self.frame)
+                .last()
+                .map_or(0, |(_, keys)| *keys);
+            Ok(keys & (1 << key) != 0)
+        }
+
+        fn check_hotkey(&mut self, action: HotkeyAction) -> Result<bool> {
+            Ok(self.hotkeys.contains(&(self.frame, action)))
+        }
+
+        fn play_sound(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn stop_sound(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn should_stop(&mut self) -> bool {
+            false
+        }
+
+        fn step(&mut self) -> Result<()> {
+            self.frame += 1;
+            Ok(())
+        }
+    }
+
+    /// A split view of `rom`, comparing the original CHIP-8 with SUPER-CHIP
+    fn split_view(
+        rom: &[u8],
+        hotkeys: Vec<(u32, HotkeyAction)>,
+        keys: Vec<(u32, u16)>,
+    ) -> Result<(SplitView<'static>, Drawn)> {
+        let drawn = Drawn::default();
+        let frontend = SplitFrontend {
+            frame: 0,
+            hotkeys,
+            keys,
+            drawn: drawn.clone(),
+        };
+        let path = PathBuf::from("test.ch8");
+        let layout = MemoryLayout::new(
+            Some(("test.ch8".to_string(), rom.to_vec())),
+            Vec::new(),
+            None,
+        )?;
+        let profile = |platform: Platform| -> Result<(EmulatorConfig, String)> {
+            Ok((
+                EmulatorConfig::default().merge(&platform.quirks())?,
+                platform.name().to_string(),
+            ))
+        };
+        let view = SplitView::new(
+            Box::new(frontend),
+            [profile(Platform::Chip8)?, profile(Platform::SuperChip)?],
+            &layout,
+            &path,
+            7,
+        )?;
+        Ok((view, drawn))
+    }
+
+    #[test]
+    /// Test the window is split into two halves below the labels
+    fn test_split_layout() {
+        let layout = SplitLayout::new(1280, 384);
+        assert_eq!(layout.label_height, 32);
+        assert_eq!(layout.display_height, 352);
+        assert_eq!(layout.cell_size(DISPLAY_ROWS, DISPLAY_COLS), (10, 11));
+        assert_eq!(layout.cell_size(HIGH_RES_ROWS, HIGH_RES_COLS), (5, 5));
+        assert_eq!(layout.origin(0), (0, 32));
+        assert_eq!(layout.origin(1), (640, 32));
+        // Never smaller than a pixel
+        let layout = SplitLayout::new(10, 10);
+        assert_eq!(layout.cell_size(DISPLAY_ROWS, DISPLAY_COLS), (1, 1));
+    }
+
+    #[test]
+    /// Test the window is twice as wide as a single display's
+    fn test_split_window_config() {
+        let config = EmulatorConfig {
+            window_scale: 10,
+            ..EmulatorConfig::default()
+        };
+        assert_eq!(split_window_config(&config).window_size(), (1280, 320));
+    }
+
+    #[test]
+    /// Test both halves run the same number of frames, with the same keys,
+    /// and are drawn every frame with their labels
+    fn test_halves_in_step() -> Result<()> {
+        // Loop until key 9 is down, V1 = 9, V0 = random, then V2 += 1 forever
+        let rom = [
+            0x63, 0x09, 0xE3, 0x9E, 0x12, 0x02, 0x61, 0x09, 0xC0, 0xFF, 0x72, 0x01, 0x12, 0x0A,
+        ];
+        let (mut view, drawn) = split_view(&rom, Vec::new(), vec![(2, 1 << 9), (4, 0)])?;
+        for _ in 0..10 {
+            view.frame()?;
+        }
+        let [left, right] = view.emulators();
+        assert_eq!(left.cycles(), right.cycles());
+        assert_eq!(left.register(1)?, 9);
+        assert_eq!(right.register(1)?, 9);
+        // The same random numbers in both
+        assert_eq!(left.register(0)?, right.register(0)?);
+        assert_eq!(left.register(2)?, right.register(2)?);
+        assert!(left.register(2)? > 0);
+        let drawn = drawn.borrow();
+        assert_eq!(drawn.len(), 10);
+        assert_eq!(drawn[0].1, ["chip8", "schip"].map(str::to_string));
+        Ok(())
+    }
+
+    #[test]
+    /// Test pausing holds both halves, and stepping runs a frame in each
+    fn test_pause_and_step() -> Result<()> {
+        let rom = [0x72, 0x01, 0x12, 0x00];
+        let hotkeys = vec![
+            (2, HotkeyAction::Pause),
+            (4, HotkeyAction::Step),
+            (6, HotkeyAction::Step),
+        ];
+        let (mut view, _) = split_view(&rom, hotkeys, Vec::new())?;
+        for _ in 0..8 {
+            view.frame()?;
+        }
+        assert!(view.is_paused());
+        let [left, right] = view.emulators();
+        // Two frames before pausing, then one for each step
+        assert_eq!(left.cycles(), 4 * left.instructions_per_frame());
+        assert_eq!(right.cycles(), left.cycles());
+        Ok(())
+    }
+
+    #[test]
+    /// Test the halves differ where their quirks do, drawn in the same frame
+    fn test_halves_differ() -> Result<()> {
+        // V0 = 1, V1 = 2, V0 >>= V1, draw a 0 at 0,V0, loop
+        let rom = [
+            0x60, 0x01, 0x61, 0x02, 0x80, 0x16, 0xF0, 0x29, 0x62, 0x00, 0xD2, 0x05, 0x12, 0x0C,
+        ];
+        let (mut view, drawn) = split_view(&rom, Vec::new(), Vec::new())?;
+        view.frame()?;
+        let [left, right] = view.emulators();
+        // The original shifts V1 into V0, SUPER-CHIP shifts V0
+        assert_eq!(left.register(0)?, 1);
+        assert_eq!(right.register(0)?, 0);
+        // So the 0 is a row lower on the left
+        let [left, right] = &drawn.borrow()[0].0;
+        assert!(!left[0] && left[DISPLAY_COLS]);
+        assert!(right[0]);
+        Ok(())
+    }
+
+    #[test]
+    /// Test an error stops just the half it happened in, which shows it until
+    /// the halves are reset
+    fn test_error_stops_half() -> Result<()> {
+        // V0 = 1, V1 = 2, V0 >>= V1, skip the unknown FFFF if V0 == 0, loop
+        let rom = [
+            0x60, 0x01, 0x61, 0x02, 0x80, 0x16, 0x30, 0x00, 0xFF, 0xFF, 0x12, 0x0A,
+        ];
+        let hotkeys = vec![(3, HotkeyAction::Pause), (3, HotkeyAction::Reset)];
+        let (mut view, drawn) = split_view(&rom, hotkeys, Vec::new())?;
+        view.frame()?;
+        view.frame()?;
+        // The original shifts V1 into V0, so doesn't skip the unknown opcode
+        let [left, right] = view.emulators();
+        let stopped_at = left.cycles();
+        assert!(right.cycles() > stopped_at);
+        let [left_error, right_error] = view.errors().map(<[String]>::to_vec);
+        assert!(
+            left_error.iter().any(|line| line.contains("FFFF")),
+            "{left_error:?}"
+        );
+        assert!(right_error.is_empty());
+        assert!(
+            left_error
+                .iter()
+                .all(|line| line.chars().count() <= ERROR_SCREEN_COLUMNS / 2)
+        );
+        // It stays stopped, and is drawn with the error
+        view.frame()?;
+        assert_eq!(view.emulators()[0].cycles(), stopped_at);
+        assert_eq!(drawn.borrow()[2].2[0], left_error);
+        // Resetting (paused, so it doesn't fail again straight away) clears it
+        view.frame()?;
+        assert!(view.errors()[0].is_empty());
+        assert!(drawn.borrow()[3].2[0].is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+#[cfg(feature = "std")]
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::compat::CompatReport;
+use crate::emulator::RunOutcome;
+use crate::instruction::Instruction;
+
+/// Version of the [RunStats] JSON schema, bumped whenever a field changes
+pub const STATS_VERSION: u32 = 5;
+
+/// Why a run ended, as recorded in [RunStats]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaltReason {
+    /// The frontend asked to stop (e.g. the window was closed)
+    Stopped,
+    /// The program exited, or halted on an error
+    Halted,
+    /// One of the run limits was reached
+    LimitReached,
+    /// A key was pressed, in a run stopping on one
+    KeyPressed,
+    /// The watchdog stopped the run
+    TimedOut,
+    /// The emulator crashed
+    Error,
+}
+
+impl From<RunOutcome> for HaltReason {
+    fn from(outcome: RunOutcome) -> Self {
+        match outcome {
+            RunOutcome::Stopped => HaltReason::Stopped,
+            RunOutcome::Halted => HaltReason::Halted,
+            RunOutcome::LimitReached => HaltReason::LimitReached,
+            RunOutcome::KeyPressed => HaltReason::KeyPressed,
+            RunOutcome::TimedOut => HaltReason::TimedOut,
+        }
+    }
+}
+
+/// Statistics about a run of the emulator, written as JSON by `--stats-out`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunStats {
+    /// Version of the schema, see [STATS_VERSION]
+    pub version: u32,
+    /// Number of instructions executed
+    pub instructions: u64,
+    /// Time spent running, in seconds
+    pub wall_time_seconds: f64,
+    /// Instructions executed per second of wall time
+    pub achieved_ips: f64,
+    /// Number of sprites drawn (DXYN instructions)
+    pub draws: u64,
+    /// Number of sprite draws which collided (set VF)
+    pub total_collisions: u64,
+    /// Number of sprite draws which collided in the last frame
+    pub collisions_this_frame: u32,
+    /// Number of complete frames presented to the frontend, so not counting the
+    /// redraws in between
+    pub frames_presented: u64,
+    /// Number of times each kind of instruction was executed, keyed by its
+    /// pattern (e.g. `DXYN`, see [opcode_pattern])
+    pub opcode_histogram: BTreeMap<String, u64>,
+    /// Number of instructions whose result depended on each quirk, keyed by
+    /// its setting (e.g. `shift_use_vy`), counted with `log_quirk_hits`
+    pub quirk_hits: BTreeMap<String, u64>,
+    /// Most entries on the stack at once
+    pub max_stack_depth: usize,
+    /// Why the run ended, None if it hasn't
+    pub halt_reason: Option<HaltReason>,
+    /// The error the emulator crashed with, if any
+    pub error: Option<String>,
+    /// The compatibility report, with `--compat-report`
+    pub compat: Option<CompatReport>,
+}
+
+impl Default for RunStats {
+    fn default() -> Self {
+        Self {
+            version: STATS_VERSION,
+            instructions: 0,
+            wall_time_seconds: 0.0,
+            achieved_ips: 0.0,
+            draws: 0,
+            total_collisions: 0,
+            collisions_this_frame: 0,
+            frames_presented: 0,
+            opcode_histogram: BTreeMap::new(),
+            quirk_hits: BTreeMap::new(),
+            max_stack_depth: 0,
+            halt_reason: None,
+            error: None,
+            compat: None,
+        }
+    }
+}
+
+impl RunStats {
+    /// Count an execution of `opcode` in the histogram
+    pub fn record_opcode(&mut self, opcode: u16) {
+        *self
+            .opcode_histogram
+            .entry(opcode_pattern(opcode))
+            .or_default() += 1;
+    }
+
+    /// Write the statistics to `path` as JSON
+    #[cfg(feature = "std")]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Converting statistics to JSON")?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("Writing statistics to {}", path.display()))
+    }
+}
+
+/// The pattern of the instruction `opcode` (e.g. `6XNN` for `6A05`), with the
+/// operands replaced by X, Y, N, NN, or NNN as in the usual opcode tables
+///
+/// Opcodes which aren't a known instruction are their own pattern.
+pub fn opcode_pattern(opcode: u16) -> String {
+    use Instruction::*;
+    let Some(instruction) = Instruction::decode(opcode) else {
+        return format!("{opcode:04X}");
+    };
+    let pattern = match instruction {
+        Clear => "00E0",
+        Return => "00EE",
+        ScrollDown { .. } => "00CN",
+        ScrollUp { .. } => "00DN",
+        ScrollRight => "00FB",
+        ScrollLeft => "00FC",
+        Exit => "00FD",
+        LowResolution => "00FE",
+        HighResolution => "00FF",
+        MachineCode { .. } => "0NNN",
+        Jump { .. } => "1NNN",
+        Call { .. } => "2NNN",
+        SkipIfEqual { .. } => "3XNN",
+        SkipIfNotEqual { .. } => "4XNN",
+        SkipIfRegistersEqual { .. } => "5XY0",
+        StoreRange { .. } => "5XY2",
+        LoadRange { .. } => "5XY3",
+        Set { .. } => "6XNN",
+        Add { .. } => "7XNN",
+        SetRegister { .. } => "8XY0",
+        Or { .. } => "8XY1",
+        And { .. } => "8XY2",
+        Xor { .. } => "8XY3",
+        AddRegister { .. } => "8XY4",
+        Subtract { .. } => "8XY5",
+        ShiftRight { .. } => "8XY6",
+        SubtractReversed { .. } => "8XY7",
+        ShiftLeft { .. } => "8XYE",
+        SkipIfRegistersNotEqual { .. } => "9XY0",
+        SetIndex { .. } => "ANNN",
+        JumpWithOffset { .. } => "BNNN",
+        Random { .. } => "CXNN",
+        Draw { .. } => "DXYN",
+        SkipIfKey { .. } => "EX9E",
+        SkipIfNotKey { .. } => "EXA1",
+        LoadLongIndex => "F000",
+        SelectPlanes { .. } => "FX01",
+        LoadAudioPattern => "F002",
+        GetDelayTimer { .. } => "FX07",
+        WaitForKey { .. } => "FX0A",
+        SetDelayTimer { .. } => "FX15",
+        SetSoundTimer { .. } => "FX18",
+        AddToIndex { .. } => "FX1E",
+        FontCharacter { .. } => "FX29",
+        BigFontCharacter { .. } => "FX30",
+        BinaryCodedDecimal { .. } => "FX33",
+        SetPitch { .. } => "FX3A",
+        StoreRegisters { .. } => "FX55",
+        LoadRegisters { .. } => "FX65",
+        SaveFlags { .. } => "FX75",
+        LoadFlags { .. } => "FX85",
+    };
+    pattern.to_string()
+}
+
+#[cfg(test)]
+mod test_stats {
+    use super::*;
+
+    #[test]
+    /// Test the opcode patterns
+    fn test_opcode_pattern() {
+        for (opcode, pattern) in [
+            (0x00E0, "00E0"),
+            (0x00EE, "00EE"),
+            (0x00C4, "00CN"),
+            (0x0123, "0NNN"),
+            (0x1228, "1NNN"),
+            (0x2ABC, "2NNN"),
+            (0x3A0F, "3XNN"),
+            (0x6A05, "6XNN"),
+            (0x5120, "5XY0"),
+            (0x8AB4, "8XY4"),
+            (0xD01F, "DXYN"),
+            (0xE29E, "EX9E"),
+            (0xF733, "FX33"),
+            (0xF301, "FX01"),
+            // Unknown opcodes
+            (0x5121, "5121"),
+            (0xF0FF, "F0FF"),
+        ] {
+            assert_eq!(opcode_pattern(opcode), pattern, "{opcode:04X}");
+        }
+    }
+
+    #[test]
+    /// Test writing the statistics, and reading them back
+    fn test_write() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("stats.json");
+        let mut stats = RunStats {
+            instructions: 3,
+            halt_reason: Some(HaltReason::LimitReached),
+            ..RunStats::default()
+        };
+        stats.record_opcode(0x6A05);
+        stats.record_opcode(0x6B01);
+        stats.record_opcode(0x1200);
+        stats.write(&path)?;
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        assert_eq!(json["version"], STATS_VERSION);
+        assert_eq!(json["halt_reason"], "limit_reached");
+        assert_eq!(json["opcode_histogram"]["6XNN"], 2);
+        assert_eq!(json["opcode_histogram"]["1NNN"], 1);
+        assert_eq!(serde_json::from_value::<RunStats>(json)?, stats);
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::display::Display;
+use crate::frontend::{Frontend, FrontendCapabilities};
+use crate::hotkeys::HotkeyAction;
+use crate::keymap::Keymap;
+use crate::menu::{MenuInput, PauseMenu};
+
+/// A frontend sending the output of the emulator to several frontends at once,
+/// e.g. a window to play in alongside a recorder
+///
+/// Drawing, sound, and the other output goes to every frontend, while input
+/// (keys, hotkeys, the menu, and choosing ROMs) comes from the primary one. The
+/// emulator stops when any of them asks it to.
+pub struct TeeFrontend<'a> {
+    primary: Box<dyn Frontend + 'a>,
+    others: Vec<Box<dyn Frontend + 'a>>,
+}
+
+impl<'a> TeeFrontend<'a> {
+    /// Send output to `primary` and each of `others`, with input from `primary`
+    pub fn new(primary: Box<dyn Frontend + 'a>, others: Vec<Box<dyn Frontend + 'a>>) -> Self {
+        Self { primary, others }
+    }
+
+    /// Every frontend, the primary first
+    fn all(&mut self) -> impl Iterator<Item = &mut Box<dyn Frontend + 'a>> {
+        std::iter::once(&mut self.primary).chain(self.others.iter_mut())
+    }
+}
+
+impl Frontend for TeeFrontend<'_> {
+    fn draw(&mut self, display: &Display) -> Result<()> {
+        self.all().try_for_each(|frontend| frontend.draw(display))
+    }
+
+    fn draw_split(
+        &mut self,
+        displays: [&Display; 2],
+        labels: [&str; 2],
+        errors: [&[String]; 2],
+    ) -> Result<()> {
+        self.all()
+            .try_for_each(|frontend| frontend.draw_split(displays, labels, errors))
+    }
+
+    fn check_key(&mut self, key: u8) -> Result<bool> {
+        self.primary.check_key(key)
+    }
+
+    fn current_keys(&mut self) -> Result<u16> {
+        self.primary.current_keys()
+    }
+
+    fn check_hotkey(&mut self, action: HotkeyAction) -> Result<bool> {
+        self.primary.check_hotkey(action)
+    }
+
+    fn set_keymap(&mut self, keymap: &Keymap) -> Result<()> {
+        self.primary.set_keymap(keymap)
+    }
+
+    /// The primary's capabilities, with audio if any of the frontends can play it
+    fn capabilities(&self) -> FrontendCapabilities {
+        FrontendCapabilities {
+            has_audio: std::iter::once(&self.primary)
+                .chain(&self.others)
+                .any(|frontend| frontend.capabilities().has_audio),
+            ..self.primary.capabilities()
+        }
+    }
+
+    fn has_menu(&self) -> bool {
+        self.primary.has_menu()
+    }
+
+    fn set_sound_active(&mut self, active: bool) {
+        self.all()
+            .for_each(|frontend| frontend.set_sound_active(active));
+    }
+
+    fn draw_menu(&mut self, display: &Display, menu: &PauseMenu) -> Result<()> {
+        self.all()
+            .try_for_each(|frontend| frontend.draw_menu(display, menu))
+    }
+
+    fn menu_input(&mut self, menu: &PauseMenu) -> Result<Option<MenuInput>> {
+        self.primary.menu_input(menu)
+    }
+
+    fn is_focused(&mut self) -> bool {
+        self.primary.is_focused()
+    }
+
+    fn set_perf_text(&mut self, text: Option<&str>) {
+        self.all().for_each(|frontend| frontend.set_perf_text(text));
+    }
+
+    fn set_cheats_text(&mut self, text: Option<&str>) {
+        self.all()
+            .for_each(|frontend| frontend.set_cheats_text(text));
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<()> {
+        self.all()
+            .try_for_each(|frontend| frontend.set_title(title))
+    }
+
+    fn cycle_theme(&mut self) -> Result<()> {
+        self.all().try_for_each(|frontend| frontend.cycle_theme())
+    }
+
+    fn choose_rom(&mut self) -> Result<Option<PathBuf>> {
+        self.primary.choose_rom()
+    }
+
+    fn show_error(&mut self, lines: &[String]) -> Result<()> {
+        self.primary.show_error(lines)
+    }
+
+    fn on_resolution_change(&mut self, rows: usize, cols: usize) -> Result<()> {
+        self.all()
+            .try_for_each(|frontend| frontend.on_resolution_change(rows, cols))
+    }
+
+    fn play_sound(&mut self) -> Result<()> {
+        self.all().try_for_each(|frontend| frontend.play_sound())
+    }
+
+    fn stop_sound(&mut self) -> Result<()> {
+        self.all().try_for_each(|frontend| frontend.stop_sound())
+    }
+
+    /// Whether any of the frontends asks to stop, every one of them is asked
+    fn should_stop(&mut self) -> bool {
+        let mut stop = false;
+        for frontend in self.all() {
+            stop |= frontend.should_stop();
+        }
+        stop
+    }
+
+    fn achieved_fps(&self) -> Option<f32> {
+        self.primary.achieved_fps()
+    }
+
+    fn step(&mut self) -> Result<()> {
+        self.all().try_for_each(|frontend| frontend.step())
+    }
+}
+
+#[cfg(test)]
+mod test_tee_frontend {
+    use super::*;
+    use crate::config::EmulatorConfig;
+    use crate::emulator::Emulator;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// What a recording frontend received
+    #[derive(Debug, Default)]
+    struct Recording {
+        frames: Vec<String>,
+        split_labels: Vec<[String; 2]>,
+        sounds: Vec<bool>,
+        steps: u32,
+    }
+
+    /// Frontend recording what it is sent, holding keypad key `key` down, and
+    /// stopping after `stop_after` loop iterations
+    struct RecordingFrontend {
+        recording: Rc<RefCell<Recording>>,
+        key: u8,
+        stop_after: u32,
+        iterations: u32,
+    }
+
+    impl RecordingFrontend {
+        fn new(key: u8, stop_after: u32) -> (Self, Rc<RefCell<Recording>>) {
+            let recording = Rc::new(RefCell::new(Recording::default()));
+            let frontend = Self {
+                recording: recording.clone(),
+                key,
+                stop_after,
+                iterations: 0,
+            };
+            (frontend, recording)
+        }
+    }
+
+    impl Frontend for RecordingFrontend {
+        fn draw(&mut self, display: &Display) -> Result<()> {
+            self.recording.borrow_mut().frames.push(display.to_ascii());
+            Ok(())
+        }
+
+        fn draw_split(
+            &mut self,
+            _displays: [&Display; 2],
+            labels: [&str; 2],
+            _errors: [&[String]; 2],
+        ) -> Result<()> {
+            let labels = labels.map(str::to_string);
+            self.recording.borrow_mut().split_labels.push(labels);
+            Ok(())
+        }
+
+        fn check_key(&mut self, key: u8) -> Result<bool> {
+            Ok(key == self.key)
+        }
+
+        fn capabilities(&self) -> FrontendCapabilities {
+            FrontendCapabilities {
+                has_audio: true,
+                ..FrontendCapabilities::default()
+            }
+        }
+
+        fn play_sound(&mut self) -> Result<()> {
+            self.recording.borrow_mut().sounds.push(true);
+            Ok(())
+        }
+
+        fn stop_sound(&mut self) -> Result<()> {
+            self.recording.borrow_mut().sounds.push(false);
+            Ok(())
+        }
+
+        fn should_stop(&mut self) -> bool {
+            self.iterations += 1;
+            self.iterations > self.stop_after
+        }
+
+        fn step(&mut self) -> Result<()> {
+            self.recording.borrow_mut().steps += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    /// Test every frontend receives each draw, with keys from the primary
+    fn test_tee() -> Result<()> {
+        let (primary, primary_recording) = RecordingFrontend::new(0x5, 20);
+        let (other, other_recording) = RecordingFrontend::new(0x7, 10);
+        let mut tee = TeeFrontend::new(Box::new(primary), vec![Box::new(other)]);
+        assert!(tee.check_key(0x5)?);
+        assert!(!tee.check_key(0x7)?);
+        assert_eq!(tee.current_keys()?, 1 << 0x5);
+        assert!(tee.capabilities().has_audio);
+
+        let config = EmulatorConfig {
+            instructions_per_second: crate::config::MAX_INSTRUCTIONS_PER_SECOND,
+            min_beep_ms: 0,
+            ..EmulatorConfig::default()
+        };
+        let mut emulator = Emulator::new(Box::new(tee), config)?;
+        // Draw a line at (0, 0) from the sprite after the code, start the beep
+        // (with the sound timer from V1), then loop
+        emulator.load_rom(&[
+            0xA2, 0x0A, 0xD0, 0x01, 0x61, 0x10, 0xF1, 0x18, 0x12, 0x08, 0xFF,
+        ])?;
+        emulator.run()?;
+
+        let primary = primary_recording.borrow();
+        let other = other_recording.borrow();
+        // The second frontend stopped the run, after the same number of frames
+        assert_eq!(primary.frames.len(), 10);
+        assert_eq!(primary.frames, other.frames);
+        assert_ne!(primary.frames[0], primary.frames[9]);
+        assert_eq!(primary.sounds, [true]);
+        assert_eq!(other.sounds, [true]);
+        assert_eq!((primary.steps, other.steps), (10, 10));
+        Ok(())
+    }
+
+    #[test]
+    /// Test split views are drawn by every frontend
+    fn test_tee_split() -> Result<()> {
+        let (primary, primary_recording) = RecordingFrontend::new(0x5, 20);
+        let (other, other_recording) = RecordingFrontend::new(0x7, 10);
+        let mut tee = TeeFrontend::new(Box::new(primary), vec![Box::new(other)]);
+        let display = Display::new();
+        tee.draw_split([&display, &display], ["left", "right"], [&[], &[]])?;
+        let expected = [["left".to_string(), "right".to_string()]];
+        assert_eq!(primary_recording.borrow().split_labels, expected);
+        assert_eq!(other_recording.borrow().split_labels, expected);
+        assert!(primary_recording.borrow().frames.is_empty());
+        Ok(())
+    }
+}
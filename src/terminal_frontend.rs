@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{execute, terminal};
+use log::warn;
+
+use crate::config::EmulatorConfig;
+use crate::display::{Display, DisplayTransform};
+use crate::frontend::{Frontend, FrontendCapabilities};
+use crate::hotkeys::{HotkeyAction, HotkeyMap};
+use crate::keymap::{Keymap, normalize_key_name};
+use crate::sound::Bell;
+
+/// Red, green, and blue components of a color
+pub type Rgb = (u8, u8, u8);
+
+/// How the terminal frontend colors the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalColor {
+    /// Truecolor if the terminal says it supports it, otherwise monochrome
+    Auto,
+    /// The foreground and background colors, with 24-bit ANSI escapes
+    Truecolor,
+    /// Block characters in the terminal's own colors
+    Monochrome,
+}
+
+impl TerminalColor {
+    /// All of the color modes
+    pub const ALL: [TerminalColor; 3] = [
+        TerminalColor::Auto,
+        TerminalColor::Truecolor,
+        TerminalColor::Monochrome,
+    ];
+
+    /// Name of the mode as used in the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            TerminalColor::Auto => "auto",
+            TerminalColor::Truecolor => "truecolor",
+            TerminalColor::Monochrome => "monochrome",
+        }
+    }
+
+    /// Find the mode with the given name (case-insensitive)
+    pub fn from_name(name: &str) -> Result<Self> {
+        let lower = name.trim().to_lowercase();
+        match Self::ALL.iter().find(|mode| mode.name() == lower) {
+            Some(mode) => Ok(*mode),
+            None => bail!(
+                "Unknown terminal color mode {name:?}, available modes are: {}",
+                Self::ALL.map(|mode| mode.name()).join(", ")
+            ),
+        }
+    }
+
+    /// Whether to draw in truecolor, checking the environment for `Auto`
+    pub fn use_truecolor(&self) -> bool {
+        match self {
+            TerminalColor::Auto => supports_truecolor(
+                std::env::var("NO_COLOR").ok().as_deref(),
+                std::env::var("TERM").ok().as_deref(),
+                std::env::var("COLORTERM").ok().as_deref(),
+            ),
+            TerminalColor::Truecolor => true,
+            TerminalColor::Monochrome => false,
+        }
+    }
+}
+
+/// Whether a terminal with these `NO_COLOR`, `TERM`, and `COLORTERM`
+/// environment variables can show 24-bit color
+///
+/// Any non-empty `NO_COLOR` turns color off (see no-color.org), as does a dumb
+/// terminal. Otherwise terminals advertise truecolor in `COLORTERM`.
+pub fn supports_truecolor(
+    no_color: Option<&str>,
+    term: Option<&str>,
+    colorterm: Option<&str>,
+) -> bool {
+    if no_color.is_some_and(|value| !value.is_empty()) || term == Some("dumb") {
+        return false;
+    }
+    matches!(colorterm, Some("truecolor" | "24bit"))
+}
+
+/// A terminal cell showing a `top` and a `bottom` pixel in their colors, with
+/// the upper half block drawn in the top color over the bottom color
+pub fn truecolor_cell(top: Rgb, bottom: Rgb) -> String {
+    let (tr, tg, tb) = top;
+    let (br, bg, bb) = bottom;
+    format!("\x1b[38;2;{tr};{tg};{tb}m\x1b[48;2;{br};{bg};{bb}m▀")
+}
+
+/// A terminal cell showing a `top` and a `bottom` pixel, lit pixels drawn in
+/// the terminal's text color
+pub fn monochrome_cell(top: bool, bottom: bool) -> char {
+    match (top, bottom) {
+        (false, false) => ' ',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (true, true) => '█',
+    }
+}
+
+/// The display as text for the terminal, two rows of pixels per line, from the
+/// top left corner of the terminal
+///
+/// With a `palette` the pixels are drawn in its colors (indexed by
+/// [Display::color_index], past the end of the palette using its last color),
+/// without one in monochrome.
+pub fn render(
+    display: &Display,
+    transform: DisplayTransform,
+    palette: Option<&[Rgb]>,
+) -> Result<String> {
+    // Every transform is its own inverse, so it also finds the display cell
+    // drawn at a position on the screen
+    let color_index = |row, col| {
+        let (row, col) = transform.apply(row, col, display.rows(), display.cols());
+        display.color_index(row, col)
+    };
+    let mut text = String::from("\x1b[H");
+    for row in (0..display.rows()).step_by(2) {
+        for col in 0..display.cols() {
+            let (top, bottom) = (color_index(row, col)?, color_index(row + 1, col)?);
+            match palette {
+                Some(palette) => {
+                    let color = |index: usize| {
+                        palette
+                            .get(index)
+                            .or(palette.last())
+                            .copied()
+                            .unwrap_or_default()
+                    };
+                    text.push_str(&truecolor_cell(color(top), color(bottom)));
+                }
+                None => text.push(monochrome_cell(top != 0, bottom != 0)),
+            }
+        }
+        if palette.is_some() {
+            text.push_str("\x1b[0m");
+        }
+        // With a carriage return, as the terminal is in raw mode
+        text.push_str("\r\n");
+    }
+    Ok(text)
+}
+
+/// How long a key counts as held after the terminal last reported it pressed,
+/// for terminals which don't report releases (most of them). Holding a key down
+/// repeats it more often than this.
+pub const KEY_HOLD: Duration = Duration::from_millis(250);
+
+/// The name (as in [KEY_NAMES](crate::keymap::KEY_NAMES)) of a key read from the
+/// terminal, None for keys which can't be bound
+///
+/// Terminals send the character a key types, so shifted keys (e.g. `!`) aren't
+/// recognized, and the keypad can't be told apart from the main keys.
+pub fn key_name(code: KeyCode) -> Option<&'static str> {
+    let name = match code {
+        KeyCode::Char(' ') => "SPACE",
+        KeyCode::Char(character) => return normalize_key_name(&character.to_string()).ok(),
+        KeyCode::F(number) => return normalize_key_name(&format!("F{number}")).ok(),
+        KeyCode::Esc => "ESCAPE",
+        KeyCode::Enter => "ENTER",
+        KeyCode::Tab => "TAB",
+        KeyCode::Backspace => "BACKSPACE",
+        KeyCode::Insert => "INSERT",
+        KeyCode::Delete => "DELETE",
+        KeyCode::Right => "RIGHT",
+        KeyCode::Left => "LEFT",
+        KeyCode::Down => "DOWN",
+        KeyCode::Up => "UP",
+        KeyCode::PageUp => "PAGE_UP",
+        KeyCode::PageDown => "PAGE_DOWN",
+        KeyCode::Home => "HOME",
+        KeyCode::End => "END",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The keys held down in the terminal, from the key events it sends
+///
+/// A key is held from when it is pressed until the terminal reports it
+/// released, or (as most terminals never do) until [KEY_HOLD] passes without
+/// it being pressed again.
+#[derive(Debug, Default)]
+pub struct TerminalKeys {
+    /// When each held key was last pressed (or repeated)
+    held: HashMap<&'static str, Instant>,
+    /// The keys pressed since [TerminalKeys::start_poll], for hotkeys
+    pressed: Vec<&'static str>,
+}
+
+impl TerminalKeys {
+    /// Forget the keys pressed before now, ready to read new events
+    pub fn start_poll(&mut self) {
+        self.pressed.clear();
+    }
+
+    /// Record the key `name` pressed (or repeated) at `now`
+    pub fn press(&mut self, name: &'static str, now: Instant) {
+        self.held.insert(name, now);
+        self.pressed.push(name);
+    }
+
+    /// Record the key `name` released
+    pub fn release(&mut self, name: &str) {
+        self.held.remove(name);
+    }
+
+    /// Whether the key `name` is held at `now`
+    pub fn is_down(&self, name: &str, now: Instant) -> bool {
+        self.held
+            .get(name)
+            .is_some_and(|pressed_at| now.saturating_duration_since(*pressed_at) < KEY_HOLD)
+    }
+
+    /// Whether the key `name` was pressed since the last poll
+    pub fn was_pressed(&self, name: &str) -> bool {
+        self.pressed.contains(&name)
+    }
+}
+
+/// A frontend drawing the display in the terminal, for playing over SSH
+///
+/// Draws, reads the keypad and hotkeys (see [TerminalKeys]), and rings the
+/// terminal bell for the beep. The terminal is put in raw mode to read keys,
+/// so Ctrl-C is read as a key, stopping the emulator, rather than killing it
+/// with the terminal still in raw mode. If stdin isn't a terminal no keys are
+/// ever pressed.
+pub struct TerminalFrontend {
+    /// The color of each combination of lit planes (see
+    /// [EmulatorConfig::palette_colors]), None to draw in monochrome
+    palette: Option<Vec<Rgb>>,
+    transform: DisplayTransform,
+    bell: Bell,
+    /// The last frame written, so unchanged frames aren't written again
+    last_frame: String,
+    keymap: Keymap,
+    hotkeys: HotkeyMap,
+    keys: TerminalKeys,
+    /// Whether the terminal is in raw mode, and so keys can be read
+    raw_mode: bool,
+    /// Whether the terminal reports keys being released
+    reports_releases: bool,
+    /// Whether Ctrl-C was pressed
+    interrupted: bool,
+}
+
+impl TerminalFrontend {
+    /// Create the frontend, clearing the terminal and hiding the cursor, with
+    /// the keypad bound to `keymap` and emulator actions to `hotkeys`
+    pub fn new(config: &EmulatorConfig, keymap: &Keymap, hotkeys: &HotkeyMap) -> Result<Self> {
+        let palette = if TerminalColor::from_name(&config.terminal_color)?.use_truecolor() {
+            Some(config.palette_colors()?)
+        } else {
+            None
+        };
+        let raw_mode = match terminal::enable_raw_mode() {
+            Ok(()) => true,
+            Err(err) => {
+                warn!("Can't read keys from the terminal, no keys will be pressed: {err}");
+                false
+            }
+        };
+        // Where the terminal can, have it report releases so held keys are exact
+        let reports_releases =
+            raw_mode && terminal::supports_keyboard_enhancement().unwrap_or(false);
+        let mut out = io::stdout().lock();
+        if reports_releases {
+            execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )
+            .context("Asking the terminal to report key releases")?;
+        }
+        out.write_all(b"\x1b[2J\x1b[?25l")
+            .and_then(|()| out.flush())
+            .context("Clearing the terminal")?;
+        Ok(Self {
+            palette,
+            transform: DisplayTransform::from_name(&config.display_transform)?,
+            bell: Bell::from_config(config),
+            last_frame: String::new(),
+            keymap: keymap.clone(),
+            hotkeys: hotkeys.clone(),
+            keys: TerminalKeys::default(),
+            raw_mode,
+            reports_releases,
+            interrupted: false,
+        })
+    }
+
+    /// Read the key events the terminal has sent, without waiting
+    fn poll_keys(&mut self) -> Result<()> {
+        self.keys.start_poll();
+        if !self.raw_mode {
+            return Ok(());
+        }
+        while event::poll(Duration::ZERO).context("Checking for terminal input")? {
+            let Event::Key(key) = event::read().context("Reading terminal input")? else {
+                continue;
+            };
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                self.interrupted = true;
+                continue;
+            }
+            let Some(name) = key_name(key.code) else {
+                continue;
+            };
+            match key.kind {
+                KeyEventKind::Press | KeyEventKind::Repeat => self.keys.press(name, Instant::now()),
+                KeyEventKind::Release => self.keys.release(name),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TerminalFrontend {
+    /// Leave the terminal as it was, with the cursor shown below the display
+    fn drop(&mut self) {
+        let mut out = io::stdout().lock();
+        if self.reports_releases {
+            let _ = execute!(out, PopKeyboardEnhancementFlags);
+        }
+        let _ = out.write_all(b"\x1b[0m\x1b[?25h");
+        let _ = out.flush();
+        if self.raw_mode {
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    fn draw(&mut self, display: &Display) -> Result<()> {
+        let frame = render(display, self.transform, self.palette.as_deref())?;
+        if frame == self.last_frame {
+            return Ok(());
+        }
+        let mut out = io::stdout().lock();
+        out.write_all(frame.as_bytes())
+            .and_then(|()| out.flush())
+            .context("Drawing the display in the terminal")?;
+        self.last_frame = frame;
+        Ok(())
+    }
+
+    /// Clear the terminal, so a smaller display doesn't leave any of the larger
+    /// one behind
+    fn on_resolution_change(&mut self, _rows: usize, _cols: usize) -> Result<()> {
+        let mut out = io::stdout().lock();
+        out.write_all(b"\x1b[2J")
+            .and_then(|()| out.flush())
+            .context("Clearing the terminal")?;
+        self.last_frame.clear();
+        Ok(())
+    }
+
+    fn check_key(&mut self, key: u8) -> Result<bool> {
+        Ok(self
+            .keymap
+            .key_name(key)
+            .is_some_and(|name| self.keys.is_down(name, Instant::now())))
+    }
+
+    fn check_hotkey(&mut self, action: HotkeyAction) -> Result<bool> {
+        Ok(self
+            .hotkeys
+            .key_name(action)
+            .is_some_and(|name| self.keys.was_pressed(name)))
+    }
+
+    fn set_keymap(&mut self, keymap: &Keymap) -> Result<()> {
+        self.keymap = keymap.clone();
+        Ok(())
+    }
+
+    /// Sound is the terminal bell, if enabled with `bell_on_beep`
+    fn capabilities(&self) -> FrontendCapabilities {
+        FrontendCapabilities {
+            has_audio: self.bell.is_enabled(),
+            ..FrontendCapabilities::default()
+        }
+    }
+
+    fn play_sound(&mut self) -> Result<()> {
+        self.bell.ring()?;
+        Ok(())
+    }
+
+    fn stop_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether Ctrl-C was pressed
+    fn should_stop(&mut self) -> bool {
+        self.interrupted
+    }
+
+    fn step(&mut self) -> Result<()> {
+        self.poll_keys()
+    }
+}
+
+#[cfg(test)]
+mod test_terminal_frontend {
+    use super::*;
+    use crate::display::{DISPLAY_COLS, DISPLAY_ROWS, HIGH_RES_COLS, HIGH_RES_ROWS};
+
+    #[test]
+    /// Test the escape sequences (and the block characters) for a pair of pixels
+    fn test_truecolor_cell() {
+        assert_eq!(
+            truecolor_cell((0xFF, 0x88, 0x00), (0, 0, 0x80)),
+            "\x1b[38;2;255;136;0m\x1b[48;2;0;0;128m▀"
+        );
+        assert_eq!(monochrome_cell(true, false), '▀');
+        assert_eq!(monochrome_cell(false, true), '▄');
+    }
+
+    #[test]
+    /// Test naming the keys read from the terminal
+    fn test_key_name() {
+        assert_eq!(key_name(KeyCode::Char('w')), Some("W"));
+        assert_eq!(key_name(KeyCode::Char('4')), Some("4"));
+        assert_eq!(key_name(KeyCode::Char(';')), Some("SEMICOLON"));
+        assert_eq!(key_name(KeyCode::Char(' ')), Some("SPACE"));
+        assert_eq!(key_name(KeyCode::F(6)), Some("F6"));
+        assert_eq!(key_name(KeyCode::Esc), Some("ESCAPE"));
+        assert_eq!(key_name(KeyCode::PageUp), Some("PAGE_UP"));
+        assert_eq!(key_name(KeyCode::Char('!')), None);
+        assert_eq!(key_name(KeyCode::F(13)), None);
+    }
+
+    #[test]
+    /// Test keys are held until released, or for a while after each press
+    fn test_terminal_keys() {
+        let start = Instant::now();
+        let mut keys = TerminalKeys::default();
+        keys.start_poll();
+        keys.press("W", start);
+        assert!(keys.is_down("W", start));
+        assert!(keys.was_pressed("W"));
+        assert!(!keys.is_down("S", start));
+        // Repeats keep the key held
+        keys.start_poll();
+        assert!(!keys.was_pressed("W"));
+        keys.press("W", start + KEY_HOLD / 2);
+        assert!(keys.is_down("W", start + KEY_HOLD));
+        // Without a repeat or a release it is let go after a while
+        assert!(!keys.is_down("W", start + KEY_HOLD * 2));
+        // Releases let go straight away
+        keys.press("S", start);
+        keys.release("S");
+        assert!(!keys.is_down("S", start));
+    }
+
+    #[test]
+    /// Test detecting whether the terminal shows truecolor
+    fn test_supports_truecolor() {
+        assert!(supports_truecolor(
+            None,
+            Some("xterm-256color"),
+            Some("truecolor")
+        ));
+        assert!(supports_truecolor(Some(""), None, Some("24bit")));
+        assert!(!supports_truecolor(None, Some("xterm-256color"), None));
+        assert!(!supports_truecolor(Some("1"), None, Some("truecolor")));
+        assert!(!supports_truecolor(None, Some("dumb"), Some("truecolor")));
+        assert!(TerminalColor::Truecolor.use_truecolor());
+        assert!(!TerminalColor::Monochrome.use_truecolor());
+    }
+
+    #[test]
+    /// Test the color mode names
+    fn test_terminal_color_names() -> Result<()> {
+        for mode in TerminalColor::ALL {
+            assert_eq!(TerminalColor::from_name(mode.name())?, mode);
+        }
+        assert_eq!(
+            TerminalColor::from_name(" TrueColor ")?,
+            TerminalColor::Truecolor
+        );
+        assert!(TerminalColor::from_name("256").is_err());
+        Ok(())
+    }
+
+    #[test]
+    /// Test rendering two rows of pixels per line, in color and monochrome
+    fn test_render() -> Result<()> {
+        let mut display = Display::new();
+        display.set(0, 0, true)?;
+        display.set(1, 1, true)?;
+        display.set(DISPLAY_ROWS - 1, DISPLAY_COLS - 1, true)?;
+
+        let text = render(&display, DisplayTransform::None, None)?;
+        let lines: Vec<&str> = text.strip_prefix("\x1b[H").unwrap().lines().collect();
+        assert_eq!(lines.len(), DISPLAY_ROWS / 2);
+        assert!(lines[0].starts_with("▀▄ "));
+        assert!(lines[DISPLAY_ROWS / 2 - 1].ends_with(" ▄"));
+        assert_eq!(lines[0].chars().count(), DISPLAY_COLS);
+
+        // Rotated, the last line has the top left corner
+        let rotated = render(&display, DisplayTransform::Rotate180, None)?;
+        let lines: Vec<&str> = rotated.lines().collect();
+        assert!(lines[0].starts_with("\x1b[H▀ "));
+        assert!(lines[DISPLAY_ROWS / 2 - 1].ends_with(" ▀▄"));
+
+        let palette = [(0, 0, 0), (255, 255, 255)];
+        let text = render(&display, DisplayTransform::None, Some(&palette))?;
+        let first = text.lines().next().unwrap();
+        assert!(first.starts_with(&format!(
+            "\x1b[H{}{}",
+            truecolor_cell((255, 255, 255), (0, 0, 0)),
+            truecolor_cell((0, 0, 0), (255, 255, 255))
+        )));
+        assert!(first.ends_with("\x1b[0m"));
+
+        // In high resolution the whole larger display is drawn
+        display.set_high_res(true);
+        display.set(HIGH_RES_ROWS - 1, HIGH_RES_COLS - 1, true)?;
+        let text = render(&display, DisplayTransform::None, None)?;
+        let lines: Vec<&str> = text.strip_prefix("\x1b[H").unwrap().lines().collect();
+        assert_eq!(lines.len(), HIGH_RES_ROWS / 2);
+        assert_eq!(lines[0].chars().count(), HIGH_RES_COLS);
+        assert!(lines[HIGH_RES_ROWS / 2 - 1].ends_with(" ▄"));
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use core::sync::atomic::{AtomicU8, Ordering};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+
+use crate::config::EmulatorConfig;
+use crate::stats::opcode_pattern;
+
+/// Default relative cost of each kind of instruction (keyed by its pattern, see
+/// [opcode_pattern]), for pacing with `cycle_accurate`
+///
+/// Derived from the instruction timings of the COSMAC VIP, relative to the
+/// quickest instructions (e.g. 6XNN), which cost 1. Drawing doesn't include
+/// waiting for the display to refresh.
+pub const DEFAULT_CYCLE_COSTS: [(&str, u32); 46] = [
+    ("00E0", 4),
+    ("00EE", 4),
+    ("00CN", 4),
+    ("00DN", 4),
+    ("00FB", 4),
+    ("00FC", 4),
+    ("00FD", 1),
+    ("00FE", 4),
+    ("00FF", 4),
+    ("0NNN", 4),
+    ("1NNN", 4),
+    ("2NNN", 4),
+    ("3XNN", 2),
+    ("4XNN", 2),
+    ("5XY0", 3),
+    ("6XNN", 1),
+    ("7XNN", 2),
+    ("8XY0", 7),
+    ("8XY1", 7),
+    ("8XY2", 7),
+    ("8XY3", 7),
+    ("8XY4", 7),
+    ("8XY5", 7),
+    ("8XY6", 7),
+    ("8XY7", 7),
+    ("8XYE", 7),
+    ("9XY0", 3),
+    ("ANNN", 2),
+    ("BNNN", 4),
+    ("CXNN", 6),
+    ("DXYN", 40),
+    ("EX9E", 3),
+    ("EXA1", 3),
+    ("FX07", 2),
+    ("FX0A", 2),
+    ("FX15", 2),
+    ("FX18", 2),
+    ("FX1E", 3),
+    ("FX29", 3),
+    ("FX30", 3),
+    ("FX33", 34),
+    ("FX55", 22),
+    ("FX65", 22),
+    ("FX75", 22),
+    ("FX85", 22),
+    ("FX3A", 2),
+];
+
+/// Cost of an instruction which isn't in the table (e.g. an unknown opcode)
+const UNKNOWN_COST: u32 = 1;
+
+/// Relative cost of each kind of instruction, for pacing the run loop by the
+/// cost of the instructions executed rather than just how many there were
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleCosts {
+    costs: BTreeMap<String, u32>,
+}
+
+impl Default for CycleCosts {
+    fn default() -> Self {
+        Self {
+            costs: DEFAULT_CYCLE_COSTS
+                .iter()
+                .map(|(pattern, cost)| (pattern.to_string(), *cost))
+                .collect(),
+        }
+    }
+}
+
+impl CycleCosts {
+    /// The default costs, with `overrides` (pattern -> cost) applied on top
+    pub fn with_overrides(overrides: &BTreeMap<String, u32>) -> Result<Self> {
+        let mut costs = Self::default();
+        for (pattern, cost) in overrides {
+            check_override(pattern, *cost)?;
+            costs.costs.insert(pattern.to_uppercase(), *cost);
+        }
+        Ok(costs)
+    }
+
+    /// The costs from the config's `cycle_costs` overrides, if it is
+    /// `cycle_accurate`, otherwise None
+    pub fn from_config(config: &EmulatorConfig) -> Result<Option<Self>> {
+        if !config.cycle_accurate {
+            return Ok(None);
+        }
+        Self::with_overrides(&config.cycle_costs).map(Some)
+    }
+
+    /// Relative cost of executing `opcode`
+    pub fn cost(&self, opcode: u16) -> u32 {
+        self.costs
+            .get(&opcode_pattern(opcode))
+            .copied()
+            .unwrap_or(UNKNOWN_COST)
+    }
+}
+
+/// Check an override of the cost of the instructions matching `pattern`
+pub fn check_override(pattern: &str, cost: u32) -> Result<()> {
+    let upper = pattern.to_uppercase();
+    if !DEFAULT_CYCLE_COSTS.iter().any(|(known, _)| *known == upper) {
+        bail!("Unknown instruction pattern {pattern:?}, expected one like DXYN or 8XY4");
+    }
+    if cost == 0 {
+        bail!("Instructions must cost at least 1");
+    }
+    Ok(())
+}
+
+/// Counts the whole timer periods passed since the last tick, so the timer
+/// thread catches up on ticks it missed while it wasn't scheduled (rather than
+/// the timers running slow under load)
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct TickCounter {
+    period: Duration,
+    /// When the last counted tick was due, the fraction of a period after it
+    /// counts towards the next tick
+    last_tick: Instant,
+}
+
+#[cfg(feature = "std")]
+impl TickCounter {
+    /// Count ticks every `period`, starting from `start`
+    pub fn new(period: Duration, start: Instant) -> Self {
+        Self {
+            period,
+            last_tick: start,
+        }
+    }
+
+    /// Number of whole periods passed by `now` since the last tick
+    pub fn ticks(&mut self, now: Instant) -> u32 {
+        let elapsed = now.saturating_duration_since(self.last_tick);
+        let ticks = (elapsed.as_nanos() / self.period.as_nanos()).min(u32::MAX.into()) as u32;
+        self.last_tick += self.period * ticks;
+        ticks
+    }
+
+    /// When the next tick is due
+    pub fn next_tick(&self) -> Instant {
+        self.last_tick + self.period
+    }
+}
+
+/// Count `timer` down by `ticks`, stopping at 0
+///
+/// The timers are atomic so the ticker thread can count them down while the
+/// emulator reads and sets them, and without std (where there are no threads)
+/// they need no lock.
+pub fn count_down(timer: &AtomicU8, ticks: u32) {
+    let ticks = ticks.min(u8::MAX.into()) as u8;
+    // The closure always returns Some, so this can't fail
+    let _ = timer.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+        Some(value.saturating_sub(ticks))
+    });
+}
+
+#[cfg(test)]
+mod test_timing {
+    use super::*;
+
+    #[test]
+    /// Test counting several ticks at once, keeping fractions of a tick
+    fn test_tick_counter() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut counter = TickCounter::new(Duration::from_millis(10), start);
+        assert_eq!(counter.ticks(at(5)), 0);
+        assert_eq!(counter.next_tick(), at(10));
+        // Starved for several periods, they are all counted
+        assert_eq!(counter.ticks(at(35)), 3);
+        assert_eq!(counter.next_tick(), at(40));
+        // The 5ms left over counts towards the next tick
+        assert_eq!(counter.ticks(at(39)), 0);
+        assert_eq!(counter.ticks(at(40)), 1);
+        // A clock going backwards counts nothing
+        assert_eq!(counter.ticks(at(20)), 0);
+        assert_eq!(counter.ticks(at(1_000)), 96);
+
+        // Each timer goes down by the ticks counted, stopping at 0
+        let timer = AtomicU8::new(10);
+        let mut counter = TickCounter::new(Duration::from_millis(10), start);
+        count_down(&timer, counter.ticks(at(50)));
+        assert_eq!(timer.load(Ordering::Relaxed), 5);
+        count_down(&timer, counter.ticks(at(90)));
+        assert_eq!(timer.load(Ordering::Relaxed), 1);
+        count_down(&timer, counter.ticks(at(10_000)));
+        assert_eq!(timer.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    /// Test a timer counted down by another thread (as the ticker thread does)
+    /// still counts down after that thread panics
+    fn test_count_down_after_panic() {
+        let timer = AtomicU8::new(10);
+        let _ = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    count_down(&timer, 2);
+                    panic!("Stopping the ticker");
+                })
+                .join()
+        });
+        count_down(&timer, 3);
+        assert_eq!(timer.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    /// Test looking up the default costs
+    fn test_default_costs() {
+        let costs = CycleCosts::default();
+        assert_eq!(costs.cost(0x6A05), 1);
+        assert_eq!(costs.cost(0xD015), 40);
+        assert_eq!(costs.cost(0xF233), 34);
+        assert_eq!(costs.cost(0x8AB4), 7);
+        // Unknown opcodes
+        assert_eq!(costs.cost(0xFFFF), UNKNOWN_COST);
+        // Every pattern in the table is one opcode_pattern produces
+        for (pattern, _) in DEFAULT_CYCLE_COSTS {
+            let opcode =
+                u16::from_str_radix(&pattern.replace(['X', 'Y'], "0").replace('N', "1"), 16)
+                    .unwrap();
+            assert_eq!(opcode_pattern(opcode), pattern);
+        }
+    }
+
+    #[test]
+    /// Test overriding the costs
+    fn test_overrides() -> Result<()> {
+        let costs = CycleCosts::with_overrides(&BTreeMap::from([
+            ("dxyn".to_string(), 100),
+            ("6XNN".to_string(), 2),
+        ]))?;
+        assert_eq!(costs.cost(0xD015), 100);
+        assert_eq!(costs.cost(0x6A05), 2);
+        assert_eq!(costs.cost(0x7A05), 2);
+
+        assert!(CycleCosts::with_overrides(&BTreeMap::from([("ZZZZ".to_string(), 1)])).is_err());
+        assert!(CycleCosts::with_overrides(&BTreeMap::from([("DXYN".to_string(), 0)])).is_err());
+        Ok(())
+    }
+}
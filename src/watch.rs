@@ -0,0 +1,175 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::debug;
+
+use crate::rom;
+
+/// Shortest time between checks of the file, so the run loop isn't slowed down
+/// by asking the filesystem every instruction
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a changed file has to stay the same before it is loaded, so a file
+/// still being written isn't loaded half way through
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// What is checked to tell whether the file changed, its modification time and
+/// size
+type FileStamp = (SystemTime, u64);
+
+/// The stamp of the file at `path`, None if it can't be read (e.g. it is being
+/// replaced)
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Watches a ROM file for changes by polling its modification time, for
+/// reloading it while it's being worked on
+#[derive(Debug)]
+pub struct RomWatcher {
+    path: PathBuf,
+    /// The file checked for changes, the archive for a ROM in a zip archive
+    file: PathBuf,
+    debounce: Duration,
+    /// Stamp of the file when it was last loaded
+    loaded: Option<FileStamp>,
+    /// The latest stamp of a changed file, and when it was first seen, while
+    /// waiting for the file to settle
+    pending: Option<(Option<FileStamp>, Instant)>,
+    last_poll: Option<Instant>,
+}
+
+impl RomWatcher {
+    /// Watch the ROM at `path`, as it is now
+    pub fn new(path: impl Into<PathBuf>, debounce: Duration) -> Self {
+        let path = path.into();
+        let (file, _) = rom::split_archive_path(&path);
+        Self {
+            loaded: file_stamp(&file),
+            path,
+            file,
+            debounce,
+            pending: None,
+            last_poll: None,
+        }
+    }
+
+    /// The path being watched
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check the file at time `now`, returning its new contents once it has
+    /// changed and then stayed the same for the debounce time
+    ///
+    /// A changed file which can't be read yet (or is empty) is tried again on
+    /// later polls.
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if self
+            .last_poll
+            .is_some_and(|last_poll| now.saturating_duration_since(last_poll) < POLL_INTERVAL)
+        {
+            return None;
+        }
+        self.last_poll = Some(now);
+        let stamp = file_stamp(&self.file);
+        match self.pending {
+            None if stamp == self.loaded => return None,
+            Some((seen, since)) if seen == stamp => {
+                if now.saturating_duration_since(since) < self.debounce {
+                    return None;
+                }
+            }
+            // Newly changed, or still changing
+            _ => {
+                debug!("{:?} changed, waiting for it to settle", self.path);
+                self.pending = Some((stamp, now));
+                return None;
+            }
+        }
+        // Missing, most likely part way through being replaced
+        stamp?;
+        match rom::open(&self.path) {
+            Ok(rom) if !rom.is_empty() => {
+                self.loaded = stamp;
+                self.pending = None;
+                Some(rom)
+            }
+            Ok(_) => {
+                debug!("{:?} is empty, trying again", self.path);
+                None
+            }
+            Err(err) => {
+                debug!("Failed to read {:?}, trying again: {err:#}", self.path);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_watch {
+    use super::*;
+    use anyhow::Result;
+
+    /// Seconds after `start`
+    fn at(start: Instant, seconds: f64) -> Instant {
+        start + Duration::from_secs_f64(seconds)
+    }
+
+    #[test]
+    /// Test a rewritten ROM is loaded once it stops changing
+    fn test_reload_after_debounce() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("game.ch8");
+        std::fs::write(&path, [0x12, 0x00])?;
+        let start = Instant::now();
+        let mut watcher = RomWatcher::new(&path, Duration::from_millis(300));
+        assert_eq!(watcher.poll(start), None);
+
+        // Half written, then finished before the debounce time is up
+        std::fs::write(&path, [0x60])?;
+        assert_eq!(watcher.poll(at(start, 1.0)), None);
+        assert_eq!(watcher.poll(at(start, 1.2)), None);
+        std::fs::write(&path, [0x60, 0x01, 0x12, 0x02])?;
+        assert_eq!(watcher.poll(at(start, 1.4)), None);
+        assert_eq!(watcher.poll(at(start, 1.65)), None);
+        // Long enough after the change, but too soon after the last check
+        assert_eq!(watcher.poll(at(start, 1.72)), None);
+        assert_eq!(
+            watcher.poll(at(start, 1.8)),
+            Some(vec![0x60, 0x01, 0x12, 0x02])
+        );
+        // Only loaded once
+        assert_eq!(watcher.poll(at(start, 2.0)), None);
+        assert_eq!(watcher.poll(at(start, 3.0)), None);
+        Ok(())
+    }
+
+    #[test]
+    /// Test a ROM which can't be loaded yet is retried until it can
+    fn test_retry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("game.ch8");
+        std::fs::write(&path, [0x12, 0x00])?;
+        let start = Instant::now();
+        let mut watcher = RomWatcher::new(&path, Duration::from_millis(300));
+
+        // Removed while being replaced
+        std::fs::remove_file(&path)?;
+        assert_eq!(watcher.poll(at(start, 1.0)), None);
+        assert_eq!(watcher.poll(at(start, 2.0)), None);
+        // Truncated, but not written yet
+        std::fs::write(&path, [])?;
+        assert_eq!(watcher.poll(at(start, 3.0)), None);
+        assert_eq!(watcher.poll(at(start, 4.0)), None);
+        std::fs::write(&path, [0x00, 0xE0, 0x12, 0x02])?;
+        assert_eq!(watcher.poll(at(start, 5.0)), None);
+        assert_eq!(
+            watcher.poll(at(start, 6.0)),
+            Some(vec![0x00, 0xE0, 0x12, 0x02])
+        );
+        Ok(())
+    }
+}
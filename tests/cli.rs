@@ -0,0 +1,687 @@
+//! Command line parsing, in particular that a bare program path still runs it
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::error::ErrorKind;
+
+use emul8rs::cli::{
+    Cli, Command, ConfigCommand, ConfigFormat, FrontendKind, LibraryCommand, RunArgs,
+};
+use emul8rs::config::EmulatorConfig;
+
+/// Parse the arguments, expecting the run subcommand
+fn parse_run(args: &[&str]) -> (Cli, RunArgs) {
+    let mut cli = Cli::parse_args(args).unwrap_or_else(|err| panic!("{args:?}: {err}"));
+    let command = std::mem::replace(&mut cli.command, Command::Config(ConfigCommand::Path));
+    match command {
+        Command::Run(run) => (cli, run),
+        other => panic!("{args:?} parsed as {other:?}"),
+    }
+}
+
+#[test]
+/// A bare program path defaults to running it
+fn test_default_to_run() {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8"]);
+    assert_eq!(run.program, Some(PathBuf::from("game.ch8")));
+
+    let (_, explicit) = parse_run(&["emul8rs", "run", "game.ch8"]);
+    assert_eq!(explicit.program, run.program);
+}
+
+#[test]
+/// Run flags can come before or after the program, with or without `run`
+fn test_run_flags() {
+    for args in [
+        vec!["emul8rs", "--no-audio", "-f", "red", "game.ch8"],
+        vec!["emul8rs", "game.ch8", "--no-audio", "-f", "red"],
+        vec!["emul8rs", "run", "--no-audio", "game.ch8", "-f", "red"],
+    ] {
+        let (_, run) = parse_run(&args);
+        assert_eq!(run.program, Some(PathBuf::from("game.ch8")), "{args:?}");
+        assert!(run.no_audio, "{args:?}");
+        assert_eq!(run.foreground.as_deref(), Some("red"), "{args:?}");
+    }
+}
+
+#[test]
+/// Shared flags are accepted before or after the subcommand
+fn test_global_flags() {
+    for args in [
+        vec!["emul8rs", "-c", "other.toml", "-ll", "game.ch8"],
+        vec!["emul8rs", "game.ch8", "-ll", "--config", "other.toml"],
+        vec!["emul8rs", "-ll", "run", "game.ch8", "-c", "other.toml"],
+    ] {
+        let (cli, _) = parse_run(&args);
+        assert_eq!(cli.config, Some(PathBuf::from("other.toml")), "{args:?}");
+        assert_eq!(cli.logging, 2, "{args:?}");
+    }
+
+    let cli = Cli::parse_args(["emul8rs", "config", "path", "-c", "other.toml"]).unwrap();
+    assert_eq!(cli.config, Some(PathBuf::from("other.toml")));
+}
+
+#[test]
+/// Subcommands other than run are still recognized
+fn test_config_subcommand() {
+    let cli = Cli::parse_args(["emul8rs", "config", "path"]).unwrap();
+    assert!(matches!(cli.command, Command::Config(ConfigCommand::Path)));
+}
+
+#[test]
+/// Listing the keymaps doesn't need a program
+fn test_list_keymaps() {
+    let (_, run) = parse_run(&["emul8rs", "--list-keymaps"]);
+    assert!(run.list_keymaps);
+    assert_eq!(run.program, None);
+}
+
+#[test]
+/// Listing the keys doesn't need a program, but can take one for its settings
+fn test_list_keys() {
+    let (_, run) = parse_run(&["emul8rs", "--list-keys", "--keymap-preset", "azerty"]);
+    assert!(run.list_keys);
+    assert_eq!(run.program, None);
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--list-keys"]);
+    assert_eq!(run.program, Some(PathBuf::from("game.ch8")));
+}
+
+#[test]
+/// Bad arguments are still reported as errors
+fn test_errors() {
+    // Nothing at all shows the help
+    let err = Cli::parse_args(["emul8rs"]).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+    );
+    // Help and version aren't swallowed by the fallback to run
+    let err = Cli::parse_args(["emul8rs", "--help"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DisplayHelp);
+    let err = Cli::parse_args(["emul8rs", "--version"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DisplayVersion);
+    // Unknown flags
+    assert!(Cli::parse_args(["emul8rs", "game.ch8", "--not-a-flag"]).is_err());
+    // A program is needed to run
+    assert!(Cli::parse_args(["emul8rs", "--no-audio"]).is_err());
+    // Errors in a named subcommand aren't retried as run
+    let err = Cli::parse_args(["emul8rs", "config", "nope"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidSubcommand);
+    // Scale must be at least 1
+    assert!(Cli::parse_args(["emul8rs", "game.ch8", "--scale", "0"]).is_err());
+}
+
+#[test]
+/// Command line values override the config
+fn test_apply() {
+    let (_, run) = parse_run(&[
+        "emul8rs",
+        "game.ch8",
+        "--instructions-per-second",
+        "1000",
+        "--shift-use-vy",
+        "false",
+        "--scale",
+        "4",
+        "--keymap-preset",
+        "dvorak",
+    ]);
+    let mut config = EmulatorConfig::default();
+    run.apply(&mut config).unwrap();
+    assert_eq!(config.instructions_per_second, 1000);
+    assert!(!config.shift_use_vy);
+    assert_eq!(config.window_scale, 4);
+    assert_eq!(config.keymap_preset, "dvorak");
+    // Values not given are left alone
+    assert_eq!(config.foreground, EmulatorConfig::default().foreground);
+    assert!(config.audio_enabled);
+}
+
+#[test]
+/// The command line settings are given as config settings
+fn test_overrides() {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--no-audio", "-b", "navy"]);
+    let overrides = run.overrides().unwrap();
+    assert_eq!(overrides.len(), 2);
+    assert_eq!(overrides["audio_enabled"], toml::Value::Boolean(false));
+    assert_eq!(overrides["background"].as_str(), Some("navy"));
+
+    let (_, run) = parse_run(&["emul8rs", "game.ch8"]);
+    assert!(run.overrides().unwrap().is_empty());
+
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--muted"]);
+    assert_eq!(
+        run.overrides().unwrap()["audio_muted"],
+        toml::Value::Boolean(true)
+    );
+}
+
+#[test]
+/// Showing the config, and dumping it when running
+fn test_config_show() {
+    let cli = Cli::parse_args(["emul8rs", "config", "show"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Config(ConfigCommand::Show {
+            rom: None,
+            format: ConfigFormat::Toml
+        })
+    ));
+    let cli = Cli::parse_args([
+        "emul8rs", "config", "show", "--rom", "a.ch8", "--format", "json",
+    ])
+    .unwrap();
+    match cli.command {
+        Command::Config(ConfigCommand::Show { rom, format }) => {
+            assert_eq!(rom, Some(PathBuf::from("a.ch8")));
+            assert_eq!(format, ConfigFormat::Json);
+        }
+        other => panic!("parsed as {other:?}"),
+    }
+    assert!(Cli::parse_args(["emul8rs", "config", "show", "--format", "yaml"]).is_err());
+
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--dump-config"]);
+    assert!(run.dump_config);
+}
+
+#[test]
+/// Creating and editing the config file
+fn test_config_init_edit() {
+    let cli = Cli::parse_args(["emul8rs", "config", "init"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Config(ConfigCommand::Init {
+            path: None,
+            force: false
+        })
+    ));
+    let cli =
+        Cli::parse_args(["emul8rs", "config", "init", "--path", "new.toml", "--force"]).unwrap();
+    match cli.command {
+        Command::Config(command) => assert_eq!(
+            command,
+            ConfigCommand::Init {
+                path: Some(PathBuf::from("new.toml")),
+                force: true
+            }
+        ),
+        other => panic!("parsed as {other:?}"),
+    }
+
+    let cli = Cli::parse_args(["emul8rs", "config", "edit"]).unwrap();
+    assert!(matches!(cli.command, Command::Config(ConfigCommand::Edit)));
+}
+
+#[test]
+/// Getting information about a ROM
+fn test_info() {
+    let cli = Cli::parse_args(["emul8rs", "info", "game.ch8"]).unwrap();
+    match cli.command {
+        Command::Info { program } => assert_eq!(program, PathBuf::from("game.ch8")),
+        other => panic!("parsed as {other:?}"),
+    }
+    assert!(Cli::parse_args(["emul8rs", "info"]).is_err());
+}
+
+#[test]
+/// The disasm subcommand, with and without the listing format
+fn test_disasm() {
+    let cli = Cli::parse_args(["emul8rs", "disasm", "game.ch8"]).unwrap();
+    match cli.command {
+        Command::Disasm {
+            program,
+            listing,
+            trace_first,
+            trace_cycles,
+        } => {
+            assert_eq!(program, PathBuf::from("game.ch8"));
+            assert!(!listing && !trace_first);
+            assert_eq!(trace_cycles, 100_000);
+        }
+        other => panic!("parsed as {other:?}"),
+    }
+    let cli = Cli::parse_args(["emul8rs", "disasm", "--listing", "game.ch8"]).unwrap();
+    assert!(matches!(cli.command, Command::Disasm { listing: true, .. }));
+    let cli = Cli::parse_args([
+        "emul8rs",
+        "disasm",
+        "--trace-first",
+        "--trace-cycles",
+        "500",
+        "game.ch8",
+    ])
+    .unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Disasm {
+            trace_first: true,
+            trace_cycles: 500,
+            ..
+        }
+    ));
+}
+
+#[test]
+/// The compare subcommand needs both profiles
+fn test_compare() {
+    let cli = Cli::parse_args([
+        "emul8rs",
+        "compare",
+        "game.ch8",
+        "--a-profile",
+        "cosmac",
+        "--b-profile",
+        "schip",
+    ])
+    .unwrap();
+    match cli.command {
+        Command::Compare(args) => {
+            assert_eq!(args.program, PathBuf::from("game.ch8"));
+            assert_eq!(
+                (args.a_profile.as_str(), args.b_profile.as_str()),
+                ("cosmac", "schip")
+            );
+            assert_eq!(args.input, None);
+            assert_eq!((args.seed, args.max_cycles), (0, 1_000_000));
+        }
+        other => panic!("parsed as {other:?}"),
+    }
+    let cli = Cli::parse_args([
+        "emul8rs",
+        "compare",
+        "game.ch8",
+        "--a-profile",
+        "cosmac",
+        "--b-profile",
+        "schip",
+        "--input",
+        "run.rec",
+    ])
+    .unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Compare(args) if args.input == Some(PathBuf::from("run.rec"))
+    ));
+    let err =
+        Cli::parse_args(["emul8rs", "compare", "game.ch8", "--a-profile", "cosmac"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+}
+
+#[test]
+/// The asm subcommand, with and without an output path
+fn test_asm() {
+    let cli = Cli::parse_args(["emul8rs", "asm", "game.8o"]).unwrap();
+    match cli.command {
+        Command::Asm { source, output } => {
+            assert_eq!(source, PathBuf::from("game.8o"));
+            assert_eq!(output, None);
+        }
+        other => panic!("parsed as {other:?}"),
+    }
+    let cli = Cli::parse_args(["emul8rs", "asm", "game.8o", "-o", "out.ch8"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Asm { output: Some(ref output), .. } if output == &PathBuf::from("out.ch8")
+    ));
+}
+
+#[test]
+/// Logging to a file
+fn test_log_file() {
+    let (cli, _) = parse_run(&[
+        "emul8rs",
+        "game.ch8",
+        "--log-file",
+        "out.log",
+        "--log-append",
+    ]);
+    assert_eq!(cli.log_file, Some(PathBuf::from("out.log")));
+    assert!(cli.log_append);
+    assert!(!cli.log_errors_to_stderr);
+
+    let cli = Cli::parse_args(["emul8rs", "--log-file", "out.log", "config", "path"]).unwrap();
+    assert_eq!(cli.log_file, Some(PathBuf::from("out.log")));
+
+    // The file options need a file
+    assert!(Cli::parse_args(["emul8rs", "game.ch8", "--log-append"]).is_err());
+    assert!(Cli::parse_args(["emul8rs", "game.ch8", "--log-errors-to-stderr"]).is_err());
+}
+
+#[test]
+/// Headless runs with limits
+fn test_run_limits() -> anyhow::Result<()> {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8"]);
+    assert_eq!(run.frontend, FrontendKind::Raylib);
+    assert!(!run.limits()?.is_limited());
+
+    let (_, run) = parse_run(&[
+        "emul8rs",
+        "game.ch8",
+        "--frontend",
+        "none",
+        "--max-cycles",
+        "100000",
+        "--max-seconds",
+        "2.5",
+    ]);
+    assert_eq!(run.frontend, FrontendKind::None);
+    let limits = run.limits()?;
+    assert_eq!(limits.max_cycles, Some(100_000));
+    assert_eq!(limits.max_duration, Some(Duration::from_millis(2500)));
+
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--max-seconds", "nan"]);
+    assert!(run.limits().is_err());
+    assert!(Cli::parse_args(["emul8rs", "game.ch8", "--frontend", "sdl"]).is_err());
+
+    let (_, run) = parse_run(&[
+        "emul8rs",
+        "game.ch8",
+        "--frontend",
+        "terminal",
+        "--terminal-color",
+        "monochrome",
+    ]);
+    assert_eq!(run.frontend, FrontendKind::Terminal);
+    assert_eq!(run.terminal_color.as_deref(), Some("monochrome"));
+    Ok(())
+}
+
+#[test]
+/// Writing run statistics
+fn test_stats_out() {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8"]);
+    assert_eq!(run.stats_out, None);
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--stats-out", "stats.json"]);
+    assert_eq!(run.stats_out, Some(PathBuf::from("stats.json")));
+}
+
+#[test]
+/// Reloading the ROM when it changes
+fn test_watch() {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8"]);
+    assert!(!run.watch);
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--watch"]);
+    assert!(run.watch);
+    let (_, run) = parse_run(&["emul8rs", "run", "--watch", "game.ch8"]);
+    assert!(run.watch);
+}
+
+#[test]
+/// Autosaving, and resuming from the latest autosave
+fn test_autosave() -> anyhow::Result<()> {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8"]);
+    assert!(!run.resume);
+    assert_eq!(run.autosave_interval_seconds, None);
+    let (_, run) = parse_run(&[
+        "emul8rs",
+        "game.ch8",
+        "--resume",
+        "--autosave-interval-seconds",
+        "30",
+    ]);
+    assert!(run.resume);
+    let mut config = EmulatorConfig::default();
+    run.apply(&mut config)?;
+    assert_eq!(config.autosave_interval(), Some(Duration::from_secs(30)));
+    Ok(())
+}
+
+#[test]
+/// The control socket is off unless a path is given
+fn test_control_socket() {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8"]);
+    assert_eq!(run.control_socket, None);
+    let (_, run) = parse_run(&[
+        "emul8rs",
+        "game.ch8",
+        "--control-socket",
+        "/tmp/emul8rs.sock",
+    ]);
+    assert_eq!(run.control_socket.as_deref(), Some("/tmp/emul8rs.sock"));
+}
+
+#[test]
+/// The profile to show side by side is only set by --compare-profile
+fn test_compare_profile() {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8"]);
+    assert_eq!(run.compare_profile, None);
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--compare-profile", "schip"]);
+    assert_eq!(run.compare_profile.as_deref(), Some("schip"));
+}
+
+#[test]
+/// A URL is taken as the program, with the cache on unless --no-cache is given
+fn test_no_cache() {
+    let (_, run) = parse_run(&["emul8rs", "https://example.com/pong.ch8"]);
+    assert_eq!(
+        run.program,
+        Some(PathBuf::from("https://example.com/pong.ch8"))
+    );
+    assert!(!run.no_cache);
+    let (_, run) = parse_run(&["emul8rs", "https://example.com/pong.ch8", "--no-cache"]);
+    assert!(run.no_cache);
+}
+
+#[test]
+/// Looking up the profile in the ROM database is opt in
+fn test_auto_profile() {
+    let (_, run) = parse_run(&["emul8rs", "pong.ch8"]);
+    assert!(!run.auto_profile);
+    let (_, run) = parse_run(&["emul8rs", "run", "--auto-profile", "pong.ch8"]);
+    assert!(run.auto_profile);
+}
+
+#[test]
+/// The compatibility report is opt in
+fn test_compat_report() {
+    let (_, run) = parse_run(&["emul8rs", "pong.ch8"]);
+    assert!(!run.compat_report);
+    let (_, run) = parse_run(&["emul8rs", "run", "pong.ch8", "--compat-report"]);
+    assert!(run.compat_report);
+}
+
+#[test]
+/// The warning about quirk dependent instructions can be silenced
+fn test_quiet_compat() {
+    let (_, run) = parse_run(&["emul8rs", "pong.ch8"]);
+    assert!(!run.quiet_compat);
+    let (_, run) = parse_run(&["emul8rs", "pong.ch8", "--quiet-compat"]);
+    assert!(run.quiet_compat);
+}
+
+#[test]
+/// Files loaded at addresses, with or instead of the program
+fn test_load() {
+    let (_, run) = parse_run(&[
+        "emul8rs",
+        "--load",
+        "0x200:main.ch8",
+        "--load",
+        "1536:data.bin",
+        "--start-address",
+        "0x600",
+    ]);
+    assert_eq!(run.program, None);
+    let loads: Vec<_> = run
+        .load
+        .iter()
+        .map(|spec| (spec.address, spec.path.clone()))
+        .collect();
+    assert_eq!(
+        loads,
+        [
+            (0x200, PathBuf::from("main.ch8")),
+            (0x600, PathBuf::from("data.bin"))
+        ]
+    );
+    assert_eq!(run.start_address, Some(0x600));
+
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--load", "0x600:data.bin"]);
+    assert_eq!(run.program, Some(PathBuf::from("game.ch8")));
+    assert_eq!(run.load.len(), 1);
+    assert_eq!(run.start_address, None);
+
+    for args in [
+        &["emul8rs", "game.ch8", "--load", "data.bin"][..],
+        &["emul8rs", "game.ch8", "--load", "0x1000:data.bin"],
+        &["emul8rs", "game.ch8", "--start-address", "start"],
+    ] {
+        let err = Cli::parse_args(args).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation, "{args:?}");
+    }
+}
+
+#[test]
+/// Pokes are kept in the order given, and malformed ones are rejected
+fn test_poke() {
+    let (_, run) = parse_run(&[
+        "emul8rs",
+        "game.ch8",
+        "--poke",
+        "mem:0x3F0=0x1A",
+        "--poke",
+        "reg:V3=7",
+    ]);
+    let pokes: Vec<_> = run.poke.iter().map(ToString::to_string).collect();
+    assert_eq!(pokes, ["mem:0x3F0=0x1A", "reg:V3=7"]);
+
+    let err = Cli::parse_args(["emul8rs", "game.ch8", "--poke", "reg:V3=0x100"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    assert!(err.to_string().contains("reg:V3=0x100"), "{err}");
+}
+
+#[test]
+/// The sprite wrap flags, with the combined flag overridden by the separate ones
+fn test_sprite_wrap() {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--sprite-wrap", "true"]);
+    let mut config = EmulatorConfig::default();
+    run.apply(&mut config).unwrap();
+    assert!(config.wrap_x && config.wrap_y);
+
+    let (_, run) = parse_run(&[
+        "emul8rs",
+        "game.ch8",
+        "--wrap-y",
+        "false",
+        "--sprite-wrap",
+        "true",
+    ]);
+    let mut config = EmulatorConfig::default();
+    run.apply(&mut config).unwrap();
+    assert!(config.wrap_x && !config.wrap_y);
+}
+
+#[test]
+/// The stack depth flag
+fn test_stack_depth() {
+    let (_, run) = parse_run(&["emul8rs", "game.ch8", "--stack-depth", "12"]);
+    let mut config = EmulatorConfig::default();
+    run.apply(&mut config).unwrap();
+    assert_eq!(config.stack_depth, 12);
+}
+
+#[test]
+/// Listing the ROM library and the recently played games
+fn test_library() {
+    let cli = Cli::parse_args(["emul8rs", "library", "list"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Library(LibraryCommand::List {
+            hashes: false,
+            titles: false
+        })
+    ));
+    let cli = Cli::parse_args(["emul8rs", "library", "list", "--hashes", "--titles"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Library(LibraryCommand::List {
+            hashes: true,
+            titles: true
+        })
+    ));
+
+    let cli = Cli::parse_args(["emul8rs", "library", "recent"]).unwrap();
+    match cli.command {
+        Command::Library(command) => assert_eq!(
+            command,
+            LibraryCommand::Recent {
+                count: 5,
+                paths: false
+            }
+        ),
+        other => panic!("parsed as {other:?}"),
+    }
+    let cli = Cli::parse_args(["emul8rs", "library", "recent", "-n", "2", "--paths"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Library(LibraryCommand::Recent {
+            count: 2,
+            paths: true
+        })
+    ));
+    let err = Cli::parse_args(["emul8rs", "library", "nope"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidSubcommand);
+}
+
+#[test]
+/// Attract mode, with the time each ROM is shown for
+fn test_play() {
+    let cli = Cli::parse_args(["emul8rs", "play", "--playlist", "roms/"]).unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("parsed as {:?}", cli.command);
+    };
+    assert_eq!(args.playlist, PathBuf::from("roms/"));
+    assert_eq!(args.each().unwrap(), Duration::from_secs(60));
+    assert_eq!(args.frontend, FrontendKind::Raylib);
+
+    let cli = Cli::parse_args([
+        "emul8rs",
+        "play",
+        "--playlist",
+        "cabinet.txt",
+        "--each",
+        "2.5",
+        "--frontend",
+        "none",
+    ])
+    .unwrap();
+    let Command::Play(args) = cli.command else {
+        panic!("parsed as {:?}", cli.command);
+    };
+    assert_eq!(args.each().unwrap(), Duration::from_millis(2500));
+    assert_eq!(args.frontend, FrontendKind::None);
+
+    for each in ["--each=0", "--each=-1"] {
+        let cli = Cli::parse_args(["emul8rs", "play", "--playlist", "roms", each]);
+        let Command::Play(args) = cli.unwrap().command else {
+            panic!("not parsed as play");
+        };
+        assert!(args.each().is_err(), "{each}");
+    }
+    // The playlist is required
+    assert!(Cli::parse_args(["emul8rs", "play"]).is_err());
+}
+
+#[test]
+/// The test subcommand, which needs at least one ROM or directory
+fn test_test() {
+    let cli = Cli::parse_args(["emul8rs", "test", "suite/", "extra.ch8"]).unwrap();
+    match cli.command {
+        Command::Test(args) => {
+            assert_eq!(
+                args.roms,
+                [PathBuf::from("suite/"), PathBuf::from("extra.ch8")]
+            );
+            assert_eq!((args.frames, args.update), (300, false));
+        }
+        other => panic!("parsed as {other:?}"),
+    }
+    let cli = Cli::parse_args(["emul8rs", "test", "suite/", "--frames", "60", "--update"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Test(args) if args.frames == 60 && args.update
+    ));
+    let err = Cli::parse_args(["emul8rs", "test"]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+}
@@ -0,0 +1,156 @@
+//! Run arbitrary bytes as ROMs headless, checking the emulator never
+//! panics.
+//!
+//! Errors (unknown opcodes, running off the end of memory, stack overflows)
+//! are expected, and the ROM keeps running after them, a panic is a bug. The
+//! ROMs come from a seeded generator, so every run checks the same inputs and
+//! a failure names the ROM that caused it. Set `FUZZ_ROMS` to check more (or
+//! fewer) ROMs than the default.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use emul8rs::config::EmulatorConfig;
+use emul8rs::display::Display;
+use emul8rs::emulator::Emulator;
+use emul8rs::frontend::Frontend;
+use emul8rs::random_program::random_program;
+use emul8rs::rom::{MAX_CHIP8_ROM_SIZE, PROGRAM_START};
+
+/// ROMs checked when `FUZZ_ROMS` isn't set
+const DEFAULT_ROMS: u64 = 200;
+/// Instructions run from each ROM, unless it fails first
+const STEPS_PER_ROM: usize = 4_000;
+
+/// A frontend pressing keys from a fixed pattern, changing each time the
+/// emulator checks one, so key waits and skips take both branches
+struct ScriptedFrontend {
+    script: u64,
+    checks: u32,
+}
+
+impl Frontend for ScriptedFrontend {
+    fn draw(&mut self, _display: &Display) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_key(&mut self, key: u8) -> Result<bool> {
+        self.checks = self.checks.wrapping_add(1);
+        let bit = (u32::from(key) + self.checks) % u64::BITS;
+        Ok(self.script & (1 << bit) != 0)
+    }
+
+    fn play_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop_sound(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn should_stop(&mut self) -> bool {
+        false
+    }
+
+    fn step(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A ROM from `seed`, of random bytes or random instructions, sometimes too big
+/// to load
+fn random_rom(seed: u64) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let length = match rng.random_range(0..10) {
+        0 => rng.random_range(0..8),
+        1 => rng.random_range(MAX_CHIP8_ROM_SIZE - 4..MAX_CHIP8_ROM_SIZE + 4),
+        _ => rng.random_range(0..512),
+    };
+    if rng.random() {
+        (0..length).map(|_| rng.random()).collect()
+    } else {
+        // Valid instructions, so the ROM gets past the first few bytes more
+        // often than random bytes do
+        random_program(rng.random(), length.div_ceil(2))
+    }
+}
+
+/// Settings from `seed`, covering each quirk both ways
+fn random_config(seed: u64) -> EmulatorConfig {
+    let mut rng = StdRng::seed_from_u64(seed);
+    EmulatorConfig {
+        synchronous_timers: true,
+        poll_keys_per_frame: rng.random(),
+        shift_use_vy: rng.random(),
+        jump_offset_use_v0: rng.random(),
+        store_memory_update_index: rng.random(),
+        wrap_x: rng.random(),
+        wrap_y: rng.random(),
+        double_buffer: rng.random(),
+        stack_depth: rng.random_range(1..=32),
+        ..EmulatorConfig::default()
+    }
+}
+
+/// Run the ROM from `seed` until it halts or runs [STEPS_PER_ROM] instructions,
+/// returning the number of instructions which failed
+///
+/// A failed instruction is skipped, starting the program again if it ran off
+/// the end of memory.
+fn run_rom(seed: u64) -> Result<usize> {
+    let frontend = ScriptedFrontend {
+        script: StdRng::seed_from_u64(seed).random(),
+        checks: 0,
+    };
+    let mut emulator = Emulator::new(Box::new(frontend), random_config(seed))?;
+    let mut errors = 0;
+    if emulator.load_rom(&random_rom(seed)).is_err() {
+        // Too big, run whatever is in memory instead
+        errors += 1;
+    }
+    for step in 0..STEPS_PER_ROM {
+        if emulator.is_halted() {
+            break;
+        }
+        // Whole frames as well as single steps, for the per frame work (timers,
+        // polled keys, presenting a double buffered display)
+        let result = if step % 64 == 0 {
+            emulator.run_frame()
+        } else {
+            emulator.step()
+        };
+        if result.is_err() {
+            errors += 1;
+            if emulator.program_counter() + 1 >= emulator.memory().len() {
+                emulator.set_program_counter(PROGRAM_START)?;
+            }
+        }
+    }
+    Ok(errors)
+}
+
+#[test]
+/// Random ROMs either run or fail with an error, never panicking
+fn test_random_roms_never_panic() {
+    let roms = std::env::var("FUZZ_ROMS")
+        .ok()
+        .and_then(|roms| roms.parse().ok())
+        .unwrap_or(DEFAULT_ROMS);
+    let mut failed = Vec::new();
+    for seed in 0..roms {
+        match panic::catch_unwind(AssertUnwindSafe(|| run_rom(seed))) {
+            Ok(result) => {
+                result.unwrap_or_else(|err| panic!("Setting up the ROM from seed {seed}: {err:#}"));
+            }
+            Err(_) => failed.push(seed),
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "The ROMs from seeds {failed:?} panicked, the first was {:02X?}",
+        random_rom(failed.first().copied().unwrap_or_default())
+    );
+}
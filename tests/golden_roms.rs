@@ -0,0 +1,56 @@
+//! Run ROMs from `tests/roms` headless, and compare the display against the
+//! checked in golden images (the display rendered as ASCII).
+//!
+//! Set `UPDATE_GOLDEN=1` to rewrite the golden images from the current output.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use emul8rs::config::EmulatorConfig;
+use emul8rs::emulator::Emulator;
+use emul8rs::headless_frontend::HeadlessFrontend;
+
+/// Path to a file in the `tests/roms` directory
+fn rom_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("roms")
+        .join(name)
+}
+
+/// Compare the rendered display against the golden image `golden`
+fn check_golden(golden: &str, rendered: &str) -> Result<()> {
+    let path = rom_path(golden);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, rendered).context("Writing golden image")?;
+        return Ok(());
+    }
+    let expected =
+        std::fs::read_to_string(&path).with_context(|| format!("Reading golden image {path:?}"))?;
+    assert_eq!(
+        rendered, expected,
+        "Display doesn't match golden image {golden}, rendered:\n{rendered}"
+    );
+    Ok(())
+}
+
+#[test]
+/// The IBM logo exercises clear, set index, set register, add, and draw
+fn test_ibm_logo() -> Result<()> {
+    // Pin the configuration rather than relying on the defaults, so changes to
+    // the defaults don't change the output. The speed is set so that the whole
+    // ROM (21 instructions) runs within a single frame.
+    let config = EmulatorConfig {
+        instructions_per_second: 1_800,
+        shift_use_vy: true,
+        jump_offset_use_v0: true,
+        store_memory_update_index: false,
+        ..Default::default()
+    };
+    let mut emulator = Emulator::new(Box::new(HeadlessFrontend {}), config)?;
+    emulator.load_file(rom_path("ibm_logo.ch8"))?;
+    emulator.run_frame()?;
+
+    check_golden("ibm_logo.txt", &emulator.display().to_ascii())
+}
@@ -0,0 +1,213 @@
+//! WebAssembly bindings for the emulator core, for building your own UI in
+//! JavaScript
+//!
+//! Built with wasm-pack (`wasm-pack build --target web`, or `--target nodejs`).
+//! Nothing runs on its own: the host calls `stepFrame` 60 times a second,
+//! draws `getFrame` (or `getFrameRgba`), and beeps while `soundActive`. The
+//! frame is `width` by `height` pixels, 64x32 or 128x64 in high resolution.
+//!
+//! ```js
+//! import init, { Emulator } from "./pkg/emul8rs_wasm.js";
+//! await init();
+//! const emulator = new Emulator(new Uint8Array(rom), { wrap_x: true });
+//! emulator.setKey(5, true);
+//! emulator.stepFrame();
+//! const { width, height } = emulator;
+//! context.putImageData(new ImageData(emulator.getFrameRgba(), width, height), 0, 0);
+//! ```
+
+use anyhow::{Context, Result, bail};
+use wasm_bindgen::prelude::*;
+
+use emul8rs::config::EmulatorConfig;
+use emul8rs::display::DisplayTransform;
+use emul8rs::emulator::{Emulator, Quirk};
+use emul8rs::host_frontend::{HostFrontend, HostKeys};
+use emul8rs::save_state::SaveState;
+
+/// Convert an error from the emulator into a JavaScript `Error`
+fn js_error(err: anyhow::Error) -> JsError {
+    JsError::new(&format!("{err:#}"))
+}
+
+/// The settings in `config` (a plain object named as in the config file) on
+/// top of the defaults, with the timers counted down by `stepFrame`
+fn parse_config(config: &JsValue) -> Result<EmulatorConfig> {
+    let mut table = if config.is_undefined() || config.is_null() {
+        toml::Table::new()
+    } else {
+        let json = js_sys::JSON::stringify(config)
+            .ok()
+            .and_then(|json| json.as_string())
+            .context("The config isn't a plain object")?;
+        serde_json::from_str(&json).context("Reading the config")?
+    };
+    // There is no background thread to count them down on the web
+    table.insert("synchronous_timers".to_string(), toml::Value::Boolean(true));
+    let config = EmulatorConfig::from_table(&table)?;
+    if let Err(errors) = config.validate() {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        bail!("Invalid configuration: {}", errors.join(", "));
+    }
+    Ok(config)
+}
+
+/// The emulator, driven from JavaScript
+#[wasm_bindgen(js_name = Emulator)]
+pub struct WasmEmulator {
+    emulator: Emulator<'static>,
+    /// Keys down, shared with the frontend
+    keys: HostKeys,
+    /// Color of each palette index for [WasmEmulator::get_frame_rgba]
+    palette: Vec<(u8, u8, u8)>,
+    /// How [WasmEmulator::get_frame_rgba] mirrors or rotates the display
+    transform: DisplayTransform,
+}
+
+#[wasm_bindgen(js_class = Emulator)]
+impl WasmEmulator {
+    /// Create an emulator running `rom`, with the settings in `config` (named
+    /// as in the config file, e.g. `{ wrap_x: true }`) on top of the defaults
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8], config: JsValue) -> Result<WasmEmulator, JsError> {
+        let config = parse_config(&config).map_err(js_error)?;
+        let palette = config.palette_colors().map_err(js_error)?;
+        let transform = DisplayTransform::from_name(&config.display_transform).map_err(js_error)?;
+        let keys = HostKeys::default();
+        let frontend = HostFrontend::new(keys.clone());
+        let mut emulator = Emulator::new(Box::new(frontend), config).map_err(js_error)?;
+        emulator.load_rom(rom).map_err(js_error)?;
+        Ok(Self {
+            emulator,
+            keys,
+            palette,
+            transform,
+        })
+    }
+
+    /// Execute one 60Hz frame's worth of instructions, counting the timers down
+    #[wasm_bindgen(js_name = stepFrame)]
+    pub fn step_frame(&mut self) -> Result<(), JsError> {
+        self.emulator.run_frame().map_err(js_error)
+    }
+
+    /// Press (`down`) or release keypad key `key` (0 to 15)
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: usize, down: bool) -> Result<(), JsError> {
+        self.keys.set(key, down).map_err(js_error)
+    }
+
+    /// The display as one byte per pixel, row by row: its palette index (0 for
+    /// off, 1 for on on a single plane display)
+    ///
+    /// With `double_buffer` on this is the last complete frame.
+    #[wasm_bindgen(js_name = getFrame)]
+    pub fn get_frame(&self) -> Result<Vec<u8>, JsError> {
+        let display = self.emulator.presented_display();
+        let mut frame = Vec::with_capacity(display.rows() * display.cols());
+        for row in 0..display.rows() {
+            for col in 0..display.cols() {
+                frame.push(display.color_index(row, col).map_err(js_error)? as u8);
+            }
+        }
+        Ok(frame)
+    }
+
+    /// The display as RGBA bytes in the configured palette colors, mirrored or
+    /// rotated by `display_transform`, ready for
+    /// `new ImageData(frame, width, height)`
+    #[wasm_bindgen(js_name = getFrameRgba)]
+    pub fn get_frame_rgba(&self) -> Result<Vec<u8>, JsError> {
+        let (rows, cols) = (self.height(), self.width());
+        let indices = self.get_frame()?;
+        let mut frame = vec![0; indices.len() * 4];
+        for (cell, index) in indices.into_iter().enumerate() {
+            // The palette was checked against the number of planes
+            let (red, green, blue) = self
+                .palette
+                .get(usize::from(index))
+                .or(self.palette.last())
+                .copied()
+                .unwrap_or_default();
+            let (row, col) = self.transform.apply(cell / cols, cell % cols, rows, cols);
+            let offset = (row * cols + col) * 4;
+            frame[offset..offset + 4].copy_from_slice(&[red, green, blue, 0xFF]);
+        }
+        Ok(frame)
+    }
+
+    /// Whether the beep should be playing
+    #[wasm_bindgen(js_name = soundActive)]
+    pub fn sound_active(&self) -> bool {
+        self.emulator.sound_timer() > 0
+    }
+
+    /// Width of the frame in pixels, which changes with the resolution
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.emulator.presented_display().cols()
+    }
+
+    /// Height of the frame in pixels, which changes with the resolution
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.emulator.presented_display().rows()
+    }
+
+    /// Whether the program has exited
+    #[wasm_bindgen(getter)]
+    pub fn halted(&self) -> bool {
+        self.emulator.is_halted()
+    }
+
+    /// The state of the running program, to carry on from with `loadState`
+    #[wasm_bindgen(js_name = saveState)]
+    pub fn save_state(&self) -> Result<Vec<u8>, JsError> {
+        let state = self.emulator.save_state_at(js_sys::Date::now() as u64);
+        Ok(state.to_json().map_err(js_error)?.into_bytes())
+    }
+
+    /// Carry on from a state from `saveState` of the same ROM
+    #[wasm_bindgen(js_name = loadState)]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsError> {
+        let state = std::str::from_utf8(state)
+            .context("Save state isn't text")
+            .and_then(SaveState::from_json)
+            .map_err(js_error)?;
+        if state.rom_sha1 != self.emulator.rom_sha1() {
+            return Err(JsError::new("Save state is from a different ROM"));
+        }
+        self.emulator.load_state(&state).map_err(js_error)
+    }
+
+    /// Whether the shifts (8XY6 and 8XYE) shift VY rather than VX
+    #[wasm_bindgen(js_name = setShiftUseVy)]
+    pub fn set_shift_use_vy(&mut self, enabled: bool) {
+        self.emulator.set_quirk(Quirk::ShiftUseVy, enabled);
+    }
+
+    /// Whether BNNN jumps to NNN plus V0, rather than plus VX
+    #[wasm_bindgen(js_name = setJumpOffsetUseV0)]
+    pub fn set_jump_offset_use_v0(&mut self, enabled: bool) {
+        self.emulator.set_quirk(Quirk::JumpOffsetUseV0, enabled);
+    }
+
+    /// Whether FX55 and FX65 leave the index register after the last address
+    #[wasm_bindgen(js_name = setStoreMemoryUpdateIndex)]
+    pub fn set_store_memory_update_index(&mut self, enabled: bool) {
+        self.emulator
+            .set_quirk(Quirk::StoreMemoryUpdateIndex, enabled);
+    }
+
+    /// Whether sprites wrap around the left and right edges
+    #[wasm_bindgen(js_name = setWrapX)]
+    pub fn set_wrap_x(&mut self, enabled: bool) {
+        self.emulator.set_quirk(Quirk::WrapX, enabled);
+    }
+
+    /// Whether sprites wrap around the top and bottom edges
+    #[wasm_bindgen(js_name = setWrapY)]
+    pub fn set_wrap_y(&mut self, enabled: bool) {
+        self.emulator.set_quirk(Quirk::WrapY, enabled);
+    }
+}
@@ -0,0 +1,77 @@
+// Runs a ROM through the bindings built for node (see `npm test`), checking the
+// framebuffer changes, the beep, save states, and quirks
+const assert = require("node:assert/strict");
+const test = require("node:test");
+
+const { Emulator } = require("../pkg-node/emul8rs_wasm.js");
+
+// V0 = 0, I = font 0, draw it at (0, 0), V1 = 60, sound timer = V1, loop
+const ROM = new Uint8Array([
+  0x60, 0x00, 0xf0, 0x29, 0xd0, 0x05, 0x61, 0x3c, 0xf1, 0x18, 0x12, 0x0a,
+]);
+
+test("a ROM runs and draws to the framebuffer", () => {
+  const emulator = new Emulator(ROM, { instructions_per_second: 600 });
+  const before = emulator.getFrame();
+  assert.equal(before.length, 64 * 32);
+  assert.equal(emulator.width, 64);
+  assert.equal(emulator.height, 32);
+  assert.ok(before.every((pixel) => pixel === 0));
+  assert.equal(emulator.soundActive(), false);
+
+  emulator.stepFrame();
+  const frame = emulator.getFrame();
+  // The top row of the 0 in the font is 0xF0
+  assert.deepEqual(Array.from(frame.slice(0, 5)), [1, 1, 1, 1, 0]);
+  assert.equal(emulator.soundActive(), true);
+  assert.equal(emulator.halted, false);
+
+  const rgba = emulator.getFrameRgba();
+  assert.equal(rgba.length, 64 * 32 * 4);
+  assert.notDeepEqual(Array.from(rgba.slice(0, 4)), Array.from(rgba.slice(16, 20)));
+});
+
+test("the RGBA frame is in the palette colors, transformed", () => {
+  const emulator = new Emulator(ROM, {
+    foreground: "FF0000",
+    display_transform: "flip_h",
+  });
+  emulator.stepFrame();
+  const rgba = emulator.getFrameRgba();
+  // The top left of the 0 is drawn at the top right
+  assert.deepEqual(Array.from(rgba.slice(63 * 4, 64 * 4)), [0xff, 0, 0, 0xff]);
+  assert.deepEqual(Array.from(rgba.slice(0, 4)), [0xff, 0xff, 0xff, 0xff]);
+});
+
+test("high resolution doubles the frame each way", () => {
+  const emulator = new Emulator(new Uint8Array([0x00, 0xff, 0x12, 0x02]));
+  emulator.stepFrame();
+  assert.equal(emulator.width, 128);
+  assert.equal(emulator.height, 64);
+  assert.equal(emulator.getFrame().length, 128 * 64);
+  assert.equal(emulator.getFrameRgba().length, 128 * 64 * 4);
+});
+
+test("save states carry on in another emulator", () => {
+  const emulator = new Emulator(ROM);
+  emulator.stepFrame();
+  const state = emulator.saveState();
+
+  const resumed = new Emulator(ROM);
+  resumed.loadState(state);
+  assert.deepEqual(resumed.getFrame(), emulator.getFrame());
+  assert.equal(resumed.soundActive(), true);
+
+  const other = new Emulator(new Uint8Array([0x00, 0xfd]));
+  assert.throws(() => other.loadState(state), /different ROM/);
+});
+
+test("bad arguments throw errors", () => {
+  assert.throws(() => new Emulator(ROM, { stack_depth: 0 }), /stack_depth/);
+  const emulator = new Emulator(ROM);
+  assert.throws(() => emulator.setKey(16, true), /Invalid keypad key/);
+  emulator.setKey(15, true);
+  emulator.setWrapX(true);
+  emulator.setShiftUseVy(false);
+  assert.throws(() => new Emulator(new Uint8Array([0xff, 0xff])).stepFrame(), /FFFF/);
+});